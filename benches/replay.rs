@@ -0,0 +1,23 @@
+//! Replaying the output of a shell, an editor and a progress bar, recorded from
+//! a pty, through a grid, drawing after each read.
+
+use std::{fs, path::Path};
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+use session_manager::replay::replay;
+
+fn replay_captures(c: &mut Criterion) {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/captures");
+    let mut group = c.benchmark_group("replay");
+    for name in ["listing", "editor", "progress"].iter().copied() {
+        let capture = fs::read(dir.join(name).with_extension("cap")).unwrap();
+        group.bench_function(name, |b| {
+            b.iter(|| black_box(replay(&capture, 80, 24, 4096)))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, replay_captures);
+criterion_main!(benches);
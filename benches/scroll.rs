@@ -0,0 +1,34 @@
+//! Scrolling a large grid by a few lines, by all of it, and by far more.
+
+use std::io;
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+use session_manager::{ansi::Processor, grid::Grid};
+
+fn feed(bytes: &[u8]) {
+    let mut grid = Grid::<io::Sink>::new(200, 60);
+    let mut processor = Processor::new();
+    for byte in bytes {
+        processor.advance(&mut grid, *byte, &mut io::sink());
+    }
+    black_box(grid.cell(0, 0));
+}
+
+fn scroll(c: &mut Criterion) {
+    let mut group = c.benchmark_group("scroll");
+    for (name, sequence) in [
+        ("a line", "\x1b[S"),
+        ("the screen", "\x1b[60S"),
+        ("a region", "\x1b[10;50r\x1b[40S"),
+        ("far", "\x1b[65535S"),
+        ("far down", "\x1b[65535T"),
+    ] {
+        let bytes = sequence.repeat(100).into_bytes();
+        group.bench_function(name, |b| b.iter(|| feed(&bytes)));
+    }
+    group.finish();
+}
+
+criterion_group!(benches, scroll);
+criterion_main!(benches);
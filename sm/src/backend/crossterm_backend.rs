@@ -0,0 +1,207 @@
+//! A `TerminalBackend` using crossterm.
+//!
+//! crossterm does not expose the bytes behind an input event, so key presses
+//! are re-encoded as a VT220-style terminal would send them.
+
+use std::{
+    io::{self, Stdout, Write},
+    thread,
+};
+
+use crossterm::{
+    cursor::MoveTo,
+    event::{
+        self, DisableMouseCapture, EnableMouseCapture, KeyCode, KeyEvent, KeyModifiers,
+        MouseEventKind,
+    },
+    queue,
+    terminal::{self, Clear, ClearType},
+};
+use futures::channel::mpsc::{self, Receiver};
+use nix::pty::Winsize;
+
+use super::{
+    Event, Key, Modifiers, MouseButton, MouseEvent, MouseKind, TerminalBackend,
+};
+
+/// A terminal accessed through crossterm.
+#[derive(Default)]
+pub struct CrosstermBackend;
+
+impl CrosstermBackend {
+    /// Create a backend for the terminal on standard input and output.
+    pub fn new() -> CrosstermBackend {
+        CrosstermBackend
+    }
+}
+
+/// Standard output, in raw mode until dropped.
+pub struct RawStdout(Stdout);
+
+impl Write for RawStdout {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+impl Drop for RawStdout {
+    fn drop(&mut self) {
+        terminal::disable_raw_mode().ok();
+    }
+}
+
+impl TerminalBackend for CrosstermBackend {
+    type Output = RawStdout;
+
+    fn raw_output(&mut self) -> io::Result<Self::Output> {
+        terminal::enable_raw_mode().map_err(io::Error::other)?;
+        Ok(RawStdout(io::stdout()))
+    }
+
+    fn input(&mut self) -> io::Result<Receiver<(Event, Vec<u8>)>> {
+        let (mut send, recv) = mpsc::channel(0x1000);
+        thread::spawn(move || {
+            while let Ok(e) = event::read() {
+                let converted = match e {
+                    event::Event::Key(key) => convert_key(key),
+                    event::Event::Mouse(mouse) => (convert_mouse(mouse), Vec::new()),
+                    _ => (Event::Unsupported, Vec::new()),
+                };
+                send.try_send(converted).unwrap();
+            }
+            send.disconnect();
+        });
+        Ok(recv)
+    }
+
+    fn size(&self) -> io::Result<Winsize> {
+        let (cols, rows) = terminal::size().map_err(io::Error::other)?;
+        Ok(Winsize {
+            ws_row: rows,
+            ws_col: cols,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        })
+    }
+
+    fn clear<T: Write>(&self, output: &mut T) -> io::Result<()> {
+        queue!(output, Clear(ClearType::All), MoveTo(0, 0)).map_err(io::Error::other)
+    }
+
+    fn set_mouse_capture<T: Write>(
+        &self,
+        output: &mut T,
+        capture: bool,
+    ) -> io::Result<()> {
+        if capture {
+            queue!(output, EnableMouseCapture).map_err(io::Error::other)
+        } else {
+            queue!(output, DisableMouseCapture).map_err(io::Error::other)
+        }
+    }
+}
+
+/// Convert a mouse event, dropping movement with no button held.
+fn convert_mouse(mouse: event::MouseEvent) -> Event {
+    let (kind, button) = match mouse.kind {
+        MouseEventKind::Down(button) => (MouseKind::Press, convert_button(button)),
+        MouseEventKind::Drag(button) => (MouseKind::Drag, convert_button(button)),
+        MouseEventKind::Up(button) => (MouseKind::Release, convert_button(button)),
+        MouseEventKind::ScrollUp => (MouseKind::Press, MouseButton::WheelUp),
+        MouseEventKind::ScrollDown => (MouseKind::Press, MouseButton::WheelDown),
+        _ => return Event::Unsupported,
+    };
+    Event::Mouse(MouseEvent {
+        kind,
+        button,
+        modifiers: Modifiers {
+            shift: mouse.modifiers.contains(KeyModifiers::SHIFT),
+            alt: mouse.modifiers.contains(KeyModifiers::ALT),
+            ctrl: mouse.modifiers.contains(KeyModifiers::CONTROL),
+        },
+        col: mouse.column,
+        row: mouse.row,
+    })
+}
+
+fn convert_button(button: event::MouseButton) -> MouseButton {
+    match button {
+        event::MouseButton::Left => MouseButton::Left,
+        event::MouseButton::Middle => MouseButton::Middle,
+        event::MouseButton::Right => MouseButton::Right,
+    }
+}
+
+/// Convert a key press, encoding the bytes a terminal would have sent for it.
+fn convert_key(key: KeyEvent) -> (Event, Vec<u8>) {
+    let csi = |s: &str| format!("\x1b[{}", s).into_bytes();
+    let (key, data) = match key.code {
+        KeyCode::Char(c) if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            (Key::Ctrl(c), vec![(c as u8) & 0x1f])
+        }
+        KeyCode::Char(c) if key.modifiers.contains(KeyModifiers::ALT) => {
+            (Key::Alt(c), format!("\x1b{}", c).into_bytes())
+        }
+        KeyCode::Char(c) => (Key::Char(c), c.to_string().into_bytes()),
+        KeyCode::Enter => (Key::Char('\n'), vec![b'\r']),
+        KeyCode::Tab => (Key::Char('\t'), vec![b'\t']),
+        KeyCode::Backspace => (Key::Backspace, vec![0x7f]),
+        KeyCode::Esc => (Key::Esc, vec![0x1b]),
+        KeyCode::Null => (Key::Null, vec![0]),
+        KeyCode::Up if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            (Key::CtrlUp, csi("1;5A"))
+        }
+        KeyCode::Down if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            (Key::CtrlDown, csi("1;5B"))
+        }
+        KeyCode::Right if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            (Key::CtrlRight, csi("1;5C"))
+        }
+        KeyCode::Left if key.modifiers.contains(KeyModifiers::CONTROL) => {
+            (Key::CtrlLeft, csi("1;5D"))
+        }
+        KeyCode::Up if key.modifiers.contains(KeyModifiers::SHIFT) => {
+            (Key::ShiftUp, csi("1;2A"))
+        }
+        KeyCode::Down if key.modifiers.contains(KeyModifiers::SHIFT) => {
+            (Key::ShiftDown, csi("1;2B"))
+        }
+        KeyCode::Right if key.modifiers.contains(KeyModifiers::SHIFT) => {
+            (Key::ShiftRight, csi("1;2C"))
+        }
+        KeyCode::Left if key.modifiers.contains(KeyModifiers::SHIFT) => {
+            (Key::ShiftLeft, csi("1;2D"))
+        }
+        KeyCode::Up => (Key::Up, csi("A")),
+        KeyCode::Down => (Key::Down, csi("B")),
+        KeyCode::Right => (Key::Right, csi("C")),
+        KeyCode::Left => (Key::Left, csi("D")),
+        KeyCode::Home => (Key::Home, csi("H")),
+        KeyCode::End => (Key::End, csi("F")),
+        KeyCode::BackTab => (Key::BackTab, csi("Z")),
+        KeyCode::Insert => (Key::Insert, csi("2~")),
+        KeyCode::Delete => (Key::Delete, csi("3~")),
+        KeyCode::PageUp => (Key::PageUp, csi("5~")),
+        KeyCode::PageDown => (Key::PageDown, csi("6~")),
+        KeyCode::F(n @ 1..=4) => (Key::F(n), vec![0x1b, b'O', b'P' + n - 1]),
+        KeyCode::F(n) => {
+            let code = match n {
+                5 => "15",
+                6 => "17",
+                7 => "18",
+                8 => "19",
+                9 => "20",
+                10 => "21",
+                11 => "23",
+                12 => "24",
+                _ => return (Event::Key(Key::F(n)), Vec::new()),
+            };
+            (Key::F(n), csi(&format!("{}~", code)))
+        }
+    };
+    (Event::Key(key), data)
+}
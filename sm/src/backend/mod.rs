@@ -0,0 +1,148 @@
+//! Abstraction over the outer terminal.
+//!
+//! The multiplexer only needs a few things from the terminal it runs in: raw
+//! mode, a stream of input events (with the bytes that produced them, so they
+//! can be forwarded to the selected window), its size, and a way of clearing
+//! it. `TerminalBackend` provides these, with implementations selected by the
+//! `termion-backend` and `crossterm-backend` features.
+
+use std::io::{self, Write};
+
+use futures::channel::mpsc::Receiver;
+use nix::pty::Winsize;
+
+#[cfg(feature = "crossterm-backend")]
+mod crossterm_backend;
+// With both features enabled, crossterm is the default and termion goes unused.
+#[cfg(feature = "termion-backend")]
+#[cfg_attr(feature = "crossterm-backend", allow(dead_code))]
+mod termion_backend;
+
+#[cfg(feature = "crossterm-backend")]
+pub use crossterm_backend::CrosstermBackend;
+#[cfg(feature = "termion-backend")]
+#[cfg_attr(feature = "crossterm-backend", allow(unused_imports))]
+pub use termion_backend::TermionBackend;
+
+/// The backend used when none is chosen explicitly.
+#[cfg(feature = "crossterm-backend")]
+pub type DefaultBackend = CrosstermBackend;
+/// The backend used when none is chosen explicitly.
+#[cfg(all(feature = "termion-backend", not(feature = "crossterm-backend")))]
+pub type DefaultBackend = TermionBackend;
+
+#[cfg(not(any(feature = "termion-backend", feature = "crossterm-backend")))]
+compile_error!("enable at least one of `termion-backend` and `crossterm-backend`");
+
+/// A key press.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Key {
+    Backspace,
+    Left,
+    Right,
+    Up,
+    Down,
+    /// An arrow key with Ctrl held.
+    CtrlLeft,
+    CtrlRight,
+    CtrlUp,
+    CtrlDown,
+    /// An arrow key with Shift held.
+    ShiftLeft,
+    ShiftRight,
+    ShiftUp,
+    ShiftDown,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    BackTab,
+    Delete,
+    Insert,
+    /// A function key, e.g. `F(1)` for F1.
+    F(u8),
+    /// A character; Enter is `Char('\n')`.
+    Char(char),
+    Alt(char),
+    Ctrl(char),
+    Null,
+    Esc,
+}
+
+/// A mouse button, or a turn of the wheel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MouseButton {
+    Left,
+    Middle,
+    Right,
+    WheelUp,
+    WheelDown,
+}
+
+/// Modifier keys held during a mouse event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Modifiers {
+    pub shift: bool,
+    pub alt: bool,
+    pub ctrl: bool,
+}
+
+/// What happened to a mouse button.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum MouseKind {
+    #[default]
+    Press,
+    /// The mouse moved with the button held.
+    Drag,
+    Release,
+}
+
+/// A mouse button pressed, dragged or released.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MouseEvent {
+    pub kind: MouseKind,
+    pub button: MouseButton,
+    pub modifiers: Modifiers,
+    /// The column, counted from 0.
+    pub col: u16,
+    /// The row, counted from 0.
+    pub row: u16,
+}
+
+/// An input event from the terminal.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    /// A key press.
+    Key(Key),
+    /// A mouse button event, reported only while mouse capture is on.
+    Mouse(MouseEvent),
+    /// Any other input.
+    Unsupported,
+}
+
+/// The terminal the multiplexer is running in.
+pub trait TerminalBackend {
+    /// A handle for writing to the terminal.
+    type Output: Write;
+
+    /// Put the terminal into raw mode, returning a handle for writing to it.
+    ///
+    /// The terminal leaves raw mode when the handle is dropped.
+    fn raw_output(&mut self) -> io::Result<Self::Output>;
+
+    /// Start reading input events, paired with the bytes that produced them.
+    fn input(&mut self) -> io::Result<Receiver<(Event, Vec<u8>)>>;
+
+    /// The current size of the terminal.
+    fn size(&self) -> io::Result<Winsize>;
+
+    /// Clear the terminal and move the cursor to its top left corner.
+    fn clear<T: Write>(&self, output: &mut T) -> io::Result<()>;
+
+    /// Ask the terminal to report mouse events, or to stop reporting them.
+    fn set_mouse_capture<T: Write>(
+        &self,
+        output: &mut T,
+        capture: bool,
+    ) -> io::Result<()>;
+}
@@ -0,0 +1,161 @@
+//! A `TerminalBackend` using termion.
+
+use std::{
+    fs::File,
+    io::{self, Write},
+    thread,
+};
+
+use futures::channel::mpsc::{self, Receiver};
+use nix::pty::Winsize;
+use termion::{
+    clear, cursor, event,
+    input::TermReadEventsAndRaw,
+    raw::{IntoRawMode, RawTerminal},
+};
+
+use super::{
+    Event, Key, Modifiers, MouseButton, MouseEvent, MouseKind, TerminalBackend,
+};
+
+/// A terminal accessed through termion.
+#[derive(Default)]
+pub struct TermionBackend {
+    tty: Option<File>,
+}
+
+impl TermionBackend {
+    /// Create a backend for the controlling terminal.
+    pub fn new() -> TermionBackend {
+        Default::default()
+    }
+
+    fn tty(&mut self) -> io::Result<File> {
+        match &self.tty {
+            Some(tty) => tty.try_clone(),
+            None => {
+                let tty = termion::get_tty()?;
+                self.tty = Some(tty.try_clone()?);
+                Ok(tty)
+            }
+        }
+    }
+}
+
+impl TerminalBackend for TermionBackend {
+    type Output = RawTerminal<File>;
+
+    fn raw_output(&mut self) -> io::Result<Self::Output> {
+        self.tty()?.into_raw_mode()
+    }
+
+    fn input(&mut self) -> io::Result<Receiver<(Event, Vec<u8>)>> {
+        let mut input_events = self.tty()?.events_and_raw();
+        let (mut send, recv) = mpsc::channel(0x1000);
+        thread::spawn(move || {
+            while let Some(Ok((e, d))) = input_events.next() {
+                send.try_send((convert_event(e), d)).unwrap();
+            }
+            send.disconnect();
+        });
+        Ok(recv)
+    }
+
+    fn size(&self) -> io::Result<Winsize> {
+        let (cols, rows) = termion::terminal_size()?;
+        Ok(Winsize {
+            ws_row: rows,
+            ws_col: cols,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        })
+    }
+
+    fn clear<T: Write>(&self, output: &mut T) -> io::Result<()> {
+        write!(output, "{}{}", clear::All, cursor::Goto(1, 1))
+    }
+
+    fn set_mouse_capture<T: Write>(
+        &self,
+        output: &mut T,
+        capture: bool,
+    ) -> io::Result<()> {
+        // Presses, releases and drags (1002), in SGR encoding (1006), as termion
+        // parses.
+        let mode = if capture { 'h' } else { 'l' };
+        write!(output, "\x1b[?1002{}\x1b[?1006{}", mode, mode)
+    }
+}
+
+fn convert_event(e: event::Event) -> Event {
+    use event::Key::*;
+
+    let key = match e {
+        event::Event::Key(key) => key,
+        event::Event::Mouse(event::MouseEvent::Press(button, x, y)) => {
+            return convert_mouse(MouseKind::Press, button, x, y);
+        }
+        // termion does not say which button is held or released.
+        event::Event::Mouse(event::MouseEvent::Hold(x, y)) => {
+            return convert_mouse(MouseKind::Drag, event::MouseButton::Left, x, y);
+        }
+        event::Event::Mouse(event::MouseEvent::Release(x, y)) => {
+            return convert_mouse(MouseKind::Release, event::MouseButton::Left, x, y);
+        }
+        // termion does not parse modified arrow keys.
+        event::Event::Unsupported(data) => {
+            return match data.as_slice() {
+                b"\x1b[1;5A" => Event::Key(Key::CtrlUp),
+                b"\x1b[1;5B" => Event::Key(Key::CtrlDown),
+                b"\x1b[1;5C" => Event::Key(Key::CtrlRight),
+                b"\x1b[1;5D" => Event::Key(Key::CtrlLeft),
+                b"\x1b[1;2A" => Event::Key(Key::ShiftUp),
+                b"\x1b[1;2B" => Event::Key(Key::ShiftDown),
+                b"\x1b[1;2C" => Event::Key(Key::ShiftRight),
+                b"\x1b[1;2D" => Event::Key(Key::ShiftLeft),
+                _ => Event::Unsupported,
+            };
+        }
+    };
+    Event::Key(match key {
+        Backspace => Key::Backspace,
+        Left => Key::Left,
+        Right => Key::Right,
+        Up => Key::Up,
+        Down => Key::Down,
+        Home => Key::Home,
+        End => Key::End,
+        PageUp => Key::PageUp,
+        PageDown => Key::PageDown,
+        BackTab => Key::BackTab,
+        Delete => Key::Delete,
+        Insert => Key::Insert,
+        F(n) => Key::F(n),
+        Char(c) => Key::Char(c),
+        Alt(c) => Key::Alt(c),
+        Ctrl(c) => Key::Ctrl(c),
+        Null => Key::Null,
+        Esc => Key::Esc,
+        _ => return Event::Unsupported,
+    })
+}
+
+fn convert_mouse(kind: MouseKind, button: event::MouseButton, x: u16, y: u16) -> Event {
+    use event::MouseButton::*;
+
+    let button = match button {
+        Left => MouseButton::Left,
+        Middle => MouseButton::Middle,
+        Right => MouseButton::Right,
+        WheelUp => MouseButton::WheelUp,
+        WheelDown => MouseButton::WheelDown,
+    };
+    // termion does not report modifiers.
+    Event::Mouse(MouseEvent {
+        kind,
+        button,
+        modifiers: Modifiers::default(),
+        col: x.saturating_sub(1),
+        row: y.saturating_sub(1),
+    })
+}
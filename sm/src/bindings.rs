@@ -0,0 +1,1608 @@
+//! What input does, other than being passed to the selected window.
+
+use std::{fmt, iter::Peekable, str::FromStr};
+
+use anyhow::{anyhow, bail, Context, Error, Result};
+use session_manager::layout::{Direction, Preset};
+
+use crate::{
+    backend::{Key, Modifiers, MouseButton, MouseEvent},
+    command,
+    config::{parse_set_environment, parse_switch},
+    mouse::Gesture,
+    options::OptionScope,
+};
+
+/// What the keys pressed after the prefix do, unless bound otherwise.
+pub const DEFAULT_KEY_BINDINGS: [KeyBinding; 52] = [
+    KeyBinding::new(
+        Key::Char('c'),
+        Action::NewWindow {
+            name: None,
+            command: None,
+            cwd: None,
+            env: Vec::new(),
+        },
+    ),
+    KeyBinding::new(Key::Char('d'), Action::Detach),
+    KeyBinding::new(Key::Char('n'), Action::NextWindow),
+    KeyBinding::new(Key::Char('p'), Action::PreviousWindow),
+    KeyBinding::new(Key::Char('l'), Action::LastWindow),
+    KeyBinding::new(Key::Char('0'), Action::SelectWindow(0)),
+    KeyBinding::new(Key::Char('1'), Action::SelectWindow(1)),
+    KeyBinding::new(Key::Char('2'), Action::SelectWindow(2)),
+    KeyBinding::new(Key::Char('3'), Action::SelectWindow(3)),
+    KeyBinding::new(Key::Char('4'), Action::SelectWindow(4)),
+    KeyBinding::new(Key::Char('5'), Action::SelectWindow(5)),
+    KeyBinding::new(Key::Char('6'), Action::SelectWindow(6)),
+    KeyBinding::new(Key::Char('7'), Action::SelectWindow(7)),
+    KeyBinding::new(Key::Char('8'), Action::SelectWindow(8)),
+    KeyBinding::new(Key::Char('9'), Action::SelectWindow(9)),
+    KeyBinding::new(Key::Char(','), Action::RenameWindow(None)),
+    KeyBinding::new(Key::Char('&'), Action::KillWindow(true)),
+    KeyBinding::new(Key::Char('w'), Action::ChooseWindow),
+    KeyBinding::new(Key::Char('s'), Action::ChooseTree),
+    KeyBinding::new(Key::Char('$'), Action::RenameSession(None)),
+    KeyBinding::new(Key::Char('<'), Action::SwapWindow(WindowTarget::Previous)),
+    KeyBinding::new(Key::Char('>'), Action::SwapWindow(WindowTarget::Next)),
+    KeyBinding::new(Key::Char('.'), Action::MoveWindow(None)),
+    KeyBinding::new(Key::Char('t'), Action::CycleTheme),
+    KeyBinding::new(Key::Char('T'), Action::ClearTheme),
+    KeyBinding::new(Key::Char('%'), Action::SplitHorizontal),
+    KeyBinding::new(Key::Char('"'), Action::SplitVertical),
+    KeyBinding::new(Key::Char('o'), Action::NextPane),
+    KeyBinding::new(Key::Char('z'), Action::ZoomPane),
+    KeyBinding::new(Key::Char('x'), Action::KillPane),
+    KeyBinding::new(Key::Char('!'), Action::BreakPane),
+    KeyBinding::new(Key::Char('m'), Action::ContextMenu),
+    KeyBinding::new(Key::Char(' '), Action::NextLayout),
+    KeyBinding::new(Key::Alt('1'), Action::SelectLayout(Preset::ALL[0])),
+    KeyBinding::new(Key::Alt('2'), Action::SelectLayout(Preset::ALL[1])),
+    KeyBinding::new(Key::Alt('3'), Action::SelectLayout(Preset::ALL[2])),
+    KeyBinding::new(Key::Alt('4'), Action::SelectLayout(Preset::ALL[3])),
+    KeyBinding::new(Key::Alt('5'), Action::SelectLayout(Preset::ALL[4])),
+    KeyBinding::new(Key::CtrlRight, Action::ResizePane(Direction::Horizontal, 1)),
+    KeyBinding::new(Key::CtrlLeft, Action::ResizePane(Direction::Horizontal, -1)),
+    KeyBinding::new(Key::CtrlDown, Action::ResizePane(Direction::Vertical, 1)),
+    KeyBinding::new(Key::CtrlUp, Action::ResizePane(Direction::Vertical, -1)),
+    KeyBinding::new(Key::Char('S'), Action::SlowPrint(10)),
+    KeyBinding::new(Key::Ctrl('p'), Action::TogglePrivate),
+    KeyBinding::new(Key::Char('y'), Action::SynchronizePanes),
+    KeyBinding::new(Key::Char('['), Action::CopyMode),
+    KeyBinding::new(Key::Char(']'), Action::PasteBuffer(None)),
+    KeyBinding::new(Key::Char('='), Action::ChooseBuffer),
+    KeyBinding::new(Key::Char('L'), Action::LockSession),
+    KeyBinding::new(Key::Char('R'), Action::SourceFile(None)),
+    KeyBinding::new(Key::Char(':'), Action::CommandPrompt),
+    KeyBinding::new(Key::Char('P'), Action::CommandPalette),
+];
+
+/// Something the multiplexer can be asked to do.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    /// Open a window running a shell command, or the shell, and select it.
+    NewWindow {
+        name: Option<String>,
+        command: Option<String>,
+        /// The directory it starts in, if not that of the selected window.
+        cwd: Option<String>,
+        /// Variables set in the environment of its panes, over the session's.
+        env: Vec<(String, String)>,
+    },
+    NextWindow,
+    PreviousWindow,
+    /// Select the window with an index, if there is one.
+    SelectWindow(usize),
+    /// Select the window selected before the selected one.
+    LastWindow,
+    /// Rename the selected window, asking for the name if it is not given.
+    RenameWindow(Option<String>),
+    /// List the windows to choose one to select.
+    ChooseWindow,
+    /// List the sessions, their windows and panes, to switch to, kill or
+    /// rename one.
+    ChooseTree,
+    /// Exchange the selected window's index with another window's.
+    SwapWindow(WindowTarget),
+    /// Give the selected window a free index, asking for it if it is not given.
+    MoveWindow(Option<usize>),
+    /// Number the windows from 0 up without gaps, keeping their order.
+    RenumberWindows,
+    /// Split the active pane into two side by side.
+    SplitHorizontal,
+    /// Split the active pane into two, one above the other.
+    SplitVertical,
+    /// Make the next pane of the selected window active.
+    NextPane,
+    /// Show the active pane alone, filling the window, or every pane again.
+    ZoomPane,
+    /// Rearrange the selected window's panes with the next preset layout.
+    NextLayout,
+    /// Rearrange the selected window's panes with a preset layout.
+    SelectLayout(Preset),
+    /// Grow the active pane by a number of cells in a direction, or shrink it
+    /// if the number is negative.
+    ResizePane(Direction, i32),
+    /// Limit the active pane's output to a number of lines a second, or remove
+    /// the limit if it has one.
+    SlowPrint(u32),
+    /// Send input to every pane of the selected window, or stop doing so.
+    SynchronizePanes,
+    /// Set the `TERM` of panes created in the selected window from now on.
+    SetTerm(String),
+    /// Choose whether panes of the selected window whose processes exit are
+    /// kept, showing what they left.
+    RemainOnExit(bool),
+    /// Start recording the input to the selected window, or stop and forget
+    /// it.
+    RecordInput(bool),
+    /// Show the input recorded for the selected window in the pager.
+    ShowInput,
+    /// Write the input recorded for the selected window to a file, relative to
+    /// the active pane's working directory.
+    SaveInput(String),
+    /// Make the active pane private, or not: while it is, nothing it shows or
+    /// is typed into it is recorded.
+    TogglePrivate,
+    /// Move the active pane into a new window of its own.
+    BreakPane,
+    /// Move the active pane into a window, splitting its active pane in a
+    /// direction.
+    JoinPane(usize, Direction),
+    /// Hang up on the active pane, which closes once its process exits.
+    KillPane,
+    /// Link the selected window into another session, by name, too.
+    LinkWindow(String),
+    /// Remove the selected window from the session, if it is linked into
+    /// others.
+    UnlinkWindow,
+    /// Kill the processes of the selected window and close it, asking first
+    /// if `true`.
+    KillWindow(bool),
+    /// Rename the session attached to, asking for the name if it is not given.
+    RenameSession(Option<String>),
+    /// Kill the processes of every window of the session attached to and
+    /// close them, ending it and detaching its clients, asking first if
+    /// `true`.
+    KillSession(bool),
+    /// Open the context menu over the active pane.
+    ContextMenu,
+    /// Run a shell command in the background, showing any output where
+    /// `output` says.
+    RunShell {
+        command: String,
+        output: ShellOutput,
+    },
+    /// Run `condition` with the shell in the background, then perform the
+    /// commands of `then` if it succeeds, or those of `otherwise` if it fails.
+    IfShell {
+        condition: String,
+        then: String,
+        otherwise: Option<String>,
+    },
+    /// Show a format, with its variables expanded, in place of the status line
+    /// for a while.
+    DisplayMessage(String),
+    /// Show the time in a new window.
+    ClockMode,
+    /// Show a file in a new window.
+    ViewFile(String),
+    /// Send the paste buffer with this name to the active pane, or the newest.
+    PasteBuffer(Option<String>),
+    /// Add a paste buffer with this name, replacing any of the same name, or
+    /// with the next automatic name.
+    SetBuffer {
+        name: Option<String>,
+        text: String,
+    },
+    /// Delete the paste buffer with this name, or the newest.
+    DeleteBuffer(Option<String>),
+    /// List the paste buffers to paste, delete or save one.
+    ChooseBuffer,
+    /// Send the system clipboard to the active pane, with the paste command.
+    PasteClipboard,
+    /// Write the text last copied to a file, relative to the active pane's
+    /// working directory.
+    SaveBuffer(String),
+    /// Apply the next built-in theme to the active pane.
+    CycleTheme,
+    ClearTheme,
+    /// Blank the screen until it is unlocked, with the lock command if there
+    /// is one.
+    LockSession,
+    /// Send the prefix key to the active pane.
+    SendPrefix,
+    /// Bind a key to an action, replacing any binding of the key in its table.
+    BindKey(Box<KeyBinding>),
+    /// Remove the binding of a key in a table, if it has one.
+    UnbindKey(KeyTable, Key),
+    /// Show the key bindings in the pager.
+    ListKeys,
+    /// Show the options of a scope, or all of them, in the pager: their values,
+    /// and where they were set.
+    ShowOptions(Option<OptionScope>),
+    /// Set a variable in the environment of new panes, or remove it if `None`.
+    SetEnvironment(String, Option<String>),
+    /// Show the environment new panes get in the pager.
+    ShowEnvironment,
+    /// Read a configuration file, or the one read on starting, again, taking
+    /// its options and bindings in place of those set so far.
+    SourceFile(Option<String>),
+    /// Scroll the active pane back through the rows scrolled away, and search
+    /// them and its screen.
+    CopyMode,
+    /// Ask for commands to perform, written as actions are in key bindings.
+    CommandPrompt,
+    /// List the actions with their keys, to find one and perform it.
+    CommandPalette,
+    /// Detach the client, leaving the session running to be attached to again.
+    Detach,
+    /// Detach every client but the one the input came from, for the windows to
+    /// take its size.
+    DetachOthers,
+}
+
+/// Where the output of `run-shell` is shown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ShellOutput {
+    /// In the pager.
+    #[default]
+    Pager,
+    /// `-m`: on its lines joined, in place of the status line, as
+    /// `display-message` shows a message.
+    Message,
+    /// `-w`: in a new window, closed with `q`.
+    Window,
+}
+
+/// A window, given relative to the selected one or by index.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WindowTarget {
+    Previous,
+    Next,
+    Index(usize),
+}
+
+impl FromStr for WindowTarget {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<WindowTarget> {
+        Ok(match s {
+            "previous" => WindowTarget::Previous,
+            "next" => WindowTarget::Next,
+            _ => WindowTarget::Index(s.parse().map_err(|_| {
+                anyhow!("expected previous, next or a window, not {:?}", s)
+            })?),
+        })
+    }
+}
+
+impl fmt::Display for WindowTarget {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WindowTarget::Previous => f.write_str("previous"),
+            WindowTarget::Next => f.write_str("next"),
+            WindowTarget::Index(idx) => write!(f, "{}", idx),
+        }
+    }
+}
+
+impl Action {
+    /// Whether the key bound to this action can be pressed again without the
+    /// prefix for a moment after it, as resizing takes a few presses.
+    pub fn repeats(&self) -> bool {
+        matches!(self, Action::ResizePane(..))
+    }
+}
+
+/// An action as the command palette lists it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ActionHelp {
+    pub name: &'static str,
+    /// The arguments written after the name, optional ones in brackets.
+    pub args: &'static str,
+    pub description: &'static str,
+}
+
+impl ActionHelp {
+    const fn new(
+        name: &'static str,
+        args: &'static str,
+        description: &'static str,
+    ) -> ActionHelp {
+        ActionHelp {
+            name,
+            args,
+            description,
+        }
+    }
+
+    /// Whether the action cannot be performed without arguments: whether any
+    /// are outside brackets.
+    pub fn needs_args(&self) -> bool {
+        let mut depth = 0;
+        self.args.chars().any(|c| {
+            match c {
+                '[' => depth += 1,
+                ']' => depth -= 1,
+                c => return depth == 0 && c != ' ',
+            }
+            false
+        })
+    }
+}
+
+/// Every action, by name. Only the actions here are parsed, so none is left
+/// out.
+pub const ACTIONS: [ActionHelp; 62] = [
+    ActionHelp::new(
+        "new-window",
+        "[-n NAME] [-c DIR] [-e NAME=VALUE] [COMMAND]",
+        "Open a window running a command, or the shell",
+    ),
+    ActionHelp::new("next-window", "", "Select the next window"),
+    ActionHelp::new("previous-window", "", "Select the previous window"),
+    ActionHelp::new("select-window", "INDEX", "Select the window with an index"),
+    ActionHelp::new("last-window", "", "Select the window selected before"),
+    ActionHelp::new("rename-window", "[NAME]", "Rename the selected window"),
+    ActionHelp::new("choose-window", "", "List the windows to choose one"),
+    ActionHelp::new(
+        "choose-tree",
+        "",
+        "List the sessions, windows and panes to switch to one",
+    ),
+    ActionHelp::new(
+        "swap-window",
+        "previous|next|INDEX",
+        "Exchange the selected window's index with another's",
+    ),
+    ActionHelp::new(
+        "move-window",
+        "[INDEX]",
+        "Give the selected window an index",
+    ),
+    ActionHelp::new("renumber-windows", "", "Number the windows without gaps"),
+    ActionHelp::new("split-horizontal", "", "Split the active pane side by side"),
+    ActionHelp::new(
+        "split-vertical",
+        "",
+        "Split the active pane one above the other",
+    ),
+    ActionHelp::new("next-pane", "", "Make the next pane active"),
+    ActionHelp::new("zoom-pane", "", "Show the active pane alone, or not"),
+    ActionHelp::new(
+        "next-layout",
+        "",
+        "Rearrange the panes with the next layout",
+    ),
+    ActionHelp::new(
+        "select-layout",
+        "LAYOUT",
+        "Rearrange the panes with a layout",
+    ),
+    ActionHelp::new(
+        "resize-pane",
+        "width|height CELLS",
+        "Grow or shrink the active pane",
+    ),
+    ActionHelp::new(
+        "slow-print",
+        "LINES",
+        "Limit the active pane's lines a second, or not",
+    ),
+    ActionHelp::new("set-term", "TERM", "Set the TERM of the window's new panes"),
+    ActionHelp::new(
+        "remain-on-exit",
+        "on|off",
+        "Keep the window's panes whose processes exit",
+    ),
+    ActionHelp::new("record-input", "on|off", "Record the input to the window"),
+    ActionHelp::new("show-input", "", "Show the input recorded for the window"),
+    ActionHelp::new("save-input", "FILE", "Write the input recorded to a file"),
+    ActionHelp::new("toggle-private", "", "Make the active pane private, or not"),
+    ActionHelp::new(
+        "synchronize-panes",
+        "",
+        "Send input to every pane of the window, or not",
+    ),
+    ActionHelp::new("break-pane", "", "Move the active pane into a new window"),
+    ActionHelp::new(
+        "join-pane",
+        "INDEX horizontal|vertical",
+        "Move the active pane into a window",
+    ),
+    ActionHelp::new("kill-pane", "", "Hang up on the active pane"),
+    ActionHelp::new(
+        "link-window",
+        "SESSION",
+        "Link the window into another session",
+    ),
+    ActionHelp::new("unlink-window", "", "Remove the window from this session"),
+    ActionHelp::new("kill-window", "[-y]", "Close the window, asking first"),
+    ActionHelp::new("rename-session", "[NAME]", "Rename the session"),
+    ActionHelp::new(
+        "kill-session",
+        "[-y]",
+        "Close every window of the session, asking first",
+    ),
+    ActionHelp::new("context-menu", "", "Open the menu over the active pane"),
+    ActionHelp::new(
+        "run-shell",
+        "[-m | -w] COMMAND",
+        "Run a command in the background",
+    ),
+    ActionHelp::new(
+        "if-shell",
+        "CONDITION COMMAND [ELSE]",
+        "Perform commands if a shell command succeeds",
+    ),
+    ActionHelp::new(
+        "display-message",
+        "FORMAT",
+        "Show a message on the status line",
+    ),
+    ActionHelp::new(
+        "paste-buffer",
+        "[-b NAME]",
+        "Paste a buffer, or the text last copied",
+    ),
+    ActionHelp::new("set-buffer", "[-b NAME] TEXT", "Add a paste buffer"),
+    ActionHelp::new(
+        "delete-buffer",
+        "[-b NAME]",
+        "Delete a buffer, or the text last copied",
+    ),
+    ActionHelp::new("choose-buffer", "", "List the paste buffers"),
+    ActionHelp::new("paste-clipboard", "", "Paste the system clipboard"),
+    ActionHelp::new(
+        "save-buffer",
+        "FILE",
+        "Write the text last copied to a file",
+    ),
+    ActionHelp::new("clock-mode", "", "Show the time in a new window"),
+    ActionHelp::new("view-file", "FILE", "Show a file in a new window"),
+    ActionHelp::new("cycle-theme", "", "Apply the next theme to the active pane"),
+    ActionHelp::new("clear-theme", "", "Remove the active pane's theme"),
+    ActionHelp::new("lock-session", "", "Blank the screen until it is unlocked"),
+    ActionHelp::new("send-prefix", "", "Send the prefix key to the active pane"),
+    ActionHelp::new("bind-key", "[-n | -T TABLE] KEY ACTION", "Bind a key"),
+    ActionHelp::new(
+        "unbind-key",
+        "[-n | -T TABLE] KEY",
+        "Remove a key's binding",
+    ),
+    ActionHelp::new("list-keys", "", "Show the key bindings"),
+    ActionHelp::new(
+        "show-options",
+        "[-g|-w]",
+        "Show the options, and where they were set",
+    ),
+    ActionHelp::new(
+        "set-environment",
+        "NAME VALUE | -r NAME",
+        "Set a variable in the environment of new panes, or remove it",
+    ),
+    ActionHelp::new("show-environment", "", "Show the environment new panes get"),
+    ActionHelp::new("source-file", "[PATH]", "Read the configuration file again"),
+    ActionHelp::new("copy-mode", "", "Scroll the active pane back and search it"),
+    ActionHelp::new("command-prompt", "", "Ask for commands to perform"),
+    ActionHelp::new("command-palette", "", "List the actions to perform one"),
+    ActionHelp::new("detach", "", "Detach, leaving the session running"),
+    ActionHelp::new("detach-client", "-a", "Detach the other clients"),
+];
+
+impl FromStr for Action {
+    type Err = Error;
+
+    /// Parse an action's name, followed by its arguments if it has any.
+    fn from_str(s: &str) -> Result<Action> {
+        let mut words = s.split_whitespace().peekable();
+        let name = words.next().ok_or_else(|| anyhow!("missing action"))?;
+        if !ACTIONS.iter().any(|help| help.name == name) {
+            bail!("unknown action: {}", name);
+        }
+        let mut arg = |what: &str| {
+            words
+                .next()
+                .ok_or_else(|| anyhow!("{} needs {}", name, what))
+        };
+        let action = match name {
+            "new-window" => {
+                let mut words = words.by_ref().peekable();
+                let (mut name, mut cwd, mut env) = (None, None, Vec::new());
+                while let Some(&flag @ ("-n" | "-c" | "-e")) = words.peek() {
+                    words.next();
+                    let what = match flag {
+                        "-n" => "a window name",
+                        "-c" => "a directory",
+                        _ => "NAME=VALUE",
+                    };
+                    let word = words
+                        .next()
+                        .ok_or_else(|| anyhow!("{} needs {}", flag, what))?;
+                    match flag {
+                        "-n" => name = Some(word.to_string()),
+                        "-c" => cwd = Some(word.to_string()),
+                        _ => {
+                            let (var, value) = word
+                                .split_once('=')
+                                .filter(|(var, _)| !var.is_empty())
+                                .ok_or_else(|| {
+                                    anyhow!("-e needs NAME=VALUE: {}", word)
+                                })?;
+                            env.push((var.to_string(), value.to_string()));
+                        }
+                    }
+                }
+                let command = words.collect::<Vec<_>>().join(" ");
+                Action::NewWindow {
+                    name,
+                    command: Some(command).filter(|command| !command.is_empty()),
+                    cwd,
+                    env,
+                }
+            }
+            "next-window" => Action::NextWindow,
+            "previous-window" => Action::PreviousWindow,
+            "select-window" => {
+                let idx = arg("a window")?;
+                let idx = idx
+                    .parse()
+                    .with_context(|| format!("invalid window: {}", idx))?;
+                Action::SelectWindow(idx)
+            }
+            "last-window" => Action::LastWindow,
+            "rename-window" => {
+                let name = words.by_ref().collect::<Vec<_>>().join(" ");
+                Action::RenameWindow(Some(name).filter(|name| !name.is_empty()))
+            }
+            "choose-window" => Action::ChooseWindow,
+            "choose-tree" => Action::ChooseTree,
+            "swap-window" => Action::SwapWindow(arg("a window")?.parse()?),
+            "move-window" => match words.next() {
+                Some(idx) => Action::MoveWindow(Some(
+                    idx.parse()
+                        .with_context(|| format!("invalid window: {}", idx))?,
+                )),
+                None => Action::MoveWindow(None),
+            },
+            "renumber-windows" => Action::RenumberWindows,
+            "split-horizontal" => Action::SplitHorizontal,
+            "split-vertical" => Action::SplitVertical,
+            "next-pane" => Action::NextPane,
+            "zoom-pane" => Action::ZoomPane,
+            "next-layout" => Action::NextLayout,
+            "select-layout" => Action::SelectLayout(
+                arg("a layout")?.parse().map_err(|e: String| anyhow!(e))?,
+            ),
+            "resize-pane" => {
+                // `-L`, `-R`, `-U` or `-D` move the pane's right or bottom edge.
+                let (direction, sign) = match arg("width or height")? {
+                    "width" => (Direction::Horizontal, None),
+                    "height" => (Direction::Vertical, None),
+                    "-L" => (Direction::Horizontal, Some(-1)),
+                    "-R" => (Direction::Horizontal, Some(1)),
+                    "-U" => (Direction::Vertical, Some(-1)),
+                    "-D" => (Direction::Vertical, Some(1)),
+                    other => bail!("expected width or height, not {:?}", other),
+                };
+                let cells = match (sign, words.next()) {
+                    (Some(sign), None) => sign,
+                    (sign, cells) => {
+                        let cells = cells.ok_or_else(|| {
+                            anyhow!("resize-pane needs a number of cells")
+                        })?;
+                        let cells: i32 = cells.parse().with_context(|| {
+                            format!("invalid number of cells: {}", cells)
+                        })?;
+                        cells * sign.unwrap_or(1)
+                    }
+                };
+                Action::ResizePane(direction, cells)
+            }
+            "slow-print" => {
+                let lines = arg("a number of lines")?;
+                let lines = lines
+                    .parse()
+                    .with_context(|| format!("invalid number of lines: {}", lines))?;
+                Action::SlowPrint(lines)
+            }
+            "set-term" => Action::SetTerm(arg("a terminal type")?.to_string()),
+            "remain-on-exit" => Action::RemainOnExit(parse_switch(arg("on or off")?)?),
+            "record-input" => Action::RecordInput(parse_switch(arg("on or off")?)?),
+            "show-input" => Action::ShowInput,
+            "save-input" => Action::SaveInput(arg("a file")?.to_string()),
+            "toggle-private" => Action::TogglePrivate,
+            "synchronize-panes" => Action::SynchronizePanes,
+            "break-pane" => Action::BreakPane,
+            "join-pane" => {
+                let window = arg("a window")?;
+                let window = window
+                    .parse()
+                    .with_context(|| format!("invalid window: {}", window))?;
+                let direction = match arg("horizontal or vertical")? {
+                    "horizontal" => Direction::Horizontal,
+                    "vertical" => Direction::Vertical,
+                    other => bail!("expected horizontal or vertical, not {:?}", other),
+                };
+                Action::JoinPane(window, direction)
+            }
+            "kill-pane" => Action::KillPane,
+            "link-window" => Action::LinkWindow(arg("a session")?.to_string()),
+            "unlink-window" => Action::UnlinkWindow,
+            "kill-window" => match words.next() {
+                None => Action::KillWindow(true),
+                Some("-y") => Action::KillWindow(false),
+                Some(other) => bail!("expected -y, not {:?}", other),
+            },
+            "rename-session" => {
+                let name = words.by_ref().collect::<Vec<_>>().join(" ");
+                Action::RenameSession(Some(name).filter(|name| !name.is_empty()))
+            }
+            "kill-session" => match words.next() {
+                None => Action::KillSession(true),
+                Some("-y") => Action::KillSession(false),
+                Some(other) => bail!("expected -y, not {:?}", other),
+            },
+            "context-menu" => Action::ContextMenu,
+            "run-shell" => {
+                let mut command: Vec<_> = words.by_ref().collect();
+                let output = match command.first() {
+                    Some(&"-m") => ShellOutput::Message,
+                    Some(&"-w") => ShellOutput::Window,
+                    _ => ShellOutput::Pager,
+                };
+                if output != ShellOutput::Pager {
+                    command.remove(0);
+                }
+                if command.is_empty() {
+                    bail!("run-shell needs a command");
+                }
+                let command = command.join(" ");
+                Action::RunShell { command, output }
+            }
+            "if-shell" => {
+                // The arguments are quoted, so split as commands are.
+                words.by_ref().for_each(drop);
+                let args = &s.trim_start()[name.len()..];
+                let (condition, then, otherwise) = command::if_shell_args(args)?;
+                // Found wrong now rather than once the condition has run.
+                command::parse(&then)?;
+                otherwise.as_deref().map(command::parse).transpose()?;
+                Action::IfShell {
+                    condition,
+                    then,
+                    otherwise,
+                }
+            }
+            "display-message" => {
+                let format = words.by_ref().collect::<Vec<_>>().join(" ");
+                if format.is_empty() {
+                    bail!("display-message needs a format");
+                }
+                Action::DisplayMessage(format)
+            }
+            "paste-buffer" => Action::PasteBuffer(parse_buffer_name(&mut words)?),
+            "set-buffer" => {
+                let name = parse_buffer_name(&mut words)?;
+                let text = words.by_ref().collect::<Vec<_>>().join(" ");
+                if text.is_empty() {
+                    bail!("set-buffer needs some text");
+                }
+                Action::SetBuffer { name, text }
+            }
+            "delete-buffer" => Action::DeleteBuffer(parse_buffer_name(&mut words)?),
+            "choose-buffer" => Action::ChooseBuffer,
+            "paste-clipboard" => Action::PasteClipboard,
+            "save-buffer" => Action::SaveBuffer(arg("a file")?.to_string()),
+            "clock-mode" => Action::ClockMode,
+            "view-file" => Action::ViewFile(arg("a file")?.to_string()),
+            "cycle-theme" => Action::CycleTheme,
+            "clear-theme" => Action::ClearTheme,
+            "lock-session" => Action::LockSession,
+            "send-prefix" => Action::SendPrefix,
+            "bind-key" => {
+                let binding = words.by_ref().collect::<Vec<_>>().join(" ");
+                Action::BindKey(Box::new(binding.parse()?))
+            }
+            "unbind-key" => {
+                let (table, key) = parse_table_key(&mut words)?;
+                Action::UnbindKey(table, key)
+            }
+            "list-keys" => Action::ListKeys,
+            "show-options" => {
+                Action::ShowOptions(words.next().map(str::parse).transpose()?)
+            }
+            "set-environment" => {
+                let args = words.by_ref().collect::<Vec<_>>().join(" ");
+                let (name, value) = parse_set_environment(&args)?;
+                Action::SetEnvironment(name, value)
+            }
+            "show-environment" => Action::ShowEnvironment,
+            "source-file" => Action::SourceFile(words.next().map(str::to_string)),
+            "copy-mode" => Action::CopyMode,
+            "command-prompt" => Action::CommandPrompt,
+            "command-palette" => Action::CommandPalette,
+            "detach" => Action::Detach,
+            // `detach` detaches the client itself.
+            "detach-client" => match words.next() {
+                Some("-a") => Action::DetachOthers,
+                other => bail!("expected -a, not {:?}", other.unwrap_or("")),
+            },
+            _ => bail!("unknown action: {}", name),
+        };
+        if let Some(extra) = words.next() {
+            bail!("unexpected {:?} after {}", extra, name);
+        }
+        Ok(action)
+    }
+}
+
+impl fmt::Display for Action {
+    /// Write the action as it is parsed.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let switch = |on: bool| if on { "on" } else { "off" };
+        match self {
+            Action::NewWindow {
+                name,
+                command,
+                cwd,
+                env,
+            } => {
+                f.write_str("new-window")?;
+                if let Some(name) = name {
+                    write!(f, " -n {}", name)?;
+                }
+                if let Some(cwd) = cwd {
+                    write!(f, " -c {}", cwd)?;
+                }
+                for (var, value) in env {
+                    write!(f, " -e {}={}", var, value)?;
+                }
+                if let Some(command) = command {
+                    write!(f, " {}", command)?;
+                }
+                Ok(())
+            }
+            Action::NextWindow => f.write_str("next-window"),
+            Action::PreviousWindow => f.write_str("previous-window"),
+            Action::SelectWindow(idx) => write!(f, "select-window {}", idx),
+            Action::LastWindow => f.write_str("last-window"),
+            Action::RenameWindow(None) => f.write_str("rename-window"),
+            Action::RenameWindow(Some(name)) => write!(f, "rename-window {}", name),
+            Action::ChooseWindow => f.write_str("choose-window"),
+            Action::ChooseTree => f.write_str("choose-tree"),
+            Action::SwapWindow(target) => write!(f, "swap-window {}", target),
+            Action::MoveWindow(None) => f.write_str("move-window"),
+            Action::MoveWindow(Some(idx)) => write!(f, "move-window {}", idx),
+            Action::RenumberWindows => f.write_str("renumber-windows"),
+            Action::SplitHorizontal => f.write_str("split-horizontal"),
+            Action::SplitVertical => f.write_str("split-vertical"),
+            Action::NextPane => f.write_str("next-pane"),
+            Action::ZoomPane => f.write_str("zoom-pane"),
+            Action::NextLayout => f.write_str("next-layout"),
+            Action::SelectLayout(preset) => write!(f, "select-layout {}", preset),
+            Action::ResizePane(direction, cells) => {
+                let direction = match direction {
+                    Direction::Horizontal => "width",
+                    Direction::Vertical => "height",
+                };
+                write!(f, "resize-pane {} {}", direction, cells)
+            }
+            Action::SlowPrint(lines) => write!(f, "slow-print {}", lines),
+            Action::SynchronizePanes => f.write_str("synchronize-panes"),
+            Action::SetTerm(term) => write!(f, "set-term {}", term),
+            Action::RemainOnExit(on) => write!(f, "remain-on-exit {}", switch(*on)),
+            Action::RecordInput(on) => write!(f, "record-input {}", switch(*on)),
+            Action::ShowInput => f.write_str("show-input"),
+            Action::SaveInput(path) => write!(f, "save-input {}", path),
+            Action::TogglePrivate => f.write_str("toggle-private"),
+            Action::BreakPane => f.write_str("break-pane"),
+            Action::JoinPane(idx, direction) => {
+                let direction = match direction {
+                    Direction::Horizontal => "horizontal",
+                    Direction::Vertical => "vertical",
+                };
+                write!(f, "join-pane {} {}", idx, direction)
+            }
+            Action::KillPane => f.write_str("kill-pane"),
+            Action::LinkWindow(session) => write!(f, "link-window {}", session),
+            Action::UnlinkWindow => f.write_str("unlink-window"),
+            Action::KillWindow(true) => f.write_str("kill-window"),
+            Action::KillWindow(false) => f.write_str("kill-window -y"),
+            Action::RenameSession(None) => f.write_str("rename-session"),
+            Action::RenameSession(Some(name)) => write!(f, "rename-session {}", name),
+            Action::KillSession(true) => f.write_str("kill-session"),
+            Action::KillSession(false) => f.write_str("kill-session -y"),
+            Action::ContextMenu => f.write_str("context-menu"),
+            Action::RunShell { command, output } => match output {
+                ShellOutput::Pager => write!(f, "run-shell {}", command),
+                ShellOutput::Message => write!(f, "run-shell -m {}", command),
+                ShellOutput::Window => write!(f, "run-shell -w {}", command),
+            },
+            Action::IfShell {
+                condition,
+                then,
+                otherwise,
+            } => {
+                let quoted = [Some(condition), Some(then), otherwise.as_ref()];
+                f.write_str("if-shell")?;
+                for arg in quoted.iter().flatten() {
+                    write!(f, " {}", command::quote(arg))?;
+                }
+                Ok(())
+            }
+            Action::DisplayMessage(format) => write!(f, "display-message {}", format),
+            Action::ClockMode => f.write_str("clock-mode"),
+            Action::ViewFile(path) => write!(f, "view-file {}", path),
+            Action::PasteBuffer(None) => f.write_str("paste-buffer"),
+            Action::PasteBuffer(Some(name)) => write!(f, "paste-buffer -b {}", name),
+            Action::SetBuffer { name: None, text } => write!(f, "set-buffer {}", text),
+            Action::SetBuffer {
+                name: Some(name),
+                text,
+            } => write!(f, "set-buffer -b {} {}", name, text),
+            Action::DeleteBuffer(None) => f.write_str("delete-buffer"),
+            Action::DeleteBuffer(Some(name)) => write!(f, "delete-buffer -b {}", name),
+            Action::ChooseBuffer => f.write_str("choose-buffer"),
+            Action::PasteClipboard => f.write_str("paste-clipboard"),
+            Action::SaveBuffer(path) => write!(f, "save-buffer {}", path),
+            Action::CycleTheme => f.write_str("cycle-theme"),
+            Action::ClearTheme => f.write_str("clear-theme"),
+            Action::LockSession => f.write_str("lock-session"),
+            Action::SendPrefix => f.write_str("send-prefix"),
+            Action::BindKey(binding) => write!(f, "bind-key {}", binding),
+            Action::UnbindKey(table, key) => {
+                f.write_str("unbind-key ")?;
+                write_table_key(f, *table, *key)
+            }
+            Action::ListKeys => f.write_str("list-keys"),
+            Action::ShowOptions(None) => f.write_str("show-options"),
+            Action::ShowOptions(Some(scope)) => {
+                write!(f, "show-options {}", scope.flag())
+            }
+            Action::SetEnvironment(name, Some(value)) => {
+                write!(f, "set-environment {} {}", name, value)
+            }
+            Action::SetEnvironment(name, None) => {
+                write!(f, "set-environment -r {}", name)
+            }
+            Action::ShowEnvironment => f.write_str("show-environment"),
+            Action::SourceFile(None) => f.write_str("source-file"),
+            Action::SourceFile(Some(path)) => write!(f, "source-file {}", path),
+            Action::CommandPrompt => f.write_str("command-prompt"),
+            Action::CopyMode => f.write_str("copy-mode"),
+            Action::CommandPalette => f.write_str("command-palette"),
+            Action::Detach => f.write_str("detach"),
+            Action::DetachOthers => f.write_str("detach-client -a"),
+        }
+    }
+}
+
+/// A set of key bindings, looked up by the keys pressed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyTable {
+    /// Keys pressed after the prefix.
+    Prefix,
+    /// Keys pressed without the prefix, which then are not passed to the
+    /// selected window.
+    Root,
+}
+
+impl fmt::Display for KeyTable {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            KeyTable::Prefix => "prefix",
+            KeyTable::Root => "root",
+        })
+    }
+}
+
+impl FromStr for KeyTable {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<KeyTable> {
+        Ok(match s {
+            "prefix" => KeyTable::Prefix,
+            "root" => KeyTable::Root,
+            _ => bail!("unknown key table: {}", s),
+        })
+    }
+}
+
+/// A key in a table bound to an action.
+///
+/// Written as `[-n | -T TABLE] KEY ACTION`, e.g. `C-Up resize-pane height -5`.
+/// The key is in the prefix table unless `-n`, for the root table, or another
+/// table is given. `KEY` is a character, `C-` or `M-` and a character, `Space`,
+/// `Enter`, `Tab`, `BTab`, `Escape`, `BSpace`, `F1` to `F12`, `Home`, `End`,
+/// `PageUp`, `PageDown`, `Insert`, `Delete`, or an arrow key (`Up`, `Down`,
+/// `Left`, `Right`) with or without `C-`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyBinding {
+    pub table: KeyTable,
+    pub key: Key,
+    pub action: Action,
+}
+
+impl KeyBinding {
+    /// Bind `key`, pressed after the prefix, to `action`.
+    pub const fn new(key: Key, action: Action) -> KeyBinding {
+        KeyBinding {
+            table: KeyTable::Prefix,
+            key,
+            action,
+        }
+    }
+}
+
+impl fmt::Display for KeyBinding {
+    /// Write the binding as it is parsed.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_table_key(f, self.table, self.key)?;
+        write!(f, " {}", self.action)
+    }
+}
+
+impl FromStr for KeyBinding {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<KeyBinding> {
+        let mut words = s.split_whitespace();
+        let (table, key) = parse_table_key(&mut words)?;
+        Ok(KeyBinding {
+            table,
+            key,
+            action: words.collect::<Vec<_>>().join(" ").parse()?,
+        })
+    }
+}
+
+/// The bindings of keys to actions, starting out with the defaults.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyBindings(Vec<KeyBinding>);
+
+impl Default for KeyBindings {
+    fn default() -> KeyBindings {
+        KeyBindings(DEFAULT_KEY_BINDINGS.to_vec())
+    }
+}
+
+impl KeyBindings {
+    /// Add `binding`, replacing the binding of its key in its table, if any.
+    pub fn bind(&mut self, binding: KeyBinding) {
+        match self.position(binding.table, binding.key) {
+            Some(idx) => self.0[idx] = binding,
+            None => self.0.push(binding),
+        }
+    }
+
+    /// Remove the binding of `key` in `table`, returning whether there was one.
+    pub fn unbind(&mut self, table: KeyTable, key: Key) -> bool {
+        match self.position(table, key) {
+            Some(idx) => {
+                self.0.remove(idx);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The action `key` is bound to in `table`.
+    pub fn get(&self, table: KeyTable, key: Key) -> Option<&Action> {
+        self.position(table, key).map(|idx| &self.0[idx].action)
+    }
+
+    /// The bindings, in the order they were added, the defaults first.
+    pub fn iter(&self) -> impl Iterator<Item = &KeyBinding> {
+        self.0.iter()
+    }
+
+    fn position(&self, table: KeyTable, key: Key) -> Option<usize> {
+        self.0
+            .iter()
+            .position(|binding| binding.table == table && binding.key == key)
+    }
+}
+
+/// Parse `[-n | -T TABLE] KEY` from `words`.
+pub fn parse_table_key<'a>(
+    words: &mut impl Iterator<Item = &'a str>,
+) -> Result<(KeyTable, Key)> {
+    let mut table = KeyTable::Prefix;
+    let mut key = words.next();
+    match key {
+        Some("-n") => {
+            table = KeyTable::Root;
+            key = words.next();
+        }
+        Some("-T") => {
+            table = words
+                .next()
+                .ok_or_else(|| anyhow!("-T needs a key table"))?
+                .parse()?;
+            key = words.next();
+        }
+        _ => (),
+    }
+    let key = key.ok_or_else(|| anyhow!("missing key"))?;
+    Ok((table, parse_key(key)?))
+}
+
+/// Parse the paste buffer named by `-b NAME` at the start of `words`, if it is.
+fn parse_buffer_name<'a, I>(words: &mut Peekable<I>) -> Result<Option<String>>
+where
+    I: Iterator<Item = &'a str>,
+{
+    if words.next_if_eq(&"-b").is_none() {
+        return Ok(None);
+    }
+    let name = words
+        .next()
+        .ok_or_else(|| anyhow!("-b needs a buffer name"))?;
+    Ok(Some(name.to_string()))
+}
+
+/// Write `key` in `table` as `parse_table_key` parses it.
+fn write_table_key(f: &mut fmt::Formatter, table: KeyTable, key: Key) -> fmt::Result {
+    match table {
+        KeyTable::Prefix => (),
+        KeyTable::Root => f.write_str("-n ")?,
+    }
+    f.write_str(&key_name(key))
+}
+
+/// The name of `key`, as `parse_key` parses it.
+pub fn key_name(key: Key) -> String {
+    match key {
+        Key::Char(' ') => "Space".to_string(),
+        Key::Char('\n') => "Enter".to_string(),
+        Key::Char('\t') => "Tab".to_string(),
+        Key::Char(c) => c.to_string(),
+        Key::Ctrl(c) => format!("C-{}", c),
+        Key::Alt(c) => format!("M-{}", c),
+        Key::F(n) => format!("F{}", n),
+        Key::Null => "C-Space".to_string(),
+        Key::Esc => "Escape".to_string(),
+        Key::Backspace => "BSpace".to_string(),
+        Key::BackTab => "BTab".to_string(),
+        Key::Up => "Up".to_string(),
+        Key::Down => "Down".to_string(),
+        Key::Left => "Left".to_string(),
+        Key::Right => "Right".to_string(),
+        Key::CtrlUp => "C-Up".to_string(),
+        Key::CtrlDown => "C-Down".to_string(),
+        Key::CtrlLeft => "C-Left".to_string(),
+        Key::CtrlRight => "C-Right".to_string(),
+        Key::ShiftUp => "S-Up".to_string(),
+        Key::ShiftDown => "S-Down".to_string(),
+        Key::ShiftLeft => "S-Left".to_string(),
+        Key::ShiftRight => "S-Right".to_string(),
+        Key::Home => "Home".to_string(),
+        Key::End => "End".to_string(),
+        Key::PageUp => "PageUp".to_string(),
+        Key::PageDown => "PageDown".to_string(),
+        Key::Insert => "Insert".to_string(),
+        Key::Delete => "Delete".to_string(),
+    }
+}
+
+fn parse_key(s: &str) -> Result<Key> {
+    /// The character `s` consists of, if it is just one.
+    fn single(s: &str) -> Option<char> {
+        let mut chars = s.chars();
+        chars.next().filter(|_| chars.next().is_none())
+    }
+
+    Ok(match s {
+        "Space" => Key::Char(' '),
+        "Enter" => Key::Char('\n'),
+        "Tab" => Key::Char('\t'),
+        "BTab" => Key::BackTab,
+        "Escape" => Key::Esc,
+        "BSpace" => Key::Backspace,
+        "C-Space" => Key::Null,
+        "Home" => Key::Home,
+        "End" => Key::End,
+        "PageUp" => Key::PageUp,
+        "PageDown" => Key::PageDown,
+        "Insert" => Key::Insert,
+        "Delete" => Key::Delete,
+        "Up" => Key::Up,
+        "Down" => Key::Down,
+        "Left" => Key::Left,
+        "Right" => Key::Right,
+        "C-Up" => Key::CtrlUp,
+        "C-Down" => Key::CtrlDown,
+        "C-Left" => Key::CtrlLeft,
+        "C-Right" => Key::CtrlRight,
+        "S-Up" => Key::ShiftUp,
+        "S-Down" => Key::ShiftDown,
+        "S-Left" => Key::ShiftLeft,
+        "S-Right" => Key::ShiftRight,
+        _ => {
+            if let Some(c) = single(s) {
+                Key::Char(c)
+            } else if let Some(c) = s.strip_prefix("C-").and_then(single) {
+                Key::Ctrl(c)
+            } else if let Some(c) = s.strip_prefix("M-").and_then(single) {
+                Key::Alt(c)
+            } else {
+                match s.strip_prefix('F').and_then(|n| n.parse().ok()) {
+                    Some(n @ 1..=12) => Key::F(n),
+                    _ => bail!("unknown key: {:?}", s),
+                }
+            }
+        }
+    })
+}
+
+/// The part of the terminal a mouse event happened in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MouseRegion {
+    /// The contents of a window.
+    Pane,
+    /// The status line.
+    Status,
+    /// A border between panes.
+    Border,
+}
+
+impl FromStr for MouseRegion {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<MouseRegion> {
+        Ok(match s {
+            "pane" => MouseRegion::Pane,
+            "status" => MouseRegion::Status,
+            "border" => MouseRegion::Border,
+            _ => bail!("unknown mouse region: {}", s),
+        })
+    }
+}
+
+/// A mouse button, with modifiers, clicked or dragged in a region and bound to
+/// an action.
+///
+/// Written as `[C-][M-][S-][Double|Triple|Drag]BUTTON REGION ACTION`, e.g.
+/// `C-Left pane new-window`, `DoubleLeft status new-window` or
+/// `WheelDown status next-window`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MouseBinding {
+    pub button: MouseButton,
+    pub modifiers: Modifiers,
+    pub gesture: Gesture,
+    pub region: MouseRegion,
+    pub action: Action,
+}
+
+impl MouseBinding {
+    /// Whether this binding applies to `event`, amounting to `gesture`, in
+    /// `region`.
+    pub fn matches(
+        &self,
+        event: &MouseEvent,
+        gesture: Gesture,
+        region: MouseRegion,
+    ) -> bool {
+        self.button == event.button
+            && self.modifiers == event.modifiers
+            && self.gesture == gesture
+            && self.region == region
+    }
+}
+
+impl FromStr for MouseBinding {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<MouseBinding> {
+        let mut words = s.split_whitespace();
+        let mut next = |what: &str| {
+            words
+                .next()
+                .ok_or_else(|| anyhow!("mouse binding needs {}", what))
+        };
+        let mut button = next("a button")?;
+        let mut modifiers = Modifiers::default();
+        loop {
+            let modifier = match button.get(..2) {
+                Some("C-") => &mut modifiers.ctrl,
+                Some("M-") => &mut modifiers.alt,
+                Some("S-") => &mut modifiers.shift,
+                _ => break,
+            };
+            *modifier = true;
+            button = &button[2..];
+        }
+        let mut gesture = Gesture::Click(1);
+        for (prefix, prefixed) in [
+            ("Double", Gesture::Click(2)),
+            ("Triple", Gesture::Click(3)),
+            ("Drag", Gesture::Drag),
+        ] {
+            if let Some(rest) = button.strip_prefix(prefix) {
+                gesture = prefixed;
+                button = rest;
+                break;
+            }
+        }
+        let button = match button {
+            "Left" => MouseButton::Left,
+            "Middle" => MouseButton::Middle,
+            "Right" => MouseButton::Right,
+            "WheelUp" => MouseButton::WheelUp,
+            "WheelDown" => MouseButton::WheelDown,
+            _ => bail!("unknown mouse button: {}", button),
+        };
+        let wheel = matches!(button, MouseButton::WheelUp | MouseButton::WheelDown);
+        if wheel && gesture != Gesture::Click(1) {
+            bail!("the wheel can only be turned");
+        }
+        let region = next("a region")?.parse()?;
+        let action = words.collect::<Vec<_>>().join(" ").parse()?;
+        Ok(MouseBinding {
+            button,
+            modifiers,
+            gesture,
+            region,
+            action,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_mouse_binding() {
+        let binding: MouseBinding = "C-M-Middle pane new-window".parse().unwrap();
+        assert_eq!(
+            binding,
+            MouseBinding {
+                button: MouseButton::Middle,
+                modifiers: Modifiers {
+                    shift: false,
+                    alt: true,
+                    ctrl: true,
+                },
+                gesture: Gesture::Click(1),
+                region: MouseRegion::Pane,
+                action: Action::NewWindow {
+                    name: None,
+                    command: None,
+                    cwd: None,
+                    env: Vec::new(),
+                },
+            }
+        );
+        let event = MouseEvent {
+            kind: Default::default(),
+            button: MouseButton::Middle,
+            modifiers: binding.modifiers,
+            col: 3,
+            row: 4,
+        };
+        assert!(binding.matches(&event, Gesture::Click(1), MouseRegion::Pane));
+        assert!(!binding.matches(&event, Gesture::Click(1), MouseRegion::Status));
+        assert!(!binding.matches(&event, Gesture::Click(2), MouseRegion::Pane));
+
+        let binding: MouseBinding = "DoubleLeft status new-window".parse().unwrap();
+        assert_eq!(binding.button, MouseButton::Left);
+        assert_eq!(binding.gesture, Gesture::Click(2));
+        let binding: MouseBinding = "S-DragRight border next-pane".parse().unwrap();
+        assert_eq!(binding.gesture, Gesture::Drag);
+        assert!(binding.modifiers.shift);
+        assert!("DragWheelUp pane next-window"
+            .parse::<MouseBinding>()
+            .is_err());
+
+        assert!("WheelUp status".parse::<MouseBinding>().is_err());
+        assert!("X-Left pane new-window".parse::<MouseBinding>().is_err());
+        assert!("Left pane next-pane now".parse::<MouseBinding>().is_err());
+        assert!("Left pane".parse::<MouseBinding>().is_err());
+    }
+
+    #[test]
+    fn parse_key_binding() {
+        let parse = |s: &str| s.parse::<KeyBinding>();
+        assert_eq!(
+            parse("C-Up resize-pane height -5").unwrap(),
+            KeyBinding::new(Key::CtrlUp, Action::ResizePane(Direction::Vertical, -5))
+        );
+        assert_eq!(
+            parse("M-9 select-layout tiled").unwrap(),
+            KeyBinding::new(Key::Alt('9'), Action::SelectLayout(Preset::Tiled))
+        );
+        assert_eq!(parse("Space next-pane").unwrap().key, Key::Char(' '));
+        assert_eq!(parse("C-a send-prefix").unwrap().key, Key::Ctrl('a'));
+        assert_eq!(parse("F12 new-window").unwrap().key, Key::F(12));
+        assert_eq!(parse("- new-window").unwrap().key, Key::Char('-'));
+        assert_eq!(
+            parse("h new-window -n logs tail -f /var/log/syslog")
+                .unwrap()
+                .action,
+            Action::NewWindow {
+                name: Some("logs".to_string()),
+                command: Some("tail -f /var/log/syslog".to_string()),
+                cwd: None,
+                env: Vec::new(),
+            }
+        );
+        assert_eq!(
+            parse("h new-window htop").unwrap().action,
+            Action::NewWindow {
+                name: None,
+                command: Some("htop".to_string()),
+                cwd: None,
+                env: Vec::new(),
+            }
+        );
+        assert!(parse("h new-window -n").is_err());
+        let action = parse("h new-window -c /tmp -n scratch make test")
+            .unwrap()
+            .action;
+        assert_eq!(
+            action,
+            Action::NewWindow {
+                name: Some("scratch".to_string()),
+                command: Some("make test".to_string()),
+                cwd: Some("/tmp".to_string()),
+                env: Vec::new(),
+            }
+        );
+        assert_eq!(
+            action.to_string(),
+            "new-window -n scratch -c /tmp make test"
+        );
+        assert!(parse("h new-window -c").is_err());
+        assert!(parse("h new-window -e EDITOR").is_err());
+        assert!(parse("h new-window -e =vi").is_err());
+
+        assert!(parse("F13 new-window").is_err());
+        assert!(parse("Cx new-window").is_err());
+        assert!(parse("x").is_err());
+        assert!(parse("x resize-pane width").is_err());
+        assert!(parse("x resize-pane depth 1").is_err());
+        assert_eq!(
+            parse("x resize-pane -L 3").unwrap().action,
+            Action::ResizePane(Direction::Horizontal, -3)
+        );
+        assert_eq!(
+            parse("x resize-pane -D").unwrap().action,
+            Action::ResizePane(Direction::Vertical, 1)
+        );
+        assert!(parse("x resize-pane -U up").is_err());
+        assert!(parse("x select-layout spiral").is_err());
+        assert!(parse("x next-pane 2").is_err());
+        assert_eq!(
+            parse("s slow-print 5").unwrap().action,
+            Action::SlowPrint(5)
+        );
+        assert!(parse("s slow-print -5").is_err());
+        assert_eq!(
+            parse("v set-term vt100").unwrap().action,
+            Action::SetTerm("vt100".to_string())
+        );
+        assert!(parse("v set-term").is_err());
+        assert_eq!(
+            parse("r remain-on-exit off").unwrap().action,
+            Action::RemainOnExit(false)
+        );
+        assert!(parse("r remain-on-exit").is_err());
+        assert_eq!(
+            parse("L link-window work").unwrap().action,
+            Action::LinkWindow("work".to_string())
+        );
+        assert!(parse("L link-window").is_err());
+        assert_eq!(
+            parse("i record-input on").unwrap().action,
+            Action::RecordInput(true)
+        );
+        assert_eq!(
+            parse("I save-input typed.txt").unwrap().action,
+            Action::SaveInput("typed.txt".to_string())
+        );
+        assert_eq!(
+            parse("j join-pane 2 vertical").unwrap().action,
+            Action::JoinPane(2, Direction::Vertical)
+        );
+        assert!(parse("j join-pane 2 width").is_err());
+        assert_eq!(
+            parse("D run-shell df  -h /").unwrap().action,
+            Action::RunShell {
+                command: "df -h /".to_string(),
+                output: ShellOutput::Pager,
+            }
+        );
+        assert_eq!(
+            parse("D run-shell -w git log").unwrap().action,
+            Action::RunShell {
+                command: "git log".to_string(),
+                output: ShellOutput::Window,
+            }
+        );
+        assert!(parse("D run-shell").is_err());
+        assert!(parse("D run-shell -m").is_err());
+        assert_eq!(
+            parse("v view-file /etc/motd").unwrap().action,
+            Action::ViewFile("/etc/motd".to_string())
+        );
+        assert!(parse("v view-file").is_err());
+        assert_eq!(
+            parse("K kill-window").unwrap().action,
+            Action::KillWindow(true)
+        );
+        assert_eq!(
+            parse("K kill-window -y").unwrap().action,
+            Action::KillWindow(false)
+        );
+        assert!(parse("K kill-window now").is_err());
+        assert_eq!(
+            parse("K kill-session -y").unwrap().action,
+            Action::KillSession(false)
+        );
+        assert!(parse("K kill-session now").is_err());
+        assert_eq!(
+            parse("$ rename-session build farm").unwrap().action,
+            Action::RenameSession(Some("build farm".to_string()))
+        );
+        assert_eq!(
+            parse("F1 select-window 12").unwrap().action,
+            Action::SelectWindow(12)
+        );
+        assert!(parse("F1 select-window").is_err());
+        assert_eq!(
+            parse("< swap-window previous").unwrap().action,
+            Action::SwapWindow(WindowTarget::Previous)
+        );
+        assert_eq!(
+            parse("< swap-window 3").unwrap().action,
+            Action::SwapWindow(WindowTarget::Index(3))
+        );
+        assert!(parse("< swap-window first").is_err());
+        assert_eq!(
+            parse(". move-window 4").unwrap().action,
+            Action::MoveWindow(Some(4))
+        );
+        assert_eq!(
+            parse(". move-window").unwrap().action,
+            Action::MoveWindow(None)
+        );
+        assert!(parse(". move-window -1").is_err());
+        assert_eq!(
+            parse("R renumber-windows").unwrap().action,
+            Action::RenumberWindows
+        );
+        assert_eq!(
+            parse("r rename-window build logs").unwrap().action,
+            Action::RenameWindow(Some("build logs".to_string()))
+        );
+        assert_eq!(
+            parse("r rename-window").unwrap().action,
+            Action::RenameWindow(None)
+        );
+    }
+
+    #[test]
+    fn parse_key_tables() {
+        let parse = |s: &str| s.parse::<KeyBinding>();
+        assert_eq!(parse("x next-pane").unwrap().table, KeyTable::Prefix);
+        assert_eq!(
+            parse("-n F5 next-window").unwrap(),
+            KeyBinding {
+                table: KeyTable::Root,
+                key: Key::F(5),
+                action: Action::NextWindow,
+            }
+        );
+        assert_eq!(parse("-T root M-n next-window").unwrap().key, Key::Alt('n'));
+        assert_eq!(parse("-T prefix - next-pane").unwrap().key, Key::Char('-'));
+        assert!(parse("-T copy x next-pane").is_err());
+        assert!(parse("-T").is_err());
+        assert!(parse("-n").is_err());
+
+        assert_eq!(
+            parse("B bind-key -n PageUp list-keys").unwrap().action,
+            Action::BindKey(Box::new(KeyBinding {
+                table: KeyTable::Root,
+                key: Key::PageUp,
+                action: Action::ListKeys,
+            }))
+        );
+        assert!(parse("B bind-key").is_err());
+        assert_eq!(
+            parse("U unbind-key -n C-Space").unwrap().action,
+            Action::UnbindKey(KeyTable::Root, Key::Null)
+        );
+        assert!(parse("U unbind-key c now").is_err());
+    }
+
+    #[test]
+    fn actions_help() {
+        for (idx, help) in ACTIONS.iter().enumerate() {
+            assert!(ACTIONS[..idx].iter().all(|other| other.name != help.name));
+            match help.name.parse::<Action>() {
+                Ok(action) => {
+                    assert!(!help.needs_args(), "{}", help.name);
+                    assert!(action.to_string().starts_with(help.name));
+                }
+                Err(e) => {
+                    assert!(help.needs_args(), "{}: {}", help.name, e);
+                    assert!(!e.to_string().starts_with("unknown"));
+                }
+            }
+        }
+        assert!(ACTIONS.iter().any(|help| help.name == "command-palette"));
+        for binding in DEFAULT_KEY_BINDINGS {
+            let name = binding.action.to_string();
+            let name = name.split(' ').next().unwrap();
+            assert!(ACTIONS.iter().any(|help| help.name == name), "{}", name);
+        }
+    }
+
+    #[test]
+    fn display_round_trip() {
+        for binding in DEFAULT_KEY_BINDINGS {
+            assert_eq!(binding.to_string().parse::<KeyBinding>().unwrap(), binding);
+        }
+        for s in [
+            "-n BTab new-window -n logs tail -f log",
+            "Enter rename-window build logs",
+            "O show-options -w",
+            "e set-environment EDITOR vim -u NONE",
+            "E set-environment -r EDITOR",
+            "C-e new-window -c src -e RUST_LOG=debug -e A= cargo run",
+            "F5 source-file /etc/sm.conf",
+            "BSpace swap-window next",
+            "Home resize-pane height -5",
+            "Tab join-pane 2 vertical",
+            "Delete kill-window -y",
+            "Escape remain-on-exit off",
+            "End record-input on",
+            "C-x bind-key -n F12 run-shell make test",
+            "M-g run-shell -m git branch --show-current",
+            "i display-message #{window_index}: #{pane_title}",
+            "M-u unbind-key -n Insert",
+            "PageDown list-keys",
+            "-n S-Up select-window 2",
+            "p paste-buffer -b notes",
+            "P set-buffer -b notes make -j 4",
+            "D delete-buffer",
+            "M-d detach-client -a",
+            "T if-shell \"test -n \\\"$DISPLAY\\\"\" \"run-shell xclip -o\" next-pane",
+        ] {
+            assert_eq!(s.parse::<KeyBinding>().unwrap().to_string(), s);
+        }
+        assert_eq!(
+            "-T prefix x next-pane"
+                .parse::<KeyBinding>()
+                .unwrap()
+                .to_string(),
+            "x next-pane"
+        );
+    }
+
+    #[test]
+    fn key_bindings() {
+        let mut bindings = KeyBindings::default();
+        let c = Key::Char('c');
+        assert!(matches!(
+            bindings.get(KeyTable::Prefix, c),
+            Some(Action::NewWindow { .. })
+        ));
+        assert_eq!(bindings.get(KeyTable::Root, c), None);
+        let count = bindings.iter().count();
+
+        bindings.bind("c next-window".parse().unwrap());
+        bindings.bind("-n c list-keys".parse().unwrap());
+        assert_eq!(bindings.get(KeyTable::Prefix, c), Some(&Action::NextWindow));
+        assert_eq!(bindings.get(KeyTable::Root, c), Some(&Action::ListKeys));
+        assert_eq!(bindings.iter().count(), count + 1);
+
+        assert!(bindings.unbind(KeyTable::Prefix, c));
+        assert!(!bindings.unbind(KeyTable::Prefix, c));
+        assert_eq!(bindings.get(KeyTable::Prefix, c), None);
+        assert_eq!(bindings.get(KeyTable::Root, c), Some(&Action::ListKeys));
+    }
+}
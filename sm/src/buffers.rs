@@ -0,0 +1,462 @@
+//! The paste buffers: the text copied from the pager or set by `set-buffer`,
+//! newest first, and the overlay listing them to paste, delete or save one.
+//!
+//! Buffers are named `buffer0`, `buffer1` and so on as they are added, unless
+//! `set-buffer -b` names them.
+
+use std::{
+    collections::VecDeque,
+    io::{self, Write},
+    time::{Duration, Instant},
+};
+
+use nix::pty::Winsize;
+
+use session_manager::width;
+
+use crate::{backend::Key, summary};
+
+/// How many buffers are kept: copying more drops the oldest.
+pub const LIMIT: usize = 50;
+
+/// Text copied, with where and when.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PasteBuffer {
+    pub name: String,
+    pub text: String,
+    /// The window selected when it was copied, e.g. `0:sh`.
+    pub source: String,
+    pub copied: Instant,
+}
+
+impl PasteBuffer {
+    /// The text on one line: runs of whitespace as a space, without controls.
+    pub fn preview(&self) -> String {
+        self.text
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+            .replace(|c: char| c.is_control(), "")
+    }
+}
+
+/// The buffers of a session, newest first.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct PasteBuffers {
+    buffers: VecDeque<PasteBuffer>,
+    /// The number in the next automatic name.
+    next: usize,
+}
+
+impl PasteBuffers {
+    /// Add `text`, copied from the window `source`, as the newest buffer, with
+    /// the next automatic name.
+    pub fn push(&mut self, text: String, source: String) {
+        let name = format!("buffer{}", self.next);
+        self.next += 1;
+        self.set(name, text, source);
+    }
+
+    /// Add `text`, copied from the window `source`, as the newest buffer,
+    /// named `name`, replacing any buffer of that name.
+    pub fn set(&mut self, name: String, text: String, source: String) {
+        if let Some(position) = self.position(&name) {
+            self.buffers.remove(position);
+        }
+        self.buffers.push_front(PasteBuffer {
+            name,
+            text,
+            source,
+            copied: Instant::now(),
+        });
+        self.buffers.truncate(LIMIT);
+    }
+
+    /// The buffer at `position`, counting from the newest.
+    pub fn get(&self, position: usize) -> Option<&PasteBuffer> {
+        self.buffers.get(position)
+    }
+
+    /// The position of the buffer named `name`, counting from the newest.
+    pub fn position(&self, name: &str) -> Option<usize> {
+        self.buffers.iter().position(|buffer| buffer.name == name)
+    }
+
+    /// Remove the buffer at `position`, counting from the newest.
+    pub fn remove(&mut self, position: usize) -> Option<PasteBuffer> {
+        self.buffers.remove(position)
+    }
+
+    /// The buffers, newest first.
+    pub fn iter(&self) -> impl Iterator<Item = &PasteBuffer> {
+        self.buffers.iter()
+    }
+}
+
+/// What the buffer list made of a key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BufferInput {
+    /// The list stays open.
+    Pending,
+    /// The list was dismissed.
+    Close,
+    /// The buffer at this position should be pasted, closing the list.
+    Paste(usize),
+    /// The buffer at this position should be deleted.
+    Delete(usize),
+    /// The buffer at this position should be written to the file at this path.
+    Save(usize, String),
+}
+
+/// What typing in the list does.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Mode {
+    /// Keys move the highlight and act on the buffer highlighted.
+    Browse,
+    /// Typing edits the search.
+    Search,
+    /// Typing edits the path to save the highlighted buffer to.
+    Save(String),
+}
+
+/// A line for each buffer: its size, source, age and text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct BufferEntry {
+    text: String,
+    line: String,
+}
+
+/// The buffers, one a line, with the highlighted one in reverse video, and a
+/// line at the bottom for the search, or the path to save to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BufferList {
+    entries: Vec<BufferEntry>,
+    /// Only the buffers containing this are listed, ignoring case.
+    search: String,
+    mode: Mode,
+    /// The position in the buffers listed of the one highlighted.
+    highlighted: usize,
+    /// The position in the buffers listed of the first on screen.
+    top: usize,
+    /// Whether the list has to be drawn again.
+    dirty: bool,
+}
+
+impl BufferList {
+    /// List `buffers`, with their ages at `now`.
+    pub fn new(buffers: &PasteBuffers, now: Instant) -> BufferList {
+        let entries = buffers
+            .iter()
+            .map(|buffer| {
+                let age = now.saturating_duration_since(buffer.copied);
+                BufferEntry {
+                    text: buffer.text.clone(),
+                    line: line(buffer, age),
+                }
+            })
+            .collect();
+        BufferList {
+            entries,
+            search: String::new(),
+            mode: Mode::Browse,
+            highlighted: 0,
+            top: 0,
+            dirty: true,
+        }
+    }
+
+    /// Move with the arrow keys, `j` and `k`, Home and End; paste with Enter
+    /// or `p`, delete with `d`, and save with `s`, typing the path and Enter.
+    /// `/` starts a search, Enter ending it and Escape clearing it. Escape or
+    /// `q` dismisses the list.
+    pub fn handle_key(&mut self, key: Key) -> BufferInput {
+        match key {
+            Key::Up => return self.highlight(self.highlighted.checked_sub(1)),
+            Key::Down => return self.highlight(Some(self.highlighted + 1)),
+            _ => (),
+        }
+        let highlighted = self.listed().nth(self.highlighted);
+        match &mut self.mode {
+            Mode::Browse => match key {
+                Key::Char('k') => {
+                    return self.highlight(self.highlighted.checked_sub(1))
+                }
+                Key::Char('j') => return self.highlight(Some(self.highlighted + 1)),
+                Key::Home | Key::Char('g') => return self.highlight(Some(0)),
+                Key::End | Key::Char('G') => {
+                    return self.highlight(self.listed().count().checked_sub(1))
+                }
+                Key::Char('\n') | Key::Char('\r') | Key::Char('p') => {
+                    if let Some(position) = highlighted {
+                        return BufferInput::Paste(position);
+                    }
+                }
+                Key::Char('d') => {
+                    if let Some(position) = highlighted {
+                        return BufferInput::Delete(position);
+                    }
+                }
+                Key::Char('s') if highlighted.is_some() => {
+                    self.mode = Mode::Save(String::new())
+                }
+                Key::Char('/') => self.mode = Mode::Search,
+                Key::Esc | Key::Char('q') => return BufferInput::Close,
+                _ => return BufferInput::Pending,
+            },
+            Mode::Search => match key {
+                Key::Char('\n') | Key::Char('\r') => self.mode = Mode::Browse,
+                Key::Esc => {
+                    self.search.clear();
+                    self.mode = Mode::Browse;
+                }
+                Key::Backspace => {
+                    self.search.pop();
+                }
+                Key::Char(c) if !c.is_control() => self.search.push(c),
+                _ => return BufferInput::Pending,
+            },
+            Mode::Save(path) => match key {
+                Key::Char('\n') | Key::Char('\r') => {
+                    let path = path.clone();
+                    self.mode = Mode::Browse;
+                    self.dirty = true;
+                    if let (Some(position), false) = (highlighted, path.is_empty()) {
+                        return BufferInput::Save(position, path);
+                    }
+                }
+                Key::Esc => self.mode = Mode::Browse,
+                Key::Backspace => {
+                    path.pop();
+                }
+                Key::Char(c) if !c.is_control() => path.push(c),
+                _ => return BufferInput::Pending,
+            },
+        }
+        // The search may have left fewer buffers listed.
+        let listed = self.listed().count();
+        self.highlighted = self.highlighted.min(listed.saturating_sub(1));
+        self.dirty = true;
+        BufferInput::Pending
+    }
+
+    /// Take the buffer at `position` out of the list, once it is deleted.
+    pub fn remove(&mut self, position: usize) {
+        if position < self.entries.len() {
+            self.entries.remove(position);
+            let listed = self.listed().count();
+            self.highlighted = self.highlighted.min(listed.saturating_sub(1));
+            self.dirty = true;
+        }
+    }
+
+    /// Draw the list over `area` if it has changed since it was last drawn,
+    /// scrolling to keep the highlighted buffer in view.
+    pub fn draw<T: Write>(&mut self, output: &mut T, area: Winsize) -> io::Result<()> {
+        let rows = usize::from(area.ws_row.saturating_sub(1)).max(1);
+        let top = if self.highlighted < self.top {
+            self.highlighted
+        } else {
+            self.top.max((self.highlighted + 1).saturating_sub(rows))
+        };
+        if !self.dirty && top == self.top {
+            return Ok(());
+        }
+        self.top = top;
+        let cols = area.ws_col as usize;
+        let listed: Vec<_> = self.listed().skip(self.top).take(rows).collect();
+        for row in 0..rows {
+            let (style, line) = match listed.get(row) {
+                Some(&position) => {
+                    let highlighted = self.top + row == self.highlighted;
+                    let style = if highlighted { "7" } else { "0" };
+                    (style, self.entries[position].line.as_str())
+                }
+                None if row == 0 && self.entries.is_empty() => ("0", "(no buffers)"),
+                None if row == 0 => ("0", "(no buffers match)"),
+                None => ("0", ""),
+            };
+            write!(
+                output,
+                "\x1b[{};1H\x1b[0;{}m{}",
+                row + 1,
+                style,
+                width::fit(line, cols)
+            )?;
+        }
+        let bottom = match &self.mode {
+            Mode::Browse if self.search.is_empty() => {
+                " Enter: paste  d: delete  s: save  /: search  q: close".to_string()
+            }
+            Mode::Browse => format!(" /{}  Esc: clear", self.search),
+            Mode::Search => format!("/{}", self.search),
+            Mode::Save(path) => format!("save to: {}", path),
+        };
+        write!(
+            output,
+            "\x1b[{};1H\x1b[0;7m{}\x1b[0m",
+            rows + 1,
+            width::fit(&bottom, cols)
+        )?;
+        self.dirty = false;
+        Ok(())
+    }
+
+    /// Draw the list again on the next `draw`.
+    pub fn mark_dirty(&mut self) {
+        self.dirty = true;
+    }
+
+    /// The positions of the buffers matching the search.
+    fn listed(&self) -> impl Iterator<Item = usize> + '_ {
+        let search = self.search.to_lowercase();
+        self.entries
+            .iter()
+            .enumerate()
+            .filter(move |(_, entry)| entry.text.to_lowercase().contains(&search))
+            .map(|(position, _)| position)
+    }
+
+    fn highlight(&mut self, listed: Option<usize>) -> BufferInput {
+        match listed {
+            Some(listed) if listed < self.listed().count() => {
+                self.highlighted = listed;
+                self.dirty = true;
+            }
+            _ => (),
+        }
+        BufferInput::Pending
+    }
+}
+
+/// The line listing `buffer`, `age` old, e.g.
+/// `buffer3: 12 bytes from 1:vim, 5s ago: "hello world"`.
+fn line(buffer: &PasteBuffer, age: Duration) -> String {
+    format!(
+        "{}: {} bytes from {}, {} ago: \"{}\"",
+        buffer.name,
+        buffer.text.len(),
+        buffer.source,
+        summary::format_duration(age),
+        buffer.preview()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AREA: Winsize = Winsize {
+        ws_row: 3,
+        ws_col: 60,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+
+    fn buffers() -> PasteBuffers {
+        let mut buffers = PasteBuffers::default();
+        buffers.push("make\ttest\n".to_string(), "0:sh".to_string());
+        buffers.push("Hello, world".to_string(), "1:vim".to_string());
+        buffers.push("hello again".to_string(), "1:vim".to_string());
+        buffers
+    }
+
+    fn draw(list: &mut BufferList) -> String {
+        let mut out = Vec::new();
+        list.draw(&mut out, AREA).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn limit() {
+        let mut buffers = PasteBuffers::default();
+        for n in 0..=LIMIT {
+            buffers.push(n.to_string(), "0:sh".to_string());
+        }
+        assert_eq!(buffers.iter().count(), LIMIT);
+        assert_eq!(buffers.get(0).unwrap().text, LIMIT.to_string());
+        assert_eq!(buffers.remove(LIMIT - 1).unwrap().text, "1");
+    }
+
+    #[test]
+    fn names() {
+        let mut buffers = buffers();
+        assert_eq!(buffers.get(0).unwrap().name, "buffer2");
+        assert_eq!(buffers.position("buffer0"), Some(2));
+        buffers.set("notes".to_string(), "a".to_string(), "-".to_string());
+        buffers.set("buffer0".to_string(), "b".to_string(), "-".to_string());
+        let names: Vec<_> = buffers.iter().map(|buffer| &buffer.name).collect();
+        assert_eq!(names, ["buffer0", "notes", "buffer2", "buffer1"]);
+        assert_eq!(buffers.get(0).unwrap().text, "b");
+        buffers.push("c".to_string(), "-".to_string());
+        assert_eq!(buffers.get(0).unwrap().name, "buffer3");
+        assert_eq!(buffers.position("missing"), None);
+    }
+
+    #[test]
+    fn keys() {
+        let buffers = buffers();
+        let now = buffers.get(2).unwrap().copied + Duration::from_secs(65);
+        let mut list = BufferList::new(&buffers, now);
+        assert_eq!(
+            list.entries[2].line,
+            "buffer0: 10 bytes from 0:sh, 1m 05s ago: \"make test\""
+        );
+        assert_eq!(list.handle_key(Key::Char('\n')), BufferInput::Paste(0));
+        list.handle_key(Key::Char('G'));
+        assert_eq!(list.handle_key(Key::Char('d')), BufferInput::Delete(2));
+        list.remove(2);
+        assert_eq!(list.handle_key(Key::Char('p')), BufferInput::Paste(1));
+
+        // Searching lists only the buffers matching, ignoring case.
+        for key in "/HELLO A".chars() {
+            assert_eq!(list.handle_key(Key::Char(key)), BufferInput::Pending);
+        }
+        assert_eq!(list.listed().collect::<Vec<_>>(), [0]);
+        list.handle_key(Key::Char('\n'));
+        assert_eq!(list.handle_key(Key::Char('p')), BufferInput::Paste(0));
+        list.handle_key(Key::Char('/'));
+        list.handle_key(Key::Esc);
+        assert_eq!(list.listed().count(), 2);
+
+        // Saving asks for the path.
+        list.handle_key(Key::Down);
+        for key in "sout.txt".chars() {
+            list.handle_key(Key::Char(key));
+        }
+        assert!(draw(&mut list).contains("save to: out.txt"));
+        assert_eq!(
+            list.handle_key(Key::Char('\n')),
+            BufferInput::Save(1, "out.txt".to_string())
+        );
+        assert_eq!(list.handle_key(Key::Char('q')), BufferInput::Close);
+    }
+
+    #[test]
+    fn draw_list() {
+        let buffers = buffers();
+        let now = buffers.get(0).unwrap().copied;
+        let mut list = BufferList::new(&buffers, now);
+        let out = draw(&mut list);
+        assert!(out.starts_with(
+            "\x1b[1;1H\x1b[0;7mbuffer2: 11 bytes from 1:vim, 0s ago: \"hello again\""
+        ));
+        assert!(out.contains("\x1b[2;1H\x1b[0;0mbuffer1: 12 bytes from 1:vim"));
+        assert!(out.contains("\x1b[3;1H\x1b[0;7m Enter: paste"));
+        assert_eq!(draw(&mut list), "");
+
+        // The list scrolls to show the highlighted buffer.
+        list.handle_key(Key::End);
+        let out = draw(&mut list);
+        assert!(out.starts_with("\x1b[1;1H\x1b[0;0mbuffer1: 12 bytes"));
+        assert!(out.contains("\x1b[2;1H\x1b[0;7mbuffer0: 10 bytes"));
+
+        for key in "/nothing".chars() {
+            list.handle_key(Key::Char(key));
+        }
+        let out = draw(&mut list);
+        assert!(out.contains("(no buffers match)"));
+        assert!(out.contains("\x1b[0;7m/nothing"));
+        assert_eq!(list.handle_key(Key::Char('\n')), BufferInput::Pending);
+        assert_eq!(list.handle_key(Key::Char('p')), BufferInput::Pending);
+    }
+}
@@ -0,0 +1,362 @@
+//! A list of the windows filling the screen, to pick one to switch to, or to
+//! put in another order.
+
+use std::io::{self, Write};
+
+use nix::pty::Winsize;
+
+use session_manager::{session::WindowFlags, width};
+
+use crate::backend::{Key, MouseButton, MouseEvent, MouseKind};
+
+/// What the chooser made of an input event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChooserInput {
+    /// The chooser stays open.
+    Pending,
+    /// The chooser was dismissed.
+    Close,
+    /// The window with this index, once the windows are reordered, was chosen,
+    /// closing the chooser.
+    Choose(usize),
+}
+
+/// A line describing a window.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChooserEntry {
+    pub idx: usize,
+    pub name: String,
+    /// Whether it is current or selected last, and what has happened in it.
+    pub flags: WindowFlags,
+    pub panes: usize,
+    pub size: Winsize,
+    /// The last line of text in the window's active pane.
+    pub preview: String,
+}
+
+impl ChooserEntry {
+    /// The line shown for the window, e.g. `1: vim* (2 panes, 80x23) "~"`, with
+    /// the flags after its name.
+    fn line(&self) -> String {
+        format!(
+            "{}: {}{} ({} pane{}, {}x{}) \"{}\"",
+            self.idx,
+            self.name,
+            self.flags.symbols(),
+            self.panes,
+            if self.panes == 1 { "" } else { "s" },
+            self.size.ws_col,
+            self.size.ws_row,
+            self.preview
+        )
+    }
+}
+
+/// The windows, one a line, with the highlighted one in reverse video.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chooser {
+    entries: Vec<ChooserEntry>,
+    /// The entry highlighted.
+    highlighted: usize,
+    /// The first entry on screen.
+    top: usize,
+    /// Whether the chooser has to be drawn again.
+    dirty: bool,
+    /// The windows swapped to reorder them, in order.
+    swaps: Vec<(usize, usize)>,
+    /// The entry dragged with the mouse, and whether it has moved.
+    dragged: Option<(usize, bool)>,
+}
+
+impl Chooser {
+    /// List `entries`, starting at the selected window.
+    pub fn new(entries: Vec<ChooserEntry>) -> Chooser {
+        let highlighted = entries.iter().position(|entry| entry.flags.current);
+        Chooser {
+            entries,
+            highlighted: highlighted.unwrap_or(0),
+            top: 0,
+            dirty: true,
+            swaps: Vec::new(),
+            dragged: None,
+        }
+    }
+
+    /// The swaps of window indices putting the windows in the order shown, to
+    /// make with `Session::swap_window` in turn.
+    pub fn swaps(&self) -> &[(usize, usize)] {
+        &self.swaps
+    }
+
+    /// Move with the arrow keys, `j` and `k`, Home and End, and choose with
+    /// Enter; a digit chooses the window with that index. Shift with Up or Down
+    /// moves the highlighted window up or down the list. Escape or `q`
+    /// dismisses the chooser.
+    pub fn handle_key(&mut self, key: Key) -> ChooserInput {
+        match key {
+            Key::ShiftUp => {
+                if let Some(above) = self.highlighted.checked_sub(1) {
+                    self.move_entry(self.highlighted, above);
+                }
+            }
+            Key::ShiftDown => self.move_entry(self.highlighted, self.highlighted + 1),
+            Key::Up | Key::Char('k') => self.highlight(self.highlighted.checked_sub(1)),
+            Key::Down | Key::Char('j') => self.highlight(Some(self.highlighted + 1)),
+            Key::Home | Key::Char('g') => self.highlight(Some(0)),
+            Key::End | Key::Char('G') => {
+                self.highlight(self.entries.len().checked_sub(1))
+            }
+            Key::Char('\n') | Key::Char('\r') => {
+                if let Some(entry) = self.entries.get(self.highlighted) {
+                    return ChooserInput::Choose(entry.idx);
+                }
+            }
+            Key::Char(c @ '0'..='9') => {
+                let idx = c.to_digit(10).unwrap() as usize;
+                if self.entries.iter().any(|entry| entry.idx == idx) {
+                    return ChooserInput::Choose(idx);
+                }
+            }
+            Key::Esc | Key::Char('q') => return ChooserInput::Close,
+            _ => (),
+        }
+        ChooserInput::Pending
+    }
+
+    /// Move with the wheel, and choose a window by clicking it; a window dragged
+    /// up or down the list is moved there.
+    pub fn handle_mouse(&mut self, event: MouseEvent) -> ChooserInput {
+        let position = self.top + event.row as usize;
+        match (event.button, event.kind) {
+            (MouseButton::WheelUp, _) => {
+                self.highlight(self.highlighted.checked_sub(1))
+            }
+            (MouseButton::WheelDown, _) => self.highlight(Some(self.highlighted + 1)),
+            (_, MouseKind::Press) => {
+                if position < self.entries.len() {
+                    self.highlight(Some(position));
+                    self.dragged = Some((position, false));
+                }
+            }
+            (_, MouseKind::Drag) => {
+                if let Some((mut from, _)) = self.dragged {
+                    // Past the end of the list is its last entry.
+                    let to = position.min(self.entries.len() - 1);
+                    while from != to {
+                        let next = if from < to { from + 1 } else { from - 1 };
+                        self.move_entry(from, next);
+                        from = next;
+                    }
+                    self.dragged = Some((to, true));
+                }
+            }
+            (_, MouseKind::Release) => match self.dragged.take() {
+                Some((from, false)) if from == position => {
+                    return ChooserInput::Choose(self.entries[from].idx);
+                }
+                _ => (),
+            },
+        }
+        ChooserInput::Pending
+    }
+
+    /// Draw the chooser over `area` if it has changed since it was last drawn,
+    /// scrolling to keep the highlighted entry in view.
+    pub fn draw<T: Write>(&mut self, output: &mut T, area: Winsize) -> io::Result<()> {
+        let rows = area.ws_row.max(1) as usize;
+        let top = if self.highlighted < self.top {
+            self.highlighted
+        } else {
+            self.top.max((self.highlighted + 1).saturating_sub(rows))
+        };
+        if !self.dirty && top == self.top {
+            return Ok(());
+        }
+        self.top = top;
+        let cols = area.ws_col as usize;
+        for row in 0..rows {
+            let idx = self.top + row;
+            let (style, line) = match self.entries.get(idx) {
+                Some(entry) if idx == self.highlighted => ("7", entry.line()),
+                Some(entry) => ("0", entry.line()),
+                None => ("0", String::new()),
+            };
+            write!(
+                output,
+                "\x1b[{};1H\x1b[0;{}m{}",
+                row + 1,
+                style,
+                width::fit(&line, cols)
+            )?;
+        }
+        write!(output, "\x1b[0m")?;
+        self.dirty = false;
+        Ok(())
+    }
+
+    /// Draw the chooser again on the next `draw`.
+    pub fn mark_dirty(&mut self) {
+        self.dirty = true;
+    }
+
+    /// Swap the entry at `from` with the one at `to`, next to it, keeping the
+    /// highlight on the entry moved. Each position keeps its window index.
+    fn move_entry(&mut self, from: usize, to: usize) {
+        if from.max(to) >= self.entries.len() {
+            return;
+        }
+        let (a, b) = (self.entries[from].idx, self.entries[to].idx);
+        self.entries.swap(from, to);
+        self.entries[from].idx = a;
+        self.entries[to].idx = b;
+        self.swaps.push((a, b));
+        if self.highlighted == from {
+            self.highlighted = to;
+        }
+        self.dirty = true;
+    }
+
+    fn highlight(&mut self, entry: Option<usize>) {
+        match entry {
+            Some(entry) if entry < self.entries.len() => {
+                self.highlighted = entry;
+                self.dirty = true;
+            }
+            _ => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AREA: Winsize = Winsize {
+        ws_row: 2,
+        ws_col: 32,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+
+    fn entries() -> Vec<ChooserEntry> {
+        [
+            (0, "sh", 1, "$ make"),
+            (1, "vim", 2, "~"),
+            (3, "logs", 1, ""),
+        ]
+        .iter()
+        .map(|(idx, name, panes, preview)| ChooserEntry {
+            idx: *idx,
+            name: name.to_string(),
+            flags: WindowFlags {
+                current: *idx == 1,
+                activity: *idx == 3,
+                ..WindowFlags::default()
+            },
+            panes: *panes,
+            size: Winsize {
+                ws_row: 23,
+                ws_col: 80,
+                ws_xpixel: 0,
+                ws_ypixel: 0,
+            },
+            preview: preview.to_string(),
+        })
+        .collect()
+    }
+
+    #[test]
+    fn keys() {
+        let mut chooser = Chooser::new(entries());
+        assert_eq!(chooser.handle_key(Key::Char('\n')), ChooserInput::Choose(1));
+        chooser.handle_key(Key::Down);
+        chooser.handle_key(Key::Char('j'));
+        assert_eq!(chooser.handle_key(Key::Char('\n')), ChooserInput::Choose(3));
+        chooser.handle_key(Key::Home);
+        chooser.handle_key(Key::Up);
+        assert_eq!(chooser.handle_key(Key::Char('\n')), ChooserInput::Choose(0));
+        assert_eq!(chooser.handle_key(Key::Char('3')), ChooserInput::Choose(3));
+        assert_eq!(chooser.handle_key(Key::Char('2')), ChooserInput::Pending);
+        assert_eq!(chooser.handle_key(Key::Esc), ChooserInput::Close);
+    }
+
+    #[test]
+    fn draw() {
+        let mut chooser = Chooser::new(entries());
+        let mut out = Vec::new();
+        chooser.draw(&mut out, AREA).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "\x1b[1;1H\x1b[0;0m0: sh (1 pane, 80x23) \"$ make\"  \
+             \x1b[2;1H\x1b[0;7m1: vim* (2 panes, 80x23) \"~\"    \x1b[0m"
+        );
+
+        let mut out = Vec::new();
+        chooser.draw(&mut out, AREA).unwrap();
+        assert!(out.is_empty());
+
+        // The list scrolls to show the highlighted entry.
+        chooser.handle_key(Key::End);
+        chooser.draw(&mut out, AREA).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.starts_with("\x1b[1;1H\x1b[0;0m1: vim*"));
+        assert!(out.contains("\x1b[2;1H\x1b[0;7m3: logs# (1 pane"));
+        assert_eq!(
+            chooser.handle_mouse(mouse(MouseKind::Press, 0)),
+            ChooserInput::Pending
+        );
+        assert_eq!(
+            chooser.handle_mouse(mouse(MouseKind::Release, 0)),
+            ChooserInput::Choose(1)
+        );
+    }
+
+    fn mouse(kind: MouseKind, row: u16) -> MouseEvent {
+        MouseEvent {
+            kind,
+            button: MouseButton::Left,
+            modifiers: Default::default(),
+            col: 4,
+            row,
+        }
+    }
+
+    /// The names of the windows listed, with their indices.
+    fn order(chooser: &Chooser) -> Vec<(usize, &str)> {
+        let entries = chooser.entries.iter();
+        entries
+            .map(|entry| (entry.idx, entry.name.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn reorder() {
+        let mut chooser = Chooser::new(entries());
+        chooser.handle_key(Key::ShiftDown);
+        assert_eq!(order(&chooser), [(0, "sh"), (1, "logs"), (3, "vim")]);
+        // Windows cannot move past the ends of the list.
+        chooser.handle_key(Key::ShiftDown);
+        chooser.handle_key(Key::Home);
+        chooser.handle_key(Key::ShiftUp);
+        assert_eq!(order(&chooser), [(0, "sh"), (1, "logs"), (3, "vim")]);
+        assert_eq!(chooser.swaps(), [(1, 3)]);
+        // The window moved keeps the highlight, and is chosen by its new index.
+        chooser.handle_key(Key::End);
+        chooser.handle_key(Key::ShiftUp);
+        assert_eq!(chooser.handle_key(Key::Char('\n')), ChooserInput::Choose(1));
+
+        // A window dragged with the mouse moves with it, even past the end of the
+        // list, and is not chosen when it is let go.
+        let mut chooser = Chooser::new(entries());
+        chooser.handle_mouse(mouse(MouseKind::Press, 0));
+        chooser.handle_mouse(mouse(MouseKind::Drag, 1));
+        chooser.handle_mouse(mouse(MouseKind::Drag, 5));
+        assert_eq!(order(&chooser), [(0, "vim"), (1, "logs"), (3, "sh")]);
+        assert_eq!(
+            chooser.handle_mouse(mouse(MouseKind::Release, 5)),
+            ChooserInput::Pending
+        );
+        assert_eq!(chooser.swaps(), [(0, 1), (1, 3)]);
+        assert_eq!(chooser.handle_key(Key::Char('\n')), ChooserInput::Choose(3));
+    }
+}
@@ -0,0 +1,521 @@
+//! The client: the terminal attached to a session running in the server.
+
+use std::{
+    io::{self, Write},
+    os::unix::net::UnixStream,
+    path::Path,
+    str::FromStr,
+    thread,
+};
+
+use anyhow::{bail, Context, Error, Result};
+use futures::{
+    channel::mpsc::{self, Receiver},
+    executor, select,
+    sink::SinkExt,
+    stream::StreamExt,
+};
+use serde::Serialize;
+use session_manager::export;
+use signal_hook::{iterator::Signals, SIGWINCH};
+
+use crate::{
+    backend::{DefaultBackend, TerminalBackend},
+    protocol::{
+        self, BufferInfo, ClientInfo, ClientMessage, PaneInfo, ServerMessage,
+        SessionInfo, WindowInfo,
+    },
+};
+
+/// Puts back the modes a program in the session may have left the terminal in:
+/// the cursor's shape and visibility, the attributes of text, bracketed paste,
+/// mouse reporting, application cursor keys and keypad, and the screen inverted
+/// by a visual bell.
+const RESET_MODES: &str = "\x1b[0 q\x1b[?12l\x1b[?25h\x1b[0m\x1b[?2004l\x1b[?5l\
+                           \x1b[?1000l\x1b[?1002l\x1b[?1003l\x1b[?1006l\x1b[?1015l\
+                           \x1b[?1l\x1b>";
+
+/// Save the terminal's title, for the session to set its own, and put it back;
+/// terminals which do not keep titles ignore these.
+const PUSH_TITLE: &str = "\x1b[22;2t";
+const POP_TITLE: &str = "\x1b[23;2t";
+
+/// Switch to the alternate screen, for the session to draw on, and back to
+/// what was on the terminal before attaching.
+const ENTER_ALTERNATE_SCREEN: &str = "\x1b[?1049h";
+const LEAVE_ALTERNATE_SCREEN: &str = "\x1b[?1049l";
+
+/// How `sm ls` and the other `list-` commands print what they list.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// A line each, to read.
+    #[default]
+    Text,
+    /// A JSON array of objects, one each, for scripts.
+    Json,
+}
+
+impl FromStr for OutputFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<OutputFormat> {
+        match s {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            _ => bail!("expected text or json, not {:?}", s),
+        }
+    }
+}
+
+/// Say hello to the server at the other end of `stream`, and make `request`.
+fn connect(stream: &mut UnixStream, request: &ClientMessage) -> Result<()> {
+    protocol::send(stream, &ClientMessage::Hello(protocol::VERSION))?;
+    protocol::send(stream, request)?;
+    Ok(())
+}
+
+/// Attach the terminal to the session `request` asks the server at the other
+/// end of `stream` for, until the server detaches the terminal or exits. The
+/// terminal draws East Asian Ambiguous characters two columns wide if
+/// `ambiguous_wide`.
+pub fn attach(
+    stream: UnixStream,
+    request: ClientMessage,
+    ambiguous_wide: bool,
+) -> Result<()> {
+    let mut stream = stream;
+    connect(&mut stream, &request)?;
+    match protocol::receive(&mut stream)? {
+        Some(ServerMessage::Attached) => (),
+        Some(ServerMessage::Error(message)) | Some(ServerMessage::Exit(message)) => {
+            bail!(message)
+        }
+        _ => bail!("lost server"),
+    }
+
+    let mut backend = DefaultBackend::new();
+    let mut output = backend.raw_output()?;
+    write!(output, "{}{}", PUSH_TITLE, ENTER_ALTERNATE_SCREEN)?;
+    let mut input = backend.input()?;
+    let mut sigwinch = sigwinch_stream();
+    let mut messages = server_messages(stream.try_clone()?);
+    protocol::send(&mut stream, &ClientMessage::AmbiguousWidth(ambiguous_wide))?;
+    protocol::send(&mut stream, &ClientMessage::Resize(backend.size()?))?;
+
+    let message: io::Result<String> = executor::block_on(async {
+        loop {
+            select! {
+                event = input.next() => match event {
+                    Some((event, data)) => {
+                        let message = ClientMessage::Input(event, data);
+                        if protocol::send(&mut stream, &message).is_err() {
+                            return Ok("lost server".to_string());
+                        }
+                    }
+                    None => return Ok("lost terminal".to_string()),
+                },
+                _ = sigwinch.next() => {
+                    let message = ClientMessage::Resize(backend.size()?);
+                    if protocol::send(&mut stream, &message).is_err() {
+                        return Ok("lost server".to_string());
+                    }
+                },
+                message = messages.next() => match message {
+                    Some(ServerMessage::Output(data)) => {
+                        output.write_all(&data)?;
+                        output.flush()?;
+                    }
+                    Some(ServerMessage::Exit(message)) => return Ok(message),
+                    // Answers to requests, which are not made once attached.
+                    Some(_) => (),
+                    None => return Ok("lost server".to_string()),
+                },
+            }
+        }
+    });
+    let message = message?;
+
+    // The server may not have left the terminal as it found it.
+    backend.set_mouse_capture(&mut output, false)?;
+    write!(output, "{}", RESET_MODES)?;
+    backend.clear(&mut output)?;
+    write!(output, "{}{}", LEAVE_ALTERNATE_SCREEN, POP_TITLE)?;
+    output.flush()?;
+    drop(output);
+    println!("[{}]", message);
+    Ok(())
+}
+
+/// Print the sessions of the server at the other end of `stream`, one a line.
+pub fn list_sessions(stream: UnixStream, format: OutputFormat) -> Result<()> {
+    let mut stream = stream;
+    connect(&mut stream, &ClientMessage::ListSessions)?;
+    match protocol::receive(&mut stream)? {
+        Some(ServerMessage::Sessions(sessions)) => {
+            print_list(&sessions, format, format_session)
+        }
+        Some(ServerMessage::Error(message)) | Some(ServerMessage::Exit(message)) => {
+            bail!(message)
+        }
+        _ => bail!("lost server"),
+    }
+}
+
+/// Print the windows of the session named `name`, or of the one attached to
+/// last, from the server at the other end of `stream`.
+pub fn list_windows(
+    stream: UnixStream,
+    name: Option<String>,
+    format: OutputFormat,
+) -> Result<()> {
+    let mut stream = stream;
+    connect(&mut stream, &ClientMessage::ListWindows(name))?;
+    match protocol::receive(&mut stream)? {
+        Some(ServerMessage::Windows(windows)) => {
+            print_list(&windows, format, format_window)
+        }
+        Some(ServerMessage::Error(message)) | Some(ServerMessage::Exit(message)) => {
+            bail!(message)
+        }
+        _ => bail!("lost server"),
+    }
+}
+
+/// Print the panes of each window of the session named `name`, or of the one
+/// attached to last, from the server at the other end of `stream`.
+pub fn list_panes(
+    stream: UnixStream,
+    name: Option<String>,
+    format: OutputFormat,
+) -> Result<()> {
+    let mut stream = stream;
+    connect(&mut stream, &ClientMessage::ListPanes(name))?;
+    match protocol::receive(&mut stream)? {
+        Some(ServerMessage::Panes(panes)) => print_list(&panes, format, format_pane),
+        Some(ServerMessage::Error(message)) | Some(ServerMessage::Exit(message)) => {
+            bail!(message)
+        }
+        _ => bail!("lost server"),
+    }
+}
+
+/// Print the clients attached to the server at the other end of `stream`.
+pub fn list_clients(stream: UnixStream, format: OutputFormat) -> Result<()> {
+    let mut stream = stream;
+    connect(&mut stream, &ClientMessage::ListClients)?;
+    match protocol::receive(&mut stream)? {
+        Some(ServerMessage::Clients(clients)) => {
+            print_list(&clients, format, format_client)
+        }
+        Some(ServerMessage::Error(message)) | Some(ServerMessage::Exit(message)) => {
+            bail!(message)
+        }
+        _ => bail!("lost server"),
+    }
+}
+
+/// Print the paste buffers of the session named `name`, or of the one attached
+/// to last, from the server at the other end of `stream`.
+pub fn list_buffers(
+    stream: UnixStream,
+    name: Option<String>,
+    format: OutputFormat,
+) -> Result<()> {
+    let mut stream = stream;
+    connect(&mut stream, &ClientMessage::ListBuffers(name))?;
+    match protocol::receive(&mut stream)? {
+        Some(ServerMessage::Buffers(buffers)) => {
+            print_list(&buffers, format, format_buffer)
+        }
+        Some(ServerMessage::Error(message)) | Some(ServerMessage::Exit(message)) => {
+            bail!(message)
+        }
+        _ => bail!("lost server"),
+    }
+}
+
+/// Print what the server at the other end of `stream` says of its sessions
+/// and client.
+pub fn info(stream: UnixStream) -> Result<()> {
+    print_text(stream, &ClientMessage::Info)
+}
+
+/// Have the server at the other end of `stream` expand `format` for the
+/// session named `name`, or the one attached to last, and print it if `print`,
+/// or show it on the status line of the clients attached.
+pub fn display_message(
+    stream: UnixStream,
+    name: Option<String>,
+    format: String,
+    print: bool,
+) -> Result<()> {
+    let request = ClientMessage::DisplayMessage {
+        name,
+        format,
+        print,
+    };
+    print_text(stream, &request)
+}
+
+/// Have the server at the other end of `stream` call the session named `name`,
+/// or the one attached to last, `new_name`.
+pub fn rename_session(
+    stream: UnixStream,
+    name: Option<String>,
+    new_name: String,
+) -> Result<()> {
+    print_text(stream, &ClientMessage::RenameSession { name, new_name })
+}
+
+/// Have the server at the other end of `stream` end the session named `name`,
+/// or the one attached to last, closing its windows and detaching its clients.
+pub fn kill_session(stream: UnixStream, name: Option<String>) -> Result<()> {
+    print_text(stream, &ClientMessage::KillSession(name))
+}
+
+/// Have the server at the other end of `stream` move the windows of the session
+/// named `source` into the one named `target`, or the one attached to last, and
+/// end `source`.
+pub fn merge_session(
+    stream: UnixStream,
+    source: String,
+    target: Option<String>,
+) -> Result<()> {
+    print_text(stream, &ClientMessage::MergeSession { source, target })
+}
+
+/// Print the text the server at the other end of `stream` answers `request`
+/// with.
+fn print_text(stream: UnixStream, request: &ClientMessage) -> Result<()> {
+    let mut stream = stream;
+    connect(&mut stream, request)?;
+    match protocol::receive(&mut stream)? {
+        Some(ServerMessage::Info(text)) => {
+            print!("{}", text);
+            Ok(())
+        }
+        Some(ServerMessage::Error(message)) | Some(ServerMessage::Exit(message)) => {
+            bail!(message)
+        }
+        _ => bail!("lost server"),
+    }
+}
+
+/// The size and contents of the active pane of the session named `name`, or of
+/// the one attached to last, from the server at the other end of `stream`.
+#[cfg(feature = "screenshot")]
+pub fn capture_pane(
+    stream: UnixStream,
+    name: Option<String>,
+) -> Result<(u16, u16, Vec<u8>)> {
+    let mut stream = stream;
+    connect(&mut stream, &ClientMessage::CapturePane(name))?;
+    match protocol::receive(&mut stream)? {
+        Some(ServerMessage::Pane {
+            cols,
+            rows,
+            contents,
+        }) => Ok((cols, rows, contents)),
+        Some(ServerMessage::Error(message)) | Some(ServerMessage::Exit(message)) => {
+            bail!(message)
+        }
+        _ => bail!("lost server"),
+    }
+}
+
+/// Write a record of the session named `name`, or of the one attached to last,
+/// from the server at the other end of `stream`, to a new directory at `path`.
+pub fn export_session(
+    stream: UnixStream,
+    name: Option<String>,
+    path: &Path,
+) -> Result<()> {
+    let mut stream = stream;
+    connect(&mut stream, &ClientMessage::ExportSession(name))?;
+    match protocol::receive(&mut stream)? {
+        Some(ServerMessage::Export(session)) => {
+            export::write(path, &session).with_context(|| path.display().to_string())
+        }
+        Some(ServerMessage::Error(message)) | Some(ServerMessage::Exit(message)) => {
+            bail!(message)
+        }
+        _ => bail!("lost server"),
+    }
+}
+
+/// Print `items` in `format`, as lines written by `line` or as JSON.
+fn print_list<T: Serialize>(
+    items: &[T],
+    format: OutputFormat,
+    line: fn(&T) -> String,
+) -> Result<()> {
+    match format {
+        OutputFormat::Text => {
+            for item in items {
+                println!("{}", line(item));
+            }
+        }
+        OutputFormat::Json => println!("{}", serde_json::to_string(items)?),
+    }
+    Ok(())
+}
+
+/// A line of `sm ls`.
+fn format_session(session: &SessionInfo) -> String {
+    let mut line = format!(
+        "{}: {} window{}",
+        session.name,
+        session.windows,
+        if session.windows == 1 { "" } else { "s" }
+    );
+    if let Some(group) = &session.group {
+        line.push_str(&format!(" (group {})", group));
+    }
+    if session.attached {
+        line.push_str(" (attached)");
+    }
+    line
+}
+
+/// A line of `sm list-windows`.
+fn format_window(window: &WindowInfo) -> String {
+    let mut line = format!(
+        "{}: {} ({} pane{})",
+        window.idx,
+        window.name,
+        window.panes,
+        if window.panes == 1 { "" } else { "s" }
+    );
+    if window.active {
+        line.push_str(" (active)");
+    }
+    line
+}
+
+/// A line of `sm list-panes`.
+fn format_pane(pane: &PaneInfo) -> String {
+    let mut line = format!("{}.{}: {}x{}", pane.window, pane.idx, pane.cols, pane.rows);
+    if let Some(title) = &pane.title {
+        line.push_str(&format!(" \"{}\"", title));
+    }
+    if pane.active {
+        line.push_str(" (active)");
+    }
+    line
+}
+
+/// A line of `sm list-clients`.
+fn format_client(client: &ClientInfo) -> String {
+    format!(
+        "client {}: {} {}x{}, {} bytes out",
+        client.id, client.session, client.cols, client.rows, client.bytes
+    )
+}
+
+/// A line of `sm list-buffers`.
+fn format_buffer(buffer: &BufferInfo) -> String {
+    format!(
+        "{}: {} bytes from {}: \"{}\"",
+        buffer.name, buffer.bytes, buffer.source, buffer.sample
+    )
+}
+
+/// The messages the server sends on `stream`, until it closes.
+fn server_messages(mut stream: UnixStream) -> Receiver<ServerMessage> {
+    let (mut send, recv) = mpsc::channel(0x100);
+    thread::spawn(move || {
+        while let Ok(Some(message)) = protocol::receive(&mut stream) {
+            if executor::block_on(send.send(message)).is_err() {
+                break;
+            }
+        }
+        send.disconnect();
+    });
+    recv
+}
+
+fn sigwinch_stream() -> Receiver<bool> {
+    let (mut send, recv) = mpsc::channel(0x1000);
+    let signal = Signals::new([SIGWINCH]).unwrap();
+    thread::spawn(move || {
+        for _ in signal.forever() {
+            send.try_send(true).unwrap();
+        }
+        send.disconnect();
+    });
+    recv
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn session_lines() {
+        let session = SessionInfo {
+            name: "work".to_string(),
+            windows: 1,
+            attached: false,
+            group: None,
+        };
+        assert_eq!(format_session(&session), "work: 1 window");
+        let session = SessionInfo {
+            windows: 3,
+            attached: true,
+            ..session
+        };
+        assert_eq!(format_session(&session), "work: 3 windows (attached)");
+        let session = SessionInfo {
+            name: "pair".to_string(),
+            group: Some("work".to_string()),
+            ..session
+        };
+        assert_eq!(
+            format_session(&session),
+            "pair: 3 windows (group work) (attached)"
+        );
+    }
+
+    #[test]
+    fn window_lines() {
+        let window = WindowInfo {
+            idx: 2,
+            name: "build".to_string(),
+            panes: 1,
+            active: false,
+        };
+        assert_eq!(format_window(&window), "2: build (1 pane)");
+        let window = WindowInfo {
+            panes: 3,
+            active: true,
+            ..window
+        };
+        assert_eq!(format_window(&window), "2: build (3 panes) (active)");
+    }
+
+    #[test]
+    fn json() {
+        assert_eq!("json".parse::<OutputFormat>().unwrap(), OutputFormat::Json);
+        assert!("yaml".parse::<OutputFormat>().is_err());
+        let sessions = vec![SessionInfo {
+            name: "pair".to_string(),
+            windows: 3,
+            attached: true,
+            group: None,
+        }];
+        assert_eq!(
+            serde_json::to_string(&sessions).unwrap(),
+            r#"[{"name":"pair","windows":3,"attached":true,"group":null}]"#
+        );
+        let window = WindowInfo {
+            idx: 2,
+            name: "build \"a\"".to_string(),
+            panes: 1,
+            active: true,
+        };
+        assert_eq!(
+            serde_json::to_string(&window).unwrap(),
+            r#"{"idx":2,"name":"build \"a\"","panes":1,"active":true}"#
+        );
+    }
+}
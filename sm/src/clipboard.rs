@@ -0,0 +1,183 @@
+//! The system clipboard, copied to and pasted from with commands such as
+//! `wl-copy` and `wl-paste`, for outer terminals which cannot be asked to do
+//! it with OSC 52.
+
+use std::{
+    io::{self, Write},
+    os::unix::fs::PermissionsExt,
+    path::Path,
+    process::{Command, Output, Stdio},
+};
+
+use session_manager::{environment::Environment, util};
+
+/// A clipboard tool: the variable saying it can be used, if any, the program
+/// to look for, and the commands copying and pasting with it.
+struct Tool {
+    display: Option<&'static str>,
+    program: &'static str,
+    copy: &'static str,
+    paste: &'static str,
+}
+
+/// The tools looked for, in order.
+const TOOLS: [Tool; 4] = [
+    Tool {
+        display: Some("WAYLAND_DISPLAY"),
+        program: "wl-copy",
+        copy: "wl-copy",
+        paste: "wl-paste --no-newline",
+    },
+    Tool {
+        display: Some("DISPLAY"),
+        program: "xclip",
+        copy: "xclip -selection clipboard",
+        paste: "xclip -selection clipboard -o",
+    },
+    Tool {
+        display: Some("DISPLAY"),
+        program: "xsel",
+        copy: "xsel --clipboard --input",
+        paste: "xsel --clipboard --output",
+    },
+    Tool {
+        display: None,
+        program: "pbcopy",
+        copy: "pbcopy",
+        paste: "pbpaste",
+    },
+];
+
+/// The command to run with the shell to copy, in `env`: `configured` if it is
+/// set, where `none` means there is none, or else that of the first tool found
+/// in `env`'s `PATH` which can be used.
+pub fn copy_command(configured: Option<&str>, env: &Environment) -> Option<String> {
+    command(configured, env, |tool| tool.copy)
+}
+
+/// The command to run with the shell to paste, chosen as `copy_command` is.
+pub fn paste_command(configured: Option<&str>, env: &Environment) -> Option<String> {
+    command(configured, env, |tool| tool.paste)
+}
+
+fn command(
+    configured: Option<&str>,
+    env: &Environment,
+    pick: fn(&Tool) -> &'static str,
+) -> Option<String> {
+    match configured {
+        Some("none") => return None,
+        Some(command) => return Some(command.to_string()),
+        None => (),
+    }
+    let path = env.get("PATH").unwrap_or("");
+    let tool = TOOLS.iter().find(|tool| {
+        let display = tool
+            .display
+            .is_none_or(|name| env.get(name).is_some_and(|value| !value.is_empty()));
+        display
+            && path
+                .split(':')
+                .any(|dir| is_executable(&Path::new(dir).join(tool.program)))
+    })?;
+    Some(pick(tool).to_string())
+}
+
+fn is_executable(path: &Path) -> bool {
+    path.metadata()
+        .is_ok_and(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+}
+
+/// Run `command` with the shell in `env`, giving it `text` on its standard
+/// input, and wait for it.
+pub fn copy(command: &str, text: &str, env: &Environment) -> io::Result<()> {
+    let mut child = shell(command, env).stdin(Stdio::piped()).spawn()?;
+    // The command may not read it all, e.g. if it fails.
+    child.stdin.take().unwrap().write_all(text.as_bytes()).ok();
+    check(command, child.wait_with_output()?)?;
+    Ok(())
+}
+
+/// Run `command` with the shell in `env`, and wait for what it writes.
+pub fn paste(command: &str, env: &Environment) -> io::Result<String> {
+    let output = shell(command, env).stdin(Stdio::null()).output()?;
+    let output = check(command, output)?;
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+fn shell(command: &str, env: &Environment) -> Command {
+    let mut shell = Command::new(util::POSIX_SHELL);
+    shell
+        .arg("-c")
+        .arg(command)
+        .env_clear()
+        .envs(env.vars())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    shell
+}
+
+/// `output`, or what it said on its standard error if `command` failed.
+fn check(command: &str, output: Output) -> io::Result<Output> {
+    if output.status.success() {
+        return Ok(output);
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    Err(io::Error::other(format!(
+        "{}: {} {}",
+        command,
+        output.status,
+        stderr.trim()
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs;
+
+    fn install(dir: &Path, program: &str) {
+        let path = dir.join(program);
+        fs::write(&path, "#!/bin/sh\n").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    #[test]
+    fn detect() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut env = Environment::new();
+        env.set("PATH", &format!("/nonexistent:{}", dir.path().display()));
+        assert_eq!(copy_command(None, &env), None);
+
+        install(dir.path(), "xsel");
+        install(dir.path(), "wl-copy");
+        // Neither can be used without a display.
+        assert_eq!(copy_command(None, &env), None);
+        env.set("DISPLAY", ":0");
+        assert_eq!(
+            paste_command(None, &env).unwrap(),
+            "xsel --clipboard --output"
+        );
+        env.set("WAYLAND_DISPLAY", "wayland-0");
+        assert_eq!(copy_command(None, &env).unwrap(), "wl-copy");
+
+        assert_eq!(
+            copy_command(Some("tee /tmp/clip"), &env).unwrap(),
+            "tee /tmp/clip"
+        );
+        assert_eq!(paste_command(Some("none"), &env), None);
+    }
+
+    #[test]
+    fn copy_and_paste() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("clip");
+        let env = Environment::new();
+        copy(&format!("cat > {}", file.display()), "hello\n", &env).unwrap();
+        let pasted = paste(&format!("cat {}", file.display()), &env).unwrap();
+        assert_eq!(pasted, "hello\n");
+        let e = paste("echo oops >&2; exit 3", &env).unwrap_err();
+        assert!(e.to_string().ends_with("oops"), "{}", e);
+    }
+}
@@ -0,0 +1,241 @@
+//! Commands typed at the command prompt: actions, written as in key bindings,
+//! e.g. `rename-window logs` or `resize-pane -R 5`.
+//!
+//! A line may hold several commands separated by `;`, performed in order.
+//! Words are split at spaces, except in single or double quotes, and a
+//! backslash takes the next character as it is, e.g. `\;` for a `;` which does
+//! not separate commands. The arguments of `if-shell` are commands themselves,
+//! each quoted as one word.
+
+use anyhow::{anyhow, bail, Result};
+
+use crate::bindings::Action;
+
+/// The actions of the commands on `line`, in order.
+pub fn parse(line: &str) -> Result<Vec<Action>> {
+    let mut actions = Vec::new();
+    let mut command = Vec::new();
+    for word in split(line)? {
+        match word {
+            Word::Separator => {
+                if !command.is_empty() {
+                    actions.push(join(&command).parse()?);
+                    command.clear();
+                }
+            }
+            Word::Text(text) => command.push(text),
+        }
+    }
+    if !command.is_empty() {
+        actions.push(join(&command).parse()?);
+    }
+    Ok(actions)
+}
+
+/// The line the action of `command` is parsed from: its words, with those
+/// after an `if-shell` quoted again for it to split.
+fn join(command: &[String]) -> String {
+    let idx = match command.iter().position(|word| word == "if-shell") {
+        Some(idx) => idx + 1,
+        None => command.len(),
+    };
+    let (plain, quoted) = command.split_at(idx);
+    let quoted = quoted.iter().map(|word| quote(word));
+    plain
+        .iter()
+        .cloned()
+        .chain(quoted)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// `word`, quoted if it has to be for it to be split back into one word.
+pub fn quote(word: &str) -> String {
+    let special = |c: char| c.is_whitespace() || "'\";\\".contains(c);
+    if !word.is_empty() && !word.contains(special) {
+        return word.to_string();
+    }
+    let mut quoted = String::from('"');
+    for c in word.chars() {
+        if c == '"' || c == '\\' {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// The arguments of `if-shell`, `CONDITION COMMAND [ELSE]`: a shell command,
+/// the commands to take if it succeeds, and any to take if it fails.
+pub fn if_shell_args(args: &str) -> Result<(String, String, Option<String>)> {
+    let mut words = words(args)?.into_iter();
+    match (words.next(), words.next(), words.next(), words.next()) {
+        (Some(condition), Some(then), otherwise, None) => {
+            Ok((condition, then, otherwise))
+        }
+        _ => bail!("if-shell needs a condition and one or two commands"),
+    }
+}
+
+/// The words of `line`, split as those of commands are, for a line holding a
+/// single command.
+pub fn words(line: &str) -> Result<Vec<String>> {
+    split(line)?
+        .into_iter()
+        .map(|word| match word {
+            Word::Text(text) => Ok(text),
+            Word::Separator => Err(anyhow!("unexpected ;")),
+        })
+        .collect()
+}
+
+/// A word of a command line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Word {
+    Text(String),
+    /// A `;` ending a command.
+    Separator,
+}
+
+/// Split `line` into words.
+fn split(line: &str) -> Result<Vec<Word>> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => words.extend(word.take().map(Word::Text)),
+            ';' => {
+                words.extend(word.take().map(Word::Text));
+                words.push(Word::Separator);
+            }
+            '\\' => match chars.next() {
+                Some(c) => word.get_or_insert_with(String::new).push(c),
+                None => bail!("nothing after \\"),
+            },
+            '\'' | '"' => {
+                let quote = c;
+                let text = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some(c) if c == quote => break,
+                        Some('\\') if quote == '"' => match chars.next() {
+                            Some(c) => text.push(c),
+                            None => bail!("missing {}", quote),
+                        },
+                        Some(c) => text.push(c),
+                        None => bail!("missing {}", quote),
+                    }
+                }
+            }
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    words.extend(word.map(Word::Text));
+    Ok(words)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use session_manager::layout::Direction;
+
+    use crate::bindings::ShellOutput;
+
+    #[test]
+    fn command_words() {
+        assert_eq!(
+            words(r#"'test -n "$DISPLAY"' "set-option mouse on" a\ b"#).unwrap(),
+            ["test -n \"$DISPLAY\"", "set-option mouse on", "a b"]
+        );
+        assert!(words("a; b").is_err());
+        assert!(words("'a").is_err());
+    }
+
+    #[test]
+    fn parse_commands() {
+        assert_eq!(
+            parse("new-window").unwrap(),
+            [Action::NewWindow {
+                name: None,
+                command: None,
+                cwd: None,
+                env: Vec::new(),
+            }]
+        );
+        assert_eq!(
+            parse(" rename-window 'build logs' ; resize-pane -R 5;next-pane ").unwrap(),
+            [
+                Action::RenameWindow(Some("build logs".to_string())),
+                Action::ResizePane(Direction::Horizontal, 5),
+                Action::NextPane,
+            ]
+        );
+        assert_eq!(
+            parse(r#"run-shell echo "a;b" c\;d"#).unwrap(),
+            [Action::RunShell {
+                command: "echo a;b c;d".to_string(),
+                output: ShellOutput::Pager,
+            }]
+        );
+        assert_eq!(
+            parse(r#"if-shell 'test -n "$DISPLAY"' "next-pane; clock-mode""#).unwrap(),
+            [Action::IfShell {
+                condition: "test -n \"$DISPLAY\"".to_string(),
+                then: "next-pane; clock-mode".to_string(),
+                otherwise: None,
+            }]
+        );
+        assert!(parse("if-shell true 'no-such-command'").is_err());
+        assert_eq!(parse("").unwrap(), []);
+        assert_eq!(parse(" ; ").unwrap(), []);
+
+        let e = parse("next-pane; resize-pane -X").unwrap_err();
+        assert_eq!(e.to_string(), "expected width or height, not \"-X\"");
+        assert!(parse("no-such-command").is_err());
+        assert!(parse("rename-window 'logs").is_err());
+        assert!(parse("rename-window logs\\").is_err());
+    }
+
+    #[test]
+    fn quote_words() {
+        for word in ["a", "", "a b", "it's", r#"say "hi""#, "a;b", r"a\b"] {
+            assert_eq!(words(&quote(word)).unwrap(), [word]);
+        }
+        assert_eq!(quote("next-pane"), "next-pane");
+        assert_eq!(quote("a b"), "\"a b\"");
+        assert_eq!(
+            if_shell_args("true 'next-pane' \"clock-mode\"").unwrap(),
+            (
+                "true".to_string(),
+                "next-pane".to_string(),
+                Some("clock-mode".to_string())
+            )
+        );
+        assert!(if_shell_args("true").is_err());
+        assert!(if_shell_args("true a b c").is_err());
+    }
+
+    #[test]
+    fn split_words() {
+        let text = |s: &str| Word::Text(s.to_string());
+        assert_eq!(
+            split(r#"a "b c" 'd "e"' f\ g "h\"i" j''"#).unwrap(),
+            [
+                text("a"),
+                text("b c"),
+                text("d \"e\""),
+                text("f g"),
+                text("h\"i"),
+                text("j"),
+            ]
+        );
+        assert_eq!(
+            split("a;b").unwrap(),
+            [text("a"), Word::Separator, text("b")]
+        );
+        assert_eq!(split("''").unwrap(), [text("")]);
+    }
+}
@@ -0,0 +1,864 @@
+//! The configuration file.
+//!
+//! Each line is a command; blank lines and lines starting with `#` are ignored.
+//! The commands are:
+//!
+//! - `bind-key BINDING`: see `KeyBinding` for the syntax. A key bound again
+//!   replaces its binding, the default or one made before.
+//! - `unbind-key [-n | -T TABLE] KEY`: remove the binding of a key.
+//! - `bind-mouse BINDING`: see `MouseBinding` for the syntax.
+//! - `set-environment NAME VALUE`, `set-environment -r NAME`: set or remove a
+//!   variable in the environment of new panes.
+//! - `update-environment NAME...`: the variables to take from the environment
+//!   `sm` is started in, replacing the defaults.
+//! - `set-hook HOOK ACTION`: perform an action, as a key binding would, when
+//!   the hook runs. Actions set on the same hook are performed in order. The
+//!   hooks are `session-created`, `after-new-window`, `pane-exited`,
+//!   `client-attached`, `alert-bell` and `alert-activity`; see `Hook`.
+//! - `if-shell CONDITION COMMAND [ELSE]`: run `CONDITION` with the shell, and
+//!   take `COMMAND`, another line of the file, if it succeeds, or `ELSE` if it
+//!   fails, e.g. to set options for one host only. The arguments are split as
+//!   at the command prompt, so each is quoted if it has spaces.
+//! - `set-option NAME VALUE`: set an option:
+//!   - `status-right`: the format of the text at the right of the status line.
+//!   - `remain-on-exit`: `on` to keep panes whose processes exit, or `off`.
+//!   - `record-input`: `on` to record the input to new windows, to be shown
+//!     with `show-input`, or `off`.
+//!   - `renumber-windows`: `on` to number the windows from the base index up
+//!     again whenever one closes, or `off`.
+//!   - `base-index`: the index of the first window, rather than 0.
+//!   - `pane-base-index`: the number of the first pane of a window, rather
+//!     than 0.
+//!   - `set-titles`: `on` to set the title of the client's terminal, or `off`.
+//!   - `set-titles-string`: the format of that title.
+//!   - `pane-min-size`: the smallest panes may be split or resized to, as
+//!     `COLSxROWS`.
+//!   - `visual-bell`: `on` to flash windows whose bells ring, or `off` to ring
+//!     the client's bell.
+//!   - `display-time`: the milliseconds `display-message` shows a message for.
+//!   - `history-limit`: how many commands the command prompt's history keeps.
+//!   - `last-window-close`: what happens when the last window of the session
+//!     attached to closes; see `LastWindowClose`.
+//!   - `window-size`: how the size of windows is chosen from those of the
+//!     clients attached; see `WindowSize`.
+//!   - `copy-command`, `paste-command`: the commands copying to the system
+//!     clipboard and pasting from it, or `none`; see `clipboard::copy_command`.
+//!   - `lock-after-time`: the minutes without input after which the screen is
+//!     locked, or `0` not to lock it.
+//!   - `lock-command`: the command given the text typed on the lock screen,
+//!     which unlocks it by exiting successfully.
+//!   - `default-shell`: the absolute path of the shell new panes run, rather
+//!     than the user's.
+//!   - `default-command`: the command new panes run with that shell when not
+//!     given one, rather than the shell itself.
+//!   - `mouse`: `on` to select windows and panes by clicking them, and scroll
+//!     panes back with the wheel, or `off`.
+//!   - `color-scheme`: the built-in theme, `default`, `ocean` or `mono`.
+//!   - `status-style`, `window-status-flags-style`, `message-style`,
+//!     `mode-style`, `pane-border-style`, `pane-active-border-style`: the
+//!     styles of the status line, the flags after window names on it, the
+//!     prompt and pager's last row, text found in the pager, the lines between
+//!     panes and those around the active pane, in place of the theme's; see
+//!     `style`.
+//!   - `ambiguous-width`: how wide the client's terminal draws East Asian
+//!     Ambiguous characters; see `AmbiguousWidth`. Each client reads it as it
+//!     attaches, with its own locale.
+
+use std::{
+    collections::BTreeMap,
+    env, fmt, fs, io,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    str::FromStr,
+    time::Duration,
+};
+
+use anyhow::{anyhow, bail, Context, Error, Result};
+
+use session_manager::{layout::MinSize, util};
+
+use crate::{
+    backend::Key,
+    bindings::{self, Action, KeyBindings, KeyTable, MouseBinding},
+    command,
+    server::WindowSize,
+    style::{ColorScheme, Style, Theme, STYLE_OPTIONS},
+};
+
+/// When actions set with `set-hook` are performed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hook {
+    /// Once the session has its first window, e.g. to open more.
+    SessionCreated,
+    /// Once `new-window` has opened a window and selected it.
+    AfterNewWindow,
+    /// When the process of a pane in the session attached to exits.
+    PaneExited,
+    /// When a client attaches.
+    ClientAttached,
+    /// When a bell rings in the session attached to.
+    AlertBell,
+    /// When a window of the session attached to, other than the selected one,
+    /// has output, once until it is selected.
+    AlertActivity,
+}
+
+impl FromStr for Hook {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Hook> {
+        match s {
+            "session-created" => Ok(Hook::SessionCreated),
+            "after-new-window" => Ok(Hook::AfterNewWindow),
+            "pane-exited" => Ok(Hook::PaneExited),
+            "client-attached" => Ok(Hook::ClientAttached),
+            "alert-bell" => Ok(Hook::AlertBell),
+            "alert-activity" => Ok(Hook::AlertActivity),
+            _ => bail!("unknown hook: {}", s),
+        }
+    }
+}
+
+/// What happens when the last window of the session attached to closes.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum LastWindowClose {
+    /// `exit`: the session ends, and the client exits.
+    #[default]
+    Exit,
+    /// `detach`: the client is detached, and the session kept with a new window
+    /// to attach to again.
+    Detach,
+    /// `summary`: the client is shown how the windows exited, to open a new
+    /// one or end the session.
+    Summary,
+}
+
+impl FromStr for LastWindowClose {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<LastWindowClose> {
+        match s {
+            "exit" => Ok(LastWindowClose::Exit),
+            "detach" => Ok(LastWindowClose::Detach),
+            "summary" => Ok(LastWindowClose::Summary),
+            _ => bail!("expected exit, detach or summary: {}", s),
+        }
+    }
+}
+
+impl fmt::Display for LastWindowClose {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            LastWindowClose::Exit => "exit",
+            LastWindowClose::Detach => "detach",
+            LastWindowClose::Summary => "summary",
+        })
+    }
+}
+
+/// How wide a client's terminal draws East Asian Ambiguous characters, such as
+/// `…` and the box-drawing characters, for the status line and the borders to
+/// line up on it.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum AmbiguousWidth {
+    /// `narrow`: one column, as most terminals do.
+    Narrow,
+    /// `wide`: two columns, as terminals set up for CJK text do. The borders
+    /// between panes are drawn with ASCII characters.
+    Wide,
+    /// `auto`: two columns if the client's locale is Chinese, Japanese or
+    /// Korean, and one otherwise.
+    #[default]
+    Auto,
+}
+
+impl AmbiguousWidth {
+    /// Whether the characters are two columns wide, with `var` giving the
+    /// client's environment variables for `Auto`.
+    pub fn is_wide<F: Fn(&str) -> Option<String>>(self, var: F) -> bool {
+        match self {
+            AmbiguousWidth::Narrow => false,
+            AmbiguousWidth::Wide => true,
+            AmbiguousWidth::Auto => {
+                // The first of these set is the locale of the characters.
+                let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+                    .iter()
+                    .find_map(|name| var(name).filter(|value| !value.is_empty()))
+                    .unwrap_or_default();
+                ["ja", "ko", "zh"]
+                    .iter()
+                    .any(|language| locale.starts_with(language))
+            }
+        }
+    }
+}
+
+impl FromStr for AmbiguousWidth {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<AmbiguousWidth> {
+        match s {
+            "narrow" => Ok(AmbiguousWidth::Narrow),
+            "wide" => Ok(AmbiguousWidth::Wide),
+            "auto" => Ok(AmbiguousWidth::Auto),
+            _ => bail!("expected narrow, wide or auto: {}", s),
+        }
+    }
+}
+
+impl fmt::Display for AmbiguousWidth {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            AmbiguousWidth::Narrow => "narrow",
+            AmbiguousWidth::Wide => "wide",
+            AmbiguousWidth::Auto => "auto",
+        })
+    }
+}
+
+/// Settings read from the configuration file.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Config {
+    /// The key bindings: the defaults, changed by those in the file.
+    pub key_bindings: KeyBindings,
+    pub mouse_bindings: Vec<MouseBinding>,
+    /// Variables to set in the session's environment, or remove if `None`.
+    pub environment: Vec<(String, Option<String>)>,
+    /// The variables to take from the client's environment, if not the default.
+    pub update_environment: Option<Vec<String>>,
+    pub hooks: Vec<(Hook, Action)>,
+    /// The format of the text at the right of the status line, if any.
+    pub status_right: Option<String>,
+    /// Whether panes whose processes exit are kept, showing what they left.
+    pub remain_on_exit: bool,
+    /// Whether the input to new windows is recorded.
+    pub record_input: bool,
+    /// Whether the windows are numbered again without gaps as they close.
+    pub renumber_windows: bool,
+    /// The index of the first window.
+    pub base_index: usize,
+    /// The number of the first pane of a window.
+    pub pane_base_index: usize,
+    /// Whether the title of the client's terminal is set.
+    pub set_titles: bool,
+    /// The format of that title, if not the default.
+    pub set_titles_string: Option<String>,
+    pub last_window_close: LastWindowClose,
+    /// Whether bells flash the window, or its entry in the status line, rather
+    /// than ringing the client's bell.
+    pub visual_bell: bool,
+    /// How long `display-message` shows a message, if not the default.
+    pub display_time: Option<Duration>,
+    /// How many commands the command prompt's history keeps, if not the
+    /// default.
+    pub history_limit: Option<usize>,
+    /// The smallest panes may be split or resized to, if not the default.
+    pub pane_min_size: Option<MinSize>,
+    pub window_size: WindowSize,
+    /// The command text copied is given to, if not one found.
+    pub copy_command: Option<String>,
+    /// The command writing the clipboard to paste, if not one found.
+    pub paste_command: Option<String>,
+    /// How long without input before the screen is locked, if it ever is.
+    pub lock_after: Option<Duration>,
+    /// The command checking the password typed to unlock the screen, if one is
+    /// asked for.
+    pub lock_command: Option<String>,
+    /// The shell new panes run, if not the user's.
+    pub default_shell: Option<String>,
+    /// The command new panes run when not given one, if not the shell.
+    pub default_command: Option<String>,
+    pub ambiguous_width: AmbiguousWidth,
+    /// Whether clicks select windows and panes.
+    pub mouse: bool,
+    pub color_scheme: ColorScheme,
+    /// The styles set in place of the color scheme's, by option name.
+    pub styles: BTreeMap<String, Style>,
+    /// The file read, if there was one.
+    pub path: Option<PathBuf>,
+    /// The line each option set was last set on, by name.
+    pub option_lines: BTreeMap<String, usize>,
+}
+
+impl Config {
+    /// The user's configuration file: `$XDG_CONFIG_HOME/session-manager/sm.conf`,
+    /// or under `~/.config` if that is unset; `PROFILE.conf` rather than
+    /// `sm.conf` for a profile.
+    pub fn default_path(profile: Option<&str>) -> Option<PathBuf> {
+        let config = env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| {
+                env::var_os("HOME").map(|home| Path::new(&home).join(".config"))
+            })?;
+        let file = match profile {
+            Some(profile) => format!("{}.conf", profile),
+            None => "sm.conf".to_string(),
+        };
+        Some(config.join("session-manager").join(file))
+    }
+
+    /// Read the configuration in `path`, which has to exist, as given with
+    /// `--config`.
+    pub fn read(path: &Path) -> Result<Config> {
+        let text =
+            fs::read_to_string(path).with_context(|| path.display().to_string())?;
+        Config::parse(&text)
+            .map(|config| config.read_from(path))
+            .with_context(|| path.display().to_string())
+    }
+
+    /// Read the configuration in `path`; a missing file gives the defaults.
+    pub fn load(path: &Path) -> Result<Config> {
+        match fs::read_to_string(path) {
+            Ok(text) => Config::parse(&text)
+                .map(|config| config.read_from(path))
+                .with_context(|| path.display().to_string()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Config::default()),
+            Err(e) => Err(e).with_context(|| path.display().to_string()),
+        }
+    }
+
+    /// The styles things are drawn in: the color scheme's, changed by those set.
+    pub fn theme(&self) -> Theme {
+        let mut theme = self.color_scheme.theme();
+        for (option, style) in &self.styles {
+            if let Some(theme_style) = theme.style_mut(option) {
+                *theme_style = *style;
+            }
+        }
+        theme
+    }
+
+    /// Whether the mouse is captured: if clicks select windows and panes, or
+    /// anything is bound to it.
+    pub fn captures_mouse(&self) -> bool {
+        self.mouse || !self.mouse_bindings.is_empty()
+    }
+
+    fn read_from(self, path: &Path) -> Config {
+        Config {
+            path: Some(path.to_path_buf()),
+            ..self
+        }
+    }
+
+    /// The actions set on `hook`, in the order they were set.
+    pub fn hook_actions(&self, hook: Hook) -> Vec<Action> {
+        self.hooks
+            .iter()
+            .filter(|(h, _)| *h == hook)
+            .map(|(_, action)| action.clone())
+            .collect()
+    }
+
+    fn parse(text: &str) -> Result<Config> {
+        let mut config = Config::default();
+        for (idx, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let context = || format!("line {}", idx + 1);
+            let mut line = line.to_string();
+            while let Some(args) = line.strip_prefix("if-shell ") {
+                line = if_shell(args).with_context(context)?;
+            }
+            if line.is_empty() {
+                continue;
+            }
+            let (command, args) = line.split_at(line.find(' ').unwrap_or(line.len()));
+            match command {
+                "bind-key" => config
+                    .key_bindings
+                    .bind(args.parse().with_context(context)?),
+                "unbind-key" => {
+                    let (table, key) = parse_table_key(args).with_context(context)?;
+                    config.key_bindings.unbind(table, key);
+                }
+                "bind-mouse" => config
+                    .mouse_bindings
+                    .push(args.parse().with_context(context)?),
+                "set-environment" => {
+                    let change = parse_set_environment(args).with_context(context)?;
+                    config.environment.push(change);
+                }
+                "update-environment" => {
+                    let names = args.split_whitespace().map(String::from).collect();
+                    config.update_environment = Some(names);
+                }
+                "set-hook" => {
+                    let hook = parse_set_hook(args).with_context(context)?;
+                    config.hooks.push(hook);
+                }
+                "set-option" => {
+                    let (name, value) = args
+                        .trim()
+                        .split_once(' ')
+                        .ok_or_else(|| anyhow!("set-option needs a name and a value"))
+                        .with_context(context)?;
+                    match name {
+                        "status-right" => config.status_right = Some(value.to_string()),
+                        "remain-on-exit" => {
+                            config.remain_on_exit =
+                                parse_switch(value).with_context(context)?
+                        }
+                        "record-input" => {
+                            config.record_input =
+                                parse_switch(value).with_context(context)?
+                        }
+                        "renumber-windows" => {
+                            config.renumber_windows =
+                                parse_switch(value).with_context(context)?
+                        }
+                        "base-index" => {
+                            config.base_index =
+                                parse_index(value).with_context(context)?
+                        }
+                        "pane-base-index" => {
+                            config.pane_base_index =
+                                parse_index(value).with_context(context)?
+                        }
+                        "set-titles" => {
+                            config.set_titles =
+                                parse_switch(value).with_context(context)?
+                        }
+                        "set-titles-string" => {
+                            config.set_titles_string = Some(value.to_string())
+                        }
+                        "pane-min-size" => {
+                            let min = value.parse().map_err(Error::msg);
+                            config.pane_min_size = Some(min.with_context(context)?)
+                        }
+                        "visual-bell" => {
+                            config.visual_bell =
+                                parse_switch(value).with_context(context)?
+                        }
+                        "display-time" => {
+                            let ms = value
+                                .parse()
+                                .with_context(|| {
+                                    format!("invalid milliseconds: {}", value)
+                                })
+                                .with_context(context)?;
+                            config.display_time = Some(Duration::from_millis(ms));
+                        }
+                        "history-limit" => {
+                            let limit = value
+                                .parse()
+                                .with_context(|| format!("invalid number: {}", value))
+                                .with_context(context)?;
+                            config.history_limit = Some(limit);
+                        }
+                        "copy-command" => config.copy_command = Some(value.to_string()),
+                        "paste-command" => {
+                            config.paste_command = Some(value.to_string())
+                        }
+                        "lock-after-time" => {
+                            let minutes: u64 = value
+                                .parse()
+                                .with_context(|| format!("invalid minutes: {}", value))
+                                .with_context(context)?;
+                            config.lock_after = Some(Duration::from_secs(minutes * 60))
+                                .filter(|after| !after.is_zero());
+                        }
+                        "lock-command" => config.lock_command = Some(value.to_string()),
+                        "default-shell" => {
+                            if !value.starts_with('/') {
+                                return Err(anyhow!(
+                                    "default-shell needs an absolute path, not {:?}",
+                                    value
+                                ))
+                                .with_context(context);
+                            }
+                            config.default_shell = Some(value.to_string())
+                        }
+                        "default-command" => {
+                            config.default_command = Some(value.to_string())
+                        }
+                        "window-size" => {
+                            let policy = value.parse().map_err(Error::msg);
+                            config.window_size = policy.with_context(context)?
+                        }
+                        "last-window-close" => {
+                            config.last_window_close =
+                                value.parse().with_context(context)?
+                        }
+                        "mouse" => {
+                            config.mouse = parse_switch(value).with_context(context)?
+                        }
+                        "ambiguous-width" => {
+                            config.ambiguous_width =
+                                value.parse().with_context(context)?
+                        }
+                        "color-scheme" => {
+                            config.color_scheme = value.parse().with_context(context)?
+                        }
+                        name if STYLE_OPTIONS.contains(&name) => {
+                            let style = value.parse().with_context(context)?;
+                            config.styles.insert(name.to_string(), style);
+                        }
+                        _ => bail!("{}: unknown option: {}", context(), name),
+                    }
+                    config.option_lines.insert(name.to_string(), idx + 1);
+                }
+                _ => bail!("{}: unknown command: {}", context(), command),
+            }
+        }
+        Ok(config)
+    }
+}
+
+/// Parse the arguments of `set-environment`: `NAME VALUE`, or `-r NAME`.
+pub fn parse_set_environment(args: &str) -> Result<(String, Option<String>)> {
+    let args = args.trim();
+    if let Some(name) = args.strip_prefix("-r ") {
+        return Ok((name.trim().to_string(), None));
+    }
+    match args.split_once(' ') {
+        Some((name, value)) => Ok((name.to_string(), Some(value.trim().to_string()))),
+        None => bail!("set-environment needs a name and a value"),
+    }
+}
+
+/// Parse an index a numbering starts from.
+fn parse_index(value: &str) -> Result<usize> {
+    value
+        .parse()
+        .with_context(|| format!("invalid index: {}", value))
+}
+
+/// Parse `on` or `off`.
+pub fn parse_switch(value: &str) -> Result<bool> {
+    match value.trim() {
+        "on" => Ok(true),
+        "off" => Ok(false),
+        other => bail!("expected on or off, not {:?}", other),
+    }
+}
+
+/// Parse the arguments of `unbind-key`: `[-n | -T TABLE] KEY`.
+fn parse_table_key(args: &str) -> Result<(KeyTable, Key)> {
+    let mut words = args.split_whitespace();
+    let table_key = bindings::parse_table_key(&mut words)?;
+    if let Some(extra) = words.next() {
+        bail!("unexpected {:?} after the key", extra);
+    }
+    Ok(table_key)
+}
+
+/// The command `if-shell` takes for its arguments, `CONDITION COMMAND [ELSE]`:
+/// `COMMAND` if `CONDITION` succeeds when run with the shell, or `ELSE`, or
+/// nothing, if it fails.
+fn if_shell(args: &str) -> Result<String> {
+    let (condition, then, otherwise) = command::if_shell_args(args)?;
+    let status = Command::new(util::POSIX_SHELL)
+        .arg("-c")
+        .arg(&condition)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .with_context(|| format!("cannot run {:?}", condition))?;
+    let command = if status.success() {
+        Some(then)
+    } else {
+        otherwise
+    };
+    Ok(command.map_or_else(String::new, |command| command.trim().to_string()))
+}
+
+/// Parse the arguments of `set-hook`: `HOOK ACTION`.
+fn parse_set_hook(args: &str) -> Result<(Hook, Action)> {
+    let (hook, action) = args
+        .trim()
+        .split_once(' ')
+        .ok_or_else(|| anyhow!("set-hook needs a hook and an action"))?;
+    Ok((hook.parse()?, action.parse()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use session_manager::layout::Direction;
+
+    use crate::bindings::{MouseRegion, ShellOutput};
+
+    #[test]
+    fn parse() {
+        let config = Config::parse(
+            "# Mouse\n\
+             \n\
+             bind-mouse WheelDown status next-window\n\
+             bind-mouse S-Right pane clear-theme\n",
+        )
+        .unwrap();
+        assert_eq!(config.mouse_bindings.len(), 2);
+        assert_eq!(config.mouse_bindings[0].region, MouseRegion::Status);
+        assert_eq!(config.mouse_bindings[1].action, Action::ClearTheme);
+
+        let e = Config::parse("bind-mouse Left pane\n").unwrap_err();
+        assert!(e.to_string().starts_with("line 1"));
+        assert!(Config::parse("bind-keys x new-window").is_err());
+        let config = Config::parse(
+            "bind-key C-Left resize-pane width -5\n\
+             bind-key c next-window\n\
+             bind-key -n F5 next-window\n\
+             unbind-key p\n",
+        )
+        .unwrap();
+        let get = |table, key| config.key_bindings.get(table, key);
+        assert_eq!(
+            get(KeyTable::Prefix, Key::CtrlLeft),
+            Some(&Action::ResizePane(Direction::Horizontal, -5))
+        );
+        assert_eq!(
+            get(KeyTable::Prefix, Key::Char('c')),
+            Some(&Action::NextWindow)
+        );
+        assert_eq!(get(KeyTable::Root, Key::F(5)), Some(&Action::NextWindow));
+        assert_eq!(get(KeyTable::Prefix, Key::Char('p')), None);
+        assert_eq!(
+            get(KeyTable::Prefix, Key::Char('n')),
+            Some(&Action::NextWindow)
+        );
+        assert!(Config::parse("unbind-key p n").is_err());
+        assert!(Config::parse("unbind-key -T copy p").is_err());
+
+        let config = Config::parse(
+            "set-environment EDITOR vim -u NONE\n\
+             set-environment -r LANG\n\
+             update-environment DISPLAY SSH_AUTH_SOCK\n",
+        )
+        .unwrap();
+        assert_eq!(
+            config.environment,
+            [
+                ("EDITOR".to_string(), Some("vim -u NONE".to_string())),
+                ("LANG".to_string(), None),
+            ]
+        );
+        assert_eq!(
+            config.update_environment.unwrap(),
+            ["DISPLAY", "SSH_AUTH_SOCK"]
+        );
+        assert!(Config::parse("set-environment EDITOR").is_err());
+
+        let config = Config::parse(
+            "set-hook session-created new-window -n logs tail -f log\n\
+             set-hook session-created select-window 0\n",
+        )
+        .unwrap();
+        assert_eq!(
+            config.hook_actions(Hook::SessionCreated),
+            [
+                Action::NewWindow {
+                    name: Some("logs".to_string()),
+                    command: Some("tail -f log".to_string()),
+                    cwd: None,
+                    env: Vec::new(),
+                },
+                Action::SelectWindow(0),
+            ]
+        );
+        assert!(Config::parse("set-hook session-created").is_err());
+        let config = Config::parse(
+            "set-hook alert-bell run-shell notify-send bell\n\
+             set-hook after-new-window rename-window new\n\
+             set-hook alert-activity display-message activity\n",
+        )
+        .unwrap();
+        assert_eq!(
+            config.hook_actions(Hook::AlertBell),
+            [Action::RunShell {
+                command: "notify-send bell".to_string(),
+                output: ShellOutput::Pager,
+            }]
+        );
+        assert_eq!(
+            config.hook_actions(Hook::AfterNewWindow),
+            [Action::RenameWindow(Some("new".to_string()))]
+        );
+        assert_eq!(
+            config.hook_actions(Hook::AlertActivity),
+            [Action::DisplayMessage("activity".to_string())]
+        );
+        assert!(config.hook_actions(Hook::PaneExited).is_empty());
+        assert!(Config::parse("set-hook window-created new-window").is_err());
+
+        let config =
+            Config::parse("set-option status-right #{window_bytes_in} in\n").unwrap();
+        assert_eq!(config.status_right.unwrap(), "#{window_bytes_in} in");
+        assert!(Config::parse("set-option status-right").is_err());
+        assert!(Config::parse("set-option status-left x").is_err());
+        assert!(
+            Config::parse("set-option remain-on-exit on")
+                .unwrap()
+                .remain_on_exit
+        );
+        assert!(Config::parse("set-option remain-on-exit yes").is_err());
+        let config = Config::parse(
+            "set-option set-titles on\nset-option set-titles-string #{window_name}\n",
+        )
+        .unwrap();
+        assert!(config.set_titles);
+        assert_eq!(config.set_titles_string.unwrap(), "#{window_name}");
+        assert_eq!(
+            Config::parse("set-option last-window-close summary")
+                .unwrap()
+                .last_window_close,
+            LastWindowClose::Summary
+        );
+        assert!(Config::parse("set-option last-window-close quit").is_err());
+        assert_eq!(
+            Config::parse("set-option pane-min-size 20x4")
+                .unwrap()
+                .pane_min_size,
+            Some(MinSize { cols: 20, rows: 4 })
+        );
+        assert!(Config::parse("set-option pane-min-size 20").is_err());
+        assert_eq!(
+            Config::parse("set-option window-size latest")
+                .unwrap()
+                .window_size,
+            WindowSize::Latest
+        );
+        assert!(Config::parse("set-option window-size biggest").is_err());
+        let config = Config::parse(
+            "set-option copy-command wl-copy\nset-option paste-command none",
+        )
+        .unwrap();
+        assert_eq!(config.copy_command.as_deref(), Some("wl-copy"));
+        assert_eq!(config.paste_command.as_deref(), Some("none"));
+        let config = Config::parse(
+            "set-option lock-after-time 15\nset-option lock-command check-password",
+        )
+        .unwrap();
+        assert_eq!(config.lock_after, Some(Duration::from_secs(900)));
+        assert_eq!(config.lock_command.as_deref(), Some("check-password"));
+        let config = Config::parse(
+            "set-option default-shell /bin/zsh\nset-option default-command htop -d 5",
+        )
+        .unwrap();
+        assert_eq!(config.default_shell.as_deref(), Some("/bin/zsh"));
+        assert_eq!(config.default_command.as_deref(), Some("htop -d 5"));
+        assert!(Config::parse("set-option default-shell zsh").is_err());
+        assert_eq!(
+            Config::parse("set-option lock-after-time 0")
+                .unwrap()
+                .lock_after,
+            None
+        );
+        assert!(Config::parse("set-option lock-after-time soon").is_err());
+        assert_eq!(
+            Config::parse("set-option display-time 2000")
+                .unwrap()
+                .display_time,
+            Some(Duration::from_millis(2000))
+        );
+        assert!(Config::parse("set-option display-time -1").is_err());
+        assert_eq!(
+            Config::parse("set-option history-limit 50")
+                .unwrap()
+                .history_limit,
+            Some(50)
+        );
+        assert!(Config::parse("set-option history-limit lots").is_err());
+        let config =
+            Config::parse("set-option base-index 1\nset-option pane-base-index 1")
+                .unwrap();
+        assert_eq!((config.base_index, config.pane_base_index), (1, 1));
+        assert!(Config::parse("set-option base-index -1").is_err());
+        assert!(
+            Config::parse("set-option record-input on")
+                .unwrap()
+                .record_input
+        );
+        assert!(
+            Config::parse("set-option renumber-windows on")
+                .unwrap()
+                .renumber_windows
+        );
+        assert!(
+            Config::parse("set-option visual-bell on")
+                .unwrap()
+                .visual_bell
+        );
+        assert_eq!(
+            Config::load(Path::new("/nonexistent/sm.conf")).unwrap(),
+            Config::default()
+        );
+        assert!(Config::read(Path::new("/nonexistent/sm.conf")).is_err());
+    }
+
+    #[test]
+    fn if_shell() {
+        let config = Config::parse(
+            "if-shell true 'set-option base-index 1' 'set-option base-index 2'\n\
+             if-shell 'test -z a' 'set-option mouse on' \"set-option visual-bell on\"\n\
+             if-shell false 'set-option set-titles on'\n\
+             if-shell true \"if-shell false '' 'bind-key T clock-mode'\"\n",
+        )
+        .unwrap();
+        assert_eq!(config.base_index, 1);
+        assert!(!config.mouse);
+        assert!(config.visual_bell);
+        assert!(!config.set_titles);
+        assert_eq!(config.option_lines["visual-bell"], 2);
+        assert!(config
+            .key_bindings
+            .iter()
+            .any(|binding| binding.action == Action::ClockMode));
+        let e = Config::parse("\nif-shell true 'set-option nosuch 1'").unwrap_err();
+        assert_eq!(format!("{:#}", e), "line 2: unknown option: nosuch");
+        assert!(Config::parse("if-shell true").is_err());
+        assert!(Config::parse("if-shell 'true").is_err());
+    }
+
+    #[test]
+    fn theme() {
+        assert_eq!(Config::default().theme(), Theme::default());
+        // Styles set change the color scheme's, wherever it is set.
+        let config = Config::parse(
+            "set-option status-style fg=black,bg=green\n\
+             set-option color-scheme ocean",
+        )
+        .unwrap();
+        let theme = config.theme();
+        assert_eq!(theme.status.to_string(), "fg=black,bg=green");
+        assert_eq!(theme.border, ColorScheme::Ocean.theme().border);
+        assert!(Config::parse("set-option color-scheme neon").is_err());
+        assert!(Config::parse("set-option mode-style fg=octarine").is_err());
+    }
+
+    #[test]
+    fn ambiguous_width() {
+        assert_eq!(Config::default().ambiguous_width, AmbiguousWidth::Auto);
+        assert_eq!(
+            Config::parse("set-option ambiguous-width wide")
+                .unwrap()
+                .ambiguous_width,
+            AmbiguousWidth::Wide
+        );
+        assert!(Config::parse("set-option ambiguous-width cjk").is_err());
+
+        let env = |vars: &'static [(&str, &str)]| {
+            move |name: &str| {
+                let value = vars.iter().find(|(var, _)| *var == name);
+                value.map(|(_, value)| value.to_string())
+            }
+        };
+        assert!(AmbiguousWidth::Auto.is_wide(env(&[("LANG", "ja_JP.UTF-8")])));
+        assert!(AmbiguousWidth::Auto.is_wide(env(&[
+            ("LC_ALL", ""),
+            ("LC_CTYPE", "zh_CN.UTF-8"),
+            ("LANG", "en_GB.UTF-8"),
+        ])));
+        assert!(!AmbiguousWidth::Auto
+            .is_wide(env(&[("LC_ALL", "C.UTF-8"), ("LANG", "ko_KR.UTF-8"),])));
+        assert!(!AmbiguousWidth::Auto.is_wide(env(&[])));
+        assert!(AmbiguousWidth::Wide.is_wide(env(&[])));
+        assert!(!AmbiguousWidth::Narrow.is_wide(env(&[("LANG", "ja_JP")])));
+    }
+}
@@ -0,0 +1,189 @@
+//! Scrolling the active pane back through the rows scrolled away, and
+//! searching them and its screen with `/` and `?`.
+
+use session_manager::{search::Search, session::SessionPane};
+
+use crate::backend::Key;
+
+/// What copy mode made of a key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CopyModeInput {
+    /// It goes on.
+    Pending,
+    /// It ended, showing the pane's screen again.
+    Exit,
+}
+
+/// A pane being scrolled back and searched, its matches highlighted.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CopyMode {
+    /// The last search, whose matches are highlighted.
+    search: Option<Search>,
+    /// The search being typed, with the line at the top when it started.
+    typing: Option<(Search, usize)>,
+    /// The line of the match found last.
+    found: Option<usize>,
+    /// Shown on the status line, e.g. that nothing matched, until taken.
+    message: Option<String>,
+}
+
+impl CopyMode {
+    /// Scroll `pane` with the arrow keys, `j`, `k`, Page Up and Page Down, a
+    /// page being `page` rows, or show its screen again with `q` or Escape, or
+    /// by scrolling to it unless searching. Other keys are ignored, rather than
+    /// typed unseen.
+    ///
+    /// `/` searches down for what is typed after it, and `?` up, moving to the
+    /// first match as it is typed; `n` finds the next match the same way, and
+    /// `N` the next the other way.
+    pub fn handle_key<P: SessionPane>(
+        &mut self,
+        key: Key,
+        pane: &mut P,
+        page: isize,
+    ) -> CopyModeInput {
+        if self.typing.is_some() {
+            self.type_search(key, pane);
+            return CopyModeInput::Pending;
+        }
+        let rows = match key {
+            Key::Char(c @ ('/' | '?')) => {
+                let search = Search {
+                    pattern: String::new(),
+                    forward: c == '/',
+                };
+                self.typing = Some((search, pane.view_top()));
+                return CopyModeInput::Pending;
+            }
+            Key::Char('n') => return self.find_next(true, pane),
+            Key::Char('N') => return self.find_next(false, pane),
+            Key::Char('q') | Key::Esc => return self.exit(pane),
+            Key::Up | Key::Char('k') => 1,
+            Key::Down | Key::Char('j') => -1,
+            Key::PageUp => page,
+            Key::PageDown => -page,
+            _ => return CopyModeInput::Pending,
+        };
+        pane.scroll_view(rows);
+        match pane.view_offset() == 0 && self.search.is_none() {
+            true => self.exit(pane),
+            false => CopyModeInput::Pending,
+        }
+    }
+
+    /// What is shown on the status line while the search is typed, after its
+    /// `/` or `?`.
+    pub fn prompt(&self) -> Option<String> {
+        let (search, _) = self.typing.as_ref()?;
+        let prompt = if search.forward { '/' } else { '?' };
+        Some(format!("{}{}", prompt, search.pattern))
+    }
+
+    /// A message to show on the status line, e.g. that nothing matched, if
+    /// there is a new one.
+    pub fn take_message(&mut self) -> Option<String> {
+        self.message.take()
+    }
+
+    /// Stop highlighting the matches in `pane`, leaving it scrolled back where
+    /// it is.
+    pub fn end<P: SessionPane>(&self, pane: &mut P) {
+        pane.set_highlight(None);
+    }
+
+    fn exit<P: SessionPane>(&self, pane: &mut P) -> CopyModeInput {
+        self.end(pane);
+        pane.scroll_view(isize::MIN);
+        CopyModeInput::Exit
+    }
+
+    /// Edit the search being typed with `key`: Enter finishes it, and Escape,
+    /// or Backspace once it is empty, abandons it.
+    fn type_search<P: SessionPane>(&mut self, key: Key, pane: &mut P) {
+        let (mut search, start) = match self.typing.take() {
+            Some(typing) => typing,
+            None => return,
+        };
+        match key {
+            Key::Char('\n') | Key::Char('\r') => {
+                if search.pattern.is_empty() {
+                    // Like `n`, with the search made last.
+                    if let Some(last) = &mut self.search {
+                        last.forward = search.forward;
+                    }
+                } else {
+                    self.search = Some(search);
+                }
+                pane.set_highlight(self.search.clone());
+                match self.search.clone() {
+                    Some(search) => match pane.find(&search, start, false) {
+                        Some(line) => self.show_match(line, pane),
+                        None => self.not_found(&search, start, pane),
+                    },
+                    None => pane.show_line(start),
+                }
+                return;
+            }
+            Key::Esc => return self.abandon(start, pane),
+            Key::Backspace if search.pattern.is_empty() => {
+                return self.abandon(start, pane);
+            }
+            Key::Backspace => {
+                search.pattern.pop();
+            }
+            Key::Char(c) if !c.is_control() => search.pattern.push(c),
+            _ => (),
+        }
+        // Move to the first match of what has been typed so far, if there is
+        // one, from where the search started.
+        pane.set_highlight(Some(search.clone()).filter(|s| !s.pattern.is_empty()));
+        match pane.find(&search, start, false) {
+            Some(line) => pane.show_line(line),
+            None => pane.show_line(start),
+        }
+        self.typing = Some((search, start));
+    }
+
+    /// Go back to line `start` and the last search's matches, abandoning the
+    /// one typed.
+    fn abandon<P: SessionPane>(&mut self, start: usize, pane: &mut P) {
+        pane.set_highlight(self.search.clone());
+        pane.show_line(start);
+    }
+
+    /// Move to the next match of the last search, going down if `same` and the
+    /// search went down, or up if not.
+    fn find_next<P: SessionPane>(&mut self, same: bool, pane: &mut P) -> CopyModeInput {
+        let mut search = match self.search.clone() {
+            Some(search) => search,
+            None => return CopyModeInput::Pending,
+        };
+        search.forward = search.forward == same;
+        let found = match self.found {
+            Some(line) => pane.find(&search, line, true),
+            None => pane.find(&search, pane.view_top(), false),
+        };
+        match found {
+            Some(line) => self.show_match(line, pane),
+            None => self.not_found(&search, pane.view_top(), pane),
+        }
+        CopyModeInput::Pending
+    }
+
+    /// Move to the match found on `line`.
+    fn show_match<P: SessionPane>(&mut self, line: usize, pane: &mut P) {
+        self.found = Some(line);
+        pane.show_line(line);
+    }
+
+    /// Say that `search` found nothing, staying at line `line`.
+    fn not_found<P: SessionPane>(
+        &mut self,
+        search: &Search,
+        line: usize,
+        pane: &mut P,
+    ) {
+        self.message = Some(format!("not found: {}", search.pattern));
+        pane.show_line(line);
+    }
+}
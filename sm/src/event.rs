@@ -0,0 +1,3111 @@
+//! The event loop driving the server's `Session`s from the terminal of the
+//! client attached.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    io::{self, Write},
+    iter, mem,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, bail, Result};
+use futures::{
+    channel::mpsc::{self, Receiver, UnboundedReceiver, UnboundedSender},
+    future::FutureExt,
+    select,
+    stream::StreamExt,
+};
+use futures_timer::Delay;
+use log::{debug, warn};
+use nix::pty::Winsize;
+
+use session_manager::{
+    applet::{Applet, Clock, Viewer},
+    console::PtyUpdate,
+    history::History,
+    layout::Direction,
+    session::{PaneUpdates, Selection, Session, SessionError, SessionPane},
+    snapshot::SessionSnapshot,
+    util, width,
+};
+
+use crate::{
+    backend::{Event, Key, Modifiers, MouseButton, MouseEvent, MouseKind},
+    bindings::{Action, KeyTable, MouseRegion, ShellOutput, WindowTarget},
+    buffers::{BufferInput, BufferList, PasteBuffers},
+    chooser::{Chooser, ChooserEntry, ChooserInput},
+    clipboard, command,
+    config::{Config, Hook, LastWindowClose},
+    copy_mode::{CopyMode, CopyModeInput},
+    format,
+    key_echo::KeyEcho,
+    linear::LinearOutput,
+    lock::{self, Lock, LockInput},
+    menu::{Menu, MenuInput},
+    mouse::{self, Gesture, MouseOptions, MouseTracker},
+    options,
+    pager::{Pager, PagerInput},
+    palette::{Palette, PaletteInput},
+    prompt::{Prompt, PromptInput},
+    protocol::{self, BufferInfo, PaneInfo, SessionInfo, WindowInfo},
+    server::{Incoming, Pending, Request, Server},
+    size_labels, state,
+    status::{self, StatusBar},
+    style::Theme,
+    summary::{Summary, SummaryInput},
+    tee::Pause,
+    themes,
+    title::TerminalTitle,
+    tree::{Tree, TreeInput, TreePane, TreeSession, TreeTarget, TreeWindow},
+    watch::Watched,
+};
+
+/// The key introducing a multiplexer command.
+const PREFIX: Key = Key::Ctrl('b');
+/// What the prefix key sends to a window.
+const PREFIX_BYTE: u8 = 0x02;
+
+/// Clears the terminal and moves the cursor to its top left corner.
+const CLEAR: &str = "\x1b[H\x1b[2J";
+/// Asks the terminal to report presses, releases and drags, in SGR encoding.
+const MOUSE_CAPTURE: &str = "\x1b[?1002h\x1b[?1006h";
+const MOUSE_RELEASE: &str = "\x1b[?1002l\x1b[?1006l";
+
+/// The fewest columns a terminal needs for windows to be drawn on it.
+const MIN_COLS: u16 = 10;
+/// The fewest rows: a row of the window, and the status line under it.
+const MIN_ROWS: u16 = 2;
+
+/// The size windows are made at when the terminal is too small for them.
+pub const DEFAULT_SIZE: Winsize = Winsize {
+    ws_row: 24,
+    ws_col: 80,
+    ws_xpixel: 0,
+    ws_ypixel: 0,
+};
+
+/// How often the selected window is redrawn.
+const REDRAW_INTERVAL: Duration = Duration::from_millis(16);
+
+/// How long blinking text is shown, and then hidden, when animated.
+const BLINK_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How long after a key bound to an action which repeats it can be pressed
+/// again without the prefix.
+const REPEAT_TIME: Duration = Duration::from_millis(500);
+
+/// How much of a paste buffer's text `sm list-buffers` shows, in characters.
+const SAMPLE_CHARS: usize = 40;
+
+/// How long a search being typed in copy mode is shown on the status line,
+/// at most, should it not be finished.
+const PROMPT_HOLD: Duration = Duration::from_secs(3600);
+
+/// How many rows a turn of the wheel scrolls a pane back or forward.
+const WHEEL_ROWS: isize = 3;
+
+/// How long the screen is inverted for a visual bell in the selected window.
+const SCREEN_FLASH: Duration = Duration::from_millis(150);
+
+/// How long the status line flags a visual bell in another window.
+const ENTRY_FLASH: Duration = Duration::from_secs(1);
+
+/// Invert the whole screen, and put it back.
+const INVERT_SCREEN: &str = "\x1b[?5h";
+const NORMAL_SCREEN: &str = "\x1b[?5l";
+
+/// How often the sessions are saved to the state file, if they have changed.
+const SAVE_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Multiplexes input, output and window updates for the sessions of a server.
+pub struct EventLoop<P: SessionPane, O: Write> {
+    server: Server,
+    input: Receiver<(u64, Event, Vec<u8>)>,
+    /// The client the input being handled came from, which looks at the
+    /// session's selected window.
+    input_client: u64,
+    /// The windows the other clients attached look at, by id.
+    views: BTreeMap<u64, Selection>,
+    /// The clients looking at the selected window as last drawn, which are
+    /// sent `output`.
+    viewers: BTreeSet<u64>,
+    /// Whether the viewers' terminals draw East Asian Ambiguous characters two
+    /// columns wide, as the client typing does.
+    ambiguous_wide: bool,
+    /// The clients looking at other windows, or at the selected one with
+    /// ambiguous characters drawn another width, by window and that width.
+    elsewhere: BTreeMap<(usize, bool), OtherView>,
+    /// The sessions sharing the windows of others, by id.
+    grouped: BTreeMap<u64, GroupedSession>,
+    /// The clients attached to grouped sessions, with the id of the session.
+    via_group: BTreeMap<u64, u64>,
+    /// The same for clients attaching, until they are seen attached.
+    joining: BTreeMap<u64, u64>,
+    /// The windows linked into more than one session.
+    links: Vec<Link>,
+    /// Told when a client attaches, or its terminal is resized.
+    resized: Receiver<()>,
+    /// The requests of clients connecting.
+    requests: Receiver<(Request, Pending)>,
+    output: O,
+    /// The session attached to, or to be attached to next.
+    session: Session<P>,
+    /// Tells `session` apart from the others, whatever they are named.
+    session_id: u64,
+    /// The sessions not attached to, by id.
+    background: BTreeMap<u64, Session<P>>,
+    next_session_id: u64,
+    /// Updates from the panes of every session, with the session's id.
+    pty_updates: Watched<PaneUpdates>,
+    prefix: bool,
+    /// Until when keys bound to actions which repeat work without the prefix,
+    /// the panes being labelled with their sizes meanwhile.
+    repeat_until: Option<Instant>,
+    status: StatusBar,
+    /// Until when the screen is inverted for a visual bell, if it is.
+    flash_until: Option<Instant>,
+    /// The windows flagged in the status line for visual bells, with until
+    /// when.
+    bells: BTreeMap<usize, Instant>,
+    config: Config,
+    /// The configuration file `source-file` reads again, if there is one.
+    config_path: Option<PathBuf>,
+    /// The context menu, if it is open.
+    menu: Option<Menu>,
+    /// Clicks and drags made so far.
+    mouse: MouseTracker,
+    /// The display of keys typed, if it is on.
+    key_echo: Option<KeyEcho>,
+    /// Writes the lines of the active pane in place of the screen, if on.
+    linear: Option<LinearOutput>,
+    /// Whether the terminal is too small to draw on, so that the windows are
+    /// left at their last size and a placeholder is shown until it grows.
+    too_small: bool,
+    /// Sets the title of the client's terminal, if on.
+    title: Option<TerminalTitle>,
+    /// Pauses recording of the output while a private pane is shown.
+    mirror_pause: Option<Pause>,
+    /// What is shown over the windows instead of them, if anything.
+    overlay: Option<Overlay>,
+    /// The prompt on the status line, if it is open.
+    prompt: Option<Prompt>,
+    /// The text copied from the pager in each session, by id.
+    buffers: BTreeMap<u64, PasteBuffers>,
+    /// The commands started by `run-shell`, with their output and where to
+    /// show it, once they finish.
+    shell_output: UnboundedReceiver<(String, String, ShellOutput)>,
+    shell_sender: UnboundedSender<(String, String, ShellOutput)>,
+    /// The commands `if-shell` took, once its condition finished.
+    chosen: UnboundedReceiver<String>,
+    chosen_sender: UnboundedSender<String>,
+    /// What the paste commands started read from the system clipboard.
+    pasted: UnboundedReceiver<String>,
+    paste_sender: UnboundedSender<String>,
+    /// The lock screen, if the screen is locked.
+    lock: Option<Lock>,
+    /// The active pane's scrolling back and searching, if it is.
+    copy_mode: Option<CopyMode>,
+    /// When input last came from a client, to lock the screen once idle.
+    last_input: Instant,
+    /// Whether the text typed on the lock screen unlocks it, once checked.
+    unlocked: UnboundedReceiver<bool>,
+    unlock_sender: UnboundedSender<bool>,
+    /// The commands entered at the command prompt.
+    history: History,
+    /// Where the sessions are saved, if anywhere.
+    state: Option<PathBuf>,
+    /// The sessions as last saved.
+    saved: Vec<SessionSnapshot>,
+    /// The sessions to start again in place of a new one.
+    restore: Vec<SessionSnapshot>,
+    /// Whether the summary of a session was dismissed, or the session killed,
+    /// to end the session.
+    quit_session: bool,
+    /// Whether the actions of a hook are being performed, for them not to run
+    /// hooks in turn, e.g. `new-window` set on `after-new-window`.
+    in_hook: bool,
+    /// How many windows of the session attached had closed when the windows
+    /// were last numbered again.
+    closed_seen: usize,
+}
+
+/// Clients looking at another window than the selected one, which are drawn
+/// it with a status line of their own, but nothing over it.
+struct OtherView {
+    clients: BTreeSet<u64>,
+    status: StatusBar,
+    /// Whether their screens are to be cleared and drawn in full.
+    fresh: bool,
+}
+
+/// A session sharing the windows of another, and of any others grouped with
+/// it, but with a selected window of its own.
+struct GroupedSession {
+    name: String,
+    /// The id of the session whose windows it shares.
+    target: u64,
+    /// The windows its clients look at, as last seen.
+    selection: Selection,
+}
+
+/// A window linked into several sessions. It is in one of them at a time: the
+/// session attached whenever it is linked into that one.
+struct Link {
+    /// The window's id, which it keeps as it moves.
+    window: usize,
+    /// The ids of the sessions it is linked into, with its index in each; that
+    /// in the session holding it is brought up to date as it leaves.
+    members: BTreeMap<u64, usize>,
+    /// The id of the session it is in.
+    holder: u64,
+}
+
+/// A view covering the windows, which gets all input until it is closed.
+enum Overlay {
+    /// Long output or errors.
+    Pager(Pager),
+    /// The window chooser.
+    Chooser(Chooser),
+    /// The command palette.
+    Palette(Palette),
+    /// The paste buffers.
+    Buffers(BufferList),
+    /// How the windows of the session exited, once the last has.
+    Summary(Summary),
+    /// The tree of sessions, windows and panes.
+    Tree(Tree),
+}
+
+impl Overlay {
+    fn draw<O: Write>(
+        &mut self,
+        output: &mut O,
+        area: Winsize,
+        theme: &Theme,
+    ) -> Result<()> {
+        match self {
+            Overlay::Pager(pager) => pager.draw(output, area, theme)?,
+            Overlay::Chooser(chooser) => chooser.draw(output, area)?,
+            Overlay::Palette(palette) => palette.draw(output, area)?,
+            Overlay::Buffers(list) => list.draw(output, area)?,
+            Overlay::Summary(summary) => summary.draw(output, area)?,
+            Overlay::Tree(tree) => tree.draw(output, area)?,
+        }
+        Ok(())
+    }
+
+    fn mark_dirty(&mut self) {
+        match self {
+            Overlay::Pager(pager) => pager.mark_dirty(),
+            Overlay::Chooser(chooser) => chooser.mark_dirty(),
+            Overlay::Palette(palette) => palette.mark_dirty(),
+            Overlay::Buffers(list) => list.mark_dirty(),
+            Overlay::Summary(summary) => summary.mark_dirty(),
+            Overlay::Tree(tree) => tree.mark_dirty(),
+        }
+    }
+}
+
+/// The part of a terminal of size `size` given to windows: all but the status
+/// line.
+fn window_area(size: Winsize) -> Winsize {
+    Winsize {
+        ws_row: size.ws_row.saturating_sub(1).max(1),
+        ..size
+    }
+}
+
+/// Whether a terminal of `size` is too small to draw windows on, as one which is
+/// starting may briefly say it has no rows or columns.
+pub fn too_small(size: Winsize) -> bool {
+    size.ws_col < MIN_COLS || size.ws_row < MIN_ROWS
+}
+
+/// `format` with its variables expanded for `session`, with `bytes_out` bytes
+/// sent to the client; see `format::variable`.
+fn expand<P: SessionPane>(
+    session: &Session<P>,
+    format: &str,
+    bytes_out: u64,
+) -> String {
+    format::expand(format, |name| format::variable(session, name, bytes_out))
+}
+
+/// Whether animated blinking text is shown at `elapsed` into the session.
+fn blink_visible(elapsed: Duration) -> bool {
+    (elapsed.as_millis() / BLINK_INTERVAL.as_millis()).is_multiple_of(2)
+}
+
+impl<P: SessionPane, O: Write> EventLoop<P, O> {
+    /// Create an event loop starting with `session`, serving the clients of
+    /// `server`, whose requests and input come from `incoming`, and drawing to
+    /// `output`.
+    pub fn new(
+        server: Server,
+        incoming: Incoming,
+        output: O,
+        session: Session<P>,
+        config: Config,
+    ) -> EventLoop<P, O> {
+        let (shell_sender, shell_output) = mpsc::unbounded();
+        let (paste_sender, pasted) = mpsc::unbounded();
+        let (chosen_sender, chosen) = mpsc::unbounded();
+        let history_limit = config.history_limit.unwrap_or(History::DEFAULT_CAPACITY);
+        let (unlock_sender, unlocked) = mpsc::unbounded();
+        let mut status = StatusBar::default();
+        status.set_right(config.status_right.clone());
+        status.set_theme(config.theme());
+        let title = config
+            .set_titles
+            .then(|| TerminalTitle::new(config.set_titles_string.clone()));
+        EventLoop {
+            server,
+            input: incoming.input,
+            input_client: 0,
+            views: BTreeMap::new(),
+            viewers: BTreeSet::new(),
+            ambiguous_wide: false,
+            elsewhere: BTreeMap::new(),
+            grouped: BTreeMap::new(),
+            via_group: BTreeMap::new(),
+            joining: BTreeMap::new(),
+            links: Vec::new(),
+            resized: incoming.resized,
+            requests: incoming.requests,
+            output,
+            session,
+            session_id: 0,
+            background: BTreeMap::new(),
+            next_session_id: 1,
+            pty_updates: Watched::default(),
+            prefix: false,
+            repeat_until: None,
+            status,
+            flash_until: None,
+            bells: BTreeMap::new(),
+            config,
+            config_path: None,
+            menu: None,
+            mouse: MouseTracker::default(),
+            key_echo: None,
+            linear: None,
+            too_small: false,
+            title,
+            mirror_pause: None,
+            overlay: None,
+            prompt: None,
+            buffers: BTreeMap::new(),
+            shell_output,
+            shell_sender,
+            chosen,
+            chosen_sender,
+            pasted,
+            paste_sender,
+            lock: None,
+            copy_mode: None,
+            last_input: Instant::now(),
+            unlocked,
+            unlock_sender,
+            history: History::new(history_limit),
+            state: None,
+            saved: Vec::new(),
+            restore: Vec::new(),
+            quit_session: false,
+            in_hook: false,
+            closed_seen: 0,
+        }
+    }
+
+    /// Save the sessions to `path` every so often, for `sm restore`, and remove
+    /// it once the last session ends.
+    pub fn set_state_path(&mut self, path: PathBuf) {
+        self.state = Some(path);
+    }
+
+    /// Read the configuration again from `path` on `source-file`.
+    pub fn set_config_path(&mut self, path: PathBuf) {
+        self.config_path = Some(path);
+    }
+
+    /// Keep the commands entered at the command prompt in `history`.
+    pub fn set_history(&mut self, history: History) {
+        self.history = history;
+    }
+
+    /// Start `sessions` again when run, rather than a new session.
+    pub fn set_restore(&mut self, sessions: Vec<SessionSnapshot>) {
+        self.restore = sessions;
+    }
+
+    /// Pause recording of the output with `pause` while a private pane is shown.
+    pub fn set_mirror_pause(&mut self, pause: Pause) {
+        self.mirror_pause = Some(pause);
+    }
+
+    /// Set the thresholds for recognising double clicks and drags.
+    pub fn set_mouse_options(&mut self, options: MouseOptions) {
+        self.mouse = MouseTracker::new(options);
+    }
+
+    /// Show the keys typed into the active pane on screen until `timeout` after
+    /// the last, or not at all if it is `None`.
+    pub fn set_show_keys(&mut self, timeout: Option<Duration>) {
+        self.key_echo = timeout.map(KeyEcho::new);
+    }
+
+    /// Write only the lines the active pane adds, one after another, rather than
+    /// drawing the windows and status line, for screen readers. Overlays,
+    /// menus and prompts are still drawn, and cleared away once closed.
+    pub fn set_linear_output(&mut self, linear: bool) {
+        self.session.set_line_log(linear);
+        self.linear = linear.then(LinearOutput::default);
+    }
+
+    /// Run until the last window of every session exits, detaching the client
+    /// from each session as its last window does.
+    pub async fn run(&mut self) -> Result<()> {
+        if too_small(self.server.size()) {
+            self.show_too_small()?;
+        } else {
+            self.session.resize(window_area(self.server.size()))?;
+        }
+        if self.restore.is_empty() {
+            self.start_session()?;
+        } else {
+            self.restore_sessions()?;
+        }
+        let start = Instant::now();
+        let mut redraw = Delay::new(REDRAW_INTERVAL).fuse();
+        let mut save = Delay::new(SAVE_INTERVAL).fuse();
+        loop {
+            select! {
+                event = self.input.next() => match event {
+                    Some((client, event, data)) => {
+                        self.last_input = Instant::now();
+                        self.focus_client(client);
+                        self.handle_input(event, &data)?;
+                        // The last window may have been killed.
+                        if !self.replace_closed_session()? {
+                            break;
+                        }
+                    }
+                    None => break,
+                },
+                _ = self.resized.select_next_some() => {
+                    // The terminal may be a new one, so draw it all.
+                    self.close_menu()?;
+                    if too_small(self.server.size()) {
+                        self.show_too_small()?;
+                    } else {
+                        self.too_small = false;
+                        self.session.resize(window_area(self.server.size()))?;
+                        self.redraw_all()?;
+                        for view in self.elsewhere.values_mut() {
+                            view.fresh = true;
+                        }
+                    }
+                },
+                (request, pending) = self.requests.select_next_some() => {
+                    self.handle_request(request, pending)?;
+                    if !self.replace_closed_session()? {
+                        break;
+                    }
+                },
+                (command, text, output) = self.shell_output.select_next_some() => {
+                    self.show_shell_output(&command, &text, output)?;
+                },
+                line = self.chosen.select_next_some() => {
+                    self.perform_commands("if-shell", &line)?;
+                },
+                text = self.pasted.select_next_some() => {
+                    if self.session.selected_window_idx().is_some() {
+                        self.session.paste(&text)?;
+                    }
+                },
+                unlocked = self.unlocked.select_next_some() => {
+                    if unlocked {
+                        self.unlock()?;
+                    } else if let Some(lock) = &mut self.lock {
+                        lock.fail();
+                    }
+                },
+                (id, update) = self.pty_updates.select_next_some() => {
+                    let id = self.pane_session(id, update.pane_idx);
+                    if id == self.session_id {
+                        let exited = matches!(update.data, PtyUpdate::Exited { .. });
+                        self.session.pty_update(update)?;
+                        if !self.replace_closed_session()? {
+                            break;
+                        }
+                        if exited {
+                            self.run_hooks(Hook::PaneExited)?;
+                        }
+                    } else if let Some(session) = self.background.get_mut(&id) {
+                        session.pty_update(update)?;
+                        self.prune_links();
+                        if self.is_finished(id) {
+                            self.forget_session(id);
+                        }
+                    }
+                },
+                _ = redraw => {
+                    let now = Instant::now();
+                    for session in iter::once(&mut self.session)
+                        .chain(self.background.values_mut())
+                    {
+                        session.advance_slow_print(now);
+                        session.tick_applets();
+                    }
+                    if self.repeat_until.is_some_and(|until| now >= until) {
+                        self.end_repeat();
+                    }
+                    self.ring_bells(now)?;
+                    if !self.session.take_activity().is_empty() {
+                        self.run_hooks(Hook::AlertActivity)?;
+                    }
+                    let idle = self
+                        .config
+                        .lock_after
+                        .is_some_and(|after| now >= self.last_input + after);
+                    if idle {
+                        self.lock_screen();
+                    }
+                    self.session.set_blink_phase(blink_visible(start.elapsed()));
+                    if let Some(pause) = &self.mirror_pause {
+                        pause.set(self.session.is_showing_private());
+                    }
+                    self.sort_clients()?;
+                    let selected_dirty = self
+                        .session
+                        .selected_window_idx()
+                        .is_some_and(|idx| self.session.is_window_dirty(idx));
+                    if self.too_small {
+                        // Nothing is drawn until the terminal grows.
+                    } else if let Some(lock) = &mut self.lock {
+                        lock.draw(&mut self.output, self.server.size())?;
+                    } else if self.linear.is_some() && !self.is_covered() {
+                        self.write_lines()?;
+                    } else {
+                        self.draw()?;
+                    }
+                    let locked = self.lock.is_some() || self.too_small;
+                    if let Some(title) = self.title.as_mut().filter(|_| !locked) {
+                        let bytes_out = self.server.bytes_written();
+                        title.draw(&mut self.output, &self.session, bytes_out)?;
+                    }
+                    self.output.flush()?;
+                    if !self.too_small {
+                        self.draw_elsewhere(selected_dirty)?;
+                    }
+                    redraw = Delay::new(REDRAW_INTERVAL).fuse();
+                },
+                _ = save => {
+                    self.save_state();
+                    save = Delay::new(SAVE_INTERVAL).fuse();
+                },
+            }
+        }
+
+        self.output.flush()?;
+        self.server.detach("no more windows");
+        if let Some(path) = &self.state {
+            // Nothing is left to restore.
+            fs::remove_file(path).ok();
+        }
+        Ok(())
+    }
+
+    /// Whether anything is drawn over the windows.
+    fn is_covered(&self) -> bool {
+        self.overlay.is_some() || self.menu.is_some() || self.prompt.is_some()
+    }
+
+    /// Clear the terminal, too small to draw the windows on, and say so on it.
+    fn show_too_small(&mut self) -> Result<()> {
+        self.too_small = true;
+        let size = self.server.size();
+        write!(self.output, "{}", CLEAR)?;
+        if size.ws_row > 0 {
+            let text = format!("terminal too small: {}x{}", size.ws_col, size.ws_row);
+            let text = width::truncate(&text, size.ws_col.into());
+            write!(self.output, "{}", text)?;
+        }
+        self.output.flush()?;
+        Ok(())
+    }
+
+    /// Clear the terminal and draw everything on it again, e.g. as it may be a
+    /// new one.
+    fn redraw_all(&mut self) -> Result<()> {
+        if let Some(lock) = &mut self.lock {
+            lock.mark_dirty();
+        }
+        if let Some(overlay) = &mut self.overlay {
+            overlay.mark_dirty();
+        }
+        if let Some(prompt) = &mut self.prompt {
+            prompt.mark_dirty();
+        }
+        self.session.mark_dirty();
+        self.status.invalidate();
+        if let Some(linear) = &mut self.linear {
+            linear.reset();
+        }
+        if let Some(title) = &mut self.title {
+            title.invalidate();
+        }
+        write!(self.output, "{}", CLEAR)?;
+        if self.config.captures_mouse() {
+            write!(self.output, "{}", MOUSE_CAPTURE)?;
+        }
+        Ok(())
+    }
+
+    /// Make `client` the one looking at the session's selected window, whose
+    /// keys act on it, keeping the selection of the client before.
+    fn focus_client(&mut self, client: u64) {
+        if client == self.input_client {
+            return;
+        }
+        let previous = mem::replace(&mut self.input_client, client);
+        self.views.insert(previous, self.session.selection());
+        if let Some(selection) = self.views.remove(&client) {
+            self.session.set_selection(selection);
+        }
+    }
+
+    /// Sort the clients attached by the window they look at: those looking at
+    /// the selected window are sent `output`, and the others are drawn their
+    /// own windows by `draw_elsewhere`. Clients which have moved are drawn in
+    /// full.
+    fn sort_clients(&mut self) -> Result<()> {
+        let clients = self.server.clients();
+        for client in &clients {
+            if let Some(id) = self.joining.remove(&client.id) {
+                // It looks at the windows its session last did.
+                if let Some(grouped) = self.grouped.get(&id) {
+                    self.views.insert(client.id, grouped.selection);
+                }
+                self.via_group.insert(client.id, id);
+            }
+        }
+        self.views
+            .retain(|id, _| clients.iter().any(|client| client.id == *id));
+        self.via_group
+            .retain(|id, _| clients.iter().any(|client| client.id == *id));
+        let selection = self.session.selection();
+        let mut viewers = BTreeSet::new();
+        let mut elsewhere: BTreeMap<_, BTreeSet<u64>> = BTreeMap::new();
+        // The viewers are drawn for the terminal of the client typing.
+        let ambiguous_wide = clients
+            .iter()
+            .find(|client| client.id == self.input_client)
+            .or(clients.first())
+            .is_some_and(|client| client.ambiguous_wide);
+        for client in clients {
+            // Every client is shown the lock screen.
+            if client.id == self.input_client || self.lock.is_some() {
+                viewers.insert(client.id);
+                continue;
+            }
+            // A client attaching looks at the selected window at first.
+            let view = self.views.entry(client.id).or_insert(selection);
+            let wide = client.ambiguous_wide;
+            match view.window {
+                Some(_)
+                    if view.window == selection.window && wide == ambiguous_wide =>
+                {
+                    viewers.insert(client.id);
+                }
+                Some(idx) if self.session.window_name(idx).is_some() => {
+                    elsewhere.entry((idx, wide)).or_default().insert(client.id);
+                }
+                _ => {
+                    // Its window closed.
+                    *view = selection;
+                    viewers.insert(client.id);
+                }
+            }
+        }
+        if viewers != self.viewers || ambiguous_wide != self.ambiguous_wide {
+            self.viewers = viewers;
+            self.ambiguous_wide = ambiguous_wide;
+            width::set_ambiguous_wide(ambiguous_wide);
+            self.redraw_all()?;
+        }
+        for (client, id) in &self.via_group {
+            let view = match *client == self.input_client {
+                true => Some(self.session.selection()),
+                false => self.views.get(client).copied(),
+            };
+            if let (Some(grouped), Some(view)) = (self.grouped.get_mut(id), view) {
+                grouped.selection = view;
+            }
+        }
+        self.elsewhere
+            .retain(|key, view| elsewhere.get(key) == Some(&view.clients));
+        let all = elsewhere.values().flatten().copied().collect();
+        for (key, clients) in elsewhere {
+            self.elsewhere.entry(key).or_insert_with(|| OtherView {
+                clients,
+                status: StatusBar::default(),
+                fresh: true,
+            });
+        }
+        self.server.set_elsewhere(all);
+        Ok(())
+    }
+
+    /// Draw the clients looking at other windows than the selected one their
+    /// windows and status lines; `selected_dirty` says whether the selected
+    /// window had changed before it was drawn.
+    fn draw_elsewhere(&mut self, selected_dirty: bool) -> Result<()> {
+        if self.elsewhere.is_empty() {
+            return Ok(());
+        }
+        let selection = self.session.selection();
+        let size = self.server.size();
+        let bytes_out = self.server.bytes_written();
+        let bells: BTreeSet<_> = self.bells.keys().copied().collect();
+        // Whether each window drawn had changed before it first was.
+        let mut dirty: BTreeMap<_, _> = selection
+            .window
+            .map(|idx| (idx, selected_dirty))
+            .into_iter()
+            .collect();
+        for (&(idx, wide), view) in &mut self.elsewhere {
+            width::set_ambiguous_wide(wide);
+            let mut output = self.server.output_to(view.clients.clone());
+            self.session.set_selection(Selection {
+                window: Some(idx),
+                last: None,
+            });
+            match dirty.get(&idx) {
+                // What changed has been drawn for clients at another width, so
+                // it is all drawn again.
+                Some(true) => self.session.mark_dirty(),
+                Some(false) => (),
+                None => {
+                    dirty.insert(idx, self.session.is_window_dirty(idx));
+                }
+            }
+            if view.fresh {
+                write!(output, "{}", CLEAR)?;
+                if self.config.captures_mouse() {
+                    write!(output, "{}", MOUSE_CAPTURE)?;
+                }
+                view.status.set_right(self.config.status_right.clone());
+                view.status.set_theme(self.config.theme());
+                self.session.mark_dirty();
+                view.fresh = false;
+            }
+            view.status.set_bells(bells.clone());
+            view.status
+                .draw(&mut output, &self.session, size, bytes_out)?;
+            self.session.redraw_window(idx, &mut output)?;
+            output.flush()?;
+        }
+        width::set_ambiguous_wide(self.ambiguous_wide);
+        self.session.set_selection(selection);
+        Ok(())
+    }
+
+    /// Draw the status line, the selected window and anything over it.
+    fn draw(&mut self) -> Result<()> {
+        let size = self.server.size();
+        if let Some(linear) = &mut self.linear {
+            // The lines are written again once nothing covers them.
+            linear.reset();
+        } else if self.prompt.is_none() {
+            let bytes_out = self.server.bytes_written();
+            self.status
+                .draw(&mut self.output, &self.session, size, bytes_out)?;
+        }
+        let area = window_area(size);
+        if let Some(overlay) = &mut self.overlay {
+            overlay.draw(&mut self.output, area, &self.config.theme())?;
+        } else if self.linear.is_none() {
+            self.session.redraw(&mut self.output)?;
+            if self.repeat_until.is_some() {
+                size_labels::draw(&mut self.output, &self.session.pane_rects())?;
+            }
+        }
+        if let Some(menu) = &self.menu {
+            // Panes may have drawn over parts of the menu.
+            menu.draw(&mut self.output)?;
+        }
+        if let Some(echo) = &mut self.key_echo {
+            if echo.draw(&mut self.output, area, Instant::now())? {
+                self.session.mark_dirty();
+                if let Some(overlay) = &mut self.overlay {
+                    overlay.mark_dirty();
+                }
+            }
+        }
+        if let Some(prompt) = &mut self.prompt {
+            // Last, to leave the cursor in the prompt.
+            prompt.draw(&mut self.output, size, &self.config.theme().message)?;
+        }
+        Ok(())
+    }
+
+    /// Ring the client's bell for windows whose bells rang, or flash them if
+    /// the bell is visual: the screen for the selected window, and the entry
+    /// in the status line for others.
+    fn ring_bells(&mut self, now: Instant) -> Result<()> {
+        let rung = self.session.take_bells();
+        if !rung.is_empty() {
+            self.run_hooks(Hook::AlertBell)?;
+        }
+        if !self.config.visual_bell {
+            if !rung.is_empty() {
+                write!(self.output, "\x07")?;
+            }
+            return Ok(());
+        }
+        let selected = self.session.selected_window_idx();
+        for idx in rung {
+            if Some(idx) == selected {
+                if self.flash_until.is_none() {
+                    write!(self.output, "{}", INVERT_SCREEN)?;
+                }
+                self.flash_until = Some(now + SCREEN_FLASH);
+            } else {
+                self.bells.insert(idx, now + ENTRY_FLASH);
+            }
+        }
+        if self.flash_until.is_some_and(|until| now >= until) {
+            self.flash_until = None;
+            write!(self.output, "{}", NORMAL_SCREEN)?;
+        }
+        self.bells.retain(|_, until| now < *until);
+        self.status.set_bells(self.bells.keys().copied().collect());
+        Ok(())
+    }
+
+    /// Write the lines the active pane of the selected window has added.
+    fn write_lines(&mut self) -> Result<()> {
+        let new_lines = match self.session.take_lines() {
+            Some(new_lines) => new_lines,
+            None => return Ok(()),
+        };
+        let heading = match self.session.selected_window_idx() {
+            Some(window) => format!(
+                "{} {}: {}",
+                self.session.name(),
+                window,
+                self.session.window_name(window).unwrap_or("")
+            ),
+            None => self.session.name().to_string(),
+        };
+        if let Some(linear) = &mut self.linear {
+            linear.write(&mut self.output, &new_lines, &heading)?;
+        }
+        Ok(())
+    }
+
+    /// Save the sessions to the state file, if there is one and they have
+    /// changed since they were last saved.
+    fn save_state(&mut self) {
+        let path = match &self.state {
+            Some(path) => path,
+            None => return,
+        };
+        let mut sessions: Vec<_> = self
+            .sessions()
+            .map(|(_, session)| session.snapshot())
+            .filter(|session| !session.windows.is_empty())
+            .collect();
+        sessions.sort_by(|a, b| a.name.cmp(&b.name));
+        if sessions == self.saved {
+            return;
+        }
+        match state::save(path, &sessions) {
+            Ok(()) => self.saved = sessions,
+            Err(e) => warn!("failed to save sessions to {}: {}", path.display(), e),
+        }
+    }
+
+    /// Start the sessions to restore again, the first in the session the loop
+    /// was created with.
+    fn restore_sessions(&mut self) -> Result<()> {
+        for (i, snapshot) in mem::take(&mut self.restore).iter().enumerate() {
+            if i > 0 {
+                let id = self.next_session_id;
+                self.next_session_id += 1;
+                self.background
+                    .insert(id, self.session.sibling(&snapshot.name));
+                self.switch_session(id)?;
+            }
+            for updates in self.session.restore(snapshot)? {
+                self.watch(updates);
+            }
+        }
+        Ok(())
+    }
+
+    /// Answer a client's `request`.
+    fn handle_request(&mut self, request: Request, pending: Pending) -> Result<()> {
+        match request {
+            Request::NewSession { name, group, env } => {
+                let name = name.unwrap_or_else(|| self.unused_session_name());
+                if self.name_taken(&name) {
+                    pending.refuse(&format!("duplicate session: {}", name));
+                    return Ok(());
+                }
+                if let Some(group) = group {
+                    let target = self.find_session(&group).or_else(|| {
+                        let id = self.find_grouped(&group)?;
+                        Some(self.grouped[&id].target)
+                    });
+                    let target = match target {
+                        Some(target) => target,
+                        None => {
+                            pending.refuse(&format!("no session: {}", group));
+                            return Ok(());
+                        }
+                    };
+                    let selection = match self.background.get(&target) {
+                        Some(session) => session.selection(),
+                        None => self.session.selection(),
+                    };
+                    let id = self.next_session_id;
+                    self.next_session_id += 1;
+                    let grouped = GroupedSession {
+                        name,
+                        target,
+                        selection,
+                    };
+                    self.grouped.insert(id, grouped);
+                    return self.attach_grouped(id, &env, pending);
+                }
+                let id = self.next_session_id;
+                self.next_session_id += 1;
+                self.background.insert(id, self.session.sibling(&name));
+                // The clients attached share a session.
+                self.server.detach("detached by another client");
+                self.switch_session(id)?;
+                self.update_environment(&env);
+                self.start_session()?;
+                self.server.attach(pending);
+                self.run_hooks(Hook::ClientAttached)?;
+            }
+            Request::Attach { name, detach, env } => {
+                if detach {
+                    // Before it attaches, so all but it.
+                    self.server.detach("detached by another client");
+                }
+                if let Some(id) = name.as_deref().and_then(|n| self.find_grouped(n)) {
+                    return self.attach_grouped(id, &env, pending);
+                }
+                let id = match name {
+                    Some(name) => match self.find_session(&name) {
+                        Some(id) => id,
+                        None => {
+                            pending.refuse(&format!("no session: {}", name));
+                            return Ok(());
+                        }
+                    },
+                    None => self.session_id,
+                };
+                if id != self.session_id {
+                    // The clients attached share a session.
+                    self.server.detach("detached by another client");
+                }
+                self.switch_session(id)?;
+                self.update_environment(&env);
+                self.server.attach(pending);
+                self.run_hooks(Hook::ClientAttached)?;
+            }
+            Request::ListSessions => {
+                // Those attached to grouped sessions are not attached to this.
+                let attached = self
+                    .server
+                    .clients()
+                    .iter()
+                    .any(|client| !self.via_group.contains_key(&client.id));
+                let mut sessions: Vec<_> = self
+                    .sessions()
+                    .map(|(id, session)| SessionInfo {
+                        name: session.name().to_string(),
+                        windows: self.window_count(id) as u32,
+                        attached: attached && id == self.session_id,
+                        group: None,
+                    })
+                    .collect();
+                for (id, grouped) in &self.grouped {
+                    let target = self
+                        .sessions()
+                        .find(|(target, _)| *target == grouped.target)
+                        .map(|(_, session)| session);
+                    sessions.push(SessionInfo {
+                        name: grouped.name.clone(),
+                        windows: self.window_count(grouped.target) as u32,
+                        attached: self.via_group.values().any(|via| via == id),
+                        group: target.map(|session| session.name().to_string()),
+                    });
+                }
+                sessions.sort_by(|a, b| a.name.cmp(&b.name));
+                pending.list_sessions(sessions);
+            }
+            Request::ListWindows(name) => self.list_windows(name.as_deref(), pending),
+            Request::ListPanes(name) => self.list_panes(name.as_deref(), pending),
+            Request::ListClients => pending.list_clients(self.list_clients()),
+            Request::ListBuffers(name) => self.list_buffers(name.as_deref(), pending),
+            Request::Info => pending.info(self.info()),
+            Request::CapturePane(name) => self.capture_pane(name.as_deref(), pending),
+            Request::ExportSession(name) => {
+                self.export_session(name.as_deref(), pending)
+            }
+            Request::DisplayMessage {
+                name,
+                format,
+                print,
+            } => self.answer_display_message(name.as_deref(), &format, print, pending),
+            Request::RenameSession { name, new_name } => {
+                match self.rename_session(name.as_deref(), &new_name) {
+                    Ok(()) => pending.info(String::new()),
+                    Err(e) => pending.refuse(&e.to_string()),
+                }
+            }
+            Request::MergeSession { source, target } => {
+                match self.merge_session(&source, target.as_deref()) {
+                    Ok(()) => pending.info(String::new()),
+                    Err(e) => pending.refuse(&e.to_string()),
+                }
+            }
+            Request::KillSession(name) => {
+                if let Some(id) = name.as_deref().and_then(|n| self.find_grouped(n)) {
+                    // Only the name goes; the windows are the other session's.
+                    self.grouped.remove(&id);
+                    let clients = self.via_group.iter().filter(|(_, via)| **via == id);
+                    let clients: Vec<_> = clients.map(|(client, _)| *client).collect();
+                    for client in clients {
+                        self.server.detach_client(client, "session killed");
+                    }
+                    pending.info(String::new());
+                    return Ok(());
+                }
+                match self.lookup(name.as_deref()) {
+                    Some((id, _)) => {
+                        self.kill_session(id)?;
+                        pending.info(String::new());
+                    }
+                    None => pending.refuse(&format!("no session: {}", name.unwrap())),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Answer `pending` with `format` expanded for the session named `name`, or
+    /// the one attached to, if `print`, or show it on the status line.
+    fn answer_display_message(
+        &mut self,
+        name: Option<&str>,
+        format: &str,
+        print: bool,
+        pending: Pending,
+    ) {
+        let (id, selection) = match self.lookup(name) {
+            Some(found) => found,
+            None => return pending.refuse(&format!("no session: {}", name.unwrap())),
+        };
+        let bytes_out = self.server.bytes_written();
+        if !print && id != self.session_id {
+            let message = format!("no client is attached to {}", name.unwrap());
+            return pending.refuse(&message);
+        }
+        let session = match self.background.get_mut(&id) {
+            Some(session) => session,
+            None => &mut self.session,
+        };
+        // A grouped session looks at a window of its own.
+        let previous = session.selection();
+        if let Some(selection) = selection {
+            session.set_selection(selection);
+        }
+        let text = expand(session, format, bytes_out);
+        session.set_selection(previous);
+        if print {
+            pending.info(format!("{}\n", text));
+        } else {
+            self.display_message(&text);
+            pending.info(String::new());
+        }
+    }
+
+    /// Show `text` in place of the status line for the display time.
+    fn display_message(&mut self, text: &str) {
+        let time = self.config.display_time;
+        let until = Instant::now() + time.unwrap_or(status::DEFAULT_DISPLAY_TIME);
+        self.status.show_message(text, until);
+    }
+
+    /// The id of the session holding the windows of the session named `name`,
+    /// or of the one attached to, with what the session named looks at if it
+    /// is grouped with another.
+    fn lookup(&self, name: Option<&str>) -> Option<(u64, Option<Selection>)> {
+        let name = match name {
+            None => return Some((self.session_id, None)),
+            Some(name) => name,
+        };
+        match (self.find_grouped(name), self.find_session(name)) {
+            (Some(id), _) => {
+                let grouped = &self.grouped[&id];
+                Some((grouped.target, Some(grouped.selection)))
+            }
+            (None, Some(id)) => Some((id, None)),
+            (None, None) => None,
+        }
+    }
+
+    /// Answer `pending` with the windows of the session named `name`, or of the
+    /// one attached to.
+    fn list_windows(&self, name: Option<&str>, pending: Pending) {
+        let (id, selection) = match self.lookup(name) {
+            Some(found) => found,
+            None => return pending.refuse(&format!("no session: {}", name.unwrap())),
+        };
+        let session = match self.background.get(&id) {
+            Some(session) => session,
+            None => &self.session,
+        };
+        // A grouped session selects a window of its own.
+        let selected = match selection {
+            Some(selection) => selection.window,
+            None => session.selected_window_idx(),
+        };
+        let windows = session
+            .windows()
+            .map(|(idx, _)| WindowInfo {
+                idx: idx as u32,
+                name: session.window_name(idx).unwrap_or_default().to_string(),
+                panes: session.pane_count(idx).unwrap_or(0) as u32,
+                active: selected == Some(idx),
+            })
+            .collect();
+        pending.list_windows(windows);
+    }
+
+    /// Answer `pending` with the panes of each window of the session named
+    /// `name`, or of the one attached to.
+    fn list_panes(&self, name: Option<&str>, pending: Pending) {
+        let (id, _) = match self.lookup(name) {
+            Some(found) => found,
+            None => return pending.refuse(&format!("no session: {}", name.unwrap())),
+        };
+        let session = match self.background.get(&id) {
+            Some(session) => session,
+            None => &self.session,
+        };
+        let mut panes = Vec::new();
+        for (idx, _) in session.windows() {
+            let rects = session.window_pane_rects(idx);
+            for (number, pane, active) in session.window_panes(idx) {
+                let rect = rects.iter().find(|(n, _)| *n == number).map(|(_, r)| r);
+                panes.push(PaneInfo {
+                    window: idx as u32,
+                    idx: number as u32,
+                    cols: rect.map_or(0, |rect| u32::from(rect.cols)),
+                    rows: rect.map_or(0, |rect| u32::from(rect.rows)),
+                    active,
+                    title: pane.title().map(str::to_string),
+                });
+            }
+        }
+        pending.list_panes(panes);
+    }
+
+    /// The clients attached, with the session each is attached to.
+    fn list_clients(&self) -> Vec<protocol::ClientInfo> {
+        self.server
+            .clients()
+            .into_iter()
+            .map(|client| {
+                let session = match self.via_group.get(&client.id) {
+                    Some(id) => self.grouped[id].name.clone(),
+                    None => self.session.name().to_string(),
+                };
+                let size = client.size;
+                protocol::ClientInfo {
+                    id: client.id,
+                    session,
+                    cols: size.map_or(0, |size| u32::from(size.ws_col)),
+                    rows: size.map_or(0, |size| u32::from(size.ws_row)),
+                    bytes: client.bytes_written,
+                }
+            })
+            .collect()
+    }
+
+    /// Answer `pending` with the paste buffers of the session named `name`, or
+    /// of the one attached to, newest first.
+    fn list_buffers(&self, name: Option<&str>, pending: Pending) {
+        let (id, _) = match self.lookup(name) {
+            Some(found) => found,
+            None => return pending.refuse(&format!("no session: {}", name.unwrap())),
+        };
+        let buffers = self.buffers.get(&id).into_iter().flat_map(|b| b.iter());
+        let buffers = buffers
+            .map(|buffer| BufferInfo {
+                name: buffer.name.clone(),
+                bytes: buffer.text.len() as u64,
+                source: buffer.source.clone(),
+                sample: buffer.preview().chars().take(SAMPLE_CHARS).collect(),
+            })
+            .collect();
+        pending.list_buffers(buffers);
+    }
+
+    /// Answer `pending` with what the active pane of the session named `name`
+    /// shows, or of the one attached to, unless the pane is private.
+    fn capture_pane(&mut self, name: Option<&str>, pending: Pending) {
+        let (id, selection) = match self.lookup(name) {
+            Some(found) => found,
+            None => return pending.refuse(&format!("no session: {}", name.unwrap())),
+        };
+        let session = match self.background.get_mut(&id) {
+            Some(session) => session,
+            None => &mut self.session,
+        };
+        // A grouped session looks at a window of its own.
+        let previous = session.selection();
+        if let Some(selection) = selection {
+            session.set_selection(selection);
+        }
+        let private = session.is_showing_private();
+        let rect = session.active_rect();
+        let captured = match (rect, session.active_pane_mut()) {
+            _ if private => Err("the pane is private"),
+            (Some(rect), Some(pane)) => Ok((rect.cols, rect.rows, pane.contents())),
+            _ => Err("no window is selected"),
+        };
+        session.set_selection(previous);
+        match captured {
+            Ok((cols, rows, contents)) => pending.pane(cols, rows, contents),
+            Err(message) => pending.refuse(message),
+        }
+    }
+
+    /// Answer `pending` with a record of the session named `name`, or of the
+    /// one attached to.
+    fn export_session(&mut self, name: Option<&str>, pending: Pending) {
+        let (id, selection) = match self.lookup(name) {
+            Some(found) => found,
+            None => return pending.refuse(&format!("no session: {}", name.unwrap())),
+        };
+        let session = match self.background.get_mut(&id) {
+            Some(session) => session,
+            None => &mut self.session,
+        };
+        let previous = session.selection();
+        if let Some(selection) = selection {
+            session.set_selection(selection);
+        }
+        let mut export = session.export();
+        session.set_selection(previous);
+        if let (Some(name), Some(_)) = (name, selection) {
+            export.name = name.to_string();
+        }
+        pending.export(export);
+    }
+
+    /// What `sm info` shows: the size of each client attached and the bytes
+    /// sent to it, and the bytes read from the panes of each window.
+    fn info(&self) -> String {
+        let mut text = String::new();
+        for client in self.server.clients() {
+            let size = client
+                .size
+                .map(|size| format!("{}x{}", size.ws_col, size.ws_row))
+                .unwrap_or_else(|| "unsized".to_string());
+            let wide = if client.ambiguous_wide {
+                ", ambiguous wide"
+            } else {
+                ""
+            };
+            text.push_str(&format!(
+                "client {}: {}, {} bytes out{}\n",
+                client.id, size, client.bytes_written, wide
+            ));
+        }
+        let mut sessions: Vec<_> =
+            self.sessions().map(|(_, session)| session).collect();
+        sessions.sort_by(|a, b| a.name().cmp(b.name()));
+        for session in sessions {
+            for (idx, _) in session.windows() {
+                text.push_str(&format!(
+                    "{} {}: {}: {} bytes in\n",
+                    session.name(),
+                    idx,
+                    session.window_name(idx).unwrap_or(""),
+                    session.window_bytes_read(idx).unwrap_or(0)
+                ));
+            }
+        }
+        text
+    }
+
+    /// Every session, with its id.
+    fn sessions(&self) -> impl Iterator<Item = (u64, &Session<P>)> {
+        iter::once((self.session_id, &self.session))
+            .chain(self.background.iter().map(|(id, session)| (*id, session)))
+    }
+
+    /// The id of the session named `name`, if there is one.
+    fn find_session(&self, name: &str) -> Option<u64> {
+        self.sessions()
+            .find(|(_, session)| session.name() == name)
+            .map(|(id, _)| id)
+    }
+
+    /// The id of the grouped session named `name`, if there is one.
+    fn find_grouped(&self, name: &str) -> Option<u64> {
+        self.grouped
+            .iter()
+            .find(|(_, grouped)| grouped.name == name)
+            .map(|(id, _)| *id)
+    }
+
+    /// Whether a session, grouped or not, is named `name`.
+    fn name_taken(&self, name: &str) -> bool {
+        self.find_session(name).is_some() || self.find_grouped(name).is_some()
+    }
+
+    /// The lowest number no session is named.
+    fn unused_session_name(&self) -> String {
+        (0..)
+            .map(|n: u64| n.to_string())
+            .find(|name| !self.name_taken(name))
+            .unwrap()
+    }
+
+    /// Take the variables of the update list from the environment `env` of a
+    /// client attaching to the session, such as `SSH_AUTH_SOCK`.
+    fn update_environment(&mut self, env: &[(String, String)]) {
+        let env = env
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()));
+        self.session.update_environment(env);
+    }
+
+    /// Attach the client waiting in `pending` to the grouped session with id
+    /// `id`, looking at the windows its clients last did, taking variables
+    /// from the client's environment `env`.
+    fn attach_grouped(
+        &mut self,
+        id: u64,
+        env: &[(String, String)],
+        pending: Pending,
+    ) -> Result<()> {
+        let target = self.grouped[&id].target;
+        if target != self.session_id {
+            // The clients attached share a session.
+            self.server.detach("detached by another client");
+        }
+        self.switch_session(target)?;
+        self.update_environment(env);
+        let client = self.server.attach(pending);
+        self.joining.insert(client, id);
+        self.run_hooks(Hook::ClientAttached)
+    }
+
+    /// Drop what is kept for the session with id `id`, which has ended, the
+    /// sessions grouped with it and its links.
+    fn forget_session(&mut self, id: u64) {
+        self.background.remove(&id);
+        self.buffers.remove(&id);
+        self.grouped.retain(|_, grouped| grouped.target != id);
+        for link in &mut self.links {
+            link.members.remove(&id);
+        }
+        self.links.retain(|link| link.members.len() > 1);
+    }
+
+    /// Whether the session with id `id` has no windows left, nor any linked
+    /// into it from elsewhere.
+    fn is_finished(&self, id: u64) -> bool {
+        let empty = self
+            .sessions()
+            .any(|(other, session)| other == id && session.windows().next().is_none());
+        empty && !self.links.iter().any(|link| link.members.contains_key(&id))
+    }
+
+    /// How many windows the session with id `id` has, counting those linked
+    /// into it which are in another session.
+    fn window_count(&self, id: u64) -> usize {
+        let own = self
+            .sessions()
+            .find(|(other, _)| *other == id)
+            .map_or(0, |(_, session)| session.windows().count());
+        let away = self
+            .links
+            .iter()
+            .filter(|link| link.holder != id && link.members.contains_key(&id))
+            .count();
+        own + away
+    }
+
+    /// The id of the session pane `pane_idx` is in: `id`, that it was created
+    /// in, unless it is in a linked window which has moved since.
+    fn pane_session(&self, id: u64, pane_idx: usize) -> u64 {
+        let created_in = match id == self.session_id {
+            true => Some(&self.session),
+            false => self.background.get(&id),
+        };
+        if created_in.is_some_and(|session| session.has_pane(pane_idx)) {
+            return id;
+        }
+        self.sessions()
+            .find(|(_, session)| session.has_pane(pane_idx))
+            .map_or(id, |(id, _)| id)
+    }
+
+    /// Link the selected window into the session named `name` too.
+    fn link_window(&mut self, name: &str) -> Result<()> {
+        let idx = match self.session.selected_window_idx() {
+            Some(idx) => idx,
+            None => return Ok(()),
+        };
+        let target = self.find_session(name).or_else(|| {
+            let id = self.find_grouped(name)?;
+            Some(self.grouped[&id].target)
+        });
+        let target = match target {
+            Some(target) if target != self.session_id => target,
+            Some(_) => {
+                let message = format!("window {} is in session {}", idx, name);
+                return self.show("link-window", &message);
+            }
+            None => return self.show("link-window", &format!("no session: {}", name)),
+        };
+        let window = self.session.window_id(idx).unwrap();
+        let position = match self.links.iter().position(|link| link.window == window) {
+            Some(position) => position,
+            None => {
+                self.links.push(Link {
+                    window,
+                    members: BTreeMap::from([(self.session_id, idx)]),
+                    holder: self.session_id,
+                });
+                self.links.len() - 1
+            }
+        };
+        if self.links[position].members.contains_key(&target) {
+            let message = format!("window {} is linked into {}", idx, name);
+            return self.show("link-window", &message);
+        }
+        let at = self
+            .background
+            .get_mut(&target)
+            .unwrap()
+            .reserve_window_idx();
+        self.links[position].members.insert(target, at);
+        Ok(())
+    }
+
+    /// Unlink the selected window from the session attached, leaving it in the
+    /// others it is linked into.
+    fn unlink_window(&mut self) -> Result<()> {
+        let idx = match self.session.selected_window_idx() {
+            Some(idx) => idx,
+            None => return Ok(()),
+        };
+        let window = self.session.window_id(idx).unwrap();
+        let position = match self.links.iter().position(|link| link.window == window) {
+            Some(position) => position,
+            None => {
+                let message = format!("window {} is not linked", idx);
+                return self.show("unlink-window", &message);
+            }
+        };
+        let link = &mut self.links[position];
+        link.members.remove(&self.session_id);
+        let (&to, &at) = link.members.iter().next().unwrap();
+        let taken = self.session.take_window(idx)?;
+        let at = self.background.get_mut(&to).unwrap().put_window(taken, at);
+        link.members.insert(to, at);
+        link.holder = to;
+        if link.members.len() == 1 {
+            self.links.remove(position);
+        }
+        self.status.invalidate();
+        Ok(())
+    }
+
+    /// Move the windows linked into the session attached which are elsewhere
+    /// into it.
+    fn bring_links(&mut self) -> Result<()> {
+        let mut links = mem::take(&mut self.links);
+        for link in &mut links {
+            let at = match link.members.get(&self.session_id) {
+                Some(&at) if link.holder != self.session_id => at,
+                _ => continue,
+            };
+            let holder = match self.background.get_mut(&link.holder) {
+                Some(holder) => holder,
+                None => continue,
+            };
+            if let Some(idx) = holder.find_window(link.window) {
+                let taken = holder.take_window(idx)?;
+                link.members.insert(link.holder, idx);
+                let at = self.session.put_window(taken, at);
+                link.members.insert(self.session_id, at);
+                link.holder = self.session_id;
+            }
+        }
+        self.links = links;
+        Ok(())
+    }
+
+    /// Drop the links of windows which have closed, and the sessions left
+    /// without windows by them.
+    fn prune_links(&mut self) {
+        let (open, closed): (Vec<_>, Vec<_>) =
+            mem::take(&mut self.links).into_iter().partition(|link| {
+                self.sessions().any(|(id, session)| {
+                    id == link.holder && session.find_window(link.window).is_some()
+                })
+            });
+        self.links = open;
+        for link in closed {
+            for id in link.members.into_keys() {
+                if id != self.session_id && self.is_finished(id) {
+                    self.forget_session(id);
+                }
+            }
+        }
+    }
+
+    /// Number the windows of the session attached from 0 up again, taking the
+    /// windows other clients and grouped sessions look at along.
+    fn renumber_windows(&mut self) {
+        let renumbered: BTreeMap<_, _> =
+            self.session.renumber_windows().into_iter().collect();
+        if renumbered.is_empty() {
+            return;
+        }
+        let renumber = |idx: usize| renumbered.get(&idx).copied().unwrap_or(idx);
+        let session_id = self.session_id;
+        let grouped = self
+            .grouped
+            .values_mut()
+            .filter(|grouped| grouped.target == session_id)
+            .map(|grouped| &mut grouped.selection);
+        for selection in self.views.values_mut().chain(grouped) {
+            selection.window = selection.window.map(renumber);
+            selection.last = selection.last.map(renumber);
+        }
+        self.bells = mem::take(&mut self.bells)
+            .into_iter()
+            .map(|(idx, until)| (renumber(idx), until))
+            .collect();
+        // Those looking at other windows are drawn them afresh.
+        self.elsewhere.clear();
+        self.status.invalidate();
+    }
+
+    /// Make the session with id `id` the one attached to, closing anything
+    /// open over the one before.
+    fn switch_session(&mut self, id: u64) -> Result<()> {
+        if let Some(session) = self.background.remove(&id) {
+            self.prefix = false;
+            self.close_menu()?;
+            self.close_prompt()?;
+            self.close_overlay()?;
+            let previous = mem::replace(&mut self.session, session);
+            self.background.insert(self.session_id, previous);
+            self.session_id = id;
+            self.bring_links()?;
+            self.status.invalidate();
+            // Window indices are only unique within a session too.
+            self.views.clear();
+            self.elsewhere.clear();
+            self.via_group.clear();
+            self.joining.clear();
+            // Bells rung while it was in the background are old news.
+            self.bells.clear();
+            self.session.take_bells();
+            if let Some(linear) = &mut self.linear {
+                // The active pane is another session's.
+                linear.reset();
+            }
+        }
+        Ok(())
+    }
+
+    /// If the session attached to has no windows left, do as the
+    /// `last-window-close` option says: end it, detaching the client, and
+    /// switch to another; whether there was one.
+    fn replace_closed_session(&mut self) -> Result<bool> {
+        self.prune_links();
+        let closed = self.session.closed_windows().len();
+        if self.config.renumber_windows && closed != self.closed_seen {
+            self.renumber_windows();
+        }
+        self.closed_seen = closed;
+        if self.session.selected_window_idx().is_some() {
+            if let Some(Overlay::Summary(_)) = self.overlay {
+                // A window was opened some other way, e.g. by a request.
+                self.close_overlay()?;
+            }
+            return Ok(true);
+        }
+        match self.config.last_window_close {
+            LastWindowClose::Summary if !self.quit_session => {
+                if !matches!(self.overlay, Some(Overlay::Summary(_))) {
+                    self.prefix = false;
+                    self.close_prompt()?;
+                    let summary = Summary::new(
+                        self.session.name(),
+                        self.session.closed_windows(),
+                        self.session.created().elapsed(),
+                    );
+                    self.open_overlay(Overlay::Summary(summary))?;
+                }
+                return Ok(true);
+            }
+            LastWindowClose::Detach if !self.quit_session => {
+                self.output.flush()?;
+                self.server.detach("detached");
+                let first = self.new_window(None)?;
+                self.session.select_window(first);
+                self.status.invalidate();
+                return Ok(true);
+            }
+            _ => (),
+        }
+        self.quit_session = false;
+        self.output.flush()?;
+        self.server.detach("no more windows");
+        let closed = self.session_id;
+        match self.background.keys().next() {
+            Some(&id) => self.switch_session(id)?,
+            None => return Ok(false),
+        }
+        self.forget_session(closed);
+        Ok(true)
+    }
+
+    /// Open the first window of a new session, and run the `session-created`
+    /// hooks.
+    fn start_session(&mut self) -> Result<()> {
+        let first = self.new_window(None)?;
+        self.session.select_window(first);
+        self.run_hooks(Hook::SessionCreated)
+    }
+
+    /// Perform the actions set on `hook`, unless those of a hook are being
+    /// performed already.
+    fn run_hooks(&mut self, hook: Hook) -> Result<()> {
+        if self.in_hook {
+            return Ok(());
+        }
+        self.in_hook = true;
+        let result = self
+            .config
+            .hook_actions(hook)
+            .into_iter()
+            .try_for_each(|action| self.perform(action));
+        self.in_hook = false;
+        result
+    }
+
+    fn handle_input(&mut self, mut event: Event, data: &[u8]) -> Result<()> {
+        let mut gesture = None;
+        if let Event::Mouse(mouse) = &mut event {
+            if mouse.kind == MouseKind::Drag {
+                mouse.button = self.mouse.held_button().unwrap_or(mouse.button);
+            }
+            if self.passes_through(mouse) {
+                self.mouse.track(*mouse, Instant::now());
+                self.session.report_mouse(mouse::report(mouse))?;
+                return Ok(());
+            }
+            gesture = match self.mouse.track(*mouse, Instant::now()) {
+                Some(gesture) => Some(gesture),
+                None => return Ok(()),
+            };
+            // Overlays and the menu only take presses.
+            if gesture == Some(Gesture::Drag)
+                && (self.prompt.is_some()
+                    || self.overlay.is_some()
+                    || self.menu.is_some())
+            {
+                return Ok(());
+            }
+        }
+        if let Some(lock) = &mut self.lock {
+            if let Event::Key(key) = event {
+                if let LockInput::Submit(typed) = lock.handle_key(key) {
+                    self.check_unlock(typed);
+                }
+            }
+            return Ok(());
+        }
+        if let Some(prompt) = &mut self.prompt {
+            let input = match event {
+                Event::Key(key) => prompt.handle_key(key),
+                _ => PromptInput::Pending,
+            };
+            return match input {
+                PromptInput::Pending => Ok(()),
+                PromptInput::Cancel => self.close_prompt(),
+                PromptInput::Submit(action) => {
+                    self.close_prompt()?;
+                    self.perform(action)
+                }
+                PromptInput::Command(line) => {
+                    self.close_prompt()?;
+                    self.run_command(&line)
+                }
+            };
+        }
+        match &mut self.overlay {
+            Some(Overlay::Pager(pager)) => {
+                let area = window_area(self.server.size());
+                let input = match event {
+                    Event::Key(key) => pager.handle_key(key, area),
+                    Event::Mouse(mouse) => pager.handle_mouse(mouse, area),
+                    Event::Unsupported => PagerInput::Pending,
+                };
+                return match input {
+                    PagerInput::Pending => Ok(()),
+                    PagerInput::Close => self.close_overlay(),
+                    PagerInput::Copy => {
+                        let text = pager.text().to_string();
+                        let source = self.buffer_source();
+                        self.copy_to_clipboard(&text);
+                        let id = self.session_id;
+                        self.buffers.entry(id).or_default().push(text, source);
+                        self.close_overlay()
+                    }
+                };
+            }
+            Some(Overlay::Buffers(list)) => {
+                let input = match event {
+                    Event::Key(key) => list.handle_key(key),
+                    _ => BufferInput::Pending,
+                };
+                let buffers = self.buffers.entry(self.session_id).or_default();
+                return match input {
+                    BufferInput::Pending => Ok(()),
+                    BufferInput::Close => self.close_overlay(),
+                    BufferInput::Paste(position) => {
+                        self.close_overlay()?;
+                        self.paste_buffer(position)
+                    }
+                    BufferInput::Delete(position) => {
+                        buffers.remove(position);
+                        list.remove(position);
+                        Ok(())
+                    }
+                    BufferInput::Save(position, path) => {
+                        if let Err(e) = self.save_buffer(position, &path) {
+                            self.show("save-buffer", &format!("{}: {}", path, e))?;
+                        }
+                        Ok(())
+                    }
+                };
+            }
+            Some(Overlay::Chooser(chooser)) => {
+                let input = match event {
+                    Event::Key(key) => chooser.handle_key(key),
+                    Event::Mouse(mouse) => chooser.handle_mouse(mouse),
+                    Event::Unsupported => ChooserInput::Pending,
+                };
+                let swaps = chooser.swaps().to_vec();
+                return match input {
+                    ChooserInput::Pending => Ok(()),
+                    ChooserInput::Close => {
+                        self.reorder_windows(&swaps);
+                        self.close_overlay()
+                    }
+                    ChooserInput::Choose(idx) => {
+                        self.close_overlay()?;
+                        self.reorder_windows(&swaps);
+                        self.session.select_window(idx);
+                        self.status.invalidate();
+                        Ok(())
+                    }
+                };
+            }
+            Some(Overlay::Palette(palette)) => {
+                let input = match event {
+                    Event::Key(key) => palette.handle_key(key),
+                    Event::Mouse(mouse) => palette.handle_mouse(mouse),
+                    Event::Unsupported => PaletteInput::Pending,
+                };
+                return match input {
+                    PaletteInput::Pending => Ok(()),
+                    PaletteInput::Close => self.close_overlay(),
+                    PaletteInput::Perform(action) => {
+                        self.close_overlay()?;
+                        self.perform(action)
+                    }
+                    PaletteInput::Prompt(text) => {
+                        self.close_overlay()?;
+                        self.command_prompt(&text);
+                        Ok(())
+                    }
+                };
+            }
+            Some(Overlay::Tree(tree)) => {
+                let input = match event {
+                    Event::Key(key) => tree.handle_key(key),
+                    Event::Mouse(mouse) => tree.handle_mouse(mouse),
+                    Event::Unsupported => TreeInput::Pending,
+                };
+                return match input {
+                    TreeInput::Pending => Ok(()),
+                    TreeInput::Close => self.close_overlay(),
+                    TreeInput::Choose(target) => {
+                        self.close_overlay()?;
+                        self.choose_tree_target(target)
+                    }
+                    TreeInput::Kill(target) => {
+                        self.kill_tree_target(target)?;
+                        self.refresh_tree();
+                        Ok(())
+                    }
+                    TreeInput::Rename(target, name) => {
+                        self.rename_tree_target(target, &name);
+                        self.refresh_tree();
+                        Ok(())
+                    }
+                };
+            }
+            Some(Overlay::Summary(summary)) => {
+                let input = match event {
+                    Event::Key(key) => summary.handle_key(key),
+                    _ => SummaryInput::Pending,
+                };
+                return match input {
+                    SummaryInput::Pending => Ok(()),
+                    SummaryInput::NewWindow => {
+                        self.close_overlay()?;
+                        let idx = self.new_window(None)?;
+                        self.session.select_window(idx);
+                        self.status.invalidate();
+                        Ok(())
+                    }
+                    SummaryInput::Quit => {
+                        self.quit_session = true;
+                        self.close_overlay()
+                    }
+                };
+            }
+            None => (),
+        }
+        if let Some(menu) = &mut self.menu {
+            let input = match event {
+                Event::Key(key) => menu.handle_key(key),
+                Event::Mouse(mouse) => menu.handle_mouse(mouse),
+                Event::Unsupported => MenuInput::Pending,
+            };
+            return match input {
+                MenuInput::Pending => Ok(()),
+                MenuInput::Close => self.close_menu(),
+                MenuInput::Choose(action) => {
+                    self.close_menu()?;
+                    self.perform(action)
+                }
+            };
+        }
+        if let (Event::Mouse(mouse), Some(gesture)) = (&event, gesture) {
+            let mouse = *mouse;
+            return self.handle_mouse(mouse, gesture);
+        }
+        if let Some(until) = self.repeat_until {
+            let action = match event {
+                Event::Key(key) if !self.prefix && Instant::now() < until => {
+                    self.binding(KeyTable::Prefix, key).filter(Action::repeats)
+                }
+                _ => None,
+            };
+            if let Some(action) = action {
+                self.repeat_until = Some(Instant::now() + REPEAT_TIME);
+                return self.perform(action);
+            }
+            self.end_repeat();
+        }
+        if !self.prefix {
+            if let Event::Key(key) = event {
+                if key == PREFIX {
+                    self.end_copy_mode();
+                } else if self.copy_mode.is_some() || self.is_scrolled_back() {
+                    self.copy_mode_key(key);
+                    return Ok(());
+                }
+            }
+            let root = match event {
+                Event::Key(key) => self.binding(KeyTable::Root, key),
+                _ => None,
+            };
+            if event == Event::Key(PREFIX) {
+                self.prefix = true;
+            } else if let Some(action) = root {
+                return self.perform(action);
+            } else {
+                let pane = self.session.active_pane();
+                let private = pane.is_some_and(|pane| pane.is_private());
+                if let (Some(echo), Event::Key(key)) = (&mut self.key_echo, event) {
+                    if !private {
+                        let secret = pane.is_some_and(|pane| pane.is_reading_secret());
+                        echo.push(key, secret, Instant::now());
+                    }
+                }
+                self.session.receive_stdin(data)?;
+            }
+            return Ok(());
+        }
+
+        self.prefix = false;
+        let key = match event {
+            Event::Key(PREFIX) => return self.perform(Action::SendPrefix),
+            Event::Key(key) => key,
+            _ => return Ok(()),
+        };
+        match self.binding(KeyTable::Prefix, key) {
+            Some(action) => {
+                if action.repeats() {
+                    self.repeat_until = Some(Instant::now() + REPEAT_TIME);
+                }
+                self.perform(action)
+            }
+            None => {
+                debug!("unbound key: {:?}", key);
+                Ok(())
+            }
+        }
+    }
+
+    /// The action bound to `key` in `table`.
+    fn binding(&self, table: KeyTable, key: Key) -> Option<Action> {
+        self.config.key_bindings.get(table, key).cloned()
+    }
+
+    /// Stop keys repeating without the prefix, and take the size labels off
+    /// the panes.
+    fn end_repeat(&mut self) {
+        if self.repeat_until.take().is_some() {
+            self.session.mark_dirty();
+        }
+    }
+
+    /// Whether `mouse` is passed through to the program in the pane under it:
+    /// if clicks select panes, a modifier is held, and nothing covers the pane
+    /// nor is bound to the button with those modifiers over panes.
+    fn passes_through(&self, mouse: &MouseEvent) -> bool {
+        let bound = self.config.mouse_bindings.iter().any(|binding| {
+            binding.button == mouse.button
+                && binding.modifiers == mouse.modifiers
+                && binding.region == MouseRegion::Pane
+        });
+        self.config.mouse
+            && mouse.modifiers != Modifiers::default()
+            && mouse.row + 1 < self.server.size().ws_row
+            && !bound
+            && !self.is_covered()
+            && self.lock.is_none()
+    }
+
+    fn handle_mouse(&mut self, mouse: MouseEvent, gesture: Gesture) -> Result<()> {
+        let size = self.server.size();
+        let region = if mouse.row + 1 >= size.ws_row {
+            MouseRegion::Status
+        } else if self.session.is_separator(mouse.col, mouse.row) {
+            MouseRegion::Border
+        } else {
+            MouseRegion::Pane
+        };
+        let binding = self
+            .config
+            .mouse_bindings
+            .iter()
+            .find(|binding| binding.matches(&mouse, gesture, region))
+            .map(|binding| binding.action.clone());
+        match binding {
+            Some(Action::ContextMenu) => {
+                // The menu acts on the pane it was opened over.
+                self.session.select_pane_at(mouse.col, mouse.row);
+                self.open_menu(mouse.col, mouse.row)
+            }
+            Some(action) => self.perform(action),
+            None if self.config.mouse => self.default_mouse(mouse, region),
+            None => {
+                debug!("unbound {:?} of {:?} in {:?}", gesture, mouse, region);
+                Ok(())
+            }
+        }
+    }
+
+    /// What the mouse does when the `mouse` option is on, unless bound
+    /// otherwise: a click selects the window or pane, a right click on a pane
+    /// opens the context menu over it, and the wheel scrolls a pane back
+    /// through the rows scrolled away, unless its program asked for the mouse.
+    fn default_mouse(&mut self, mouse: MouseEvent, region: MouseRegion) -> Result<()> {
+        match (mouse.button, region) {
+            (MouseButton::Left, MouseRegion::Status) => {
+                if let Some(idx) = self.status.window_at(mouse.col) {
+                    self.session.select_window(idx);
+                }
+            }
+            (MouseButton::Left, MouseRegion::Pane) => {
+                self.end_copy_mode();
+                self.session.select_pane_at(mouse.col, mouse.row);
+            }
+            (MouseButton::Right, MouseRegion::Pane) => {
+                self.session.select_pane_at(mouse.col, mouse.row);
+                self.open_menu(mouse.col, mouse.row)?;
+            }
+            (MouseButton::WheelUp | MouseButton::WheelDown, MouseRegion::Pane) => {
+                if self.session.report_mouse(mouse::report(&mouse))? {
+                    return Ok(());
+                }
+                self.session.select_pane_at(mouse.col, mouse.row);
+                let rows = match mouse.button {
+                    MouseButton::WheelUp => WHEEL_ROWS,
+                    _ => -WHEEL_ROWS,
+                };
+                if let Some(pane) = self.session.active_pane_mut() {
+                    pane.scroll_view(rows);
+                }
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+
+    /// Whether the active pane is shown back through the rows scrolled away.
+    fn is_scrolled_back(&self) -> bool {
+        let pane = self.session.active_pane();
+        pane.is_some_and(|pane| pane.view_offset() > 0)
+    }
+
+    /// Scroll or search the active pane with `key`, in copy mode, entering it
+    /// if the pane was scrolled back some other way.
+    fn copy_mode_key(&mut self, key: Key) {
+        let page = self.session.active_rect().map_or(1, |rect| rect.rows);
+        let mode = self.copy_mode.get_or_insert_with(CopyMode::default);
+        let pane = match self.session.active_pane_mut() {
+            Some(pane) => pane,
+            None => return self.copy_mode = None,
+        };
+        let typing = mode.prompt().is_some();
+        let input = mode.handle_key(key, pane, page as isize);
+        let now = Instant::now();
+        match (mode.prompt(), mode.take_message()) {
+            (Some(prompt), _) => self.status.show_message(&prompt, now + PROMPT_HOLD),
+            (None, Some(message)) => self.display_message(&message),
+            // The prompt goes once the search is typed.
+            (None, None) if typing => self.status.show_message("", now),
+            (None, None) => (),
+        }
+        if input == CopyModeInput::Exit {
+            self.copy_mode = None;
+        }
+    }
+
+    /// Stop searching the active pane, if it is being searched, leaving it
+    /// scrolled back where it is.
+    fn end_copy_mode(&mut self) {
+        let mode = self.copy_mode.take();
+        if let (Some(mode), Some(pane)) = (mode, self.session.active_pane_mut()) {
+            mode.end(pane);
+        }
+    }
+
+    fn perform(&mut self, action: Action) -> Result<()> {
+        match action {
+            Action::SendPrefix => {
+                let prefix = [PREFIX_BYTE];
+                self.session.receive_stdin(&prefix)?;
+            }
+            Action::NewWindow {
+                name,
+                command,
+                cwd,
+                env,
+            } => {
+                let cwd = cwd.as_deref().map(Path::new);
+                let (idx, updates) =
+                    match self
+                        .session
+                        .new_window_running(command.as_deref(), cwd, &env)
+                    {
+                        Err(e @ SessionError::NoSuchDirectory(_)) => {
+                            return self.show("new-window", &e.to_string());
+                        }
+                        result => result?,
+                    };
+                self.watch(updates);
+                self.session.select_window(idx);
+                if let Some(name) = name {
+                    self.session.rename_window(&name)?;
+                }
+                self.run_hooks(Hook::AfterNewWindow)?;
+            }
+            Action::Detach => {
+                self.output.flush()?;
+                self.server.detach_client(self.input_client, "detached");
+            }
+            Action::DetachOthers => {
+                let message = "detached by another client";
+                self.server.detach_others(self.input_client, message);
+            }
+            Action::NextWindow => {
+                if let Some(idx) = self.session.next_window_idx() {
+                    self.session.select_window(idx);
+                }
+            }
+            Action::PreviousWindow => {
+                if let Some(idx) = self.session.prev_window_idx() {
+                    self.session.select_window(idx);
+                }
+            }
+            Action::SelectWindow(idx) => {
+                self.session.select_window(idx);
+            }
+            Action::LastWindow => {
+                if let Some(idx) = self.session.last_selected_window_idx() {
+                    self.session.select_window(idx);
+                }
+            }
+            Action::RenameWindow(Some(name)) => self.session.rename_window(&name)?,
+            Action::RenameWindow(None) => {
+                if let Some(idx) = self.session.selected_window_idx() {
+                    let name = self.session.window_name(idx).unwrap_or("");
+                    self.prompt = Some(Prompt::new("(rename-window)", name, |name| {
+                        Action::RenameWindow(Some(name))
+                    }));
+                }
+            }
+            Action::ChooseWindow => self.choose_window()?,
+            Action::ChooseTree => {
+                let tree = Tree::new(self.tree_sessions());
+                self.open_overlay(Overlay::Tree(tree))?;
+            }
+            Action::SwapWindow(target) => {
+                let other = match target {
+                    WindowTarget::Previous => self.session.prev_window_idx(),
+                    WindowTarget::Next => self.session.next_window_idx(),
+                    WindowTarget::Index(idx) => Some(idx),
+                };
+                if let (Some(idx), Some(other)) =
+                    (self.session.selected_window_idx(), other)
+                {
+                    match self.session.swap_window(idx, other) {
+                        Ok(()) => (),
+                        Err(e @ SessionError::NoSuchWindow(_)) => {
+                            self.show("swap-window", &e.to_string())?
+                        }
+                        Err(e) => return Err(e.into()),
+                    }
+                }
+            }
+            Action::MoveWindow(Some(new_idx)) => {
+                let idx = self.session.selected_window_idx();
+                match idx.map(|idx| self.session.move_window(idx, new_idx)) {
+                    None | Some(Ok(())) => (),
+                    Some(Err(e @ SessionError::WindowExists(_))) => {
+                        self.show("move-window", &e.to_string())?
+                    }
+                    Some(Err(e)) => return Err(e.into()),
+                }
+            }
+            Action::RenumberWindows => self.renumber_windows(),
+            Action::MoveWindow(None) => {
+                if let Some(idx) = self.session.selected_window_idx() {
+                    // Anything but an index asks again.
+                    let prompt =
+                        Prompt::new("(move-window)", &idx.to_string(), |text| {
+                            Action::MoveWindow(text.trim().parse().ok())
+                        });
+                    self.prompt = Some(prompt);
+                }
+            }
+            Action::SplitHorizontal => self.split_pane(Direction::Horizontal)?,
+            Action::SplitVertical => self.split_pane(Direction::Vertical)?,
+            Action::NextPane => {
+                self.session.select_next_pane();
+            }
+            Action::ZoomPane => self.session.toggle_zoom()?,
+            Action::NextLayout => self.session.next_layout()?,
+            Action::ResizePane(direction, cells) => {
+                self.session.resize_pane(direction, cells)?
+            }
+            Action::SlowPrint(lines_per_sec) => {
+                let limited = self
+                    .session
+                    .active_pane_mut()
+                    .and_then(|pane| pane.slow_print())
+                    .is_some();
+                let lines_per_sec = if limited { None } else { Some(lines_per_sec) };
+                self.session.set_slow_print(lines_per_sec)?;
+            }
+            Action::SynchronizePanes => {
+                if let Some(idx) = self.session.selected_window_idx() {
+                    let synchronized = self.session.is_synchronized(idx);
+                    self.session.set_synchronize_panes(!synchronized)?;
+                    self.status.invalidate();
+                }
+            }
+            Action::SetTerm(term) => {
+                if let Err(e) = self.session.set_window_term(Some(&term)) {
+                    self.show("set-term", &e.to_string())?;
+                }
+            }
+            Action::RemainOnExit(remain) => {
+                self.session.set_window_remain_on_exit(remain)?;
+            }
+            Action::RecordInput(record) => {
+                self.session.set_window_record_input(record)?;
+            }
+            Action::ShowInput => match self.recorded_input() {
+                Ok((title, text)) => self.show(&title, &text)?,
+                Err(message) => self.show("show-input", &message)?,
+            },
+            Action::SaveInput(path) => {
+                let saved = self.recorded_input().and_then(|(_, text)| {
+                    fs::write(self.pane_path(&path), text).map_err(|e| e.to_string())
+                });
+                if let Err(message) = saved {
+                    self.show("save-input", &format!("{}: {}", path, message))?;
+                }
+            }
+            Action::TogglePrivate => {
+                let private = self
+                    .session
+                    .active_pane()
+                    .is_some_and(|pane| pane.is_private());
+                self.session.set_private(!private)?;
+                self.status.invalidate();
+            }
+            Action::BreakPane => match self.session.break_pane() {
+                Ok(idx) => {
+                    self.session.select_window(idx);
+                }
+                Err(SessionError::OnlyPane) => debug!("no pane to break out"),
+                Err(e) => return Err(e.into()),
+            },
+            Action::JoinPane(idx, direction) => {
+                match self.session.join_pane(idx, direction) {
+                    Ok(()) => (),
+                    Err(e @ SessionError::NoSuchWindow(_)) => {
+                        self.show("join-pane", &e.to_string())?
+                    }
+                    Err(SessionError::NoSpace) => self.show_no_space("join-pane")?,
+                    Err(e) => return Err(e.into()),
+                }
+            }
+            Action::LinkWindow(name) => self.link_window(&name)?,
+            Action::UnlinkWindow => self.unlink_window()?,
+            Action::KillPane => self.session.kill_pane()?,
+            Action::KillWindow(true) => {
+                if let Some(idx) = self.session.selected_window_idx() {
+                    let name = self.session.window_name(idx).unwrap_or("");
+                    let question = format!("kill-window {}:{}?", idx, name);
+                    self.prompt =
+                        Some(Prompt::confirm(&question, Action::KillWindow(false)));
+                }
+            }
+            Action::KillWindow(false) => {
+                self.session.kill_window()?;
+                self.status.invalidate();
+            }
+            Action::RenameSession(Some(name)) => {
+                if let Err(e) = self.rename_session(None, &name) {
+                    self.display_message(&e.to_string());
+                }
+            }
+            Action::RenameSession(None) => {
+                let name = self.session.name();
+                self.prompt = Some(Prompt::new("(rename-session)", name, |name| {
+                    Action::RenameSession(Some(name))
+                }));
+            }
+            Action::KillSession(true) => {
+                let question = format!("kill-session {}?", self.session.name());
+                self.prompt =
+                    Some(Prompt::confirm(&question, Action::KillSession(false)));
+            }
+            Action::KillSession(false) => self.kill_session(self.session_id)?,
+            Action::ContextMenu => {
+                if let Some(rect) = self.session.active_rect() {
+                    self.open_menu(rect.col, rect.row)?;
+                }
+            }
+            Action::RunShell { command, output } => self.run_shell(command, output),
+            Action::IfShell {
+                condition,
+                then,
+                otherwise,
+            } => self.if_shell(condition, then, otherwise),
+            Action::DisplayMessage(format) => {
+                let bytes_out = self.server.bytes_written();
+                let text = expand(&self.session, &format, bytes_out);
+                self.display_message(&text);
+            }
+            Action::ClockMode => self.open_applet(Box::new(Clock::new()))?,
+            Action::ViewFile(path) => match Viewer::open(&path) {
+                Ok(viewer) => self.open_applet(Box::new(viewer))?,
+                Err(e) => self.show("view-file", &format!("{}: {}", path, e))?,
+            },
+            Action::PasteBuffer(name) => match self.buffer_position(name.as_deref()) {
+                Ok(Some(position)) => self.paste_buffer(position)?,
+                Ok(None) => (),
+                Err(e) => self.show("paste-buffer", &e)?,
+            },
+            Action::SetBuffer { name, text } => {
+                let source = self.buffer_source();
+                let buffers = self.buffers.entry(self.session_id).or_default();
+                match name {
+                    Some(name) => buffers.set(name, text, source),
+                    None => buffers.push(text, source),
+                }
+            }
+            Action::DeleteBuffer(name) => match self.buffer_position(name.as_deref()) {
+                Ok(Some(position)) => {
+                    let buffers = self.buffers.entry(self.session_id).or_default();
+                    buffers.remove(position);
+                }
+                Ok(None) => (),
+                Err(e) => self.show("delete-buffer", &e)?,
+            },
+            Action::PasteClipboard => self.paste_clipboard(),
+            Action::LockSession => self.lock_screen(),
+            Action::BindKey(binding) => self.config.key_bindings.bind(*binding),
+            Action::UnbindKey(table, key) => {
+                self.config.key_bindings.unbind(table, key);
+            }
+            Action::CommandPrompt => self.command_prompt(""),
+            Action::CopyMode => {
+                if self.session.active_pane().is_some() {
+                    self.copy_mode = Some(CopyMode::default());
+                    self.display_message("copy mode: / and ? search, q quits");
+                }
+            }
+            Action::CommandPalette => {
+                let palette = Palette::new(&self.config.key_bindings);
+                self.open_overlay(Overlay::Palette(palette))?;
+            }
+            Action::ListKeys => {
+                let list: String = self
+                    .config
+                    .key_bindings
+                    .iter()
+                    .map(|binding| format!("bind-key {}\n", binding))
+                    .collect();
+                self.show("list-keys", &list)?;
+            }
+            Action::SourceFile(path) => {
+                let read = match (path, &self.config_path) {
+                    (Some(path), _) => Config::read(Path::new(&path)),
+                    (None, Some(path)) => Config::load(path),
+                    (None, None) => Err(anyhow!("no configuration file")),
+                };
+                match read {
+                    Ok(config) => self.apply_config(config)?,
+                    Err(e) => self.show("source-file", &format!("{:#}", e))?,
+                }
+            }
+            Action::ShowOptions(scope) => {
+                let env = self.session.environment();
+                let text = options::show(&self.config, env, scope);
+                self.show("show-options", &text)?;
+            }
+            Action::SetEnvironment(name, value) => {
+                let env = self.session.environment_mut();
+                match value {
+                    Some(value) => env.set(&name, &value),
+                    None => env.remove(&name),
+                }
+            }
+            Action::ShowEnvironment => {
+                let text = self.session.environment().show();
+                self.show("show-environment", &text)?;
+            }
+            Action::ChooseBuffer => {
+                let buffers = self.buffers.entry(self.session_id).or_default();
+                let list = BufferList::new(buffers, Instant::now());
+                self.open_overlay(Overlay::Buffers(list))?;
+            }
+            Action::SaveBuffer(path) => {
+                if let Err(e) = self.save_buffer(0, &path) {
+                    self.show("save-buffer", &format!("{}: {}", path, e))?;
+                }
+            }
+            Action::SelectLayout(preset) => self.session.select_layout(preset)?,
+            Action::CycleTheme => {
+                if let Some(pane) = self.session.active_pane_mut() {
+                    let current = pane.theme().map(|theme| theme.name.as_str());
+                    let theme = themes::next(current);
+                    pane.set_theme(theme);
+                }
+            }
+            Action::ClearTheme => {
+                if let Some(pane) = self.session.active_pane_mut() {
+                    pane.set_theme(None);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn open_menu(&mut self, col: u16, row: u16) -> Result<()> {
+        let area = window_area(self.server.size());
+        self.menu = Some(Menu::new(col, row, area));
+        write!(self.output, "\x1b[?25l")?;
+        Ok(())
+    }
+
+    /// Close the context menu, if it is open, uncovering the window.
+    fn close_menu(&mut self) -> Result<()> {
+        if self.menu.take().is_some() {
+            self.session.mark_dirty();
+            write!(self.output, "\x1b[?25h")?;
+        }
+        Ok(())
+    }
+
+    /// Take `config` in place of the configuration read before, applying its
+    /// options to every session and its bindings and status line to every
+    /// client.
+    fn apply_config(&mut self, config: Config) -> Result<()> {
+        let captured = self.config.captures_mouse();
+        self.config = config;
+        let config = &self.config;
+        let theme = config.theme();
+        let background = self.background.values_mut();
+        for session in iter::once(&mut self.session).chain(background) {
+            session.set_border_styles(theme.border_styles());
+            session.set_default_remain_on_exit(config.remain_on_exit);
+            session.set_default_shell(config.default_shell.as_deref());
+            session.set_default_command(config.default_command.as_deref());
+            session.set_default_record_input(config.record_input);
+            session.set_base_index(config.base_index);
+            session.set_pane_base_index(config.pane_base_index);
+            session.set_min_pane_size(config.pane_min_size.unwrap_or_default());
+            for (name, value) in &config.environment {
+                match value {
+                    Some(value) => session.environment_mut().set(name, value),
+                    None => session.environment_mut().remove(name),
+                }
+            }
+        }
+        self.server.set_window_size(config.window_size);
+        let history_limit = config.history_limit.unwrap_or(History::DEFAULT_CAPACITY);
+        self.history.set_capacity(history_limit);
+        self.status.set_right(config.status_right.clone());
+        self.status.set_theme(theme);
+        self.title = config
+            .set_titles
+            .then(|| TerminalTitle::new(config.set_titles_string.clone()));
+        if captured && !config.captures_mouse() {
+            write!(self.output, "{}", MOUSE_RELEASE)?;
+        }
+        for view in self.elsewhere.values_mut() {
+            view.fresh = true;
+        }
+        self.redraw_all()
+    }
+
+    /// Show `text` in the pager, titled `title`, in place of anything shown
+    /// already.
+    fn show(&mut self, title: &str, text: &str) -> Result<()> {
+        self.open_overlay(Overlay::Pager(Pager::new(title, text)))
+    }
+
+    /// Cover the windows with `overlay`, in place of anything shown already.
+    ///
+    /// The summary of a session is left alone, as nothing is under it.
+    fn open_overlay(&mut self, overlay: Overlay) -> Result<()> {
+        if let Some(Overlay::Summary(_)) = self.overlay {
+            return Ok(());
+        }
+        self.close_menu()?;
+        if self.overlay.is_none() {
+            write!(self.output, "\x1b[?25l")?;
+        }
+        self.overlay = Some(overlay);
+        Ok(())
+    }
+
+    /// Send the paste buffer at `position`, counting from the newest, to the
+    /// active pane, if there is one.
+    fn paste_buffer(&mut self, position: usize) -> Result<()> {
+        let buffers = self.buffers.get(&self.session_id);
+        if let Some(buffer) = buffers.and_then(|buffers| buffers.get(position)) {
+            self.session.paste(&buffer.text)?;
+        }
+        Ok(())
+    }
+
+    /// The position of the paste buffer named `name`, or of the newest if
+    /// `None` and there is one.
+    fn buffer_position(&self, name: Option<&str>) -> Result<Option<usize>, String> {
+        let buffers = self.buffers.get(&self.session_id);
+        match name {
+            Some(name) => match buffers.and_then(|buffers| buffers.position(name)) {
+                Some(position) => Ok(Some(position)),
+                None => Err(format!("no buffer {}", name)),
+            },
+            None => Ok(buffers.and_then(|buffers| buffers.get(0)).map(|_| 0)),
+        }
+    }
+
+    /// Where a paste buffer added now comes from: the selected window, e.g.
+    /// `1:vim`.
+    fn buffer_source(&self) -> String {
+        match self.session.selected_window_idx() {
+            Some(idx) => {
+                format!("{}:{}", idx, self.session.window_name(idx).unwrap_or(""))
+            }
+            None => "-".to_string(),
+        }
+    }
+
+    /// Write the paste buffer at `position`, counting from the newest, to
+    /// `path`, relative to the active pane's working directory.
+    fn save_buffer(&self, position: usize, path: &str) -> io::Result<()> {
+        let buffers = self.buffers.get(&self.session_id);
+        let buffer = buffers
+            .and_then(|buffers| buffers.get(position))
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no buffer"))?;
+        fs::write(self.pane_path(path), &buffer.text)
+    }
+
+    /// `path`, relative to the active pane's working directory if it is known.
+    fn pane_path(&self, path: &str) -> PathBuf {
+        let cwd = self.session.active_pane().and_then(|pane| pane.cwd());
+        match cwd {
+            Some(cwd) => cwd.join(path),
+            None => PathBuf::from(path),
+        }
+    }
+
+    /// The input recorded for the selected window, with a title saying which
+    /// it is, or why there is none.
+    fn recorded_input(&self) -> Result<(String, String), String> {
+        let idx = self
+            .session
+            .selected_window_idx()
+            .ok_or_else(|| "no window".to_string())?;
+        let log = self.session.input_log(idx).ok_or_else(|| {
+            format!(
+                "the input to window {} is not recorded: see record-input",
+                idx
+            )
+        })?;
+        let name = self.session.window_name(idx).unwrap_or("");
+        Ok((format!("input to {}:{}", idx, name), log.text()))
+    }
+
+    /// List the windows to choose one from.
+    fn choose_window(&mut self) -> Result<()> {
+        let size = self.session.size();
+        let entries = self
+            .session
+            .windows()
+            .map(|(idx, pane)| ChooserEntry {
+                idx,
+                name: self.session.window_name(idx).unwrap_or("").to_string(),
+                flags: self.session.window_flags(idx).unwrap_or_default(),
+                panes: self.session.pane_count(idx).unwrap_or(1),
+                size,
+                preview: pane.preview(),
+            })
+            .collect();
+        self.open_overlay(Overlay::Chooser(Chooser::new(entries)))
+    }
+
+    /// Every session, by name, with its windows and their panes, as the tree
+    /// lists them. Grouped sessions are given with those they are grouped
+    /// with.
+    fn tree_sessions(&self) -> Vec<TreeSession> {
+        let mut sessions: Vec<_> = self
+            .sessions()
+            .map(|(id, session)| {
+                let windows = session.windows().map(|(idx, _)| {
+                    let panes = session.window_panes(idx).into_iter();
+                    let panes = panes.map(|(number, pane, active)| TreePane {
+                        number,
+                        active,
+                        preview: Some(pane.preview()).filter(|_| !pane.is_private()),
+                    });
+                    TreeWindow {
+                        idx,
+                        name: session.window_name(idx).unwrap_or("").to_string(),
+                        flags: session.window_flags(idx).unwrap_or_default(),
+                        panes: panes.collect(),
+                    }
+                });
+                let grouped = self.grouped.values().filter(|g| g.target == id);
+                TreeSession {
+                    id,
+                    name: session.name().to_string(),
+                    attached: id == self.session_id,
+                    grouped: grouped.map(|grouped| grouped.name.clone()).collect(),
+                    windows: windows.collect(),
+                }
+            })
+            .collect();
+        sessions.sort_by(|a, b| a.name.cmp(&b.name));
+        sessions
+    }
+
+    /// List the sessions in the tree again, if it is open, as they have
+    /// changed.
+    fn refresh_tree(&mut self) {
+        let sessions = self.tree_sessions();
+        if let Some(Overlay::Tree(tree)) = &mut self.overlay {
+            tree.update(sessions);
+        }
+    }
+
+    /// The session with id `id`, attached or not.
+    fn session_by_id_mut(&mut self, id: u64) -> Option<&mut Session<P>> {
+        match id == self.session_id {
+            true => Some(&mut self.session),
+            false => self.background.get_mut(&id),
+        }
+    }
+
+    /// Switch to the session, window or pane chosen in the tree.
+    fn choose_tree_target(&mut self, target: TreeTarget) -> Result<()> {
+        self.switch_session(target.session())?;
+        // It may have closed while the tree was open.
+        let result = match target {
+            TreeTarget::Session(_) => Ok(()),
+            TreeTarget::Window(_, idx) => {
+                self.session.select_window(idx);
+                Ok(())
+            }
+            TreeTarget::Pane(_, idx, number) => {
+                self.session.select_window(idx);
+                self.session.select_pane_number(idx, number)
+            }
+        };
+        if let Err(e) = result {
+            self.display_message(&e.to_string());
+        }
+        self.status.invalidate();
+        Ok(())
+    }
+
+    /// Kill the session, window or pane `target`, as confirmed in the tree.
+    fn kill_tree_target(&mut self, target: TreeTarget) -> Result<()> {
+        let id = target.session();
+        let session = match self.session_by_id_mut(id) {
+            Some(session) => session,
+            None => return Ok(()),
+        };
+        let result = match target {
+            TreeTarget::Session(_) => return self.kill_session(id),
+            TreeTarget::Window(_, idx) => session.kill_window_idx(idx),
+            TreeTarget::Pane(_, idx, number) => session.kill_pane_number(idx, number),
+        };
+        if let Err(e) = result {
+            self.display_message(&e.to_string());
+        }
+        self.prune_links();
+        if id != self.session_id && self.is_finished(id) {
+            self.forget_session(id);
+        }
+        self.status.invalidate();
+        Ok(())
+    }
+
+    /// Give the session or window `target` the name typed in the tree.
+    fn rename_tree_target(&mut self, target: TreeTarget, name: &str) {
+        let result = match (target, self.session_by_id_mut(target.session())) {
+            (TreeTarget::Session(_), Some(session)) => {
+                let current = session.name().to_string();
+                self.rename_session(Some(&current), name)
+            }
+            (TreeTarget::Window(_, idx), Some(session)) => {
+                session.rename_window_idx(idx, name).map_err(Into::into)
+            }
+            _ => Ok(()),
+        };
+        if let Err(e) = result {
+            self.display_message(&e.to_string());
+        }
+        self.status.invalidate();
+    }
+
+    /// Call the session named `name`, or the one attached to, `new_name`,
+    /// unless another session has that name.
+    fn rename_session(&mut self, name: Option<&str>, new_name: &str) -> Result<()> {
+        if new_name.is_empty() {
+            bail!("a session needs a name");
+        }
+        let name = name.unwrap_or(self.session.name()).to_string();
+        if new_name != name && self.name_taken(new_name) {
+            bail!("duplicate session: {}", new_name);
+        }
+        if let Some(id) = self.find_grouped(&name) {
+            self.grouped.get_mut(&id).unwrap().name = new_name.to_string();
+        } else {
+            let id = self.find_session(&name);
+            let id = id.ok_or_else(|| anyhow!("no session: {}", name))?;
+            self.session_by_id_mut(id).unwrap().set_name(new_name);
+        }
+        self.status.invalidate();
+        Ok(())
+    }
+
+    /// Move every window of the session named `source` into the one named
+    /// `target`, or the one attached to, after its own, and end `source`,
+    /// detaching its clients. Sessions grouped with `source` end with it.
+    fn merge_session(&mut self, source: &str, target: Option<&str>) -> Result<()> {
+        let find = |name: &str| {
+            self.find_session(name)
+                .ok_or_else(|| anyhow!("no session: {}", name))
+        };
+        let from = find(source)?;
+        let to = match target {
+            Some(target) => find(target)?,
+            None => self.session_id,
+        };
+        if from == to {
+            bail!("cannot merge session {} into itself", source);
+        }
+        // A window cannot be in a session twice.
+        let twice = self.links.iter().find(|link| {
+            link.members.contains_key(&from) && link.members.contains_key(&to)
+        });
+        if let Some(link) = twice {
+            let target = target.unwrap_or(self.session.name());
+            let idx = link.members[&from];
+            bail!("window {} of {} is linked into {}", idx, source, target);
+        }
+        if from == self.session_id {
+            self.output.flush()?;
+            self.server.detach("session merged");
+            self.switch_session(to)?;
+        }
+        let merged = self.background.remove(&from).unwrap();
+        let taken = self
+            .pty_updates
+            .unwatch(|_, updates| merged.has_pane(updates.pane_idx()));
+        let taken = taken.into_iter().map(|(_, updates)| updates);
+        let session = self.session_by_id_mut(to).unwrap();
+        for updates in session.absorb(merged, taken) {
+            self.pty_updates.push(to, updates);
+        }
+        let mut links = mem::take(&mut self.links);
+        for link in &mut links {
+            if link.members.remove(&from).is_none() {
+                continue;
+            }
+            let session = self.session_by_id_mut(to).unwrap();
+            let at = match link.holder == from {
+                true => session.find_window(link.window).unwrap(),
+                // It is elsewhere, so only its place moves.
+                false => session.reserve_window_idx(),
+            };
+            link.members.insert(to, at);
+            if link.holder == from {
+                link.holder = to;
+            }
+        }
+        self.links = links;
+        self.forget_session(from);
+        self.status.invalidate();
+        self.refresh_tree();
+        Ok(())
+    }
+
+    /// Kill the processes of every window of the session with id `id` and
+    /// close them, ending it. Its clients are detached.
+    fn kill_session(&mut self, id: u64) -> Result<()> {
+        if let Some(session) = self.session_by_id_mut(id) {
+            let windows: Vec<_> = session.windows().map(|(idx, _)| idx).collect();
+            for idx in windows {
+                session.kill_window_idx(idx)?;
+            }
+        }
+        self.prune_links();
+        if id == self.session_id {
+            // Ended, rather than replaced, once its windows are found closed.
+            self.quit_session = true;
+            self.output.flush()?;
+            self.server.detach("session killed");
+        } else if self.is_finished(id) {
+            self.forget_session(id);
+        }
+        self.status.invalidate();
+        Ok(())
+    }
+
+    /// Put the windows in the order they were given in the chooser, swapping
+    /// them in turn. Windows closed while the chooser was open are left out.
+    fn reorder_windows(&mut self, swaps: &[(usize, usize)]) {
+        for (a, b) in swaps {
+            let _ = self.session.swap_window(*a, *b);
+        }
+        self.status.invalidate();
+    }
+
+    /// Ask for commands at the command prompt, starting with `text`.
+    fn command_prompt(&mut self, text: &str) {
+        if let Err(e) = self.history.reload() {
+            warn!("could not read the command history: {}", e);
+        }
+        let history = self.history.iter().map(String::from).collect();
+        self.prompt = Some(Prompt::command(text, history));
+    }
+
+    /// Perform the commands on `line`, entered at the command prompt, or show
+    /// why they cannot be.
+    fn run_command(&mut self, line: &str) -> Result<()> {
+        if let Err(e) = self.history.push(line) {
+            warn!("could not save the command history: {}", e);
+        }
+        self.perform_commands("command-prompt", line)
+    }
+
+    /// Perform the commands on `line`, or show why they cannot be under
+    /// `title`.
+    fn perform_commands(&mut self, title: &str, line: &str) -> Result<()> {
+        match command::parse(line) {
+            Ok(actions) => actions
+                .into_iter()
+                .try_for_each(|action| self.perform(action)),
+            Err(e) => self.show(title, &format!("{}: {}", line.trim(), e)),
+        }
+    }
+
+    /// Close the prompt, if it is open, and show the status line again.
+    fn close_prompt(&mut self) -> Result<()> {
+        if self.prompt.take().is_some() {
+            self.status.invalidate();
+        }
+        Ok(())
+    }
+
+    /// Close the overlay, if one is open, uncovering the window.
+    fn close_overlay(&mut self) -> Result<()> {
+        if self.overlay.take().is_some() {
+            self.session.mark_dirty();
+            write!(self.output, "\x1b[?25h")?;
+        }
+        Ok(())
+    }
+
+    /// Show `text`, the output of `command`, where `output` says, if there is
+    /// any.
+    fn show_shell_output(
+        &mut self,
+        command: &str,
+        text: &str,
+        output: ShellOutput,
+    ) -> Result<()> {
+        if text.trim().is_empty() {
+            return Ok(());
+        }
+        match output {
+            ShellOutput::Pager => self.show(command, text)?,
+            ShellOutput::Message => {
+                let lines = text.lines().map(str::trim).filter(|line| !line.is_empty());
+                self.display_message(&lines.collect::<Vec<_>>().join(" "));
+            }
+            ShellOutput::Window => {
+                self.open_applet(Box::new(Viewer::new(command, text)))?
+            }
+        }
+        Ok(())
+    }
+
+    /// Run `command` with the user's shell in a thread of its own, with the
+    /// environment new panes get, showing its output where `output` says.
+    fn run_shell(&self, command: String, output: ShellOutput) {
+        let sender = self.shell_sender.clone();
+        let mut shell = Command::new(util::POSIX_SHELL);
+        shell
+            .arg("-c")
+            .arg(&command)
+            .env_clear()
+            .envs(self.session.environment().vars())
+            .stdin(Stdio::null());
+        thread::spawn(move || {
+            let text = match shell.output() {
+                Ok(finished) => {
+                    let mut text =
+                        String::from_utf8_lossy(&finished.stdout).into_owned();
+                    text.push_str(&String::from_utf8_lossy(&finished.stderr));
+                    if !finished.status.success() {
+                        text.push_str(&format!("[{}]\n", finished.status));
+                    }
+                    text
+                }
+                Err(e) => format!("{}\n", e),
+            };
+            // The event loop may have finished meanwhile.
+            let _ = sender.unbounded_send((command, text, output));
+        });
+    }
+
+    /// Run `condition` with the shell in a thread of its own, with the
+    /// environment new panes get, and then perform the commands of `then` if it
+    /// succeeds, or of `otherwise` if it fails.
+    fn if_shell(&self, condition: String, then: String, otherwise: Option<String>) {
+        let sender = self.chosen_sender.clone();
+        let mut shell = Command::new(util::POSIX_SHELL);
+        shell
+            .arg("-c")
+            .arg(&condition)
+            .env_clear()
+            .envs(self.session.environment().vars())
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null());
+        thread::spawn(move || {
+            let succeeded = match shell.status() {
+                Ok(status) => status.success(),
+                Err(e) => {
+                    warn!("cannot run {:?}: {}", condition, e);
+                    false
+                }
+            };
+            let chosen = if succeeded { Some(then) } else { otherwise };
+            if let Some(line) = chosen {
+                // The event loop may have finished meanwhile.
+                let _ = sender.unbounded_send(line);
+            }
+        });
+    }
+
+    /// Give `text` to the copy command in the background, if there is one,
+    /// showing any error in the pager.
+    fn copy_to_clipboard(&self, text: &str) {
+        let env = self.session.environment().clone();
+        let configured = self.config.copy_command.as_deref();
+        let command = match clipboard::copy_command(configured, &env) {
+            Some(command) => command,
+            None => return,
+        };
+        let sender = self.shell_sender.clone();
+        let text = text.to_string();
+        thread::spawn(move || {
+            if let Err(e) = clipboard::copy(&command, &text, &env) {
+                let title = "copy-command".to_string();
+                let _ =
+                    sender.unbounded_send((title, e.to_string(), ShellOutput::Pager));
+            }
+        });
+    }
+
+    /// Cover the screen of every client with the lock screen, if it is not
+    /// already.
+    fn lock_screen(&mut self) {
+        if self.lock.is_none() {
+            self.lock = Some(Lock::new(self.config.lock_command.is_some()));
+        }
+    }
+
+    /// Unlock the screen if `typed` is the password, checking it with the lock
+    /// command in the background, or straight away if there is none.
+    fn check_unlock(&self, typed: String) {
+        let sender = self.unlock_sender.clone();
+        let command = match &self.config.lock_command {
+            Some(command) => command.clone(),
+            None => {
+                let _ = sender.unbounded_send(true);
+                return;
+            }
+        };
+        let env = self.session.environment().clone();
+        thread::spawn(move || {
+            let _ = sender.unbounded_send(lock::verify(&command, &typed, &env));
+        });
+    }
+
+    /// Take the lock screen away, drawing everything under it again.
+    fn unlock(&mut self) -> Result<()> {
+        if self.lock.take().is_none() {
+            return Ok(());
+        }
+        if self.overlay.is_none() {
+            write!(self.output, "\x1b[?25h")?;
+        }
+        self.redraw_all()
+    }
+
+    /// Run the paste command in the background, sending what it writes to the
+    /// active pane once it finishes, or showing why there is nothing to paste.
+    fn paste_clipboard(&self) {
+        let env = self.session.environment().clone();
+        let configured = self.config.paste_command.as_deref();
+        let sender = self.shell_sender.clone();
+        let command = match clipboard::paste_command(configured, &env) {
+            Some(command) => command,
+            None => {
+                let message = "no clipboard command found: set paste-command";
+                let _ = sender.unbounded_send((
+                    "paste-clipboard".to_string(),
+                    message.to_string(),
+                    ShellOutput::Pager,
+                ));
+                return;
+            }
+        };
+        let paste_sender = self.paste_sender.clone();
+        thread::spawn(move || match clipboard::paste(&command, &env) {
+            Ok(text) => {
+                let _ = paste_sender.unbounded_send(text);
+            }
+            Err(e) => {
+                let title = "paste-command".to_string();
+                let _ =
+                    sender.unbounded_send((title, e.to_string(), ShellOutput::Pager));
+            }
+        });
+    }
+
+    fn split_pane(&mut self, direction: Direction) -> Result<()> {
+        match self.session.split_pane(direction) {
+            Ok(updates) => self.watch(updates),
+            Err(SessionError::NoSpace) => self.show_no_space(match direction {
+                Direction::Horizontal => "split-horizontal",
+                Direction::Vertical => "split-vertical",
+            })?,
+            Err(e) => return Err(e.into()),
+        }
+        Ok(())
+    }
+
+    /// Say that `action` found no space for a pane of the minimum size.
+    fn show_no_space(&mut self, action: &str) -> Result<()> {
+        let min = self.session.min_pane_size();
+        let message = format!("{}: panes are at least {}", SessionError::NoSpace, min);
+        self.show(action, &message)
+    }
+
+    /// Take the updates of a pane of the session attached to.
+    fn watch(&mut self, updates: PaneUpdates) {
+        self.pty_updates.push(self.session_id, updates);
+    }
+
+    /// Open a window running `command`, or the shell, without selecting it.
+    fn new_window(&mut self, command: Option<&str>) -> Result<usize> {
+        let (idx, updates) = self.session.new_window_running(command, None, &[])?;
+        self.watch(updates);
+        Ok(idx)
+    }
+
+    /// Run `applet` in a new window, and select it.
+    fn open_applet(&mut self, applet: Box<dyn Applet>) -> Result<()> {
+        let (idx, updates) = self.session.new_applet_window(applet)?;
+        self.watch(updates);
+        self.session.select_window(idx);
+        Ok(())
+    }
+}
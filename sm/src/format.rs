@@ -0,0 +1,87 @@
+//! Formats: text with variables in it, written `#{name}`, such as the right of
+//! the status line.
+
+use session_manager::session::{Session, SessionPane};
+
+/// Replace each `#{name}` in `format` with `value(name)`, or nothing if it has
+/// none, and each `##` with `#`.
+pub fn expand<F>(format: &str, value: F) -> String
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let mut text = String::with_capacity(format.len());
+    let mut rest = format;
+    while let Some(start) = rest.find('#') {
+        text.push_str(&rest[..start]);
+        rest = &rest[start..];
+        if let Some(after) = rest.strip_prefix("##") {
+            text.push('#');
+            rest = after;
+        } else if let Some(end) = rest.find('}').filter(|_| rest.starts_with("#{")) {
+            text.push_str(&value(&rest[2..end]).unwrap_or_default());
+            rest = &rest[end + 1..];
+        } else {
+            text.push('#');
+            rest = &rest[1..];
+        }
+    }
+    text.push_str(rest);
+    text
+}
+
+/// The value of the variable `name` for `session`, if it has one:
+///
+/// - `session_name`, `window_index` and `window_name`, of the selected window.
+/// - `pane_index`: the number of the active pane in its window.
+/// - `pane_title`: the title set by the active pane's program.
+/// - `window_bytes_in`: the bytes of output read from the panes of the
+///   selected window.
+/// - `pane_bytes_in`: those read from the active pane.
+/// - `client_bytes_out`: the bytes sent to the client attached.
+/// - `exit_status`: how the process of the pane to exit last exited, e.g.
+///   `killed by SIGTERM`, for the `pane-exited` hook.
+/// - `exit_code`: its status as a shell gives it, 128 and the signal's number
+///   if it was killed by one.
+pub fn variable<P: SessionPane>(
+    session: &Session<P>,
+    name: &str,
+    client_bytes_out: u64,
+) -> Option<String> {
+    let window = session.selected_window_idx();
+    Some(match name {
+        "session_name" => session.name().to_string(),
+        "window_index" => window?.to_string(),
+        "window_name" => session.window_name(window?)?.to_string(),
+        "pane_index" => session.active_pane_number()?.to_string(),
+        "pane_title" => session.active_pane()?.title()?.to_string(),
+        "window_bytes_in" => session.window_bytes_read(window?)?.to_string(),
+        "pane_bytes_in" => session.active_pane()?.bytes_read().to_string(),
+        "client_bytes_out" => client_bytes_out.to_string(),
+        "exit_status" => session.last_exit_status()?.to_string(),
+        "exit_code" => session.last_exit_status()?.code().to_string(),
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn variables() {
+        let value = |name: &str| match name {
+            "window_name" => Some("vim".to_string()),
+            "window_bytes_in" => Some("1024".to_string()),
+            _ => None,
+        };
+        assert_eq!(
+            expand("#{window_name}: #{window_bytes_in} in", value),
+            "vim: 1024 in"
+        );
+        assert_eq!(
+            expand("#{nothing}|##{window_name}|#1|#{", value),
+            "|#{window_name}|#1|#{"
+        );
+        assert_eq!(expand("", value), "");
+    }
+}
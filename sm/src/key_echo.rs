@@ -0,0 +1,233 @@
+//! An on-screen display of the keys typed, for screencasts and teaching.
+
+use std::{
+    collections::VecDeque,
+    io::{self, Write},
+    time::{Duration, Instant},
+};
+
+use nix::pty::Winsize;
+use session_manager::width;
+
+use crate::backend::Key;
+
+/// How many columns of keys are shown.
+const WIDTH: usize = 24;
+
+/// How long the keys stay on screen after the last one is typed.
+pub const TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How long they stay with reduced motion, to be read without hurrying.
+pub const REDUCED_MOTION_TIMEOUT: Duration = Duration::from_secs(6);
+
+/// What typed characters are shown as while a password is being entered.
+const REDACTED: &str = "\u{2022}";
+
+/// The keys recently sent to the active pane, drawn in the bottom right corner
+/// of the windows.
+#[derive(Debug)]
+pub struct KeyEcho {
+    /// The labels of the keys, oldest first.
+    keys: VecDeque<String>,
+    last: Option<Instant>,
+    /// How long the keys stay after the last one.
+    timeout: Duration,
+    /// The text on screen, if any.
+    drawn: Option<String>,
+}
+
+impl Default for KeyEcho {
+    fn default() -> KeyEcho {
+        KeyEcho::new(TIMEOUT)
+    }
+}
+
+impl KeyEcho {
+    /// A display of keys which stay on screen until `timeout` after the last.
+    pub fn new(timeout: Duration) -> KeyEcho {
+        KeyEcho {
+            keys: VecDeque::new(),
+            last: None,
+            timeout,
+            drawn: None,
+        }
+    }
+
+    /// Record that `key` was typed at `now`, hiding characters if `secret`.
+    pub fn push(&mut self, key: Key, secret: bool, now: Instant) {
+        if self.is_expired(now) {
+            self.keys.clear();
+        }
+        let label = match key {
+            Key::Char(c) | Key::Alt(c) if secret && c != '\n' => REDACTED.to_string(),
+            key => label(key),
+        };
+        self.keys.push_back(label);
+        // Only as many keys as could be shown are kept.
+        while self.keys.len() > WIDTH {
+            self.keys.pop_front();
+        }
+        self.last = Some(now);
+    }
+
+    /// Draw the keys typed recently on the last row of `area`, returning
+    /// `true` if something they covered before has to be redrawn.
+    ///
+    /// The cursor is left where it was.
+    pub fn draw<T: Write>(
+        &mut self,
+        output: &mut T,
+        area: Winsize,
+        now: Instant,
+    ) -> io::Result<bool> {
+        let text = if self.is_expired(now) {
+            String::new()
+        } else {
+            self.text()
+        };
+        let uncovered = match &self.drawn {
+            Some(drawn) => width::str_width(drawn) > width::str_width(&text),
+            None => false,
+        };
+        if text.is_empty() {
+            self.drawn = None;
+            return Ok(uncovered);
+        }
+        let width = width::str_width(&text) as u16;
+        write!(
+            output,
+            "\x1b7\x1b[{};{}H\x1b[0;7m{}\x1b[0m\x1b8",
+            area.ws_row,
+            area.ws_col.saturating_sub(width) + 1,
+            text
+        )?;
+        self.drawn = Some(text);
+        Ok(uncovered)
+    }
+
+    fn is_expired(&self, now: Instant) -> bool {
+        match self.last {
+            Some(last) => now.saturating_duration_since(last) >= self.timeout,
+            None => true,
+        }
+    }
+
+    /// The last `WIDTH` columns of the keys, with named keys set apart by
+    /// spaces.
+    fn text(&self) -> String {
+        let mut text = String::new();
+        for key in &self.keys {
+            if key.chars().count() == 1 {
+                text.push_str(key);
+            } else {
+                if !text.is_empty() && !text.ends_with(' ') {
+                    text.push(' ');
+                }
+                text.push_str(key);
+                text.push(' ');
+            }
+        }
+        let text = text.trim_end();
+        let skip = text.chars().count().saturating_sub(WIDTH);
+        format!(" {} ", text.chars().skip(skip).collect::<String>())
+    }
+}
+
+/// How `key` is shown.
+fn label(key: Key) -> String {
+    match key {
+        Key::Char(' ') => "\u{2423}".to_string(),
+        Key::Char('\n') => "\u{23ce}".to_string(),
+        Key::Char('\t') => "Tab".to_string(),
+        Key::Char(c) => c.to_string(),
+        Key::Alt(c) => format!("M-{}", c),
+        Key::Ctrl(c) => format!("C-{}", c),
+        Key::Backspace => "\u{232b}".to_string(),
+        Key::Left => "\u{2190}".to_string(),
+        Key::Right => "\u{2192}".to_string(),
+        Key::Up => "\u{2191}".to_string(),
+        Key::Down => "\u{2193}".to_string(),
+        Key::CtrlLeft => "C-\u{2190}".to_string(),
+        Key::CtrlRight => "C-\u{2192}".to_string(),
+        Key::CtrlUp => "C-\u{2191}".to_string(),
+        Key::CtrlDown => "C-\u{2193}".to_string(),
+        Key::ShiftLeft => "S-\u{2190}".to_string(),
+        Key::ShiftRight => "S-\u{2192}".to_string(),
+        Key::ShiftUp => "S-\u{2191}".to_string(),
+        Key::ShiftDown => "S-\u{2193}".to_string(),
+        Key::F(n) => format!("F{}", n),
+        key => format!("{:?}", key),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AREA: Winsize = Winsize {
+        ws_row: 23,
+        ws_col: 80,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+
+    #[test]
+    fn text() {
+        let now = Instant::now();
+        let mut echo = KeyEcho::default();
+        for key in [
+            Key::Char('l'),
+            Key::Char('s'),
+            Key::Char(' '),
+            Key::Ctrl('c'),
+        ] {
+            echo.push(key, false, now);
+        }
+        echo.push(Key::Char('\n'), false, now);
+        assert_eq!(echo.text(), " ls\u{2423} C-c \u{23ce} ");
+
+        let mut echo = KeyEcho::default();
+        for c in "hunter2".chars() {
+            echo.push(Key::Char(c), true, now);
+        }
+        echo.push(Key::Char('\n'), true, now);
+        assert_eq!(echo.text(), format!(" {}\u{23ce} ", REDACTED.repeat(7)));
+
+        for c in "abcdefghijklmnopqrstuvwxyz".chars() {
+            echo.push(Key::Char(c), false, now);
+        }
+        assert_eq!(echo.text(), " cdefghijklmnopqrstuvwxyz ");
+    }
+
+    #[test]
+    fn draw() {
+        let now = Instant::now();
+        let mut echo = KeyEcho::default();
+        let mut out = Vec::new();
+        assert!(!echo.draw(&mut out, AREA, now).unwrap());
+        assert!(out.is_empty());
+
+        echo.push(Key::Char('q'), false, now);
+        assert!(!echo.draw(&mut out, AREA, now).unwrap());
+        assert_eq!(out, b"\x1b7\x1b[23;78H\x1b[0;7m q \x1b[0m\x1b8");
+
+        // Old keys go, uncovering what was underneath.
+        out.clear();
+        let later = now + TIMEOUT;
+        assert!(echo.draw(&mut out, AREA, later).unwrap());
+        assert!(out.is_empty());
+        echo.push(Key::Char('w'), false, later);
+        assert_eq!(echo.text(), " w ");
+
+        // With reduced motion, they stay longer.
+        let mut echo = KeyEcho::new(REDUCED_MOTION_TIMEOUT);
+        echo.push(Key::Char('q'), false, now);
+        out.clear();
+        assert!(!echo.draw(&mut out, AREA, now + TIMEOUT).unwrap());
+        assert!(!out.is_empty());
+        out.clear();
+        let later = now + REDUCED_MOTION_TIMEOUT;
+        assert!(echo.draw(&mut out, AREA, later).unwrap());
+        assert!(out.is_empty());
+    }
+}
@@ -0,0 +1,120 @@
+//! Output for screen readers and braille displays: the lines the active pane
+//! writes, one after another, rather than a screen painted in place.
+
+use std::io::{self, Write};
+
+use session_manager::session::NewLines;
+
+/// Clears the terminal and moves the cursor to its top left corner.
+const CLEAR: &str = "\x1b[H\x1b[2J";
+
+/// Writes what the active pane adds as plain lines, so that a reader following
+/// the terminal line by line is not sent the whole screen again on each
+/// change.
+#[derive(Debug, Default)]
+pub struct LinearOutput {
+    /// The pane written from last.
+    pane_idx: Option<usize>,
+    /// What is on the last line written so far.
+    partial: String,
+    /// Whether the terminal is to be cleared before anything else is written.
+    clear: bool,
+}
+
+impl LinearOutput {
+    /// Start again on a clear terminal, e.g. after something else was drawn on
+    /// it: the line being written is written again in full.
+    pub fn reset(&mut self) {
+        self.pane_idx = None;
+        self.partial.clear();
+        self.clear = true;
+    }
+
+    /// Write `new_lines`, announcing the pane with `heading` if it is not the
+    /// one written from last.
+    pub fn write<T: Write>(
+        &mut self,
+        output: &mut T,
+        new_lines: &NewLines,
+        heading: &str,
+    ) -> io::Result<()> {
+        if self.clear {
+            write!(output, "{}", CLEAR)?;
+            self.clear = false;
+        }
+        if self.pane_idx != Some(new_lines.pane_idx) {
+            if !self.partial.is_empty() {
+                write!(output, "\r\n")?;
+            }
+            write!(output, "[{}]\r\n", heading)?;
+            self.pane_idx = Some(new_lines.pane_idx);
+            self.partial.clear();
+        }
+        for line in &new_lines.lines {
+            self.update(output, line)?;
+            write!(output, "\r\n")?;
+            self.partial.clear();
+        }
+        self.update(output, &new_lines.current)
+    }
+
+    /// Change the last line from what was written to `text`, erasing only the
+    /// characters which differ.
+    fn update<T: Write>(&mut self, output: &mut T, text: &str) -> io::Result<()> {
+        let common = self
+            .partial
+            .char_indices()
+            .zip(text.chars())
+            .find(|((_, a), b)| a != b)
+            .map_or(self.partial.len().min(text.len()), |((i, _), _)| i);
+        for _ in self.partial[common..].chars() {
+            write!(output, "\x08 \x08")?;
+        }
+        write!(output, "{}", &text[common..])?;
+        self.partial = text.to_string();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(
+        linear: &mut LinearOutput,
+        pane_idx: usize,
+        lines: &[&str],
+        current: &str,
+    ) -> String {
+        let new_lines = NewLines {
+            pane_idx,
+            lines: lines.iter().map(|line| line.to_string()).collect(),
+            current: current.to_string(),
+        };
+        let mut out = Vec::new();
+        linear.write(&mut out, &new_lines, "0: sh").unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn lines() {
+        let mut linear = LinearOutput::default();
+        assert_eq!(write(&mut linear, 0, &[], "$ "), "[0: sh]\r\n$ ");
+        assert_eq!(write(&mut linear, 0, &[], "$ ls"), "ls");
+        assert_eq!(
+            write(&mut linear, 0, &["$ ls", "a  b"], "$ "),
+            "\r\na  b\r\n$ "
+        );
+        assert_eq!(write(&mut linear, 0, &[], "$ "), "");
+        // Characters rubbed out are erased.
+        assert_eq!(write(&mut linear, 0, &[], "$ ca"), "ca");
+        assert_eq!(write(&mut linear, 0, &[], "$ cd"), "\x08 \x08d");
+        assert_eq!(write(&mut linear, 0, &[], "$ é"), "\x08 \x08\x08 \x08é");
+        assert_eq!(write(&mut linear, 0, &[], "$ è"), "\x08 \x08è");
+
+        // Another pane is announced on a line of its own.
+        assert_eq!(write(&mut linear, 1, &["x"], ""), "\r\n[0: sh]\r\nx\r\n");
+        linear.reset();
+        assert_eq!(write(&mut linear, 1, &[], "y"), "\x1b[H\x1b[2J[0: sh]\r\ny");
+    }
+}
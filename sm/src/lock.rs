@@ -0,0 +1,190 @@
+//! The lock screen, hiding the windows of an idle session until whoever is at
+//! the terminal unlocks it, with a password if there is a lock command.
+
+use std::{
+    io::{self, Write},
+    mem,
+    process::{Command, Stdio},
+};
+
+use nix::pty::Winsize;
+
+use session_manager::{environment::Environment, util, width};
+
+use crate::backend::Key;
+
+/// What the lock screen made of a key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LockInput {
+    /// The screen stays locked.
+    Pending,
+    /// The text typed is to be checked, to unlock the screen.
+    Submit(String),
+}
+
+/// A blank screen taking every key typed, without showing it, until Enter.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lock {
+    typed: String,
+    /// Whether a password is asked for, rather than only Enter.
+    password: bool,
+    /// Whether the text last submitted is being checked.
+    checking: bool,
+    /// Whether the text last submitted was wrong.
+    failed: bool,
+    /// Whether the screen has to be drawn again.
+    dirty: bool,
+}
+
+impl Lock {
+    /// Lock the screen, asking for a password to unlock it if `password`.
+    pub fn new(password: bool) -> Lock {
+        Lock {
+            typed: String::new(),
+            password,
+            checking: false,
+            failed: false,
+            dirty: true,
+        }
+    }
+
+    /// Add characters to the text typed, and submit it with Enter. Nothing is
+    /// taken while the text submitted before is checked.
+    pub fn handle_key(&mut self, key: Key) -> LockInput {
+        if self.checking {
+            return LockInput::Pending;
+        }
+        match key {
+            Key::Char('\n') => {
+                self.checking = true;
+                self.failed = false;
+                self.dirty = true;
+                return LockInput::Submit(mem::take(&mut self.typed));
+            }
+            Key::Char(c) => self.typed.push(c),
+            Key::Backspace => {
+                self.typed.pop();
+            }
+            Key::Ctrl('u') | Key::Esc => self.typed.clear(),
+            _ => (),
+        }
+        LockInput::Pending
+    }
+
+    /// Take keys again after the text submitted was wrong.
+    pub fn fail(&mut self) {
+        self.checking = false;
+        self.failed = true;
+        self.dirty = true;
+    }
+
+    /// Draw the screen, all of `size`, if it has changed since it was last
+    /// drawn.
+    pub fn draw<T: Write>(&mut self, output: &mut T, size: Winsize) -> io::Result<()> {
+        if !self.dirty {
+            return Ok(());
+        }
+        let cols = size.ws_col as usize;
+        let line = if self.checking {
+            "Checking..."
+        } else if self.failed {
+            "Wrong password. Type it again and press Enter."
+        } else if self.password {
+            "Locked. Type the password and press Enter."
+        } else {
+            "Locked. Press Enter."
+        };
+        let line = width::truncate(line, cols);
+        let col = cols.saturating_sub(width::str_width(line)) / 2;
+        write!(
+            output,
+            "\x1b[0m\x1b[?25l\x1b[H\x1b[2J\x1b[{};{}H{}",
+            size.ws_row / 2 + 1,
+            col + 1,
+            line
+        )?;
+        self.dirty = false;
+        Ok(())
+    }
+
+    /// Draw the screen again on the next `draw`, e.g. after the terminal was
+    /// cleared.
+    pub fn mark_dirty(&mut self) {
+        self.dirty = true;
+    }
+}
+
+/// Whether `command`, run with the shell in `env` and given `typed` on its
+/// standard input, exits successfully, to unlock the screen.
+pub fn verify(command: &str, typed: &str, env: &Environment) -> bool {
+    let child = Command::new(util::POSIX_SHELL)
+        .arg("-c")
+        .arg(command)
+        .env_clear()
+        .envs(env.vars())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
+    let mut child = match child {
+        Ok(child) => child,
+        Err(_) => return false,
+    };
+    // The command may not read it all, e.g. if it fails.
+    let _ = writeln!(child.stdin.take().unwrap(), "{}", typed);
+    child.wait().is_ok_and(|status| status.success())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys() {
+        let mut lock = Lock::new(true);
+        for key in [Key::Char('x'), Key::Esc, Key::Char('p'), Key::Char('w')] {
+            assert_eq!(lock.handle_key(key), LockInput::Pending);
+        }
+        lock.handle_key(Key::Char('!'));
+        lock.handle_key(Key::Backspace);
+        let submitted = lock.handle_key(Key::Char('\n'));
+        assert_eq!(submitted, LockInput::Submit("pw".to_string()));
+        // Keys are ignored while it is checked.
+        lock.handle_key(Key::Char('a'));
+        lock.fail();
+        lock.handle_key(Key::Char('b'));
+        let submitted = lock.handle_key(Key::Char('\n'));
+        assert_eq!(submitted, LockInput::Submit("b".to_string()));
+    }
+
+    #[test]
+    fn draw_hides_typing() {
+        let size = Winsize {
+            ws_row: 5,
+            ws_col: 40,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        };
+        let mut lock = Lock::new(false);
+        lock.handle_key(Key::Char('s'));
+        let mut output = Vec::new();
+        lock.draw(&mut output, size).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(
+            output.ends_with("\x1b[3;11HLocked. Press Enter."),
+            "{:?}",
+            output
+        );
+        let mut output = Vec::new();
+        lock.draw(&mut output, size).unwrap();
+        assert!(output.is_empty());
+    }
+
+    #[test]
+    fn verify_password() {
+        let env = Environment::new();
+        assert!(verify("read pw && test \"$pw\" = secret", "secret", &env));
+        assert!(!verify("read pw && test \"$pw\" = secret", "guess", &env));
+        assert!(!verify("exit 1", "", &env));
+    }
+}
@@ -0,0 +1,676 @@
+//! Session manager
+//!
+//! A would-be terminal multiplexer.
+
+#![recursion_limit = "1024"]
+
+mod backend;
+mod bindings;
+mod buffers;
+mod chooser;
+mod client;
+mod clipboard;
+mod command;
+mod config;
+mod copy_mode;
+mod event;
+mod format;
+mod key_echo;
+mod linear;
+mod lock;
+mod menu;
+mod mouse;
+mod options;
+mod pager;
+mod palette;
+mod prompt;
+mod protocol;
+#[cfg(feature = "screenshot")]
+mod screenshot;
+mod server;
+mod size_labels;
+mod slow_link;
+mod state;
+mod status;
+mod style;
+mod summary;
+mod tee;
+mod themes;
+mod title;
+mod tree;
+mod watch;
+
+use std::{
+    env,
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    os::unix::{
+        io::{AsRawFd, FromRawFd, RawFd},
+        net::{UnixListener, UnixStream},
+    },
+    path::PathBuf,
+    time::Duration,
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use futures::executor;
+use log::{error, warn, LevelFilter};
+use log4rs::{
+    append::file::FileAppender,
+    config::{Appender, Config as LogConfig, Root},
+};
+use nix::{
+    fcntl::{self, FcntlArg},
+    pty::Winsize,
+    unistd::{self, ForkResult},
+};
+
+use session_manager::{
+    grid::TextBlink,
+    history::History,
+    replay,
+    session::{CursorBlink, Pane, Session},
+    snapshot::SessionSnapshot,
+    terminfo,
+};
+
+use crate::{
+    backend::{DefaultBackend, TerminalBackend},
+    client::OutputFormat,
+    config::Config,
+    event::EventLoop,
+    mouse::MouseOptions,
+    protocol::ClientMessage,
+    server::Server,
+    slow_link::{LinkConditions, SlowLink},
+    tee::Tee,
+};
+
+/// How much of a capture `sm replay` feeds in at once: as much as a pane reads
+/// from its pty.
+const REPLAY_CHUNK: usize = 4096;
+
+/// What `sm` was asked to do.
+#[derive(Debug, Default, PartialEq, Eq)]
+enum Command {
+    /// Start a session, and the server if there is none, and attach to it.
+    #[default]
+    New,
+    /// Attach to a session running.
+    Attach,
+    /// List the sessions running.
+    List,
+    /// List the windows of a session.
+    ListWindows,
+    /// List the panes of each window of a session.
+    ListPanes,
+    /// List the clients attached.
+    ListClients,
+    /// List the paste buffers of a session.
+    ListBuffers,
+    /// Show the bytes read from each window and sent to the client.
+    Info,
+    /// Start a server with the sessions saved by the last one, and attach to
+    /// the first.
+    Restore,
+    /// Print the environment new panes would get.
+    ShowEnvironment,
+    /// Draw what the active pane of a session shows to a PNG file.
+    Screenshot,
+    /// Write a record of a session as text to a new directory.
+    ExportSession,
+    /// Show a format on the status line of a session, or print it.
+    DisplayMessage,
+    /// Rename a session.
+    RenameSession,
+    /// End a session, closing its windows.
+    KillSession,
+    /// Move the windows of one session into another, ending the first.
+    MergeSession,
+    /// Replay output captured from a terminal through a grid, and print what
+    /// drawing it cost.
+    Replay,
+}
+
+/// Options given on the command line.
+#[derive(Debug, Default)]
+struct Options {
+    command: Command,
+    /// The name of the session to start, or attach to.
+    session: Option<String>,
+    /// The session whose windows the session started shares, if any.
+    group: Option<String>,
+    /// The session whose windows `merge-session` moves.
+    source: Option<String>,
+    /// The profile whose server, configuration and saved sessions are used,
+    /// rather than the default ones.
+    profile: Option<String>,
+    /// The configuration file read instead of the profile's.
+    config: Option<PathBuf>,
+    /// How `ls` and the other `list-` commands print what they list.
+    format: OutputFormat,
+    /// The format `display-message` expands, or the name `rename-session`
+    /// gives.
+    message: Option<String>,
+    /// Whether `display-message` prints the message rather than show it.
+    print: bool,
+    /// Whether `attach` detaches the other clients.
+    detach: bool,
+    /// Where `screenshot` writes its picture, or `export-session` its
+    /// directory.
+    output: Option<PathBuf>,
+    /// The capture `replay` reads.
+    capture: Option<PathBuf>,
+    /// The size `replay` draws at, if not 80 by 24.
+    cols: Option<u16>,
+    rows: Option<u16>,
+    /// Whether the cursor blinks, if given.
+    cursor_blink: Option<CursorBlink>,
+    scale_line_attrs: bool,
+    pane_titles: bool,
+    /// How blinking text is shown, if given.
+    text_blink: Option<TextBlink>,
+    show_keys: bool,
+    /// Whether to do without animations, and leave things on screen longer.
+    reduced_motion: bool,
+    /// Whether to write the lines of the active pane in turn, for screen
+    /// readers, rather than draw the screen.
+    screen_reader: bool,
+    /// The `TERM` given to new windows, rather than the one `sm` runs in.
+    term: Option<String>,
+    tee: Option<TeeTarget>,
+    link: LinkConditions,
+    mouse: MouseOptions,
+}
+
+/// Where to mirror output to the terminal.
+#[derive(Debug)]
+enum TeeTarget {
+    Path(PathBuf),
+    Fd(RawFd),
+}
+
+impl TeeTarget {
+    fn open(&self) -> Result<Box<dyn Write + Send>> {
+        Ok(Box::new(match self {
+            TeeTarget::Path(path) => File::create(path)?,
+            // The fd is inherited from whoever started us, for us to own, and
+            // was checked to be open by `tee_fd`.
+            TeeTarget::Fd(fd) => unsafe { File::from_raw_fd(*fd) },
+        }))
+    }
+}
+
+/// The file descriptor given to `--tee-fd` as `arg`, if it is open and not
+/// standard input, output or error, which the server replaces.
+fn tee_fd(arg: &str) -> Result<RawFd> {
+    let fd: RawFd = arg
+        .parse()
+        .map_err(|_| anyhow!("--tee-fd needs a file descriptor, not {}", arg))?;
+    if (0..=2).contains(&fd) {
+        bail!("--tee-fd cannot be standard input, output or error");
+    }
+    fcntl::fcntl(fd, FcntlArg::F_GETFD)
+        .map_err(|e| anyhow!("--tee-fd {} is not open: {}", fd, e))?;
+    Ok(fd)
+}
+
+fn parse_args() -> Result<Options> {
+    let mut options = Options::default();
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "new" => options.command = Command::New,
+            "attach" => options.command = Command::Attach,
+            "ls" => options.command = Command::List,
+            "list-windows" => options.command = Command::ListWindows,
+            "list-panes" => options.command = Command::ListPanes,
+            "list-clients" => options.command = Command::ListClients,
+            "list-buffers" => options.command = Command::ListBuffers,
+            "info" => options.command = Command::Info,
+            "restore" => options.command = Command::Restore,
+            "screenshot" => options.command = Command::Screenshot,
+            "export-session" => options.command = Command::ExportSession,
+            "display-message" => options.command = Command::DisplayMessage,
+            "rename-session" => options.command = Command::RenameSession,
+            "kill-session" => options.command = Command::KillSession,
+            "merge-session" => options.command = Command::MergeSession,
+            "replay" => options.command = Command::Replay,
+            "-p" => options.print = true,
+            "-d" => options.detach = true,
+            "--format" => {
+                let format = args
+                    .next()
+                    .ok_or_else(|| anyhow!("--format needs text or json"))?;
+                options.format = format.parse()?;
+            }
+            "-o" => {
+                let path = args.next().ok_or_else(|| anyhow!("-o needs a path"))?;
+                options.output = Some(path.into());
+            }
+            "-x" | "-y" if options.command == Command::Replay => {
+                let size = args
+                    .next()
+                    .ok_or_else(|| anyhow!("{} needs a number of cells", arg))?;
+                let size = size.parse().context("invalid number of cells")?;
+                match arg.as_str() {
+                    "-x" => options.cols = Some(size),
+                    _ => options.rows = Some(size),
+                }
+            }
+            "-s" if options.command == Command::MergeSession => {
+                let name = args
+                    .next()
+                    .ok_or_else(|| anyhow!("-s needs a session name"))?;
+                options.source = Some(name);
+            }
+            "-s" | "-t" => {
+                let name = args
+                    .next()
+                    .ok_or_else(|| anyhow!("{} needs a session name", arg))?;
+                options.session = Some(name);
+            }
+            "-g" => {
+                let name = args
+                    .next()
+                    .ok_or_else(|| anyhow!("-g needs a session name"))?;
+                options.group = Some(name);
+            }
+            "--profile" => {
+                let name = args
+                    .next()
+                    .ok_or_else(|| anyhow!("--profile needs a name"))?;
+                // It names files, so must stay in their directory.
+                if name.is_empty() || name.starts_with('.') || name.contains('/') {
+                    bail!("bad profile name: {:?}", name);
+                }
+                options.profile = Some(name);
+            }
+            "--config" => {
+                let path = args
+                    .next()
+                    .ok_or_else(|| anyhow!("--config needs a path"))?;
+                options.config = Some(path.into());
+            }
+            "show-environment" => options.command = Command::ShowEnvironment,
+            "--cursor-blink" => {
+                let value = args
+                    .next()
+                    .ok_or_else(|| anyhow!("--cursor-blink needs a value"))?;
+                let value = value.parse().map_err(|e: String| anyhow!(e))?;
+                options.cursor_blink = Some(value);
+            }
+            "--scale-line-attrs" => options.scale_line_attrs = true,
+            "--pane-titles" => options.pane_titles = true,
+            "--text-blink" => {
+                let value = args
+                    .next()
+                    .ok_or_else(|| anyhow!("--text-blink needs a value"))?;
+                let value = value.parse().map_err(|e: String| anyhow!(e))?;
+                options.text_blink = Some(value);
+            }
+            "--show-keys" => options.show_keys = true,
+            "--reduced-motion" => options.reduced_motion = true,
+            "--screen-reader" => options.screen_reader = true,
+            "--term" => {
+                let term =
+                    args.next().ok_or_else(|| anyhow!("--term needs a value"))?;
+                options.term = Some(term);
+            }
+            "--tee" => {
+                let path = args.next().ok_or_else(|| anyhow!("--tee needs a path"))?;
+                options.tee = Some(TeeTarget::Path(path.into()));
+            }
+            "--tee-fd" => {
+                let fd = args
+                    .next()
+                    .ok_or_else(|| anyhow!("--tee-fd needs a file descriptor"))?;
+                options.tee = Some(TeeTarget::Fd(tee_fd(&fd)?));
+            }
+            "--simulate-latency" => {
+                let ms = args
+                    .next()
+                    .ok_or_else(|| anyhow!("--simulate-latency needs milliseconds"))?;
+                options.link.latency = Duration::from_millis(ms.parse()?);
+            }
+            "--simulate-bandwidth" => {
+                let rate = args.next().ok_or_else(|| {
+                    anyhow!("--simulate-bandwidth needs bytes per second")
+                })?;
+                options.link.bandwidth = Some(rate.parse()?);
+            }
+            "--double-click-time" => {
+                let ms = args
+                    .next()
+                    .ok_or_else(|| anyhow!("--double-click-time needs milliseconds"))?;
+                options.mouse.double_click_time = Duration::from_millis(ms.parse()?);
+            }
+            "--click-distance" => {
+                let cells = args.next().ok_or_else(|| {
+                    anyhow!("--click-distance needs a number of cells")
+                })?;
+                options.mouse.click_distance = cells.parse()?;
+            }
+            "--drag-threshold" => {
+                let cells = args.next().ok_or_else(|| {
+                    anyhow!("--drag-threshold needs a number of cells")
+                })?;
+                options.mouse.drag_threshold = cells.parse()?;
+            }
+            _ if matches!(
+                options.command,
+                Command::DisplayMessage | Command::RenameSession
+            ) && options.message.is_none() =>
+            {
+                options.message = Some(arg)
+            }
+            _ if options.command == Command::Replay && options.capture.is_none() => {
+                options.capture = Some(arg.into())
+            }
+            _ => bail!("unknown argument: {}", arg),
+        }
+    }
+    Ok(options)
+}
+
+fn main() -> Result<()> {
+    let options = parse_args()?;
+    let profile = options.profile.as_deref();
+    let socket = server::socket_path(profile).context("no directory for the socket")?;
+    let connect = || {
+        UnixStream::connect(&socket)
+            .with_context(|| format!("no server running at {}", socket.display()))
+    };
+    let load_config = || match (&options.config, Config::default_path(profile)) {
+        (Some(path), _) => Config::read(path),
+        (None, Some(path)) => Config::load(&path),
+        (None, None) => Ok(Config::default()),
+    };
+    match options.command {
+        Command::Attach => {
+            let wide = ambiguous_wide(&load_config()?);
+            let request = ClientMessage::Attach {
+                name: options.session,
+                detach: options.detach,
+                env: env::vars().collect(),
+            };
+            return client::attach(connect()?, request, wide);
+        }
+        Command::List => return client::list_sessions(connect()?, options.format),
+        Command::ListWindows => {
+            return client::list_windows(connect()?, options.session, options.format)
+        }
+        Command::ListPanes => {
+            return client::list_panes(connect()?, options.session, options.format)
+        }
+        Command::ListClients => {
+            return client::list_clients(connect()?, options.format)
+        }
+        Command::ListBuffers => {
+            return client::list_buffers(connect()?, options.session, options.format)
+        }
+        Command::Info => return client::info(connect()?),
+        #[cfg(feature = "screenshot")]
+        Command::Screenshot => {
+            let path = options
+                .output
+                .ok_or_else(|| anyhow!("screenshot needs -o PATH"))?;
+            let (cols, rows, contents) =
+                client::capture_pane(connect()?, options.session)?;
+            return screenshot::save(&path, cols, rows, &contents);
+        }
+        #[cfg(not(feature = "screenshot"))]
+        Command::Screenshot => bail!("sm was built without the screenshot feature"),
+        Command::ExportSession => {
+            let path = options
+                .output
+                .ok_or_else(|| anyhow!("export-session needs -o DIR"))?;
+            return client::export_session(connect()?, options.session, &path);
+        }
+        Command::DisplayMessage => {
+            let format = options
+                .message
+                .ok_or_else(|| anyhow!("display-message needs a format"))?;
+            let stream = connect()?;
+            return client::display_message(
+                stream,
+                options.session,
+                format,
+                options.print,
+            );
+        }
+        Command::RenameSession => {
+            let name = options
+                .message
+                .ok_or_else(|| anyhow!("rename-session needs a name"))?;
+            return client::rename_session(connect()?, options.session, name);
+        }
+        Command::KillSession => {
+            return client::kill_session(connect()?, options.session)
+        }
+        Command::MergeSession => {
+            let source = options
+                .source
+                .ok_or_else(|| anyhow!("merge-session needs -s and a session name"))?;
+            return client::merge_session(connect()?, source, options.session);
+        }
+        Command::Replay => {
+            let path = options
+                .capture
+                .ok_or_else(|| anyhow!("replay needs a capture file"))?;
+            let (cols, rows) = (options.cols.unwrap_or(80), options.rows.unwrap_or(24));
+            let stats = replay::replay_file(&path, cols, rows, REPLAY_CHUNK)
+                .with_context(|| format!("cannot read {}", path.display()))?;
+            println!(
+                "{} bytes read, {} frames, {} cells updated, {} bytes emitted",
+                stats.bytes_read,
+                stats.frames,
+                stats.cells_updated,
+                stats.bytes_emitted
+            );
+            return Ok(());
+        }
+        _ => (),
+    }
+    if let Some(term) = &options.term {
+        // Fail before taking over the terminal.
+        if !terminfo::exists(term) {
+            bail!("no terminfo entry for {}", term);
+        }
+    }
+    let config = load_config()?;
+    let wide = ambiguous_wide(&config);
+    if options.command == Command::ShowEnvironment {
+        // No panes are created, so the size does not matter.
+        let size = Winsize {
+            ws_row: 0,
+            ws_col: 0,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        };
+        let mut session = Session::<Pane>::new(size);
+        apply_environment(&mut session, &config);
+        print!("{}", session.environment().show());
+        return Ok(());
+    }
+
+    let restore = if options.command == Command::Restore {
+        if UnixStream::connect(&socket).is_ok() {
+            bail!("a server is running already; `sm restore` starts one");
+        }
+        let path = state::default_path(profile)
+            .ok_or_else(|| anyhow!("no directory for the state file"))?;
+        let sessions = state::load(&path)?;
+        if sessions.is_empty() {
+            bail!("no sessions saved in {}", path.display());
+        }
+        sessions
+    } else {
+        if let Ok(stream) = UnixStream::connect(&socket) {
+            let message = ClientMessage::NewSession {
+                name: options.session,
+                group: options.group,
+                env: env::vars().collect(),
+            };
+            return client::attach(stream, message, wide);
+        }
+        if let Some(group) = &options.group {
+            bail!("no session: {}", group);
+        }
+        Vec::new()
+    };
+    // Left behind by a server which did not exit cleanly.
+    fs::remove_file(&socket).ok();
+    let listener = UnixListener::bind(&socket)
+        .with_context(|| format!("failed to listen on {}", socket.display()))?;
+    let size = DefaultBackend::new().size()?;
+    // The windows are made at a usable size until the terminal has one.
+    let size = match event::too_small(size) {
+        true => event::DEFAULT_SIZE,
+        false => size,
+    };
+    // Nothing has started a thread yet, so it is safe to fork.
+    match unistd::fork()? {
+        ForkResult::Parent { .. } => {
+            drop(listener);
+            let name = match restore.first() {
+                Some(session) => session.name.clone(),
+                None => options.session.unwrap_or_else(|| "0".to_string()),
+            };
+            let request = ClientMessage::Attach {
+                name: Some(name),
+                detach: false,
+                env: env::vars().collect(),
+            };
+            client::attach(connect()?, request, wide)
+        }
+        ForkResult::Child => {
+            let result = serve(options, config, listener, size, restore);
+            fs::remove_file(&socket).ok();
+            if let Err(e) = &result {
+                error!("server failed: {:#}", e);
+            }
+            result
+        }
+    }
+}
+
+/// Whether the terminal `sm` runs in draws East Asian Ambiguous characters two
+/// columns wide, as `config` says.
+fn ambiguous_wide(config: &Config) -> bool {
+    config.ambiguous_width.is_wide(|name| env::var(name).ok())
+}
+
+/// Run the server in the background, starting with a session named as given in
+/// `options`, or the sessions in `restore`, for clients to connect to on
+/// `listener`.
+fn serve(
+    options: Options,
+    config: Config,
+    listener: UnixListener,
+    size: Winsize,
+    restore: Vec<SessionSnapshot>,
+) -> Result<()> {
+    // Leave the terminal to the client.
+    unistd::setsid()?;
+    let null = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open("/dev/null")?;
+    for fd in [io::stdin().as_raw_fd(), io::stdout().as_raw_fd(), 2] {
+        unistd::dup2(null.as_raw_fd(), fd)?;
+    }
+
+    let logfile = FileAppender::builder()
+        // Pattern: https://docs.rs/log4rs/*/log4rs/encode/pattern/index.html
+        .build("log")
+        .unwrap();
+    let log_config = LogConfig::builder()
+        .appender(Appender::builder().build("logfile", Box::new(logfile)))
+        .build(
+            Root::builder()
+                .appender("logfile")
+                .build(LevelFilter::Trace),
+        )
+        .unwrap();
+    let _handle = log4rs::init_config(log_config)?;
+
+    let mirror = options.tee.as_ref().map(TeeTarget::open).transpose()?;
+    let (server, incoming) = Server::new(size);
+    server.listen(listener);
+    let output = Tee::new(SlowLink::new(server.output(), options.link), mirror);
+    let mirror_pause = output.pause();
+    let mut session = Session::<Pane>::new(size);
+    if let Some(name) = &options.session {
+        session.set_name(name);
+    }
+    // Reduced motion makes blinking steady, unless asked for.
+    let (cursor_blink, text_blink, key_timeout) = if options.reduced_motion {
+        (
+            CursorBlink::Off,
+            TextBlink::Off,
+            key_echo::REDUCED_MOTION_TIMEOUT,
+        )
+    } else {
+        (
+            CursorBlink::default(),
+            TextBlink::default(),
+            key_echo::TIMEOUT,
+        )
+    };
+    session.set_cursor_blink(options.cursor_blink.unwrap_or(cursor_blink));
+    session.set_scale_line_attrs(options.scale_line_attrs);
+    session.set_pane_titles(options.pane_titles);
+    session.set_border_styles(config.theme().border_styles());
+    session.set_text_blink(options.text_blink.unwrap_or(text_blink));
+    session.set_default_term(options.term.as_deref())?;
+    session.set_default_remain_on_exit(config.remain_on_exit);
+    session.set_default_shell(config.default_shell.as_deref());
+    session.set_default_command(config.default_command.as_deref());
+    session.set_default_record_input(config.record_input);
+    session.set_base_index(config.base_index);
+    session.set_pane_base_index(config.pane_base_index);
+    if let Some(min) = config.pane_min_size {
+        session.set_min_pane_size(min);
+    }
+    apply_environment(&mut session, &config);
+    server.set_window_size(config.window_size);
+
+    let history_limit = config.history_limit.unwrap_or(History::DEFAULT_CAPACITY);
+    let mut event_loop = EventLoop::new(server, incoming, output, session, config);
+    event_loop.set_show_keys(options.show_keys.then_some(key_timeout));
+    event_loop.set_linear_output(options.screen_reader);
+    event_loop.set_mouse_options(options.mouse);
+    event_loop.set_mirror_pause(mirror_pause);
+    event_loop.set_restore(restore);
+    match History::default_path().map(|path| History::open(path, history_limit)) {
+        Some(Ok(history)) => event_loop.set_history(history),
+        Some(Err(e)) => warn!("could not read the command history: {}", e),
+        None => (),
+    }
+    if let Some(path) = state::default_path(options.profile.as_deref()) {
+        event_loop.set_state_path(path);
+    }
+    let profile = options.profile.as_deref();
+    if let Some(path) = options.config.or_else(|| Config::default_path(profile)) {
+        event_loop.set_config_path(path);
+    }
+    executor::block_on(event_loop.run())?;
+
+    Ok(())
+}
+
+/// Set up the environment of new panes, as `sm` is the client attaching.
+fn apply_environment(session: &mut Session<Pane>, config: &Config) {
+    for (name, value) in &config.environment {
+        match value {
+            Some(value) => session.environment_mut().set(name, value),
+            None => session.environment_mut().remove(name),
+        }
+    }
+    if let Some(names) = &config.update_environment {
+        session.set_update_environment(names.clone());
+    }
+    let client: Vec<_> = env::vars().collect();
+    session.update_environment(client.iter().map(|(k, v)| (k.as_str(), v.as_str())));
+}
@@ -0,0 +1,254 @@
+//! The context menu, drawn over the selected window.
+
+use std::io::{self, Write};
+
+use nix::pty::Winsize;
+
+use session_manager::{layout::Rect, width};
+
+use crate::{
+    backend::{Key, MouseButton, MouseEvent},
+    bindings::Action,
+};
+
+/// The entries of the context menu.
+const ITEMS: [(&str, Action); 9] = [
+    ("Split horizontally", Action::SplitHorizontal),
+    ("Split vertically", Action::SplitVertical),
+    ("Next pane", Action::NextPane),
+    ("Next layout", Action::NextLayout),
+    ("Kill pane", Action::KillPane),
+    ("Zoom pane", Action::ZoomPane),
+    ("Copy mode", Action::CopyMode),
+    ("Rename window", Action::RenameWindow(None)),
+    (
+        "New window",
+        Action::NewWindow {
+            name: None,
+            command: None,
+            cwd: None,
+            env: Vec::new(),
+        },
+    ),
+];
+
+/// What the menu made of an input event.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MenuInput {
+    /// The menu stays open.
+    Pending,
+    /// The menu was dismissed.
+    Close,
+    /// An entry was chosen, closing the menu.
+    Choose(Action),
+}
+
+/// A menu of actions, navigated with the arrow keys or the mouse.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Menu {
+    /// Where the menu is drawn, including its border.
+    rect: Rect,
+    selected: usize,
+}
+
+impl Menu {
+    /// Open a menu with its top left corner at `col`, `row`, moved up and left
+    /// if necessary to fit in an area of size `area`.
+    pub fn new(col: u16, row: u16, area: Winsize) -> Menu {
+        let width = ITEMS
+            .iter()
+            .map(|(label, _)| label.len())
+            .max()
+            .unwrap_or(0);
+        let cols = width as u16 + 4;
+        let rows = ITEMS.len() as u16 + 2;
+        Menu {
+            rect: Rect {
+                col: col.min(area.ws_col.saturating_sub(cols)),
+                row: row.min(area.ws_row.saturating_sub(rows)),
+                cols,
+                rows,
+            },
+            selected: 0,
+        }
+    }
+
+    /// Move the selection with the arrow keys (or `j` and `k`), choose with
+    /// Enter, and dismiss with Escape or `q`.
+    pub fn handle_key(&mut self, key: Key) -> MenuInput {
+        match key {
+            Key::Up | Key::Char('k') => self.move_selection(ITEMS.len() - 1),
+            Key::Down | Key::Char('j') => self.move_selection(1),
+            Key::Home => self.selected = 0,
+            Key::End => self.selected = ITEMS.len() - 1,
+            Key::Char('\n') | Key::Char('\r') => {
+                return MenuInput::Choose(ITEMS[self.selected].1.clone())
+            }
+            Key::Esc | Key::Char('q') => return MenuInput::Close,
+            _ => (),
+        }
+        MenuInput::Pending
+    }
+
+    /// Choose an entry by clicking it, move the selection with the wheel, and
+    /// dismiss the menu by clicking outside it.
+    pub fn handle_mouse(&mut self, event: MouseEvent) -> MenuInput {
+        match event.button {
+            MouseButton::WheelUp => self.move_selection(ITEMS.len() - 1),
+            MouseButton::WheelDown => self.move_selection(1),
+            _ => {
+                return match self.item_at(event.col, event.row) {
+                    Some(idx) => MenuInput::Choose(ITEMS[idx].1.clone()),
+                    None if self.contains(event.col, event.row) => MenuInput::Pending,
+                    None => MenuInput::Close,
+                }
+            }
+        }
+        MenuInput::Pending
+    }
+
+    /// Draw the menu to `output`.
+    pub fn draw<T: Write>(&self, output: &mut T) -> io::Result<()> {
+        for (idx, line) in self.lines().iter().enumerate() {
+            let row = self.rect.row + idx as u16 + 1;
+            write!(output, "\x1b[{};{}H{}", row, self.rect.col + 1, line)?;
+        }
+        write!(output, "\x1b[0m")
+    }
+
+    /// The rows of the menu, with the selected entry in reverse video.
+    fn lines(&self) -> Vec<String> {
+        let glyph = width::box_char;
+        let width = self.rect.cols as usize - 4;
+        let rule = glyph('\u{2500}').to_string().repeat(width + 2);
+        let (side, corners) = (glyph('\u{2502}'), ['\u{250c}', '\u{2510}'].map(glyph));
+        let mut lines = vec![format!("\x1b[0m{}{}{}", corners[0], rule, corners[1])];
+        for (idx, (label, _)) in ITEMS.iter().enumerate() {
+            let style = if idx == self.selected { "7" } else { "0" };
+            lines.push(format!(
+                "\x1b[0m{}\x1b[{}m {:width$} \x1b[0m{}",
+                side,
+                style,
+                label,
+                side,
+                width = width
+            ));
+        }
+        let corners = ['\u{2514}', '\u{2518}'].map(glyph);
+        lines.push(format!("\x1b[0m{}{}{}", corners[0], rule, corners[1]));
+        lines
+    }
+
+    fn move_selection(&mut self, by: usize) {
+        self.selected = (self.selected + by) % ITEMS.len();
+    }
+
+    fn contains(&self, col: u16, row: u16) -> bool {
+        let rect = self.rect;
+        (rect.col..rect.col + rect.cols).contains(&col)
+            && (rect.row..rect.row + rect.rows).contains(&row)
+    }
+
+    /// The entry drawn at `col`, `row`, if any.
+    fn item_at(&self, col: u16, row: u16) -> Option<usize> {
+        let rect = self.rect;
+        if !(rect.col + 1..rect.col + rect.cols - 1).contains(&col) || row <= rect.row {
+            return None;
+        }
+        Some((row - rect.row - 1) as usize).filter(|idx| *idx < ITEMS.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::backend::Modifiers;
+
+    const AREA: Winsize = Winsize {
+        ws_row: 23,
+        ws_col: 80,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+
+    fn click(button: MouseButton, col: u16, row: u16) -> MouseEvent {
+        MouseEvent {
+            kind: Default::default(),
+            button,
+            modifiers: Modifiers::default(),
+            col,
+            row,
+        }
+    }
+
+    #[test]
+    fn placement() {
+        let menu = Menu::new(2, 3, AREA);
+        assert_eq!(
+            menu.rect,
+            Rect {
+                col: 2,
+                row: 3,
+                cols: 22,
+                rows: 11,
+            }
+        );
+        let menu = Menu::new(79, 22, AREA);
+        assert_eq!((menu.rect.col, menu.rect.row), (58, 12));
+
+        let lines = Menu::new(0, 0, AREA).lines();
+        assert_eq!(lines.len(), 11);
+        assert_eq!(
+            lines[1],
+            "\x1b[0m\u{2502}\x1b[7m Split horizontally \x1b[0m\u{2502}"
+        );
+        assert!(lines[2].contains("\x1b[0m Split vertically   "));
+    }
+
+    #[test]
+    fn keys() {
+        let mut menu = Menu::new(0, 0, AREA);
+        assert_eq!(menu.handle_key(Key::Up), MenuInput::Pending);
+        assert_eq!(
+            menu.handle_key(Key::Char('\n')),
+            MenuInput::Choose(Action::NewWindow {
+                name: None,
+                command: None,
+                cwd: None,
+                env: Vec::new(),
+            })
+        );
+        menu.handle_key(Key::Down);
+        menu.handle_key(Key::Char('j'));
+        assert_eq!(
+            menu.handle_key(Key::Char('\n')),
+            MenuInput::Choose(Action::SplitVertical)
+        );
+        assert_eq!(menu.handle_key(Key::Char('x')), MenuInput::Pending);
+        assert_eq!(menu.handle_key(Key::Esc), MenuInput::Close);
+    }
+
+    #[test]
+    fn mouse() {
+        let mut menu = Menu::new(10, 5, AREA);
+        assert_eq!(
+            menu.handle_mouse(click(MouseButton::Left, 12, 6)),
+            MenuInput::Choose(Action::SplitHorizontal)
+        );
+        assert_eq!(
+            menu.handle_mouse(click(MouseButton::Right, 30, 10)),
+            MenuInput::Choose(Action::KillPane)
+        );
+        assert_eq!(
+            menu.handle_mouse(click(MouseButton::Left, 10, 6)),
+            MenuInput::Pending
+        );
+        assert_eq!(
+            menu.handle_mouse(click(MouseButton::Left, 9, 6)),
+            MenuInput::Close
+        );
+        menu.handle_mouse(click(MouseButton::WheelDown, 0, 0));
+        assert_eq!(menu.selected, 1);
+    }
+}
@@ -0,0 +1,247 @@
+//! Recognition of double and triple clicks, and of drags, from mouse events.
+
+use std::time::{Duration, Instant};
+
+use session_manager::grid::MouseReport;
+
+use crate::backend::{MouseButton, MouseEvent, MouseKind};
+
+/// How close in time and space mouse events have to be to make up a gesture.
+///
+/// What works on a local terminal can be too tight over a slow connection,
+/// where the events of a double click may arrive far apart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MouseOptions {
+    /// The longest time between the presses of a double or triple click.
+    pub double_click_time: Duration,
+    /// How many cells apart the presses of a double or triple click may be.
+    pub click_distance: u16,
+    /// How many cells the mouse has to move with a button held to start a drag.
+    pub drag_threshold: u16,
+}
+
+impl Default for MouseOptions {
+    fn default() -> MouseOptions {
+        MouseOptions {
+            double_click_time: Duration::from_millis(400),
+            click_distance: 0,
+            drag_threshold: 1,
+        }
+    }
+}
+
+/// What a mouse event amounts to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Gesture {
+    /// A press, counting the presses in a row: 2 for a double click, and 3 for
+    /// a triple click.
+    Click(u8),
+    /// The start of a drag: the mouse moved far enough with the button held.
+    Drag,
+}
+
+/// A button press, remembered to recognise the next one.
+#[derive(Debug, Clone, Copy)]
+struct Press {
+    button: MouseButton,
+    col: u16,
+    row: u16,
+    at: Instant,
+    count: u8,
+}
+
+/// Turns the presses, drags and releases reported by the terminal into clicks
+/// and drags.
+#[derive(Debug, Default)]
+pub struct MouseTracker {
+    options: MouseOptions,
+    /// The last press, if it could start a double or triple click.
+    last: Option<Press>,
+    /// The press of the button held, if any, and whether it started a drag.
+    held: Option<(Press, bool)>,
+}
+
+impl MouseTracker {
+    pub fn new(options: MouseOptions) -> MouseTracker {
+        MouseTracker {
+            options,
+            ..MouseTracker::default()
+        }
+    }
+
+    /// What `event`, at `now`, amounts to, if anything.
+    ///
+    /// A drag gives the button held and where the mouse is; it is only
+    /// reported once, when it starts.
+    pub fn track(&mut self, event: MouseEvent, now: Instant) -> Option<Gesture> {
+        let wheel =
+            matches!(event.button, MouseButton::WheelUp | MouseButton::WheelDown);
+        match event.kind {
+            MouseKind::Press if wheel => Some(Gesture::Click(1)),
+            MouseKind::Press => {
+                let count = match self.last {
+                    Some(last) if self.continues(&last, &event, now) => {
+                        last.count % 3 + 1
+                    }
+                    _ => 1,
+                };
+                let press = Press {
+                    button: event.button,
+                    col: event.col,
+                    row: event.row,
+                    at: now,
+                    count,
+                };
+                self.last = Some(press);
+                self.held = Some((press, false));
+                Some(Gesture::Click(count))
+            }
+            MouseKind::Drag => {
+                let (press, dragging) = self.held.as_mut()?;
+                let moved = distance(press.col, press.row, event.col, event.row);
+                if *dragging || moved < self.options.drag_threshold.max(1) {
+                    return None;
+                }
+                *dragging = true;
+                // A drag is not the first click of a double click.
+                self.last = None;
+                Some(Gesture::Drag)
+            }
+            MouseKind::Release => {
+                self.held = None;
+                None
+            }
+        }
+    }
+
+    /// The button held, which drags report for terminals which do not say.
+    pub fn held_button(&self) -> Option<MouseButton> {
+        self.held.map(|(press, _)| press.button)
+    }
+
+    /// Whether `event` at `now` is the next press of a click after `last`.
+    fn continues(&self, last: &Press, event: &MouseEvent, now: Instant) -> bool {
+        last.button == event.button
+            && now.saturating_duration_since(last.at) <= self.options.double_click_time
+            && distance(last.col, last.row, event.col, event.row)
+                <= self.options.click_distance
+    }
+}
+
+/// `event`, to report to an application.
+pub fn report(event: &MouseEvent) -> MouseReport {
+    let mut button = match event.button {
+        MouseButton::Left => 0,
+        MouseButton::Middle => 1,
+        MouseButton::Right => 2,
+        MouseButton::WheelUp => 64,
+        MouseButton::WheelDown => 65,
+    };
+    let modifiers = event.modifiers;
+    for (held, bit) in [
+        (modifiers.shift, 4),
+        (modifiers.alt, 8),
+        (modifiers.ctrl, 16),
+    ] {
+        if held {
+            button |= bit;
+        }
+    }
+    if event.kind == MouseKind::Drag {
+        button |= 32;
+    }
+    MouseReport {
+        button,
+        release: event.kind == MouseKind::Release,
+        col: event.col,
+        row: event.row,
+    }
+}
+
+/// How many cells apart two positions are, moving diagonally as well.
+fn distance(col: u16, row: u16, other_col: u16, other_row: u16) -> u16 {
+    col.abs_diff(other_col).max(row.abs_diff(other_row))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(kind: MouseKind, col: u16, row: u16) -> MouseEvent {
+        MouseEvent {
+            kind,
+            button: MouseButton::Left,
+            modifiers: Default::default(),
+            col,
+            row,
+        }
+    }
+
+    #[test]
+    fn clicks() {
+        let mut tracker = MouseTracker::new(MouseOptions::default());
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let press = event(MouseKind::Press, 4, 2);
+        let release = event(MouseKind::Release, 4, 2);
+        assert_eq!(tracker.track(press, at(0)), Some(Gesture::Click(1)));
+        assert_eq!(tracker.track(release, at(50)), None);
+        assert_eq!(tracker.track(press, at(300)), Some(Gesture::Click(2)));
+        assert_eq!(tracker.track(press, at(600)), Some(Gesture::Click(3)));
+        assert_eq!(tracker.track(press, at(700)), Some(Gesture::Click(1)));
+        // Too slow, or too far.
+        assert_eq!(tracker.track(press, at(1200)), Some(Gesture::Click(1)));
+        let moved = event(MouseKind::Press, 5, 2);
+        assert_eq!(tracker.track(moved, at(1300)), Some(Gesture::Click(1)));
+
+        // Looser thresholds, as over a slow link.
+        let mut tracker = MouseTracker::new(MouseOptions {
+            double_click_time: Duration::from_millis(1000),
+            click_distance: 1,
+            drag_threshold: 1,
+        });
+        assert_eq!(tracker.track(press, at(0)), Some(Gesture::Click(1)));
+        assert_eq!(tracker.track(moved, at(900)), Some(Gesture::Click(2)));
+    }
+
+    #[test]
+    fn drags() {
+        let mut tracker = MouseTracker::new(MouseOptions {
+            drag_threshold: 3,
+            ..MouseOptions::default()
+        });
+        let now = Instant::now();
+        assert_eq!(tracker.track(event(MouseKind::Drag, 9, 9), now), None);
+        tracker.track(event(MouseKind::Press, 4, 2), now);
+        assert_eq!(tracker.held_button(), Some(MouseButton::Left));
+        assert_eq!(tracker.track(event(MouseKind::Drag, 6, 3), now), None);
+        let drag = event(MouseKind::Drag, 7, 2);
+        assert_eq!(tracker.track(drag, now), Some(Gesture::Drag));
+        assert_eq!(tracker.track(drag, now), None);
+        assert_eq!(tracker.track(event(MouseKind::Release, 7, 2), now), None);
+        assert_eq!(tracker.held_button(), None);
+        // The press before the drag does not make the next a double click.
+        let press = event(MouseKind::Press, 4, 2);
+        assert_eq!(tracker.track(press, now), Some(Gesture::Click(1)));
+    }
+
+    #[test]
+    fn reports() {
+        let mut drag = event(MouseKind::Drag, 4, 2);
+        drag.modifiers.ctrl = true;
+        assert_eq!(
+            report(&drag),
+            MouseReport {
+                button: 48,
+                release: false,
+                col: 4,
+                row: 2,
+            }
+        );
+        let mut wheel = event(MouseKind::Press, 0, 0);
+        wheel.button = MouseButton::WheelDown;
+        wheel.modifiers.shift = true;
+        assert_eq!(report(&wheel).button, 69);
+        assert!(report(&event(MouseKind::Release, 0, 0)).release);
+    }
+}
@@ -0,0 +1,324 @@
+//! The options, as `show-options` lists them: the value each has, the values
+//! it takes, and whether it has its default or which line of the configuration
+//! file set it, to find why one set does not take effect.
+
+use std::{fmt::Write as _, str::FromStr};
+
+use anyhow::{bail, Error, Result};
+
+use session_manager::{environment::Environment, history::History, util};
+
+use crate::{clipboard, config::Config, status, style::STYLE_OPTIONS, title};
+
+/// Which options are listed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OptionScope {
+    /// `-g`: those of the session and the clients attached to it.
+    Global,
+    /// `-w`: those of windows and their panes, taken by each as it opens.
+    Window,
+}
+
+impl OptionScope {
+    fn name(self) -> &'static str {
+        match self {
+            OptionScope::Global => "global",
+            OptionScope::Window => "window",
+        }
+    }
+
+    /// The flag of `show-options` listing the options of the scope.
+    pub fn flag(self) -> &'static str {
+        match self {
+            OptionScope::Global => "-g",
+            OptionScope::Window => "-w",
+        }
+    }
+}
+
+impl FromStr for OptionScope {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<OptionScope> {
+        match s {
+            "-g" => Ok(OptionScope::Global),
+            "-w" => Ok(OptionScope::Window),
+            _ => bail!("expected -g or -w, not {:?}", s),
+        }
+    }
+}
+
+/// An option, with the value in effect.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OptionValue {
+    pub name: &'static str,
+    pub scope: OptionScope,
+    /// The values it takes, e.g. `on|off`.
+    pub kind: &'static str,
+    /// The value in effect, as it would be set.
+    pub value: String,
+    /// The line of the configuration file it was set on, or `None` if it has
+    /// its default.
+    pub line: Option<usize>,
+}
+
+/// Every option, with the value it has in `config`. The clipboard commands
+/// not set are those found in `env`.
+pub fn options(config: &Config, env: &Environment) -> Vec<OptionValue> {
+    use OptionScope::{Global, Window};
+
+    let switch = |on: bool| if on { "on" } else { "off" }.to_string();
+    let copy = clipboard::copy_command(config.copy_command.as_deref(), env);
+    let paste = clipboard::paste_command(config.paste_command.as_deref(), env);
+    let lock_after = config.lock_after.map_or(0, |after| after.as_secs() / 60);
+    let mut theme = config.theme();
+    let mut options = vec![
+        (
+            "status-right",
+            Global,
+            "format",
+            config.status_right.clone().unwrap_or_default(),
+        ),
+        (
+            "remain-on-exit",
+            Window,
+            "on|off",
+            switch(config.remain_on_exit),
+        ),
+        (
+            "record-input",
+            Window,
+            "on|off",
+            switch(config.record_input),
+        ),
+        (
+            "renumber-windows",
+            Global,
+            "on|off",
+            switch(config.renumber_windows),
+        ),
+        (
+            "base-index",
+            Global,
+            "number",
+            config.base_index.to_string(),
+        ),
+        (
+            "pane-base-index",
+            Window,
+            "number",
+            config.pane_base_index.to_string(),
+        ),
+        ("set-titles", Global, "on|off", switch(config.set_titles)),
+        (
+            "set-titles-string",
+            Global,
+            "format",
+            config
+                .set_titles_string
+                .clone()
+                .unwrap_or_else(|| title::DEFAULT_FORMAT.to_string()),
+        ),
+        (
+            "pane-min-size",
+            Window,
+            "COLSxROWS",
+            config.pane_min_size.unwrap_or_default().to_string(),
+        ),
+        ("visual-bell", Global, "on|off", switch(config.visual_bell)),
+        (
+            "display-time",
+            Global,
+            "milliseconds",
+            config
+                .display_time
+                .unwrap_or(status::DEFAULT_DISPLAY_TIME)
+                .as_millis()
+                .to_string(),
+        ),
+        (
+            "history-limit",
+            Global,
+            "number",
+            config
+                .history_limit
+                .unwrap_or(History::DEFAULT_CAPACITY)
+                .to_string(),
+        ),
+        (
+            "last-window-close",
+            Global,
+            "exit|detach|summary",
+            config.last_window_close.to_string(),
+        ),
+        (
+            "window-size",
+            Global,
+            "smallest|largest|latest",
+            config.window_size.to_string(),
+        ),
+        (
+            "copy-command",
+            Global,
+            "command|none",
+            copy.unwrap_or_else(|| "none".to_string()),
+        ),
+        (
+            "paste-command",
+            Global,
+            "command|none",
+            paste.unwrap_or_else(|| "none".to_string()),
+        ),
+        ("lock-after-time", Global, "minutes", lock_after.to_string()),
+        (
+            "lock-command",
+            Global,
+            "command",
+            config.lock_command.clone().unwrap_or_default(),
+        ),
+        (
+            "default-shell",
+            Global,
+            "path",
+            config.default_shell.clone().unwrap_or_else(util::get_shell),
+        ),
+        (
+            "default-command",
+            Global,
+            "command",
+            config.default_command.clone().unwrap_or_default(),
+        ),
+        ("mouse", Global, "on|off", switch(config.mouse)),
+        (
+            "ambiguous-width",
+            Global,
+            "narrow|wide|auto",
+            config.ambiguous_width.to_string(),
+        ),
+        (
+            "color-scheme",
+            Global,
+            "default|ocean|mono",
+            config.color_scheme.to_string(),
+        ),
+    ];
+    for name in STYLE_OPTIONS {
+        let style = theme.style_mut(name).map(|style| style.to_string());
+        options.push((name, Global, "style", style.unwrap_or_default()));
+    }
+    options
+        .into_iter()
+        .map(|(name, scope, kind, value)| OptionValue {
+            name,
+            scope,
+            kind,
+            value,
+            line: config.option_lines.get(name).copied(),
+        })
+        .collect()
+}
+
+/// The options of `scope`, or all of them, as `show-options` writes them: a
+/// line each with its name, scope, the values it takes, where it was set and
+/// its value.
+pub fn show(config: &Config, env: &Environment, scope: Option<OptionScope>) -> String {
+    let options: Vec<_> = options(config, env)
+        .into_iter()
+        .filter(|option| scope.is_none_or(|scope| option.scope == scope))
+        .collect();
+    let mut text = match &config.path {
+        Some(path) => format!("configuration file: {}\n\n", path.display()),
+        None => "no configuration file\n\n".to_string(),
+    };
+    let origin = |option: &OptionValue| match option.line {
+        Some(line) => format!("line {}", line),
+        None => "default".to_string(),
+    };
+    let name_width = options.iter().map(|option| option.name.len()).max();
+    let kind_width = options.iter().map(|option| option.kind.len()).max();
+    let (name_width, kind_width) = (name_width.unwrap_or(0), kind_width.unwrap_or(0));
+    for option in &options {
+        let value = match option.value.as_str() {
+            "" => "\"\"",
+            value => value,
+        };
+        let _ = writeln!(
+            text,
+            "{:<name_width$}  {:<6}  {:<kind_width$}  {:<8}  {}",
+            option.name,
+            option.scope.name(),
+            option.kind,
+            origin(option),
+            value,
+        );
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    #[test]
+    fn option_values() {
+        let config = Config::default();
+        let env = Environment::default();
+        let options = options(&config, &env);
+        // Every option set in the configuration file is listed.
+        for name in [
+            "remain-on-exit",
+            "pane-min-size",
+            "window-size",
+            "ambiguous-width",
+            "pane-active-border-style",
+        ] {
+            assert!(options.iter().any(|option| option.name == name), "{}", name);
+        }
+        assert!(options.iter().all(|option| option.line.is_none()));
+        let min_size = options.iter().find(|option| option.name == "pane-min-size");
+        assert_eq!(min_size.unwrap().value, "5x2");
+        let style = options.iter().find(|option| option.name == "status-style");
+        assert_eq!(style.unwrap().value, "reverse");
+    }
+
+    #[test]
+    fn show_options() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("sm.conf");
+        std::fs::write(
+            &path,
+            "# Windows\n\
+             set-option base-index 1\n\
+             set-option remain-on-exit on\n\
+             set-option remain-on-exit off\n",
+        )
+        .unwrap();
+        let config = Config::read(&path).unwrap();
+        let env = Environment::default();
+        let text = show(&config, &env, None);
+        assert!(
+            text.starts_with(&format!("configuration file: {}\n\n", path.display()))
+        );
+        // The line setting an option last is given.
+        assert!(text.contains("\nremain-on-exit             window  on|off  "));
+        assert!(text.contains("  line 4    off\n"));
+        assert!(text.contains("  line 2    1\n"));
+        assert!(text.contains("  default   \"\"\n"));
+
+        let text = show(&config, &env, Some(OptionScope::Window));
+        assert!(text.contains("\nremain-on-exit "));
+        assert!(!text.contains("\nbase-index "));
+        let text = show(&Config::default(), &env, Some(OptionScope::Global));
+        assert!(text.starts_with("no configuration file\n\n"));
+        assert!(text.contains("\nbase-index "));
+        assert!(!text.contains("\nremain-on-exit "));
+        assert_eq!(
+            Config::load(Path::new("/nonexistent/sm.conf"))
+                .unwrap()
+                .path,
+            None
+        );
+    }
+}
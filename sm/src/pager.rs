@@ -0,0 +1,426 @@
+//! A scrollable overlay showing text too long for one line, such as the output
+//! of `run-shell`, which can be searched.
+
+use std::io::{self, Write};
+
+use nix::pty::Winsize;
+
+use session_manager::{search::Search, width};
+
+use crate::{
+    backend::{Key, MouseButton, MouseEvent},
+    style::Theme,
+};
+
+/// How many lines a turn of the wheel scrolls.
+const WHEEL_LINES: isize = 3;
+
+/// Columns between tab stops when showing text.
+const TAB_WIDTH: usize = 8;
+
+/// What the pager made of an input event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PagerInput {
+    /// The pager stays open.
+    Pending,
+    /// The pager was dismissed.
+    Close,
+    /// The text should be copied to the paste buffer, closing the pager.
+    Copy,
+}
+
+/// Text shown over the windows a screenful at a time, with a line at the
+/// bottom saying what it is.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pager {
+    title: String,
+    text: String,
+    /// The text as shown: without control characters, and tabs expanded.
+    lines: Vec<String>,
+    /// The first line on screen.
+    top: usize,
+    /// The last search, whose matches are highlighted.
+    search: Option<Search>,
+    /// The search being typed, with the first line on screen when it started.
+    typing: Option<(Search, usize)>,
+    /// The line of the match found last.
+    found: Option<usize>,
+    /// Shown in the footer until the next key, e.g. that nothing matched.
+    message: Option<String>,
+    /// Whether the pager has to be drawn again.
+    dirty: bool,
+}
+
+impl Pager {
+    /// Show `text`, describing it with `title`.
+    pub fn new(title: &str, text: &str) -> Pager {
+        Pager {
+            title: title.to_string(),
+            text: text.to_string(),
+            lines: text.lines().map(printable).collect(),
+            top: 0,
+            search: None,
+            typing: None,
+            found: None,
+            message: None,
+            dirty: true,
+        }
+    }
+
+    /// The text shown, as given.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Scroll with the arrow keys, `j` and `k`, Page Up and Page Down, Space,
+    /// Home and End; copy the text with `y`, and dismiss with `q` or Escape.
+    ///
+    /// `/` searches down the text for what is typed after it, and `?` up it,
+    /// moving to the first match as it is typed; `n` finds the next match the
+    /// same way, and `N` the next the other way.
+    ///
+    /// `area` is the size of the part of the terminal the pager covers.
+    pub fn handle_key(&mut self, key: Key, area: Winsize) -> PagerInput {
+        if self.message.take().is_some() {
+            self.dirty = true;
+        }
+        if self.typing.is_some() {
+            self.type_search(key, area);
+            return PagerInput::Pending;
+        }
+        let page = page_height(area) as isize;
+        match key {
+            Key::Char(c @ ('/' | '?')) => {
+                let search = Search {
+                    pattern: String::new(),
+                    forward: c == '/',
+                };
+                self.typing = Some((search, self.top));
+                self.dirty = true;
+            }
+            Key::Char('n') => self.find_next(true, area),
+            Key::Char('N') => self.find_next(false, area),
+            Key::Up | Key::Char('k') => self.scroll(-1, area),
+            Key::Down | Key::Char('j') | Key::Char('\n') => self.scroll(1, area),
+            Key::PageUp | Key::Char('b') => self.scroll(-page, area),
+            Key::PageDown | Key::Char(' ') => self.scroll(page, area),
+            Key::Home | Key::Char('g') => self.scroll(-(self.top as isize), area),
+            Key::End | Key::Char('G') => self.scroll(self.lines.len() as isize, area),
+            Key::Char('y') => return PagerInput::Copy,
+            Key::Char('q') | Key::Esc => return PagerInput::Close,
+            _ => (),
+        }
+        PagerInput::Pending
+    }
+
+    /// Scroll with the wheel.
+    pub fn handle_mouse(&mut self, event: MouseEvent, area: Winsize) -> PagerInput {
+        match event.button {
+            MouseButton::WheelUp => self.scroll(-WHEEL_LINES, area),
+            MouseButton::WheelDown => self.scroll(WHEEL_LINES, area),
+            _ => (),
+        }
+        PagerInput::Pending
+    }
+
+    /// Draw the pager over `area` if it has changed since it was last drawn.
+    pub fn draw<T: Write>(
+        &mut self,
+        output: &mut T,
+        area: Winsize,
+        theme: &Theme,
+    ) -> io::Result<()> {
+        if !self.dirty {
+            return Ok(());
+        }
+        let cols = area.ws_col as usize;
+        let page = page_height(area);
+        // Matches of the search being typed are shown as it is.
+        let search = self.typing.as_ref().map(|(search, _)| search);
+        let search = search.or(self.search.as_ref());
+        write!(output, "\x1b[0m")?;
+        for row in 0..page {
+            let line = self.lines.get(self.top + row).map_or("", String::as_str);
+            let line = width::fit(line, cols);
+            write!(output, "\x1b[{};1H", row + 1)?;
+            let mut shown = 0;
+            for found in search
+                .map(|search| search.matches(&line))
+                .unwrap_or_default()
+            {
+                write!(
+                    output,
+                    "{}{}{}\x1b[0m",
+                    &line[shown..found.start],
+                    theme.selection.sgr(),
+                    &line[found.clone()]
+                )?;
+                shown = found.end;
+            }
+            write!(output, "{}", &line[shown..])?;
+        }
+        let bottom = (self.top + page).min(self.lines.len());
+        let position = format!("[{}-{}/{}]", self.top + 1, bottom, self.lines.len());
+        let footer = match (&self.typing, &self.message) {
+            (Some((search, _)), _) => {
+                let prompt = if search.forward { '/' } else { '?' };
+                format!("{}{}", prompt, search.pattern)
+            }
+            (None, Some(message)) => format!(" {}", message),
+            (None, None) => {
+                format!(" {} {}  q: close  y: copy  /: search", self.title, position)
+            }
+        };
+        write!(
+            output,
+            "\x1b[{};1H{}{}\x1b[0m",
+            area.ws_row,
+            theme.message.sgr(),
+            width::fit(&footer, cols)
+        )?;
+        self.dirty = false;
+        Ok(())
+    }
+
+    /// Draw the pager again on the next `draw`, e.g. after the terminal was
+    /// cleared.
+    pub fn mark_dirty(&mut self) {
+        self.dirty = true;
+    }
+
+    /// Edit the search being typed with `key`: Enter finishes it, and Escape,
+    /// or Backspace once it is empty, abandons it.
+    fn type_search(&mut self, key: Key, area: Winsize) {
+        let (mut search, start) = match self.typing.take() {
+            Some(typing) => typing,
+            None => return,
+        };
+        self.dirty = true;
+        match key {
+            Key::Char('\n') | Key::Char('\r') => {
+                if search.pattern.is_empty() {
+                    // Like `n`, with the search made last.
+                    if let Some(last) = &mut self.search {
+                        last.forward = search.forward;
+                    }
+                } else {
+                    self.search = Some(search);
+                }
+                match self.search.clone() {
+                    Some(search) => match self.find(&search, start, search.forward) {
+                        Some(line) => self.show_match(line, area),
+                        None => self.not_found(&search),
+                    },
+                    None => self.top = start,
+                }
+                return;
+            }
+            Key::Esc => {
+                self.top = start;
+                return;
+            }
+            Key::Backspace if search.pattern.is_empty() => {
+                self.top = start;
+                return;
+            }
+            Key::Backspace => {
+                search.pattern.pop();
+            }
+            Key::Char(c) if !c.is_control() => search.pattern.push(c),
+            _ => (),
+        }
+        // Move to the first match of what has been typed so far, if there is
+        // one, from where the search started.
+        let found = self.find(&search, start, search.forward);
+        match found.filter(|_| !search.pattern.is_empty()) {
+            Some(line) => self.show_line(line, area),
+            None => self.top = start,
+        }
+        self.typing = Some((search, start));
+    }
+
+    /// Move to the next match of the last search, going down if `same` and the
+    /// search went down, or up if not.
+    fn find_next(&mut self, same: bool, area: Winsize) {
+        let mut search = match self.search.clone() {
+            Some(search) => search,
+            None => return,
+        };
+        search.forward = search.forward == same;
+        let from = match (self.found, search.forward) {
+            (Some(line), true) => line + 1,
+            (Some(line), false) => line + self.lines.len() - 1,
+            (None, _) => self.top,
+        };
+        match self.find(&search, from % self.lines.len().max(1), search.forward) {
+            Some(line) => self.show_match(line, area),
+            None => self.not_found(&search),
+        }
+    }
+
+    /// The first line from `from`, going down if `forward` or up if not, and
+    /// going around the text at its ends, with a match of `search`.
+    fn find(&self, search: &Search, from: usize, forward: bool) -> Option<usize> {
+        let search = Search {
+            forward,
+            ..search.clone()
+        };
+        let line = |idx: usize| self.lines.get(idx).cloned();
+        search.find(self.lines.len(), from, false, line)
+    }
+
+    /// Move to the match found on `line`.
+    fn show_match(&mut self, line: usize, area: Winsize) {
+        self.found = Some(line);
+        self.show_line(line, area);
+    }
+
+    fn not_found(&mut self, search: &Search) {
+        self.message = Some(format!("not found: {}", search.pattern));
+        self.dirty = true;
+    }
+
+    /// Scroll `line` to the top, or as near it as the text goes.
+    fn show_line(&mut self, line: usize, area: Winsize) {
+        self.scroll(line as isize - self.top as isize, area);
+        self.dirty = true;
+    }
+
+    /// Move the text `by` lines, keeping the last line at the bottom or above.
+    fn scroll(&mut self, by: isize, area: Winsize) {
+        let last = self.lines.len().saturating_sub(page_height(area));
+        let top = (self.top as isize + by).clamp(0, last as isize) as usize;
+        if top != self.top {
+            self.top = top;
+            self.dirty = true;
+        }
+    }
+}
+
+/// How many lines of text fit in `area` above the footer.
+fn page_height(area: Winsize) -> usize {
+    area.ws_row.saturating_sub(1).max(1) as usize
+}
+
+/// `line` with its tabs expanded and any other control characters removed.
+fn printable(line: &str) -> String {
+    let mut printable = String::new();
+    for c in line.chars() {
+        if c == '\t' {
+            let spaces = TAB_WIDTH - width::str_width(&printable) % TAB_WIDTH;
+            printable.push_str(&" ".repeat(spaces));
+        } else if !c.is_control() {
+            printable.push(c);
+        }
+    }
+    printable
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AREA: Winsize = Winsize {
+        ws_row: 4,
+        ws_col: 20,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+
+    #[test]
+    fn scroll() {
+        let text = "one\ntwo\nthree\nfour\nfive\n";
+        let mut pager = Pager::new("run-shell", text);
+        assert_eq!(pager.handle_key(Key::Up, AREA), PagerInput::Pending);
+        assert_eq!(pager.top, 0);
+        pager.handle_key(Key::PageDown, AREA);
+        assert_eq!(pager.top, 2);
+        pager.handle_key(Key::Char('k'), AREA);
+        assert_eq!(pager.top, 1);
+        pager.handle_key(Key::End, AREA);
+        assert_eq!(pager.top, 2);
+        pager.handle_key(Key::Home, AREA);
+        assert_eq!(pager.top, 0);
+        assert_eq!(pager.handle_key(Key::Char('y'), AREA), PagerInput::Copy);
+        assert_eq!(pager.text(), text);
+        assert_eq!(pager.handle_key(Key::Esc, AREA), PagerInput::Close);
+    }
+
+    #[test]
+    fn search() {
+        let text = "error: one\ntwo\nthree\nERROR: four\nfive\nsix\n";
+        let mut pager = Pager::new("run-shell", text);
+        let type_keys = |pager: &mut Pager, keys: &str| {
+            for c in keys.chars() {
+                pager.handle_key(Key::Char(c), AREA);
+            }
+        };
+        // The text moves to the first match as it is typed.
+        type_keys(&mut pager, "/f");
+        assert_eq!(pager.top, 3);
+        type_keys(&mut pager, "i");
+        assert_eq!(pager.top, 3);
+        pager.handle_key(Key::Esc, AREA);
+        assert_eq!(pager.top, 0);
+        assert_eq!(pager.search, None);
+
+        type_keys(&mut pager, "/error\n");
+        assert_eq!(pager.found, Some(0));
+        pager.handle_key(Key::Char('n'), AREA);
+        assert_eq!((pager.found, pager.top), (Some(3), 3));
+        // The search goes around the ends of the text.
+        pager.handle_key(Key::Char('n'), AREA);
+        assert_eq!(pager.found, Some(0));
+        pager.handle_key(Key::Char('N'), AREA);
+        assert_eq!(pager.found, Some(3));
+        // `?` searches up, and `n` goes on up.
+        type_keys(&mut pager, "?t");
+        pager.handle_key(Key::Char('\n'), AREA);
+        assert_eq!(pager.found, Some(2));
+        pager.handle_key(Key::Char('n'), AREA);
+        assert_eq!(pager.found, Some(1));
+
+        type_keys(&mut pager, "/nothing\n");
+        assert_eq!(pager.message.as_deref(), Some("not found: nothing"));
+        assert_eq!(pager.found, Some(1));
+        // The message goes at the next key.
+        pager.handle_key(Key::Char('k'), AREA);
+        assert_eq!(pager.message, None);
+        assert_eq!(pager.handle_key(Key::Char('q'), AREA), PagerInput::Close);
+    }
+
+    #[test]
+    fn draw_search() {
+        let mut pager = Pager::new("ls", "a file\nanother file\n");
+        for c in "/file".chars() {
+            pager.handle_key(Key::Char(c), AREA);
+        }
+        let mut out = Vec::new();
+        pager.draw(&mut out, AREA, &Theme::default()).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("\x1b[1;1Ha \x1b[0;7mfile\x1b[0m              "));
+        assert!(out.contains("\x1b[4;1H\x1b[0;7m/file               \x1b[0m"));
+    }
+
+    #[test]
+    fn draw() {
+        let mut pager = Pager::new("ls", "a\tb\x07\n\u{65e5}\u{672c}\n");
+        let mut out = Vec::new();
+        pager.draw(&mut out, AREA, &Theme::default()).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(
+            out,
+            "\x1b[0m\x1b[1;1Ha       b           \
+             \x1b[2;1H\u{65e5}\u{672c}                \
+             \x1b[3;1H                    \
+             \x1b[4;1H\x1b[0;7m ls [1-2/2]  q: clos\x1b[0m"
+        );
+
+        let mut out = Vec::new();
+        pager.draw(&mut out, AREA, &Theme::default()).unwrap();
+        assert!(out.is_empty());
+        pager.mark_dirty();
+        pager.draw(&mut out, AREA, &Theme::default()).unwrap();
+        assert!(!out.is_empty());
+    }
+}
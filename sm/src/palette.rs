@@ -0,0 +1,372 @@
+//! The command palette: every action with the keys bound to it, filtered by
+//! what is typed, to find one and perform it.
+
+use std::io::{self, Write};
+
+use nix::pty::Winsize;
+
+use session_manager::width;
+
+use crate::{
+    backend::{Key, MouseButton, MouseEvent, MouseKind},
+    bindings::{self, Action, ActionHelp, KeyBindings, KeyTable, ACTIONS},
+};
+
+/// What the palette made of an input event.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PaletteInput {
+    /// The palette stays open.
+    Pending,
+    /// The palette was dismissed.
+    Close,
+    /// The action was chosen, closing the palette.
+    Perform(Action),
+    /// An action needing arguments was chosen: the command prompt should be
+    /// opened with this text, its name, for them to be typed.
+    Prompt(String),
+}
+
+/// An action, with the keys bound to it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct PaletteEntry {
+    help: ActionHelp,
+    /// The keys, as `list-keys` writes them, e.g. `c` or `-n F12`.
+    keys: Vec<String>,
+}
+
+impl PaletteEntry {
+    /// The line shown for the action, e.g. `rename-window [NAME]  ,  Rename the
+    /// selected window`.
+    fn line(&self) -> String {
+        let usage = match self.help.args {
+            "" => self.help.name.to_string(),
+            args => format!("{} {}", self.help.name, args),
+        };
+        format!(
+            "{:<36} {:<10} {}",
+            usage,
+            self.keys.join(" "),
+            self.help.description
+        )
+    }
+}
+
+/// The actions matching the filter typed, one a line under it, with the
+/// highlighted one in reverse video.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Palette {
+    entries: Vec<PaletteEntry>,
+    filter: String,
+    /// The positions in `entries` of the actions matching the filter, best
+    /// first.
+    matches: Vec<usize>,
+    /// The match highlighted.
+    highlighted: usize,
+    /// The first match on screen.
+    top: usize,
+    /// Whether the palette has to be drawn again.
+    dirty: bool,
+}
+
+impl Palette {
+    /// List every action, with the keys `bindings` binds to it.
+    pub fn new(bindings: &KeyBindings) -> Palette {
+        let entries = ACTIONS
+            .iter()
+            .map(|help| PaletteEntry {
+                help: *help,
+                keys: bindings
+                    .iter()
+                    .filter(|binding| name(&binding.action) == help.name)
+                    .map(|binding| match binding.table {
+                        KeyTable::Prefix => bindings::key_name(binding.key),
+                        KeyTable::Root => {
+                            format!("-n {}", bindings::key_name(binding.key))
+                        }
+                    })
+                    .collect(),
+            })
+            .collect();
+        let mut palette = Palette {
+            entries,
+            filter: String::new(),
+            matches: Vec::new(),
+            highlighted: 0,
+            top: 0,
+            dirty: true,
+        };
+        palette.update_matches();
+        palette
+    }
+
+    /// Type to filter the actions, and move with the arrow keys, Page Up and
+    /// Page Down. Enter performs the action highlighted, and Escape dismisses
+    /// the palette.
+    pub fn handle_key(&mut self, key: Key) -> PaletteInput {
+        match key {
+            Key::Up | Key::Ctrl('p') => self.highlight(self.highlighted.checked_sub(1)),
+            Key::Down | Key::Ctrl('n') => self.highlight(Some(self.highlighted + 1)),
+            Key::PageUp => self.highlight(Some(self.highlighted.saturating_sub(10))),
+            Key::PageDown => {
+                let last = self.matches.len().saturating_sub(1);
+                self.highlight(Some((self.highlighted + 10).min(last)))
+            }
+            Key::Char('\n') | Key::Char('\r') => return self.choose(self.highlighted),
+            Key::Char(c) if !c.is_control() => {
+                self.filter.push(c);
+                self.update_matches();
+            }
+            Key::Backspace => {
+                self.filter.pop();
+                self.update_matches();
+            }
+            Key::Ctrl('u') => {
+                self.filter.clear();
+                self.update_matches();
+            }
+            Key::Esc | Key::Ctrl('c') => return PaletteInput::Close,
+            _ => (),
+        }
+        PaletteInput::Pending
+    }
+
+    /// Move with the wheel, and perform an action by clicking it.
+    pub fn handle_mouse(&mut self, event: MouseEvent) -> PaletteInput {
+        match (event.button, event.kind) {
+            (MouseButton::WheelUp, _) => {
+                self.highlight(self.highlighted.checked_sub(1))
+            }
+            (MouseButton::WheelDown, _) => self.highlight(Some(self.highlighted + 1)),
+            // The first row is the filter.
+            (MouseButton::Left, MouseKind::Press) if event.row > 0 => {
+                return self.choose(self.top + event.row as usize - 1);
+            }
+            _ => (),
+        }
+        PaletteInput::Pending
+    }
+
+    /// Draw the palette over `area` if it has changed since it was last drawn,
+    /// scrolling to keep the highlighted action in view.
+    pub fn draw<T: Write>(&mut self, output: &mut T, area: Winsize) -> io::Result<()> {
+        let rows = (area.ws_row as usize).saturating_sub(1).max(1);
+        let top = if self.highlighted < self.top {
+            self.highlighted
+        } else {
+            self.top.max((self.highlighted + 1).saturating_sub(rows))
+        };
+        if !self.dirty && top == self.top {
+            return Ok(());
+        }
+        self.top = top;
+        let cols = area.ws_col as usize;
+        let filter = format!("> {}_", self.filter);
+        write!(output, "\x1b[1;1H\x1b[0;1m{}", width::fit(&filter, cols))?;
+        for row in 0..rows {
+            let position = self.top + row;
+            let entry = self.matches.get(position).map(|idx| &self.entries[*idx]);
+            let (style, line) = match entry {
+                Some(entry) if position == self.highlighted => ("7", entry.line()),
+                Some(entry) => ("0", entry.line()),
+                None => ("0", String::new()),
+            };
+            write!(
+                output,
+                "\x1b[{};1H\x1b[0;{}m{}",
+                row + 2,
+                style,
+                width::fit(&line, cols)
+            )?;
+        }
+        write!(output, "\x1b[0m")?;
+        self.dirty = false;
+        Ok(())
+    }
+
+    /// Draw the palette again on the next `draw`.
+    pub fn mark_dirty(&mut self) {
+        self.dirty = true;
+    }
+
+    /// Perform the action at `position` in the matches, or ask for its
+    /// arguments.
+    fn choose(&self, position: usize) -> PaletteInput {
+        let help = match self.matches.get(position) {
+            Some(idx) => self.entries[*idx].help,
+            None => return PaletteInput::Pending,
+        };
+        if help.needs_args() {
+            return PaletteInput::Prompt(format!("{} ", help.name));
+        }
+        match help.name.parse() {
+            Ok(action) => PaletteInput::Perform(action),
+            Err(_) => PaletteInput::Prompt(format!("{} ", help.name)),
+        }
+    }
+
+    /// Find the actions matching the filter again, highlighting the best.
+    fn update_matches(&mut self) {
+        let mut scored: Vec<_> = self
+            .entries
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, entry)| {
+                let help = &entry.help;
+                // Matches in the name come before those in the description.
+                let score = fuzzy_score(&self.filter, help.name).or_else(|| {
+                    fuzzy_score(&self.filter, help.description)
+                        .map(|score| score + 1000)
+                })?;
+                Some((score, idx))
+            })
+            .collect();
+        // Equal matches keep the order of `ACTIONS`.
+        scored.sort_by_key(|(score, _)| *score);
+        self.matches = scored.into_iter().map(|(_, idx)| idx).collect();
+        self.highlighted = 0;
+        self.top = 0;
+        self.dirty = true;
+    }
+
+    fn highlight(&mut self, position: Option<usize>) {
+        match position {
+            Some(position) if position < self.matches.len() => {
+                self.highlighted = position;
+                self.dirty = true;
+            }
+            _ => (),
+        }
+    }
+}
+
+/// The name of `action`, as it is written.
+fn name(action: &Action) -> String {
+    let text = action.to_string();
+    text.split(' ').next().unwrap_or_default().to_string()
+}
+
+/// How well `text` matches `filter`, lower being better, if it has all the
+/// characters of `filter` in order, ignoring case: the characters skipped
+/// before and between them.
+fn fuzzy_score(filter: &str, text: &str) -> Option<usize> {
+    let mut score = 0;
+    let mut chars = text.chars().map(|c| c.to_ascii_lowercase());
+    for wanted in filter.chars().map(|c| c.to_ascii_lowercase()) {
+        if wanted == ' ' {
+            continue;
+        }
+        loop {
+            if chars.next()? == wanted {
+                break;
+            }
+            score += 1;
+        }
+    }
+    Some(score)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The names of the actions matching, best first.
+    fn names(palette: &Palette) -> Vec<&str> {
+        let matches = palette.matches.iter();
+        matches.map(|idx| palette.entries[*idx].help.name).collect()
+    }
+
+    fn type_text(palette: &mut Palette, text: &str) {
+        for c in text.chars() {
+            palette.handle_key(Key::Char(c));
+        }
+    }
+
+    #[test]
+    fn fuzzy() {
+        assert_eq!(fuzzy_score("", "next-pane"), Some(0));
+        assert_eq!(fuzzy_score("np", "next-pane"), Some(4));
+        assert_eq!(fuzzy_score("NEXT", "next-pane"), Some(0));
+        assert_eq!(fuzzy_score("pn", "next-pane"), Some(6));
+        assert_eq!(fuzzy_score("en", "pane"), None);
+    }
+
+    #[test]
+    fn palette_filter() {
+        let mut palette = Palette::new(&KeyBindings::default());
+        assert_eq!(names(&palette).len(), ACTIONS.len());
+        type_text(&mut palette, "kill");
+        assert_eq!(names(&palette)[..2], ["kill-pane", "kill-window"]);
+        palette.handle_key(Key::Down);
+        assert_eq!(
+            palette.handle_key(Key::Char('\n')),
+            PaletteInput::Perform(Action::KillWindow(true))
+        );
+        // Actions are found by their descriptions too, after those found by
+        // their names.
+        palette.handle_key(Key::Ctrl('u'));
+        type_text(&mut palette, "clipboard");
+        assert_eq!(names(&palette), ["paste-clipboard"]);
+        palette.handle_key(Key::Backspace);
+        type_text(&mut palette, "xyz");
+        assert!(names(&palette).is_empty());
+        assert_eq!(palette.handle_key(Key::Char('\n')), PaletteInput::Pending);
+        assert_eq!(palette.handle_key(Key::Esc), PaletteInput::Close);
+    }
+
+    #[test]
+    fn palette_actions() {
+        let mut bindings = KeyBindings::default();
+        bindings.bind("-n F12 run-shell make".parse().unwrap());
+        let mut palette = Palette::new(&bindings);
+        type_text(&mut palette, "run-shell");
+        let entry = &palette.entries[palette.matches[0]];
+        assert_eq!(entry.keys, ["-n F12"]);
+        assert!(entry.line().starts_with("run-shell [-m | -w] COMMAND "));
+        // Actions needing arguments are completed at the command prompt.
+        assert_eq!(
+            palette.handle_key(Key::Char('\n')),
+            PaletteInput::Prompt("run-shell ".to_string())
+        );
+
+        let mut palette = Palette::new(&bindings);
+        type_text(&mut palette, "new-window");
+        let entry = &palette.entries[palette.matches[0]];
+        assert_eq!(entry.keys, ["c"]);
+        assert_eq!(
+            palette.handle_mouse(MouseEvent {
+                kind: MouseKind::Press,
+                button: MouseButton::Left,
+                modifiers: Default::default(),
+                col: 3,
+                row: 1,
+            }),
+            PaletteInput::Perform(Action::NewWindow {
+                name: None,
+                command: None,
+                cwd: None,
+                env: Vec::new(),
+            })
+        );
+    }
+
+    #[test]
+    fn draw() {
+        let mut palette = Palette::new(&KeyBindings::default());
+        type_text(&mut palette, "detach");
+        let mut out = Vec::new();
+        let area = Winsize {
+            ws_row: 3,
+            ws_col: 60,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        };
+        palette.draw(&mut out, area).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.starts_with("\x1b[1;1H\x1b[0;1m> detach_ "));
+        assert!(out.contains("\x1b[2;1H\x1b[0;7mdetach    "));
+        assert!(out.contains(" d          Detach"));
+        let mut out = Vec::new();
+        palette.draw(&mut out, area).unwrap();
+        assert!(out.is_empty());
+    }
+}
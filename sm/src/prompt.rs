@@ -0,0 +1,334 @@
+//! A line of text entered on the status line, such as a window's new name or a
+//! command, or a question to confirm something drastic.
+
+use std::io::{self, Write};
+
+use nix::pty::Winsize;
+
+use session_manager::width;
+
+use crate::{backend::Key, bindings::Action, style::Style};
+
+/// What the prompt made of a key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PromptInput {
+    /// The prompt stays open.
+    Pending,
+    /// The prompt was dismissed.
+    Cancel,
+    /// The text was entered, giving the action to perform with it.
+    Submit(Action),
+    /// A command line was entered, to be parsed and performed.
+    Command(String),
+}
+
+/// A prompt for a line of text, edited with the usual keys, or for `y` or `n`.
+#[derive(Debug, Clone)]
+pub struct Prompt {
+    label: String,
+    text: Vec<char>,
+    /// The position of the cursor in `text`.
+    cursor: usize,
+    submit: Submit,
+    /// Earlier entries to go back to with Up and Down, oldest first.
+    history: Vec<String>,
+    /// How far back in `history` the text shown is, with the text typed before
+    /// going back, if it is from there.
+    back: Option<(usize, Vec<char>)>,
+    /// Whether the prompt has to be drawn again.
+    dirty: bool,
+}
+
+/// What answering a prompt does.
+#[derive(Debug, Clone)]
+enum Submit {
+    /// Turn the text entered into an action.
+    Text(fn(String) -> Action),
+    /// Perform the action if `y` is pressed.
+    Confirm(Action),
+    /// Give back the text entered as a command line.
+    Command,
+}
+
+impl Prompt {
+    /// Ask for text after `label`, starting with `text`, to be turned into an
+    /// action by `submit`.
+    pub fn new(label: &str, text: &str, submit: fn(String) -> Action) -> Prompt {
+        let text: Vec<char> = text.chars().collect();
+        Prompt {
+            label: label.to_string(),
+            cursor: text.len(),
+            text,
+            submit: Submit::Text(submit),
+            history: Vec::new(),
+            back: None,
+            dirty: true,
+        }
+    }
+
+    /// Ask for a command line, starting with `text`, with the commands entered
+    /// before, oldest first, to go back to.
+    pub fn command(text: &str, history: Vec<String>) -> Prompt {
+        let text: Vec<char> = text.chars().collect();
+        Prompt {
+            label: ":".to_string(),
+            cursor: text.len(),
+            text,
+            submit: Submit::Command,
+            history,
+            back: None,
+            dirty: true,
+        }
+    }
+
+    /// Ask `question`, performing `action` if it is answered with `y`; any
+    /// other key cancels.
+    pub fn confirm(question: &str, action: Action) -> Prompt {
+        Prompt {
+            label: format!("{} (y/n)", question),
+            text: Vec::new(),
+            cursor: 0,
+            submit: Submit::Confirm(action),
+            history: Vec::new(),
+            back: None,
+            dirty: true,
+        }
+    }
+
+    /// Edit the text with `key`: Enter submits it and Escape dismisses the
+    /// prompt; the arrow keys, Home and End, `C-a` and `C-e` move the cursor,
+    /// and Backspace, Delete and `C-u` erase. Up and Down go back and forth
+    /// through the history, if there is one.
+    ///
+    /// A question is answered by any key: `y` confirms it.
+    pub fn handle_key(&mut self, key: Key) -> PromptInput {
+        let submit = match &self.submit {
+            Submit::Text(submit) => Some(submit),
+            Submit::Command => None,
+            Submit::Confirm(action) => {
+                return match key {
+                    Key::Char('y') | Key::Char('Y') => {
+                        PromptInput::Submit(action.clone())
+                    }
+                    _ => PromptInput::Cancel,
+                };
+            }
+        };
+        match key {
+            Key::Char('\n') | Key::Char('\r') => {
+                let text = self.text.iter().collect();
+                return match submit {
+                    Some(submit) => PromptInput::Submit(submit(text)),
+                    None => PromptInput::Command(text),
+                };
+            }
+            Key::Esc | Key::Ctrl('c') => return PromptInput::Cancel,
+            Key::Char(c) if !c.is_control() => {
+                self.text.insert(self.cursor, c);
+                self.cursor += 1;
+            }
+            Key::Backspace if self.cursor > 0 => {
+                self.cursor -= 1;
+                self.text.remove(self.cursor);
+            }
+            Key::Delete if self.cursor < self.text.len() => {
+                self.text.remove(self.cursor);
+            }
+            Key::Ctrl('u') => {
+                self.text.drain(..self.cursor);
+                self.cursor = 0;
+            }
+            Key::Left => self.cursor = self.cursor.saturating_sub(1),
+            Key::Right => self.cursor = (self.cursor + 1).min(self.text.len()),
+            Key::Up => self.go_back(1),
+            Key::Down => self.go_back(-1),
+            Key::Home | Key::Ctrl('a') => self.cursor = 0,
+            Key::End | Key::Ctrl('e') => self.cursor = self.text.len(),
+            _ => return PromptInput::Pending,
+        }
+        self.dirty = true;
+        PromptInput::Pending
+    }
+
+    /// Show the entry `by` further back in the history than the one shown, or
+    /// closer if negative; past the most recent is the text typed before
+    /// going back.
+    fn go_back(&mut self, by: isize) {
+        let back = self.back.as_ref().map_or(0, |(back, _)| *back as isize + 1);
+        let back = (back + by).clamp(0, self.history.len() as isize) as usize;
+        let draft = match self.back.take() {
+            Some((_, draft)) => draft,
+            None => self.text.clone(),
+        };
+        if back == 0 {
+            self.text = draft;
+        } else {
+            let entry = &self.history[self.history.len() - back];
+            self.text = entry.chars().collect();
+            self.back = Some((back - 1, draft));
+        }
+        self.cursor = self.text.len();
+    }
+
+    /// Draw the prompt on the last row of a terminal of size `size` if it has
+    /// changed, and put the cursor in it.
+    ///
+    /// If the text is too long, the part around the cursor is shown.
+    pub fn draw<T: Write>(
+        &mut self,
+        output: &mut T,
+        size: Winsize,
+        style: &Style,
+    ) -> io::Result<()> {
+        let cols = size.ws_col as usize;
+        let label = format!("{} ", self.label);
+        let room = cols.saturating_sub(width::str_width(&label) + 1);
+        let before: String = self.text[..self.cursor].iter().collect();
+        let skip = width::str_width(&before).saturating_sub(room);
+        let mut shown = String::new();
+        let mut skipped = 0;
+        for c in self.text.iter() {
+            if skipped < skip {
+                skipped += width::char_width(*c);
+            } else {
+                shown.push(*c);
+            }
+        }
+        let before_width = width::str_width(&before) - skipped;
+        if self.dirty {
+            let line = width::fit(&format!("{}{}", label, shown), cols);
+            let style = style.sgr();
+            write!(output, "\x1b[{};1H{}{}\x1b[0m", size.ws_row, style, line)?;
+            self.dirty = false;
+        }
+        let col = (width::str_width(&label) + before_width + 1).min(cols);
+        write!(output, "\x1b[{};{}H", size.ws_row, col)
+    }
+
+    /// Draw the prompt again on the next `draw`.
+    pub fn mark_dirty(&mut self) {
+        self.dirty = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::style::Theme;
+
+    const SIZE: Winsize = Winsize {
+        ws_row: 24,
+        ws_col: 16,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+
+    fn rename(name: String) -> Action {
+        Action::RenameWindow(Some(name))
+    }
+
+    #[test]
+    fn edit() {
+        let mut prompt = Prompt::new("name:", "sh", rename);
+        for key in [Key::Backspace, Key::Char('s'), Key::Home, Key::Char('z')] {
+            assert_eq!(prompt.handle_key(key), PromptInput::Pending);
+        }
+        prompt.handle_key(Key::Right);
+        prompt.handle_key(Key::Delete);
+        assert_eq!(
+            prompt.handle_key(Key::Char('\n')),
+            PromptInput::Submit(rename("zs".to_string()))
+        );
+        prompt.handle_key(Key::Left);
+        prompt.handle_key(Key::Ctrl('u'));
+        assert_eq!(
+            prompt.handle_key(Key::Char('\n')),
+            PromptInput::Submit(rename("s".to_string()))
+        );
+        assert_eq!(prompt.handle_key(Key::Esc), PromptInput::Cancel);
+    }
+
+    #[test]
+    fn command() {
+        let history = vec!["new-window".to_string(), "next-pane".to_string()];
+        let mut prompt = Prompt::command("", history);
+        prompt.handle_key(Key::Char('l'));
+        let text = |prompt: &Prompt| prompt.text.iter().collect::<String>();
+        prompt.handle_key(Key::Up);
+        assert_eq!(text(&prompt), "next-pane");
+        prompt.handle_key(Key::Up);
+        prompt.handle_key(Key::Up);
+        assert_eq!(text(&prompt), "new-window");
+        prompt.handle_key(Key::Down);
+        assert_eq!(text(&prompt), "next-pane");
+        prompt.handle_key(Key::Down);
+        prompt.handle_key(Key::Down);
+        // Past the most recent is what was typed.
+        assert_eq!(text(&prompt), "l");
+        prompt.handle_key(Key::Up);
+        prompt.handle_key(Key::Char('!'));
+        assert_eq!(
+            prompt.handle_key(Key::Char('\n')),
+            PromptInput::Command("next-pane!".to_string())
+        );
+
+        let mut out = Vec::new();
+        prompt
+            .draw(&mut out, SIZE, &Theme::default().message)
+            .unwrap();
+        assert!(String::from_utf8(out).unwrap().contains(": next-pane!"));
+    }
+
+    #[test]
+    fn confirm() {
+        let mut prompt = Prompt::confirm("kill-window 2:vim?", Action::NextWindow);
+        assert_eq!(
+            prompt.handle_key(Key::Char('y')),
+            PromptInput::Submit(Action::NextWindow)
+        );
+        assert_eq!(prompt.handle_key(Key::Char('n')), PromptInput::Cancel);
+        assert_eq!(prompt.handle_key(Key::Char('\n')), PromptInput::Cancel);
+
+        let mut out = Vec::new();
+        let size = Winsize { ws_col: 32, ..SIZE };
+        prompt
+            .draw(&mut out, size, &Theme::default().message)
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "\x1b[24;1H\x1b[0;7mkill-window 2:vim? (y/n)        \x1b[0m\x1b[24;26H"
+        );
+    }
+
+    #[test]
+    fn draw() {
+        let mut prompt = Prompt::new("name:", "logs", rename);
+        let mut out = Vec::new();
+        prompt
+            .draw(&mut out, SIZE, &Theme::default().message)
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "\x1b[24;1H\x1b[0;7mname: logs      \x1b[0m\x1b[24;11H"
+        );
+        let mut out = Vec::new();
+        prompt
+            .draw(&mut out, SIZE, &Theme::default().message)
+            .unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "\x1b[24;11H");
+
+        // Long text scrolls to keep the cursor in view.
+        for c in "-and-more".chars() {
+            prompt.handle_key(Key::Char(c));
+        }
+        let mut out = Vec::new();
+        prompt
+            .draw(&mut out, SIZE, &Theme::default().message)
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "\x1b[24;1H\x1b[0;7mname: -and-more \x1b[0m\x1b[24;16H"
+        );
+    }
+}
@@ -0,0 +1,1120 @@
+//! The messages exchanged by the server and its clients over the socket.
+//!
+//! Each message is a frame: its length as a big-endian `u32`, then a tag byte
+//! saying what it is, then its fields. A client starts with `Hello`, so that a
+//! server and client from different versions of `sm` do not misread each other,
+//! then says what it wants: to create a session, attach to one, or list them.
+
+use std::{
+    convert::{TryFrom, TryInto},
+    io::{self, Read, Write},
+    path::PathBuf,
+};
+
+use nix::pty::Winsize;
+use serde::Serialize;
+
+use session_manager::{
+    export::{PaneExport, SessionExport, WindowExport},
+    session::ClosedWindow,
+};
+
+use crate::backend::{Event, Key, Modifiers, MouseButton, MouseEvent, MouseKind};
+
+/// The version of the protocol, changed whenever a message is.
+pub const VERSION: u32 = 16;
+
+/// The longest frame accepted, to not allocate whatever a broken peer asks for.
+const MAX_FRAME: usize = 16 << 20;
+
+/// What a client sends the server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClientMessage {
+    /// The version of the protocol the client speaks, sent first.
+    Hello(u32),
+    /// Create a session, named `name` or a number, and attach to it, sharing
+    /// the windows of the session named `group` if given. `env` is the
+    /// client's environment.
+    NewSession {
+        name: Option<String>,
+        group: Option<String>,
+        env: Vec<(String, String)>,
+    },
+    /// Attach to the named session, or the one attached to last, detaching the
+    /// other clients if `detach`. `env` is the client's environment.
+    Attach {
+        name: Option<String>,
+        detach: bool,
+        env: Vec<(String, String)>,
+    },
+    /// List the sessions.
+    ListSessions,
+    /// List the windows of the named session, or of the one attached to last.
+    ListWindows(Option<String>),
+    /// Describe the sessions and the client attached.
+    Info,
+    /// What the active pane of the named session shows, or of the one attached
+    /// to last.
+    CapturePane(Option<String>),
+    /// A record of the named session, or of the one attached to last, to
+    /// archive.
+    ExportSession(Option<String>),
+    /// Expand `format` for the named session, or the one attached to last, and
+    /// answer with it if `print`, or show it on the status line.
+    DisplayMessage {
+        name: Option<String>,
+        format: String,
+        print: bool,
+    },
+    /// Call the named session, or the one attached to last, `new_name`.
+    RenameSession {
+        name: Option<String>,
+        new_name: String,
+    },
+    /// End the named session, or the one attached to last, closing its
+    /// windows.
+    KillSession(Option<String>),
+    /// Move the windows of the session named `source` into the one named
+    /// `target`, or the one attached to last, after its own, and end `source`.
+    MergeSession {
+        source: String,
+        target: Option<String>,
+    },
+    /// List the panes of the named session, or of the one attached to last.
+    ListPanes(Option<String>),
+    /// List the clients attached.
+    ListClients,
+    /// List the paste buffers of the named session, or of the one attached to
+    /// last.
+    ListBuffers(Option<String>),
+    /// The size of the client's terminal, sent on attaching and on resizing.
+    Resize(Winsize),
+    /// Whether the client's terminal draws East Asian Ambiguous characters two
+    /// columns wide, sent on attaching before the size.
+    AmbiguousWidth(bool),
+    /// An input event, with the bytes that produced it.
+    Input(Event, Vec<u8>),
+}
+
+/// What the server sends a client.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ServerMessage {
+    /// Output for the client's terminal.
+    Output(Vec<u8>),
+    /// The client is detached: it should restore its terminal, show the
+    /// message and exit.
+    Exit(String),
+    /// What the client asked for cannot be done, for the reason given.
+    Error(String),
+    /// The client is attached to the session it asked for.
+    Attached,
+    /// The sessions, answering `ListSessions`.
+    Sessions(Vec<SessionInfo>),
+    /// The windows, answering `ListWindows`.
+    Windows(Vec<WindowInfo>),
+    /// The panes, answering `ListPanes`.
+    Panes(Vec<PaneInfo>),
+    /// The clients, answering `ListClients`.
+    Clients(Vec<ClientInfo>),
+    /// The paste buffers, answering `ListBuffers`.
+    Buffers(Vec<BufferInfo>),
+    /// Lines of text answering `Info`, `DisplayMessage`, `RenameSession`,
+    /// `KillSession` or `MergeSession`.
+    Info(String),
+    /// The pane asked for by `CapturePane`: its size, and escape sequences
+    /// drawing what it shows on a terminal of that size.
+    Pane {
+        cols: u16,
+        rows: u16,
+        contents: Vec<u8>,
+    },
+    /// The session asked for by `ExportSession`.
+    Export(Box<SessionExport>),
+}
+
+/// What `ListSessions` tells about a session.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SessionInfo {
+    pub name: String,
+    pub windows: u32,
+    /// Whether a client is attached to it.
+    pub attached: bool,
+    /// The session whose windows it shares, if it is grouped with one.
+    pub group: Option<String>,
+}
+
+/// What `ListWindows` tells about a window.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct WindowInfo {
+    pub idx: u32,
+    pub name: String,
+    pub panes: u32,
+    /// Whether it is the window selected.
+    pub active: bool,
+}
+
+/// What `ListPanes` tells about a pane.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PaneInfo {
+    /// The index of its window.
+    pub window: u32,
+    /// Its number in the window, counting from the pane base index.
+    pub idx: u32,
+    pub cols: u32,
+    pub rows: u32,
+    /// Whether it is the active pane of its window.
+    pub active: bool,
+    /// The title its program set, if any.
+    pub title: Option<String>,
+}
+
+/// What `ListClients` tells about a client attached.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ClientInfo {
+    pub id: u64,
+    /// The session it is attached to.
+    pub session: String,
+    /// The size of its terminal, or 0 until it has said.
+    pub cols: u32,
+    pub rows: u32,
+    /// The bytes of output sent to it since it attached.
+    pub bytes: u64,
+}
+
+/// What `ListBuffers` tells about a paste buffer.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BufferInfo {
+    pub name: String,
+    /// The length of its text in bytes.
+    pub bytes: u64,
+    /// The window it was copied from, e.g. `0:sh`.
+    pub source: String,
+    /// The start of its text, on one line.
+    pub sample: String,
+}
+
+/// A message which can be sent as a frame.
+pub trait Message: Sized {
+    fn encode(&self, buf: &mut Vec<u8>);
+    fn decode(fields: &mut Fields) -> io::Result<Self>;
+}
+
+/// Send `message` on `output` as one frame.
+pub fn send<M: Message, W: Write>(output: &mut W, message: &M) -> io::Result<()> {
+    let mut frame = vec![0; 4];
+    message.encode(&mut frame);
+    let len = (frame.len() - 4) as u32;
+    frame[..4].copy_from_slice(&len.to_be_bytes());
+    output.write_all(&frame)?;
+    output.flush()
+}
+
+/// Read the next message from `input`, or `None` if it closed between frames.
+pub fn receive<M: Message, R: Read>(input: &mut R) -> io::Result<Option<M>> {
+    let mut len = [0; 4];
+    match input.read_exact(&mut len) {
+        Ok(()) => (),
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+    let len = u32::from_be_bytes(len) as usize;
+    if len > MAX_FRAME {
+        return Err(malformed());
+    }
+    let mut frame = vec![0; len];
+    input.read_exact(&mut frame)?;
+    let mut fields = Fields(&frame);
+    let message = M::decode(&mut fields)?;
+    if !fields.0.is_empty() {
+        return Err(malformed());
+    }
+    Ok(Some(message))
+}
+
+fn malformed() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "malformed message")
+}
+
+/// The fields of a frame not decoded yet.
+pub struct Fields<'a>(&'a [u8]);
+
+impl<'a> Fields<'a> {
+    fn take(&mut self, len: usize) -> io::Result<&'a [u8]> {
+        if self.0.len() < len {
+            return Err(malformed());
+        }
+        let (taken, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(taken)
+    }
+
+    fn u8(&mut self) -> io::Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> io::Result<u16> {
+        Ok(u16::from_be_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        Ok(u32::from_be_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> io::Result<u64> {
+        Ok(u64::from_be_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn char(&mut self) -> io::Result<char> {
+        char::from_u32(self.u32()?).ok_or_else(malformed)
+    }
+
+    /// A string, after its length as a `u32`.
+    fn string(&mut self) -> io::Result<String> {
+        let len = self.u32()? as usize;
+        let bytes = self.take(len)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| malformed())
+    }
+
+    /// Bytes, after their length as a `u32`.
+    fn bytes(&mut self) -> io::Result<Vec<u8>> {
+        let len = self.u32()? as usize;
+        Ok(self.take(len)?.to_vec())
+    }
+
+    /// A string if the next byte is 1, or `None` if it is 0.
+    fn optional_string(&mut self) -> io::Result<Option<String>> {
+        self.optional(Fields::string)
+    }
+
+    /// What `read` reads if the next byte is 1, or `None` if it is 0.
+    fn optional<T, F>(&mut self, read: F) -> io::Result<Option<T>>
+    where
+        F: FnOnce(&mut Self) -> io::Result<T>,
+    {
+        match self.u8()? {
+            0 => Ok(None),
+            1 => read(self).map(Some),
+            _ => Err(malformed()),
+        }
+    }
+
+    /// Variables and their values, as strings, to the end of the frame.
+    fn environment(&mut self) -> io::Result<Vec<(String, String)>> {
+        let mut env = Vec::new();
+        while !self.0.is_empty() {
+            env.push((self.string()?, self.string()?));
+        }
+        Ok(env)
+    }
+
+    /// The rest of the frame.
+    fn rest(&mut self) -> &'a [u8] {
+        std::mem::take(&mut self.0)
+    }
+}
+
+impl Message for ClientMessage {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        match self {
+            ClientMessage::Hello(version) => {
+                buf.push(2);
+                buf.extend_from_slice(&version.to_be_bytes());
+            }
+            ClientMessage::Resize(size) => {
+                buf.push(0);
+                for value in [size.ws_row, size.ws_col, size.ws_xpixel, size.ws_ypixel]
+                {
+                    buf.extend_from_slice(&value.to_be_bytes());
+                }
+            }
+            ClientMessage::Input(event, data) => {
+                buf.push(1);
+                encode_event(event, buf);
+                buf.extend_from_slice(data);
+            }
+            ClientMessage::NewSession { name, group, env } => {
+                buf.push(3);
+                encode_optional_string(name.as_deref(), buf);
+                encode_optional_string(group.as_deref(), buf);
+                encode_environment(env, buf);
+            }
+            ClientMessage::Attach { name, detach, env } => {
+                buf.push(4);
+                encode_optional_string(name.as_deref(), buf);
+                buf.push(*detach as u8);
+                encode_environment(env, buf);
+            }
+            ClientMessage::ListSessions => buf.push(5),
+            ClientMessage::Info => buf.push(6),
+            ClientMessage::CapturePane(name) => {
+                buf.push(7);
+                encode_optional_string(name.as_deref(), buf);
+            }
+            ClientMessage::ExportSession(name) => {
+                buf.push(8);
+                encode_optional_string(name.as_deref(), buf);
+            }
+            ClientMessage::AmbiguousWidth(wide) => {
+                buf.push(9);
+                buf.push(*wide as u8);
+            }
+            ClientMessage::ListWindows(name) => {
+                buf.push(10);
+                encode_optional_string(name.as_deref(), buf);
+            }
+            ClientMessage::DisplayMessage {
+                name,
+                format,
+                print,
+            } => {
+                buf.push(11);
+                encode_optional_string(name.as_deref(), buf);
+                encode_string(format, buf);
+                buf.push(*print as u8);
+            }
+            ClientMessage::RenameSession { name, new_name } => {
+                buf.push(12);
+                encode_optional_string(name.as_deref(), buf);
+                encode_string(new_name, buf);
+            }
+            ClientMessage::KillSession(name) => {
+                buf.push(13);
+                encode_optional_string(name.as_deref(), buf);
+            }
+            ClientMessage::MergeSession { source, target } => {
+                buf.push(14);
+                encode_string(source, buf);
+                encode_optional_string(target.as_deref(), buf);
+            }
+            ClientMessage::ListPanes(name) => {
+                buf.push(15);
+                encode_optional_string(name.as_deref(), buf);
+            }
+            ClientMessage::ListClients => buf.push(16),
+            ClientMessage::ListBuffers(name) => {
+                buf.push(17);
+                encode_optional_string(name.as_deref(), buf);
+            }
+        }
+    }
+
+    fn decode(fields: &mut Fields) -> io::Result<ClientMessage> {
+        Ok(match fields.u8()? {
+            0 => ClientMessage::Resize(Winsize {
+                ws_row: fields.u16()?,
+                ws_col: fields.u16()?,
+                ws_xpixel: fields.u16()?,
+                ws_ypixel: fields.u16()?,
+            }),
+            1 => {
+                let event = decode_event(fields)?;
+                ClientMessage::Input(event, fields.rest().to_vec())
+            }
+            2 => ClientMessage::Hello(fields.u32()?),
+            3 => ClientMessage::NewSession {
+                name: fields.optional_string()?,
+                group: fields.optional_string()?,
+                env: fields.environment()?,
+            },
+            4 => ClientMessage::Attach {
+                name: fields.optional_string()?,
+                detach: fields.u8()? != 0,
+                env: fields.environment()?,
+            },
+            5 => ClientMessage::ListSessions,
+            6 => ClientMessage::Info,
+            7 => ClientMessage::CapturePane(fields.optional_string()?),
+            8 => ClientMessage::ExportSession(fields.optional_string()?),
+            9 => ClientMessage::AmbiguousWidth(fields.u8()? != 0),
+            10 => ClientMessage::ListWindows(fields.optional_string()?),
+            11 => ClientMessage::DisplayMessage {
+                name: fields.optional_string()?,
+                format: fields.string()?,
+                print: fields.u8()? != 0,
+            },
+            12 => ClientMessage::RenameSession {
+                name: fields.optional_string()?,
+                new_name: fields.string()?,
+            },
+            13 => ClientMessage::KillSession(fields.optional_string()?),
+            14 => ClientMessage::MergeSession {
+                source: fields.string()?,
+                target: fields.optional_string()?,
+            },
+            15 => ClientMessage::ListPanes(fields.optional_string()?),
+            16 => ClientMessage::ListClients,
+            17 => ClientMessage::ListBuffers(fields.optional_string()?),
+            _ => return Err(malformed()),
+        })
+    }
+}
+
+impl Message for ServerMessage {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        match self {
+            ServerMessage::Output(data) => {
+                buf.push(0);
+                buf.extend_from_slice(data);
+            }
+            ServerMessage::Exit(message) => {
+                buf.push(1);
+                buf.extend_from_slice(message.as_bytes());
+            }
+            ServerMessage::Error(message) => {
+                buf.push(2);
+                buf.extend_from_slice(message.as_bytes());
+            }
+            ServerMessage::Attached => buf.push(3),
+            ServerMessage::Sessions(sessions) => {
+                buf.push(4);
+                for session in sessions {
+                    encode_string(&session.name, buf);
+                    buf.extend_from_slice(&session.windows.to_be_bytes());
+                    buf.push(session.attached as u8);
+                    encode_optional_string(session.group.as_deref(), buf);
+                }
+            }
+            ServerMessage::Info(text) => {
+                buf.push(5);
+                buf.extend_from_slice(text.as_bytes());
+            }
+            ServerMessage::Pane {
+                cols,
+                rows,
+                contents,
+            } => {
+                buf.push(6);
+                buf.extend_from_slice(&cols.to_be_bytes());
+                buf.extend_from_slice(&rows.to_be_bytes());
+                buf.extend_from_slice(contents);
+            }
+            ServerMessage::Export(export) => {
+                buf.push(7);
+                encode_export(export, buf);
+            }
+            ServerMessage::Windows(windows) => {
+                buf.push(8);
+                for window in windows {
+                    buf.extend_from_slice(&window.idx.to_be_bytes());
+                    encode_string(&window.name, buf);
+                    buf.extend_from_slice(&window.panes.to_be_bytes());
+                    buf.push(window.active as u8);
+                }
+            }
+            ServerMessage::Panes(panes) => {
+                buf.push(9);
+                for pane in panes {
+                    buf.extend_from_slice(&pane.window.to_be_bytes());
+                    buf.extend_from_slice(&pane.idx.to_be_bytes());
+                    buf.extend_from_slice(&pane.cols.to_be_bytes());
+                    buf.extend_from_slice(&pane.rows.to_be_bytes());
+                    buf.push(pane.active as u8);
+                    encode_optional_string(pane.title.as_deref(), buf);
+                }
+            }
+            ServerMessage::Clients(clients) => {
+                buf.push(10);
+                for client in clients {
+                    buf.extend_from_slice(&client.id.to_be_bytes());
+                    encode_string(&client.session, buf);
+                    buf.extend_from_slice(&client.cols.to_be_bytes());
+                    buf.extend_from_slice(&client.rows.to_be_bytes());
+                    buf.extend_from_slice(&client.bytes.to_be_bytes());
+                }
+            }
+            ServerMessage::Buffers(buffers) => {
+                buf.push(11);
+                for buffer in buffers {
+                    encode_string(&buffer.name, buf);
+                    buf.extend_from_slice(&buffer.bytes.to_be_bytes());
+                    encode_string(&buffer.source, buf);
+                    encode_string(&buffer.sample, buf);
+                }
+            }
+        }
+    }
+
+    fn decode(fields: &mut Fields) -> io::Result<ServerMessage> {
+        Ok(match fields.u8()? {
+            0 => ServerMessage::Output(fields.rest().to_vec()),
+            1 => {
+                ServerMessage::Exit(String::from_utf8_lossy(fields.rest()).into_owned())
+            }
+            2 => ServerMessage::Error(
+                String::from_utf8_lossy(fields.rest()).into_owned(),
+            ),
+            3 => ServerMessage::Attached,
+            4 => {
+                let mut sessions = Vec::new();
+                while !fields.0.is_empty() {
+                    sessions.push(SessionInfo {
+                        name: fields.string()?,
+                        windows: fields.u32()?,
+                        attached: fields.u8()? != 0,
+                        group: fields.optional_string()?,
+                    });
+                }
+                ServerMessage::Sessions(sessions)
+            }
+            5 => {
+                ServerMessage::Info(String::from_utf8_lossy(fields.rest()).into_owned())
+            }
+            6 => ServerMessage::Pane {
+                cols: fields.u16()?,
+                rows: fields.u16()?,
+                contents: fields.rest().to_vec(),
+            },
+            7 => ServerMessage::Export(Box::new(decode_export(fields)?)),
+            8 => {
+                let mut windows = Vec::new();
+                while !fields.0.is_empty() {
+                    windows.push(WindowInfo {
+                        idx: fields.u32()?,
+                        name: fields.string()?,
+                        panes: fields.u32()?,
+                        active: fields.u8()? != 0,
+                    });
+                }
+                ServerMessage::Windows(windows)
+            }
+            9 => {
+                let mut panes = Vec::new();
+                while !fields.0.is_empty() {
+                    panes.push(PaneInfo {
+                        window: fields.u32()?,
+                        idx: fields.u32()?,
+                        cols: fields.u32()?,
+                        rows: fields.u32()?,
+                        active: fields.u8()? != 0,
+                        title: fields.optional_string()?,
+                    });
+                }
+                ServerMessage::Panes(panes)
+            }
+            10 => {
+                let mut clients = Vec::new();
+                while !fields.0.is_empty() {
+                    clients.push(ClientInfo {
+                        id: fields.u64()?,
+                        session: fields.string()?,
+                        cols: fields.u32()?,
+                        rows: fields.u32()?,
+                        bytes: fields.u64()?,
+                    });
+                }
+                ServerMessage::Clients(clients)
+            }
+            11 => {
+                let mut buffers = Vec::new();
+                while !fields.0.is_empty() {
+                    buffers.push(BufferInfo {
+                        name: fields.string()?,
+                        bytes: fields.u64()?,
+                        source: fields.string()?,
+                        sample: fields.string()?,
+                    });
+                }
+                ServerMessage::Buffers(buffers)
+            }
+            _ => return Err(malformed()),
+        })
+    }
+}
+
+fn encode_string(string: &str, buf: &mut Vec<u8>) {
+    buf.extend_from_slice(&(string.len() as u32).to_be_bytes());
+    buf.extend_from_slice(string.as_bytes());
+}
+
+fn encode_environment(env: &[(String, String)], buf: &mut Vec<u8>) {
+    for (name, value) in env {
+        encode_string(name, buf);
+        encode_string(value, buf);
+    }
+}
+
+fn encode_optional_string(string: Option<&str>, buf: &mut Vec<u8>) {
+    match string {
+        Some(string) => {
+            buf.push(1);
+            encode_string(string, buf);
+        }
+        None => buf.push(0),
+    }
+}
+
+fn encode_optional<T, F: FnOnce(T, &mut Vec<u8>)>(
+    value: Option<T>,
+    buf: &mut Vec<u8>,
+    encode: F,
+) {
+    match value {
+        Some(value) => {
+            buf.push(1);
+            encode(value, buf);
+        }
+        None => buf.push(0),
+    }
+}
+
+fn encode_u32(value: usize, buf: &mut Vec<u8>) {
+    buf.extend_from_slice(&(value as u32).to_be_bytes());
+}
+
+fn encode_status(status: i32, buf: &mut Vec<u8>) {
+    buf.extend_from_slice(&status.to_be_bytes());
+}
+
+fn encode_export(export: &SessionExport, buf: &mut Vec<u8>) {
+    encode_string(&export.name, buf);
+    encode_optional(export.selected, buf, encode_u32);
+    encode_u32(export.windows.len(), buf);
+    for window in &export.windows {
+        encode_u32(window.idx, buf);
+        encode_string(&window.name, buf);
+        encode_string(&window.layout.to_string(), buf);
+        encode_u32(window.active, buf);
+        encode_optional_string(window.input.as_deref(), buf);
+        encode_u32(window.panes.len(), buf);
+        for pane in &window.panes {
+            buf.push(pane.applet as u8);
+            encode_optional_string(pane.command.as_deref(), buf);
+            let cwd = pane.cwd.as_ref().map(|cwd| cwd.to_string_lossy());
+            encode_optional_string(cwd.as_deref(), buf);
+            encode_optional(pane.status, buf, encode_status);
+            buf.extend_from_slice(&pane.cols.to_be_bytes());
+            buf.extend_from_slice(&pane.rows.to_be_bytes());
+            encode_optional(pane.contents.as_deref(), buf, |contents, buf| {
+                encode_u32(contents.len(), buf);
+                buf.extend_from_slice(contents);
+            });
+        }
+    }
+    encode_u32(export.closed.len(), buf);
+    for closed in &export.closed {
+        encode_u32(closed.idx, buf);
+        encode_string(&closed.name, buf);
+        encode_optional(closed.status, buf, encode_status);
+    }
+    encode_u32(export.pane_base_index, buf);
+}
+
+fn decode_export(fields: &mut Fields) -> io::Result<SessionExport> {
+    let index = |fields: &mut Fields| fields.u32().map(|idx| idx as usize);
+    let status = |fields: &mut Fields| fields.u32().map(|status| status as i32);
+    let name = fields.string()?;
+    let selected = fields.optional(index)?;
+    let mut windows = Vec::new();
+    for _ in 0..fields.u32()? {
+        let idx = index(fields)?;
+        let name = fields.string()?;
+        let layout = fields.string()?.parse().map_err(|_| malformed())?;
+        let active = index(fields)?;
+        let input = fields.optional_string()?;
+        let mut panes = Vec::new();
+        for _ in 0..fields.u32()? {
+            panes.push(PaneExport {
+                applet: fields.u8()? != 0,
+                command: fields.optional_string()?,
+                cwd: fields.optional_string()?.map(PathBuf::from),
+                status: fields.optional(status)?,
+                cols: fields.u16()?,
+                rows: fields.u16()?,
+                contents: fields.optional(Fields::bytes)?,
+            });
+        }
+        windows.push(WindowExport {
+            idx,
+            name,
+            panes,
+            layout,
+            active,
+            input,
+        });
+    }
+    let mut closed = Vec::new();
+    for _ in 0..fields.u32()? {
+        closed.push(ClosedWindow {
+            idx: index(fields)?,
+            name: fields.string()?,
+            status: fields.optional(status)?,
+        });
+    }
+    Ok(SessionExport {
+        name,
+        selected,
+        windows,
+        closed,
+        pane_base_index: index(fields)?,
+    })
+}
+
+fn encode_event(event: &Event, buf: &mut Vec<u8>) {
+    match event {
+        Event::Key(key) => {
+            let (tag, value) = match *key {
+                Key::Backspace => (0, 0),
+                Key::Left => (1, 0),
+                Key::Right => (2, 0),
+                Key::Up => (3, 0),
+                Key::Down => (4, 0),
+                Key::CtrlLeft => (5, 0),
+                Key::CtrlRight => (6, 0),
+                Key::CtrlUp => (7, 0),
+                Key::CtrlDown => (8, 0),
+                Key::Home => (9, 0),
+                Key::End => (10, 0),
+                Key::PageUp => (11, 0),
+                Key::PageDown => (12, 0),
+                Key::BackTab => (13, 0),
+                Key::Delete => (14, 0),
+                Key::Insert => (15, 0),
+                Key::F(n) => (16, n as u32),
+                Key::Char(c) => (17, c as u32),
+                Key::Alt(c) => (18, c as u32),
+                Key::Ctrl(c) => (19, c as u32),
+                Key::Null => (20, 0),
+                Key::Esc => (21, 0),
+                Key::ShiftLeft => (22, 0),
+                Key::ShiftRight => (23, 0),
+                Key::ShiftUp => (24, 0),
+                Key::ShiftDown => (25, 0),
+            };
+            buf.extend_from_slice(&[0, tag]);
+            buf.extend_from_slice(&u32::to_be_bytes(value));
+        }
+        Event::Mouse(mouse) => {
+            let kind = match mouse.kind {
+                MouseKind::Press => 0,
+                MouseKind::Drag => 1,
+                MouseKind::Release => 2,
+            };
+            let button = match mouse.button {
+                MouseButton::Left => 0,
+                MouseButton::Middle => 1,
+                MouseButton::Right => 2,
+                MouseButton::WheelUp => 3,
+                MouseButton::WheelDown => 4,
+            };
+            let modifiers = mouse.modifiers.shift as u8
+                | (mouse.modifiers.alt as u8) << 1
+                | (mouse.modifiers.ctrl as u8) << 2;
+            buf.extend_from_slice(&[1, kind, button, modifiers]);
+            buf.extend_from_slice(&mouse.col.to_be_bytes());
+            buf.extend_from_slice(&mouse.row.to_be_bytes());
+        }
+        Event::Unsupported => buf.push(2),
+    }
+}
+
+fn decode_event(fields: &mut Fields) -> io::Result<Event> {
+    Ok(match fields.u8()? {
+        0 => {
+            let tag = fields.u8()?;
+            Event::Key(match tag {
+                16 => Key::F(u8::try_from(fields.u32()?).map_err(|_| malformed())?),
+                17 => Key::Char(fields.char()?),
+                18 => Key::Alt(fields.char()?),
+                19 => Key::Ctrl(fields.char()?),
+                _ => {
+                    fields.u32()?;
+                    match tag {
+                        0 => Key::Backspace,
+                        1 => Key::Left,
+                        2 => Key::Right,
+                        3 => Key::Up,
+                        4 => Key::Down,
+                        5 => Key::CtrlLeft,
+                        6 => Key::CtrlRight,
+                        7 => Key::CtrlUp,
+                        8 => Key::CtrlDown,
+                        9 => Key::Home,
+                        10 => Key::End,
+                        11 => Key::PageUp,
+                        12 => Key::PageDown,
+                        13 => Key::BackTab,
+                        14 => Key::Delete,
+                        15 => Key::Insert,
+                        20 => Key::Null,
+                        21 => Key::Esc,
+                        22 => Key::ShiftLeft,
+                        23 => Key::ShiftRight,
+                        24 => Key::ShiftUp,
+                        25 => Key::ShiftDown,
+                        _ => return Err(malformed()),
+                    }
+                }
+            })
+        }
+        1 => {
+            let kind = match fields.u8()? {
+                0 => MouseKind::Press,
+                1 => MouseKind::Drag,
+                2 => MouseKind::Release,
+                _ => return Err(malformed()),
+            };
+            let button = match fields.u8()? {
+                0 => MouseButton::Left,
+                1 => MouseButton::Middle,
+                2 => MouseButton::Right,
+                3 => MouseButton::WheelUp,
+                4 => MouseButton::WheelDown,
+                _ => return Err(malformed()),
+            };
+            let modifiers = fields.u8()?;
+            Event::Mouse(MouseEvent {
+                kind,
+                button,
+                modifiers: Modifiers {
+                    shift: modifiers & 1 != 0,
+                    alt: modifiers & 2 != 0,
+                    ctrl: modifiers & 4 != 0,
+                },
+                col: fields.u16()?,
+                row: fields.u16()?,
+            })
+        }
+        2 => Event::Unsupported,
+        _ => return Err(malformed()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip<M: Message + std::fmt::Debug + PartialEq>(message: M) {
+        let mut frame = Vec::new();
+        send(&mut frame, &message).unwrap();
+        let mut input = frame.as_slice();
+        assert_eq!(receive::<M, _>(&mut input).unwrap(), Some(message));
+        assert_eq!(receive::<M, _>(&mut input).unwrap(), None);
+    }
+
+    #[test]
+    fn messages() {
+        round_trip(ClientMessage::Hello(VERSION));
+        let env = vec![
+            ("SSH_AUTH_SOCK".to_string(), "/tmp/agent".to_string()),
+            ("EMPTY".to_string(), String::new()),
+        ];
+        round_trip(ClientMessage::NewSession {
+            name: Some("work".to_string()),
+            group: None,
+            env: env.clone(),
+        });
+        round_trip(ClientMessage::NewSession {
+            name: None,
+            group: Some("0".to_string()),
+            env: Vec::new(),
+        });
+        round_trip(ClientMessage::Attach {
+            name: Some("".to_string()),
+            detach: true,
+            env,
+        });
+        round_trip(ClientMessage::Attach {
+            name: None,
+            detach: false,
+            env: Vec::new(),
+        });
+        round_trip(ClientMessage::ListSessions);
+        round_trip(ClientMessage::ListWindows(Some("work".to_string())));
+        round_trip(ClientMessage::ListWindows(None));
+        round_trip(ClientMessage::Info);
+        round_trip(ClientMessage::CapturePane(Some("work".to_string())));
+        round_trip(ClientMessage::CapturePane(None));
+        round_trip(ClientMessage::ExportSession(Some("work".to_string())));
+        round_trip(ClientMessage::ExportSession(None));
+        round_trip(ClientMessage::DisplayMessage {
+            name: Some("work".to_string()),
+            format: "#{window_index}: #{pane_title}".to_string(),
+            print: true,
+        });
+        round_trip(ClientMessage::DisplayMessage {
+            name: None,
+            format: String::new(),
+            print: false,
+        });
+        round_trip(ClientMessage::RenameSession {
+            name: Some("work".to_string()),
+            new_name: "build farm".to_string(),
+        });
+        round_trip(ClientMessage::RenameSession {
+            name: None,
+            new_name: String::new(),
+        });
+        round_trip(ClientMessage::KillSession(Some("work".to_string())));
+        round_trip(ClientMessage::KillSession(None));
+        round_trip(ClientMessage::MergeSession {
+            source: "scratch".to_string(),
+            target: Some("work".to_string()),
+        });
+        round_trip(ClientMessage::MergeSession {
+            source: "scratch".to_string(),
+            target: None,
+        });
+        round_trip(ClientMessage::ListPanes(Some("work".to_string())));
+        round_trip(ClientMessage::ListClients);
+        round_trip(ClientMessage::ListBuffers(None));
+        round_trip(ClientMessage::AmbiguousWidth(true));
+        round_trip(ClientMessage::AmbiguousWidth(false));
+        round_trip(ClientMessage::Resize(Winsize {
+            ws_row: 24,
+            ws_col: 80,
+            ws_xpixel: 640,
+            ws_ypixel: 480,
+        }));
+        round_trip(ClientMessage::Input(Event::Key(Key::Char('é')), "é".into()));
+        round_trip(ClientMessage::Input(
+            Event::Key(Key::F(12)),
+            b"\x1b[24~".to_vec(),
+        ));
+        round_trip(ClientMessage::Input(Event::Key(Key::CtrlDown), Vec::new()));
+        round_trip(ClientMessage::Input(
+            Event::Mouse(MouseEvent {
+                kind: MouseKind::Drag,
+                button: MouseButton::Right,
+                modifiers: Modifiers {
+                    shift: true,
+                    alt: false,
+                    ctrl: true,
+                },
+                col: 300,
+                row: 2,
+            }),
+            Vec::new(),
+        ));
+        round_trip(ClientMessage::Input(
+            Event::Unsupported,
+            b"\x1b[?1;2c".to_vec(),
+        ));
+        round_trip(ServerMessage::Output(b"\x1b[H\x1b[2Jhello".to_vec()));
+        round_trip(ServerMessage::Exit("detached".to_string()));
+        round_trip(ServerMessage::Error("no session: work".to_string()));
+        round_trip(ServerMessage::Attached);
+        round_trip(ServerMessage::Sessions(Vec::new()));
+        round_trip(ServerMessage::Sessions(vec![
+            SessionInfo {
+                name: "0".to_string(),
+                windows: 3,
+                attached: false,
+                group: None,
+            },
+            SessionInfo {
+                name: "作業".to_string(),
+                windows: 1,
+                attached: true,
+                group: Some("0".to_string()),
+            },
+        ]));
+        round_trip(ServerMessage::Windows(vec![
+            WindowInfo {
+                idx: 0,
+                name: "sh".to_string(),
+                panes: 2,
+                active: false,
+            },
+            WindowInfo {
+                idx: 3,
+                name: "ログ".to_string(),
+                panes: 1,
+                active: true,
+            },
+        ]));
+        round_trip(ServerMessage::Panes(vec![
+            PaneInfo {
+                window: 0,
+                idx: 0,
+                cols: 40,
+                rows: 23,
+                active: true,
+                title: Some("vim".to_string()),
+            },
+            PaneInfo {
+                window: 2,
+                idx: 1,
+                cols: 39,
+                rows: 23,
+                active: false,
+                title: None,
+            },
+        ]));
+        round_trip(ServerMessage::Clients(vec![ClientInfo {
+            id: 3,
+            session: "work".to_string(),
+            cols: 80,
+            rows: 24,
+            bytes: 1 << 40,
+        }]));
+        round_trip(ServerMessage::Buffers(vec![BufferInfo {
+            name: "buffer0".to_string(),
+            bytes: 12,
+            source: "0:sh".to_string(),
+            sample: "hello world".to_string(),
+        }]));
+        round_trip(ServerMessage::Info("client: 12 bytes out\n".to_string()));
+        round_trip(ServerMessage::Pane {
+            cols: 80,
+            rows: 24,
+            contents: b"\x1b[1;1H\x1b[0mhello".to_vec(),
+        });
+        let pane = PaneExport {
+            applet: false,
+            command: Some("make test".to_string()),
+            cwd: Some(PathBuf::from("/src")),
+            status: Some(-1),
+            cols: 40,
+            rows: 24,
+            contents: Some(b"\x1b[1;1Hok".to_vec()),
+        };
+        round_trip(ServerMessage::Export(Box::new(SessionExport {
+            name: "work".to_string(),
+            selected: Some(3),
+            windows: vec![WindowExport {
+                idx: 3,
+                name: "build".to_string(),
+                panes: vec![
+                    pane.clone(),
+                    PaneExport {
+                        applet: true,
+                        command: None,
+                        cwd: None,
+                        status: None,
+                        contents: None,
+                        ..pane
+                    },
+                ],
+                layout: "v(1:1,2:0)".parse().unwrap(),
+                active: 1,
+                input: Some("make\n".to_string()),
+            }],
+            closed: vec![ClosedWindow {
+                idx: 0,
+                name: "sh".to_string(),
+                status: None,
+            }],
+            pane_base_index: 1,
+        })));
+        round_trip(ServerMessage::Export(Box::new(SessionExport {
+            name: "0".to_string(),
+            selected: None,
+            windows: Vec::new(),
+            closed: Vec::new(),
+            pane_base_index: 0,
+        })));
+    }
+
+    #[test]
+    fn malformed_frames() {
+        let receive = |frame: &[u8]| receive::<ClientMessage, _>(&mut &frame[..]);
+        // Unknown tags, a truncated frame, and one with bytes left over.
+        assert!(receive(b"\0\0\0\x01\x09").is_err());
+        assert!(receive(b"\0\0\0\x07\x01\0\x11\0\0").is_err());
+        assert!(receive(b"\0\0\0\x0a\0\0\x18\0\x50\0\0\0\0\0").is_err());
+        assert!(receive(b"\xff\xff\xff\xff").is_err());
+        // A name longer than the frame, and one which is not UTF-8.
+        assert!(receive(b"\0\0\0\x07\x04\x01\0\0\0\x09a").is_err());
+        assert!(receive(b"\0\0\0\x07\x03\x01\0\0\0\x01\xff").is_err());
+    }
+}
@@ -0,0 +1,263 @@
+//! Pictures of panes as PNG files, for documentation and bug reports: what a
+//! pane shows is drawn with a bundled monospace font, in its colours and with
+//! its attributes.
+
+use std::{
+    fs::File,
+    io::{self, BufWriter},
+    path::Path,
+};
+
+use ab_glyph::{point, Font, FontRef, PxScale, ScaleFont};
+use anyhow::{anyhow, Context, Result};
+
+use session_manager::{
+    ansi::{Color, NamedColor, Processor, Rgb},
+    grid::{Flags, Grid, Palette},
+};
+
+/// The height of the font, in pixels.
+const FONT_SIZE: f32 = 16.0;
+
+/// How far italic text leans: the pixels right per pixel up.
+const ITALIC_SLANT: f32 = 0.2;
+
+/// A picture, as rows of pixels from the top.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pixels: Vec<Rgb>,
+}
+
+impl Image {
+    fn new(width: u32, height: u32, fill: Rgb) -> Image {
+        Image {
+            width,
+            height,
+            pixels: vec![fill; (width * height) as usize],
+        }
+    }
+
+    /// Fill the rectangle `width` by `height` from `x`, `y` with `color`.
+    fn fill(&mut self, x: u32, y: u32, width: u32, height: u32, color: Rgb) {
+        for y in y..(y + height).min(self.height) {
+            for x in x..(x + width).min(self.width) {
+                self.pixels[(y * self.width + x) as usize] = color;
+            }
+        }
+    }
+
+    /// Cover the pixel at `x`, `y` with `color` by `coverage`, from 0 to 1.
+    fn blend(&mut self, x: i32, y: i32, color: Rgb, coverage: f32) {
+        if x < 0 || y < 0 || x as u32 >= self.width || y as u32 >= self.height {
+            return;
+        }
+        let pixel = &mut self.pixels[(y as u32 * self.width + x as u32) as usize];
+        *pixel = mix(*pixel, color, coverage.clamp(0.0, 1.0));
+    }
+}
+
+/// Draw the `contents` of a pane of `cols` by `rows`, as the server sends them,
+/// and write the picture to `path`.
+pub fn save(path: &Path, cols: u16, rows: u16, contents: &[u8]) -> Result<()> {
+    let mut grid = Grid::<io::Sink>::new(cols, rows);
+    let mut processor = Processor::new();
+    for byte in contents {
+        processor.advance(&mut grid, *byte, &mut io::sink());
+    }
+    let image = render(&grid, cols, rows)?;
+    write_png(&image, path).with_context(|| path.display().to_string())
+}
+
+/// Draw the `cols` by `rows` cells of `grid`.
+pub fn render<W: io::Write>(grid: &Grid<W>, cols: u16, rows: u16) -> Result<Image> {
+    let font = FontRef::try_from_slice(epaint_default_fonts::HACK_REGULAR)
+        .map_err(|_| anyhow!("the bundled font is broken"))?;
+    let scale = PxScale::from(FONT_SIZE);
+    let scaled = font.as_scaled(scale);
+    let cell_width = scaled.h_advance(font.glyph_id('M')).ceil() as u32;
+    let cell_height = (scaled.ascent() - scaled.descent()).ceil() as u32;
+    let ascent = scaled.ascent();
+    let palette = grid.palette();
+    let background = rgb(Color::Named(NamedColor::Background), palette);
+    let mut image = Image::new(
+        u32::from(cols) * cell_width,
+        u32::from(rows) * cell_height,
+        background,
+    );
+    for row in 0..rows {
+        for col in 0..cols {
+            let cell = match grid.cell(col, row) {
+                Some(cell) => cell,
+                None => continue,
+            };
+            let flags = cell.flags();
+            let (mut fg, mut bg) = (rgb(cell.fg(), palette), rgb(cell.bg(), palette));
+            if flags.contains(Flags::INVERSE) {
+                (fg, bg) = (bg, fg);
+            }
+            if flags.contains(Flags::DIM) {
+                fg = mix(fg, bg, 0.5);
+            }
+            let x = u32::from(col) * cell_width;
+            let y = u32::from(row) * cell_height;
+            image.fill(x, y, cell_width, cell_height, bg);
+            if flags.contains(Flags::HIDDEN) {
+                continue;
+            }
+            let baseline = y as f32 + ascent;
+            let glyph = font
+                .glyph_id(cell.c())
+                .with_scale_and_position(scale, point(x as f32, baseline));
+            if let Some(outline) = font.outline_glyph(glyph) {
+                let bounds = outline.px_bounds();
+                // Bold is drawn twice, a pixel apart.
+                let strikes = if flags.contains(Flags::BOLD) { 2 } else { 1 };
+                outline.draw(|gx, gy, coverage| {
+                    let px = bounds.min.x + gx as f32;
+                    let py = bounds.min.y + gy as f32;
+                    let slant = match flags.contains(Flags::ITALIC) {
+                        true => (baseline - py) * ITALIC_SLANT,
+                        false => 0.0,
+                    };
+                    for strike in 0..strikes {
+                        let px = (px + slant).round() as i32 + strike;
+                        image.blend(px, py as i32, fg, coverage);
+                    }
+                });
+            }
+            let line = |offset: f32| (baseline + offset).round().max(0.0) as u32;
+            let underline = cell.underline_color().map_or(fg, |c| rgb(c, palette));
+            if flags.contains(Flags::UNDERLINE) {
+                image.fill(x, line(2.0), cell_width, 1, underline);
+            }
+            if flags.contains(Flags::DOUBLE_UNDERLINE) {
+                image.fill(x, line(1.0), cell_width, 1, underline);
+                image.fill(x, line(3.0), cell_width, 1, underline);
+            }
+            if flags.contains(Flags::STRIKETHROUGH) {
+                image.fill(x, line(-ascent / 3.0), cell_width, 1, fg);
+            }
+        }
+    }
+    Ok(image)
+}
+
+/// Write `image` to `path` as a PNG file.
+pub fn write_png(image: &Image, path: &Path) -> Result<()> {
+    let file = BufWriter::new(File::create(path)?);
+    let mut encoder = png::Encoder::new(file, image.width, image.height);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    let data: Vec<u8> = image
+        .pixels
+        .iter()
+        .flat_map(|pixel| [pixel.r, pixel.g, pixel.b])
+        .collect();
+    writer.write_image_data(&data)?;
+    writer.finish()?;
+    Ok(())
+}
+
+/// The colour `color` stands for in `palette`.
+fn rgb(color: Color, palette: &Palette) -> Rgb {
+    let index = match color {
+        Color::Spec(rgb) => return rgb,
+        Color::Indexed(index) => usize::from(index),
+        Color::Named(name) => name as usize,
+    };
+    // The dim and bright foregrounds are not in the palette.
+    palette
+        .get(index)
+        .or_else(|| palette.get(NamedColor::Foreground as usize))
+        .unwrap()
+}
+
+/// `from` moved towards `to` by `amount`, from 0 to 1.
+fn mix(from: Rgb, to: Rgb, amount: f32) -> Rgb {
+    let channel = |a: u8, b: u8| {
+        (f32::from(a) + (f32::from(b) - f32::from(a)) * amount).round() as u8
+    };
+    Rgb {
+        r: channel(from.r, to.r),
+        g: channel(from.g, to.g),
+        b: channel(from.b, to.b),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grid(contents: &[u8]) -> Grid<io::Sink> {
+        let mut grid = Grid::new(4, 2);
+        let mut processor = Processor::new();
+        for byte in contents {
+            processor.advance(&mut grid, *byte, &mut io::sink());
+        }
+        grid
+    }
+
+    /// The colours of the pixels of the cell at `col`, `row`, without repeats.
+    fn colors(image: &Image, col: u32, row: u32) -> Vec<Rgb> {
+        let (width, height) = (image.width / 4, image.height / 2);
+        let mut colors = Vec::new();
+        for y in row * height..(row + 1) * height {
+            for x in col * width..(col + 1) * width {
+                let pixel = image.pixels[(y * image.width + x) as usize];
+                if !colors.contains(&pixel) {
+                    colors.push(pixel);
+                }
+            }
+        }
+        colors
+    }
+
+    #[test]
+    fn render_cells() {
+        let red = Rgb {
+            r: 0xcd,
+            g: 0,
+            b: 0,
+        };
+        let blue = Rgb {
+            r: 0,
+            g: 0,
+            b: 0xee,
+        };
+        let black = Rgb { r: 0, g: 0, b: 0 };
+        let grid = grid(b"\x1b[31;44mM\x1b[0m \x1b[7m \x1b[0m\x1b[8mM");
+        let image = render(&grid, 4, 2).unwrap();
+        assert_eq!(image.width % 4, 0);
+        assert!(image.height >= 32);
+
+        // The letter is drawn in red on blue, smoothed at its edges.
+        let first = colors(&image, 0, 0);
+        assert!(first.contains(&red) && first.contains(&blue), "{:?}", first);
+        assert!(first.len() > 2);
+        assert_eq!(colors(&image, 1, 0), [black]);
+        let inverse = Rgb {
+            r: 0xe5,
+            g: 0xe5,
+            b: 0xe5,
+        };
+        assert_eq!(colors(&image, 2, 0), [inverse]);
+        // Hidden text is not drawn.
+        assert_eq!(colors(&image, 3, 0), [black]);
+    }
+
+    #[test]
+    fn save_png() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("pane.png");
+        save(&path, 4, 2, b"\x1b[1;1H\x1b[0mhi").unwrap();
+        let decoder = png::Decoder::new(File::open(&path).unwrap());
+        let reader = decoder.read_info().unwrap();
+        let info = reader.info();
+        assert_eq!(info.color_type, png::ColorType::Rgb);
+        assert!(info.width >= 4 * 8);
+        assert!(save(&dir.path().join("missing/pane.png"), 4, 2, b"").is_err());
+    }
+}
@@ -0,0 +1,890 @@
+//! The server's end of the socket clients attach to.
+//!
+//! The server runs sessions without a terminal of its own. The clients attached
+//! to one stand in for it: their input events and sizes come over the socket,
+//! and the output goes back to each. Detaching leaves the session running, to
+//! be attached to again with `sm attach`.
+
+use std::{
+    collections::BTreeSet,
+    env, fmt, fs,
+    io::{self, Write},
+    net::Shutdown,
+    os::unix::{
+        fs::DirBuilderExt,
+        net::{UnixListener, UnixStream},
+    },
+    path::PathBuf,
+    str::FromStr,
+    sync::{Arc, Mutex},
+    thread,
+};
+
+use futures::channel::mpsc::{self, Receiver, Sender};
+use log::{debug, warn};
+use nix::{pty::Winsize, unistd::Uid};
+
+use session_manager::export::SessionExport;
+
+use crate::{
+    backend::Event,
+    protocol::{
+        self, BufferInfo, ClientMessage, PaneInfo, ServerMessage, SessionInfo,
+        WindowInfo,
+    },
+};
+
+/// The socket of the server: `$XDG_RUNTIME_DIR/session-manager/default`, or
+/// under `/tmp/session-manager-UID` if that is unset, named after the profile
+/// rather than `default` for one.
+///
+/// The directory is created, readable only by the user, if it is missing.
+pub fn socket_path(profile: Option<&str>) -> io::Result<PathBuf> {
+    let dir = match env::var_os("XDG_RUNTIME_DIR").filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir).join("session-manager"),
+        None => env::temp_dir().join(format!("session-manager-{}", Uid::current())),
+    };
+    match fs::DirBuilder::new().mode(0o700).create(&dir) {
+        Err(e) if e.kind() != io::ErrorKind::AlreadyExists => return Err(e),
+        _ => (),
+    }
+    Ok(dir.join(profile.unwrap_or("default")))
+}
+
+/// Input events from the clients attached, with the id of the client and the
+/// bytes that produced them.
+pub type Input = Receiver<(u64, Event, Vec<u8>)>;
+
+/// How the size windows are drawn at is chosen from the sizes of the clients'
+/// terminals.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum WindowSize {
+    /// The smallest columns and rows of any client, so all see everything.
+    #[default]
+    Smallest,
+    /// The largest columns and rows of any client.
+    Largest,
+    /// The size of the client to attach or resize last.
+    Latest,
+}
+
+impl FromStr for WindowSize {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<WindowSize, String> {
+        match s {
+            "smallest" => Ok(WindowSize::Smallest),
+            "largest" => Ok(WindowSize::Largest),
+            "latest" => Ok(WindowSize::Latest),
+            _ => Err(format!("expected smallest, largest or latest: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for WindowSize {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            WindowSize::Smallest => "smallest",
+            WindowSize::Largest => "largest",
+            WindowSize::Latest => "latest",
+        })
+    }
+}
+
+/// What `sm info` says of a client attached.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClientInfo {
+    pub id: u64,
+    /// The size of its terminal, once it has said.
+    pub size: Option<Winsize>,
+    /// The bytes of output sent to it since it attached.
+    pub bytes_written: u64,
+    /// Whether its terminal draws East Asian Ambiguous characters two columns
+    /// wide.
+    pub ambiguous_wide: bool,
+}
+
+/// What a client asks for once it has said hello.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Request {
+    /// Create a session, named `name` or a number, and attach to it. It shares
+    /// the windows of the session named `group`, and any grouped with it, if
+    /// given. `env` is the client's environment.
+    NewSession {
+        name: Option<String>,
+        group: Option<String>,
+        env: Vec<(String, String)>,
+    },
+    /// Attach to the named session, or the one attached to last, detaching the
+    /// other clients if `detach`. `env` is the client's environment.
+    Attach {
+        name: Option<String>,
+        detach: bool,
+        env: Vec<(String, String)>,
+    },
+    ListSessions,
+    /// List the windows of the named session, or of the one attached to last.
+    ListWindows(Option<String>),
+    /// Describe the sessions and the client attached, for `sm info`.
+    Info,
+    /// What the active pane of the named session shows, or of the one attached
+    /// to last, for `sm screenshot`.
+    CapturePane(Option<String>),
+    /// A record of the named session, or of the one attached to last, for
+    /// `sm export-session`.
+    ExportSession(Option<String>),
+    /// Expand `format` for the named session, or the one attached to last, for
+    /// `sm display-message`.
+    DisplayMessage {
+        name: Option<String>,
+        format: String,
+        print: bool,
+    },
+    /// Call the named session, or the one attached to last, `new_name`, for
+    /// `sm rename-session`.
+    RenameSession {
+        name: Option<String>,
+        new_name: String,
+    },
+    /// End the named session, or the one attached to last, for
+    /// `sm kill-session`.
+    KillSession(Option<String>),
+    /// Move the windows of the session named `source` into the one named
+    /// `target`, or the one attached to last, ending `source`, for
+    /// `sm merge-session`.
+    MergeSession {
+        source: String,
+        target: Option<String>,
+    },
+    /// List the panes of the named session, or of the one attached to last.
+    ListPanes(Option<String>),
+    /// List the clients attached.
+    ListClients,
+    /// List the paste buffers of the named session, or of the one attached to
+    /// last.
+    ListBuffers(Option<String>),
+}
+
+/// A client which has made a request, waiting for the answer.
+#[derive(Debug)]
+pub struct Pending {
+    stream: UnixStream,
+}
+
+impl Pending {
+    /// Tell the client its request cannot be done because of `message`, and
+    /// hang up on it.
+    pub fn refuse(mut self, message: &str) {
+        let message = ServerMessage::Error(message.to_string());
+        protocol::send(&mut self.stream, &message).ok();
+    }
+
+    /// Answer `ListSessions` with `sessions`, and hang up on the client.
+    pub fn list_sessions(mut self, sessions: Vec<SessionInfo>) {
+        protocol::send(&mut self.stream, &ServerMessage::Sessions(sessions)).ok();
+    }
+
+    /// Answer `ListWindows` with `windows`, and hang up on the client.
+    pub fn list_windows(mut self, windows: Vec<WindowInfo>) {
+        protocol::send(&mut self.stream, &ServerMessage::Windows(windows)).ok();
+    }
+
+    /// Answer `ListPanes` with `panes`, and hang up on the client.
+    pub fn list_panes(mut self, panes: Vec<PaneInfo>) {
+        protocol::send(&mut self.stream, &ServerMessage::Panes(panes)).ok();
+    }
+
+    /// Answer `ListClients` with `clients`, and hang up on the client.
+    pub fn list_clients(mut self, clients: Vec<protocol::ClientInfo>) {
+        protocol::send(&mut self.stream, &ServerMessage::Clients(clients)).ok();
+    }
+
+    /// Answer `ListBuffers` with `buffers`, and hang up on the client.
+    pub fn list_buffers(mut self, buffers: Vec<BufferInfo>) {
+        protocol::send(&mut self.stream, &ServerMessage::Buffers(buffers)).ok();
+    }
+
+    /// Answer `Info`, `DisplayMessage`, `RenameSession`, `KillSession` or
+    /// `MergeSession` with `text`, and hang up on the client.
+    pub fn info(mut self, text: String) {
+        protocol::send(&mut self.stream, &ServerMessage::Info(text)).ok();
+    }
+
+    /// Answer `CapturePane` with the `contents` of a pane of `cols` by `rows`,
+    /// and hang up on the client.
+    pub fn pane(mut self, cols: u16, rows: u16, contents: Vec<u8>) {
+        let message = ServerMessage::Pane {
+            cols,
+            rows,
+            contents,
+        };
+        protocol::send(&mut self.stream, &message).ok();
+    }
+
+    /// Answer `ExportSession` with `export`, and hang up on the client.
+    pub fn export(mut self, export: SessionExport) {
+        let message = ServerMessage::Export(Box::new(export));
+        protocol::send(&mut self.stream, &message).ok();
+    }
+}
+
+/// What the server hears from clients.
+pub struct Incoming {
+    pub input: Input,
+    /// Told when a client attaches or its terminal is resized.
+    pub resized: Receiver<()>,
+    /// The requests of clients which have connected, to be answered.
+    pub requests: Receiver<(Request, Pending)>,
+}
+
+/// The clients attached, in the order they attached.
+#[derive(Debug)]
+struct Shared {
+    clients: Vec<Client>,
+    /// The size windows are drawn at: chosen from the clients' sizes by
+    /// `policy`, or the last chosen while none is attached.
+    size: Winsize,
+    policy: WindowSize,
+    /// Counts clients, to tell them apart.
+    attached: u64,
+    /// Counts attaches and resizes, to find the latest.
+    changes: u64,
+    /// The clients showing another window than the rest, skipped by the
+    /// output of `Server::output`.
+    elsewhere: BTreeSet<u64>,
+}
+
+#[derive(Debug)]
+struct Client {
+    id: u64,
+    stream: UnixStream,
+    size: Option<Winsize>,
+    bytes_written: u64,
+    ambiguous_wide: bool,
+    /// When the client last attached or resized, by `Shared::changes`.
+    changed: u64,
+}
+
+impl Shared {
+    /// Send `message` to every client, forgetting those which have gone.
+    fn send(&mut self, message: &ServerMessage) {
+        self.send_to(message, |_| true);
+    }
+
+    /// Send `message` to the clients `to` is true for, forgetting those which
+    /// have gone, and counting the bytes of output sent to the rest.
+    fn send_to<F: Fn(u64) -> bool>(&mut self, message: &ServerMessage, to: F) {
+        let len = match message {
+            ServerMessage::Output(frame) => frame.len() as u64,
+            _ => 0,
+        };
+        self.clients.retain_mut(|client| {
+            if !to(client.id) {
+                return true;
+            }
+            match protocol::send(&mut client.stream, message) {
+                Ok(()) => {
+                    client.bytes_written += len;
+                    true
+                }
+                Err(e) => {
+                    debug!("lost client {}: {}", client.id, e);
+                    false
+                }
+            }
+        });
+    }
+
+    /// Tell the clients to exit with `message`, and hang up on them.
+    fn detach(&mut self, message: &str) {
+        self.send(&ServerMessage::Exit(message.to_string()));
+        for client in self.clients.drain(..) {
+            client.stream.shutdown(Shutdown::Both).ok();
+        }
+    }
+
+    /// Tell client `id` to exit with `message`, and hang up on it.
+    fn detach_client(&mut self, id: u64, message: &str) {
+        if let Some(position) = self.clients.iter().position(|client| client.id == id) {
+            let mut client = self.clients.remove(position);
+            let message = ServerMessage::Exit(message.to_string());
+            protocol::send(&mut client.stream, &message).ok();
+            client.stream.shutdown(Shutdown::Both).ok();
+        }
+    }
+
+    /// Choose the size windows are drawn at again; whether it changed.
+    fn negotiate(&mut self) -> bool {
+        let sized = self
+            .clients
+            .iter()
+            .filter_map(|client| Some((client.size?, client.changed)));
+        let size = match self.policy {
+            WindowSize::Smallest => {
+                sized.map(|(size, _)| size).reduce(|a, b| Winsize {
+                    ws_row: a.ws_row.min(b.ws_row),
+                    ws_col: a.ws_col.min(b.ws_col),
+                    ..a
+                })
+            }
+            WindowSize::Largest => sized.map(|(size, _)| size).reduce(|a, b| Winsize {
+                ws_row: a.ws_row.max(b.ws_row),
+                ws_col: a.ws_col.max(b.ws_col),
+                ..a
+            }),
+            WindowSize::Latest => sized
+                .max_by_key(|(_, changed)| *changed)
+                .map(|(size, _)| size),
+        };
+        match size {
+            Some(size) if size != self.size => {
+                self.size = size;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Clients connecting to the server, any number of which are attached at
+/// once.
+#[derive(Debug, Clone)]
+pub struct Server {
+    shared: Arc<Mutex<Shared>>,
+    /// Told when a client attaches or its terminal is resized.
+    resized: Sender<()>,
+    requests: Sender<(Request, Pending)>,
+    input: Sender<(u64, Event, Vec<u8>)>,
+}
+
+impl Server {
+    /// A server with no client attached, assuming a terminal of size `size`
+    /// until one is, and what it hears from clients.
+    pub fn new(size: Winsize) -> (Server, Incoming) {
+        let (input, input_recv) = mpsc::channel(0x1000);
+        let (resized, resized_recv) = mpsc::channel(0x100);
+        let (requests, requests_recv) = mpsc::channel(0x100);
+        let shared = Shared {
+            clients: Vec::new(),
+            size,
+            policy: WindowSize::default(),
+            attached: 0,
+            changes: 0,
+            elsewhere: BTreeSet::new(),
+        };
+        let server = Server {
+            shared: Arc::new(Mutex::new(shared)),
+            resized,
+            requests,
+            input,
+        };
+        let incoming = Incoming {
+            input: input_recv,
+            resized: resized_recv,
+            requests: requests_recv,
+        };
+        (server, incoming)
+    }
+
+    /// Accept the clients connecting to `listener`, in the background.
+    pub fn listen(&self, listener: UnixListener) {
+        let server = self.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                match stream {
+                    Ok(stream) => server.accept(stream),
+                    Err(e) => warn!("failed to accept a client: {}", e),
+                }
+            }
+        });
+    }
+
+    /// Pass on the request of the client at the other end of `stream`, once it
+    /// has said hello.
+    pub fn accept(&self, stream: UnixStream) {
+        let mut server = self.clone();
+        thread::spawn(move || {
+            let mut stream = stream;
+            match protocol::receive(&mut stream) {
+                Ok(Some(ClientMessage::Hello(protocol::VERSION))) => (),
+                Ok(Some(ClientMessage::Hello(version))) => {
+                    let message = format!(
+                        "the server speaks version {} of the protocol, not {}; \
+                         attach with the sm it was started with",
+                        protocol::VERSION,
+                        version
+                    );
+                    protocol::send(&mut stream, &ServerMessage::Exit(message)).ok();
+                    return;
+                }
+                _ => return warn!("client did not say hello"),
+            }
+            let request = match protocol::receive(&mut stream) {
+                Ok(Some(ClientMessage::NewSession { name, group, env })) => {
+                    Request::NewSession { name, group, env }
+                }
+                Ok(Some(ClientMessage::Attach { name, detach, env })) => {
+                    Request::Attach { name, detach, env }
+                }
+                Ok(Some(ClientMessage::ListSessions)) => Request::ListSessions,
+                Ok(Some(ClientMessage::ListWindows(name))) => {
+                    Request::ListWindows(name)
+                }
+                Ok(Some(ClientMessage::Info)) => Request::Info,
+                Ok(Some(ClientMessage::CapturePane(name))) => {
+                    Request::CapturePane(name)
+                }
+                Ok(Some(ClientMessage::ExportSession(name))) => {
+                    Request::ExportSession(name)
+                }
+                Ok(Some(ClientMessage::DisplayMessage {
+                    name,
+                    format,
+                    print,
+                })) => Request::DisplayMessage {
+                    name,
+                    format,
+                    print,
+                },
+                Ok(Some(ClientMessage::RenameSession { name, new_name })) => {
+                    Request::RenameSession { name, new_name }
+                }
+                Ok(Some(ClientMessage::KillSession(name))) => {
+                    Request::KillSession(name)
+                }
+                Ok(Some(ClientMessage::MergeSession { source, target })) => {
+                    Request::MergeSession { source, target }
+                }
+                Ok(Some(ClientMessage::ListPanes(name))) => Request::ListPanes(name),
+                Ok(Some(ClientMessage::ListClients)) => Request::ListClients,
+                Ok(Some(ClientMessage::ListBuffers(name))) => {
+                    Request::ListBuffers(name)
+                }
+                _ => return warn!("client made no request"),
+            };
+            server.requests.try_send((request, Pending { stream })).ok();
+        });
+    }
+
+    /// Choose the size windows are drawn at from the clients' sizes by
+    /// `policy`.
+    pub fn set_window_size(&self, policy: WindowSize) {
+        let mut shared = self.shared.lock().unwrap();
+        shared.policy = policy;
+        if shared.negotiate() {
+            self.resized.clone().try_send(()).ok();
+        }
+    }
+
+    /// Attach the client waiting in `pending`, alongside any attached already,
+    /// returning the id it is known by once it is.
+    pub fn attach(&self, pending: Pending) -> u64 {
+        let id = {
+            let mut shared = self.shared.lock().unwrap();
+            shared.attached += 1;
+            shared.attached
+        };
+        let mut server = self.clone();
+        thread::spawn(move || {
+            let mut reader = pending.stream;
+            let mut stream = match reader.try_clone() {
+                Ok(stream) => stream,
+                Err(e) => return warn!("failed to attach a client: {}", e),
+            };
+            {
+                let mut shared = server.shared.lock().unwrap();
+                // Before any output, which is sent with the lock held.
+                if protocol::send(&mut stream, &ServerMessage::Attached).is_err() {
+                    return;
+                }
+                shared.changes += 1;
+                let changed = shared.changes;
+                shared.clients.push(Client {
+                    id,
+                    stream,
+                    size: None,
+                    bytes_written: 0,
+                    ambiguous_wide: false,
+                    changed,
+                });
+            }
+            debug!("attached client {}", id);
+            loop {
+                match protocol::receive(&mut reader) {
+                    Ok(Some(ClientMessage::Resize(size))) => {
+                        let mut shared = server.shared.lock().unwrap();
+                        shared.changes += 1;
+                        let changed = shared.changes;
+                        let client =
+                            shared.clients.iter_mut().find(|client| client.id == id);
+                        if let Some(client) = client {
+                            client.size = Some(size);
+                            client.changed = changed;
+                            shared.negotiate();
+                            // The new client is drawn in full, whatever the size.
+                            server.resized.try_send(()).ok();
+                        }
+                    }
+                    Ok(Some(ClientMessage::AmbiguousWidth(wide))) => {
+                        // The size, sent next, has the client drawn again.
+                        let mut shared = server.shared.lock().unwrap();
+                        let client =
+                            shared.clients.iter_mut().find(|client| client.id == id);
+                        if let Some(client) = client {
+                            client.ambiguous_wide = wide;
+                        }
+                    }
+                    Ok(Some(ClientMessage::Input(event, data))) => {
+                        if server.is_attached(id) {
+                            server.input.try_send((id, event, data)).ok();
+                        }
+                    }
+                    Ok(Some(message)) => {
+                        warn!("unexpected {:?} from client {}", message, id);
+                    }
+                    Ok(None) => break,
+                    Err(e) => {
+                        warn!("bad message from client {}: {}", id, e);
+                        break;
+                    }
+                }
+            }
+            let mut shared = server.shared.lock().unwrap();
+            if let Some(position) = shared.clients.iter().position(|c| c.id == id) {
+                debug!("client {} went away", id);
+                shared.clients.remove(position);
+            }
+            // The others may have room for more.
+            if shared.negotiate() {
+                server.resized.try_send(()).ok();
+            }
+        });
+        id
+    }
+
+    fn is_attached(&self, id: u64) -> bool {
+        let shared = self.shared.lock().unwrap();
+        shared.clients.iter().any(|client| client.id == id)
+    }
+
+    /// The clients attached, in the order they attached.
+    pub fn clients(&self) -> Vec<ClientInfo> {
+        let shared = self.shared.lock().unwrap();
+        shared
+            .clients
+            .iter()
+            .map(|client| ClientInfo {
+                id: client.id,
+                size: client.size,
+                bytes_written: client.bytes_written,
+                ambiguous_wide: client.ambiguous_wide,
+            })
+            .collect()
+    }
+
+    /// The size windows are drawn at, chosen from the sizes of the clients
+    /// attached, or the last chosen.
+    pub fn size(&self) -> Winsize {
+        self.shared.lock().unwrap().size
+    }
+
+    /// How many bytes of output have been sent to the clients attached, since
+    /// each attached.
+    pub fn bytes_written(&self) -> u64 {
+        let shared = self.shared.lock().unwrap();
+        shared
+            .clients
+            .iter()
+            .map(|client| client.bytes_written)
+            .sum()
+    }
+
+    /// Detach every client, each showing `message` as it exits.
+    pub fn detach(&self, message: &str) {
+        self.shared.lock().unwrap().detach(message);
+    }
+
+    /// Detach every client but `id`, each showing `message` as it exits, for
+    /// the windows to take the size of `id`'s terminal.
+    pub fn detach_others(&self, id: u64, message: &str) {
+        let mut shared = self.shared.lock().unwrap();
+        let clients = shared.clients.iter().map(|client| client.id);
+        let others: Vec<_> = clients.filter(|other| *other != id).collect();
+        for other in others {
+            shared.detach_client(other, message);
+        }
+        if shared.negotiate() {
+            self.resized.clone().try_send(()).ok();
+        }
+    }
+
+    /// Detach client `id`, which shows `message` as it exits, if it is still
+    /// attached.
+    pub fn detach_client(&self, id: u64, message: &str) {
+        let mut shared = self.shared.lock().unwrap();
+        shared.detach_client(id, message);
+        if shared.negotiate() {
+            self.resized.clone().try_send(()).ok();
+        }
+    }
+
+    /// Leave `clients`, which show another window than the rest, out of the
+    /// output of `output`, to be drawn to with `output_to` instead.
+    pub fn set_elsewhere(&self, clients: BTreeSet<u64>) {
+        self.shared.lock().unwrap().elsewhere = clients;
+    }
+
+    /// A writer sending output to every client attached but those set with
+    /// `set_elsewhere`, a frame for each flush; the output is dropped while
+    /// none is.
+    pub fn output(&self) -> ClientOutput {
+        ClientOutput {
+            shared: self.shared.clone(),
+            frame: Vec::new(),
+            only: None,
+        }
+    }
+
+    /// A writer like `output`'s sending only to `clients`.
+    pub fn output_to(&self, clients: BTreeSet<u64>) -> ClientOutput {
+        ClientOutput {
+            shared: self.shared.clone(),
+            frame: Vec::new(),
+            only: Some(clients),
+        }
+    }
+}
+
+/// Output for the clients attached; see `Server::output`.
+pub struct ClientOutput {
+    shared: Arc<Mutex<Shared>>,
+    frame: Vec<u8>,
+    /// The clients sent to, if not all those not elsewhere.
+    only: Option<BTreeSet<u64>>,
+}
+
+impl Write for ClientOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.frame.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if !self.frame.is_empty() {
+            let message = ServerMessage::Output(std::mem::take(&mut self.frame));
+            let mut shared = self.shared.lock().unwrap();
+            match &self.only {
+                Some(only) => shared.send_to(&message, |id| only.contains(&id)),
+                None => {
+                    let elsewhere = shared.elsewhere.clone();
+                    shared.send_to(&message, |id| !elsewhere.contains(&id));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use futures::{executor, StreamExt};
+
+    use crate::backend::Key;
+
+    const SIZE: Winsize = Winsize {
+        ws_row: 24,
+        ws_col: 80,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+
+    fn receive(client: &mut UnixStream) -> Option<ServerMessage> {
+        protocol::receive(client).unwrap()
+    }
+
+    /// Connect a client which says hello and makes `request`, returning it
+    /// with the request as the server hears it.
+    fn connect(
+        server: &Server,
+        incoming: &mut Incoming,
+        request: ClientMessage,
+    ) -> (UnixStream, Request, Pending) {
+        let (mut client, stream) = UnixStream::pair().unwrap();
+        server.accept(stream);
+        protocol::send(&mut client, &ClientMessage::Hello(protocol::VERSION)).unwrap();
+        protocol::send(&mut client, &request).unwrap();
+        let (request, pending) = executor::block_on(incoming.requests.next()).unwrap();
+        (client, request, pending)
+    }
+
+    #[test]
+    fn requests() {
+        let (server, mut incoming) = Server::new(SIZE);
+        let (mut client, request, pending) =
+            connect(&server, &mut incoming, ClientMessage::ListSessions);
+        assert_eq!(request, Request::ListSessions);
+        let sessions = vec![SessionInfo {
+            name: "0".to_string(),
+            windows: 2,
+            attached: false,
+            group: None,
+        }];
+        pending.list_sessions(sessions.clone());
+        assert_eq!(
+            receive(&mut client),
+            Some(ServerMessage::Sessions(sessions))
+        );
+        assert_eq!(receive(&mut client), None);
+
+        let env = vec![("DISPLAY".to_string(), ":1".to_string())];
+        let new = ClientMessage::NewSession {
+            name: Some("work".to_string()),
+            group: Some("0".to_string()),
+            env: env.clone(),
+        };
+        let (mut client, request, pending) = connect(&server, &mut incoming, new);
+        assert_eq!(
+            request,
+            Request::NewSession {
+                name: Some("work".to_string()),
+                group: Some("0".to_string()),
+                env,
+            }
+        );
+        pending.refuse("duplicate session: work");
+        assert_eq!(
+            receive(&mut client),
+            Some(ServerMessage::Error("duplicate session: work".to_string()))
+        );
+        assert_eq!(receive(&mut client), None);
+        assert!(server.clients().is_empty());
+    }
+
+    #[test]
+    fn attach_and_detach() {
+        let (server, mut incoming) = Server::new(SIZE);
+        let mut output = server.output();
+        write!(output, "dropped").unwrap();
+        output.flush().unwrap();
+
+        let attach = ClientMessage::Attach {
+            name: None,
+            detach: false,
+            env: Vec::new(),
+        };
+        let (mut client, _, pending) = connect(&server, &mut incoming, attach.clone());
+        let id = server.attach(pending);
+        assert_eq!(receive(&mut client), Some(ServerMessage::Attached));
+        assert_eq!(server.clients()[0].id, id);
+        assert!(!server.clients()[0].ambiguous_wide);
+        protocol::send(&mut client, &ClientMessage::AmbiguousWidth(true)).unwrap();
+        let size = Winsize { ws_row: 10, ..SIZE };
+        protocol::send(&mut client, &ClientMessage::Resize(size)).unwrap();
+        executor::block_on(incoming.resized.next()).unwrap();
+        assert_eq!(server.size(), size);
+        assert!(server.clients()[0].ambiguous_wide);
+        let key = ClientMessage::Input(Event::Key(Key::Char('x')), b"x".to_vec());
+        protocol::send(&mut client, &key).unwrap();
+        let id = server.clients()[0].id;
+        assert_eq!(
+            executor::block_on(incoming.input.next()),
+            Some((id, Event::Key(Key::Char('x')), b"x".to_vec()))
+        );
+
+        write!(output, "hello, ").unwrap();
+        write!(output, "client").unwrap();
+        output.flush().unwrap();
+        assert_eq!(
+            receive(&mut client),
+            Some(ServerMessage::Output(b"hello, client".to_vec()))
+        );
+        assert_eq!(server.bytes_written(), 13);
+
+        // A client speaking another version is turned away.
+        let (mut stranger, stream) = UnixStream::pair().unwrap();
+        server.accept(stream);
+        let hello = ClientMessage::Hello(protocol::VERSION + 1);
+        protocol::send(&mut stranger, &hello).unwrap();
+        assert!(matches!(
+            receive(&mut stranger),
+            Some(ServerMessage::Exit(_))
+        ));
+        assert_eq!(receive(&mut stranger), None);
+
+        // Another client joins, the windows fitting both.
+        let (mut other, _, pending) = connect(&server, &mut incoming, attach);
+        let other_id = server.attach(pending);
+        assert_eq!(receive(&mut other), Some(ServerMessage::Attached));
+        let other_size = Winsize {
+            ws_row: 20,
+            ws_col: 60,
+            ..SIZE
+        };
+        protocol::send(&mut other, &ClientMessage::Resize(other_size)).unwrap();
+        executor::block_on(incoming.resized.next()).unwrap();
+        let smallest = Winsize {
+            ws_row: 10,
+            ws_col: 60,
+            ..SIZE
+        };
+        assert_eq!(server.size(), smallest);
+        write!(output, "both").unwrap();
+        output.flush().unwrap();
+        for client in [&mut client, &mut other] {
+            assert_eq!(
+                receive(client),
+                Some(ServerMessage::Output(b"both".to_vec()))
+            );
+        }
+        let clients = server.clients();
+        assert_eq!(clients.len(), 2);
+        assert_eq!(clients[0].bytes_written, 17);
+        assert_eq!(clients[1].size, Some(other_size));
+        assert_eq!(clients[1].bytes_written, 4);
+
+        // A client looking at another window is drawn to on its own.
+        server.set_elsewhere([id].into());
+        let mut elsewhere = server.output_to([id].into());
+        write!(elsewhere, "mine").unwrap();
+        elsewhere.flush().unwrap();
+        write!(output, "theirs").unwrap();
+        output.flush().unwrap();
+        assert_eq!(
+            receive(&mut client),
+            Some(ServerMessage::Output(b"mine".to_vec()))
+        );
+        assert_eq!(
+            receive(&mut other),
+            Some(ServerMessage::Output(b"theirs".to_vec()))
+        );
+        assert_eq!(server.clients()[0].bytes_written, 21);
+        server.set_elsewhere(BTreeSet::new());
+
+        server.set_window_size(WindowSize::Largest);
+        executor::block_on(incoming.resized.next()).unwrap();
+        assert_eq!(server.size(), Winsize { ws_row: 20, ..SIZE });
+
+        // Detaching the others leaves one, at its own size.
+        server.detach_others(other_id, "detached");
+        assert_eq!(
+            receive(&mut client),
+            Some(ServerMessage::Exit("detached".to_string()))
+        );
+        assert_eq!(receive(&mut client), None);
+        executor::block_on(incoming.resized.next()).unwrap();
+        assert_eq!(server.size(), other_size);
+        assert_eq!(server.clients().len(), 1);
+
+        server.detach("no more windows");
+        assert_eq!(
+            receive(&mut other),
+            Some(ServerMessage::Exit("no more windows".to_string()))
+        );
+        assert_eq!(receive(&mut other), None);
+        assert!(server.clients().is_empty());
+        // The last size is kept while no client is attached.
+        assert_eq!(server.size(), other_size);
+    }
+}
@@ -0,0 +1,52 @@
+//! Labels over panes giving their sizes, shown while they are being resized.
+
+use std::io::{self, Write};
+
+use session_manager::layout::Rect;
+
+/// Label each pane in `rects` with its size, e.g. `83x24`, in the middle of
+/// the pane, leaving the cursor where it was.
+pub fn draw<T: Write>(output: &mut T, rects: &[(usize, Rect)]) -> io::Result<()> {
+    write!(output, "\x1b7")?;
+    for (_, rect) in rects {
+        if let Some((col, row, text)) = label(*rect) {
+            write!(output, "\x1b[{};{}H\x1b[0;7m{}", row + 1, col + 1, text)?;
+        }
+    }
+    write!(output, "\x1b[0m\x1b8")
+}
+
+/// Where the label of a pane at `rect` goes, and what it says; `None` if the
+/// pane is too small for it.
+fn label(rect: Rect) -> Option<(u16, u16, String)> {
+    let text = format!("{}x{}", rect.cols, rect.rows);
+    let width = text.len() as u16;
+    if width > rect.cols || rect.rows == 0 {
+        return None;
+    }
+    Some((
+        rect.col + (rect.cols - width) / 2,
+        rect.row + rect.rows / 2,
+        text,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn labels() {
+        let rect = Rect {
+            col: 41,
+            row: 0,
+            cols: 39,
+            rows: 23,
+        };
+        assert_eq!(label(rect), Some((58, 11, "39x23".to_string())));
+        assert_eq!(label(Rect { cols: 3, ..rect }), None);
+        let mut out = Vec::new();
+        draw(&mut out, &[(0, rect)]).unwrap();
+        assert_eq!(out, b"\x1b7\x1b[12;59H\x1b[0;7m39x23\x1b[0m\x1b8");
+    }
+}
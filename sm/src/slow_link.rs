@@ -0,0 +1,197 @@
+//! Simulation of a slow connection to the terminal, for development.
+
+use std::{
+    io::{self, Write},
+    sync::mpsc::{self, Sender},
+    thread,
+    time::{Duration, Instant},
+};
+
+use log::warn;
+
+/// The conditions to simulate.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LinkConditions {
+    /// Delay added to every frame.
+    pub latency: Duration,
+    /// Throughput in bytes per second, or `None` for unlimited.
+    pub bandwidth: Option<u64>,
+}
+
+impl LinkConditions {
+    /// Whether these conditions change anything.
+    pub fn is_simulated(&self) -> bool {
+        self.latency > Duration::from_secs(0) || self.bandwidth.is_some()
+    }
+
+    /// How long sending a frame of `len` bytes takes.
+    pub fn delay(&self, len: usize) -> Duration {
+        let transfer = match self.bandwidth {
+            Some(0) | None => Duration::from_secs(0),
+            Some(bandwidth) => {
+                Duration::from_micros(len as u64 * 1_000_000 / bandwidth)
+            }
+        };
+        self.latency + transfer
+    }
+}
+
+/// Where a `SlowLink` passes frames on.
+enum Link<W> {
+    /// Straight to the writer, nothing being simulated.
+    Direct(W),
+    /// To the thread holding them back, with when each was sent.
+    Delayed(Sender<(Instant, Vec<u8>)>),
+}
+
+/// A writer which holds back each frame as if it were sent over a slow link.
+///
+/// Frames are delimited by `flush`, which hands the frame to a thread passing
+/// it on once the simulated time is up, so the writer never blocks.
+pub struct SlowLink<W: Write> {
+    link: Link<W>,
+    frame: Vec<u8>,
+}
+
+impl<W: Write + Send + 'static> SlowLink<W> {
+    /// Write to `inner` under `conditions`.
+    pub fn new(inner: W, conditions: LinkConditions) -> SlowLink<W> {
+        let link = match conditions.is_simulated() {
+            true => Link::Delayed(spawn_link(inner, conditions)),
+            false => Link::Direct(inner),
+        };
+        SlowLink {
+            link,
+            frame: Vec::new(),
+        }
+    }
+}
+
+/// Start the thread passing the frames sent to `inner` under `conditions`.
+///
+/// Frames cross the link one after another, each arriving the latency after
+/// it is through.
+fn spawn_link<W: Write + Send + 'static>(
+    mut inner: W,
+    conditions: LinkConditions,
+) -> Sender<(Instant, Vec<u8>)> {
+    let (sender, frames) = mpsc::channel::<(Instant, Vec<u8>)>();
+    thread::spawn(move || {
+        let mut free = Instant::now();
+        for (sent, frame) in frames {
+            let transfer = conditions.delay(frame.len()) - conditions.latency;
+            free = free.max(sent) + transfer;
+            let due = free + conditions.latency;
+            let now = Instant::now();
+            if due > now {
+                thread::sleep(due - now);
+            }
+            if let Err(e) = inner.write_all(&frame).and_then(|()| inner.flush()) {
+                warn!("simulated link closed: {}", e);
+                return;
+            }
+        }
+    });
+    sender
+}
+
+impl<W: Write> Write for SlowLink<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match &mut self.link {
+            Link::Direct(inner) => inner.write(buf),
+            Link::Delayed(_) => {
+                self.frame.extend_from_slice(buf);
+                Ok(buf.len())
+            }
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.link {
+            Link::Direct(inner) => inner.flush(),
+            Link::Delayed(_) if self.frame.is_empty() => Ok(()),
+            Link::Delayed(sender) => {
+                let frame = std::mem::take(&mut self.frame);
+                sender.send((Instant::now(), frame)).map_err(|_| {
+                    io::Error::new(io::ErrorKind::BrokenPipe, "simulated link closed")
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::{Arc, Mutex};
+
+    /// A writer whose output can be looked at from another thread.
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Shared {
+        /// Wait up to a second for `len` bytes to arrive, returning them.
+        fn wait_for(&self, len: usize) -> Vec<u8> {
+            let start = Instant::now();
+            while self.0.lock().unwrap().len() < len
+                && start.elapsed() < Duration::from_secs(1)
+            {
+                thread::sleep(Duration::from_millis(1));
+            }
+            self.0.lock().unwrap().clone()
+        }
+    }
+
+    #[test]
+    fn delay() {
+        let conditions = LinkConditions {
+            latency: Duration::from_millis(5),
+            bandwidth: Some(1000),
+        };
+        assert!(conditions.is_simulated());
+        assert_eq!(conditions.delay(100), Duration::from_millis(105));
+        assert!(!LinkConditions::default().is_simulated());
+        assert_eq!(LinkConditions::default().delay(100), Duration::from_secs(0));
+    }
+
+    #[test]
+    fn slow_link() {
+        let conditions = LinkConditions {
+            latency: Duration::from_millis(20),
+            bandwidth: None,
+        };
+        let output = Shared::default();
+        let mut link = SlowLink::new(output.clone(), conditions);
+        let start = Instant::now();
+        link.write_all(b"one ").unwrap();
+        link.flush().unwrap();
+        link.write_all(b"two").unwrap();
+        assert!(start.elapsed() < conditions.latency, "writer blocked");
+        assert!(output.wait_for(0).is_empty(), "frame sent before its time");
+
+        link.flush().unwrap();
+        link.flush().unwrap();
+        assert!(start.elapsed() < conditions.latency, "flush blocked");
+        assert_eq!(output.wait_for(7), b"one two");
+        assert!(start.elapsed() >= conditions.latency);
+    }
+
+    #[test]
+    fn unsimulated() {
+        let output = Shared::default();
+        let mut link = SlowLink::new(output.clone(), LinkConditions::default());
+        link.write_all(b"now").unwrap();
+        assert_eq!(output.wait_for(0), b"now");
+    }
+}
@@ -0,0 +1,80 @@
+//! The file the server keeps its sessions in, for `sm restore` to start them
+//! again after the server has gone, e.g. with a reboot.
+
+use std::{
+    env, fs,
+    io::{self, Write},
+    os::unix::fs::OpenOptionsExt,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Context, Result};
+
+use session_manager::snapshot::{self, SessionSnapshot};
+
+/// The state file: `$XDG_STATE_HOME/session-manager/sessions`, or under
+/// `~/.local/state` if that is unset; `PROFILE.sessions` for a profile.
+pub fn default_path(profile: Option<&str>) -> Option<PathBuf> {
+    let state = env::var_os("XDG_STATE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| {
+            env::var_os("HOME").map(|home| Path::new(&home).join(".local/state"))
+        })?;
+    let file = match profile {
+        Some(profile) => format!("{}.sessions", profile),
+        None => "sessions".to_string(),
+    };
+    Some(state.join("session-manager").join(file))
+}
+
+/// Write `sessions` to `path`, readable only by the user, replacing what was
+/// there in one go so that a reader never sees half of it.
+pub fn save(path: &Path, sessions: &[SessionSnapshot]) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let partial = path.with_extension("partial");
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(&partial)?;
+    file.write_all(snapshot::save(sessions).as_bytes())?;
+    file.sync_all()?;
+    fs::rename(&partial, path)
+}
+
+/// Read the sessions saved in `path`.
+pub fn load(path: &Path) -> Result<Vec<SessionSnapshot>> {
+    let text = fs::read_to_string(path).with_context(|| path.display().to_string())?;
+    snapshot::load(&text).map_err(|e| anyhow!("{}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn save_and_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("session-manager").join("sessions");
+        let sessions = vec![SessionSnapshot {
+            name: "0".to_string(),
+            selected: None,
+            windows: Vec::new(),
+        }];
+        save(&path, &sessions).unwrap();
+        assert_eq!(load(&path).unwrap(), sessions);
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        save(&path, &[]).unwrap();
+        assert!(load(&path).unwrap().is_empty());
+        fs::write(&path, "nonsense\n").unwrap();
+        assert!(load(&path).is_err());
+    }
+}
@@ -0,0 +1,395 @@
+//! The status line at the bottom of the terminal.
+
+use std::{
+    collections::BTreeSet,
+    io::{self, Write},
+    ops::Range,
+    time::{Duration, Instant},
+};
+
+use nix::pty::Winsize;
+
+use session_manager::{
+    session::{Session, SessionPane, WindowFlags},
+    width,
+};
+
+use crate::{format, style::Theme};
+
+/// How often the text at the right is expanded again. Its variables change
+/// with each frame drawn, so it is not kept up to date, or drawing it would
+/// change it again.
+const RIGHT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How long `display-message` shows a message, unless `display-time` is set.
+pub const DEFAULT_DISPLAY_TIME: Duration = Duration::from_millis(750);
+
+/// What the status line shows about a window.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WindowStatus<'a> {
+    pub idx: usize,
+    pub name: &'a str,
+    /// Whether it is current or selected last, and what has happened in it.
+    pub flags: WindowFlags,
+    /// The name of the window's theme, if it has one.
+    pub theme: Option<&'a str>,
+    /// Whether the window's active pane is private.
+    pub private: bool,
+    /// Whether input goes to all the window's panes.
+    pub synchronized: bool,
+    /// Whether the window's entry is flashed, for its bell.
+    pub bell: bool,
+}
+
+/// The status line, redrawn only when its contents change.
+#[derive(Debug, Default)]
+pub struct StatusBar {
+    drawn: Option<String>,
+    /// The format of the text at the right, if any.
+    right: Option<String>,
+    /// The text at the right as last expanded, and when.
+    right_text: Option<(String, Instant)>,
+    /// The windows whose entries are flashed.
+    bells: BTreeSet<usize>,
+    /// The columns of each window's entry, and of the flags in it, by window
+    /// index, as last drawn.
+    entries: Vec<(usize, Range<usize>, Range<usize>)>,
+    theme: Theme,
+    /// The message shown in place of the line, and until when.
+    message: Option<(String, Instant)>,
+}
+
+impl StatusBar {
+    /// Forget what was drawn, so the next `draw` repaints the line.
+    pub fn invalidate(&mut self) {
+        self.drawn = None;
+        self.right_text = None;
+    }
+
+    /// Show `format` at the right of the line, with its variables expanded; see
+    /// `format::variable`.
+    pub fn set_right(&mut self, format: Option<String>) {
+        self.right = format;
+        self.invalidate();
+    }
+
+    /// Draw the line, and the flags of windows on it, in the styles of `theme`.
+    pub fn set_theme(&mut self, theme: Theme) {
+        self.theme = theme;
+        self.invalidate();
+    }
+
+    /// The window whose entry is at `col` on the line, as last drawn.
+    pub fn window_at(&self, col: u16) -> Option<usize> {
+        let col = usize::from(col);
+        let entry = self.entries.iter().find(|(_, cols, _)| cols.contains(&col));
+        Some(entry?.0)
+    }
+
+    /// Show `message` in place of the line, in the message style, until
+    /// `until`.
+    pub fn show_message(&mut self, message: &str, until: Instant) {
+        let message = message.chars().filter(|c| !c.is_control()).collect();
+        self.message = Some((message, until));
+        self.invalidate();
+    }
+
+    /// Flash the entries of `windows`, and only those, for their bells.
+    pub fn set_bells(&mut self, windows: BTreeSet<usize>) {
+        self.bells = windows;
+    }
+
+    /// Draw the status of `session` on the last row of a terminal of size
+    /// `size`, to which `client_bytes_out` bytes have been sent.
+    pub fn draw<T: Write, P: SessionPane>(
+        &mut self,
+        output: &mut T,
+        session: &Session<P>,
+        size: Winsize,
+        client_bytes_out: u64,
+    ) -> io::Result<()> {
+        let now = Instant::now();
+        match &self.message {
+            Some((message, until)) if now < *until => {
+                let text = width::fit(message, size.ws_col as usize);
+                if self.drawn.as_ref() != Some(&text) {
+                    let style = self.theme.message.sgr();
+                    write!(output, "\x1b[{};1H{}{}\x1b[0m", size.ws_row, style, text)?;
+                    self.drawn = Some(text);
+                }
+                return Ok(());
+            }
+            Some(_) => {
+                self.message = None;
+                self.invalidate();
+            }
+            None => (),
+        }
+        let bells = &self.bells;
+        let windows = session.windows().map(|(idx, pane)| WindowStatus {
+            idx,
+            name: session.window_name(idx).unwrap_or(""),
+            flags: session.window_flags(idx).unwrap_or_default(),
+            theme: pane.theme().map(|theme| theme.name.as_str()),
+            private: pane.is_private(),
+            synchronized: session.is_synchronized(idx),
+            bell: bells.contains(&idx),
+        });
+        let windows: Vec<_> = windows.collect();
+        let right = match (&self.right, &self.right_text) {
+            (Some(_), Some((text, at))) if now < *at + RIGHT_INTERVAL => text.clone(),
+            (Some(right), _) => {
+                let text = format::expand(right, |name| {
+                    format::variable(session, name, client_bytes_out)
+                });
+                self.right_text = Some((text.clone(), now));
+                text
+            }
+            (None, _) => String::new(),
+        };
+        let cols = size.ws_col as usize;
+        let left = cols - width::str_width(width::truncate(&right, cols));
+        self.entries = entry_columns(session.name(), &windows, left);
+        let text =
+            format_status(session.name(), windows.into_iter(), &right, size.ws_col);
+        if self.drawn.as_ref() == Some(&text) {
+            return Ok(());
+        }
+        let line = styled(&text, &self.entries, &self.theme);
+        write!(output, "\x1b[{};1H{}\x1b[0m", size.ws_row, line)?;
+        self.drawn = Some(text);
+        Ok(())
+    }
+}
+
+/// Lay out the status line for the windows of session `name`, with `right` at
+/// the right, exactly `width` columns wide however wide its characters are.
+fn format_status<'a>(
+    name: &str,
+    windows: impl Iterator<Item = WindowStatus<'a>>,
+    right: &str,
+    width: u16,
+) -> String {
+    let windows = windows
+        .map(|window| entry(&window))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let right = width::truncate(right, width as usize);
+    let left = width as usize - width::str_width(right);
+    width::fit(&format!("[{}] {}", name, windows), left) + right
+}
+
+/// The status line `text`, with the styles of `theme`, the flags in `entries`
+/// in theirs.
+fn styled(
+    text: &str,
+    entries: &[(usize, Range<usize>, Range<usize>)],
+    theme: &Theme,
+) -> String {
+    let (style, flags_style) = (theme.status.sgr(), theme.flags.sgr());
+    let mut line = style.clone();
+    let mut col = 0;
+    let mut in_flags = false;
+    for c in text.chars() {
+        let flag = flags_style != style
+            && entries.iter().any(|(_, _, flags)| flags.contains(&col));
+        if flag != in_flags {
+            line.push_str(if flag { &flags_style } else { &style });
+            in_flags = flag;
+        }
+        line.push(c);
+        col += width::char_width(c);
+    }
+    line
+}
+
+/// The entry of `window` on the status line.
+fn entry(window: &WindowStatus) -> String {
+    format!("{}:{}{}", window.idx, window.name, flags(window))
+}
+
+/// The flags after the name of `window` on the status line, e.g. `*(sync)`.
+fn flags(window: &WindowStatus) -> String {
+    let mut entry = window.flags.symbols();
+    if let Some(theme) = window.theme {
+        entry.push_str(&format!("[{}]", theme));
+    }
+    if window.private {
+        entry.push_str("(private)");
+    }
+    if window.synchronized {
+        entry.push_str("(sync)");
+    }
+    if window.bell {
+        entry.push_str("(bell)");
+    }
+    entry
+}
+
+/// The columns the entry of each of `windows` takes up on the status line of
+/// session `name`, and the flags in it, as `format_status` lays it out with
+/// `left` columns for them, leaving out those which do not fit.
+fn entry_columns(
+    name: &str,
+    windows: &[WindowStatus],
+    left: usize,
+) -> Vec<(usize, Range<usize>, Range<usize>)> {
+    let mut col = width::str_width(&format!("[{}] ", name));
+    let mut entries = Vec::new();
+    for window in windows {
+        let end = col + width::str_width(&entry(window));
+        if col >= left {
+            break;
+        }
+        let flags = (end - width::str_width(&flags(window))).min(left);
+        entries.push((window.idx, col..end.min(left), flags..end.min(left)));
+        col = end + 1;
+    }
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use session_manager::session::Pane;
+
+    use crate::style::ColorScheme;
+
+    #[test]
+    fn status_text() {
+        let current = WindowFlags {
+            current: true,
+            ..WindowFlags::default()
+        };
+        let windows = vec![
+            WindowStatus {
+                idx: 0,
+                name: "sh",
+                flags: WindowFlags {
+                    last: true,
+                    activity: true,
+                    ..WindowFlags::default()
+                },
+                theme: None,
+                private: true,
+                synchronized: false,
+                bell: false,
+            },
+            WindowStatus {
+                idx: 2,
+                name: "vim",
+                flags: current,
+                theme: Some("production"),
+                private: false,
+                synchronized: true,
+                bell: true,
+            },
+        ];
+        assert_eq!(
+            format_status("0", windows.clone().into_iter(), "", 50),
+            "[0] 0:sh-#(private) 2:vim*[production](sync)(bell)"
+        );
+        assert_eq!(
+            format_status("work", windows.into_iter(), "", 29),
+            "[work] 0:sh-#(private) 2:vim*"
+        );
+
+        let windows = vec![WindowStatus {
+            idx: 1,
+            name: "",
+            flags: current,
+            theme: Some("本番"),
+            private: false,
+            synchronized: false,
+            bell: false,
+        }];
+        assert_eq!(
+            format_status("0", windows.clone().into_iter(), "", 14),
+            "[0] 1:*[本番] "
+        );
+        assert_eq!(
+            format_status("0", windows.clone().into_iter(), "", 11),
+            "[0] 1:*[本 "
+        );
+
+        // The text at the right is kept over the windows.
+        assert_eq!(
+            format_status("0", windows.clone().into_iter(), "12 in", 14),
+            "[0] 1:*[ 12 in"
+        );
+        assert_eq!(
+            format_status("0", windows.into_iter(), "1234 bytes in", 6),
+            "1234 b"
+        );
+    }
+
+    #[test]
+    fn window_entries() {
+        let window = |idx, name| WindowStatus {
+            idx,
+            name,
+            flags: WindowFlags {
+                current: idx == 2,
+                ..WindowFlags::default()
+            },
+            theme: None,
+            private: false,
+            synchronized: false,
+            bell: false,
+        };
+        let windows = [window(0, "sh"), window(2, "vim"), window(3, "top")];
+        assert_eq!(
+            entry_columns("work", &windows, 80),
+            [(0, 7..11, 11..11), (2, 12..18, 17..18), (3, 19..24, 24..24)]
+        );
+        // Entries are cut off by the text at the right.
+        assert_eq!(
+            entry_columns("work", &windows, 14),
+            [(0, 7..11, 11..11), (2, 12..14, 14..14)]
+        );
+        let entries = entry_columns("work", &windows, 80);
+        let text = format_status("work", windows.iter().cloned(), "", 26);
+        assert_eq!(
+            styled(&text, &entries, &Theme::default()),
+            "\x1b[0;7m[work] 0:sh 2:vim* 3:top  "
+        );
+        let theme = ColorScheme::Mono.theme();
+        assert_eq!(
+            styled(&text, &entries, &theme),
+            "\x1b[0;7m[work] 0:sh 2:vim\x1b[0;1;7m*\x1b[0;7m 3:top  "
+        );
+        let status = StatusBar {
+            entries,
+            ..StatusBar::default()
+        };
+        assert_eq!(status.window_at(12), Some(2));
+        assert_eq!(status.window_at(11), None);
+        assert_eq!(status.window_at(3), None);
+    }
+
+    #[test]
+    fn message() {
+        let size = Winsize {
+            ws_row: 24,
+            ws_col: 20,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        };
+        let mut session: Session<Pane> = Session::new(size);
+        session.set_name("work");
+        let mut status = StatusBar::default();
+        let now = Instant::now();
+        status.show_message("window\x07 0", now + Duration::from_secs(60));
+        let mut out = Vec::new();
+        status.draw(&mut out, &session, size, 0).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(out, "\x1b[24;1H\x1b[0;7mwindow 0            \x1b[0m");
+        // The line is drawn again once the message has been shown.
+        status.show_message("gone", now);
+        let mut out = Vec::new();
+        status.draw(&mut out, &session, size, 0).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.starts_with("\x1b[24;1H\x1b[0;7m[work] "));
+    }
+}
@@ -0,0 +1,361 @@
+//! How the parts of the screen `sm` draws itself look: the status line, the
+//! prompt and pager, the lines between panes, and text highlighted in the pager.
+//!
+//! A style is written as in the configuration file, e.g. `fg=white,bg=blue,bold`:
+//! `fg=COLOUR` and `bg=COLOUR`, and the attributes `bold`, `dim`, `italics`,
+//! `underscore`, `blink` and `reverse`, separated by commas; `default` is the
+//! terminal's own. A colour is `black`, `red`, `green`, `yellow`, `blue`,
+//! `magenta`, `cyan` or `white`, any of those after `bright`, `colourN` for one
+//! of the 256 colours, `#rrggbb`, or `default`.
+
+use std::{fmt, str::FromStr};
+
+use anyhow::{anyhow, bail, Error, Result};
+
+use session_manager::session::BorderStyles;
+
+/// The names of the eight colours, in order.
+const COLOUR_NAMES: [&str; 8] = [
+    "black", "red", "green", "yellow", "blue", "magenta", "cyan", "white",
+];
+
+/// The attributes, as bits of `Style::attrs`.
+const BOLD: u8 = 1;
+const DIM: u8 = 1 << 1;
+const UNDERSCORE: u8 = 1 << 3;
+const REVERSE: u8 = 1 << 5;
+
+/// The attributes, with their SGR parameters, in the order of their bits.
+const ATTRIBUTES: [(&str, u8); 6] = [
+    ("bold", 1),
+    ("dim", 2),
+    ("italics", 3),
+    ("underscore", 4),
+    ("blink", 5),
+    ("reverse", 7),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Colour {
+    /// One of the eight colours, by number.
+    Named(u8),
+    /// One of the eight colours, bright.
+    Bright(u8),
+    /// One of the 256 colours.
+    Indexed(u8),
+    Rgb(u8, u8, u8),
+}
+
+impl Colour {
+    /// The SGR parameters setting the colour as foreground, or as background
+    /// if `background`.
+    fn sgr(self, background: bool) -> String {
+        let base = if background { 40 } else { 30 };
+        match self {
+            Colour::Named(n) => (base + n).to_string(),
+            Colour::Bright(n) => (base + 60 + n).to_string(),
+            Colour::Indexed(n) => format!("{};5;{}", base + 8, n),
+            Colour::Rgb(r, g, b) => format!("{};2;{};{};{}", base + 8, r, g, b),
+        }
+    }
+}
+
+impl FromStr for Colour {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Colour> {
+        let named = |name: &str| COLOUR_NAMES.iter().position(|n| *n == name);
+        if let Some(n) = named(s) {
+            return Ok(Colour::Named(n as u8));
+        }
+        if let Some(n) = s.strip_prefix("bright").and_then(named) {
+            return Ok(Colour::Bright(n as u8));
+        }
+        let indexed = s.strip_prefix("colour").or_else(|| s.strip_prefix("color"));
+        if let Some(n) = indexed.and_then(|n| n.parse().ok()) {
+            return Ok(Colour::Indexed(n));
+        }
+        let hex = |range| {
+            let digits = s.get(range)?;
+            u8::from_str_radix(digits, 16).ok()
+        };
+        match (s.len(), s.starts_with('#')) {
+            (7, true) => Ok(Colour::Rgb(
+                hex(1..3).ok_or_else(|| anyhow!("invalid colour: {}", s))?,
+                hex(3..5).ok_or_else(|| anyhow!("invalid colour: {}", s))?,
+                hex(5..7).ok_or_else(|| anyhow!("invalid colour: {}", s))?,
+            )),
+            _ => bail!("invalid colour: {}", s),
+        }
+    }
+}
+
+impl fmt::Display for Colour {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Colour::Named(n) => f.write_str(COLOUR_NAMES[*n as usize]),
+            Colour::Bright(n) => write!(f, "bright{}", COLOUR_NAMES[*n as usize]),
+            Colour::Indexed(n) => write!(f, "colour{}", n),
+            Colour::Rgb(r, g, b) => write!(f, "#{:02x}{:02x}{:02x}", r, g, b),
+        }
+    }
+}
+
+/// Colours and attributes of text; the terminal's own where not given.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Style {
+    pub fg: Option<Colour>,
+    pub bg: Option<Colour>,
+    /// The attributes set, as bits in the order of `ATTRIBUTES`.
+    attrs: u8,
+}
+
+impl Style {
+    fn new(fg: Option<Colour>, bg: Option<Colour>, attrs: u8) -> Style {
+        Style { fg, bg, attrs }
+    }
+
+    /// The SGR sequence drawing text in the style, after resetting whatever
+    /// was drawn before, e.g. `\x1b[0;7m`.
+    pub fn sgr(&self) -> String {
+        let mut params = vec!["0".to_string()];
+        for (bit, (_, param)) in ATTRIBUTES.iter().enumerate() {
+            if self.attrs & 1 << bit != 0 {
+                params.push(param.to_string());
+            }
+        }
+        params.extend(self.fg.map(|fg| fg.sgr(false)));
+        params.extend(self.bg.map(|bg| bg.sgr(true)));
+        format!("\x1b[{}m", params.join(";"))
+    }
+}
+
+impl FromStr for Style {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Style> {
+        let mut style = Style::default();
+        for part in s.split(',').map(str::trim) {
+            let colour = |value: &str| match value {
+                "default" => Ok(None),
+                value => value.parse().map(Some),
+            };
+            if let Some(value) = part.strip_prefix("fg=") {
+                style.fg = colour(value)?;
+            } else if let Some(value) = part.strip_prefix("bg=") {
+                style.bg = colour(value)?;
+            } else if part == "default" {
+                style = Style::default();
+            } else {
+                let bit = ATTRIBUTES
+                    .iter()
+                    .position(|(name, _)| *name == part)
+                    .ok_or_else(|| anyhow!("invalid style: {}", part))?;
+                style.attrs |= 1 << bit;
+            }
+        }
+        Ok(style)
+    }
+}
+
+impl fmt::Display for Style {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut parts = Vec::new();
+        parts.extend(self.fg.map(|fg| format!("fg={}", fg)));
+        parts.extend(self.bg.map(|bg| format!("bg={}", bg)));
+        for (bit, (name, _)) in ATTRIBUTES.iter().enumerate() {
+            if self.attrs & 1 << bit != 0 {
+                parts.push(name.to_string());
+            }
+        }
+        match parts.is_empty() {
+            true => f.write_str("default"),
+            false => f.write_str(&parts.join(",")),
+        }
+    }
+}
+
+/// The styles of everything `sm` draws itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Theme {
+    /// The status line.
+    pub status: Style,
+    /// The flags after a window's name on the status line, e.g. `*` or
+    /// `(sync)`.
+    pub flags: Style,
+    /// The prompt, and the last row of the pager.
+    pub message: Style,
+    /// Text highlighted in the pager: what a search found.
+    pub selection: Style,
+    /// The lines between panes.
+    pub border: Style,
+    /// The lines around the active pane, and its title.
+    pub active_border: Style,
+}
+
+/// The options setting the styles of a theme, in the order of its fields.
+pub const STYLE_OPTIONS: [&str; 6] = [
+    "status-style",
+    "window-status-flags-style",
+    "message-style",
+    "mode-style",
+    "pane-border-style",
+    "pane-active-border-style",
+];
+
+impl Theme {
+    /// The styles of the lines between panes, for the session to draw them.
+    pub fn border_styles(&self) -> BorderStyles {
+        BorderStyles {
+            border: self.border.sgr(),
+            active: self.active_border.sgr(),
+        }
+    }
+
+    /// The style `option`, one of `STYLE_OPTIONS`, sets.
+    pub fn style_mut(&mut self, option: &str) -> Option<&mut Style> {
+        Some(match option {
+            "status-style" => &mut self.status,
+            "window-status-flags-style" => &mut self.flags,
+            "message-style" => &mut self.message,
+            "mode-style" => &mut self.selection,
+            "pane-border-style" => &mut self.border,
+            "pane-active-border-style" => &mut self.active_border,
+            _ => return None,
+        })
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Theme {
+        ColorScheme::default().theme()
+    }
+}
+
+/// The themes built in, which the styles set in the configuration file change.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ColorScheme {
+    /// Reverse video, and green around the active pane.
+    #[default]
+    Default,
+    /// White on blue, with cyan around the active pane.
+    Ocean,
+    /// No colour, for terminals without it.
+    Mono,
+}
+
+impl ColorScheme {
+    pub fn theme(self) -> Theme {
+        let reverse = Style::new(None, None, REVERSE);
+        let [black, _, green, yellow, blue, _, cyan, white] =
+            [0, 1, 2, 3, 4, 5, 6, 7].map(|n| Some(Colour::Named(n)));
+        match self {
+            ColorScheme::Default => Theme {
+                status: reverse,
+                flags: reverse,
+                message: reverse,
+                selection: reverse,
+                border: Style::default(),
+                active_border: Style::new(green, None, 0),
+            },
+            ColorScheme::Ocean => Theme {
+                status: Style::new(white, blue, 0),
+                flags: Style::new(yellow, blue, BOLD),
+                message: Style::new(black, cyan, 0),
+                selection: Style::new(black, yellow, 0),
+                border: Style::new(blue, None, 0),
+                active_border: Style::new(cyan, None, BOLD),
+            },
+            ColorScheme::Mono => Theme {
+                status: reverse,
+                flags: Style::new(None, None, BOLD | REVERSE),
+                message: Style::new(None, None, BOLD | REVERSE),
+                selection: Style::new(None, None, UNDERSCORE | REVERSE),
+                border: Style::new(None, None, DIM),
+                active_border: Style::new(None, None, BOLD),
+            },
+        }
+    }
+}
+
+impl FromStr for ColorScheme {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<ColorScheme> {
+        match s {
+            "default" => Ok(ColorScheme::Default),
+            "ocean" => Ok(ColorScheme::Ocean),
+            "mono" => Ok(ColorScheme::Mono),
+            _ => bail!("expected default, ocean or mono, not {:?}", s),
+        }
+    }
+}
+
+impl fmt::Display for ColorScheme {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            ColorScheme::Default => "default",
+            ColorScheme::Ocean => "ocean",
+            ColorScheme::Mono => "mono",
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn colours() {
+        for (s, colour) in [
+            ("red", Colour::Named(1)),
+            ("brightwhite", Colour::Bright(7)),
+            ("colour208", Colour::Indexed(208)),
+            ("#0a80ff", Colour::Rgb(10, 128, 255)),
+        ] {
+            assert_eq!(s.parse::<Colour>().unwrap(), colour);
+            assert_eq!(colour.to_string(), s);
+        }
+        assert_eq!("color1".parse::<Colour>().unwrap(), Colour::Indexed(1));
+        for s in ["purple", "colour256", "#12345", "#12345g", "#1234567"] {
+            assert!(s.parse::<Colour>().is_err(), "{}", s);
+        }
+    }
+
+    #[test]
+    fn styles() {
+        let style: Style = "fg=white, bg=colour17,bold,reverse".parse().unwrap();
+        assert_eq!(style.sgr(), "\x1b[0;1;7;37;48;5;17m");
+        assert_eq!(style.to_string(), "fg=white,bg=colour17,bold,reverse");
+        let style: Style = "bg=#102030,fg=brightred".parse().unwrap();
+        assert_eq!(style.sgr(), "\x1b[0;91;48;2;16;32;48m");
+        assert_eq!("default".parse::<Style>().unwrap(), Style::default());
+        assert_eq!(Style::default().sgr(), "\x1b[0m");
+        assert_eq!(Style::default().to_string(), "default");
+        assert_eq!("fg=default".parse::<Style>().unwrap(), Style::default());
+        assert!("fg=red,shiny".parse::<Style>().is_err());
+        assert!("fg=".parse::<Style>().is_err());
+    }
+
+    #[test]
+    fn schemes() {
+        let theme = Theme::default();
+        assert_eq!(theme.status.sgr(), "\x1b[0;7m");
+        assert_eq!(theme.active_border.sgr(), "\x1b[0;32m");
+        for scheme in [ColorScheme::Default, ColorScheme::Ocean, ColorScheme::Mono] {
+            assert_eq!(scheme.to_string().parse::<ColorScheme>().unwrap(), scheme);
+        }
+        assert_eq!(
+            ColorScheme::Ocean.theme().flags.to_string(),
+            "fg=yellow,bg=blue,bold"
+        );
+        assert!("solarized".parse::<ColorScheme>().is_err());
+        let mut theme = Theme::default();
+        for option in STYLE_OPTIONS {
+            *theme.style_mut(option).unwrap() = Style::default();
+        }
+        assert_eq!(theme.status, Style::default());
+        assert_eq!(theme.active_border, Style::default());
+        assert!(theme.style_mut("status-left-style").is_none());
+    }
+}
@@ -0,0 +1,145 @@
+//! The overlay shown once the last window of a session closes, saying how its
+//! windows exited, to open a new one or end the session.
+
+use std::{
+    io::{self, Write},
+    time::Duration,
+};
+
+use nix::pty::Winsize;
+
+use session_manager::{session::ClosedWindow, width};
+
+use crate::backend::Key;
+
+/// What the summary made of a key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SummaryInput {
+    /// The summary stays open.
+    Pending,
+    /// A new window should be opened in the session.
+    NewWindow,
+    /// The session should end.
+    Quit,
+}
+
+/// The windows of a session which closed, with their exit statuses, and how
+/// long the session lasted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Summary {
+    lines: Vec<String>,
+    /// Whether the summary has to be drawn again.
+    dirty: bool,
+}
+
+impl Summary {
+    /// Sum up `closed` windows of the session `name`, which lasted `duration`.
+    pub fn new(name: &str, closed: &[ClosedWindow], duration: Duration) -> Summary {
+        let mut lines = vec![format!(
+            "Session {} ran for {}.",
+            name,
+            format_duration(duration)
+        )];
+        if !closed.is_empty() {
+            lines.push(String::new());
+        }
+        lines.extend(closed.iter().map(|window| {
+            let status = match window.status {
+                Some(status) => format!("exited with status {}", status),
+                None => "killed".to_string(),
+            };
+            format!("{}:{}  {}", window.idx, window.name, status)
+        }));
+        Summary { lines, dirty: true }
+    }
+
+    /// Open a new window with `n` or Enter; end the session with `q`.
+    pub fn handle_key(&self, key: Key) -> SummaryInput {
+        match key {
+            Key::Char('n') | Key::Char('\n') => SummaryInput::NewWindow,
+            Key::Char('q') => SummaryInput::Quit,
+            _ => SummaryInput::Pending,
+        }
+    }
+
+    /// Draw the summary over `area` if it has changed since it was last drawn,
+    /// the latest windows last if they do not all fit.
+    pub fn draw<T: Write>(&mut self, output: &mut T, area: Winsize) -> io::Result<()> {
+        if !self.dirty {
+            return Ok(());
+        }
+        let cols = area.ws_col as usize;
+        let rows = usize::from(area.ws_row.saturating_sub(1));
+        // The first line, and as many of the last as fit after it.
+        let tail = self
+            .lines
+            .len()
+            .saturating_sub(rows.saturating_sub(1))
+            .max(1);
+        let mut lines = self.lines.iter().take(1).chain(&self.lines[tail..]);
+        write!(output, "\x1b[0m")?;
+        for row in 0..rows {
+            let line = lines.next().map_or("", String::as_str);
+            write!(output, "\x1b[{};1H{}", row + 1, width::fit(line, cols))?;
+        }
+        write!(
+            output,
+            "\x1b[{};1H\x1b[7m{}\x1b[0m",
+            area.ws_row,
+            width::fit(" n: new window  q: quit", cols)
+        )?;
+        self.dirty = false;
+        Ok(())
+    }
+
+    /// Draw the summary again on the next `draw`, e.g. after the terminal was
+    /// cleared.
+    pub fn mark_dirty(&mut self) {
+        self.dirty = true;
+    }
+}
+
+/// `duration` to the second, e.g. `1h 02m 05s`.
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    match (secs / 3600, secs / 60 % 60, secs % 60) {
+        (0, 0, s) => format!("{}s", s),
+        (0, m, s) => format!("{}m {:02}s", m, s),
+        (h, m, s) => format!("{}h {:02}m {:02}s", h, m, s),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summary_lines() {
+        let closed = [
+            ClosedWindow {
+                idx: 0,
+                name: "sh".to_string(),
+                status: Some(0),
+            },
+            ClosedWindow {
+                idx: 2,
+                name: "make".to_string(),
+                status: None,
+            },
+        ];
+        let summary = Summary::new("work", &closed, Duration::from_secs(3725));
+        assert_eq!(
+            summary.lines,
+            [
+                "Session work ran for 1h 02m 05s.",
+                "",
+                "0:sh  exited with status 0",
+                "2:make  killed",
+            ]
+        );
+        assert_eq!(format_duration(Duration::from_millis(5900)), "5s");
+        assert_eq!(format_duration(Duration::from_secs(65)), "1m 05s");
+        assert_eq!(summary.handle_key(Key::Char('q')), SummaryInput::Quit);
+        assert_eq!(summary.handle_key(Key::Char('x')), SummaryInput::Pending);
+    }
+}
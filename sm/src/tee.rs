@@ -0,0 +1,125 @@
+//! Mirroring of terminal output.
+
+use std::{
+    io::{self, Write},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use log::warn;
+
+/// A switch for pausing a `Tee`'s mirroring, e.g. while a private pane is shown.
+#[derive(Debug, Clone, Default)]
+pub struct Pause(Arc<AtomicBool>);
+
+impl Pause {
+    /// Pause mirroring, or resume it.
+    pub fn set(&self, paused: bool) {
+        self.0.store(paused, Ordering::Relaxed);
+    }
+
+    fn is_set(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// A writer which copies everything written to it to a mirror.
+///
+/// The mirror is flushed along with the terminal, so it receives whole frames.
+/// If writing to the mirror fails, mirroring stops but the terminal is
+/// unaffected.
+pub struct Tee<W: Write> {
+    inner: W,
+    mirror: Option<Box<dyn Write + Send>>,
+    pause: Pause,
+}
+
+impl<W: Write> Tee<W> {
+    /// Write to `inner`, copying to `mirror` if given.
+    pub fn new(inner: W, mirror: Option<Box<dyn Write + Send>>) -> Tee<W> {
+        Tee {
+            inner,
+            mirror,
+            pause: Pause::default(),
+        }
+    }
+
+    /// The switch pausing this tee's mirroring.
+    pub fn pause(&self) -> Pause {
+        self.pause.clone()
+    }
+
+    fn mirror(&mut self, f: impl FnOnce(&mut dyn Write) -> io::Result<()>) {
+        if self.pause.is_set() {
+            return;
+        }
+        if let Some(mirror) = self.mirror.as_mut() {
+            if let Err(e) = f(mirror) {
+                warn!("stopped mirroring output: {}", e);
+                self.mirror = None;
+            }
+        }
+    }
+}
+
+impl<W: Write> Write for Tee<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.mirror(|mirror| mirror.write_all(&buf[..written]));
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()?;
+        self.mirror(|mirror| mirror.flush());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::{Arc, Mutex};
+
+    /// A mirror which can be inspected, and made to fail.
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<Option<Vec<u8>>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            match self.0.lock().unwrap().as_mut() {
+                Some(data) => data.write(buf),
+                None => Err(io::ErrorKind::BrokenPipe.into()),
+            }
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn tee() {
+        let mirror = Shared(Arc::new(Mutex::new(Some(Vec::new()))));
+        let mut tee = Tee::new(Vec::new(), Some(Box::new(mirror.clone())));
+        tee.write_all(b"frame one").unwrap();
+        tee.flush().unwrap();
+        assert_eq!(mirror.0.lock().unwrap().as_deref(), Some(&b"frame one"[..]));
+
+        let pause = tee.pause();
+        pause.set(true);
+        tee.write_all(b" (private)").unwrap();
+        pause.set(false);
+        tee.flush().unwrap();
+        assert_eq!(mirror.0.lock().unwrap().as_deref(), Some(&b"frame one"[..]));
+
+        *mirror.0.lock().unwrap() = None;
+        tee.write_all(b", frame two").unwrap();
+        assert!(tee.mirror.is_none());
+        tee.write_all(b".").unwrap();
+        assert_eq!(tee.inner, b"frame one (private), frame two.");
+    }
+}
@@ -0,0 +1,62 @@
+//! Themes which can be applied to windows.
+
+use session_manager::{
+    ansi::{NamedColor, Rgb},
+    grid::{Palette, Theme},
+};
+
+/// The themes available, in the order they are cycled through.
+pub fn builtin() -> Vec<Theme> {
+    vec![
+        tinted(
+            "production",
+            Rgb {
+                r: 0x40,
+                g: 0,
+                b: 0,
+            },
+        ),
+        tinted(
+            "staging",
+            Rgb {
+                r: 0x38,
+                g: 0x30,
+                b: 0,
+            },
+        ),
+    ]
+}
+
+/// A theme changing only the default background.
+fn tinted(name: &str, background: Rgb) -> Theme {
+    let mut palette = Palette::default();
+    palette.set(NamedColor::Background as usize, background);
+    Theme {
+        name: name.to_string(),
+        palette,
+    }
+}
+
+/// The theme after the one named `current`, or `None` after the last.
+pub fn next(current: Option<&str>) -> Option<Theme> {
+    let themes = builtin();
+    let next = match current {
+        None => 0,
+        Some(name) => 1 + themes.iter().position(|theme| theme.name == name)?,
+    };
+    themes.into_iter().nth(next)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cycle() {
+        let name = |theme: Option<Theme>| theme.map(|theme| theme.name);
+        assert_eq!(name(next(None)).as_deref(), Some("production"));
+        assert_eq!(name(next(Some("production"))).as_deref(), Some("staging"));
+        assert_eq!(name(next(Some("staging"))), None);
+        assert_eq!(name(next(Some("unknown"))), None);
+    }
+}
@@ -0,0 +1,84 @@
+//! The title of the client's terminal, set from a format as the session
+//! changes.
+
+use std::io::{self, Write};
+
+use session_manager::session::{Session, SessionPane};
+
+use crate::format;
+
+/// The format of the title if none is set.
+pub const DEFAULT_FORMAT: &str = "#{session_name}:#{window_index}:#{window_name} - \
+                                  #{pane_title}";
+
+/// Sets the terminal's title, writing it only when it changes.
+#[derive(Debug)]
+pub struct TerminalTitle {
+    format: String,
+    /// The title last written, if it is still the terminal's.
+    drawn: Option<String>,
+}
+
+impl TerminalTitle {
+    /// Set titles from `format`; see `format::variable` for its variables.
+    pub fn new(format: Option<String>) -> TerminalTitle {
+        TerminalTitle {
+            format: format.unwrap_or_else(|| DEFAULT_FORMAT.to_string()),
+            drawn: None,
+        }
+    }
+
+    /// Write the title on the next draw, e.g. for a terminal newly attached.
+    pub fn invalidate(&mut self) {
+        self.drawn = None;
+    }
+
+    /// Set the title for `session`, if it is not already.
+    pub fn draw<P: SessionPane, T: Write>(
+        &mut self,
+        output: &mut T,
+        session: &Session<P>,
+        client_bytes_out: u64,
+    ) -> io::Result<()> {
+        let title: String = format::expand(&self.format, |name| {
+            format::variable(session, name, client_bytes_out)
+        })
+        .chars()
+        .filter(|c| !c.is_control())
+        .collect();
+        if self.drawn.as_ref() != Some(&title) {
+            write!(output, "\x1b]2;{}\x07", title)?;
+            self.drawn = Some(title);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use nix::pty::Winsize;
+    use session_manager::session::Pane;
+
+    #[test]
+    fn title_changes() {
+        let mut session: Session<Pane> = Session::new(Winsize {
+            ws_row: 24,
+            ws_col: 80,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        });
+        session.set_name("work");
+        let mut title = TerminalTitle::new(Some("#{session_name}\x07!".to_string()));
+        let mut out = Vec::new();
+        title.draw(&mut out, &session, 0).unwrap();
+        assert_eq!(out, b"\x1b]2;work!\x07");
+        out.clear();
+        title.draw(&mut out, &session, 0).unwrap();
+        assert!(out.is_empty());
+        title.invalidate();
+        title.draw(&mut out, &session, 0).unwrap();
+        assert_eq!(out, b"\x1b]2;work!\x07");
+    }
+}
@@ -0,0 +1,662 @@
+//! The tree of sessions, their windows and the windows' panes, filling the
+//! screen, to switch to one, kill one or rename one.
+
+use std::{
+    collections::BTreeSet,
+    io::{self, Write},
+};
+
+use nix::pty::Winsize;
+
+use session_manager::{session::WindowFlags, width};
+
+use crate::backend::{Key, MouseButton, MouseEvent, MouseKind};
+
+/// A session, window or pane in the tree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TreeTarget {
+    /// The session with an id.
+    Session(u64),
+    /// A window of a session, by index.
+    Window(u64, usize),
+    /// A pane of a window, by number.
+    Pane(u64, usize, usize),
+}
+
+impl TreeTarget {
+    /// The id of the session it is, or is in.
+    pub fn session(self) -> u64 {
+        match self {
+            TreeTarget::Session(id)
+            | TreeTarget::Window(id, _)
+            | TreeTarget::Pane(id, _, _) => id,
+        }
+    }
+
+    /// What is above it in the tree, if anything.
+    fn parent(self) -> Option<TreeTarget> {
+        match self {
+            TreeTarget::Session(_) => None,
+            TreeTarget::Window(id, _) => Some(TreeTarget::Session(id)),
+            TreeTarget::Pane(id, idx, _) => Some(TreeTarget::Window(id, idx)),
+        }
+    }
+}
+
+/// What the tree made of an input event.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TreeInput {
+    /// The tree stays open.
+    Pending,
+    /// The tree was dismissed.
+    Close,
+    /// This was chosen to switch to, closing the tree.
+    Choose(TreeTarget),
+    /// This is to be killed, as was confirmed.
+    Kill(TreeTarget),
+    /// This session or window is to be given the name typed.
+    Rename(TreeTarget, String),
+}
+
+/// A session, with its windows.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TreeSession {
+    pub id: u64,
+    pub name: String,
+    /// Whether it is the session attached to.
+    pub attached: bool,
+    /// The sessions grouped with it, sharing its windows.
+    pub grouped: Vec<String>,
+    pub windows: Vec<TreeWindow>,
+}
+
+/// A window, with its panes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TreeWindow {
+    pub idx: usize,
+    pub name: String,
+    /// Whether it is current or selected last in its session, and what has
+    /// happened in it.
+    pub flags: WindowFlags,
+    pub panes: Vec<TreePane>,
+}
+
+/// A pane.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TreePane {
+    pub number: usize,
+    /// Whether it is the active pane of its window.
+    pub active: bool,
+    /// The last line of text in it, or `None` if it is private.
+    pub preview: Option<String>,
+}
+
+/// Where the tree takes what is typed: keys moving in it, or the answer to a
+/// question at its last row.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Mode {
+    Browse,
+    /// Whether to kill this.
+    Confirm(TreeTarget),
+    /// The new name of this, as typed so far.
+    Rename(TreeTarget, String),
+}
+
+/// The sessions, their windows and the windows' panes, one a line, those of
+/// sessions and windows expanded indented under them, with the highlighted one
+/// in reverse video.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tree {
+    sessions: Vec<TreeSession>,
+    /// The sessions and windows expanded.
+    expanded: BTreeSet<TreeTarget>,
+    /// What is on each line, in order, as last laid out.
+    rows: Vec<TreeTarget>,
+    /// The row highlighted.
+    highlighted: usize,
+    /// The first row on screen.
+    top: usize,
+    mode: Mode,
+    /// The row clicked, until the button is released.
+    pressed: Option<usize>,
+    /// Whether the tree has to be drawn again.
+    dirty: bool,
+}
+
+impl Tree {
+    /// List `sessions`, the one attached expanded, starting at its selected
+    /// window.
+    pub fn new(sessions: Vec<TreeSession>) -> Tree {
+        let attached = sessions.iter().find(|session| session.attached);
+        let expanded = attached.map(|session| TreeTarget::Session(session.id));
+        let window = attached.and_then(|session| {
+            let window = session.windows.iter().find(|w| w.flags.current)?;
+            Some(TreeTarget::Window(session.id, window.idx))
+        });
+        let mut tree = Tree {
+            sessions,
+            expanded: expanded.into_iter().collect(),
+            rows: Vec::new(),
+            highlighted: 0,
+            top: 0,
+            mode: Mode::Browse,
+            pressed: None,
+            dirty: true,
+        };
+        tree.lay_out(window.or(expanded));
+        tree
+    }
+
+    /// List `sessions` in place of those listed, as they have changed, keeping
+    /// what is expanded and highlighted if it is still there.
+    pub fn update(&mut self, sessions: Vec<TreeSession>) {
+        let highlighted = self.rows.get(self.highlighted).copied();
+        self.sessions = sessions;
+        self.lay_out(highlighted);
+    }
+
+    /// Move with the arrow keys, `j` and `k`, Home and End; Right, `l` or `+`
+    /// expands the session or window highlighted, and Left, `h` or `-` folds
+    /// it, or moves to what it is in. Enter switches to it, `x` kills it after
+    /// asking, and `r` renames a session or window. Escape or `q` dismisses the
+    /// tree.
+    pub fn handle_key(&mut self, key: Key) -> TreeInput {
+        match self.mode.clone() {
+            Mode::Confirm(target) => {
+                self.set_mode(Mode::Browse);
+                if let Key::Char('y') | Key::Char('Y') = key {
+                    return TreeInput::Kill(target);
+                }
+                return TreeInput::Pending;
+            }
+            Mode::Rename(target, mut name) => {
+                match key {
+                    Key::Char('\n') | Key::Char('\r') => {
+                        self.set_mode(Mode::Browse);
+                        if name.is_empty() {
+                            return TreeInput::Pending;
+                        }
+                        return TreeInput::Rename(target, name);
+                    }
+                    Key::Esc | Key::Ctrl('c') => {
+                        self.set_mode(Mode::Browse);
+                        return TreeInput::Pending;
+                    }
+                    Key::Backspace => {
+                        name.pop();
+                    }
+                    Key::Ctrl('u') => name.clear(),
+                    Key::Char(c) if !c.is_control() => name.push(c),
+                    _ => (),
+                }
+                self.set_mode(Mode::Rename(target, name));
+                return TreeInput::Pending;
+            }
+            Mode::Browse => (),
+        }
+        let target = match self.rows.get(self.highlighted) {
+            Some(target) => *target,
+            None => {
+                return match key {
+                    Key::Esc | Key::Char('q') => TreeInput::Close,
+                    _ => TreeInput::Pending,
+                }
+            }
+        };
+        match key {
+            Key::Up | Key::Char('k') => self.highlight(self.highlighted.checked_sub(1)),
+            Key::Down | Key::Char('j') => self.highlight(Some(self.highlighted + 1)),
+            Key::Home | Key::Char('g') => self.highlight(Some(0)),
+            Key::End | Key::Char('G') => self.highlight(self.rows.len().checked_sub(1)),
+            Key::Right | Key::Char('l') | Key::Char('+')
+                if !matches!(target, TreeTarget::Pane(..)) =>
+            {
+                self.expanded.insert(target);
+                self.lay_out(Some(target));
+            }
+            Key::Left | Key::Char('h') | Key::Char('-') => {
+                if self.expanded.remove(&target) {
+                    self.lay_out(Some(target));
+                } else if let Some(parent) = target.parent() {
+                    self.expanded.remove(&parent);
+                    self.lay_out(Some(parent));
+                }
+            }
+            Key::Char('\n') | Key::Char('\r') => return TreeInput::Choose(target),
+            Key::Char('x') => self.set_mode(Mode::Confirm(target)),
+            Key::Char('r') => {
+                if let Some(name) = self.name(target) {
+                    self.set_mode(Mode::Rename(target, name.to_string()));
+                }
+            }
+            Key::Esc | Key::Char('q') => return TreeInput::Close,
+            _ => (),
+        }
+        TreeInput::Pending
+    }
+
+    /// Move with the wheel, and switch to what is clicked.
+    pub fn handle_mouse(&mut self, event: MouseEvent) -> TreeInput {
+        let position = self.top + event.row as usize;
+        match (event.button, event.kind) {
+            (MouseButton::WheelUp, _) => {
+                self.highlight(self.highlighted.checked_sub(1))
+            }
+            (MouseButton::WheelDown, _) => self.highlight(Some(self.highlighted + 1)),
+            (MouseButton::Left, MouseKind::Press) if position < self.rows.len() => {
+                self.highlight(Some(position));
+                self.pressed = Some(position);
+            }
+            (MouseButton::Left, MouseKind::Release) => {
+                let pressed = self.pressed.take();
+                match self.rows.get(position) {
+                    Some(target) if pressed == Some(position) => {
+                        return TreeInput::Choose(*target)
+                    }
+                    _ => (),
+                }
+            }
+            _ => (),
+        }
+        TreeInput::Pending
+    }
+
+    /// Draw the tree over `area` if it has changed since it was last drawn,
+    /// scrolling to keep the highlighted row in view. The last row asks any
+    /// question, or says which keys do what.
+    pub fn draw<T: Write>(&mut self, output: &mut T, area: Winsize) -> io::Result<()> {
+        let rows = (area.ws_row as usize).saturating_sub(1).max(1);
+        let top = if self.highlighted < self.top {
+            self.highlighted
+        } else {
+            self.top.max((self.highlighted + 1).saturating_sub(rows))
+        };
+        if !self.dirty && top == self.top {
+            return Ok(());
+        }
+        self.top = top;
+        let cols = area.ws_col as usize;
+        for row in 0..rows {
+            let position = self.top + row;
+            let (style, line) = match self.rows.get(position) {
+                Some(target) if position == self.highlighted => {
+                    ("7", self.line(*target))
+                }
+                Some(target) => ("0", self.line(*target)),
+                None => ("0", String::new()),
+            };
+            write!(
+                output,
+                "\x1b[{};1H\x1b[0;{}m{}",
+                row + 1,
+                style,
+                width::fit(&line, cols)
+            )?;
+        }
+        let footer = match &self.mode {
+            Mode::Browse => {
+                "Enter switch  x kill  r rename  +/- expand/fold  q close".to_string()
+            }
+            Mode::Confirm(target) => format!("kill {}? (y/n)", self.label(*target)),
+            Mode::Rename(target, name) => {
+                format!("rename {} to: {}_", self.label(*target), name)
+            }
+        };
+        write!(
+            output,
+            "\x1b[{};1H\x1b[0;1m{}\x1b[0m",
+            rows + 1,
+            width::fit(&footer, cols)
+        )?;
+        self.dirty = false;
+        Ok(())
+    }
+
+    /// Draw the tree again on the next `draw`.
+    pub fn mark_dirty(&mut self) {
+        self.dirty = true;
+    }
+
+    /// Lay out the rows again, highlighting the row of `target`, or the nearest
+    /// row if it is gone.
+    fn lay_out(&mut self, target: Option<TreeTarget>) {
+        let mut rows = Vec::new();
+        for session in &self.sessions {
+            let id = session.id;
+            rows.push(TreeTarget::Session(id));
+            if !self.expanded.contains(&TreeTarget::Session(id)) {
+                continue;
+            }
+            for window in &session.windows {
+                rows.push(TreeTarget::Window(id, window.idx));
+                if !self.expanded.contains(&TreeTarget::Window(id, window.idx)) {
+                    continue;
+                }
+                let panes = window.panes.iter();
+                rows.extend(
+                    panes.map(|pane| TreeTarget::Pane(id, window.idx, pane.number)),
+                );
+            }
+        }
+        self.rows = rows;
+        // What is gone is left expanded in case it comes back.
+        let found = target.and_then(|target| {
+            let mut target = Some(target);
+            while let Some(t) = target {
+                if let Some(position) = self.rows.iter().position(|row| *row == t) {
+                    return Some(position);
+                }
+                target = t.parent();
+            }
+            None
+        });
+        let last = self.rows.len().saturating_sub(1);
+        self.highlighted = found.unwrap_or(self.highlighted).min(last);
+        self.dirty = true;
+    }
+
+    /// The line shown for `target`, indented by its depth, e.g. `  - 1: vim*
+    /// (2 panes) "~"`.
+    fn line(&self, target: TreeTarget) -> String {
+        let marker = |expanded: bool| if expanded { '-' } else { '+' };
+        let expanded = self.expanded.contains(&target);
+        match target {
+            TreeTarget::Session(id) => {
+                let session = match self.find_session(id) {
+                    Some(session) => session,
+                    None => return String::new(),
+                };
+                let count = session.windows.len();
+                let mut line = format!(
+                    "{} {}: {} window{}",
+                    marker(expanded),
+                    session.name,
+                    count,
+                    if count == 1 { "" } else { "s" }
+                );
+                if session.attached {
+                    line.push_str(" (attached)");
+                }
+                if !session.grouped.is_empty() {
+                    line.push_str(&format!(
+                        " (grouped: {})",
+                        session.grouped.join(", ")
+                    ));
+                }
+                line
+            }
+            TreeTarget::Window(id, idx) => {
+                let window = match self.find_window(id, idx) {
+                    Some(window) => window,
+                    None => return String::new(),
+                };
+                let count = window.panes.len();
+                let active = window.panes.iter().find(|pane| pane.active);
+                format!(
+                    "  {} {}: {}{} ({} pane{}) {}",
+                    marker(expanded),
+                    window.idx,
+                    window.name,
+                    window.flags.symbols(),
+                    count,
+                    if count == 1 { "" } else { "s" },
+                    preview(active)
+                )
+            }
+            TreeTarget::Pane(id, idx, number) => {
+                let window = self.find_window(id, idx);
+                let panes = window.map(|window| window.panes.iter());
+                let pane =
+                    panes.and_then(|mut panes| panes.find(|p| p.number == number));
+                format!(
+                    "      {}{} {}",
+                    number,
+                    if pane.is_some_and(|pane| pane.active) {
+                        "*"
+                    } else {
+                        ""
+                    },
+                    preview(pane)
+                )
+            }
+        }
+    }
+
+    /// How `target` is named in questions, e.g. `window work:1 (vim)`.
+    fn label(&self, target: TreeTarget) -> String {
+        let session = self
+            .find_session(target.session())
+            .map_or("", |session| session.name.as_str());
+        match target {
+            TreeTarget::Session(_) => format!("session {}", session),
+            TreeTarget::Window(id, idx) => {
+                let name = self.find_window(id, idx).map_or("", |window| &window.name);
+                format!("window {}:{} ({})", session, idx, name)
+            }
+            TreeTarget::Pane(_, idx, number) => {
+                format!("pane {}:{}.{}", session, idx, number)
+            }
+        }
+    }
+
+    /// The name of the session or window `target`.
+    fn name(&self, target: TreeTarget) -> Option<&str> {
+        match target {
+            TreeTarget::Session(id) => Some(&self.find_session(id)?.name),
+            TreeTarget::Window(id, idx) => Some(&self.find_window(id, idx)?.name),
+            TreeTarget::Pane(..) => None,
+        }
+    }
+
+    fn find_session(&self, id: u64) -> Option<&TreeSession> {
+        self.sessions.iter().find(|session| session.id == id)
+    }
+
+    fn find_window(&self, id: u64, idx: usize) -> Option<&TreeWindow> {
+        let session = self.find_session(id)?;
+        session.windows.iter().find(|window| window.idx == idx)
+    }
+
+    fn set_mode(&mut self, mode: Mode) {
+        self.mode = mode;
+        self.dirty = true;
+    }
+
+    fn highlight(&mut self, row: Option<usize>) {
+        match row {
+            Some(row) if row < self.rows.len() => {
+                self.highlighted = row;
+                self.dirty = true;
+            }
+            _ => (),
+        }
+    }
+}
+
+/// What a pane shows, in quotes, or that it is private.
+fn preview(pane: Option<&TreePane>) -> String {
+    match pane.map(|pane| pane.preview.as_deref()) {
+        Some(Some(text)) => format!("\"{}\"", text),
+        Some(None) => "(private)".to_string(),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sessions() -> Vec<TreeSession> {
+        let current = WindowFlags {
+            current: true,
+            ..WindowFlags::default()
+        };
+        let pane = |number, active| TreePane {
+            number,
+            active,
+            preview: Some(format!("$ {}", number)),
+        };
+        vec![
+            TreeSession {
+                id: 3,
+                name: "build".to_string(),
+                attached: false,
+                grouped: Vec::new(),
+                windows: vec![TreeWindow {
+                    idx: 0,
+                    name: "make".to_string(),
+                    flags: current,
+                    panes: vec![pane(0, true)],
+                }],
+            },
+            TreeSession {
+                id: 1,
+                name: "work".to_string(),
+                attached: true,
+                grouped: vec!["view".to_string()],
+                windows: vec![
+                    TreeWindow {
+                        idx: 0,
+                        name: "vim".to_string(),
+                        flags: WindowFlags {
+                            last: true,
+                            ..WindowFlags::default()
+                        },
+                        panes: vec![pane(0, true)],
+                    },
+                    TreeWindow {
+                        idx: 1,
+                        name: "logs".to_string(),
+                        flags: current,
+                        panes: vec![
+                            pane(0, false),
+                            TreePane {
+                                number: 1,
+                                active: true,
+                                preview: None,
+                            },
+                        ],
+                    },
+                ],
+            },
+        ]
+    }
+
+    fn lines(tree: &Tree) -> Vec<String> {
+        tree.rows.iter().map(|target| tree.line(*target)).collect()
+    }
+
+    fn highlighted(tree: &Tree) -> TreeTarget {
+        tree.rows[tree.highlighted]
+    }
+
+    #[test]
+    fn tree_expand() {
+        let mut tree = Tree::new(sessions());
+        // The session attached is expanded, at its selected window.
+        assert_eq!(
+            lines(&tree),
+            [
+                "+ build: 1 window",
+                "- work: 2 windows (attached) (grouped: view)",
+                "  + 0: vim- (1 pane) \"$ 0\"",
+                "  + 1: logs* (2 panes) (private)",
+            ]
+        );
+        assert_eq!(highlighted(&tree), TreeTarget::Window(1, 1));
+        tree.handle_key(Key::Char('l'));
+        assert_eq!(lines(&tree)[4..], ["      0 \"$ 0\"", "      1* (private)"]);
+        tree.handle_key(Key::End);
+        assert_eq!(highlighted(&tree), TreeTarget::Pane(1, 1, 1));
+        // Folding a pane folds its window.
+        tree.handle_key(Key::Left);
+        assert_eq!(highlighted(&tree), TreeTarget::Window(1, 1));
+        assert_eq!(tree.rows.len(), 4);
+        tree.handle_key(Key::Left);
+        assert_eq!(highlighted(&tree), TreeTarget::Session(1));
+        assert_eq!(tree.rows.len(), 2);
+        tree.handle_key(Key::Up);
+        tree.handle_key(Key::Char('+'));
+        assert_eq!(tree.rows.len(), 3);
+        assert_eq!(tree.handle_key(Key::Char('j')), TreeInput::Pending);
+        assert_eq!(
+            tree.handle_key(Key::Char('\n')),
+            TreeInput::Choose(TreeTarget::Window(3, 0))
+        );
+        assert_eq!(tree.handle_key(Key::Char('q')), TreeInput::Close);
+    }
+
+    #[test]
+    fn tree_kill_and_rename() {
+        let mut tree = Tree::new(sessions());
+        tree.handle_key(Key::Char('x'));
+        assert_eq!(tree.mode, Mode::Confirm(TreeTarget::Window(1, 1)));
+        // Anything but y answers no.
+        assert_eq!(tree.handle_key(Key::Char('n')), TreeInput::Pending);
+        tree.handle_key(Key::Char('x'));
+        assert_eq!(
+            tree.handle_key(Key::Char('y')),
+            TreeInput::Kill(TreeTarget::Window(1, 1))
+        );
+
+        tree.handle_key(Key::Char('r'));
+        tree.handle_key(Key::Backspace);
+        for c in "in".chars() {
+            tree.handle_key(Key::Char(c));
+        }
+        assert_eq!(
+            tree.handle_key(Key::Char('\n')),
+            TreeInput::Rename(TreeTarget::Window(1, 1), "login".to_string())
+        );
+        tree.handle_key(Key::Char('r'));
+        tree.handle_key(Key::Ctrl('u'));
+        assert_eq!(tree.handle_key(Key::Char('\n')), TreeInput::Pending);
+        assert_eq!(tree.mode, Mode::Browse);
+
+        // The highlight stays where it was as the sessions change, or moves
+        // to what its row was in.
+        let mut changed = sessions();
+        changed[1].windows.pop();
+        tree.update(changed);
+        assert_eq!(highlighted(&tree), TreeTarget::Session(1));
+        tree.update(sessions()[..1].to_vec());
+        assert_eq!(highlighted(&tree), TreeTarget::Session(3));
+    }
+
+    #[test]
+    fn draw() {
+        let mut tree = Tree::new(sessions());
+        let mut out = Vec::new();
+        let area = Winsize {
+            ws_row: 3,
+            ws_col: 40,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        };
+        tree.draw(&mut out, area).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        // The highlighted row is scrolled to.
+        assert!(out.starts_with("\x1b[1;1H\x1b[0;0m  + 0: vim- "));
+        assert!(out.contains("\x1b[2;1H\x1b[0;7m  + 1: logs* "));
+        assert!(out.contains("\x1b[3;1H\x1b[0;1mEnter switch "));
+        let mut out = Vec::new();
+        tree.draw(&mut out, area).unwrap();
+        assert!(out.is_empty());
+
+        tree.handle_key(Key::Char('x'));
+        let mut out = Vec::new();
+        tree.draw(&mut out, area).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("kill window work:1 (logs)? (y/n)"));
+        assert_eq!(
+            tree.handle_mouse(MouseEvent {
+                kind: MouseKind::Press,
+                button: MouseButton::Left,
+                modifiers: Default::default(),
+                col: 3,
+                row: 0,
+            }),
+            TreeInput::Pending
+        );
+    }
+}
@@ -0,0 +1,147 @@
+//! The streams of updates from panes, each watched for a session, and taken
+//! back when the panes move to another.
+
+use std::{
+    pin::Pin,
+    task::{Context, Poll, Waker},
+};
+
+use futures::stream::{FusedStream, Stream};
+
+/// Streams, each with the id of the session it is watched for, merged into one
+/// yielding their items with that id.
+///
+/// Unlike `SelectAll`, the streams can be taken out again.
+pub struct Watched<S> {
+    streams: Vec<(u64, S)>,
+    /// Which stream is polled first next, so that a busy one does not keep the
+    /// others waiting.
+    next: usize,
+    /// Woken when a stream is added, for the merged stream to be polled again.
+    waker: Option<Waker>,
+}
+
+impl<S> Default for Watched<S> {
+    fn default() -> Self {
+        Watched {
+            streams: Vec::new(),
+            next: 0,
+            waker: None,
+        }
+    }
+}
+
+impl<S> Watched<S> {
+    /// Watch `stream` for the session with id `id`.
+    pub fn push(&mut self, id: u64, stream: S) {
+        self.streams.push((id, stream));
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+
+    /// Stop watching the streams `which` is true of, returning them with the
+    /// ids of their sessions.
+    pub fn unwatch<F>(&mut self, mut which: F) -> Vec<(u64, S)>
+    where
+        F: FnMut(u64, &S) -> bool,
+    {
+        let (taken, kept) = self
+            .streams
+            .drain(..)
+            .partition(|(id, stream)| which(*id, stream));
+        self.streams = kept;
+        self.next = 0;
+        taken
+    }
+}
+
+impl<S: Stream + Unpin> Stream for Watched<S> {
+    type Item = (u64, S::Item);
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context,
+    ) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        let mut polled = 0;
+        while polled < this.streams.len() {
+            let i = (this.next + polled) % this.streams.len();
+            let (id, stream) = &mut this.streams[i];
+            match Pin::new(stream).poll_next(cx) {
+                Poll::Ready(Some(item)) => {
+                    this.next = i + 1;
+                    return Poll::Ready(Some((*id, item)));
+                }
+                // Finished, so the stream after it takes its place.
+                Poll::Ready(None) => {
+                    this.streams.remove(i);
+                }
+                Poll::Pending => polled += 1,
+            }
+        }
+        this.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+impl<S: Stream + Unpin> FusedStream for Watched<S> {
+    /// More streams may be pushed, so it never ends.
+    fn is_terminated(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::{channel::mpsc, executor, FutureExt, SinkExt, StreamExt};
+
+    use super::*;
+
+    #[test]
+    fn merges_and_takes() {
+        let mut watched = Watched::default();
+        let (mut a, a_recv) = mpsc::channel(4);
+        let (mut b, b_recv) = mpsc::channel(4);
+        watched.push(1, a_recv);
+        watched.push(2, b_recv);
+        executor::block_on(async {
+            a.send('a').await.unwrap();
+            b.send('b').await.unwrap();
+            let mut items =
+                vec![watched.next().await.unwrap(), watched.next().await.unwrap()];
+            items.sort();
+            assert_eq!(items, vec![(1, 'a'), (2, 'b')]);
+
+            let taken = watched.unwatch(|id, _| id == 1);
+            assert_eq!(taken.len(), 1);
+            let (_, a_recv) = taken.into_iter().next().unwrap();
+            watched.push(3, a_recv);
+            a.send('c').await.unwrap();
+            assert_eq!(watched.next().await, Some((3, 'c')));
+
+            // Ended streams are dropped.
+            drop(b);
+            assert_eq!(watched.next().now_or_never(), None);
+            assert_eq!(watched.unwatch(|_, _| true).len(), 1);
+        });
+    }
+
+    #[test]
+    fn takes_turns() {
+        let mut watched = Watched::default();
+        let (mut a, a_recv) = mpsc::channel(4);
+        let (mut b, b_recv) = mpsc::channel(4);
+        watched.push(1, a_recv);
+        watched.push(2, b_recv);
+        executor::block_on(async {
+            for _ in 0..2 {
+                a.send(()).await.unwrap();
+            }
+            b.send(()).await.unwrap();
+            let first = watched.next().await.unwrap().0;
+            let second = watched.next().await.unwrap().0;
+            assert_ne!(first, second);
+        });
+    }
+}
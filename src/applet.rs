@@ -0,0 +1,245 @@
+//! Programs built into the multiplexer, run in a pane instead of a process.
+//!
+//! An applet writes terminal output like any program, which goes through the
+//! pane's grid, so it is drawn, themed and laid out as other panes are.
+
+use std::{
+    fs,
+    io::{self, Write},
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use nix::pty::Winsize;
+
+use crate::width;
+
+/// What an applet made of input, or of time passing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AppletUpdate {
+    /// What the applet shows is the same.
+    Unchanged,
+    /// The applet has to be drawn again.
+    Redraw,
+    /// The applet is done, and its pane closes.
+    Exit,
+}
+
+/// A program run in a pane in place of a process on a PTY.
+pub trait Applet: Send {
+    /// What the applet is called, which its window is named after.
+    fn name(&self) -> &str;
+    /// Draw the whole applet on a screen of size `size`, as terminal output.
+    fn draw(&mut self, output: &mut dyn Write, size: Winsize) -> io::Result<()>;
+    /// Handle input typed into the pane.
+    fn receive_input(&mut self, data: &[u8]) -> AppletUpdate;
+    /// Catch up with the time, e.g. to show a clock's new second.
+    fn tick(&mut self) -> AppletUpdate {
+        AppletUpdate::Unchanged
+    }
+}
+
+/// The time, shown in the middle of the pane until `q` is pressed.
+#[derive(Debug, Default)]
+pub struct Clock {
+    /// The text last drawn.
+    shown: Option<(String, String)>,
+}
+
+impl Clock {
+    pub fn new() -> Clock {
+        Clock::default()
+    }
+}
+
+impl Applet for Clock {
+    fn name(&self) -> &str {
+        "clock"
+    }
+
+    fn draw(&mut self, output: &mut dyn Write, size: Winsize) -> io::Result<()> {
+        let (time, date) = clock_text(SystemTime::now());
+        let row = size.ws_row / 2 + 1;
+        write!(output, "\x1b[?25l\x1b[0m\x1b[H\x1b[2J")?;
+        for (row, text) in [(row, &time), (row + 1, &date)] {
+            if row > size.ws_row {
+                break;
+            }
+            let text = width::truncate(text, size.ws_col as usize);
+            let col = (size.ws_col as usize - width::str_width(text)) / 2 + 1;
+            write!(output, "\x1b[{};{}H{}", row, col, text)?;
+        }
+        self.shown = Some((time, date));
+        Ok(())
+    }
+
+    fn receive_input(&mut self, data: &[u8]) -> AppletUpdate {
+        match data {
+            b"q" | b"\x1b" => AppletUpdate::Exit,
+            _ => AppletUpdate::Unchanged,
+        }
+    }
+
+    fn tick(&mut self) -> AppletUpdate {
+        if self.shown.as_ref() == Some(&clock_text(SystemTime::now())) {
+            AppletUpdate::Unchanged
+        } else {
+            AppletUpdate::Redraw
+        }
+    }
+}
+
+/// The local time at `time`, as `HH:MM:SS`, and its date, as `YYYY-MM-DD`.
+fn clock_text(time: SystemTime) -> (String, String) {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs()) as libc::time_t;
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    if unsafe { libc::localtime_r(&secs, &mut tm) }.is_null() {
+        return ("--:--:--".to_string(), String::new());
+    }
+    (
+        format!("{:02}:{:02}:{:02}", tm.tm_hour, tm.tm_min, tm.tm_sec),
+        format!(
+            "{}-{:02}-{:02}",
+            tm.tm_year + 1900,
+            tm.tm_mon + 1,
+            tm.tm_mday
+        ),
+    )
+}
+
+/// A file, shown a screenful at a time and scrolled like the pager.
+#[derive(Debug)]
+pub struct Viewer {
+    name: String,
+    lines: Vec<String>,
+    /// The first line on screen.
+    top: usize,
+    /// How many lines were on screen when last drawn.
+    page: usize,
+}
+
+impl Viewer {
+    /// View the file at `path`, which must be text.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Viewer> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)?;
+        let name = path.file_name().map_or_else(
+            || path.display().to_string(),
+            |name| name.to_string_lossy().into_owned(),
+        );
+        Ok(Viewer::new(&name, &text))
+    }
+
+    /// View `text`, naming the window `name`.
+    pub fn new(name: &str, text: &str) -> Viewer {
+        Viewer {
+            name: name.to_string(),
+            lines: text
+                .lines()
+                .map(|line| line.replace('\t', "    "))
+                .collect(),
+            top: 0,
+            page: 1,
+        }
+    }
+
+    fn scroll(&mut self, by: isize) -> AppletUpdate {
+        let last = self.lines.len().saturating_sub(self.page);
+        let top = (self.top as isize + by).clamp(0, last as isize) as usize;
+        if top == self.top {
+            return AppletUpdate::Unchanged;
+        }
+        self.top = top;
+        AppletUpdate::Redraw
+    }
+}
+
+impl Applet for Viewer {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn draw(&mut self, output: &mut dyn Write, size: Winsize) -> io::Result<()> {
+        self.page = size.ws_row.max(1) as usize;
+        self.top = self.top.min(self.lines.len().saturating_sub(self.page));
+        write!(output, "\x1b[?25l\x1b[0m\x1b[H\x1b[2J")?;
+        let lines = self.lines.iter().skip(self.top).take(self.page);
+        for (row, line) in lines.enumerate() {
+            let line: String = line.chars().filter(|c| !c.is_control()).collect();
+            let line = width::truncate(&line, size.ws_col as usize);
+            write!(output, "\x1b[{};1H{}", row + 1, line)?;
+        }
+        Ok(())
+    }
+
+    fn receive_input(&mut self, data: &[u8]) -> AppletUpdate {
+        let page = self.page as isize;
+        match data {
+            b"\x1b[A" | b"\x1bOA" | b"k" => self.scroll(-1),
+            b"\x1b[B" | b"\x1bOB" | b"j" | b"\r" => self.scroll(1),
+            b"\x1b[5~" | b"b" => self.scroll(-page),
+            b"\x1b[6~" | b" " => self.scroll(page),
+            b"\x1b[H" | b"\x1bOH" | b"g" => self.scroll(-(self.top as isize)),
+            b"\x1b[F" | b"\x1bOF" | b"G" => self.scroll(self.lines.len() as isize),
+            b"q" | b"\x1b" => AppletUpdate::Exit,
+            _ => AppletUpdate::Unchanged,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::Duration;
+
+    const SIZE: Winsize = Winsize {
+        ws_row: 2,
+        ws_col: 6,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+
+    #[test]
+    fn clock() {
+        let (time, date) = clock_text(UNIX_EPOCH + Duration::from_secs(86400 * 365));
+        assert_eq!(time.len(), 8);
+        assert!(date.starts_with("1970-12-3") || date.starts_with("1971-01-0"));
+
+        let mut clock = Clock::new();
+        assert_eq!(clock.tick(), AppletUpdate::Redraw);
+        let mut out = Vec::new();
+        clock.draw(&mut out, SIZE).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.starts_with("\x1b[?25l\x1b[0m\x1b[H\x1b[2J\x1b[2;1H"));
+        assert!(!out.contains("\x1b[3;"));
+        assert_eq!(clock.receive_input(b"x"), AppletUpdate::Unchanged);
+        assert_eq!(clock.receive_input(b"q"), AppletUpdate::Exit);
+    }
+
+    #[test]
+    fn viewer() {
+        let mut viewer = Viewer::new("notes", "one\ttwo\nthree\nfour\n");
+        let mut out = Vec::new();
+        viewer.draw(&mut out, SIZE).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "\x1b[?25l\x1b[0m\x1b[H\x1b[2J\x1b[1;1Hone   \x1b[2;1Hthree"
+        );
+        assert_eq!(viewer.receive_input(b"k"), AppletUpdate::Unchanged);
+        assert_eq!(viewer.receive_input(b"\x1b[B"), AppletUpdate::Redraw);
+        assert_eq!(viewer.top, 1);
+        assert_eq!(viewer.receive_input(b" "), AppletUpdate::Unchanged);
+        assert_eq!(viewer.receive_input(b"g"), AppletUpdate::Redraw);
+        assert_eq!(viewer.top, 0);
+        assert_eq!(viewer.receive_input(b"q"), AppletUpdate::Exit);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notes.txt");
+        fs::write(&path, "text").unwrap();
+        assert_eq!(Viewer::open(&path).unwrap().name(), "notes.txt");
+        assert!(Viewer::open(dir.path().join("missing")).is_err());
+    }
+}
@@ -2,86 +2,240 @@
 
 use std::{
     ffi::OsStr,
-    fs::File,
-    io::Read,
+    fmt,
+    fs::{self, File},
+    io::{self, Read},
     os::unix::io::{FromRawFd, RawFd},
     os::unix::process::CommandExt,
+    path::{Path, PathBuf},
     process::{Command, Stdio},
     thread,
+    time::{Duration, Instant},
 };
 
 use futures::{
     channel::mpsc::{self, Receiver},
-    executor, future,
+    executor,
+    sink::SinkExt,
 };
 use nix::{
+    errno::Errno,
+    fcntl::{fcntl, FcntlArg, FdFlag},
     pty::{openpty, Winsize},
-    unistd::setsid,
+    sys::{
+        signal::{killpg, Signal},
+        termios::{tcgetattr, LocalFlags},
+        wait::{waitpid, WaitPidFlag, WaitStatus},
+    },
+    unistd::{setsid, tcgetpgrp, Pid},
 };
 
-use crate::grid::Grid;
+use thiserror::Error;
+
+use crate::{environment::Environment, grid::Grid};
 
 mod ioctl {
     nix::ioctl_none_bad!(set_controlling, libc::TIOCSCTTY);
     nix::ioctl_write_ptr_bad!(win_resize, libc::TIOCSWINSZ, nix::pty::Winsize);
 }
 
+/// Why a PTY could not be opened, or its processes started or signalled.
+#[derive(Debug, Error)]
+pub enum PtyError {
+    /// A call to the system failed.
+    #[error("{0}")]
+    Sys(#[from] nix::Error),
+    /// Starting the process, or copying a file for it, failed.
+    #[error("{0}")]
+    Io(#[from] io::Error),
+}
+
+/// How long a process closing its PTY is given to exit, for its status to be
+/// reported.
+const EXIT_GRACE: Duration = Duration::from_millis(200);
+
 /// Initialise a new process and grid.
+///
+/// The process is given exactly the variables in `env`, and starts in `cwd`, or
+/// the current directory if it is `None`. Output from it is
+/// delivered, a byte at a time, on the returned `Receiver`, followed by
+/// `PtyUpdate::Exited` when the process closes the PTY. That says how the
+/// process exited if it does within `EXIT_GRACE`; one which closes the PTY but
+/// runs on has its pane closed all the same.
 pub fn spawn_pty<I, S>(
     command: &str,
     args: I,
+    env: &Environment,
+    cwd: Option<&Path>,
     size: Winsize,
-) -> Result<(ChildPty, Grid<File>), ()>
+) -> Result<(ChildPty, Grid<File>, Receiver<PtyUpdate>), PtyError>
 where
     I: IntoIterator<Item = S>,
     S: AsRef<OsStr>,
 {
-    let child_pty = ChildPty::new(command, args, size)?;
-    let mut pty_output = child_pty.file.try_clone().unwrap();
+    let child_pty = ChildPty::new(command, args, env, cwd, size)?;
+    let pid = child_pty.pid;
+    let mut pty_output = child_pty.file.try_clone()?;
     let grid = Grid::new(size.ws_col, size.ws_row);
-    Ok((child_pty, grid))
+    let (mut send, recv) = mpsc::channel(0x1000);
+    thread::spawn(move || {
+        let mut buf = [0u8; 4096];
+        // Blocking on each send regulates how far ahead of the consumer we read.
+        while let Ok(sz) = pty_output.read(&mut buf) {
+            if sz == 0 {
+                break;
+            }
+            for byte in &buf[..sz] {
+                if executor::block_on(send.send(PtyUpdate::Byte(*byte))).is_err() {
+                    return;
+                }
+            }
+        }
+        let deadline = Instant::now() + EXIT_GRACE;
+        let mut status = wait(pid, false);
+        while status == Wait::Running && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(5));
+            status = wait(pid, false);
+        }
+        let exited = PtyUpdate::Exited {
+            status: match status {
+                Wait::Exited(status) => Some(status),
+                Wait::Running | Wait::Unknown => None,
+            },
+        };
+        executor::block_on(send.send(exited)).ok();
+        if status == Wait::Running {
+            // Collected whenever it does exit, not to be left a zombie.
+            wait(pid, true);
+        }
+    });
+    Ok((child_pty, grid, recv))
+}
+
+/// What waiting for a process found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Wait {
+    Exited(ExitStatus),
+    Running,
+    /// It cannot be waited for, e.g. as it has been already.
+    Unknown,
+}
+
+/// Wait for the process `pid` to exit if `block`, or see whether it has.
+fn wait(pid: Pid, block: bool) -> Wait {
+    let flags = Some(WaitPidFlag::WNOHANG).filter(|_| !block);
+    loop {
+        match waitpid(pid, flags) {
+            Ok(WaitStatus::Exited(_, code)) => {
+                return Wait::Exited(ExitStatus::Code(code))
+            }
+            Ok(WaitStatus::Signaled(_, signal, _)) => {
+                return Wait::Exited(ExitStatus::Signal(signal))
+            }
+            Ok(WaitStatus::StillAlive) => return Wait::Running,
+            // Stopped or continued, so not gone yet.
+            Ok(_) | Err(nix::Error::Sys(Errno::EINTR)) => (),
+            Err(_) => return Wait::Unknown,
+        }
+    }
 }
 
 /// An update from a PTY.
 #[derive(Debug, PartialEq, Eq)]
 pub enum PtyUpdate {
-    /// The PTY has closed the file.
-    Exited,
+    /// The PTY has closed the file, and its process has exited.
+    Exited {
+        /// How it exited, if it is known.
+        status: Option<ExitStatus>,
+    },
     /// PTY sends byte.
     Byte(u8),
 }
 
+/// How the process started in a PTY exited.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitStatus {
+    /// It exited with a code.
+    Code(i32),
+    /// It was killed by a signal.
+    Signal(Signal),
+}
+
+impl ExitStatus {
+    /// The status as a shell gives it: the code, or 128 and the signal's
+    /// number.
+    pub fn code(self) -> i32 {
+        match self {
+            ExitStatus::Code(code) => code,
+            ExitStatus::Signal(signal) => 128 + signal as i32,
+        }
+    }
+}
+
+impl fmt::Display for ExitStatus {
+    /// E.g. `exited with status 1` or `killed by SIGTERM`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ExitStatus::Code(code) => write!(f, "exited with status {}", code),
+            ExitStatus::Signal(signal) => write!(f, "killed by {}", signal),
+        }
+    }
+}
+
 /// A pseudoterminal.
 pub struct ChildPty {
     fd: RawFd,
+    /// The process started in this PTY, which leads its session.
+    pid: Pid,
     /// The File used by this PTY.
     pub file: File,
 }
 
 impl ChildPty {
-    /// Spawn a process in a new pty.
-    pub fn new<I, S>(command: &str, args: I, size: Winsize) -> Result<ChildPty, ()>
+    /// Spawn a process in a new pty, with the variables in `env`, in `cwd` if
+    /// it is given.
+    pub fn new<I, S>(
+        command: &str,
+        args: I,
+        env: &Environment,
+        cwd: Option<&Path>,
+        size: Winsize,
+    ) -> Result<ChildPty, PtyError>
     where
         I: IntoIterator<Item = S>,
         S: AsRef<OsStr>,
     {
-        let pty = openpty(&size, None).unwrap();
+        let pty = openpty(&size, None)?;
+        // Neither end is for other processes, or it is never hung up.
+        for fd in [pty.master, pty.slave].iter().copied() {
+            fcntl(fd, FcntlArg::F_SETFD(FdFlag::FD_CLOEXEC))?;
+        }
+        // Each of the child's standard streams needs its own copy of the slave.
+        let slave = unsafe { File::from_raw_fd(pty.slave) };
+        let stdio = || slave.try_clone().map(Stdio::from);
+        let mut command = Command::new(command);
+        if let Some(cwd) = cwd {
+            command.current_dir(cwd);
+        }
         unsafe {
-            Command::new(&command)
+            command
                 .args(args)
-                .stdin(Stdio::from_raw_fd(pty.slave))
-                .stdout(Stdio::from_raw_fd(pty.slave))
-                .stderr(Stdio::from_raw_fd(pty.slave))
+                .env_clear()
+                .envs(env.vars())
+                .stdin(stdio()?)
+                .stdout(stdio()?)
+                .stderr(stdio()?)
                 .pre_exec(|| {
                     setsid().unwrap();
                     ioctl::set_controlling(0).unwrap();
                     Ok(())
                 })
                 .spawn()
-                .map_err(|_| ())
-                .and_then(|_| {
+                .map_err(PtyError::from)
+                .and_then(|child| {
                     let child = ChildPty {
                         fd: pty.master,
+                        pid: Pid::from_raw(child.id() as i32),
                         file: File::from_raw_fd(pty.master),
                     };
 
@@ -93,10 +247,52 @@ impl ChildPty {
     }
 
     /// Send a resize to the process running in this PTY.
-    pub fn resize(&self, size: Winsize) -> Result<(), ()> {
-        unsafe { ioctl::win_resize(self.fd, &size) }
-            .map(|_| ())
-            .map_err(|_| ())
+    pub fn resize(&self, size: Winsize) -> Result<(), PtyError> {
+        unsafe { ioctl::win_resize(self.fd, &size) }?;
+        Ok(())
+    }
+
+    /// The working directory of the process started in this PTY, where the
+    /// system tells it.
+    pub fn cwd(&self) -> Option<PathBuf> {
+        fs::read_link(format!("/proc/{}/cwd", self.pid)).ok()
+    }
+
+    /// Whether the process looks to be reading a password: the terminal is
+    /// reading whole lines, but not echoing them.
+    pub fn is_reading_secret(&self) -> bool {
+        match tcgetattr(self.fd) {
+            Ok(termios) => {
+                let flags = termios.local_flags;
+                flags.contains(LocalFlags::ICANON) && !flags.contains(LocalFlags::ECHO)
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Hang up on the processes running in this PTY, as if its terminal closed.
+    ///
+    /// `SIGHUP` goes to the foreground job and to the process started in the
+    /// PTY; the PTY's updates end with `PtyUpdate::Exited` once they have gone.
+    pub fn hangup(&self) -> Result<(), PtyError> {
+        if let Ok(foreground) = tcgetpgrp(self.fd) {
+            let _ = killpg(foreground, Signal::SIGHUP);
+        }
+        killpg(self.pid, Signal::SIGHUP)?;
+        Ok(())
+    }
+
+    /// Kill the processes running in this PTY at once, even one ignoring
+    /// `SIGHUP`: they are hung up on, then sent `SIGKILL`.
+    pub fn kill(&self) -> Result<(), PtyError> {
+        let foreground = tcgetpgrp(self.fd).ok();
+        for signal in [Signal::SIGHUP, Signal::SIGKILL] {
+            if let Some(foreground) = foreground {
+                let _ = killpg(foreground, signal);
+            }
+            killpg(self.pid, signal)?;
+        }
+        Ok(())
     }
 }
 
@@ -115,24 +311,122 @@ mod tests {
         use std::str;
 
         let args: [&str; 0] = [];
-        let mut child = ChildPty::new("pwd", &args, WINSZ).unwrap();
+        let mut child =
+            ChildPty::new("pwd", args, &Environment::capture(), None, WINSZ).unwrap();
         let mut buffer = [0; 1024];
         let count = child.file.read(&mut buffer).unwrap();
         let data = str::from_utf8(&buffer[..count]).unwrap().trim();
         assert_eq!(Path::new(&data), std::env::current_dir().unwrap());
     }
 
+    #[test]
+    fn working_directory() {
+        use std::io::Read;
+
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path().canonicalize().unwrap();
+        let args = ["-c", "pwd; sleep 5"];
+        let env = Environment::capture();
+        let mut child = ChildPty::new("sh", args, &env, Some(&dir), WINSZ).unwrap();
+        let mut buffer = [0; 1024];
+        let count = child.file.read(&mut buffer).unwrap();
+        let data = std::str::from_utf8(&buffer[..count]).unwrap().trim();
+        assert_eq!(Path::new(data), dir);
+        if Path::new("/proc/self/cwd").exists() {
+            assert_eq!(child.cwd(), Some(dir));
+        }
+        child.kill().unwrap();
+    }
+
     #[test]
     fn regulate_pty_update() {
         let mut dir = PathBuf::from(std::env!("CARGO_MANIFEST_DIR"));
         dir.push("Cargo.lock"); // a suitably long file
+        let env = Environment::capture();
         let (_, _, mut recv) =
-            spawn_pty("cat", &[&dir.into_os_string()], WINSZ).unwrap();
+            spawn_pty("cat", [&dir.into_os_string()], &env, None, WINSZ).unwrap();
         while let Some(msg) = executor::block_on(recv.next()) {
-            if msg == PtyUpdate::Exited {
+            if let PtyUpdate::Exited { status } = msg {
+                assert_eq!(status, Some(ExitStatus::Code(0)));
                 return;
             }
         }
-        assert!(false, "update thread did not exit cleanly")
+        panic!("update thread did not exit cleanly")
+    }
+
+    #[test]
+    fn environment() {
+        use std::io::Read;
+
+        let mut env = Environment::new();
+        env.set("GREETING", "hello");
+        let args = ["-c", "echo $GREETING ${HOME:-unset}"];
+        let mut child = ChildPty::new("/bin/sh", args, &env, None, WINSZ).unwrap();
+        let mut buffer = [0; 64];
+        let count = child.file.read(&mut buffer).unwrap();
+        assert_eq!(&buffer[..count], b"hello unset\r\n");
+    }
+
+    #[test]
+    fn reading_secret() {
+        use std::io::Read;
+
+        let args = ["-c", "stty -echo; echo ready; sleep 5"];
+        let mut child =
+            ChildPty::new("sh", args, &Environment::capture(), None, WINSZ).unwrap();
+        let mut buffer = [0; 64];
+        assert!(child.file.read(&mut buffer).unwrap() > 0);
+        assert!(child.is_reading_secret());
+        child.hangup().unwrap();
+    }
+
+    #[test]
+    fn exit_status() {
+        let exit = |script: &str| {
+            let env = Environment::capture();
+            let (_, _, recv) =
+                spawn_pty("sh", ["-c", script], &env, None, WINSZ).unwrap();
+            let updates = executor::block_on_stream(recv);
+            updates.into_iter().find_map(|update| match update {
+                PtyUpdate::Exited { status } => Some(status),
+                PtyUpdate::Byte(_) => None,
+            })
+        };
+        let status = exit("exit 3").unwrap().unwrap();
+        assert_eq!(status, ExitStatus::Code(3));
+        assert_eq!(status.to_string(), "exited with status 3");
+        let status = exit("kill -TERM $$").unwrap().unwrap();
+        assert_eq!(status, ExitStatus::Signal(Signal::SIGTERM));
+        assert_eq!(status.code(), 143);
+        assert_eq!(status.to_string(), "killed by SIGTERM");
+    }
+
+    #[test]
+    fn hangup() {
+        let args: [&str; 0] = [];
+        let (child, _, mut recv) =
+            spawn_pty("cat", args, &Environment::capture(), None, WINSZ).unwrap();
+        child.hangup().unwrap();
+        while let Some(msg) = executor::block_on(recv.next()) {
+            if let PtyUpdate::Exited { status } = msg {
+                assert_eq!(status, Some(ExitStatus::Signal(Signal::SIGHUP)));
+                return;
+            }
+        }
+        panic!("update thread did not exit after hangup")
+    }
+
+    #[test]
+    fn runs_on_after_closing() {
+        let env = Environment::capture();
+        let script = "exec sleep 5 </dev/null >/dev/null 2>&1";
+        let (child, _, recv) =
+            spawn_pty("sh", ["-c", script], &env, None, WINSZ).unwrap();
+        let exited = executor::block_on_stream(recv).find_map(|update| match update {
+            PtyUpdate::Exited { status } => Some(status),
+            PtyUpdate::Byte(_) => None,
+        });
+        assert_eq!(exited, Some(None));
+        child.hangup().unwrap();
     }
 }
@@ -0,0 +1,180 @@
+//! The environment given to processes started in a session.
+//!
+//! A session captures the environment it was created in. Variables can then be
+//! set or removed, and those named by the session's update list are taken from
+//! each client which attaches, so that e.g. `SSH_AUTH_SOCK` follows the user.
+//! Changes apply to panes created afterwards.
+
+use std::{collections::BTreeMap, env};
+
+use crate::terminfo;
+
+/// The variables taken from an attaching client unless configured otherwise.
+pub const DEFAULT_UPDATE: [&str; 8] = [
+    "DISPLAY",
+    "KRB5CCNAME",
+    "SSH_ASKPASS",
+    "SSH_AUTH_SOCK",
+    "SSH_AGENT_PID",
+    "SSH_CONNECTION",
+    "WINDOWID",
+    "XAUTHORITY",
+];
+
+/// The variables of other terminal multiplexers, which `sm` may be started in,
+/// left out of the environment of its panes as they do not run in them.
+pub const FOREIGN: [&str; 6] = [
+    "STY",
+    "TMUX",
+    "TMUX_PANE",
+    "WINDOW",
+    "ZELLIJ",
+    "ZELLIJ_SESSION_NAME",
+];
+
+/// A set of environment variables, some of which may be marked as removed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Environment {
+    /// Each variable's value, or `None` if it has been removed.
+    vars: BTreeMap<String, Option<String>>,
+}
+
+impl Environment {
+    /// An empty environment.
+    pub fn new() -> Environment {
+        Default::default()
+    }
+
+    /// The environment of this process, skipping variables which are not valid
+    /// Unicode.
+    pub fn capture() -> Environment {
+        let vars = env::vars_os()
+            .filter_map(|(name, value)| {
+                Some((name.into_string().ok()?, Some(value.into_string().ok()?)))
+            })
+            .collect();
+        Environment { vars }
+    }
+
+    /// The environment of this process, as panes start with it: `TERM` and
+    /// `COLORTERM` describe the terminal `sm` emulates rather than the one it
+    /// runs in, and the variables in `FOREIGN` are left out. `TERM` is kept if
+    /// there is no terminfo entry for any of `terminfo::EMULATED`.
+    pub fn for_panes() -> Environment {
+        let mut env = Environment::capture();
+        env.vars.retain(|name, _| !FOREIGN.contains(&name.as_str()));
+        if let Some(term) = terminfo::emulated() {
+            env.set("TERM", term);
+        }
+        env.set("COLORTERM", "truecolor");
+        env
+    }
+
+    /// Set `name` to `value`.
+    pub fn set(&mut self, name: &str, value: &str) {
+        self.vars.insert(name.to_string(), Some(value.to_string()));
+    }
+
+    /// Remove `name`, so processes started later do not get it.
+    pub fn remove(&mut self, name: &str) {
+        self.vars.insert(name.to_string(), None);
+    }
+
+    /// The value of `name`, unless it is unset or removed.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.vars.get(name)?.as_deref()
+    }
+
+    /// Take the variables in `names` from a client's environment `client`,
+    /// removing any the client does not have.
+    pub fn update<'a, I, S>(&mut self, client: I, names: &[S])
+    where
+        I: IntoIterator<Item = (&'a str, &'a str)>,
+        S: AsRef<str>,
+    {
+        let client: BTreeMap<_, _> = client.into_iter().collect();
+        for name in names {
+            let name = name.as_ref();
+            match client.get(name) {
+                Some(value) => self.set(name, value),
+                None => self.remove(name),
+            }
+        }
+    }
+
+    /// Every variable, in order of name, with its value or `None` if removed.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, Option<&str>)> {
+        self.vars
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_deref()))
+    }
+
+    /// The variables to give a process.
+    pub fn vars(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.iter().filter_map(|(name, value)| Some((name, value?)))
+    }
+
+    /// List the variables as `show-environment` does: `NAME=value`, or `-NAME`
+    /// for those removed, one per line.
+    pub fn show(&self) -> String {
+        self.iter()
+            .map(|(name, value)| match value {
+                Some(value) => format!("{}={}\n", name, value),
+                None => format!("-{}\n", name),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn environment() {
+        let mut env = Environment::new();
+        env.set("TERM", "xterm");
+        env.set("SSH_AUTH_SOCK", "/tmp/old");
+        env.set("DISPLAY", ":0");
+        env.remove("LANG");
+        assert_eq!(env.get("TERM"), Some("xterm"));
+        assert_eq!(env.get("LANG"), None);
+
+        env.update(
+            vec![("SSH_AUTH_SOCK", "/tmp/new"), ("TERM", "vt100")],
+            &DEFAULT_UPDATE,
+        );
+        assert_eq!(env.get("SSH_AUTH_SOCK"), Some("/tmp/new"));
+        assert_eq!(env.get("TERM"), Some("xterm"));
+        assert_eq!(env.get("DISPLAY"), None);
+        assert_eq!(
+            env.vars().collect::<Vec<_>>(),
+            [("SSH_AUTH_SOCK", "/tmp/new"), ("TERM", "xterm")]
+        );
+        assert_eq!(
+            env.show(),
+            "-DISPLAY\n-KRB5CCNAME\n-LANG\n-SSH_AGENT_PID\n-SSH_ASKPASS\n\
+             SSH_AUTH_SOCK=/tmp/new\n-SSH_CONNECTION\nTERM=xterm\n-WINDOWID\n\
+             -XAUTHORITY\n"
+        );
+    }
+
+    #[test]
+    fn capture() {
+        let env = Environment::capture();
+        assert_eq!(env.get("PATH"), env::var("PATH").ok().as_deref());
+    }
+
+    #[test]
+    fn for_panes() {
+        let env = Environment::for_panes();
+        assert_eq!(env.get("PATH"), env::var("PATH").ok().as_deref());
+        assert_eq!(env.get("COLORTERM"), Some("truecolor"));
+        let term = terminfo::emulated().map(String::from);
+        assert_eq!(
+            env.get("TERM").map(String::from),
+            term.or(env::var("TERM").ok())
+        );
+        assert!(env.iter().all(|(name, _)| !FOREIGN.contains(&name)));
+    }
+}
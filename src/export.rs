@@ -0,0 +1,273 @@
+//! A record of a session as text, for archiving: what each of its panes shows,
+//! what they run and where, how they exited, and how the windows are laid out.
+//!
+//! An export is written as a directory:
+//!
+//! ```text
+//! session.txt             the windows, their layouts and panes, and the
+//!                         windows closed so far
+//! window-IDX/pane-N.txt   what pane N of window IDX shows, as text
+//! window-IDX/pane-N.ansi  the same with its colours, to `cat` to a terminal
+//! window-IDX/input.txt    the input recorded for the window, if it is
+//! ```
+//!
+//! Panes are numbered by their position in the window's layout, counting from
+//! the pane base index. Only what a pane shows is kept, as panes keep no lines
+//! which have scrolled away.
+
+use std::{
+    fmt::Write as _,
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{ansi::Processor, grid::Grid, layout::Layout, session::ClosedWindow};
+
+/// A session's windows, and those which have closed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionExport {
+    pub name: String,
+    /// The index of the window selected.
+    pub selected: Option<usize>,
+    pub windows: Vec<WindowExport>,
+    pub closed: Vec<ClosedWindow>,
+    /// The number of the first pane of a window.
+    pub pane_base_index: usize,
+}
+
+/// A window, with its panes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WindowExport {
+    pub idx: usize,
+    pub name: String,
+    /// The panes, in layout order.
+    pub panes: Vec<PaneExport>,
+    /// The arrangement of the panes, identified by their position in `panes`.
+    pub layout: Layout,
+    /// The position of the active pane in `panes`.
+    pub active: usize,
+    /// The input recorded for the window, as `InputLog::text` gives it, if it
+    /// is recorded.
+    pub input: Option<String>,
+}
+
+/// What a pane runs, where, and what it shows.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PaneExport {
+    /// Whether it shows an applet rather than running a process.
+    pub applet: bool,
+    /// The command run with the shell, or `None` for the shell itself.
+    pub command: Option<String>,
+    /// The working directory of the pane's process, if it is known.
+    pub cwd: Option<PathBuf>,
+    /// The status its process exited with, if it has.
+    pub status: Option<i32>,
+    pub cols: u16,
+    pub rows: u16,
+    /// Escape sequences drawing what it shows on a terminal of its size, or
+    /// `None` if it is private.
+    pub contents: Option<Vec<u8>>,
+}
+
+impl PaneExport {
+    /// What the pane runs, for `session.txt`.
+    fn describe(&self) -> String {
+        let mut text = match (&self.command, self.applet) {
+            (_, true) => "an applet".to_string(),
+            (Some(command), false) => format!("`{}`", command),
+            (None, false) => "the shell".to_string(),
+        };
+        if let Some(cwd) = &self.cwd {
+            let _ = write!(text, " in {}", cwd.display());
+        }
+        match self.status {
+            Some(status) => {
+                let _ = write!(text, ", exited with {}", status);
+            }
+            None if !self.applet => text.push_str(", running"),
+            None => (),
+        }
+        if self.contents.is_none() {
+            text.push_str(", private");
+        }
+        text
+    }
+}
+
+/// The text `contents`, drawing a pane of `cols` by `rows`, shows: its rows
+/// without trailing blanks, leaving out blank rows at the end.
+pub fn text(cols: u16, rows: u16, contents: &[u8]) -> String {
+    let mut grid = Grid::<io::Sink>::new(cols, rows);
+    let mut processor = Processor::new();
+    for byte in contents {
+        processor.advance(&mut grid, *byte, &mut io::sink());
+    }
+    let mut lines: Vec<_> = (0..rows)
+        .filter_map(|row| grid.row_text(row))
+        .map(|text| text.trim_end().to_string())
+        .collect();
+    while lines.last().is_some_and(String::is_empty) {
+        lines.pop();
+    }
+    lines.iter().map(|line| format!("{}\n", line)).collect()
+}
+
+/// What `session.txt` says of `export`.
+pub fn summary(export: &SessionExport) -> String {
+    let mut text = format!("session {}\n", export.name);
+    if let Some(selected) = export.selected {
+        let _ = writeln!(text, "selected window: {}", selected);
+    }
+    for window in &export.windows {
+        let base = export.pane_base_index;
+        let mut layout = window.layout.clone();
+        layout.renumber(&mut |position| base + position);
+        let _ = writeln!(text, "\nwindow {}: {}", window.idx, window.name);
+        let _ = writeln!(text, "  layout: {}", layout);
+        for (position, pane) in window.panes.iter().enumerate() {
+            let active = if position == window.active {
+                " (active)"
+            } else {
+                ""
+            };
+            let number = base + position;
+            let _ = writeln!(text, "  pane {}{}: {}", number, active, pane.describe());
+        }
+        if window.input.is_some() {
+            text.push_str("  input recorded\n");
+        }
+    }
+    if !export.closed.is_empty() {
+        text.push_str("\nclosed windows:\n");
+    }
+    for closed in &export.closed {
+        let status = match closed.status {
+            Some(status) => format!("exited with {}", status),
+            None => "killed".to_string(),
+        };
+        let _ = writeln!(text, "  {}: {}, {}", closed.idx, closed.name, status);
+    }
+    text
+}
+
+/// Write `export` to a new directory at `path`, which must not exist yet.
+pub fn write(path: &Path, export: &SessionExport) -> io::Result<()> {
+    fs::create_dir(path)?;
+    fs::write(path.join("session.txt"), summary(export))?;
+    for window in &export.windows {
+        let dir = path.join(format!("window-{}", window.idx));
+        fs::create_dir(&dir)?;
+        for (position, pane) in window.panes.iter().enumerate() {
+            let contents = match &pane.contents {
+                Some(contents) => contents,
+                None => continue,
+            };
+            let name = format!("pane-{}", export.pane_base_index + position);
+            let text = text(pane.cols, pane.rows, contents);
+            fs::write(dir.join(&name).with_extension("txt"), text)?;
+            let mut ansi = fs::File::create(dir.join(name).with_extension("ansi"))?;
+            ansi.write_all(contents)?;
+            // Leave the terminal it is shown on as it was.
+            write!(ansi, "\x1b[0m\r\n")?;
+        }
+        if let Some(input) = &window.input {
+            fs::write(dir.join("input.txt"), input)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn export() -> SessionExport {
+        let pane =
+            |command: Option<&str>, status, contents: Option<&[u8]>| PaneExport {
+                applet: false,
+                command: command.map(String::from),
+                cwd: Some(PathBuf::from("/src")),
+                status,
+                cols: 10,
+                rows: 3,
+                contents: contents.map(<[u8]>::to_vec),
+            };
+        SessionExport {
+            name: "work".to_string(),
+            selected: Some(2),
+            windows: vec![WindowExport {
+                idx: 2,
+                name: "build".to_string(),
+                panes: vec![
+                    pane(None, None, Some(b"\x1b[1;1H$ make\x1b[2;1H\x1b[31mok")),
+                    pane(Some("make test"), Some(2), None),
+                ],
+                layout: "h(1:0,1:1)".parse().unwrap(),
+                active: 1,
+                input: Some("make\n".to_string()),
+            }],
+            closed: vec![ClosedWindow {
+                idx: 0,
+                name: "sh".to_string(),
+                status: Some(0),
+            }],
+            pane_base_index: 0,
+        }
+    }
+
+    #[test]
+    fn export_text() {
+        assert_eq!(text(10, 3, b"ab   \x1b[2;1Hc"), "ab\nc\n");
+        assert_eq!(text(10, 3, b"\x1b[3;1Hc"), "\n\nc\n");
+        assert_eq!(text(10, 3, b""), "");
+    }
+
+    #[test]
+    fn export_summary() {
+        assert_eq!(
+            summary(&export()),
+            "session work\n\
+             selected window: 2\n\
+             \n\
+             window 2: build\n  \
+               layout: h(1:0,1:1)\n  \
+               pane 0: the shell in /src, running\n  \
+               pane 1 (active): `make test` in /src, exited with 2, private\n  \
+               input recorded\n\
+             \n\
+             closed windows:\n  \
+               0: sh, exited with 0\n"
+        );
+    }
+
+    #[test]
+    fn export_write() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("export");
+        write(&path, &export()).unwrap();
+        let read = |name: &str| fs::read_to_string(path.join(name)).unwrap();
+        assert!(read("session.txt").starts_with("session work\n"));
+        assert_eq!(read("window-2/pane-0.txt"), "$ make\nok\n");
+        assert!(read("window-2/pane-0.ansi").contains("\x1b[31mok"));
+        assert_eq!(read("window-2/input.txt"), "make\n");
+        // Nothing is written of what a private pane shows.
+        assert!(!path.join("window-2/pane-1.txt").exists());
+        // An export is not written over another.
+        assert!(write(&path, &export()).is_err());
+    }
+
+    #[test]
+    fn export_pane_base_index() {
+        let export = SessionExport {
+            pane_base_index: 1,
+            ..export()
+        };
+        let summary = summary(&export);
+        assert!(summary.contains("layout: h(1:1,1:2)\n  pane 1: the shell"));
+        assert!(summary.contains("\n  pane 2 (active): `make test`"));
+        let dir = tempfile::tempdir().unwrap();
+        write(&dir.path().join("export"), &export).unwrap();
+        assert!(dir.path().join("export/window-2/pane-1.txt").exists());
+    }
+}
@@ -2,23 +2,30 @@
 
 use std::{
     cmp::{max, min, Ord, Ordering, PartialOrd},
-    collections::BTreeSet,
+    collections::{btree_map, BTreeMap, BTreeSet, VecDeque},
     convert::{TryFrom, TryInto},
     fmt,
     io::Write,
     iter::Iterator,
     marker::PhantomData,
     ops::{Index, IndexMut, Range},
+    str::FromStr,
 };
 
-use log::{debug, info, trace, warn};
-use termion::{
-    color::{self, Color as TermionColor},
-    cursor::Goto,
-};
-use crate::ansi::{
-    Attr, CharsetIndex, ClearMode, Color, CursorStyle, Handler, LineClearMode, Mode,
-    NamedColor, Rgb, StandardCharset, TabulationClearMode,
+use log::{debug, trace, warn};
+
+mod cell;
+mod palette;
+
+pub use cell::{Cell, Flags};
+pub use palette::{Palette, Theme};
+
+use crate::{
+    ansi::{
+        Attr, CharsetIndex, ClearMode, Color, CursorStyle, Handler, LineAttr,
+        LineClearMode, Mode, NamedColor, Rgb, StandardCharset, TabulationClearMode,
+    },
+    search::Search,
 };
 
 enum Displace {
@@ -44,9 +51,12 @@ impl CursorPos {
     }
 }
 
-impl From<CursorPos> for Goto {
-    fn from(p: CursorPos) -> Goto {
-        Goto(1 + p.col, 1 + p.row)
+/// Moves the cursor to a position when displayed.
+struct Goto(CursorPos);
+
+impl fmt::Display for Goto {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "\x1b[{};{}H", 1 + self.0.row, 1 + self.0.col)
     }
 }
 
@@ -65,14 +75,32 @@ impl Ord for CursorPos {
 #[derive(Clone)]
 struct Row<C: Clone + Copy> {
     buf: Vec<C>,
+    attr: LineAttr,
 }
 
 impl<C: Clone + Copy> Row<C> {
     pub fn new(cols: u16, fill: C) -> Row<C> {
         Row {
             buf: vec![fill; cols as usize],
+            attr: LineAttr::Normal,
         }
     }
+
+    /// Set every cell to `fill`, and the line back to normal size.
+    pub fn fill(&mut self, fill: C) {
+        self.buf.fill(fill);
+        self.attr = LineAttr::Normal;
+    }
+}
+
+/// The escape sequence selecting `attr` for the current line.
+fn line_attr_sequence(attr: LineAttr) -> &'static str {
+    match attr {
+        LineAttr::Normal => "\x1b#5",
+        LineAttr::DoubleWidth => "\x1b#6",
+        LineAttr::DoubleHeightTop => "\x1b#3",
+        LineAttr::DoubleHeightBottom => "\x1b#4",
+    }
 }
 
 struct GridBuffer<C: Clone + Copy> {
@@ -101,66 +129,285 @@ impl<C: Clone + Copy> IndexMut<CursorPos> for GridBuffer<C> {
     }
 }
 
-#[derive(Debug)]
-struct BoxColor(Color);
+/// The colours set by the application, over those of the window's theme.
+#[derive(Clone, Copy)]
+struct Colors<'a> {
+    palette: &'a Palette,
+    theme: Option<&'a Palette>,
+}
+
+impl Colors<'_> {
+    /// The colour at `index` if it differs from the outer terminal's.
+    fn overridden(&self, index: usize) -> Option<Rgb> {
+        self.palette
+            .overridden(index)
+            .or_else(|| self.theme.and_then(|theme| theme.overridden(index)))
+    }
 
-impl BoxColor {
-    fn new(c: Color) -> BoxColor {
-        BoxColor(c)
+    /// The colour at `index`, or `None` if `index` is out of range.
+    fn get(&self, index: usize) -> Option<Rgb> {
+        self.overridden(index).or_else(|| self.palette.get(index))
     }
 }
 
-impl TermionColor for BoxColor {
-    fn write_fg(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let tc = to_termion_color(self.0);
-        (*tc).write_fg(f)
+/// Selects the rendition of a cell when displayed.
+///
+/// The last field inverts the cell, for a screen in reverse video.
+struct Style<'a>(&'a Cell, Colors<'a>, bool);
+
+impl fmt::Display for Style<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let Style(cell, colors, reverse) = *self;
+        let mut flags = cell.flags;
+        if reverse {
+            flags.toggle(Flags::INVERSE);
+        }
+        f.write_str("\x1b[0")?;
+        let attrs = [
+            (Flags::BOLD, ";1"),
+            (Flags::DIM, ";2"),
+            (Flags::ITALIC, ";3"),
+            (Flags::UNDERLINE, ";4"),
+            (Flags::DOUBLE_UNDERLINE, ";21"),
+            (Flags::BLINK, ";5"),
+            (Flags::INVERSE, ";7"),
+            (Flags::HIDDEN, ";8"),
+            (Flags::STRIKETHROUGH, ";9"),
+        ];
+        for (flag, code) in attrs.iter() {
+            if flags.contains(*flag) {
+                f.write_str(code)?;
+            }
+        }
+        write_color(f, 38, cell.fg, colors)?;
+        write_color(f, 48, cell.bg, colors)?;
+        if let Some(color) = cell.underline_color {
+            write_color(f, 58, color, colors)?;
+        }
+        f.write_str("m")
     }
+}
 
-    fn write_bg(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let tc = to_termion_color(self.0);
-        (*tc).write_bg(f)
+/// Write the `;`-prefixed parameters selecting `color` with SGR `selector`.
+///
+/// Colours redefined in `colors` are sent as RGB; the rest are left to the outer
+/// terminal.
+fn write_color(
+    f: &mut fmt::Formatter,
+    selector: u8,
+    color: Color,
+    colors: Colors,
+) -> fmt::Result {
+    let index = match color {
+        Color::Spec(rgb) => return write_rgb(f, selector, rgb),
+        Color::Indexed(i) => usize::from(i),
+        Color::Named(
+            n @ (NamedColor::Foreground | NamedColor::Background | NamedColor::Cursor),
+        ) => n as usize,
+        Color::Named(n) => usize::from(named_color_index(n)),
+    };
+    if let Some(rgb) = colors.overridden(index) {
+        return write_rgb(f, selector, rgb);
+    }
+    match color {
+        Color::Named(NamedColor::Foreground) if selector == 38 => Ok(()),
+        Color::Named(NamedColor::Background) if selector == 48 => Ok(()),
+        Color::Named(n) => write!(f, ";{};5;{}", selector, named_color_index(n)),
+        _ => write!(f, ";{};5;{}", selector, index),
     }
 }
 
+fn write_rgb(f: &mut fmt::Formatter, selector: u8, rgb: Rgb) -> fmt::Result {
+    write!(f, ";{};2;{};{};{}", selector, rgb.r, rgb.g, rgb.b)
+}
+
 // Don't include this as it's basically processing an enum.
 #[cfg(not(tarpaulin_include))]
-fn to_termion_color(c: Color) -> Box<dyn TermionColor> {
+fn named_color_index(c: NamedColor) -> u8 {
     use NamedColor::*;
 
     match c {
-        Color::Named(n) => match n {
-            Cursor => Box::new(color::LightWhite),
-            Foreground => Box::new(color::LightWhite),
-            BrightForeground => Box::new(color::LightWhite),
-            DimForeground => Box::new(color::White),
-            Background => Box::new(color::Black),
-            Black => Box::new(color::Black),
-            Red => Box::new(color::Red),
-            Green => Box::new(color::Green),
-            Yellow => Box::new(color::Yellow),
-            Blue => Box::new(color::Blue),
-            Magenta => Box::new(color::Magenta),
-            Cyan => Box::new(color::Cyan),
-            White => Box::new(color::White),
-            DimBlack => Box::new(color::Black),
-            DimRed => Box::new(color::Red),
-            DimGreen => Box::new(color::Green),
-            DimYellow => Box::new(color::Yellow),
-            DimBlue => Box::new(color::Blue),
-            DimMagenta => Box::new(color::Magenta),
-            DimCyan => Box::new(color::Cyan),
-            DimWhite => Box::new(color::White),
-            BrightBlack => Box::new(color::LightBlack),
-            BrightRed => Box::new(color::LightRed),
-            BrightGreen => Box::new(color::LightGreen),
-            BrightYellow => Box::new(color::LightYellow),
-            BrightBlue => Box::new(color::LightBlue),
-            BrightMagenta => Box::new(color::LightMagenta),
-            BrightCyan => Box::new(color::LightCyan),
-            BrightWhite => Box::new(color::LightWhite),
-        },
-        Color::Spec(rgb) => Box::new(color::Rgb(rgb.r, rgb.g, rgb.b)),
-        Color::Indexed(i) => Box::new(color::AnsiValue(i)),
+        Cursor | Foreground | BrightForeground => 15,
+        DimForeground => 7,
+        Background => 0,
+        Black | DimBlack => 0,
+        Red | DimRed => 1,
+        Green | DimGreen => 2,
+        Yellow | DimYellow => 3,
+        Blue | DimBlue => 4,
+        Magenta | DimMagenta => 5,
+        Cyan | DimCyan => 6,
+        White | DimWhite => 7,
+        BrightBlack => 8,
+        BrightRed => 9,
+        BrightGreen => 10,
+        BrightYellow => 11,
+        BrightBlue => 12,
+        BrightMagenta => 13,
+        BrightCyan => 14,
+        BrightWhite => 15,
+    }
+}
+
+/// Tab stops every `TAB_WIDTH` columns within `cols`.
+fn default_tab_stops(cols: Range<u16>) -> impl Iterator<Item = u16> {
+    cols.filter(|col| col % TAB_WIDTH == 0)
+}
+
+/// The position of the character set `index` in `Grid::charsets`.
+fn charset_slot(index: CharsetIndex) -> usize {
+    match index {
+        CharsetIndex::G0 => 0,
+        CharsetIndex::G1 => 1,
+        CharsetIndex::G2 => 2,
+        CharsetIndex::G3 => 3,
+    }
+}
+
+/// An event raised by a `Grid` that an embedder may want to act upon.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GridEvent {
+    /// The title was changed; `None` means it was reset.
+    TitleChanged(Option<String>),
+    /// The bell was rung.
+    Bell,
+    /// The application asked to store `data` (base64-encoded) in `clipboard`.
+    ClipboardStore { clipboard: u8, data: Vec<u8> },
+    /// The application asked for the contents of `clipboard`.
+    ///
+    /// Replies should be terminated with `terminator`.
+    ClipboardLoad { clipboard: u8, terminator: String },
+    /// The application queried the colour at `index`.
+    ///
+    /// The grid replies itself with the colour it draws; this tells an
+    /// embedder the query was made.
+    ColorQuery {
+        prefix: u8,
+        index: usize,
+        terminator: String,
+    },
+}
+
+/// A callback invoked with each `GridEvent`.
+pub type GridEventCallback = Box<dyn FnMut(&GridEvent) + Send>;
+
+/// Distance between the default tab stops.
+const TAB_WIDTH: u16 = 8;
+
+/// Maximum number of titles kept by `push_title`.
+const TITLE_STACK_MAX: usize = 4096;
+
+/// Maximum number of lines kept by `set_line_log` until they are taken.
+const LINE_LOG_LIMIT: usize = 1000;
+
+/// Maximum number of rows scrolled off the top kept, to scroll back through.
+const SCROLLBACK_LIMIT: usize = 2000;
+
+/// The fewest columns and rows a grid has, whatever size it is asked to be, as
+/// a terminal may briefly say it has none.
+const MIN_SIZE: u16 = 1;
+
+/// A count of rows to scroll by, of which any more than `u16::MAX` could only
+/// clear the region like that many.
+fn scroll_count(rows: usize) -> u16 {
+    u16::try_from(rows).unwrap_or(u16::MAX)
+}
+
+/// How text with the blink attribute (SGR 5) is shown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TextBlink {
+    /// Hide and show blinking text as the renderer's blink phase changes.
+    #[default]
+    Animate,
+    /// Pass the attribute on, for the outer terminal to blink.
+    Terminal,
+    /// Show blinking text steadily.
+    Off,
+}
+
+impl FromStr for TextBlink {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<TextBlink, String> {
+        match s {
+            "animate" => Ok(TextBlink::Animate),
+            "terminal" => Ok(TextBlink::Terminal),
+            "off" => Ok(TextBlink::Off),
+            _ => Err(format!("invalid text blink setting: {}", s)),
+        }
+    }
+}
+
+/// How an application asked for mouse events to be reported to it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MouseEncoding {
+    /// `ESC [ M` and three bytes: the button, column and row, each plus 32.
+    Default,
+    /// `ESC [ < BUTTON ; COL ; ROW M`, or `m` for a release (DECSET 1006).
+    Sgr,
+}
+
+/// A mouse event, to be reported to an application.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MouseReport {
+    /// The button as xterm numbers it: 0 to 2 for the left, middle and right
+    /// buttons and 64 and 65 for the wheel, plus 4 with Shift, 8 with Alt, 16
+    /// with Control and 32 for a drag.
+    pub button: u8,
+    /// Whether the button was released, rather than pressed or dragged.
+    pub release: bool,
+    /// The column, counted from 0.
+    pub col: u16,
+    /// The row, counted from 0.
+    pub row: u16,
+}
+
+impl MouseReport {
+    /// The bytes reporting the event in `encoding`. The default encoding cannot
+    /// say which button was released, nor where past column or row 223.
+    pub fn encode(&self, encoding: MouseEncoding) -> Vec<u8> {
+        match encoding {
+            MouseEncoding::Sgr => {
+                let end = if self.release { 'm' } else { 'M' };
+                let (col, row) = (self.col + 1, self.row + 1);
+                format!("\x1b[<{};{};{}{}", self.button, col, row, end).into_bytes()
+            }
+            MouseEncoding::Default => {
+                let button = match self.release {
+                    true => 3 | (self.button & 0x1c),
+                    false => self.button,
+                };
+                let position = |n: u16| (n.min(222) + 33) as u8;
+                let bytes = [button + 32, position(self.col), position(self.row)];
+                [b"\x1b[M".as_slice(), &bytes].concat()
+            }
+        }
+    }
+}
+
+/// Part of a row which has changed since the grid was last drawn.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DamageSpan {
+    pub row: u16,
+    /// The columns changed, possibly along with some unchanged between them.
+    pub cols: Range<u16>,
+}
+
+/// The parts of a grid which have changed since it was last drawn, one span per
+/// row, from the top.
+pub struct DamageIter<'a> {
+    spans: btree_map::Iter<'a, u16, Range<u16>>,
+}
+
+impl Iterator for DamageIter<'_> {
+    type Item = DamageSpan;
+
+    fn next(&mut self) -> Option<DamageSpan> {
+        let (row, cols) = self.spans.next()?;
+        Some(DamageSpan {
+            row: *row,
+            cols: cols.clone(),
+        })
     }
 }
 
@@ -169,54 +416,525 @@ pub struct Grid<W> {
     cursor: CursorPos,
     saved_cursor: CursorPos,
     scrolling_region: Range<u16>,
+    /// Where the top left corner is drawn on the terminal.
+    origin: CursorPos,
     width: u16,
     height: u16,
     buffer: GridBuffer<Cell>,
-    dirty_rows: BTreeSet<u16>,
+    /// The columns of each row changed since the last draw.
+    damage: BTreeMap<u16, Range<u16>>,
     sgr_template: Cell,
+    tab_stops: BTreeSet<u16>,
+    charsets: [StandardCharset; 4],
+    active_charset: CharsetIndex,
+    /// The charset of the next character printed, if not the active one.
+    single_shift: Option<CharsetIndex>,
+    cursor_style: CursorStyle,
+    reverse_video: bool,
+    /// Whether the application asked for pasted text to be bracketed.
+    bracketed_paste: bool,
+    /// Whether the application asked for mouse events, and for them in the SGR
+    /// encoding.
+    mouse_reporting: bool,
+    sgr_mouse: bool,
+    palette: Palette,
+    theme: Option<Theme>,
+    scale_line_attrs: bool,
+    text_blink: TextBlink,
+    blink_visible: bool,
+    title: Option<String>,
+    title_stack: Vec<Option<String>>,
+    /// The rows the cursor has left by line feeds and wrapping, oldest first, if
+    /// they are being kept.
+    line_log: Option<VecDeque<String>>,
+    /// Whether what the application writes is kept out of the log and the
+    /// scrollback.
+    private: bool,
+    /// The rows scrolled off the top of the screen, oldest first.
+    scrollback: VecDeque<Row<Cell>>,
+    /// How many rows back through the scrollback the grid is shown, or 0 if it
+    /// shows its screen.
+    view_offset: usize,
+    /// The search whose matches are highlighted, if any.
+    highlight: Option<Search>,
+    listeners: Vec<GridEventCallback>,
     _phantom: PhantomData<W>,
 }
 
 impl<W: Write> Grid<W> {
-    /// Initialise an empty display buffer.
+    /// Initialise an empty display buffer, at least one cell in size.
     pub fn new(width: u16, height: u16) -> Grid<W> {
-        let dirty_rows = (0..height).collect();
+        let (width, height) = (width.max(MIN_SIZE), height.max(MIN_SIZE));
+        let damage = (0..height).map(|row| (row, 0..width)).collect();
         Grid {
             cursor: Default::default(),
             saved_cursor: Default::default(),
             scrolling_region: 0..height,
+            origin: CursorPos::default(),
             width,
             height,
             buffer: GridBuffer::new(width, height, Cell::default()),
-            dirty_rows,
+            damage,
             sgr_template: Cell::default(),
+            tab_stops: default_tab_stops(0..width).collect(),
+            charsets: Default::default(),
+            active_charset: CharsetIndex::default(),
+            single_shift: None,
+            cursor_style: CursorStyle::default(),
+            reverse_video: false,
+            bracketed_paste: false,
+            mouse_reporting: false,
+            sgr_mouse: false,
+            palette: Palette::default(),
+            theme: None,
+            scale_line_attrs: false,
+            text_blink: TextBlink::default(),
+            blink_visible: true,
+            title: None,
+            title_stack: Vec::new(),
+            line_log: None,
+            private: false,
+            scrollback: VecDeque::new(),
+            view_offset: 0,
+            highlight: None,
+            listeners: Vec::new(),
             _phantom: Default::default(),
         }
     }
 
+    /// Register `callback` to be invoked with every subsequent `GridEvent`.
+    pub fn subscribe<F>(&mut self, callback: F)
+    where
+        F: FnMut(&GridEvent) + Send + 'static,
+    {
+        self.listeners.push(Box::new(callback));
+    }
+
+    /// The cell at column `col` of row `row`, if it is within the grid.
+    pub fn cell(&self, col: u16, row: u16) -> Option<&Cell> {
+        self.buffer
+            .rows
+            .get(row as usize)
+            .and_then(|r| r.buf.get(col as usize))
+    }
+
+    /// The characters of row `row`, if it is within the grid.
+    pub fn row_text(&self, row: u16) -> Option<String> {
+        let row = self.buffer.rows.get(row as usize)?;
+        Some(row.buf.iter().map(|cell| cell.c).collect())
+    }
+
+    /// The characters of the cursor row before the cursor or to the last
+    /// non-blank, whichever is further.
+    pub fn cursor_line(&self) -> String {
+        let text = self.row_text(self.cursor.row).unwrap_or_default();
+        let end = text.trim_end().chars().count().max(self.cursor.col.into());
+        text.chars().take(end).collect()
+    }
+
+    /// Choose whether to keep the lines the cursor leaves, for `take_lines`.
+    pub fn set_line_log(&mut self, log: bool) {
+        if log != self.line_log.is_some() {
+            self.line_log = if log { Some(VecDeque::new()) } else { None };
+        }
+    }
+
+    /// The lines the cursor has left since the last call, oldest first,
+    /// without trailing blanks, if `set_line_log` is on. Only the last
+    /// `LINE_LOG_LIMIT` are kept.
+    pub fn take_lines(&mut self) -> Vec<String> {
+        match &mut self.line_log {
+            Some(log) => log.drain(..).collect(),
+            None => Vec::new(),
+        }
+    }
+
+    fn log_line(&mut self) {
+        if self.line_log.is_none() {
+            return;
+        }
+        let text = self.row_text(self.cursor.row).unwrap_or_default();
+        let log = self.line_log.as_mut().unwrap();
+        if log.len() == LINE_LOG_LIMIT {
+            log.pop_front();
+        }
+        log.push_back(text.trim_end().to_string());
+    }
+
+    /// How many rows back through the rows scrolled off the top the grid is
+    /// shown, or 0 if it shows its screen.
+    pub fn view_offset(&self) -> usize {
+        self.view_offset
+    }
+
+    /// Show the grid `rows` further back through the rows scrolled off the top,
+    /// or forward if negative, as far as the oldest kept and the screen.
+    pub fn scroll_view(&mut self, rows: isize) {
+        let offset = self.view_offset.saturating_add_signed(rows);
+        let offset = offset.min(self.scrollback.len());
+        if offset != self.view_offset {
+            self.view_offset = offset;
+            self.mark_all_dirty();
+        }
+    }
+
+    /// How many lines can be searched: the rows scrolled off the top which are
+    /// kept, oldest first, then those of the screen.
+    pub fn line_count(&self) -> usize {
+        self.scrollback.len() + self.height as usize
+    }
+
+    /// The characters of line `line`, counting from the oldest row kept.
+    pub fn line_text(&self, line: usize) -> Option<String> {
+        let row = match self.scrollback.get(line) {
+            Some(row) => row,
+            None => self.buffer.rows.get(line - self.scrollback.len())?,
+        };
+        Some(row.buf.iter().map(|cell| cell.c).collect())
+    }
+
+    /// The line shown at the top, as `line_text` counts.
+    pub fn view_top(&self) -> usize {
+        self.scrollback.len() - self.view_offset
+    }
+
+    /// Show line `line` at the top, or the screen if the line is on it.
+    pub fn show_line(&mut self, line: usize) {
+        let offset = self.scrollback.len().saturating_sub(line);
+        self.scroll_view(offset as isize - self.view_offset as isize);
+    }
+
+    /// The first line from `line`, or after it if `after`, with a match of
+    /// `search`, going around at the ends, if there is one.
+    pub fn find(&self, search: &Search, line: usize, after: bool) -> Option<usize> {
+        let text = |line| self.line_text(line);
+        search.find(self.line_count(), line, after, text)
+    }
+
+    /// Highlight the matches of `search` wherever they are shown, or none if
+    /// `None`.
+    pub fn set_highlight(&mut self, search: Option<Search>) {
+        if search != self.highlight {
+            self.highlight = search;
+            self.mark_all_dirty();
+        }
+    }
+
+    /// The columns of `cells` in matches of the search highlighted.
+    fn highlighted(&self, cells: &[Cell]) -> Vec<Range<usize>> {
+        match &self.highlight {
+            Some(search) => {
+                let text: String = cells.iter().map(|cell| cell.c).collect();
+                search.char_matches(&text)
+            }
+            None => Vec::new(),
+        }
+    }
+
+    /// Keep the first `rows` rows, about to scroll off the top, in the
+    /// scrollback, leaving a view back through it where it was, unless the
+    /// grid is private.
+    fn save_rows(&mut self, rows: u16) {
+        if self.private {
+            return;
+        }
+        for row in &self.buffer.rows[..rows as usize] {
+            if self.scrollback.len() == SCROLLBACK_LIMIT {
+                self.scrollback.pop_front();
+            } else if self.view_offset > 0 {
+                self.view_offset += 1;
+            }
+            self.scrollback.push_back(row.clone());
+        }
+        if self.view_offset > 0 {
+            self.mark_all_dirty();
+        }
+    }
+
+    /// The cursor style requested by the application.
+    pub fn cursor_style(&self) -> CursorStyle {
+        self.cursor_style
+    }
+
+    /// Whether the application asked for pasted text to be bracketed by
+    /// `ESC [ 200 ~` and `ESC [ 201 ~` (DECSET 2004).
+    pub fn bracketed_paste(&self) -> bool {
+        self.bracketed_paste
+    }
+
+    /// How the application asked for mouse events to be reported to it
+    /// (DECSET 1000, 1002 or 1003, and 1006), or `None` if it did not.
+    pub fn mouse_reporting(&self) -> Option<MouseEncoding> {
+        match (self.mouse_reporting, self.sgr_mouse) {
+            (false, _) => None,
+            (true, false) => Some(MouseEncoding::Default),
+            (true, true) => Some(MouseEncoding::Sgr),
+        }
+    }
+
+    /// The colours as redefined by the application.
+    pub fn palette(&self) -> &Palette {
+        &self.palette
+    }
+
+    /// The theme applied to this grid.
+    pub fn theme(&self) -> Option<&Theme> {
+        self.theme.as_ref()
+    }
+
+    /// Apply `theme` beneath the colours set by the application, or remove the
+    /// theme if `None`.
+    pub fn set_theme(&mut self, theme: Option<Theme>) {
+        self.theme = theme;
+        self.mark_all_dirty();
+    }
+
+    fn colors(&self) -> Colors<'_> {
+        Colors {
+            palette: &self.palette,
+            theme: self.theme.as_ref().map(|theme| &theme.palette),
+        }
+    }
+
+    /// The current title, as last set by the application.
+    pub fn title(&self) -> Option<&str> {
+        self.title.as_deref()
+    }
+
+    /// Keep what the application writes, e.g. a password, out of the log and
+    /// the scrollback while `private`.
+    pub fn set_private(&mut self, private: bool) {
+        self.private = private;
+    }
+
+    /// Whether what the application writes is kept out of the log and the
+    /// scrollback.
+    pub fn is_private(&self) -> bool {
+        self.private
+    }
+
+    fn emit(&mut self, event: GridEvent) {
+        if !self.private {
+            trace!("event: {:?}", event);
+        }
+        for listener in self.listeners.iter_mut() {
+            listener(&event);
+        }
+    }
+
     /// Mark all rows as dirty.
     pub fn mark_all_dirty(&mut self) {
-        self.dirty_rows.clear();
-        self.dirty_rows.extend(0..self.height);
+        self.damage = (0..self.height).map(|row| (row, 0..self.width)).collect();
     }
 
-    /// Draw this buffer to `term`.
+    /// The parts of the grid changed since it was last drawn, or since
+    /// `clear_damage`, for frontends which read the cells themselves.
+    pub fn damage(&self) -> DamageIter<'_> {
+        DamageIter {
+            spans: self.damage.iter(),
+        }
+    }
+
+    /// Forget the changes, as if the grid had been drawn.
+    pub fn clear_damage(&mut self) {
+        self.damage.clear();
+    }
+
+    /// Record that columns `cols` of `row` have changed.
+    fn add_damage(&mut self, row: u16, cols: Range<u16>) {
+        if cols.is_empty() {
+            return;
+        }
+        let span = self.damage.entry(row).or_insert(cols.clone());
+        *span = min(span.start, cols.start)..max(span.end, cols.end);
+    }
+
+    /// Record that the whole of `row` has changed.
+    fn damage_row(&mut self, row: u16) {
+        self.add_damage(row, 0..self.width);
+    }
+
+    /// Draw the parts of this buffer which have changed to `term`.
     pub fn draw<T: Write>(&mut self, term: &mut T) {
-        for row in self.dirty_rows.iter() {
-            let start = CursorPos { row: *row, col: 0 };
-            let row: String = self.buffer.rows[*row as usize]
-                .buf
-                .iter()
-                .map(|cell| format!("{}{}", color::Fg(BoxColor::new(cell.fg)), cell.c))
-                .collect();
-            write!(term, "{}{}", Goto::from(start), &row).unwrap();
+        if self.view_offset > 0 {
+            if !self.damage.is_empty() {
+                self.draw_view(term);
+            }
+            self.damage.clear();
+            return;
+        }
+        for (row, cols) in self.damage.iter() {
+            let width = self.row_width(*row);
+            let row_idx = *row;
+            let row = &self.buffer.rows[row_idx as usize];
+            let found = self.highlighted(&row.buf);
+            // Columns of lines with attributes are not where they seem, so the
+            // whole line is drawn, as are lines with matches which may have
+            // changed.
+            let cols = if row.attr == LineAttr::Normal && self.highlight.is_none() {
+                cols.start.min(width)..cols.end.min(width)
+            } else {
+                0..width
+            };
+            let start = CursorPos::at(cols.start, row_idx);
+            let scaled = self.scale_line_attrs && row.attr != LineAttr::Normal;
+            let mut text = String::new();
+            let mut style: Option<Cell> = None;
+            for (col, cell) in row.buf.iter().enumerate() {
+                if !cols.contains(&(col as u16)) {
+                    continue;
+                }
+                let cell = &self.shown_at(cell, col, &found);
+                if !style.is_some_and(|s| s.same_style(cell)) {
+                    text.push_str(
+                        &Style(cell, self.colors(), self.reverse_video).to_string(),
+                    );
+                    style = Some(*cell);
+                }
+                text.push(cell.c);
+                if scaled {
+                    text.push(' ');
+                }
+            }
+            // Drawing part of a line leaves its attribute as it was.
+            let attr = if self.scale_line_attrs || cols.start > 0 {
+                ""
+            } else {
+                line_attr_sequence(row.attr)
+            };
+            write!(term, "{}{}{}", self.goto_screen(start), attr, &text).unwrap();
+        }
+        let mut cursor = self.cursor;
+        if self.scale_line_attrs && self.row_width(cursor.row) < self.width {
+            cursor.col *= 2;
+        }
+        write!(term, "\x1b[0m{}", self.goto_screen(cursor)).unwrap();
+        self.damage.clear();
+    }
+
+    /// Draw every row of the view back through the scrollback, with how far
+    /// back it is at the top right.
+    fn draw_view<T: Write>(&self, term: &mut T) {
+        let saved = self.scrollback.len();
+        let top = saved - self.view_offset;
+        for row in 0..self.height {
+            let idx = top + row as usize;
+            let cells = match self.scrollback.get(idx) {
+                Some(saved) => &saved.buf,
+                None => &self.buffer.rows[idx - saved].buf,
+            };
+            let found = self.highlighted(cells);
+            let mut text = String::new();
+            let mut style: Option<Cell> = None;
+            for col in 0..self.width as usize {
+                // Rows saved at another width are cut off or filled out.
+                let cell = cells.get(col).copied().unwrap_or_default();
+                let cell = &self.shown_at(&cell, col, &found);
+                if !style.is_some_and(|s| s.same_style(cell)) {
+                    text.push_str(
+                        &Style(cell, self.colors(), self.reverse_video).to_string(),
+                    );
+                    style = Some(*cell);
+                }
+                text.push(cell.c);
+            }
+            let start = CursorPos::at(0, row);
+            let attr = match self.scale_line_attrs {
+                true => "",
+                false => line_attr_sequence(LineAttr::Normal),
+            };
+            write!(term, "{}{}{}", self.goto_screen(start), attr, &text).unwrap();
+        }
+        let position = format!("[{}/{}]", self.view_offset, saved);
+        if let Some(col) = self.width.checked_sub(position.len() as u16) {
+            let start = self.goto_screen(CursorPos::at(col, 0));
+            write!(term, "{}\x1b[0;7m{}", start, position).unwrap();
+        }
+        write!(term, "\x1b[0m").unwrap();
+    }
+
+    /// The whole buffer as escape sequences drawing it on a terminal of its
+    /// size, from the top left corner, e.g. to capture what a pane shows. What
+    /// is left to draw is left as it was.
+    pub fn contents(&mut self) -> Vec<u8> {
+        let damage = std::mem::take(&mut self.damage);
+        let origin = std::mem::take(&mut self.origin);
+        let view_offset = std::mem::take(&mut self.view_offset);
+        let highlight = self.highlight.take();
+        self.mark_all_dirty();
+        let mut contents = Vec::new();
+        self.draw(&mut contents);
+        self.origin = origin;
+        self.damage = damage;
+        self.view_offset = view_offset;
+        self.highlight = highlight;
+        contents
+    }
+
+    /// Draw this buffer with its top left corner at `col`, `row` of the terminal.
+    pub fn set_origin(&mut self, col: u16, row: u16) {
+        let origin = CursorPos::at(col, row);
+        if origin != self.origin {
+            self.origin = origin;
+            self.mark_all_dirty();
+        }
+    }
+
+    /// Moves the terminal's cursor to `pos` in this buffer.
+    fn goto_screen(&self, pos: CursorPos) -> Goto {
+        Goto(CursorPos::at(
+            self.origin.col + pos.col,
+            self.origin.row + pos.row,
+        ))
+    }
+
+    /// Choose whether double-width and double-height lines are drawn by spacing
+    /// out their characters, for outer terminals without DECDWL and DECDHL.
+    pub fn set_scale_line_attrs(&mut self, scale: bool) {
+        if scale != self.scale_line_attrs {
+            self.scale_line_attrs = scale;
+            self.mark_all_dirty();
+        }
+    }
+
+    /// Choose how text with the blink attribute is shown.
+    pub fn set_text_blink(&mut self, text_blink: TextBlink) {
+        if text_blink != self.text_blink {
+            self.text_blink = text_blink;
+            self.mark_all_dirty();
+        }
+    }
+
+    /// Show or hide blinking text, when it is animated by the renderer.
+    pub fn set_blink_phase(&mut self, visible: bool) {
+        if visible == self.blink_visible {
+            return;
+        }
+        self.blink_visible = visible;
+        if self.text_blink != TextBlink::Animate {
+            return;
+        }
+        let blinking = |cell: &Cell| cell.flags.contains(Flags::BLINK);
+        let spans: Vec<_> = self
+            .buffer
+            .rows
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, row)| {
+                let start = row.buf.iter().position(blinking)?;
+                let end = row.buf.iter().rposition(blinking)? + 1;
+                Some((idx as u16, start as u16..end as u16))
+            })
+            .collect();
+        for (row, cols) in spans {
+            self.add_damage(row, cols);
         }
-        write!(term, "{}", Goto::from(self.cursor)).unwrap();
-        self.dirty_rows.clear();
     }
 
-    /// Resize this grid (not its connected PTY).
+    /// Resize this grid (not its connected PTY), to at least one cell.
     pub fn resize(&mut self, new_width: u16, new_height: u16) {
+        let (new_width, new_height) =
+            (new_width.max(MIN_SIZE), new_height.max(MIN_SIZE));
         // TODO: support re-flowing
         if new_height < self.height {
             let end = if self.cursor.col == 0 {
@@ -240,8 +958,12 @@ impl<W: Write> Grid<W> {
             .resize(self.height as usize, Row::new(self.width, Cell::default()));
 
         if new_width < self.width {
-            self.cursor.row = min(self.cursor.row, new_width - 1);
-            self.saved_cursor.row = min(self.saved_cursor.row, new_width - 1);
+            self.cursor.col = min(self.cursor.col, new_width - 1);
+            self.saved_cursor.col = min(self.saved_cursor.col, new_width - 1);
+            self.tab_stops.split_off(&new_width);
+        } else {
+            self.tab_stops
+                .extend(default_tab_stops(self.width..new_width));
         }
         self.width = new_width;
         self.buffer
@@ -252,24 +974,124 @@ impl<W: Write> Grid<W> {
         self.mark_all_dirty();
     }
 
+    /// The number of columns usable in `row`, accounting for its line attribute.
+    ///
+    /// The cursor may sit one row past the end of the grid, which is treated as a
+    /// normal line.
+    fn row_width(&self, row: u16) -> u16 {
+        match self.buffer.rows.get(row as usize).map(|r| r.attr) {
+            Some(LineAttr::Normal) | None => self.width,
+            Some(_) => max(1, self.width / 2),
+        }
+    }
+
+    fn set_reverse_video(&mut self, reverse: bool) {
+        if reverse != self.reverse_video {
+            self.reverse_video = reverse;
+            self.mark_all_dirty();
+        }
+    }
+
+    /// `cell` as it should be drawn, with blinking resolved by `text_blink`.
+    fn shown(&self, cell: &Cell) -> Cell {
+        let mut cell = *cell;
+        if cell.flags.contains(Flags::BLINK) {
+            match self.text_blink {
+                TextBlink::Terminal => return cell,
+                TextBlink::Animate if !self.blink_visible => {
+                    cell.flags.insert(Flags::HIDDEN)
+                }
+                TextBlink::Animate | TextBlink::Off => (),
+            }
+            cell.flags.remove(Flags::BLINK);
+        }
+        cell
+    }
+
+    /// `cell`, at column `col`, as shown: inverted if it is in one of the
+    /// columns `found` by the search highlighted.
+    fn shown_at(&self, cell: &Cell, col: usize, found: &[Range<usize>]) -> Cell {
+        let mut cell = self.shown(cell);
+        if found.iter().any(|found| found.contains(&col)) {
+            cell.flags.toggle(Flags::INVERSE);
+        }
+        cell
+    }
+
+    /// The cell left behind by erasing, which keeps the current background colour.
+    fn blank_cell(&self) -> Cell {
+        Cell {
+            c: ' ',
+            bg: self.sgr_template.bg,
+            ..Cell::default()
+        }
+    }
+
+    /// Erase part of the cursor row (EL), sparing protected cells if `selective`.
+    fn erase_in_line(&mut self, mode: LineClearMode, selective: bool) {
+        let blank = self.blank_cell();
+        let range = match mode {
+            LineClearMode::All => 0..(self.width as usize),
+            LineClearMode::Left => 0..(self.cursor.col as usize),
+            LineClearMode::Right => (self.cursor.col as usize)..(self.width as usize),
+        };
+        let cols = range.start as u16..range.end as u16;
+        self.add_damage(self.cursor.row, cols);
+        self.buffer.rows[self.cursor.row as usize].buf[range]
+            .iter_mut()
+            .filter(|cell| !(selective && cell.flags.contains(Flags::PROTECTED)))
+            .for_each(|i| *i = blank);
+    }
+
+    /// Erase part of the screen (ED), sparing protected cells if `selective`.
+    fn erase_in_display(&mut self, mode: ClearMode, selective: bool) {
+        if matches!(mode, ClearMode::Saved) {
+            self.scrollback.clear();
+            self.scroll_view(isize::MIN);
+        }
+        let blank = self.blank_cell();
+        let range = match mode {
+            ClearMode::All | ClearMode::Saved => {
+                CursorPos::at(0, 0)..CursorPos::at(0, self.height)
+            }
+            ClearMode::Above => CursorPos::at(0, 0)..self.cursor,
+            ClearMode::Below => self.cursor..CursorPos::at(0, self.height),
+        };
+        for row in range.start.row..=range.end.row {
+            for col in 0..self.width {
+                let pos = CursorPos::at(col, row);
+                if range.contains(&pos)
+                    && !(selective
+                        && self.cell_at(pos).flags.contains(Flags::PROTECTED))
+                {
+                    *self.cell_at_mut(pos) = blank;
+                }
+            }
+        }
+    }
+
     fn cell_at(&self, pos: CursorPos) -> &Cell {
         &self.buffer[pos]
     }
 
     fn cell_at_mut(&mut self, pos: CursorPos) -> &mut Cell {
-        self.dirty_rows.insert(pos.row);
+        self.add_damage(pos.row, pos.col..pos.col + 1);
         &mut self.buffer[pos]
     }
 
     fn move_horizontal(&mut self, displacement: Displace) {
+        let width = self.row_width(self.cursor.row) as i64;
         self.cursor.col = match displacement {
-            Displace::Absolute(offset) => max(0, min(self.width as i64 - 1, offset)),
-            Displace::Relative(offset) => max(
-                0,
-                min(self.width as i64 - 1, self.cursor.col as i64 + offset),
-            ),
+            Displace::Absolute(offset) => max(0, min(width - 1, offset)),
+            Displace::Relative(offset) => {
+                max(0, min(width - 1, self.cursor.col as i64 + offset))
+            }
             Displace::ToStart => 0,
-            Displace::ToTabStop => ((self.cursor.col + 8) & !7).into(),
+            Displace::ToTabStop => self
+                .tab_stops
+                .range(self.cursor.col + 1..)
+                .next()
+                .map_or(width - 1, |&col| min(width - 1, col.into())),
         }
         .try_into()
         .unwrap();
@@ -291,37 +1113,72 @@ impl<W: Write> Grid<W> {
         .try_into()
         .unwrap();
         // no scrolling
+        self.cursor.col = min(self.cursor.col, self.row_width(self.cursor.row) - 1);
     }
 
     fn scroll_up_in_region(&mut self, start: u16, end: u16, lines: u16) {
         // Move text UP
         trace!("SU ({}), rows: ({}, {})", lines, start, end);
+        // Scrolling by more than the region only clears it.
+        let lines = min(lines, end.saturating_sub(start));
         if lines < 1 {
             return;
         }
+        if start == 0 {
+            self.save_rows(lines);
+        }
+        let blank = self.blank_cell();
+        if lines == end - start {
+            self.clear_rows(start..end, blank);
+            return;
+        }
         for row in start..end {
+            self.buffer.rows[row as usize].attr = if row + lines < end {
+                self.buffer.rows[(row + lines) as usize].attr
+            } else {
+                LineAttr::Normal
+            };
             for col in 0..self.width {
                 *self.cell_at_mut(CursorPos { col, row }) = if row + lines < end {
                     *self.cell_at(CursorPos::at(col, row + lines))
                 } else {
-                    Cell::default()
+                    blank
                 };
             }
         }
     }
 
+    /// Fill `rows` with `blank` a row at a time.
+    fn clear_rows(&mut self, rows: Range<u16>, blank: Cell) {
+        for row in rows {
+            self.buffer.rows[row as usize].fill(blank);
+            self.damage_row(row);
+        }
+    }
+
     fn scroll_down_in_region(&mut self, start: u16, end: u16, lines: u16) {
         // Move text DOWN
         trace!("SD ({}), rows ({}, {})", lines, start, end);
+        let lines = min(lines, end.saturating_sub(start));
         if lines < 1 {
             return;
         }
+        let blank = self.blank_cell();
+        if lines == end - start {
+            self.clear_rows(start..end, blank);
+            return;
+        }
         for row in (start..end).rev() {
+            self.buffer.rows[row as usize].attr = if row >= lines + start {
+                self.buffer.rows[(row - lines) as usize].attr
+            } else {
+                LineAttr::Normal
+            };
             for col in 0..self.width {
                 *self.cell_at_mut(CursorPos { col, row }) = if row >= lines + start {
                     *self.cell_at(CursorPos::at(col, row - lines))
                 } else {
-                    Cell::default()
+                    blank
                 };
             }
         }
@@ -329,13 +1186,34 @@ impl<W: Write> Grid<W> {
 }
 
 impl<W: Write> Handler<W> for Grid<W> {
+    fn is_private(&self) -> bool {
+        self.private
+    }
+
     fn set_title(&mut self, title: Option<&str>) {
-        // TODO
-        info!("set title: {:?}", title);
+        self.title = title.map(str::to_owned);
+        self.emit(GridEvent::TitleChanged(self.title.clone()));
     }
 
-    fn set_cursor_style(&mut self, _: Option<CursorStyle>) {
-        // TODO
+    fn set_line_attribute(&mut self, attr: LineAttr) {
+        // After the last column of the last row, the cursor waits past it to
+        // wrap, still on that row.
+        let row = min(self.cursor.row, self.height - 1);
+        match self.buffer.rows.get_mut(row as usize) {
+            Some(line) => line.attr = attr,
+            None => return,
+        }
+        let width = self.row_width(row);
+        // Characters beyond the new (halved) line width are lost.
+        for col in width..self.width {
+            *self.cell_at_mut(CursorPos::at(col, row)) = Cell::default();
+        }
+        self.damage_row(row);
+        self.cursor.col = min(self.cursor.col, width - 1);
+    }
+
+    fn set_cursor_style(&mut self, style: Option<CursorStyle>) {
+        self.cursor_style = style.unwrap_or_default();
     }
 
     fn input(&mut self, c: char) {
@@ -344,12 +1222,15 @@ impl<W: Write> Handler<W> for Grid<W> {
             self.scroll_up(1);
             self.cursor.row -= 1;
         }
+        let charset = self.single_shift.take().unwrap_or(self.active_charset);
+        let c = self.charsets[charset_slot(charset)].map(c);
         *self.cell_at_mut(self.cursor) = Cell {
             c,
             ..self.sgr_template
         };
         self.cursor.col += 1;
-        if self.cursor.col == self.width {
+        if self.cursor.col == self.row_width(self.cursor.row) {
+            self.log_line();
             self.cursor.row += 1;
             self.carriage_return();
         }
@@ -374,12 +1255,14 @@ impl<W: Write> Handler<W> for Grid<W> {
         if cols < 1 {
             return;
         }
-        for col in (self.cursor.col..self.width).rev() {
+        let blank = self.blank_cell();
+        let width = self.row_width(self.cursor.row);
+        for col in (self.cursor.col..width).rev() {
             *self.cell_at_mut(CursorPos::at(col, self.cursor.row)) =
                 if col >= cols + self.cursor.col {
                     *self.cell_at(CursorPos::at(col - cols, self.cursor.row))
                 } else {
-                    Cell::default()
+                    blank
                 };
         }
     }
@@ -438,7 +1321,6 @@ impl<W: Write> Handler<W> for Grid<W> {
     }
 
     fn put_tab(&mut self, count: i64) {
-        // FIXME
         for _ in 0..count {
             self.move_horizontal(Displace::ToTabStop);
         }
@@ -453,6 +1335,7 @@ impl<W: Write> Handler<W> for Grid<W> {
     }
 
     fn linefeed(&mut self) {
+        self.log_line();
         if self.cursor.row + 1 == self.scrolling_region.end {
             self.scroll_up(1);
         } else if self.cursor.row + 1 < self.height {
@@ -468,7 +1351,7 @@ impl<W: Write> Handler<W> for Grid<W> {
     }
 
     fn bell(&mut self) {
-        info!("BEL");
+        self.emit(GridEvent::Bell);
     }
 
     fn substitute(&mut self) {}
@@ -478,14 +1361,14 @@ impl<W: Write> Handler<W> for Grid<W> {
     }
 
     fn set_horizontal_tabstop(&mut self) {
-        // TODO
+        self.tab_stops.insert(self.cursor.col);
     }
 
     fn scroll_up(&mut self, rows: usize) {
         self.scroll_up_in_region(
             self.scrolling_region.start,
             self.scrolling_region.end,
-            u16::try_from(rows).unwrap(),
+            scroll_count(rows),
         );
     }
 
@@ -493,7 +1376,7 @@ impl<W: Write> Handler<W> for Grid<W> {
         self.scroll_down_in_region(
             self.scrolling_region.start,
             self.scrolling_region.end,
-            u16::try_from(rows).unwrap(),
+            scroll_count(rows),
         );
     }
 
@@ -505,13 +1388,13 @@ impl<W: Write> Handler<W> for Grid<W> {
         self.scroll_down_in_region(
             self.cursor.row,
             self.scrolling_region.end,
-            u16::try_from(rows).unwrap(),
+            scroll_count(rows),
         );
     }
 
     fn delete_lines(&mut self, rows: usize) {
         trace!("DL: {}", rows);
-        let rows = u16::try_from(rows).unwrap();
+        let rows = scroll_count(rows);
         if !self.scrolling_region.contains(&self.cursor.row) {
             return;
         }
@@ -519,30 +1402,39 @@ impl<W: Write> Handler<W> for Grid<W> {
     }
 
     fn erase_chars(&mut self, cols: usize) {
+        let blank = self.blank_cell();
         let cols = u16::try_from(cols).unwrap();
         for x1 in 0..cols {
             let col = self.cursor.col + x1;
             if col < self.width {
-                *self.cell_at_mut(CursorPos::at(col, self.cursor.row)) =
-                    Cell::default();
+                *self.cell_at_mut(CursorPos::at(col, self.cursor.row)) = blank;
             }
         }
     }
 
     fn delete_chars(&mut self, cols: usize) {
+        let blank = self.blank_cell();
         let cols = u16::try_from(cols).unwrap();
-        for col in self.cursor.col..self.width {
+        let width = self.row_width(self.cursor.row);
+        for col in self.cursor.col..width {
             *self.cell_at_mut(CursorPos::at(col, self.cursor.row)) =
-                if col + cols < self.width {
+                if col + cols < width {
                     *self.cell_at(CursorPos::at(col + cols, self.cursor.row))
                 } else {
-                    Cell::default()
+                    blank
                 };
         }
     }
 
-    fn move_backward_tabs(&mut self, _count: i64) {
-        // TODO
+    fn move_backward_tabs(&mut self, count: i64) {
+        for _ in 0..count {
+            self.cursor.col = self
+                .tab_stops
+                .range(..self.cursor.col)
+                .next_back()
+                .copied()
+                .unwrap_or(0);
+        }
     }
 
     fn move_forward_tabs(&mut self, count: i64) {
@@ -560,41 +1452,68 @@ impl<W: Write> Handler<W> for Grid<W> {
     }
 
     fn clear_line(&mut self, mode: LineClearMode) {
-        let range = match mode {
-            LineClearMode::All => 0..(self.width as usize),
-            LineClearMode::Left => 0..(self.cursor.col as usize),
-            LineClearMode::Right => (self.cursor.col as usize)..(self.width as usize),
-        };
-        self.dirty_rows.insert(self.cursor.row);
-        self.buffer.rows[self.cursor.row as usize].buf[range]
-            .iter_mut()
-            .for_each(|i| *i = Cell::default());
+        self.erase_in_line(mode, false);
     }
 
     fn clear_screen(&mut self, mode: ClearMode) {
-        let range = match mode {
-            ClearMode::All | ClearMode::Saved => {
-                CursorPos::at(0, 0)..CursorPos::at(0, self.height)
-            }
-            ClearMode::Above => CursorPos::at(0, 0)..self.cursor,
-            ClearMode::Below => self.cursor..CursorPos::at(0, self.height),
-        };
-        for row in range.start.row..=range.end.row {
-            for col in 0..self.width {
-                let pos = CursorPos::at(col, row as u16);
-                if range.contains(&pos) {
-                    *self.cell_at_mut(pos) = Cell::default();
-                }
-            }
+        self.erase_in_display(mode, false);
+    }
+
+    fn selective_clear_line(&mut self, mode: LineClearMode) {
+        self.erase_in_line(mode, true);
+    }
+
+    fn selective_clear_screen(&mut self, mode: ClearMode) {
+        self.erase_in_display(mode, true);
+    }
+
+    fn set_character_protection(&mut self, protected: bool) {
+        if protected {
+            self.sgr_template.flags.insert(Flags::PROTECTED);
+        } else {
+            self.sgr_template.flags.remove(Flags::PROTECTED);
         }
     }
 
-    fn clear_tabs(&mut self, _mode: TabulationClearMode) {
-        // TODO
+    fn clear_tabs(&mut self, mode: TabulationClearMode) {
+        match mode {
+            TabulationClearMode::Current => {
+                self.tab_stops.remove(&self.cursor.col);
+            }
+            TabulationClearMode::All => self.tab_stops.clear(),
+        }
     }
 
     fn reset_state(&mut self) {
-        // TODO
+        // RIS: everything the application can change goes, but the title, the
+        // rows scrolled away, as xterm keeps them, and anything set by the
+        // embedder stay.
+        debug!("RIS");
+        let fresh = Grid::new(self.width, self.height);
+        let old = std::mem::replace(self, fresh);
+        self.title = old.title;
+        self.title_stack = old.title_stack;
+        self.listeners = old.listeners;
+        self.line_log = old.line_log;
+        self.private = old.private;
+        self.scrollback = old.scrollback;
+        self.view_offset = old.view_offset;
+        self.highlight = old.highlight;
+        self.origin = old.origin;
+        self.scale_line_attrs = old.scale_line_attrs;
+        self.text_blink = old.text_blink;
+        self.blink_visible = old.blink_visible;
+        self.theme = old.theme;
+    }
+
+    fn soft_reset(&mut self) {
+        debug!("DECSTR");
+        self.sgr_template = Cell::default();
+        self.charsets = Default::default();
+        self.active_charset = CharsetIndex::default();
+        self.single_shift = None;
+        self.scrolling_region = 0..self.height;
+        self.saved_cursor = CursorPos::default();
     }
 
     fn reverse_index(&mut self) {
@@ -607,24 +1526,70 @@ impl<W: Write> Handler<W> for Grid<W> {
     }
 
     fn terminal_attribute(&mut self, attr: Attr) {
-        // TODO
         // SGR: set an attribute to apply to subsequently-received characters.
+        let template = &mut self.sgr_template;
         match attr {
-            Attr::Reset => self.sgr_template = Cell::default(),
-            Attr::Foreground(color) => self.sgr_template.fg = color,
-            Attr::Background(color) => self.sgr_template.bg = color,
-            _ => debug!("unhandled SGR {:?}", attr),
+            Attr::Reset => {
+                // DECSCA is independent of SGR.
+                let protected = template.flags.contains(Flags::PROTECTED);
+                *template = Cell::default();
+                if protected {
+                    template.flags.insert(Flags::PROTECTED);
+                }
+            }
+            Attr::Bold => template.flags.insert(Flags::BOLD),
+            Attr::Dim => template.flags.insert(Flags::DIM),
+            Attr::Italic => template.flags.insert(Flags::ITALIC),
+            Attr::Underline => {
+                template.flags.remove(Flags::ALL_UNDERLINES);
+                template.flags.insert(Flags::UNDERLINE);
+            }
+            Attr::DoubleUnderline => {
+                template.flags.remove(Flags::ALL_UNDERLINES);
+                template.flags.insert(Flags::DOUBLE_UNDERLINE);
+            }
+            Attr::BlinkSlow | Attr::BlinkFast => template.flags.insert(Flags::BLINK),
+            Attr::Reverse => template.flags.insert(Flags::INVERSE),
+            Attr::Hidden => template.flags.insert(Flags::HIDDEN),
+            Attr::Strike => template.flags.insert(Flags::STRIKETHROUGH),
+            Attr::CancelBold => template.flags.remove(Flags::BOLD),
+            Attr::CancelBoldDim => template.flags.remove(Flags::BOLD | Flags::DIM),
+            Attr::CancelItalic => template.flags.remove(Flags::ITALIC),
+            Attr::CancelUnderline => template.flags.remove(Flags::ALL_UNDERLINES),
+            Attr::CancelBlink => template.flags.remove(Flags::BLINK),
+            Attr::CancelReverse => template.flags.remove(Flags::INVERSE),
+            Attr::CancelHidden => template.flags.remove(Flags::HIDDEN),
+            Attr::CancelStrike => template.flags.remove(Flags::STRIKETHROUGH),
+            Attr::Foreground(color) => template.fg = color,
+            Attr::Background(color) => template.bg = color,
+            Attr::UnderlineColor(color) => template.underline_color = color,
         }
     }
 
     fn set_mode(&mut self, mode: Mode) {
-        // TODO
-        debug!("set mode: {:?}", mode);
+        match mode {
+            Mode::BlinkingCursor => self.cursor_style.blinking = true,
+            Mode::ReverseVideo => self.set_reverse_video(true),
+            Mode::BracketedPaste => self.bracketed_paste = true,
+            Mode::ReportMouseClicks
+            | Mode::ReportCellMouseMotion
+            | Mode::ReportAllMouseMotion => self.mouse_reporting = true,
+            Mode::SgrMouse => self.sgr_mouse = true,
+            _ => debug!("set mode: {:?}", mode),
+        }
     }
 
     fn unset_mode(&mut self, mode: Mode) {
-        // TODO
-        debug!("unset mode: {:?}", mode);
+        match mode {
+            Mode::BlinkingCursor => self.cursor_style.blinking = false,
+            Mode::ReverseVideo => self.set_reverse_video(false),
+            Mode::BracketedPaste => self.bracketed_paste = false,
+            Mode::ReportMouseClicks
+            | Mode::ReportCellMouseMotion
+            | Mode::ReportAllMouseMotion => self.mouse_reporting = false,
+            Mode::SgrMouse => self.sgr_mouse = false,
+            _ => debug!("unset mode: {:?}", mode),
+        }
     }
 
     fn set_scrolling_region(&mut self, top: usize, bottom: Option<usize>) {
@@ -645,50 +1610,93 @@ impl<W: Write> Handler<W> for Grid<W> {
         debug!("unset keypad");
     }
 
-    fn set_active_charset(&mut self, _: CharsetIndex) {
-        debug!("set charset");
+    fn set_active_charset(&mut self, index: CharsetIndex) {
+        trace!("set charset {:?}", index);
+        self.active_charset = index;
     }
 
-    fn configure_charset(&mut self, _: CharsetIndex, _: StandardCharset) {
-        debug!("config charset");
+    fn single_shift(&mut self, index: CharsetIndex) {
+        trace!("single shift {:?}", index);
+        self.single_shift = Some(index);
     }
 
-    fn set_color(&mut self, _: usize, _: Rgb) {
-        debug!("set color");
+    fn configure_charset(&mut self, index: CharsetIndex, charset: StandardCharset) {
+        trace!("configure charset {:?} as {:?}", index, charset);
+        self.charsets[charset_slot(index)] = charset;
     }
 
-    fn dynamic_color_sequence(&mut self, _: &mut W, _: u8, _: usize, _: &str) {
-        debug!("write color seq");
+    fn set_color(&mut self, index: usize, color: Rgb) {
+        trace!("set color {} to {:?}", index, color);
+        if self.palette.set(index, color) {
+            self.mark_all_dirty();
+        }
     }
 
-    fn reset_color(&mut self, _: usize) {
-        debug!("reset color");
+    fn dynamic_color_sequence(
+        &mut self,
+        writer: &mut W,
+        prefix: u8,
+        index: usize,
+        terminator: &str,
+    ) {
+        self.emit(GridEvent::ColorQuery {
+            prefix,
+            index,
+            terminator: terminator.to_owned(),
+        });
+        let color = match self.colors().get(index) {
+            Some(color) => color,
+            None => return,
+        };
+        // OSC 4 replies name the index; OSC 10-12 replies only name themselves.
+        let prefix = if index < 256 {
+            format!("4;{}", index)
+        } else {
+            prefix.to_string()
+        };
+        let reply = format!(
+            "\x1b]{};rgb:{2:02x}{2:02x}/{3:02x}{3:02x}/{4:02x}{4:02x}{1}",
+            prefix, terminator, color.r, color.g, color.b
+        );
+        if let Err(e) = writer.write_all(reply.as_bytes()) {
+            warn!("could not reply to colour query: {}", e);
+        }
     }
 
-    fn clipboard_store(&mut self, _: u8, _: &[u8]) {}
-
-    fn clipboard_load(&mut self, _: u8, _: &str) {}
+    fn reset_color(&mut self, index: usize) {
+        trace!("reset color {}", index);
+        if self.palette.overridden(index).is_some() {
+            self.palette.reset(index);
+            self.mark_all_dirty();
+        }
+    }
 
-    fn decaln(&mut self) {}
+    fn clipboard_store(&mut self, clipboard: u8, data: &[u8]) {
+        self.emit(GridEvent::ClipboardStore {
+            clipboard,
+            data: data.to_vec(),
+        });
+    }
 
-    fn push_title(&mut self) {}
+    fn clipboard_load(&mut self, clipboard: u8, terminator: &str) {
+        self.emit(GridEvent::ClipboardLoad {
+            clipboard,
+            terminator: terminator.to_owned(),
+        });
+    }
 
-    fn pop_title(&mut self) {}
-}
+    fn decaln(&mut self) {}
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-struct Cell {
-    pub c: char,
-    pub bg: Color,
-    pub fg: Color,
-}
+    fn push_title(&mut self) {
+        if self.title_stack.len() >= TITLE_STACK_MAX {
+            self.title_stack.remove(0);
+        }
+        self.title_stack.push(self.title.clone());
+    }
 
-impl Cell {
-    pub fn default() -> Cell {
-        Cell {
-            c: '.',
-            bg: Color::Named(NamedColor::Background),
-            fg: Color::Named(NamedColor::Foreground),
+    fn pop_title(&mut self) {
+        if let Some(title) = self.title_stack.pop() {
+            self.set_title(title.as_deref());
         }
     }
 }
@@ -697,7 +1705,8 @@ impl Cell {
 mod tests {
     use super::*;
 
-    use std::io::{Read, Sink};
+    use crate::ansi::CursorShape;
+    use std::io::{self, Read, Sink};
     use std::str;
     use tempfile::NamedTempFile;
 
@@ -960,6 +1969,531 @@ mod tests {
         assert_eq!(grid.height, 2);
     }
 
+    #[test]
+    fn resize_to_nothing() {
+        let grid = Grid::<Sink>::new(0, 0);
+        assert_eq!((grid.width, grid.height), (1, 1));
+        let mut grid = Grid::<Sink>::new(4, 3);
+        input_str!(grid, "abc");
+        check_cur!(grid, 3, 0);
+        grid.resize(2, 3);
+        check_cur!(grid, 1, 0);
+        grid.resize(0, 0);
+        assert_eq!((grid.width, grid.height), (1, 1));
+        check_cur!(grid, 0, 0);
+        grid.draw(&mut Vec::new());
+        grid.resize(4, 3);
+        input_str!(grid, "xy");
+        check_char!(grid, 1, 0, 'y');
+    }
+
+    #[test]
+    fn origin() {
+        let mut grid = Grid::<Sink>::new(4, 2);
+        input_str!(grid, "ab");
+        grid.set_origin(10, 5);
+        let mut out = Vec::new();
+        grid.draw(&mut out);
+        let out = str::from_utf8(&out).unwrap();
+        assert!(out.starts_with("\x1b[6;11H\x1b#5\x1b[0mab  \x1b[7;11H"));
+        assert!(out.ends_with("\x1b[0m\x1b[6;13H"));
+    }
+
+    #[test]
+    fn double_width_line() {
+        let mut grid = Grid::<Sink>::new(8, 3);
+        input_str!(grid, "Hello");
+        grid.goto(0, 2);
+        grid.set_line_attribute(LineAttr::DoubleWidth);
+        check_char!(grid, 3, 0, 'l');
+        assert_eq!(grid.buffer[CursorPos::at(4, 0)], Cell::default());
+        grid.goto_col(7);
+        check_cur!(grid, 3, 0);
+        grid.goto_col(0);
+        input_str!(grid, "World");
+        check_char!(grid, 3, 0, 'l');
+        check_char!(grid, 0, 1, 'd');
+        check_cur!(grid, 1, 1);
+
+        // The attribute moves with its row, and the cursor is clamped to it.
+        grid.goto(1, 7);
+        grid.scroll_down(1);
+        assert_eq!(grid.buffer.rows[0].attr, LineAttr::Normal);
+        assert_eq!(grid.buffer.rows[1].attr, LineAttr::DoubleWidth);
+        grid.goto(1, 7);
+        check_cur!(grid, 3, 1);
+        grid.move_up(1);
+        check_cur!(grid, 3, 0);
+        grid.goto(1, 0);
+        grid.set_line_attribute(LineAttr::Normal);
+        grid.goto_col(7);
+        check_cur!(grid, 7, 1);
+    }
+
+    #[test]
+    fn line_attr_after_last_cell() {
+        let mut grid = Grid::<Sink>::new(4, 2);
+        grid.goto(1, 3);
+        input_str!(grid, "X");
+        grid.set_line_attribute(LineAttr::DoubleWidth);
+        assert_eq!(grid.buffer.rows[1].attr, LineAttr::DoubleWidth);
+        assert_eq!(grid.buffer[CursorPos::at(3, 1)], Cell::default());
+        grid.set_line_attribute(LineAttr::DoubleHeightTop);
+        assert_eq!(grid.buffer.rows[1].attr, LineAttr::DoubleHeightTop);
+        input_str!(grid, "y");
+        check_char!(grid, 0, 1, 'y');
+        assert_eq!(grid.buffer.rows[0].attr, LineAttr::DoubleHeightTop);
+    }
+
+    #[test]
+    fn scaled_line_attrs() {
+        let mut grid = Grid::<Sink>::new(6, 2);
+        input_str!(grid, "ab");
+        grid.set_line_attribute(LineAttr::DoubleWidth);
+        let mut out = Vec::new();
+        grid.draw(&mut out);
+        let out = str::from_utf8(&out).unwrap();
+        assert!(out.contains("\x1b[1;1H\x1b#6\x1b[0mab \x1b[2;1H\x1b#5"));
+        assert!(out.ends_with("\x1b[1;3H"));
+
+        grid.set_scale_line_attrs(true);
+        let mut out = Vec::new();
+        grid.draw(&mut out);
+        let out = str::from_utf8(&out).unwrap();
+        assert!(!out.contains("\x1b#"));
+        assert!(out.contains("\x1b[1;1H\x1b[0ma b   \x1b[2;1H\x1b[0m      "));
+        assert!(out.ends_with("\x1b[1;5H"));
+    }
+
+    #[test]
+    fn tab_stops() {
+        let mut grid = Grid::<Sink>::new(20, 2);
+        grid.put_tab(1);
+        check_cur!(grid, 8, 0);
+        grid.goto_col(3);
+        grid.set_horizontal_tabstop();
+        grid.goto_col(0);
+        grid.put_tab(2);
+        check_cur!(grid, 8, 0);
+        grid.clear_tabs(TabulationClearMode::Current);
+        grid.goto_col(0);
+        grid.put_tab(2);
+        check_cur!(grid, 16, 0);
+        grid.move_backward_tabs(1);
+        check_cur!(grid, 3, 0);
+        grid.clear_tabs(TabulationClearMode::All);
+        grid.put_tab(1);
+        check_cur!(grid, 19, 0);
+        grid.move_backward_tabs(1);
+        check_cur!(grid, 0, 0);
+    }
+
+    #[test]
+    fn reset() {
+        let mut grid = Grid::<Sink>::new(20, 4);
+        let mut processor = crate::ansi::Processor::new();
+        let mut feed = |grid: &mut Grid<Sink>, bytes: &[u8]| {
+            for byte in bytes {
+                processor.advance(grid, *byte, &mut io::sink());
+            }
+        };
+
+        feed(
+            &mut grid,
+            b"\x1b]2;title\x07\x1b[2;3r\x1b[1;31m\x1b(0q\x1b[3g",
+        );
+        check_char!(grid, 0, 0, '─');
+        feed(&mut grid, b"\x1b[!pq\t");
+        assert_eq!(grid.scrolling_region, 0..4);
+        check_cell!(
+            grid,
+            1,
+            0,
+            Cell {
+                c: 'q',
+                ..Cell::default()
+            }
+        );
+        check_cur!(grid, 19, 0);
+
+        grid.set_color(1, Rgb { r: 1, g: 2, b: 3 });
+        feed(&mut grid, b"\x1bc\t");
+        check_char!(grid, 0, 0, ' ');
+        check_cur!(grid, 8, 0);
+        assert_eq!(grid.palette().overridden(1), None);
+        assert_eq!(grid.title(), Some("title"));
+    }
+
+    #[test]
+    fn single_shift() {
+        let mut grid = Grid::<Sink>::new(8, 2);
+        let mut processor = crate::ansi::Processor::new();
+        for byte in b"\x1b+0\x1bOqq\x1bnq\x1bNq\x0fq" {
+            processor.advance(&mut grid, *byte, &mut io::sink());
+        }
+        // G3 is line drawing, G2 and G0 ASCII.
+        check_char!(grid, 0, 0, '─');
+        check_char!(grid, 1, 0, 'q');
+        check_char!(grid, 2, 0, 'q');
+        check_char!(grid, 3, 0, 'q');
+        check_char!(grid, 4, 0, 'q');
+
+        for byte in b"\x1bo\x1bNqq" {
+            processor.advance(&mut grid, *byte, &mut io::sink());
+        }
+        check_char!(grid, 5, 0, 'q');
+        check_char!(grid, 6, 0, '─');
+    }
+
+    #[test]
+    fn erase_with_background() {
+        let mut grid = Grid::<Sink>::new(4, 3);
+        let red = Color::Named(NamedColor::Red);
+        input_str!(grid, "abcdefgh");
+        grid.terminal_attribute(Attr::Background(red));
+        grid.terminal_attribute(Attr::Bold);
+        grid.goto(0, 2);
+        grid.clear_line(LineClearMode::Right);
+        grid.goto(1, 1);
+        grid.erase_chars(1);
+        grid.linefeed();
+        grid.linefeed();
+        let blank = Cell {
+            c: ' ',
+            bg: red,
+            ..Cell::default()
+        };
+        // The first row ("ab" and two erased cells) has scrolled off.
+        check_char!(grid, 0, 0, 'e');
+        check_cell!(grid, 1, 0, blank);
+        check_char!(grid, 2, 0, 'g');
+        check_cell!(grid, 0, 1, Cell::default());
+        check_cell!(grid, 3, 2, blank);
+        grid.terminal_attribute(Attr::Reset);
+        grid.clear_screen(ClearMode::All);
+        check_cell!(grid, 0, 0, Cell::default());
+        assert_eq!(Cell::default().c(), ' ');
+    }
+
+    #[test]
+    fn selective_erase() {
+        let mut grid = Grid::<Sink>::new(4, 3);
+        let mut processor = crate::ansi::Processor::new();
+        let mut feed = |grid: &mut Grid<Sink>, bytes: &[u8]| {
+            for byte in bytes {
+                processor.advance(grid, *byte, &mut io::sink());
+            }
+        };
+        let row = |grid: &Grid<Sink>, row| {
+            (0..4)
+                .map(|col| grid.cell(col, row).unwrap().c())
+                .collect::<String>()
+        };
+
+        // SGR 0 leaves protection on; only DECSCA turns it off.
+        feed(&mut grid, b"ab\x1b[1\"qcd\x1b[0mef\x1b[0\"qgh");
+        assert!(grid.cell(2, 0).unwrap().flags().contains(Flags::PROTECTED));
+        assert!(grid.cell(0, 1).unwrap().flags().contains(Flags::PROTECTED));
+        assert!(grid.cell(2, 1).unwrap().flags().is_empty());
+
+        feed(&mut grid, b"\x1b[2;1H\x1b[?2K\x1b[1;1H\x1b[?K");
+        assert_eq!(row(&grid, 0), "  cd");
+        assert_eq!(row(&grid, 1), "ef  ");
+        feed(&mut grid, b"\x1b[?2J");
+        assert_eq!(row(&grid, 0), "  cd");
+        assert_eq!(row(&grid, 1), "ef  ");
+        feed(&mut grid, b"\x1b[2J");
+        assert_eq!(row(&grid, 0), "    ");
+        assert_eq!(row(&grid, 1), "    ");
+    }
+
+    #[test]
+    fn reverse_video() {
+        let mut grid = Grid::<Sink>::new(2, 1);
+        grid.terminal_attribute(Attr::Reverse);
+        input_str!(grid, "a");
+        let mut out = Vec::new();
+        grid.draw(&mut out);
+        assert!(str::from_utf8(&out).unwrap().contains("\x1b[0;7ma\x1b[0m "));
+
+        grid.set_mode(Mode::ReverseVideo);
+        assert_eq!(grid.damage().count(), 1, "screen not redrawn");
+        let mut out = Vec::new();
+        grid.draw(&mut out);
+        assert!(str::from_utf8(&out).unwrap().contains("\x1b[0ma\x1b[0;7m "));
+
+        grid.unset_mode(Mode::ReverseVideo);
+        assert_eq!(grid.damage().count(), 1, "screen not redrawn");
+    }
+
+    #[test]
+    fn private() {
+        let mut grid = Grid::<Sink>::new(2, 1);
+        assert!(!Handler::<Sink>::is_private(&grid));
+        grid.set_private(true);
+        assert!(Handler::<Sink>::is_private(&grid));
+        grid.reset_state();
+        assert!(grid.is_private(), "RIS ended privacy");
+        grid.set_private(false);
+        assert!(!Handler::<Sink>::is_private(&grid));
+    }
+
+    #[test]
+    fn bracketed_paste() {
+        let mut grid = Grid::<Sink>::new(2, 1);
+        assert!(!grid.bracketed_paste());
+        grid.set_mode(Mode::BracketedPaste);
+        assert!(grid.bracketed_paste());
+        grid.reset_state();
+        assert!(!grid.bracketed_paste());
+        grid.set_mode(Mode::BracketedPaste);
+        grid.unset_mode(Mode::BracketedPaste);
+        assert!(!grid.bracketed_paste());
+    }
+
+    #[test]
+    fn mouse_reporting() {
+        let mut grid = Grid::<Sink>::new(2, 1);
+        assert_eq!(grid.mouse_reporting(), None);
+        grid.set_mode(Mode::SgrMouse);
+        assert_eq!(grid.mouse_reporting(), None);
+        grid.set_mode(Mode::ReportCellMouseMotion);
+        assert_eq!(grid.mouse_reporting(), Some(MouseEncoding::Sgr));
+        grid.unset_mode(Mode::SgrMouse);
+        assert_eq!(grid.mouse_reporting(), Some(MouseEncoding::Default));
+        grid.unset_mode(Mode::ReportCellMouseMotion);
+        assert_eq!(grid.mouse_reporting(), None);
+
+        let report = |button, release| MouseReport {
+            button,
+            release,
+            col: 2,
+            row: 300,
+        };
+        assert_eq!(
+            report(16, false).encode(MouseEncoding::Sgr),
+            b"\x1b[<16;3;301M"
+        );
+        assert_eq!(
+            report(0, true).encode(MouseEncoding::Sgr),
+            b"\x1b[<0;3;301m"
+        );
+        assert_eq!(
+            report(16, false).encode(MouseEncoding::Default),
+            b"\x1b[M0#\xff"
+        );
+        // Which button was released is not said.
+        assert_eq!(
+            report(18, true).encode(MouseEncoding::Default),
+            b"\x1b[M3#\xff"
+        );
+    }
+
+    #[test]
+    fn scrollback() {
+        let mut grid = Grid::<Sink>::new(8, 2);
+        let mut processor = crate::ansi::Processor::new();
+        let mut feed = |grid: &mut Grid<Sink>, bytes: &[u8]| {
+            for byte in bytes {
+                processor.advance(grid, *byte, &mut io::sink());
+            }
+        };
+        feed(&mut grid, b"one\r\ntwo\r\nthree\r\nfour");
+        assert_eq!(grid.scrollback.len(), 2);
+        let drawn = |grid: &mut Grid<Sink>| {
+            let mut out = Vec::new();
+            grid.draw(&mut out);
+            String::from_utf8(out).unwrap()
+        };
+        drawn(&mut grid);
+        assert_eq!(drawn(&mut grid), "\x1b[0m\x1b[2;5H");
+
+        // Scrolling back shows the rows saved above the screen.
+        grid.scroll_view(1);
+        let out = drawn(&mut grid);
+        assert!(out.contains("\x1b[1;1H\x1b#5\x1b[0mtwo     "), "{:?}", out);
+        assert!(out.contains("\x1b[2;1H\x1b#5\x1b[0mthree   "), "{:?}", out);
+        assert!(out.ends_with("\x1b[1;4H\x1b[0;7m[1/2]\x1b[0m"), "{:?}", out);
+        assert_eq!(drawn(&mut grid), "");
+        // The view stays put as more rows scroll away, and is drawn again.
+        feed(&mut grid, b"\r\nfive");
+        assert_eq!(grid.view_offset(), 2);
+        assert!(drawn(&mut grid).contains("two"));
+        grid.scroll_view(10);
+        assert_eq!(grid.view_offset(), 3);
+        // What the grid contains is its screen, whatever is shown.
+        let contents = String::from_utf8(grid.contents()).unwrap();
+        assert!(contents.contains("five") && !contents.contains("one"));
+        grid.scroll_view(-10);
+        assert_eq!(grid.view_offset(), 0);
+        assert!(drawn(&mut grid).contains("four"));
+
+        // Rows leaving a scrolling region below the top are not saved.
+        feed(&mut grid, b"\x1b[2;2r\x1b[2;1H\r\n");
+        assert_eq!(grid.scrollback.len(), 3);
+        feed(&mut grid, b"\x1b[3J");
+        assert!(grid.scrollback.is_empty());
+    }
+
+    #[test]
+    fn reset_keeps_scrollback() {
+        let mut grid = Grid::<Sink>::new(8, 2);
+        let mut processor = crate::ansi::Processor::new();
+        for byte in b"one\r\ntwo\r\nthree\x1bc" {
+            processor.advance(&mut grid, *byte, &mut io::sink());
+        }
+        assert_eq!(grid.line_count(), 3);
+        assert_eq!(grid.line_text(0).unwrap().trim_end(), "one");
+        assert_eq!(grid.line_text(1).unwrap().trim_end(), "");
+        grid.scroll_view(1);
+        grid.reset_state();
+        assert_eq!(grid.view_offset(), 1);
+    }
+
+    #[test]
+    fn private_scrollback() {
+        let mut grid = Grid::<Sink>::new(8, 2);
+        let mut processor = crate::ansi::Processor::new();
+        let mut feed = |grid: &mut Grid<Sink>, bytes: &[u8]| {
+            for byte in bytes {
+                processor.advance(grid, *byte, &mut io::sink());
+            }
+        };
+        feed(&mut grid, b"one\r\ntwo\r\n");
+        grid.set_private(true);
+        feed(&mut grid, b"secret\r\nhidden\r\n");
+        assert_eq!(grid.scrollback.len(), 1);
+        grid.set_private(false);
+        feed(&mut grid, b"three\r\n");
+        assert_eq!(grid.line_count(), 4);
+        let lines = (0..3).map(|line| grid.line_text(line).unwrap());
+        let lines: Vec<_> = lines.map(|line| line.trim_end().to_string()).collect();
+        assert_eq!(lines, ["one", "hidden", "three"]);
+        let secret = Search {
+            pattern: "secret".to_string(),
+            forward: true,
+        };
+        assert_eq!(grid.find(&secret, 0, false), None);
+    }
+
+    #[test]
+    fn search() {
+        let mut grid = Grid::<Sink>::new(8, 2);
+        let mut processor = crate::ansi::Processor::new();
+        for byte in b"an err\r\ntwo\r\nerrors\r\nfour" {
+            processor.advance(&mut grid, *byte, &mut io::sink());
+        }
+        assert_eq!(grid.line_count(), 4);
+        assert_eq!(grid.line_text(0).as_deref(), Some("an err  "));
+        assert_eq!(grid.line_text(3).as_deref(), Some("four    "));
+        assert_eq!(grid.view_top(), 2);
+        let mut search = Search {
+            pattern: "ERR".to_string(),
+            forward: false,
+        };
+        assert_eq!(grid.find(&search, 2, false), None);
+        search.pattern = "err".to_string();
+        assert_eq!(grid.find(&search, 1, false), Some(0));
+        assert_eq!(grid.find(&search, 0, true), Some(2));
+        grid.show_line(0);
+        assert_eq!(grid.view_offset(), 2);
+        // A line on the screen shows the screen.
+        grid.show_line(3);
+        assert_eq!(grid.view_offset(), 0);
+
+        let drawn = |grid: &mut Grid<Sink>| {
+            let mut out = Vec::new();
+            grid.draw(&mut out);
+            String::from_utf8(out).unwrap()
+        };
+        drawn(&mut grid);
+        grid.set_highlight(Some(search.clone()));
+        let out = drawn(&mut grid);
+        assert!(out.contains("\x1b[0;7merr\x1b[0mors"), "{:?}", out);
+        grid.show_line(0);
+        let out = drawn(&mut grid);
+        assert!(out.contains("an \x1b[0;7merr\x1b[0m  "), "{:?}", out);
+        // What the grid contains is not highlighted.
+        let contents = String::from_utf8(grid.contents()).unwrap();
+        assert!(!contents.contains("\x1b[0;7m"), "{:?}", contents);
+        grid.set_highlight(None);
+        assert!(!drawn(&mut grid).contains("\x1b[0;7merr"));
+    }
+
+    #[test]
+    fn theme() {
+        let mut grid = Grid::<Vec<u8>>::new(2, 1);
+        let mut palette = Palette::default();
+        palette.set(NamedColor::Background as usize, Rgb { r: 64, g: 0, b: 0 });
+        palette.set(1, Rgb { r: 255, g: 0, b: 0 });
+        grid.set_theme(Some(Theme {
+            name: "production".to_string(),
+            palette,
+        }));
+        assert_eq!(grid.theme().map(|t| t.name.as_str()), Some("production"));
+        grid.set_color(1, Rgb { r: 1, g: 2, b: 3 });
+        grid.terminal_attribute(Attr::Foreground(Color::Indexed(1)));
+        input_str!(grid, "a");
+        let mut out = Vec::new();
+        grid.draw(&mut out);
+        let out = str::from_utf8(&out).unwrap();
+        assert!(out.contains("\x1b[0;38;2;1;2;3;48;2;64;0;0ma\x1b[0;48;2;64;0;0m "));
+
+        let mut reply = Vec::new();
+        grid.dynamic_color_sequence(
+            &mut reply,
+            11,
+            NamedColor::Background as usize,
+            "\x07",
+        );
+        assert_eq!(reply, b"\x1b]11;rgb:4040/0000/0000\x07");
+
+        grid.reset_state();
+        assert!(grid.theme().is_some(), "RIS removed the theme");
+        grid.set_theme(None);
+        let mut out = Vec::new();
+        grid.draw(&mut out);
+        assert!(str::from_utf8(&out).unwrap().contains("\x1b[0m  "));
+    }
+
+    #[test]
+    fn events() {
+        use std::sync::{Arc, Mutex};
+
+        let mut grid = Grid::<Sink>::new(4, 4);
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink = events.clone();
+        grid.subscribe(move |e| sink.lock().unwrap().push(e.clone()));
+
+        grid.bell();
+        grid.set_title(Some("first"));
+        grid.push_title();
+        grid.set_title(Some("second"));
+        assert_eq!(grid.title(), Some("second"));
+        grid.pop_title();
+        assert_eq!(grid.title(), Some("first"));
+        grid.clipboard_store(b'c', b"aGk=");
+        grid.dynamic_color_sequence(&mut io::sink(), 11, 257, "\x07");
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![
+                GridEvent::Bell,
+                GridEvent::TitleChanged(Some("first".to_string())),
+                GridEvent::TitleChanged(Some("second".to_string())),
+                GridEvent::TitleChanged(Some("first".to_string())),
+                GridEvent::ClipboardStore {
+                    clipboard: b'c',
+                    data: b"aGk=".to_vec()
+                },
+                GridEvent::ColorQuery {
+                    prefix: 11,
+                    index: 257,
+                    terminator: "\x07".to_string(),
+                },
+            ]
+        );
+    }
+
     #[test]
     fn sgr_color() {
         let mut grid = Grid::<Sink>::new(4, 3);
@@ -1022,7 +2556,8 @@ mod tests {
             Cell {
                 c: 'r',
                 fg: blue,
-                bg: rgb
+                bg: rgb,
+                ..Cell::default()
             }
         );
         grid.terminal_attribute(Attr::Reset);
@@ -1053,7 +2588,8 @@ mod tests {
             Cell {
                 c: 'l',
                 fg: blue,
-                bg: rgb
+                bg: rgb,
+                ..Cell::default()
             }
         );
         check_cell!(
@@ -1066,4 +2602,268 @@ mod tests {
             }
         );
     }
+
+    #[test]
+    fn sgr_flags() {
+        let mut grid = Grid::<Sink>::new(4, 2);
+        grid.terminal_attribute(Attr::Bold);
+        grid.terminal_attribute(Attr::Underline);
+        input_str!(grid, "a");
+        grid.terminal_attribute(Attr::DoubleUnderline);
+        grid.terminal_attribute(Attr::UnderlineColor(Some(Color::Indexed(3))));
+        input_str!(grid, "b");
+        grid.terminal_attribute(Attr::CancelBoldDim);
+        grid.terminal_attribute(Attr::CancelUnderline);
+        input_str!(grid, "c");
+        let a = grid.cell(0, 0).unwrap();
+        assert_eq!(a.flags(), Flags::BOLD | Flags::UNDERLINE);
+        assert_eq!(a.underline_color(), None);
+        let b = grid.cell(1, 0).unwrap();
+        assert_eq!(b.flags(), Flags::BOLD | Flags::DOUBLE_UNDERLINE);
+        assert_eq!(b.underline_color(), Some(Color::Indexed(3)));
+        assert!(grid.cell(2, 0).unwrap().flags().is_empty());
+        assert_eq!(grid.cell(4, 0), None);
+
+        let mut out = Vec::new();
+        grid.draw(&mut out);
+        let out = str::from_utf8(&out).unwrap();
+        assert!(out
+            .contains("\x1b[0;1;4ma\x1b[0;1;21;58;5;3mb\x1b[0;58;5;3mc\x1b[0m \x1b["));
+    }
+
+    #[test]
+    fn text_blink() {
+        let mut grid = Grid::<Sink>::new(4, 2);
+        grid.terminal_attribute(Attr::BlinkSlow);
+        input_str!(grid, "a");
+        grid.terminal_attribute(Attr::CancelBlink);
+        input_str!(grid, "b");
+        assert!(grid.cell(0, 0).unwrap().flags().contains(Flags::BLINK));
+        let draw = |grid: &mut Grid<Sink>| {
+            let mut out = Vec::new();
+            grid.draw(&mut out);
+            String::from_utf8(out).unwrap()
+        };
+        assert!(draw(&mut grid).contains("\x1b[0mab"));
+
+        grid.set_blink_phase(false);
+        assert_eq!(
+            grid.damage().collect::<Vec<_>>(),
+            [DamageSpan { row: 0, cols: 0..1 }]
+        );
+        // Only the blinking text is drawn again.
+        assert_eq!(draw(&mut grid), "\x1b[1;1H\x1b#5\x1b[0;8ma\x1b[0m\x1b[1;3H");
+        grid.set_blink_phase(false);
+        assert_eq!(grid.damage().count(), 0);
+
+        grid.set_text_blink(TextBlink::Terminal);
+        assert!(draw(&mut grid).contains("\x1b[0;5ma\x1b[0mb"));
+        grid.set_blink_phase(true);
+        assert_eq!(grid.damage().count(), 0);
+        grid.set_text_blink(TextBlink::Off);
+        grid.set_blink_phase(false);
+        assert!(draw(&mut grid).contains("\x1b[0mab"));
+    }
+
+    #[test]
+    fn scroll_far() {
+        let mut grid = Grid::<Sink>::new(4, 4);
+        let mut processor = crate::ansi::Processor::new();
+        let mut feed = |grid: &mut Grid<Sink>, bytes: &[u8]| {
+            for byte in bytes {
+                processor.advance(grid, *byte, &mut io::sink());
+            }
+        };
+        let fill = b"\x1b[H\x1b#6a\r\ncd\r\nef\r\ngh";
+        let rows = |grid: &Grid<Sink>| -> Vec<_> {
+            (0..4).map(|row| grid.row_text(row).unwrap()).collect()
+        };
+        feed(&mut grid, fill);
+        // Counts beyond the region, however large, clear it.
+        grid.scroll_up(usize::MAX);
+        assert_eq!(rows(&grid), ["    "; 4]);
+        assert_eq!(grid.row_width(0), 4);
+
+        feed(&mut grid, fill);
+        feed(&mut grid, b"\x1b[2;3r\x1b[65535T");
+        assert_eq!(rows(&grid), ["a   ", "    ", "    ", "gh  "]);
+        assert_eq!(grid.row_width(0), 2);
+        feed(&mut grid, b"\x1b[1;3r\x1b[2;1H\x1b[65535M");
+        assert_eq!(rows(&grid), ["a   ", "    ", "    ", "gh  "]);
+        feed(&mut grid, b"\x1b[r\x1b[4;1H\x1b[65535L");
+        assert_eq!(rows(&grid), ["a   ", "    ", "    ", "    "]);
+
+        // Scrolling by less than the region still moves its text.
+        feed(&mut grid, fill);
+        grid.scroll_down(1);
+        assert_eq!(rows(&grid), ["    ", "a   ", "cd  ", "ef  "]);
+        assert_eq!(grid.row_width(1), 2);
+    }
+
+    #[test]
+    fn line_log() {
+        let mut grid = Grid::<Sink>::new(4, 2);
+        input_str!(grid, "ab");
+        grid.linefeed();
+        assert!(grid.take_lines().is_empty());
+
+        grid.set_line_log(true);
+        grid.carriage_return();
+        input_str!(grid, "cd");
+        grid.linefeed();
+        grid.carriage_return();
+        // Wrapped lines are logged as they fill up.
+        input_str!(grid, "efghi");
+        assert_eq!(grid.take_lines(), ["cd", "efgh"]);
+        assert!(grid.take_lines().is_empty());
+        assert_eq!(grid.cursor_line(), "i");
+        grid.move_forward(2);
+        assert_eq!(grid.cursor_line(), "i  ");
+
+        grid.set_line_log(false);
+        grid.linefeed();
+        assert!(grid.take_lines().is_empty());
+    }
+
+    #[test]
+    fn damage() {
+        let mut grid = Grid::<Sink>::new(8, 3);
+        let mut processor = crate::ansi::Processor::new();
+        let mut feed = |grid: &mut Grid<Sink>, bytes: &[u8]| {
+            for byte in bytes {
+                processor.advance(grid, *byte, &mut io::sink());
+            }
+        };
+        assert_eq!(grid.damage().count(), 3);
+        grid.clear_damage();
+        assert_eq!(grid.row_text(2).as_deref(), Some("        "));
+        assert_eq!(grid.row_text(3), None);
+
+        feed(&mut grid, b"\x1b[2;3Hab\x1b[3;6H\x1b[1K");
+        assert_eq!(
+            grid.damage().collect::<Vec<_>>(),
+            [
+                DamageSpan { row: 1, cols: 2..4 },
+                DamageSpan { row: 2, cols: 0..5 },
+            ]
+        );
+        let mut out = Vec::new();
+        grid.draw(&mut out);
+        assert_eq!(
+            str::from_utf8(&out).unwrap(),
+            "\x1b[2;3H\x1b[0mab\x1b[3;1H\x1b#5\x1b[0m     \x1b[0m\x1b[3;6H"
+        );
+        assert_eq!(grid.damage().count(), 0);
+        assert_eq!(grid.row_text(1).as_deref(), Some("  ab    "));
+
+        // The contents are drawn from the top left corner, whatever the origin,
+        // and leave the damage alone.
+        grid.set_origin(4, 4);
+        feed(&mut grid, b"\x1b[1;1H\x1b[31mc");
+        let damage: Vec<_> = grid.damage().collect();
+        let mut copy = Grid::<Sink>::new(8, 3);
+        feed(&mut copy, &grid.contents());
+        assert_eq!(grid.damage().collect::<Vec<_>>(), damage);
+        assert_eq!(copy.row_text(0).as_deref(), Some("c       "));
+        assert_eq!(copy.row_text(1).as_deref(), Some("  ab    "));
+        assert_eq!(copy.cell(0, 0).unwrap().fg(), Color::Indexed(1));
+        grid.set_origin(0, 0);
+
+        // Every cell which changes is covered by the damage.
+        let snapshot = |grid: &Grid<Sink>| {
+            (0..3)
+                .flat_map(|row| (0..8).map(move |col| (col, row)))
+                .map(|(col, row)| *grid.cell(col, row).unwrap())
+                .collect::<Vec<_>>()
+        };
+        let sequences: [&[u8]; 8] = [
+            b"\x1b[1;4Hxyz",
+            b"\x1b[2;2H\x1b[2P",
+            b"\x1b[1;1H\x1b[@",
+            b"\x1b[3;8Hq\r\n",
+            b"\x1b[2;5H\x1b[J",
+            b"\x1b[1;2H\x1b[3X\x1bM",
+            b"\x1b[1;1Habc\x1b[3S",
+            b"\x1b[2;1Hdef\x1b[9T",
+        ];
+        for bytes in sequences.iter() {
+            let before = snapshot(&grid);
+            grid.clear_damage();
+            feed(&mut grid, bytes);
+            let after = snapshot(&grid);
+            let damage: Vec<_> = grid.damage().collect();
+            for (idx, (old, new)) in before.iter().zip(&after).enumerate() {
+                let (col, row) = ((idx % 8) as u16, (idx / 8) as u16);
+                if old != new {
+                    assert!(
+                        damage
+                            .iter()
+                            .any(|span| span.row == row && span.cols.contains(&col)),
+                        "{:?}: change at {}, {} not in {:?}",
+                        bytes,
+                        col,
+                        row,
+                        damage
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn cursor_blink() {
+        let mut grid = Grid::<Sink>::new(4, 2);
+        assert_eq!(grid.cursor_style(), CursorStyle::default());
+        grid.set_mode(Mode::BlinkingCursor);
+        assert!(grid.cursor_style().blinking);
+        grid.set_cursor_style(Some(CursorStyle {
+            shape: CursorShape::Beam,
+            blinking: false,
+        }));
+        assert!(!grid.cursor_style().blinking);
+        grid.set_mode(Mode::BlinkingCursor);
+        assert_eq!(grid.cursor_style().shape, CursorShape::Beam);
+        assert!(grid.cursor_style().blinking);
+        grid.unset_mode(Mode::BlinkingCursor);
+        assert!(!grid.cursor_style().blinking);
+        grid.set_cursor_style(None);
+        assert_eq!(grid.cursor_style(), CursorStyle::default());
+    }
+
+    #[test]
+    fn dynamic_colors() {
+        let mut grid = Grid::<Vec<u8>>::new(4, 2);
+        let red = Rgb { r: 255, g: 0, b: 0 };
+        let mut reply = Vec::new();
+        grid.dynamic_color_sequence(&mut reply, 4, 4, "\x07");
+        assert_eq!(reply, b"\x1b]4;4;rgb:0000/0000/eeee\x07");
+
+        grid.set_color(NamedColor::Background as usize, red);
+        reply.clear();
+        grid.dynamic_color_sequence(
+            &mut reply,
+            11,
+            NamedColor::Background as usize,
+            "\x1b\\",
+        );
+        assert_eq!(reply, b"\x1b]11;rgb:ffff/0000/0000\x1b\\");
+
+        grid.set_color(1, Rgb { r: 1, g: 2, b: 3 });
+        grid.terminal_attribute(Attr::Foreground(Color::Named(NamedColor::Red)));
+        input_str!(grid, "a");
+        grid.terminal_attribute(Attr::Foreground(Color::Indexed(2)));
+        input_str!(grid, "b");
+        let mut out = Vec::new();
+        grid.draw(&mut out);
+        let out = str::from_utf8(&out).unwrap();
+        assert!(out
+            .contains("\x1b[0;38;2;1;2;3;48;2;255;0;0ma\x1b[0;38;5;2;48;2;255;0;0mb"));
+
+        grid.reset_color(1);
+        grid.reset_color(NamedColor::Background as usize);
+        let mut out = Vec::new();
+        grid.draw(&mut out);
+        let out = str::from_utf8(&out).unwrap();
+        assert!(out.contains("\x1b[0;38;5;1ma\x1b[0;38;5;2mb"));
+    }
 }
@@ -0,0 +1,168 @@
+//! The contents of a single grid position.
+
+use std::ops::{BitOr, BitOrAssign};
+
+use crate::ansi::{Color, NamedColor};
+
+/// Rendition attributes of a `Cell`, stored as a bitfield.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Flags(u16);
+
+impl Flags {
+    pub const BOLD: Flags = Flags(1);
+    pub const DIM: Flags = Flags(1 << 1);
+    pub const ITALIC: Flags = Flags(1 << 2);
+    pub const UNDERLINE: Flags = Flags(1 << 3);
+    pub const DOUBLE_UNDERLINE: Flags = Flags(1 << 4);
+    pub const STRIKETHROUGH: Flags = Flags(1 << 5);
+    pub const INVERSE: Flags = Flags(1 << 6);
+    pub const HIDDEN: Flags = Flags(1 << 7);
+    pub const BLINK: Flags = Flags(1 << 8);
+    /// Survives selective erase (DECSCA); not part of the rendition.
+    pub const PROTECTED: Flags = Flags(1 << 9);
+
+    /// Either kind of underline.
+    pub const ALL_UNDERLINES: Flags =
+        Flags(Flags::UNDERLINE.0 | Flags::DOUBLE_UNDERLINE.0);
+
+    /// No attributes set.
+    pub const fn empty() -> Flags {
+        Flags(0)
+    }
+
+    /// The raw bits of this set.
+    pub const fn bits(self) -> u16 {
+        self.0
+    }
+
+    /// Whether no attributes are set.
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Whether every attribute in `other` is set.
+    pub const fn contains(self, other: Flags) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Set every attribute in `other`.
+    pub fn insert(&mut self, other: Flags) {
+        self.0 |= other.0;
+    }
+
+    /// Clear every attribute in `other`.
+    pub fn remove(&mut self, other: Flags) {
+        self.0 &= !other.0;
+    }
+
+    /// Flip every attribute in `other`.
+    pub fn toggle(&mut self, other: Flags) {
+        self.0 ^= other.0;
+    }
+}
+
+impl BitOr for Flags {
+    type Output = Flags;
+
+    fn bitor(self, rhs: Flags) -> Flags {
+        Flags(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for Flags {
+    fn bitor_assign(&mut self, rhs: Flags) {
+        self.insert(rhs);
+    }
+}
+
+/// A character together with its rendition.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cell {
+    pub(super) c: char,
+    pub(super) fg: Color,
+    pub(super) bg: Color,
+    pub(super) flags: Flags,
+    /// The underline colour; `None` follows the foreground.
+    pub(super) underline_color: Option<Color>,
+    /// Identifies the OSC 8 hyperlink this cell belongs to, if any.
+    pub(super) hyperlink: Option<u16>,
+}
+
+impl Default for Cell {
+    fn default() -> Cell {
+        Cell {
+            c: ' ',
+            fg: Color::Named(NamedColor::Foreground),
+            bg: Color::Named(NamedColor::Background),
+            flags: Flags::empty(),
+            underline_color: None,
+            hyperlink: None,
+        }
+    }
+}
+
+impl Cell {
+    /// The character displayed in this cell.
+    pub fn c(&self) -> char {
+        self.c
+    }
+
+    /// The foreground colour.
+    pub fn fg(&self) -> Color {
+        self.fg
+    }
+
+    /// The background colour.
+    pub fn bg(&self) -> Color {
+        self.bg
+    }
+
+    /// The rendition attributes.
+    pub fn flags(&self) -> Flags {
+        self.flags
+    }
+
+    /// The underline colour, if it differs from the foreground.
+    pub fn underline_color(&self) -> Option<Color> {
+        self.underline_color
+    }
+
+    /// The id of the hyperlink this cell belongs to, if any.
+    pub fn hyperlink(&self) -> Option<u16> {
+        self.hyperlink
+    }
+
+    /// Whether `other` is displayed with the same rendition, ignoring the character.
+    pub fn same_style(&self, other: &Cell) -> bool {
+        self.fg == other.fg
+            && self.bg == other.bg
+            && self.flags.0 & !Flags::PROTECTED.0 == other.flags.0 & !Flags::PROTECTED.0
+            && self.underline_color == other.underline_color
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flags() {
+        let mut flags = Flags::BOLD | Flags::UNDERLINE;
+        assert!(flags.contains(Flags::BOLD));
+        assert!(!flags.contains(Flags::BOLD | Flags::ITALIC));
+        assert!(!flags.contains(Flags::ALL_UNDERLINES));
+        flags.remove(Flags::ALL_UNDERLINES);
+        assert_eq!(flags, Flags::BOLD);
+        flags |= Flags::BLINK;
+        assert_eq!(flags.bits(), 0b1_0000_0001);
+        flags.toggle(Flags::BOLD | Flags::ITALIC);
+        assert_eq!(flags, Flags::ITALIC | Flags::BLINK);
+        flags.remove(Flags::ITALIC | Flags::BLINK);
+        assert!(flags.is_empty());
+    }
+
+    #[test]
+    fn compact() {
+        assert!(std::mem::size_of::<Cell>() <= 24);
+    }
+}
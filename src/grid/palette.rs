@@ -0,0 +1,175 @@
+//! Colours which applications may redefine.
+
+use crate::ansi::{NamedColor, Rgb};
+
+/// Number of entries: the 256 indexed colours, then foreground, background and
+/// cursor.
+const PALETTE_LEN: usize = NamedColor::Cursor as usize + 1;
+
+/// The colour table of a `Grid`.
+///
+/// Only colours set by the application are stored; the rest are assumed to
+/// match the outer terminal, for which xterm's defaults are reported.
+#[derive(Debug, Clone)]
+pub struct Palette {
+    overrides: Vec<Option<Rgb>>,
+}
+
+impl Default for Palette {
+    fn default() -> Palette {
+        Palette {
+            overrides: vec![None; PALETTE_LEN],
+        }
+    }
+}
+
+impl Palette {
+    /// The colour at `index`, or `None` if `index` is out of range.
+    pub fn get(&self, index: usize) -> Option<Rgb> {
+        if index < PALETTE_LEN {
+            Some(self.overrides[index].unwrap_or_else(|| default_color(index)))
+        } else {
+            None
+        }
+    }
+
+    /// The colour at `index` if the application has changed it.
+    pub fn overridden(&self, index: usize) -> Option<Rgb> {
+        self.overrides.get(index).copied().flatten()
+    }
+
+    /// Set the colour at `index`, returning whether `index` is in range.
+    pub fn set(&mut self, index: usize, color: Rgb) -> bool {
+        match self.overrides.get_mut(index) {
+            Some(entry) => {
+                *entry = Some(color);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Restore the colour at `index` to its default.
+    pub fn reset(&mut self, index: usize) {
+        if let Some(entry) = self.overrides.get_mut(index) {
+            *entry = None;
+        }
+    }
+}
+
+/// A named set of colours applied to a window, beneath any the application sets.
+#[derive(Debug, Clone)]
+pub struct Theme {
+    /// Shown to the user while the theme is applied.
+    pub name: String,
+    /// The colours to change; entries left unset keep their defaults.
+    pub palette: Palette,
+}
+
+/// xterm's default colour at `index`.
+fn default_color(index: usize) -> Rgb {
+    const ANSI: [(u8, u8, u8); 16] = [
+        (0x00, 0x00, 0x00),
+        (0xcd, 0x00, 0x00),
+        (0x00, 0xcd, 0x00),
+        (0xcd, 0xcd, 0x00),
+        (0x00, 0x00, 0xee),
+        (0xcd, 0x00, 0xcd),
+        (0x00, 0xcd, 0xcd),
+        (0xe5, 0xe5, 0xe5),
+        (0x7f, 0x7f, 0x7f),
+        (0xff, 0x00, 0x00),
+        (0x00, 0xff, 0x00),
+        (0xff, 0xff, 0x00),
+        (0x5c, 0x5c, 0xff),
+        (0xff, 0x00, 0xff),
+        (0x00, 0xff, 0xff),
+        (0xff, 0xff, 0xff),
+    ];
+    let gray = |level: u8| Rgb {
+        r: level,
+        g: level,
+        b: level,
+    };
+
+    match index {
+        0..=15 => {
+            let (r, g, b) = ANSI[index];
+            Rgb { r, g, b }
+        }
+        16..=231 => {
+            // 6x6x6 colour cube.
+            let level = |i: usize| if i == 0 { 0 } else { 55 + 40 * i as u8 };
+            let i = index - 16;
+            Rgb {
+                r: level(i / 36),
+                g: level(i / 6 % 6),
+                b: level(i % 6),
+            }
+        }
+        232..=255 => gray(8 + 10 * (index - 232) as u8),
+        _ if index == NamedColor::Background as usize => gray(0),
+        _ => gray(0xe5),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults() {
+        let palette = Palette::default();
+        assert_eq!(
+            palette.get(1),
+            Some(Rgb {
+                r: 0xcd,
+                g: 0,
+                b: 0
+            })
+        );
+        assert_eq!(palette.get(16), Some(Rgb { r: 0, g: 0, b: 0 }));
+        assert_eq!(
+            palette.get(196),
+            Some(Rgb {
+                r: 0xff,
+                g: 0,
+                b: 0
+            })
+        );
+        assert_eq!(
+            palette.get(244),
+            Some(Rgb {
+                r: 0x80,
+                g: 0x80,
+                b: 0x80
+            })
+        );
+        assert_eq!(
+            palette.get(NamedColor::Background as usize),
+            Some(Rgb { r: 0, g: 0, b: 0 })
+        );
+        assert_eq!(palette.get(PALETTE_LEN), None);
+    }
+
+    #[test]
+    fn overrides() {
+        let mut palette = Palette::default();
+        let color = Rgb { r: 1, g: 2, b: 3 };
+        assert!(palette.set(4, color));
+        assert!(!palette.set(PALETTE_LEN, color));
+        assert_eq!(palette.get(4), Some(color));
+        assert_eq!(palette.overridden(4), Some(color));
+        assert_eq!(palette.overridden(5), None);
+        palette.reset(4);
+        assert_eq!(palette.overridden(4), None);
+        assert_eq!(
+            palette.get(4),
+            Some(Rgb {
+                r: 0,
+                g: 0,
+                b: 0xee
+            })
+        );
+    }
+}
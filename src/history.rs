@@ -0,0 +1,195 @@
+//! History of commands entered at the command prompt.
+//!
+//! The history is kept in a file per user, which every client reads before
+//! adding to it, so commands entered in one client are available in the others
+//! and survive restarts.
+
+use std::{
+    collections::VecDeque,
+    env, fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+/// A bounded list of commands without duplicates, most recent last.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct History {
+    entries: VecDeque<String>,
+    capacity: usize,
+    path: Option<PathBuf>,
+}
+
+impl History {
+    /// How many commands are kept unless configured otherwise.
+    pub const DEFAULT_CAPACITY: usize = 1000;
+
+    /// Create an empty history of at most `capacity` commands, kept in memory.
+    pub fn new(capacity: usize) -> History {
+        History {
+            entries: VecDeque::new(),
+            capacity,
+            path: None,
+        }
+    }
+
+    /// Load the history kept in `path`, which need not exist yet.
+    pub fn open<P: Into<PathBuf>>(path: P, capacity: usize) -> io::Result<History> {
+        let mut history = History {
+            path: Some(path.into()),
+            ..History::new(capacity)
+        };
+        history.reload()?;
+        Ok(history)
+    }
+
+    /// The user's history file: `$XDG_STATE_HOME/session-manager/history`, or
+    /// under `~/.local/state` if that is unset.
+    pub fn default_path() -> Option<PathBuf> {
+        let state = env::var_os("XDG_STATE_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| {
+                env::var_os("HOME").map(|home| Path::new(&home).join(".local/state"))
+            })?;
+        Some(state.join("session-manager").join("history"))
+    }
+
+    /// Re-read the history file, picking up commands from other clients.
+    pub fn reload(&mut self) -> io::Result<()> {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e),
+        };
+        self.entries.clear();
+        for line in contents.lines() {
+            self.insert(line);
+        }
+        Ok(())
+    }
+
+    /// Record `command` as the most recent, saving the history if it has a file.
+    ///
+    /// An earlier entry for the same command is removed; blank commands are
+    /// ignored.
+    pub fn push(&mut self, command: &str) -> io::Result<()> {
+        if command.trim().is_empty() {
+            return Ok(());
+        }
+        self.reload()?;
+        self.insert(command);
+        self.save()
+    }
+
+    /// Change how many commands are kept, dropping the oldest if necessary.
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        self.truncate();
+    }
+
+    /// The number of commands in the history.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether the history has no commands.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The command entered `back` commands ago, `0` being the most recent.
+    pub fn get(&self, back: usize) -> Option<&str> {
+        let idx = self.entries.len().checked_sub(back + 1)?;
+        self.entries.get(idx).map(String::as_str)
+    }
+
+    /// The commands, oldest first.
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &str> {
+        self.entries.iter().map(String::as_str)
+    }
+
+    fn insert(&mut self, command: &str) {
+        let command = command.trim();
+        if command.is_empty() {
+            return;
+        }
+        self.entries.retain(|entry| entry != command);
+        self.entries.push_back(command.to_string());
+        self.truncate();
+    }
+
+    fn truncate(&mut self) {
+        while self.entries.len() > self.capacity {
+            self.entries.pop_front();
+        }
+    }
+
+    /// Replace the history file, so readers never see a partial write.
+    fn save(&self) -> io::Result<()> {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut tmp = path.clone().into_os_string();
+        tmp.push(format!(".{}", std::process::id()));
+        let mut file = fs::File::create(&tmp)?;
+        for entry in &self.entries {
+            writeln!(file, "{}", entry)?;
+        }
+        file.sync_all()?;
+        fs::rename(&tmp, path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dedup_and_capacity() {
+        let mut history = History::new(3);
+        for command in &["new-window", "rename-window a", " ", "new-window", "kill"] {
+            history.push(command).unwrap();
+        }
+        assert_eq!(
+            history.iter().collect::<Vec<_>>(),
+            ["rename-window a", "new-window", "kill"]
+        );
+        assert_eq!(history.get(0), Some("kill"));
+        assert_eq!(history.get(2), Some("rename-window a"));
+        assert_eq!(history.get(3), None);
+
+        history.push("split").unwrap();
+        assert_eq!(history.len(), 3);
+        assert_eq!(history.get(2), Some("new-window"));
+        history.set_capacity(1);
+        assert_eq!(history.iter().collect::<Vec<_>>(), ["split"]);
+    }
+
+    #[test]
+    fn shared_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state").join("history");
+        let mut first = History::open(&path, 10).unwrap();
+        let mut second = History::open(&path, 10).unwrap();
+        assert!(first.is_empty());
+
+        first.push("new-window").unwrap();
+        second.push("kill-window").unwrap();
+        first.push("new-window").unwrap();
+        assert_eq!(
+            first.iter().collect::<Vec<_>>(),
+            ["kill-window", "new-window"]
+        );
+
+        let reopened = History::open(&path, 10).unwrap();
+        assert_eq!(reopened, first);
+    }
+}
@@ -0,0 +1,78 @@
+//! A record of the input sent to a window, to see what was typed into it.
+
+use std::collections::VecDeque;
+
+/// The most recent bytes of input to a window, up to a limit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InputLog {
+    bytes: VecDeque<u8>,
+    capacity: usize,
+}
+
+impl InputLog {
+    /// How many bytes are kept unless configured otherwise.
+    pub const DEFAULT_CAPACITY: usize = 64 * 1024;
+
+    /// Create an empty log keeping the last `capacity` bytes.
+    pub fn new(capacity: usize) -> InputLog {
+        InputLog {
+            bytes: VecDeque::new(),
+            capacity,
+        }
+    }
+
+    /// Add `data` to the log, forgetting the oldest bytes beyond its capacity.
+    pub fn record(&mut self, data: &[u8]) {
+        self.bytes.extend(data);
+        let excess = self.bytes.len().saturating_sub(self.capacity);
+        self.bytes.drain(..excess);
+    }
+
+    /// How many bytes are kept.
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    /// The input as text to read: Enter starts a new line, and other control
+    /// characters are written in caret notation, e.g. `^C` or `^[` for Escape.
+    pub fn text(&self) -> String {
+        let (front, back) = self.bytes.as_slices();
+        let bytes = [front, back].concat();
+        let mut text = String::with_capacity(bytes.len());
+        for c in String::from_utf8_lossy(&bytes).chars() {
+            match c {
+                '\r' | '\n' => text.push('\n'),
+                '\t' => text.push('\t'),
+                '\x7f' => text.push_str("^?"),
+                c if (c as u32) < 0x20 => {
+                    text.push('^');
+                    text.push(char::from(c as u8 + 0x40));
+                }
+                c => text.push(c),
+            }
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn input_log_text() {
+        let mut log = InputLog::new(16);
+        assert!(log.is_empty());
+        log.record(b"ls -l\r");
+        log.record(b"vim\x1b:q\x7f\x03");
+        assert_eq!(log.text(), "ls -l\nvim^[:q^?^C");
+        log.record("héllo".as_bytes());
+        // Only the last 16 bytes are kept.
+        assert_eq!(log.len(), 16);
+        assert_eq!(log.text(), "l\nvim^[:q^?^Chéllo");
+    }
+}
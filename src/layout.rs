@@ -0,0 +1,903 @@
+//! Arrangement of the panes of a window.
+//!
+//! A window's panes form a tree: each split lays out its children side by side
+//! or one above the other, dividing its space between them by weight, with a
+//! separator line between neighbours. Because the weights are relative, the
+//! proportions are kept when the terminal is resized.
+
+use std::{fmt, str::FromStr};
+
+use nix::pty::Winsize;
+
+/// How a split arranges its children.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// Side by side, separated by vertical lines.
+    Horizontal,
+    /// One above the other, separated by horizontal lines.
+    Vertical,
+}
+
+/// A rectangle of the terminal, in cells.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Rect {
+    pub col: u16,
+    pub row: u16,
+    pub cols: u16,
+    pub rows: u16,
+}
+
+impl Rect {
+    /// The rectangle covering a terminal of size `size`.
+    pub fn from_size(size: Winsize) -> Rect {
+        Rect {
+            col: 0,
+            row: 0,
+            cols: size.ws_col,
+            rows: size.ws_row,
+        }
+    }
+
+    /// The size of a terminal filling this rectangle.
+    pub fn size(&self) -> Winsize {
+        Winsize {
+            ws_row: self.rows,
+            ws_col: self.cols,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        }
+    }
+
+    /// Whether this rectangle covers no cells.
+    pub fn is_empty(&self) -> bool {
+        self.cols == 0 || self.rows == 0
+    }
+}
+
+/// The smallest a pane may be made by splitting or resizing, in cells.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MinSize {
+    pub cols: u16,
+    pub rows: u16,
+}
+
+impl MinSize {
+    /// The length a pane must have in `direction`: its width across a
+    /// horizontal split.
+    fn length(self, direction: Direction) -> i32 {
+        match direction {
+            Direction::Horizontal => i32::from(self.cols),
+            Direction::Vertical => i32::from(self.rows),
+        }
+    }
+}
+
+/// Wide enough for a prompt, and high enough for a line of output under it.
+impl Default for MinSize {
+    fn default() -> MinSize {
+        MinSize { cols: 5, rows: 2 }
+    }
+}
+
+/// Written `COLSxROWS`, e.g. `5x2`.
+impl fmt::Display for MinSize {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}x{}", self.cols, self.rows)
+    }
+}
+
+impl FromStr for MinSize {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<MinSize, String> {
+        let size = s.split_once('x').and_then(|(cols, rows)| {
+            Some(MinSize {
+                cols: cols.parse().ok().filter(|cols| *cols > 0)?,
+                rows: rows.parse().ok().filter(|rows| *rows > 0)?,
+            })
+        });
+        size.ok_or_else(|| format!("expected COLSxROWS, not {:?}", s))
+    }
+}
+
+/// Where a layout puts its panes and separators.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Arrangement {
+    /// Each pane, in layout order, with its rectangle.
+    pub panes: Vec<(usize, Rect)>,
+    /// The lines between panes, each one cell wide or high.
+    pub separators: Vec<(Direction, Rect)>,
+}
+
+impl Arrangement {
+    /// Whether every pane is at least `min` in size.
+    pub fn fits(&self, min: MinSize) -> bool {
+        self.panes
+            .iter()
+            .all(|(_, rect)| rect.cols >= min.cols && rect.rows >= min.rows)
+    }
+}
+
+/// A part of a split: a layout, and its share of the split's space.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Child {
+    pub layout: Layout,
+    /// Space is divided between children in proportion to their weights.
+    pub weight: u32,
+}
+
+impl Child {
+    /// A child with weight 1.
+    pub fn new(layout: Layout) -> Child {
+        Child { layout, weight: 1 }
+    }
+}
+
+/// A tree of panes, identified by index.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Layout {
+    Pane(usize),
+    Split {
+        direction: Direction,
+        children: Vec<Child>,
+    },
+}
+
+/// A way of arranging any number of panes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Preset {
+    /// All panes side by side, the same width.
+    EvenHorizontal,
+    /// All panes one above the other, the same height.
+    EvenVertical,
+    /// The first pane on the top half, the others side by side below.
+    MainHorizontal,
+    /// The first pane on the left half, the others stacked on the right.
+    MainVertical,
+    /// Rows and columns of panes, as near square as possible.
+    Tiled,
+}
+
+impl Preset {
+    /// Every preset, in the order they are cycled through.
+    pub const ALL: [Preset; 5] = [
+        Preset::EvenHorizontal,
+        Preset::EvenVertical,
+        Preset::MainHorizontal,
+        Preset::MainVertical,
+        Preset::Tiled,
+    ];
+
+    /// The preset after this one, wrapping around.
+    pub fn next(self) -> Preset {
+        let idx = Preset::ALL.iter().position(|p| *p == self).unwrap();
+        Preset::ALL[(idx + 1) % Preset::ALL.len()]
+    }
+
+    /// Arrange `panes`, in order, in this way.
+    pub fn layout(self, panes: &[usize]) -> Layout {
+        let even = |direction, panes: &[usize]| {
+            Layout::split_evenly(direction, panes.iter().copied().map(Layout::Pane))
+        };
+        // The first pane, and the others split the other way.
+        let main = |direction, panes: &[usize]| match panes.split_first() {
+            Some((first, others)) if !others.is_empty() => {
+                let across = match direction {
+                    Direction::Horizontal => Direction::Vertical,
+                    Direction::Vertical => Direction::Horizontal,
+                };
+                Layout::split_evenly(
+                    direction,
+                    vec![Layout::Pane(*first), even(across, others)],
+                )
+            }
+            _ => even(direction, panes),
+        };
+        match self {
+            Preset::EvenHorizontal => even(Direction::Horizontal, panes),
+            Preset::EvenVertical => even(Direction::Vertical, panes),
+            Preset::MainHorizontal => main(Direction::Vertical, panes),
+            Preset::MainVertical => main(Direction::Horizontal, panes),
+            Preset::Tiled => {
+                let mut cols = 1;
+                while cols * cols < panes.len() {
+                    cols += 1;
+                }
+                let rows = panes
+                    .chunks(cols)
+                    .map(|row| even(Direction::Horizontal, row));
+                Layout::split_evenly(Direction::Vertical, rows)
+            }
+        }
+    }
+}
+
+impl fmt::Display for Preset {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Preset::EvenHorizontal => "even-horizontal",
+            Preset::EvenVertical => "even-vertical",
+            Preset::MainHorizontal => "main-horizontal",
+            Preset::MainVertical => "main-vertical",
+            Preset::Tiled => "tiled",
+        })
+    }
+}
+
+impl FromStr for Preset {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Preset, String> {
+        match s {
+            "even-horizontal" => Ok(Preset::EvenHorizontal),
+            "even-vertical" => Ok(Preset::EvenVertical),
+            "main-horizontal" => Ok(Preset::MainHorizontal),
+            "main-vertical" => Ok(Preset::MainVertical),
+            "tiled" => Ok(Preset::Tiled),
+            _ => Err(format!("unknown layout: {}", s)),
+        }
+    }
+}
+
+impl Layout {
+    /// Lay out `layouts` in `direction` with equal shares; a single layout is
+    /// returned as is.
+    pub fn split_evenly<I: IntoIterator<Item = Layout>>(
+        direction: Direction,
+        layouts: I,
+    ) -> Layout {
+        let mut children: Vec<_> = layouts.into_iter().map(Child::new).collect();
+        if children.len() == 1 {
+            return children.pop().unwrap().layout;
+        }
+        Layout::Split {
+            direction,
+            children,
+        }
+    }
+
+    /// Split `target` in half, placing `pane` after it in `direction`.
+    ///
+    /// Returns `false` if `target` is not in this layout.
+    pub fn split(&mut self, target: usize, pane: usize, direction: Direction) -> bool {
+        match self {
+            Layout::Pane(idx) if *idx == target => {
+                *self = Layout::split_evenly(
+                    direction,
+                    vec![Layout::Pane(target), Layout::Pane(pane)],
+                );
+                true
+            }
+            Layout::Pane(_) => false,
+            Layout::Split {
+                direction: split_direction,
+                children,
+            } => {
+                // Join a split going the same way rather than nesting another.
+                if *split_direction == direction {
+                    let position = children
+                        .iter()
+                        .position(|child| child.layout == Layout::Pane(target));
+                    if let Some(position) = position {
+                        // Doubling every weight keeps the proportions, and lets
+                        // the target's share be halved exactly.
+                        for child in children.iter_mut() {
+                            child.weight *= 2;
+                        }
+                        let weight = children[position].weight / 2;
+                        children[position].weight = weight;
+                        children.insert(
+                            position + 1,
+                            Child {
+                                layout: Layout::Pane(pane),
+                                weight,
+                            },
+                        );
+                        normalize(children);
+                        return true;
+                    }
+                }
+                children
+                    .iter_mut()
+                    .any(|child| child.layout.split(target, pane, direction))
+            }
+        }
+    }
+
+    /// Remove `pane`, giving its space to a neighbour, and returning `false` if
+    /// it is not in this layout or is the only pane left.
+    pub fn remove(&mut self, pane: usize) -> bool {
+        let children = match self {
+            Layout::Pane(_) => return false,
+            Layout::Split { children, .. } => children,
+        };
+        let removed = match children
+            .iter()
+            .position(|child| child.layout == Layout::Pane(pane))
+        {
+            Some(position) => {
+                let weight = children.remove(position).weight;
+                let neighbour = position.saturating_sub(1).min(children.len() - 1);
+                children[neighbour].weight += weight;
+                normalize(children);
+                true
+            }
+            None => children.iter_mut().any(|child| child.layout.remove(pane)),
+        };
+        if children.len() == 1 {
+            *self = children.pop().unwrap().layout;
+        }
+        removed
+    }
+
+    /// Grow `pane` by `cells` in `direction`, or shrink it if `cells` is
+    /// negative, with the layout filling `area`.
+    ///
+    /// The innermost split going that way gives or takes the space from the
+    /// next child (the previous one, for the last), without shrinking the panes
+    /// of either below `min`. Returns whether anything changed.
+    pub fn resize(
+        &mut self,
+        pane: usize,
+        direction: Direction,
+        cells: i32,
+        area: Rect,
+        min: MinSize,
+    ) -> bool {
+        let (split_direction, children) = match self {
+            Layout::Pane(_) => return false,
+            Layout::Split {
+                direction,
+                children,
+            } => (*direction, children),
+        };
+        let areas = split_area(area, split_direction, children);
+        let position = match children
+            .iter()
+            .position(|child| child.layout.contains(pane))
+        {
+            Some(position) => position,
+            None => return false,
+        };
+        if children[position].layout.resize(
+            pane,
+            direction,
+            cells,
+            areas[position],
+            min,
+        ) {
+            return true;
+        }
+        if split_direction != direction {
+            return false;
+        }
+        let lengths: Vec<i32> = areas
+            .iter()
+            .map(|rect| match direction {
+                Direction::Horizontal => i32::from(rect.cols),
+                Direction::Vertical => i32::from(rect.rows),
+            })
+            .collect();
+        let neighbour = if position + 1 < children.len() {
+            position + 1
+        } else {
+            position - 1
+        };
+        // Panes already smaller than `min`, e.g. in a small terminal, are not
+        // made smaller still, nor grown unasked.
+        let least = (children[position].layout.min_length(direction, min)
+            - lengths[position])
+            .min(0);
+        let most = (lengths[neighbour]
+            - children[neighbour].layout.min_length(direction, min))
+        .max(0);
+        let cells = cells.clamp(least, most);
+        if cells == 0 {
+            return false;
+        }
+        // With weights in cells, the new lengths are exact at this size.
+        for (child, length) in children.iter_mut().zip(&lengths) {
+            child.weight = *length as u32;
+        }
+        children[position].weight = (lengths[position] + cells) as u32;
+        children[neighbour].weight = (lengths[neighbour] - cells) as u32;
+        normalize(children);
+        true
+    }
+
+    /// The length this layout needs in `direction` for each of its panes to
+    /// be at least `min`, with the separators between them.
+    fn min_length(&self, direction: Direction, min: MinSize) -> i32 {
+        match self {
+            Layout::Pane(_) => min.length(direction),
+            Layout::Split {
+                direction: split_direction,
+                children,
+            } => {
+                let lengths = children
+                    .iter()
+                    .map(|child| child.layout.min_length(direction, min));
+                if *split_direction == direction {
+                    lengths.sum::<i32>() + children.len() as i32 - 1
+                } else {
+                    lengths.max().unwrap_or(0)
+                }
+            }
+        }
+    }
+
+    /// The panes, in layout order.
+    pub fn panes(&self) -> Vec<usize> {
+        match self {
+            Layout::Pane(idx) => vec![*idx],
+            Layout::Split { children, .. } => children
+                .iter()
+                .flat_map(|child| child.layout.panes())
+                .collect(),
+        }
+    }
+
+    /// Whether `pane` is in this layout.
+    pub fn contains(&self, pane: usize) -> bool {
+        self.panes().contains(&pane)
+    }
+
+    /// Give each pane the index `renumber` maps its index to.
+    pub fn renumber<F: FnMut(usize) -> usize>(&mut self, renumber: &mut F) {
+        match self {
+            Layout::Pane(idx) => *idx = renumber(*idx),
+            Layout::Split { children, .. } => {
+                for child in children {
+                    child.layout.renumber(renumber);
+                }
+            }
+        }
+    }
+
+    /// Lay out the panes in `area`.
+    pub fn arrange(&self, area: Rect) -> Arrangement {
+        let mut arrangement = Arrangement::default();
+        self.arrange_into(area, &mut arrangement);
+        arrangement
+    }
+
+    fn arrange_into(&self, area: Rect, arrangement: &mut Arrangement) {
+        let (direction, children) = match self {
+            Layout::Pane(idx) => {
+                arrangement.panes.push((*idx, area));
+                return;
+            }
+            Layout::Split {
+                direction,
+                children,
+            } => (*direction, children),
+        };
+        let areas = split_area(area, direction, children);
+        for (idx, (child, rect)) in children.iter().zip(&areas).enumerate() {
+            child.layout.arrange_into(*rect, arrangement);
+            if idx + 1 < children.len() {
+                let separator = match direction {
+                    Direction::Horizontal => Rect {
+                        col: rect.col + rect.cols,
+                        cols: 1,
+                        ..*rect
+                    },
+                    Direction::Vertical => Rect {
+                        row: rect.row + rect.rows,
+                        rows: 1,
+                        ..*rect
+                    },
+                };
+                arrangement.separators.push((direction, separator));
+            }
+        }
+    }
+}
+
+/// Written as the pane's index, or for a split, `h` or `v` for its direction and
+/// its children in brackets, each as its weight, a colon and its layout:
+/// `h(1:0,1:v(2:1,1:2))`.
+impl fmt::Display for Layout {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Layout::Pane(idx) => write!(f, "{}", idx),
+            Layout::Split {
+                direction,
+                children,
+            } => {
+                let direction = match direction {
+                    Direction::Horizontal => 'h',
+                    Direction::Vertical => 'v',
+                };
+                write!(f, "{}(", direction)?;
+                for (i, child) in children.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}:{}", child.weight, child.layout)?;
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+impl FromStr for Layout {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Layout, String> {
+        let mut rest = s;
+        match parse_layout(&mut rest) {
+            Some(layout) if rest.is_empty() => Ok(layout),
+            _ => Err(format!("bad layout: {}", s)),
+        }
+    }
+}
+
+/// Take a layout from the start of `s`.
+fn parse_layout(s: &mut &str) -> Option<Layout> {
+    let direction = match s.get(..2) {
+        Some("h(") => Direction::Horizontal,
+        Some("v(") => Direction::Vertical,
+        _ => return parse_number(s).map(Layout::Pane),
+    };
+    *s = &s[2..];
+    let mut children = Vec::new();
+    loop {
+        let weight = parse_number(s).filter(|weight| *weight > 0)?;
+        *s = s.strip_prefix(':')?;
+        let layout = parse_layout(s)?;
+        children.push(Child { layout, weight });
+        match s.strip_prefix(',') {
+            Some(rest) => *s = rest,
+            None => break,
+        }
+    }
+    *s = s.strip_prefix(')')?;
+    if children.len() < 2 {
+        return None;
+    }
+    Some(Layout::Split {
+        direction,
+        children,
+    })
+}
+
+/// Take a number from the start of `s`.
+fn parse_number<T: FromStr>(s: &mut &str) -> Option<T> {
+    let end = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let number = s[..end].parse().ok()?;
+    *s = &s[end..];
+    Some(number)
+}
+
+/// The areas of `children` of a split in `direction` filling `area`, leaving a
+/// cell between neighbours for the separator.
+fn split_area(area: Rect, direction: Direction, children: &[Child]) -> Vec<Rect> {
+    let count = children.len() as u16;
+    let (start, length) = match direction {
+        Direction::Horizontal => (area.col, area.cols),
+        Direction::Vertical => (area.row, area.rows),
+    };
+    let available = length.saturating_sub(count - 1);
+    let sizes = divide(available, children.iter().map(|child| child.weight));
+    let mut offset = start;
+    sizes
+        .into_iter()
+        .map(|size| {
+            let rect = match direction {
+                Direction::Horizontal => Rect {
+                    col: offset,
+                    cols: size,
+                    ..area
+                },
+                Direction::Vertical => Rect {
+                    row: offset,
+                    rows: size,
+                    ..area
+                },
+            };
+            offset += size + 1;
+            rect
+        })
+        .collect()
+}
+
+/// Divide `length` cells in proportion to `weights`, giving any cells left over
+/// from rounding to the first parts.
+fn divide<I: Iterator<Item = u32>>(length: u16, weights: I) -> Vec<u16> {
+    let mut weights: Vec<u64> = weights.map(u64::from).collect();
+    if weights.iter().all(|weight| *weight == 0) {
+        weights.iter_mut().for_each(|weight| *weight = 1);
+    }
+    let total: u64 = weights.iter().sum();
+    let mut sizes: Vec<u16> = weights
+        .iter()
+        .map(|weight| (u64::from(length) * weight / total) as u16)
+        .collect();
+    // Fewer cells are left over than there are parts.
+    let left = length - sizes.iter().sum::<u16>();
+    for size in sizes.iter_mut().take(left as usize) {
+        *size += 1;
+    }
+    sizes
+}
+
+/// Keep weights small by dividing out their common factor.
+fn normalize(children: &mut [Child]) {
+    fn gcd(a: u32, b: u32) -> u32 {
+        if b == 0 {
+            a
+        } else {
+            gcd(b, a % b)
+        }
+    }
+    let factor = children.iter().fold(0, |acc, child| gcd(acc, child.weight));
+    if factor > 1 {
+        for child in children.iter_mut() {
+            child.weight /= factor;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AREA: Rect = Rect {
+        col: 0,
+        row: 0,
+        cols: 80,
+        rows: 24,
+    };
+
+    const CELL: MinSize = MinSize { cols: 1, rows: 1 };
+
+    #[test]
+    fn split_and_remove() {
+        let mut layout = Layout::Pane(0);
+        assert!(layout.split(0, 1, Direction::Horizontal));
+        assert!(layout.split(1, 2, Direction::Vertical));
+        assert!(layout.split(0, 3, Direction::Horizontal));
+        assert!(!layout.split(7, 8, Direction::Vertical));
+        assert_eq!(layout.panes(), [0, 3, 1, 2]);
+
+        assert!(layout.remove(1));
+        assert!(!layout.remove(1));
+        assert_eq!(
+            layout,
+            Layout::Split {
+                direction: Direction::Horizontal,
+                children: vec![
+                    Child {
+                        layout: Layout::Pane(0),
+                        weight: 1,
+                    },
+                    Child {
+                        layout: Layout::Pane(3),
+                        weight: 1,
+                    },
+                    Child {
+                        layout: Layout::Pane(2),
+                        weight: 2,
+                    },
+                ],
+            }
+        );
+        let mut renumbered = layout.clone();
+        renumbered.renumber(&mut |idx| idx + 10);
+        assert_eq!(renumbered.panes(), [10, 13, 12]);
+
+        assert!(layout.remove(3));
+        assert!(layout.remove(2));
+        assert_eq!(layout, Layout::Pane(0));
+        assert!(!layout.remove(0));
+    }
+
+    #[test]
+    fn arrange() {
+        let mut layout = Layout::Pane(0);
+        assert_eq!(layout.arrange(AREA).panes, [(0, AREA)]);
+        layout.split(0, 1, Direction::Horizontal);
+        layout.split(1, 2, Direction::Vertical);
+        let arrangement = layout.arrange(AREA);
+        assert_eq!(
+            arrangement.panes,
+            [
+                (0, Rect { cols: 40, ..AREA }),
+                (
+                    1,
+                    Rect {
+                        col: 41,
+                        cols: 39,
+                        rows: 12,
+                        ..AREA
+                    }
+                ),
+                (
+                    2,
+                    Rect {
+                        col: 41,
+                        row: 13,
+                        cols: 39,
+                        rows: 11,
+                    }
+                ),
+            ]
+        );
+        assert_eq!(
+            arrangement.separators,
+            [
+                (
+                    Direction::Horizontal,
+                    Rect {
+                        col: 40,
+                        cols: 1,
+                        ..AREA
+                    }
+                ),
+                (
+                    Direction::Vertical,
+                    Rect {
+                        col: 41,
+                        row: 12,
+                        cols: 39,
+                        rows: 1,
+                    }
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn weights() {
+        let mut layout = Layout::Pane(0);
+        layout.split(0, 1, Direction::Vertical);
+        layout.split(1, 2, Direction::Vertical);
+        let rows = |layout: &Layout, rows| {
+            let area = Rect { rows, ..AREA };
+            let arrangement = layout.arrange(area);
+            arrangement
+                .panes
+                .iter()
+                .map(|(_, rect)| rect.rows)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(rows(&layout, 24), [12, 5, 5]);
+        assert_eq!(rows(&layout, 50), [24, 12, 12]);
+        layout.remove(0);
+        assert_eq!(rows(&layout, 50), [37, 12]);
+        assert_eq!(divide(10, vec![0, 0].into_iter()), [5, 5]);
+    }
+
+    #[test]
+    fn resize() {
+        let mut layout = Layout::Pane(0);
+        layout.split(0, 1, Direction::Horizontal);
+        let widths = |layout: &Layout| -> Vec<u16> {
+            let panes = layout.arrange(AREA).panes;
+            panes.iter().map(|(_, rect)| rect.cols).collect()
+        };
+        assert_eq!(widths(&layout), [40, 39]);
+        assert!(layout.resize(0, Direction::Horizontal, 5, AREA, CELL));
+        assert_eq!(widths(&layout), [45, 34]);
+        assert!(!layout.resize(0, Direction::Vertical, 5, AREA, CELL));
+        assert!(!layout.resize(7, Direction::Horizontal, 5, AREA, CELL));
+
+        // The last pane takes from the one before it, leaving it a cell.
+        assert!(layout.resize(1, Direction::Horizontal, 100, AREA, CELL));
+        assert_eq!(widths(&layout), [1, 78]);
+        assert!(!layout.resize(1, Direction::Horizontal, 1, AREA, CELL));
+
+        // Nested splits going the other way pass the resize up.
+        layout.split(1, 2, Direction::Vertical);
+        assert!(layout.resize(2, Direction::Horizontal, -38, AREA, CELL));
+        assert_eq!(widths(&layout), [39, 40, 40]);
+        assert!(layout.resize(2, Direction::Vertical, 2, AREA, CELL));
+        let heights: Vec<u16> = layout
+            .arrange(AREA)
+            .panes
+            .iter()
+            .map(|(_, rect)| rect.rows)
+            .collect();
+        assert_eq!(heights, [24, 10, 13]);
+        // Proportions are kept when the area changes.
+        let half = Rect { rows: 12, ..AREA };
+        let heights: Vec<u16> = layout
+            .arrange(half)
+            .panes
+            .iter()
+            .map(|(_, rect)| rect.rows)
+            .collect();
+        assert_eq!(heights, [12, 5, 6]);
+    }
+
+    #[test]
+    fn resize_min_size() {
+        let min = MinSize { cols: 10, rows: 3 };
+        let mut layout = Layout::Pane(0);
+        layout.split(0, 1, Direction::Horizontal);
+        layout.split(1, 2, Direction::Vertical);
+        layout.split(2, 3, Direction::Horizontal);
+        let widths = |layout: &Layout| -> Vec<u16> {
+            let panes = layout.arrange(AREA).panes;
+            panes.iter().map(|(_, rect)| rect.cols).collect()
+        };
+        assert_eq!(widths(&layout), [40, 39, 19, 19]);
+        // The right half keeps room for its two panes side by side.
+        assert!(layout.resize(0, Direction::Horizontal, 100, AREA, min));
+        assert_eq!(widths(&layout), [58, 21, 10, 10]);
+        assert!(!layout.resize(0, Direction::Horizontal, 1, AREA, min));
+        assert!(layout.resize(0, Direction::Horizontal, -100, AREA, min));
+        assert_eq!(widths(&layout)[0], 10);
+        assert!(layout.resize(1, Direction::Vertical, -100, AREA, min));
+        let heights: Vec<u16> = layout
+            .arrange(AREA)
+            .panes
+            .iter()
+            .map(|(_, rect)| rect.rows)
+            .collect();
+        assert_eq!(heights, [24, 3, 20, 20]);
+        assert!(layout.arrange(AREA).fits(min));
+        assert!(!layout.arrange(AREA).fits(MinSize { cols: 11, rows: 3 }));
+
+        // A pane already too small is not shrunk further.
+        let small = Rect { cols: 15, ..AREA };
+        assert!(!layout.resize(0, Direction::Horizontal, -1, small, min));
+
+        assert_eq!("10x3".parse(), Ok(min));
+        assert_eq!(min.to_string(), "10x3");
+        assert!("0x3".parse::<MinSize>().is_err());
+        assert!("10".parse::<MinSize>().is_err());
+    }
+
+    #[test]
+    fn presets() {
+        let panes = |preset: Preset, count| {
+            let panes: Vec<usize> = (0..count).collect();
+            let layout = preset.layout(&panes);
+            assert_eq!(layout.panes(), panes);
+            layout.arrange(AREA).panes
+        };
+        assert_eq!(panes(Preset::Tiled, 1), [(0, AREA)]);
+        assert_eq!(
+            panes(Preset::EvenVertical, 3)
+                .iter()
+                .map(|(_, rect)| (rect.row, rect.rows))
+                .collect::<Vec<_>>(),
+            [(0, 8), (9, 7), (17, 7)]
+        );
+        let main = panes(Preset::MainVertical, 3);
+        assert_eq!(main[0].1, Rect { cols: 40, ..AREA });
+        assert_eq!((main[1].1.col, main[2].1.col), (41, 41));
+        let main = panes(Preset::MainHorizontal, 3);
+        assert_eq!(main[0].1, Rect { rows: 12, ..AREA });
+        assert_eq!((main[1].1.row, main[2].1.col), (13, 41));
+
+        let tiled = panes(Preset::Tiled, 5);
+        let cols = |row| tiled.iter().filter(|(_, rect)| rect.row == row).count();
+        assert_eq!((cols(0), cols(13)), (3, 2));
+
+        assert_eq!(Preset::Tiled.next(), Preset::EvenHorizontal);
+        assert_eq!("main-vertical".parse(), Ok(Preset::MainVertical));
+        for preset in Preset::ALL {
+            assert_eq!(preset.to_string().parse(), Ok(preset));
+        }
+    }
+
+    #[test]
+    fn layout_text() {
+        let mut layout = Layout::Pane(0);
+        assert_eq!(layout.to_string(), "0");
+        layout.split(0, 1, Direction::Horizontal);
+        layout.split(1, 2, Direction::Vertical);
+        layout.resize(1, Direction::Vertical, 4, AREA, CELL);
+        let text = layout.to_string();
+        assert!(text.starts_with("h(1:0,1:v("), "{}", text);
+        assert_eq!(text.parse(), Ok(layout));
+
+        for bad in ["", "h()", "h(1:0)", "h(0:1,1:2)", "x(1:0,1:1)", "h(1:0,1:1"] {
+            assert!(bad.parse::<Layout>().is_err(), "{}", bad);
+        }
+    }
+}
@@ -1,25 +1,48 @@
 //! Abstractions used by the session manager.
 
-#![recursion_limit = "1024"]
+pub mod ansi;
+pub mod applet;
 #[warn(missing_docs)]
 pub mod console;
+pub mod environment;
+pub mod export;
 pub mod grid;
+pub mod history;
+pub mod input_log;
+pub mod layout;
+pub mod replay;
+pub mod search;
 pub mod session;
+pub mod snapshot;
+pub mod terminfo;
+pub mod width;
 
 pub mod util {
     use std::io;
 
     use nix::pty::Winsize;
 
+    /// The shell running the commands `sm` runs for itself, such as those of
+    /// `run-shell`, whatever the user's shell is.
+    pub const POSIX_SHELL: &str = "/bin/sh";
+
+    #[cfg(not(test))]
+    mod ioctl {
+        nix::ioctl_read_bad!(win_size, libc::TIOCGWINSZ, nix::pty::Winsize);
+    }
+
+    /// Return the size of the terminal on standard output.
     #[cfg(not(test))]
     pub fn get_term_size() -> io::Result<Winsize> {
-        let (cols, rows) = termion::terminal_size()?;
-        Ok(Winsize {
-            ws_row: rows,
-            ws_col: cols,
+        let mut size = Winsize {
+            ws_row: 0,
+            ws_col: 0,
             ws_xpixel: 0,
             ws_ypixel: 0,
-        })
+        };
+        unsafe { ioctl::win_size(libc::STDOUT_FILENO, &mut size) }
+            .map_err(io::Error::other)?;
+        Ok(size)
     }
 
     #[cfg(test)]
@@ -27,10 +50,30 @@ pub mod util {
         Ok(crate::tests::WINSZ)
     }
 
-    /// Return the path to the shell executable.
+    /// Return the path to the user's shell: `$SHELL`, or the shell of the user's
+    /// passwd entry, or `/bin/sh`, the first which is an executable's absolute
+    /// path.
+    #[cfg(not(test))]
+    pub fn get_shell() -> String {
+        use std::{env, path::Path};
+
+        use nix::unistd::{self, User};
+
+        let usable =
+            |shell: &String| shell.starts_with('/') && Path::new(shell).is_file();
+        env::var("SHELL")
+            .ok()
+            .filter(usable)
+            .or_else(|| {
+                let user = User::from_uid(unistd::getuid()).ok()??;
+                user.shell.to_str().map(String::from).filter(usable)
+            })
+            .unwrap_or_else(|| POSIX_SHELL.to_string())
+    }
+
+    #[cfg(test)]
     pub fn get_shell() -> String {
-        // TODO: something reasonable
-        "/bin/sh".to_string()
+        POSIX_SHELL.to_string()
     }
 }
 
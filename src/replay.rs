@@ -0,0 +1,145 @@
+//! Replaying output captured from a pty through a grid, headlessly, to measure
+//! how much drawing it costs: the cells updated and the bytes written to the
+//! terminal, for catching rendering regressions in tests and benchmarks.
+
+use std::{
+    fs,
+    io::{self, Write},
+    path::Path,
+};
+
+use crate::{ansi::Processor, grid::Grid};
+
+/// What replaying a capture took.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ReplayStats {
+    /// The bytes of the capture fed in.
+    pub bytes_read: usize,
+    /// How many times the grid was drawn, once per chunk which changed it.
+    pub frames: usize,
+    /// The cells drawn again, summed over the frames.
+    pub cells_updated: usize,
+    /// The bytes written to the terminal to draw them.
+    pub bytes_emitted: usize,
+}
+
+/// A writer counting the bytes written to it and dropping them.
+#[derive(Debug, Default)]
+struct Counter(usize);
+
+impl Write for Counter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0 += buf.len();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Feed `capture` to a grid of `cols` by `rows` in reads of at most `chunk`
+/// bytes, as a pane would get it from its pty, drawing the changes after each.
+/// The grid starts out drawn, so the first frame counts only what the capture
+/// changed.
+pub fn replay(capture: &[u8], cols: u16, rows: u16, chunk: usize) -> ReplayStats {
+    let mut grid = Grid::<io::Sink>::new(cols, rows);
+    grid.clear_damage();
+    let mut processor = Processor::new();
+    let mut stats = ReplayStats::default();
+    for read in capture.chunks(chunk.max(1)) {
+        for byte in read {
+            processor.advance(&mut grid, *byte, &mut io::sink());
+        }
+        stats.bytes_read += read.len();
+        let cells: usize = grid.damage().map(|span| span.cols.len()).sum();
+        if cells == 0 {
+            continue;
+        }
+        let mut output = Counter::default();
+        grid.draw(&mut output);
+        stats.frames += 1;
+        stats.cells_updated += cells;
+        stats.bytes_emitted += output.0;
+    }
+    stats
+}
+
+/// Replay the raw pty output in the file at `path`, e.g. one recorded with
+/// `script` or under `tests/captures`, as `replay` does.
+pub fn replay_file(
+    path: &Path,
+    cols: u16,
+    rows: u16,
+    chunk: usize,
+) -> io::Result<ReplayStats> {
+    Ok(replay(&fs::read(path)?, cols, rows, chunk))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Output recorded from programs run in an 80 by 24 pty: a shell listing
+    /// `/usr/bin`, vim inserting lines and searching, and a progress bar
+    /// redrawn in place.
+    const LISTING: &[u8] = include_bytes!("../tests/captures/listing.cap");
+    const EDITOR: &[u8] = include_bytes!("../tests/captures/editor.cap");
+    const PROGRESS: &[u8] = include_bytes!("../tests/captures/progress.cap");
+
+    #[test]
+    fn replay_listing() {
+        let stats = replay(LISTING, 80, 24, 4096);
+        assert_eq!(stats.bytes_read, LISTING.len());
+        assert_eq!(stats.frames, LISTING.len().div_ceil(4096));
+        // Scrolling redraws the screen once per read, not once per line.
+        assert!(stats.cells_updated <= stats.frames * 80 * 24, "{:?}", stats);
+        assert!(
+            stats.bytes_emitted <= stats.cells_updated * 2,
+            "{:?}",
+            stats
+        );
+    }
+
+    #[test]
+    fn replay_editor() {
+        let stats = replay(EDITOR, 80, 24, 64);
+        // Mostly the lines changed are drawn again, not the screen.
+        assert!(
+            stats.cells_updated <= stats.frames * 80 * 24 / 4,
+            "{:?}",
+            stats
+        );
+        assert!(
+            stats.bytes_emitted <= stats.cells_updated * 2,
+            "{:?}",
+            stats
+        );
+    }
+
+    #[test]
+    fn replay_progress() {
+        let stats = replay(PROGRESS, 80, 24, 16);
+        assert!(stats.frames > 0);
+        // Only the bar's line, and only the cells which change.
+        assert!(stats.cells_updated <= stats.frames * 40, "{:?}", stats);
+        assert!(stats.bytes_emitted <= stats.frames * 60, "{:?}", stats);
+    }
+
+    #[test]
+    fn replay_capture_file() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/captures");
+        let stats = replay_file(&dir.join("progress.cap"), 80, 24, 16).unwrap();
+        assert_eq!(stats, replay(PROGRESS, 80, 24, 16));
+        assert!(replay_file(&dir.join("nonexistent.cap"), 80, 24, 16).is_err());
+    }
+
+    #[test]
+    fn replay_nothing() {
+        assert_eq!(replay(b"", 80, 24, 4096), ReplayStats::default());
+        // Input changing nothing on screen draws nothing.
+        let stats = replay(b"\x1b[?25l\x1b[?25h", 80, 24, 1);
+        assert_eq!(stats.frames, 0);
+        assert_eq!(stats.bytes_emitted, 0);
+    }
+}
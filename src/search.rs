@@ -0,0 +1,136 @@
+//! Searches of text for a pattern, as typed after `/` or `?`.
+
+use std::ops::Range;
+
+/// A search for a pattern, ignoring case unless the pattern has capitals.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Search {
+    /// What is searched for.
+    pub pattern: String,
+    /// Whether it goes down the text, as `/` does, rather than up, as `?` does.
+    pub forward: bool,
+}
+
+impl Search {
+    /// Where the pattern is in `line`, as byte ranges.
+    pub fn matches(&self, line: &str) -> Vec<Range<usize>> {
+        let ignore_case = !self.pattern.chars().any(char::is_uppercase);
+        let same = |a: char, b: char| {
+            a == b || ignore_case && a.to_lowercase().eq(b.to_lowercase())
+        };
+        let mut matches = Vec::new();
+        if self.pattern.is_empty() {
+            return matches;
+        }
+        let mut start = 0;
+        while let Some(first) = line[start..].chars().next() {
+            let mut rest = line[start..].chars();
+            let mut end = start;
+            let found = self.pattern.chars().all(|p| match rest.next() {
+                Some(c) if same(c, p) => {
+                    end += c.len_utf8();
+                    true
+                }
+                _ => false,
+            });
+            if found {
+                // Matches do not overlap.
+                matches.push(start..end);
+                start = end;
+            } else {
+                start += first.len_utf8();
+            }
+        }
+        matches
+    }
+
+    /// Where the pattern is in `line`, as ranges of characters rather than
+    /// bytes, e.g. the columns of a row with a character in each.
+    pub fn char_matches(&self, line: &str) -> Vec<Range<usize>> {
+        let char_idx = |byte: usize| line[..byte].chars().count();
+        self.matches(line)
+            .into_iter()
+            .map(|found| char_idx(found.start)..char_idx(found.end))
+            .collect()
+    }
+
+    /// The first of `count` lines from `from`, or after it if `after`, going
+    /// down if the search goes down or up if not, and going around at the ends,
+    /// in which `line` finds a match.
+    pub fn find<F>(
+        &self,
+        count: usize,
+        from: usize,
+        after: bool,
+        line: F,
+    ) -> Option<usize>
+    where
+        F: Fn(usize) -> Option<String>,
+    {
+        let from = match (after, self.forward) {
+            (false, _) => from,
+            (true, true) => from + 1,
+            (true, false) => from + count.saturating_sub(1),
+        };
+        let from = from.checked_rem(count)?;
+        (0..count)
+            .map(|step| match self.forward {
+                true => (from + step) % count,
+                false => (from + count - step % count) % count,
+            })
+            .find(|idx| line(*idx).is_some_and(|text| !self.matches(&text).is_empty()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn search(pattern: &str) -> Search {
+        Search {
+            pattern: pattern.to_string(),
+            forward: true,
+        }
+    }
+
+    #[test]
+    fn matches() {
+        let found = |pattern: &str, line: &str| {
+            let matches = search(pattern).matches(line).into_iter();
+            matches
+                .map(|found| (found.start, found.end))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(found("ab", "xabyABab"), [(1, 3), (4, 6), (6, 8)]);
+        // Capitals make the case matter.
+        assert_eq!(found("Ab", "ab Ab"), [(3, 5)]);
+        assert_eq!(found("aa", "aaa"), [(0, 2)]);
+        assert_eq!(found("\u{e9}t\u{e9}", "l'\u{c9}t\u{c9}"), [(2, 7)]);
+        assert!(found("", "abc").is_empty());
+        assert!(found("abcd", "abc").is_empty());
+        let found = search("t\u{e9}").char_matches("\u{e9}t\u{e9}");
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0], 1..3);
+    }
+
+    #[test]
+    fn find() {
+        let lines = ["one", "two", "three", "two"];
+        let line = |idx: usize| lines.get(idx).map(|line| line.to_string());
+        let mut two = search("two");
+        assert_eq!(two.find(lines.len(), 2, false, line), Some(3));
+        assert_eq!(two.find(lines.len(), 0, false, line), Some(1));
+        assert_eq!(two.find(lines.len(), 1, false, line), Some(1));
+        assert_eq!(two.find(lines.len(), 1, true, line), Some(3));
+        // Going around the bottom.
+        assert_eq!(two.find(lines.len(), 3, true, line), Some(1));
+        two.forward = false;
+        assert_eq!(two.find(lines.len(), 2, false, line), Some(1));
+        assert_eq!(two.find(lines.len(), 3, true, line), Some(1));
+        // Going around the top.
+        assert_eq!(two.find(lines.len(), 0, false, line), Some(3));
+        assert_eq!(two.find(lines.len(), 1, true, line), Some(3));
+        assert_eq!(search("four").find(lines.len(), 0, false, line), None);
+        assert_eq!(two.find(0, 0, false, line), None);
+    }
+}
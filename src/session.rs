@@ -1,399 +1,4691 @@
-//! Structures and functions to manage windows.
+//! Structures and functions to manage windows and their panes.
 
 use std::{
-    collections::BTreeMap,
-    fs::File,
+    collections::{BTreeMap, BTreeSet, VecDeque},
+    fs::{File, OpenOptions},
     io::{self, Write},
-    thread,
+    path::{Path, PathBuf},
+    pin::Pin,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+    time::Instant,
 };
 
-use anyhow::Result;
 use futures::{
-    channel::mpsc::{self, Receiver},
-    stream::{Stream, StreamExt},
+    channel::mpsc::{self, Receiver, Sender},
+    stream::{FusedStream, Stream},
 };
 use log::debug;
 use nix::pty::Winsize;
 use thiserror::Error;
-use vte::ansi::Processor;
 
 use crate::{
-    console::{self, ChildPty, PtyUpdate},
-    grid::Grid,
-    util,
+    ansi::{CursorShape, CursorStyle, Processor},
+    applet::{Applet, AppletUpdate},
+    console::{self, ChildPty, ExitStatus, PtyError, PtyUpdate},
+    environment::{self, Environment},
+    export::{PaneExport, SessionExport, WindowExport},
+    grid::{Grid, GridEvent, MouseEncoding, MouseReport, TextBlink, Theme},
+    input_log::InputLog,
+    layout::{Direction, Layout, MinSize, Preset, Rect},
+    search::Search,
+    snapshot::{PaneSnapshot, SessionSnapshot, WindowSnapshot},
+    terminfo, util, width,
 };
 
-/// A Window object for a `Session`.
+/// A Pane object for a `Session`.
 ///
-/// This trait exists to allow `Session` to handle different types of `Window`,
+/// This trait exists to allow `Session` to handle different types of `Pane`,
 /// which is useful for testing.
-pub trait SessionWindow
+pub trait SessionPane
 where
     Self: Sized,
 {
-    fn new(command: &str, size: Winsize) -> Result<(Self, Receiver<PtyUpdate>), ()>;
-    fn receive_stdin(&self, data: &[u8]) -> Result<(), io::Error>;
-    // fn resize(&mut self, sz: Winsize);
-    // fn mark_dirty(&mut self);
-    // fn redraw<T: Write>(&mut self, output: &mut T);
+    /// Spawn `command` with `args` and the variables in `env`, in `cwd` if it is
+    /// given, in a new pane of size `size`.
+    fn new(
+        command: &str,
+        args: &[&str],
+        env: &Environment,
+        cwd: Option<&Path>,
+        size: Winsize,
+    ) -> Result<(Self, Receiver<PtyUpdate>), PtyError>;
+    /// Run `applet` in a new pane of size `size`.
+    fn with_applet(
+        applet: Box<dyn Applet>,
+        size: Winsize,
+    ) -> Result<(Self, Receiver<PtyUpdate>), PtyError>;
+    /// Hang up on the process running in this pane, which then exits.
+    fn hangup(&self);
+    /// Kill the processes running in this pane, even if they ignore hangups.
+    fn kill(&self);
+    /// Whether the process running in this pane looks to be reading a password.
+    fn is_reading_secret(&self) -> bool;
+    /// The process running in this pane has exited: keep showing what it left,
+    /// with a line saying how it exited, rather than closing, if it can.
+    /// Whether it does; panes running applets do not.
+    fn keep_exited(&mut self) -> bool;
+    /// Whether the process running in this pane has exited, and the pane was
+    /// kept by `keep_exited`.
+    fn is_dead(&self) -> bool;
+    /// Record how the process running in this pane exited, before
+    /// `keep_exited`.
+    fn set_exit_status(&mut self, status: ExitStatus);
+    /// How the process running in this pane exited, once it has and if it is
+    /// known.
+    fn exit_status(&self) -> Option<ExitStatus>;
+    /// Forward input to the process running in this pane.
+    fn receive_stdin(&mut self, data: &[u8]) -> Result<(), io::Error>;
+    /// Process output from the process running in this pane.
+    fn pty_update(&mut self, byte: u8);
+    /// How many bytes of output have been read from the process running in
+    /// this pane.
+    fn bytes_read(&self) -> u64;
+    /// Whether the process running in this pane has rung the bell since the
+    /// last call.
+    fn take_bell(&mut self) -> bool;
+    /// Resize this pane and its process.
+    fn resize(&mut self, sz: Winsize);
+    /// Draw this pane with its top left corner at `col`, `row` of the terminal.
+    fn set_origin(&mut self, col: u16, row: u16);
+    /// Mark the whole pane to be redrawn.
+    fn mark_dirty(&mut self);
+    /// Draw the changed parts of this pane to `output`.
+    fn redraw<T: Write>(&mut self, output: &mut T);
+    /// Whether any part of this pane has changed since it was last drawn.
+    fn is_dirty(&self) -> bool;
+    /// What this pane shows, as escape sequences drawing it on a terminal of
+    /// its size.
+    fn contents(&mut self) -> Vec<u8>;
+    /// The cursor style requested by the process running in this pane.
+    fn cursor_style(&self) -> CursorStyle;
+    /// Whether the process running in this pane asked for pasted text to be
+    /// bracketed.
+    fn bracketed_paste(&self) -> bool;
+    /// How the process running in this pane asked for mouse events to be
+    /// reported to it, if it did.
+    fn mouse_reporting(&self) -> Option<MouseEncoding>;
+    /// How many rows back through those scrolled away this pane is shown, or
+    /// 0 if it shows its screen.
+    fn view_offset(&self) -> usize;
+    /// Show this pane `rows` further back through the rows scrolled away, or
+    /// forward if negative.
+    fn scroll_view(&mut self, rows: isize);
+    /// The line shown at the top of this pane, counting from the oldest row
+    /// scrolled away which is kept.
+    fn view_top(&self) -> usize;
+    /// Show line `line` at the top of this pane, or its screen if the line is
+    /// on it.
+    fn show_line(&mut self, line: usize);
+    /// The first line of this pane, scrolled away or on its screen, from
+    /// `line`, or after it if `after`, with a match of `search`, going around
+    /// at the ends.
+    fn find(&self, search: &Search, line: usize, after: bool) -> Option<usize>;
+    /// Highlight the matches of `search` in this pane, or none if `None`.
+    fn set_highlight(&mut self, search: Option<Search>);
+    /// Choose whether to space out double-width and double-height lines rather
+    /// than asking the outer terminal to scale them.
+    fn set_scale_line_attrs(&mut self, scale: bool);
+    /// Apply `theme` to this pane, or remove its theme if `None`.
+    fn set_theme(&mut self, theme: Option<Theme>);
+    /// The theme applied to this pane.
+    fn theme(&self) -> Option<&Theme>;
+    /// Choose how text with the blink attribute is shown.
+    fn set_text_blink(&mut self, text_blink: TextBlink);
+    /// Show or hide blinking text animated by the renderer.
+    fn set_blink_phase(&mut self, visible: bool);
+    /// Hold back output to apply at most `lines_per_sec` lines a second, or
+    /// apply it as it arrives, catching up at once, if `None`.
+    fn set_slow_print(&mut self, lines_per_sec: Option<u32>);
+    /// The rate output is limited to, if it is.
+    fn slow_print(&self) -> Option<u32>;
+    /// Apply the output held back which is due by `now`.
+    fn advance_slow_print(&mut self, now: Instant);
+    /// Let an applet running in this pane catch up with the time.
+    fn tick(&mut self);
+    /// Choose whether this pane is private, for entering secrets: nothing it
+    /// shows or is sent should be logged or recorded.
+    fn set_private(&mut self, private: bool);
+    /// Whether this pane is private.
+    fn is_private(&self) -> bool;
+    /// The title set by the process running in this pane, if any.
+    fn title(&self) -> Option<&str>;
+    /// The working directory of the process running in this pane, if it is
+    /// known.
+    fn cwd(&self) -> Option<PathBuf>;
+    /// The last line of text on screen, trimmed, to give an idea of what the
+    /// pane shows.
+    fn preview(&self) -> String;
+    /// Choose whether to keep the lines this pane finishes, for `take_lines`.
+    fn set_line_log(&mut self, log: bool);
+    /// The lines finished since the last call, oldest first.
+    fn take_lines(&mut self) -> Vec<String>;
+    /// The text of the line the cursor is on, so far.
+    fn cursor_line(&self) -> String;
 }
 
-/// Window: a `Console` abstraction.
+/// Pane: a `Console` abstraction.
 ///
 /// This structure exists so that `Console` can be only concerned with the
-/// underlying terminal implementation and frame, whereas `Window` acts as the
+/// underlying terminal implementation and frame, whereas `Pane` acts as the
 /// interface between the multiplexer and the `Console`.
-pub struct Window {
-    pty: ChildPty,
+pub struct Pane {
+    source: Source,
+    /// Where replies to queries in the output are written.
+    replies: File,
+    grid: Grid<File>,
     processor: Processor,
     size: Winsize,
+    slow_print: Option<SlowPrint>,
+    bytes_read: u64,
+    /// Whether the process has exited, the pane being kept to show its output.
+    dead: bool,
+    /// How the process exited, once it has been waited for.
+    exit_status: Option<ExitStatus>,
+    /// Set when the bell rings, until `take_bell`.
+    bell: Arc<AtomicBool>,
 }
 
-impl SessionWindow for Window {
-    fn new(command: &str, size: Winsize) -> Result<(Window, Receiver<PtyUpdate>), ()> {
-        let args: [&str; 0] = [];
-        let (pty, mut grid) = console::spawn_pty(command, &args, size)?;
-        let mut processor = Processor::new();
-        let mut pty_output = pty.file.try_clone().unwrap();
-        let (mut send, pty_update) = mpsc::channel(0x100);
-        thread::spawn(move || {
-            use std::io::Read;
-            let mut buf = [0u8; 4096];
-            while let Ok(sz) = pty_output.read(&mut buf) {
-                for byte in &buf[..sz] {
-                    processor.advance(&mut grid, *byte, &mut pty_output);
-                }
-                send.try_send(PtyUpdate::Exited).unwrap();
-                send.disconnect();
-            }
-        });
+impl SessionPane for Pane {
+    fn new(
+        command: &str,
+        args: &[&str],
+        env: &Environment,
+        cwd: Option<&Path>,
+        size: Winsize,
+    ) -> Result<(Pane, Receiver<PtyUpdate>), PtyError> {
+        let (pty, grid, pty_update) =
+            console::spawn_pty(command, args, env, cwd, size)?;
+        let replies = pty.file.try_clone()?;
         Ok((
-            Window {
-                pty,
-                processor: Processor::new(),
-                size,
-            },
+            Pane::with_source(Source::Pty(pty), replies, grid, size),
             pty_update,
         ))
     }
 
-    fn receive_stdin(&self, data: &[u8]) -> Result<(), io::Error> {
-        let mut file = &self.pty.file;
-        file.write_all(data)?;
-        file.flush()?;
+    fn with_applet(
+        applet: Box<dyn Applet>,
+        size: Winsize,
+    ) -> Result<(Pane, Receiver<PtyUpdate>), PtyError> {
+        // An applet asks nothing, so nothing has to reach it.
+        let replies = OpenOptions::new().write(true).open("/dev/null")?;
+        let grid = Grid::new(size.ws_col, size.ws_row);
+        let (exited, updates) = mpsc::channel(1);
+        let mut pane =
+            Pane::with_source(Source::Applet(applet, exited), replies, grid, size);
+        pane.draw_applet();
+        Ok((pane, updates))
+    }
+
+    fn hangup(&self) {
+        match &self.source {
+            Source::Pty(pty) => {
+                if pty.hangup().is_err() {
+                    debug!("could not hang up on pane");
+                }
+            }
+            Source::Applet(_, exited) => exit_applet(exited),
+        }
+    }
+
+    fn kill(&self) {
+        match &self.source {
+            Source::Pty(pty) => {
+                if pty.kill().is_err() {
+                    debug!("could not kill pane");
+                }
+            }
+            Source::Applet(_, exited) => exit_applet(exited),
+        }
+    }
+
+    fn is_reading_secret(&self) -> bool {
+        match &self.source {
+            Source::Pty(pty) => pty.is_reading_secret(),
+            Source::Applet(..) => false,
+        }
+    }
+
+    fn keep_exited(&mut self) -> bool {
+        if let Source::Applet(..) = self.source {
+            return false;
+        }
+        // What the process wrote is shown before the banner.
+        self.set_slow_print(None);
+        let banner = match self.exit_status {
+            Some(status) => format!("[{}]", status),
+            None => "[exited]".to_string(),
+        };
+        let text = format!("\x1b[0m\r\n\x1b[7m{}\x1b[0m", banner);
+        for byte in text.bytes() {
+            self.processor
+                .advance(&mut self.grid, byte, &mut self.replies);
+        }
+        self.dead = true;
+        true
+    }
+
+    fn is_dead(&self) -> bool {
+        self.dead
+    }
+
+    fn set_exit_status(&mut self, status: ExitStatus) {
+        self.exit_status = Some(status);
+    }
+
+    fn exit_status(&self) -> Option<ExitStatus> {
+        self.exit_status
+    }
+
+    fn receive_stdin(&mut self, data: &[u8]) -> Result<(), io::Error> {
+        if self.dead {
+            return Ok(());
+        }
+        match &mut self.source {
+            Source::Pty(pty) => {
+                let mut file = &pty.file;
+                file.write_all(data)?;
+                file.flush()?;
+            }
+            Source::Applet(applet, _) => {
+                let update = applet.receive_input(data);
+                self.apply_applet_update(update);
+            }
+        }
         Ok(())
     }
 
-    // fn resize(&mut self, sz: Winsize) {
-    //     if sz != self.size {
-    //         self.size = sz;
-    //         self.grid.resize(sz.ws_col, sz.ws_row);
-    //         self.pty.resize(sz).unwrap();
-    //         self.mark_dirty();
-    //     }
-    // }
+    fn pty_update(&mut self, byte: u8) {
+        self.bytes_read += 1;
+        match &mut self.slow_print {
+            Some(slow_print) => slow_print.pending.push_back(byte),
+            None => self.advance(byte),
+        }
+    }
+
+    fn bytes_read(&self) -> u64 {
+        self.bytes_read
+    }
+
+    fn take_bell(&mut self) -> bool {
+        self.bell.swap(false, Ordering::Relaxed)
+    }
+
+    fn resize(&mut self, sz: Winsize) {
+        if sz != self.size {
+            self.size = sz;
+            self.grid.resize(sz.ws_col, sz.ws_row);
+            match &self.source {
+                Source::Pty(pty) => pty.resize(sz).unwrap(),
+                Source::Applet(..) => self.draw_applet(),
+            }
+            self.mark_dirty();
+        }
+    }
+
+    fn set_origin(&mut self, col: u16, row: u16) {
+        self.grid.set_origin(col, row);
+    }
+
+    fn mark_dirty(&mut self) {
+        self.grid.mark_all_dirty();
+    }
+
+    fn redraw<T: Write>(&mut self, output: &mut T) {
+        self.grid.draw(output);
+    }
+
+    fn is_dirty(&self) -> bool {
+        self.grid.damage().next().is_some()
+    }
+
+    fn contents(&mut self) -> Vec<u8> {
+        self.grid.contents()
+    }
+
+    fn cursor_style(&self) -> CursorStyle {
+        self.grid.cursor_style()
+    }
+
+    fn bracketed_paste(&self) -> bool {
+        self.grid.bracketed_paste()
+    }
+
+    fn mouse_reporting(&self) -> Option<MouseEncoding> {
+        self.grid.mouse_reporting()
+    }
+
+    fn view_offset(&self) -> usize {
+        self.grid.view_offset()
+    }
+
+    fn scroll_view(&mut self, rows: isize) {
+        self.grid.scroll_view(rows);
+    }
+
+    fn view_top(&self) -> usize {
+        self.grid.view_top()
+    }
+
+    fn show_line(&mut self, line: usize) {
+        self.grid.show_line(line);
+    }
+
+    fn find(&self, search: &Search, line: usize, after: bool) -> Option<usize> {
+        self.grid.find(search, line, after)
+    }
+
+    fn set_highlight(&mut self, search: Option<Search>) {
+        self.grid.set_highlight(search);
+    }
+
+    fn set_scale_line_attrs(&mut self, scale: bool) {
+        self.grid.set_scale_line_attrs(scale);
+    }
+
+    fn set_theme(&mut self, theme: Option<Theme>) {
+        self.grid.set_theme(theme);
+    }
+
+    fn theme(&self) -> Option<&Theme> {
+        self.grid.theme()
+    }
+
+    fn set_text_blink(&mut self, text_blink: TextBlink) {
+        self.grid.set_text_blink(text_blink);
+    }
+
+    fn set_blink_phase(&mut self, visible: bool) {
+        self.grid.set_blink_phase(visible);
+    }
+
+    fn set_slow_print(&mut self, lines_per_sec: Option<u32>) {
+        match (lines_per_sec, &mut self.slow_print) {
+            (Some(rate), Some(slow_print)) => slow_print.lines_per_sec = rate,
+            (Some(rate), None) => {
+                self.slow_print = Some(SlowPrint::new(rate, Instant::now()))
+            }
+            (None, _) => {
+                if let Some(slow_print) = self.slow_print.take() {
+                    for byte in slow_print.pending {
+                        self.advance(byte);
+                    }
+                }
+            }
+        }
+    }
+
+    fn slow_print(&self) -> Option<u32> {
+        self.slow_print
+            .as_ref()
+            .map(|slow_print| slow_print.lines_per_sec)
+    }
+
+    fn advance_slow_print(&mut self, now: Instant) {
+        let bytes = match &mut self.slow_print {
+            Some(slow_print) => slow_print.release(now),
+            None => return,
+        };
+        for byte in bytes {
+            self.advance(byte);
+        }
+    }
+
+    fn tick(&mut self) {
+        if let Source::Applet(applet, _) = &mut self.source {
+            let update = applet.tick();
+            self.apply_applet_update(update);
+        }
+    }
+
+    fn set_private(&mut self, private: bool) {
+        self.grid.set_private(private);
+    }
+
+    fn is_private(&self) -> bool {
+        self.grid.is_private()
+    }
+
+    fn title(&self) -> Option<&str> {
+        self.grid.title()
+    }
 
-    // fn mark_dirty(&mut self) {
-    //     self.grid.mark_all_dirty();
-    // }
+    fn cwd(&self) -> Option<PathBuf> {
+        match &self.source {
+            Source::Pty(pty) => pty.cwd(),
+            Source::Applet(..) => None,
+        }
+    }
+
+    fn preview(&self) -> String {
+        (0..self.size.ws_row)
+            .rev()
+            .filter_map(|row| self.grid.row_text(row))
+            .map(|text| text.trim().to_string())
+            .find(|text| !text.is_empty())
+            .unwrap_or_default()
+    }
+
+    fn set_line_log(&mut self, log: bool) {
+        self.grid.set_line_log(log);
+    }
+
+    fn take_lines(&mut self) -> Vec<String> {
+        self.grid.take_lines()
+    }
 
-    // fn redraw<T: Write>(&mut self, output: &mut T) {
-    //     self.grid.draw(output);
-    // }
+    fn cursor_line(&self) -> String {
+        self.grid.cursor_line()
+    }
 }
 
-#[cfg(test)]
-pub mod tests {
-    use super::*;
-    use crate::tests::WINSZ;
+impl Pane {
+    fn with_source(
+        source: Source,
+        replies: File,
+        mut grid: Grid<File>,
+        size: Winsize,
+    ) -> Pane {
+        let bell = Arc::new(AtomicBool::new(false));
+        let rung = bell.clone();
+        grid.subscribe(move |event| {
+            if *event == GridEvent::Bell {
+                rung.store(true, Ordering::Relaxed);
+            }
+        });
+        Pane {
+            source,
+            replies,
+            grid,
+            processor: Processor::new(),
+            size,
+            slow_print: None,
+            bytes_read: 0,
+            dead: false,
+            exit_status: None,
+            bell,
+        }
+    }
 
-    use futures::channel::mpsc::{self, Sender};
+    fn advance(&mut self, byte: u8) {
+        self.processor
+            .advance(&mut self.grid, byte, &mut self.replies);
+    }
 
-    pub struct MockWindow {
-        stdin_channel: (Sender<u8>, Receiver<u8>),
-        pty_channel: (Sender<u8>, Receiver<u8>),
-        resize_channel: (Sender<Winsize>, Receiver<Winsize>),
-        dirty_channel: (Sender<bool>, Receiver<bool>),
+    /// Have the applet running in this pane draw itself on the grid.
+    fn draw_applet(&mut self) {
+        let mut output = Vec::new();
+        if let Source::Applet(applet, _) = &mut self.source {
+            if applet.draw(&mut output, self.size).is_err() {
+                debug!("could not draw applet {}", applet.name());
+            }
+        }
+        for byte in output {
+            self.advance(byte);
+        }
+    }
+
+    fn apply_applet_update(&mut self, update: AppletUpdate) {
+        match (update, &self.source) {
+            (AppletUpdate::Redraw, _) => self.draw_applet(),
+            (AppletUpdate::Exit, Source::Applet(_, exited)) => exit_applet(exited),
+            _ => (),
+        }
+    }
+}
+
+/// What runs in a pane.
+enum Source {
+    /// A process, on the other side of a PTY.
+    Pty(ChildPty),
+    /// An applet, with the sender to report its exit on.
+    Applet(Box<dyn Applet>, Sender<PtyUpdate>),
+}
+
+/// Have the pane of an applet closed, as if its process had exited.
+fn exit_applet(exited: &Sender<PtyUpdate>) {
+    // The update is already on its way if this fails.
+    let _ = exited.clone().try_send(PtyUpdate::Exited { status: None });
+}
+
+/// Output from a pane's process, held back to be applied a few lines at a time.
+///
+/// Everything up to the next newline is released at once, so partial lines are
+/// not delayed.
+struct SlowPrint {
+    lines_per_sec: u32,
+    pending: VecDeque<u8>,
+    /// How many more lines may be released.
+    credit: f64,
+    last: Instant,
+}
+
+impl SlowPrint {
+    fn new(lines_per_sec: u32, now: Instant) -> SlowPrint {
+        SlowPrint {
+            lines_per_sec,
+            pending: VecDeque::new(),
+            credit: 0.0,
+            last: now,
+        }
+    }
+
+    /// Take the output which is due by `now`.
+    fn release(&mut self, now: Instant) -> Vec<u8> {
+        let elapsed = now.saturating_duration_since(self.last).as_secs_f64();
+        let rate = f64::from(self.lines_per_sec);
+        // Time spent idle lets at most a second's worth of lines through.
+        self.credit = (self.credit + elapsed * rate).min(rate.max(1.0));
+        self.last = now;
+        let mut released = Vec::new();
+        while let Some(&byte) = self.pending.front() {
+            if byte == b'\n' {
+                if self.credit < 1.0 {
+                    break;
+                }
+                self.credit -= 1.0;
+            }
+            released.push(byte);
+            self.pending.pop_front();
+        }
+        released
+    }
+}
+
+/// Whether the cursor blinks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CursorBlink {
+    /// Follow the application in the selected window.
+    #[default]
+    Application,
+    /// Always blink.
+    On,
+    /// Never blink.
+    Off,
+}
+
+impl FromStr for CursorBlink {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<CursorBlink, String> {
+        match s {
+            "app" | "application" => Ok(CursorBlink::Application),
+            "on" => Ok(CursorBlink::On),
+            "off" => Ok(CursorBlink::Off),
+            _ => Err(format!("invalid cursor blink setting: {}", s)),
+        }
+    }
+}
+
+/// The sequences selecting `style` on the outer terminal (DECSCUSR and DECSET 12).
+fn cursor_style_sequence(style: CursorStyle) -> String {
+    let steady = match style.shape {
+        CursorShape::Underline => 4,
+        CursorShape::Beam => 6,
+        _ => 2,
+    };
+    if style.blinking {
+        format!("\x1b[{} q\x1b[?12h", steady - 1)
+    } else {
+        format!("\x1b[{} q\x1b[?12l", steady)
+    }
+}
+
+/// An error from a `Session` operation.
+#[derive(Debug, Error)]
+pub enum SessionError {
+    /// The operation needs a selected window, but there is none.
+    #[error("no window is selected")]
+    NoSelectedWindow,
+    /// A new pane could not be created.
+    #[error("could not create window: {0}")]
+    WindowCreation(#[from] PtyError),
+    /// There is no terminfo entry for a `TERM`.
+    #[error("unknown terminal type: {0}")]
+    UnknownTerm(String),
+    /// A directory to start a pane in does not exist.
+    #[error("no directory {}", .0.display())]
+    NoSuchDirectory(PathBuf),
+    /// There is no window with an index.
+    #[error("no window {0}")]
+    NoSuchWindow(usize),
+    /// There is no pane with a number in a window.
+    #[error("no pane {0}")]
+    NoSuchPane(usize),
+    /// A window cannot be moved to an index another window has.
+    #[error("window {0} already exists")]
+    WindowExists(usize),
+    /// A window's only pane cannot be taken out of it.
+    #[error("the window has only one pane")]
+    OnlyPane,
+    /// A pane is too small to be split, leaving both parts at least the
+    /// minimum size.
+    #[error("no space for a new pane")]
+    NoSpace,
+    /// Communicating with a pane failed.
+    #[error("window I/O failed")]
+    Io(#[from] io::Error),
+}
+
+/// An update from the PTY of one of the panes in a `Session`.
+#[derive(Debug, PartialEq, Eq)]
+pub struct SessionPtyUpdate {
+    /// The index of the pane the update is for.
+    pub pane_idx: usize,
+    /// The update itself.
+    pub data: PtyUpdate,
+}
+
+/// What the active pane of a `Session` has written since it was last asked,
+/// as lines of text.
+#[derive(Debug, PartialEq, Eq)]
+pub struct NewLines {
+    /// The index of the pane.
+    pub pane_idx: usize,
+    /// The lines finished, oldest first.
+    pub lines: Vec<String>,
+    /// The line the cursor is on, so far.
+    pub current: String,
+}
+
+/// The stream of `SessionPtyUpdate`s from a single pane.
+pub struct PaneUpdates {
+    pane_idx: usize,
+    updates: Receiver<PtyUpdate>,
+}
+
+impl PaneUpdates {
+    /// The index of the pane the updates are from.
+    pub fn pane_idx(&self) -> usize {
+        self.pane_idx
+    }
+}
+
+impl Stream for PaneUpdates {
+    type Item = SessionPtyUpdate;
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context,
+    ) -> Poll<Option<Self::Item>> {
+        let pane_idx = self.pane_idx;
+        Pin::new(&mut self.updates)
+            .poll_next(cx)
+            .map(|update| update.map(|data| SessionPtyUpdate { pane_idx, data }))
+    }
+}
+
+impl FusedStream for PaneUpdates {
+    fn is_terminated(&self) -> bool {
+        self.updates.is_terminated()
+    }
+}
+
+/// A window which has closed, for a summary of the session.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClosedWindow {
+    pub idx: usize,
+    pub name: String,
+    /// The status the process of its last pane exited with, if it is known;
+    /// not if the window was killed.
+    pub status: Option<i32>,
+}
+
+/// Which window is selected, and which was selected before it: what one of
+/// the clients attached to a session is looking at.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Selection {
+    pub window: Option<usize>,
+    pub last: Option<usize>,
+}
+
+/// What is shown after the name of a window about it.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct WindowFlags {
+    /// It is the selected window.
+    pub current: bool,
+    /// It is the window selected before the selected one.
+    pub last: bool,
+    /// Its panes have written output since it was last selected.
+    pub activity: bool,
+    /// Its panes have rung the bell since it was last selected.
+    pub bell: bool,
+    /// Its active pane is shown alone.
+    pub zoomed: bool,
+}
+
+impl WindowFlags {
+    /// The flags as they are written after the window's name: `*` if it is
+    /// current, `-` if it was selected last, `#` for activity, `!` for a bell
+    /// and `Z` if it is zoomed, e.g. `-#`.
+    pub fn symbols(self) -> String {
+        [
+            (self.current, '*'),
+            (self.last, '-'),
+            (self.activity, '#'),
+            (self.bell, '!'),
+            (self.zoomed, 'Z'),
+        ]
+        .iter()
+        .filter(|(set, _)| *set)
+        .map(|(_, symbol)| *symbol)
+        .collect()
+    }
+}
+
+/// The panes sharing the screen as one window, one of which is active.
+struct Window {
+    /// Tells the window apart from every other of the session and its
+    /// siblings, wherever it is moved.
+    id: usize,
+    /// What the window is called, at first after the command it was created
+    /// to run.
+    name: String,
+    layout: Layout,
+    active: usize,
+    /// The preset the panes were last arranged with.
+    preset: Option<Preset>,
+    /// The active pane and titles the borders were last drawn with, or `None`
+    /// if they need to be drawn.
+    drawn_borders: Option<(usize, Vec<Option<String>>)>,
+    /// The size the panes were last arranged for, if they have been.
+    arranged: Option<Winsize>,
+    /// Whether input goes to every pane, rather than only the active one.
+    synchronize: bool,
+    /// Whether the active pane is shown alone, filling the window.
+    zoomed: bool,
+    /// The `TERM` given to new panes, if not the session's.
+    term: Option<String>,
+    /// Variables set in the environment of new panes, over the session's.
+    environment: Vec<(String, String)>,
+    /// Whether panes whose processes exit are kept, if not as the session
+    /// says.
+    remain_on_exit: Option<bool>,
+    /// The input sent to the window, if it is recorded.
+    input_log: Option<InputLog>,
+    /// Whether its panes have written output since it was last selected.
+    activity: bool,
+    /// Whether its panes have rung the bell since it was last selected.
+    bell: bool,
+}
+
+impl Window {
+    /// The layout the panes are shown in: the active pane alone if zoomed.
+    fn shown_layout(&self) -> Layout {
+        match self.zoomed {
+            true => Layout::Pane(self.active),
+            false => self.layout.clone(),
+        }
+    }
+}
+
+/// A window taken out of a session by `take_window`, with its panes, to be put
+/// into another by `put_window`.
+pub struct TakenWindow<P> {
+    window: Window,
+    panes: BTreeMap<usize, P>,
+    launches: BTreeMap<usize, Option<String>>,
+    hung_up: BTreeSet<usize>,
+}
+
+/// The next pane index and window id, shared by a session and its siblings so
+/// that panes and windows moved from one to another keep theirs.
+#[derive(Debug, Default)]
+struct Ids {
+    pane: AtomicUsize,
+    window: AtomicUsize,
+}
+
+/// A collection of windows, at most one of which is selected.
+///
+/// Windows and panes are identified by indices. Pane indices are never reused
+/// within a session; a window keeps its index unless it is moved or swapped.
+pub struct Session<P: SessionPane> {
+    /// What the session is called, to attach to it by.
+    name: String,
+    panes: BTreeMap<usize, P>,
+    windows: BTreeMap<usize, Window>,
+    selected_window: Option<usize>,
+    /// The window selected before the selected window, if it is still open.
+    last_selected: Option<usize>,
+    next_window_idx: usize,
+    /// The index of the first window.
+    base_index: usize,
+    /// The number of the first pane of a window.
+    pane_base_index: usize,
+    ids: Arc<Ids>,
+    /// What each pane running a process was started to run: a command, or the
+    /// shell if `None`.
+    launches: BTreeMap<usize, Option<String>>,
+    size: Winsize,
+    cursor_blink: CursorBlink,
+    /// The cursor style last sent to the outer terminal, if it is known to be
+    /// in effect.
+    drawn_cursor_style: Option<CursorStyle>,
+    scale_line_attrs: bool,
+    text_blink: TextBlink,
+    /// Whether panes keep the lines they finish, for `take_lines`.
+    line_log: bool,
+    /// The environment given to new panes.
+    environment: Environment,
+    /// The variables taken from an attaching client.
+    update_environment: Vec<String>,
+    /// The `TERM` given to new windows, if not the one in the environment.
+    default_term: Option<String>,
+    /// The shell new panes run, if not the user's.
+    default_shell: Option<String>,
+    /// The command new panes run with the shell when not given one, if not
+    /// the shell itself.
+    default_command: Option<String>,
+    /// Whether panes sharing a window have a row above them for their title.
+    pane_titles: bool,
+    border_styles: BorderStyles,
+    /// Whether panes whose processes exit are kept, in windows which do not
+    /// say otherwise.
+    remain_on_exit: bool,
+    /// Whether the input to new windows is recorded.
+    record_input: bool,
+    /// The smallest panes may be made by splitting or resizing.
+    min_pane_size: MinSize,
+    /// The panes hung up on by `kill_pane`, to be closed when they exit.
+    hung_up: BTreeSet<usize>,
+    /// The windows closed so far, oldest first.
+    closed_windows: Vec<ClosedWindow>,
+    /// How the process of the pane to exit last exited, if it is known.
+    last_exit_status: Option<ExitStatus>,
+    /// The windows flagged for activity since the last `take_activity`.
+    new_activity: BTreeSet<usize>,
+    created: Instant,
+}
+
+impl<P: SessionPane> Session<P> {
+    /// Create a session named `0` with no windows, to be displayed at size
+    /// `size`.
+    pub fn new(size: Winsize) -> Session<P> {
+        Session {
+            name: "0".to_string(),
+            panes: BTreeMap::new(),
+            windows: BTreeMap::new(),
+            selected_window: None,
+            last_selected: None,
+            next_window_idx: 0,
+            base_index: 0,
+            pane_base_index: 0,
+            ids: Arc::default(),
+            launches: BTreeMap::new(),
+            size,
+            cursor_blink: CursorBlink::default(),
+            drawn_cursor_style: None,
+            scale_line_attrs: false,
+            text_blink: TextBlink::default(),
+            line_log: false,
+            environment: Environment::for_panes(),
+            update_environment: environment::DEFAULT_UPDATE
+                .iter()
+                .map(|name| name.to_string())
+                .collect(),
+            default_term: None,
+            default_shell: None,
+            default_command: None,
+            pane_titles: false,
+            border_styles: BorderStyles::default(),
+            remain_on_exit: false,
+            record_input: false,
+            min_pane_size: MinSize::default(),
+            hung_up: BTreeSet::new(),
+            closed_windows: Vec::new(),
+            last_exit_status: None,
+            new_activity: BTreeSet::new(),
+            created: Instant::now(),
+        }
+    }
+
+    /// Create a session named `name` with no windows, with this session's size,
+    /// settings and environment.
+    pub fn sibling(&self, name: &str) -> Session<P> {
+        Session {
+            name: name.to_string(),
+            cursor_blink: self.cursor_blink,
+            scale_line_attrs: self.scale_line_attrs,
+            text_blink: self.text_blink,
+            line_log: self.line_log,
+            environment: self.environment.clone(),
+            update_environment: self.update_environment.clone(),
+            default_term: self.default_term.clone(),
+            default_shell: self.default_shell.clone(),
+            default_command: self.default_command.clone(),
+            pane_titles: self.pane_titles,
+            border_styles: self.border_styles.clone(),
+            remain_on_exit: self.remain_on_exit,
+            record_input: self.record_input,
+            min_pane_size: self.min_pane_size,
+            next_window_idx: self.base_index,
+            base_index: self.base_index,
+            pane_base_index: self.pane_base_index,
+            ids: self.ids.clone(),
+            ..Session::new(self.size)
+        }
+    }
+
+    /// What the session is called.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// When the session was created.
+    pub fn created(&self) -> Instant {
+        self.created
+    }
+
+    /// The windows which have closed, oldest first.
+    pub fn closed_windows(&self) -> &[ClosedWindow] {
+        &self.closed_windows
+    }
+
+    /// How the process of the pane to exit last exited, if it is known, e.g.
+    /// for the `pane-exited` hook.
+    pub fn last_exit_status(&self) -> Option<ExitStatus> {
+        self.last_exit_status
+    }
+
+    /// Call the session `name`.
+    pub fn set_name(&mut self, name: &str) {
+        self.name = name.to_string();
+    }
+
+    /// The environment given to new panes: that of the process which created
+    /// the session, with any changes since.
+    pub fn environment(&self) -> &Environment {
+        &self.environment
+    }
+
+    /// Change the environment given to panes created from now on.
+    pub fn environment_mut(&mut self) -> &mut Environment {
+        &mut self.environment
+    }
+
+    /// Set the `TERM` given to new windows, or if `None`, leave it to the
+    /// environment.
+    ///
+    /// Fails if there is no terminfo entry for `term`.
+    pub fn set_default_term(&mut self, term: Option<&str>) -> Result<(), SessionError> {
+        self.default_term = check_term(term)?;
+        Ok(())
+    }
+
+    /// Set the `TERM` given to new panes in the selected window, e.g. to one a
+    /// remote system knows; panes already running keep theirs.
+    ///
+    /// Fails if there is no terminfo entry for `term`.
+    pub fn set_window_term(&mut self, term: Option<&str>) -> Result<(), SessionError> {
+        let idx = self.selected_window.ok_or(SessionError::NoSelectedWindow)?;
+        let term = check_term(term)?;
+        self.windows.get_mut(&idx).unwrap().term = term;
+        Ok(())
+    }
+
+    /// Refuse to split or resize panes to less than `min`.
+    pub fn set_min_pane_size(&mut self, min: MinSize) {
+        self.min_pane_size = min;
+    }
+
+    /// The smallest panes may be made by splitting or resizing.
+    pub fn min_pane_size(&self) -> MinSize {
+        self.min_pane_size
+    }
+
+    /// Number windows from `idx` rather than 0: new windows are given no
+    /// lower index, and are renumbered from it.
+    pub fn set_base_index(&mut self, idx: usize) {
+        self.base_index = idx;
+        self.next_window_idx = self.next_window_idx.max(idx);
+    }
+
+    /// Number the panes of a window from `number` rather than 0.
+    pub fn set_pane_base_index(&mut self, number: usize) {
+        self.pane_base_index = number;
+    }
+
+    /// Set the shell new panes run, or if `None`, the user's; see
+    /// `util::get_shell`.
+    pub fn set_default_shell(&mut self, shell: Option<&str>) {
+        self.default_shell = shell.map(String::from);
+    }
+
+    /// Set the command new panes run with the shell when not given one, or if
+    /// `None`, run the shell itself.
+    pub fn set_default_command(&mut self, command: Option<&str>) {
+        self.default_command = command.map(String::from);
+    }
+
+    /// Choose whether panes whose processes exit are kept, showing what they
+    /// left, in windows which do not say otherwise.
+    pub fn set_default_remain_on_exit(&mut self, remain: bool) {
+        self.remain_on_exit = remain;
+    }
+
+    /// Choose whether panes of the selected window whose processes exit are
+    /// kept, whatever the session says.
+    pub fn set_window_remain_on_exit(
+        &mut self,
+        remain: bool,
+    ) -> Result<(), SessionError> {
+        let idx = self.selected_window.ok_or(SessionError::NoSelectedWindow)?;
+        self.windows.get_mut(&idx).unwrap().remain_on_exit = Some(remain);
+        Ok(())
+    }
+
+    /// Choose whether the input to windows created from now on is recorded.
+    pub fn set_default_record_input(&mut self, record: bool) {
+        self.record_input = record;
+    }
+
+    /// Start recording the input to the selected window, or stop and forget
+    /// what was recorded.
+    pub fn set_window_record_input(
+        &mut self,
+        record: bool,
+    ) -> Result<(), SessionError> {
+        let idx = self.selected_window.ok_or(SessionError::NoSelectedWindow)?;
+        let log = &mut self.windows.get_mut(&idx).unwrap().input_log;
+        match (record, &log) {
+            (true, None) => *log = Some(InputLog::new(InputLog::DEFAULT_CAPACITY)),
+            (false, _) => *log = None,
+            (true, Some(_)) => (),
+        }
+        Ok(())
+    }
+
+    /// The input recorded for window `idx`, if it is recorded.
+    pub fn input_log(&self, idx: usize) -> Option<&InputLog> {
+        self.windows.get(&idx)?.input_log.as_ref()
+    }
+
+    /// Choose which variables `update_environment` takes from a client.
+    pub fn set_update_environment(&mut self, names: Vec<String>) {
+        self.update_environment = names;
+    }
+
+    /// Take the variables chosen with `set_update_environment` from the
+    /// environment `client` of a client attaching to the session.
+    pub fn update_environment<'a, I>(&mut self, client: I)
+    where
+        I: IntoIterator<Item = (&'a str, &'a str)>,
+    {
+        self.environment.update(client, &self.update_environment);
+    }
+
+    /// Choose whether the cursor blinks, overriding applications unless
+    /// `CursorBlink::Application` is given.
+    pub fn set_cursor_blink(&mut self, cursor_blink: CursorBlink) {
+        self.cursor_blink = cursor_blink;
+    }
+
+    /// Choose whether double-width and double-height lines are spaced out, for
+    /// outer terminals which cannot display them.
+    pub fn set_scale_line_attrs(&mut self, scale: bool) {
+        self.scale_line_attrs = scale;
+        for pane in self.panes.values_mut() {
+            pane.set_scale_line_attrs(scale);
+        }
+        if let Some(idx) = self.selected_window {
+            self.arrange_window(idx);
+        }
+    }
+
+    /// Choose whether panes sharing a window show their titles in a row above
+    /// them.
+    pub fn set_pane_titles(&mut self, titles: bool) {
+        self.pane_titles = titles;
+        if let Some(idx) = self.selected_window {
+            self.arrange_window(idx);
+        }
+    }
+
+    /// Draw the lines between panes with `styles`.
+    pub fn set_border_styles(&mut self, styles: BorderStyles) {
+        self.border_styles = styles;
+        for window in self.windows.values_mut() {
+            window.drawn_borders = None;
+        }
+    }
+
+    /// Choose how text with the blink attribute is shown in every pane.
+    pub fn set_text_blink(&mut self, text_blink: TextBlink) {
+        self.text_blink = text_blink;
+        for pane in self.panes.values_mut() {
+            pane.set_text_blink(text_blink);
+        }
+    }
+
+    /// Choose whether every pane keeps the lines it finishes, for `take_lines`.
+    pub fn set_line_log(&mut self, log: bool) {
+        self.line_log = log;
+        for pane in self.panes.values_mut() {
+            pane.set_line_log(log);
+        }
+    }
+
+    /// The lines written by the active pane of the selected window since the
+    /// last call, if `set_line_log` is on. What the other panes wrote in the
+    /// meantime is dropped.
+    pub fn take_lines(&mut self) -> Option<NewLines> {
+        if !self.line_log {
+            return None;
+        }
+        let active = self.windows.get(&self.selected_window?)?.active;
+        let mut new_lines = None;
+        for (idx, pane) in self.panes.iter_mut() {
+            let lines = pane.take_lines();
+            if *idx == active {
+                new_lines = Some(NewLines {
+                    pane_idx: *idx,
+                    lines,
+                    current: pane.cursor_line(),
+                });
+            }
+        }
+        new_lines
+    }
+
+    /// Show or hide blinking text, for `TextBlink::Animate`.
+    pub fn set_blink_phase(&mut self, visible: bool) {
+        for pane in self.panes.values_mut() {
+            pane.set_blink_phase(visible);
+        }
+    }
+
+    /// Limit the output of the active pane of the selected window to
+    /// `lines_per_sec` lines a second, or remove the limit if `None`.
+    pub fn set_slow_print(
+        &mut self,
+        lines_per_sec: Option<u32>,
+    ) -> Result<(), SessionError> {
+        let idx = self.selected_window.ok_or(SessionError::NoSelectedWindow)?;
+        let pane = self.panes.get_mut(&self.windows[&idx].active).unwrap();
+        pane.set_slow_print(lines_per_sec);
+        Ok(())
+    }
+
+    /// Make the active pane of the selected window private, or not.
+    pub fn set_private(&mut self, private: bool) -> Result<(), SessionError> {
+        let idx = self.selected_window.ok_or(SessionError::NoSelectedWindow)?;
+        let pane = self.panes.get_mut(&self.windows[&idx].active).unwrap();
+        pane.set_private(private);
+        Ok(())
+    }
+
+    /// Whether any pane on screen is private, so that the screen should not be
+    /// recorded.
+    pub fn is_showing_private(&self) -> bool {
+        let window = match self.selected_window {
+            Some(idx) => &self.windows[&idx],
+            None => return false,
+        };
+        window
+            .layout
+            .panes()
+            .iter()
+            .any(|pane| self.panes[pane].is_private())
+    }
+
+    /// Apply the output held back by panes with a limit which is due by `now`.
+    pub fn advance_slow_print(&mut self, now: Instant) {
+        for pane in self.panes.values_mut() {
+            pane.advance_slow_print(now);
+        }
+    }
+
+    /// Let the applets running in panes catch up with the time.
+    pub fn tick_applets(&mut self) {
+        for pane in self.panes.values_mut() {
+            pane.tick();
+        }
+    }
+
+    /// Spawn a new window with a single pane running the user's shell.
+    ///
+    /// The new window is not selected.
+    pub fn new_window(&mut self) -> Result<(usize, PaneUpdates), SessionError> {
+        self.new_window_running(None, None, &[])
+    }
+
+    /// Spawn a new window with a single pane running `command` with the user's
+    /// shell, or the shell itself if `None`, named after the command.
+    ///
+    /// It starts in `cwd`, taken from the working directory of the selected
+    /// window's active pane if relative, or in that directory if `None`. The
+    /// variables in `env` are set in the environment of its panes, over the
+    /// session's.
+    ///
+    /// The window closes when the command exits. It is not selected.
+    pub fn new_window_running(
+        &mut self,
+        command: Option<&str>,
+        cwd: Option<&Path>,
+        env: &[(String, String)],
+    ) -> Result<(usize, PaneUpdates), SessionError> {
+        let current = self.selected_window.and_then(|idx| {
+            let active = self.windows[&idx].active;
+            self.panes.get(&active)?.cwd()
+        });
+        let cwd = match (cwd, current) {
+            (Some(cwd), Some(current)) => Some(current.join(cwd)),
+            (cwd, current) => cwd.map(PathBuf::from).or(current),
+        };
+        if let Some(cwd) = cwd.as_ref().filter(|cwd| !cwd.is_dir()) {
+            return Err(SessionError::NoSuchDirectory(cwd.clone()));
+        }
+        let term = self.default_term.clone();
+        let (pane_idx, updates) =
+            self.spawn_pane(self.size, term.as_deref(), env, command, cwd.as_deref())?;
+        let name = self.command_name(command);
+        let window_idx = self.insert_window(name, pane_idx, term);
+        self.windows.get_mut(&window_idx).unwrap().environment = env.to_vec();
+        Ok((window_idx, updates))
+    }
+
+    /// Create a new window with a single pane running `applet`, named after it.
+    ///
+    /// The new window is not selected.
+    pub fn new_applet_window(
+        &mut self,
+        applet: Box<dyn Applet>,
+    ) -> Result<(usize, PaneUpdates), SessionError> {
+        let name = applet.name().to_string();
+        let (mut pane, updates) = P::with_applet(applet, self.size)?;
+        pane.set_scale_line_attrs(self.scale_line_attrs);
+        pane.set_text_blink(self.text_blink);
+        pane.set_line_log(self.line_log);
+        let pane_idx = self.insert_pane(pane);
+        let term = self.default_term.clone();
+        let window_idx = self.insert_window(name, pane_idx, term);
+        Ok((window_idx, PaneUpdates { pane_idx, updates }))
+    }
+
+    /// Split the active pane of the selected window in `direction`, running the
+    /// user's shell in the new pane, which becomes active.
+    pub fn split_pane(
+        &mut self,
+        direction: Direction,
+    ) -> Result<PaneUpdates, SessionError> {
+        let window_idx = self.selected_window.ok_or(SessionError::NoSelectedWindow)?;
+        let window = &self.windows[&window_idx];
+        let pane_idx = self.ids.pane.load(Ordering::Relaxed);
+        let mut layout = window.layout.clone();
+        layout.split(window.active, pane_idx, direction);
+        let arrangement = layout.arrange(Rect::from_size(self.size));
+        if !arrangement.fits(self.min_pane_size) {
+            return Err(SessionError::NoSpace);
+        }
+        let (_, rect) = *arrangement
+            .panes
+            .iter()
+            .find(|(idx, _)| *idx == pane_idx)
+            .unwrap();
+
+        let term = self.windows[&window_idx].term.clone();
+        let env = self.windows[&window_idx].environment.clone();
+        let (pane_idx, updates) =
+            self.spawn_pane(rect.size(), term.as_deref(), &env, None, None)?;
+        self.unzoom(window_idx);
+        let window = self.windows.get_mut(&window_idx).unwrap();
+        window.layout = layout;
+        window.active = pane_idx;
+        debug!("split window {} with pane {}", window_idx, pane_idx);
+        self.arrange_window(window_idx);
+        Ok(updates)
+    }
+
+    /// Move the active pane of the selected window into a new window of its own,
+    /// returning the new window's index.
+    ///
+    /// The new window is not selected.
+    pub fn break_pane(&mut self) -> Result<usize, SessionError> {
+        let source = self.selected_window.ok_or(SessionError::NoSelectedWindow)?;
+        let pane_idx = self.windows[&source].active;
+        if !self.take_pane(source, pane_idx) {
+            return Err(SessionError::OnlyPane);
+        }
+        self.arrange_window(source);
+        let term = self.windows[&source].term.clone();
+        let env = self.windows[&source].environment.clone();
+        let name = self.command_name(None);
+        let window_idx = self.insert_window(name, pane_idx, term);
+        self.windows.get_mut(&window_idx).unwrap().environment = env;
+        debug!("broke pane {} out into window {}", pane_idx, window_idx);
+        Ok(window_idx)
+    }
+
+    /// Move the active pane of the selected window into window `target`,
+    /// splitting its active pane in `direction`; the moved pane becomes active
+    /// there.
+    ///
+    /// If it was the only pane, the selected window closes and `target` is
+    /// selected instead.
+    pub fn join_pane(
+        &mut self,
+        target: usize,
+        direction: Direction,
+    ) -> Result<(), SessionError> {
+        let source = self.selected_window.ok_or(SessionError::NoSelectedWindow)?;
+        let pane_idx = self.windows[&source].active;
+        let window = match self.windows.get(&target) {
+            Some(window) if target != source => window,
+            _ => return Err(SessionError::NoSuchWindow(target)),
+        };
+        let mut layout = window.layout.clone();
+        layout.split(window.active, pane_idx, direction);
+        let arrangement = layout.arrange(Rect::from_size(self.size));
+        if !arrangement.fits(self.min_pane_size) {
+            return Err(SessionError::NoSpace);
+        }
+
+        let emptied = !self.take_pane(source, pane_idx);
+        self.unzoom(target);
+        let window = self.windows.get_mut(&target).unwrap();
+        window.layout = layout;
+        window.active = pane_idx;
+        debug!("joined pane {} to window {}", pane_idx, target);
+        if emptied {
+            // The window closes, but its pane lives on in `target`, and the
+            // window selected before it is still the last one.
+            let last = self.last_selected.filter(|idx| *idx != target);
+            self.select_window(target);
+            self.remove_window(source, None);
+            self.last_selected = last.or(self.last_selected);
+        } else {
+            self.arrange_window(source);
+        }
+        self.arrange_window(target);
+        Ok(())
+    }
+
+    /// Rearrange the panes of the selected window with `preset`.
+    pub fn select_layout(&mut self, preset: Preset) -> Result<(), SessionError> {
+        let idx = self.selected_window.ok_or(SessionError::NoSelectedWindow)?;
+        self.unzoom(idx);
+        let window = self.windows.get_mut(&idx).unwrap();
+        window.layout = preset.layout(&window.layout.panes());
+        window.preset = Some(preset);
+        self.arrange_window(idx);
+        Ok(())
+    }
+
+    /// Rearrange the panes of the selected window with the preset after the one
+    /// last used.
+    pub fn next_layout(&mut self) -> Result<(), SessionError> {
+        let idx = self.selected_window.ok_or(SessionError::NoSelectedWindow)?;
+        let preset = match self.windows[&idx].preset {
+            Some(preset) => preset.next(),
+            None => Preset::ALL[0],
+        };
+        self.select_layout(preset)
+    }
+
+    /// Grow the active pane of the selected window by `cells` in `direction`,
+    /// or shrink it if `cells` is negative, resizing its neighbours to match,
+    /// as far as the minimum pane size allows.
+    ///
+    /// Only panes whose size or position changed are redrawn.
+    pub fn resize_pane(
+        &mut self,
+        direction: Direction,
+        cells: i32,
+    ) -> Result<(), SessionError> {
+        let idx = self.selected_window.ok_or(SessionError::NoSelectedWindow)?;
+        let unzoomed = self.unzoom(idx);
+        let window = self.windows.get_mut(&idx).unwrap();
+        let area = Rect::from_size(self.size);
+        let min = self.min_pane_size;
+        let resized = window
+            .layout
+            .resize(window.active, direction, cells, area, min);
+        if resized {
+            window.preset = None;
+        }
+        if resized || unzoomed {
+            self.arrange_window(idx);
+        }
+        Ok(())
+    }
+
+    /// Show the active pane of the selected window alone, filling the window,
+    /// or every pane again if it is; a window of one pane is left alone.
+    ///
+    /// Changing the panes or which is active shows them all again.
+    pub fn toggle_zoom(&mut self) -> Result<(), SessionError> {
+        let idx = self.selected_window.ok_or(SessionError::NoSelectedWindow)?;
+        if !self.unzoom(idx) {
+            let window = self.windows.get_mut(&idx).unwrap();
+            if window.layout.panes().len() < 2 {
+                return Ok(());
+            }
+            window.zoomed = true;
+            self.panes.get_mut(&window.active).unwrap().mark_dirty();
+            debug!("zoomed window {}", idx);
+        }
+        self.arrange_window(idx);
+        Ok(())
+    }
+
+    /// Make the pane after the active one in the selected window active.
+    pub fn select_next_pane(&mut self) -> Option<usize> {
+        let idx = self.selected_window?;
+        let window = self.windows.get_mut(&idx)?;
+        let panes = window.layout.panes();
+        let position = panes.iter().position(|idx| *idx == window.active)?;
+        let next = panes[(position + 1) % panes.len()];
+        window.active = next;
+        if next != panes[position] && self.unzoom(idx) {
+            self.arrange_window(idx);
+        }
+        Some(next)
+    }
+
+    /// Make the pane of the selected window at `col`, `row` active, returning
+    /// its index, or `None` if there is no pane there.
+    pub fn select_pane_at(&mut self, col: u16, row: u16) -> Option<usize> {
+        let window = self.windows.get_mut(&self.selected_window?)?;
+        let arrangement = window.shown_layout().arrange(Rect::from_size(self.size));
+        let (idx, _) = arrangement.panes.into_iter().find(|(_, rect)| {
+            (rect.col..rect.col + rect.cols).contains(&col)
+                && (rect.row..rect.row + rect.rows).contains(&row)
+        })?;
+        window.active = idx;
+        Some(idx)
+    }
+
+    /// Report `report`, of a mouse event at a position in the window area, to
+    /// the process in the pane of the selected window there, at its position in
+    /// the pane, if the process asked for mouse events; whether it was reported.
+    pub fn report_mouse(&mut self, report: MouseReport) -> Result<bool, SessionError> {
+        let found = self.pane_rects().into_iter().find(|(_, rect)| {
+            (rect.col..rect.col + rect.cols).contains(&report.col)
+                && (rect.row..rect.row + rect.rows).contains(&report.row)
+        });
+        let (idx, rect) = match found {
+            Some(found) => found,
+            None => return Ok(false),
+        };
+        let pane = self.panes.get_mut(&idx).unwrap();
+        let encoding = match pane.mouse_reporting() {
+            Some(encoding) => encoding,
+            None => return Ok(false),
+        };
+        let report = MouseReport {
+            col: report.col - rect.col,
+            row: report.row - rect.row,
+            ..report
+        };
+        pane.receive_stdin(&report.encode(encoding))?;
+        Ok(true)
+    }
+
+    /// Hang up on the active pane of the selected window.
+    ///
+    /// The pane closes once its process has exited.
+    pub fn kill_pane(&mut self) -> Result<(), SessionError> {
+        let idx = self.selected_window.ok_or(SessionError::NoSelectedWindow)?;
+        self.hang_up(self.windows[&idx].active);
+        Ok(())
+    }
+
+    /// Hang up on the pane numbered `number` in window `idx`, as `kill_pane`
+    /// does on the active pane.
+    pub fn kill_pane_number(
+        &mut self,
+        idx: usize,
+        number: usize,
+    ) -> Result<(), SessionError> {
+        let pane_idx = self.pane_by_number(idx, number)?;
+        self.hang_up(pane_idx);
+        Ok(())
+    }
+
+    /// Hang up on pane `pane_idx`, or close it if its process has exited.
+    fn hang_up(&mut self, pane_idx: usize) {
+        if self.panes[&pane_idx].is_dead() {
+            self.close_pane(pane_idx);
+        } else {
+            self.panes[&pane_idx].hangup();
+            self.hung_up.insert(pane_idx);
+        }
+    }
+
+    /// Kill the processes in every pane of the selected window, and close it,
+    /// selecting a neighbour.
+    ///
+    /// This closes a window whose processes do not exit when hung up on.
+    pub fn kill_window(&mut self) -> Result<(), SessionError> {
+        let idx = self.selected_window.ok_or(SessionError::NoSelectedWindow)?;
+        self.kill_window_idx(idx)
+    }
+
+    /// Kill the processes in every pane of window `idx`, and close it, as
+    /// `kill_window` does the selected window.
+    pub fn kill_window_idx(&mut self, idx: usize) -> Result<(), SessionError> {
+        let window = self
+            .windows
+            .get(&idx)
+            .ok_or(SessionError::NoSuchWindow(idx))?;
+        for pane in window.layout.panes() {
+            self.panes[&pane].kill();
+        }
+        self.close_window(idx, None);
+        Ok(())
+    }
+
+    /// Move every window of `other` into this session, after its own windows,
+    /// and end `other`.
+    ///
+    /// Windows and panes are renumbered to follow this session's; `updates`,
+    /// the streams of `other`'s panes, are returned with their new indices.
+    /// The windows keep their names, and none is selected.
+    pub fn absorb<I>(&mut self, other: Session<P>, updates: I) -> Vec<PaneUpdates>
+    where
+        I: IntoIterator<Item = PaneUpdates>,
+    {
+        let Session {
+            mut panes,
+            windows,
+            mut launches,
+            ..
+        } = other;
+        let mut renumbered = BTreeMap::new();
+        for (idx, mut window) in windows {
+            window.layout.renumber(&mut |pane_idx| {
+                let mut pane = panes.remove(&pane_idx).unwrap();
+                pane.set_scale_line_attrs(self.scale_line_attrs);
+                pane.set_text_blink(self.text_blink);
+                pane.set_line_log(self.line_log);
+                let new_idx = self.insert_pane(pane);
+                if let Some(launch) = launches.remove(&pane_idx) {
+                    self.launches.insert(new_idx, launch);
+                }
+                renumbered.insert(pane_idx, new_idx);
+                new_idx
+            });
+            window.active = renumbered[&window.active];
+            window.drawn_borders = None;
+            let new_idx = self.next_window_idx;
+            self.next_window_idx += 1;
+            self.windows.insert(new_idx, window);
+            debug!("absorbed window {} as {}", idx, new_idx);
+        }
+        // Panes in no window are gone with the other session.
+        updates
+            .into_iter()
+            .filter_map(|updates| {
+                let pane_idx = *renumbered.get(&updates.pane_idx)?;
+                Some(PaneUpdates {
+                    pane_idx,
+                    updates: updates.updates,
+                })
+            })
+            .collect()
+    }
+
+    /// What the session runs where, to start it again with `restore`.
+    ///
+    /// Panes running applets are left out, and so are windows of nothing else.
+    pub fn snapshot(&self) -> SessionSnapshot {
+        let windows: Vec<_> = self
+            .windows
+            .iter()
+            .filter_map(|(&idx, window)| {
+                let all = window.layout.panes();
+                let panes: Vec<_> = all
+                    .iter()
+                    .copied()
+                    .filter(|pane| self.launches.contains_key(pane))
+                    .collect();
+                if panes.is_empty() {
+                    return None;
+                }
+                let mut layout = window.layout.clone();
+                for pane in all.iter().filter(|pane| !panes.contains(pane)) {
+                    layout.remove(*pane);
+                }
+                let position = |pane| panes.iter().position(|p| *p == pane);
+                layout.renumber(&mut |pane| position(pane).unwrap());
+                Some(WindowSnapshot {
+                    idx,
+                    name: window.name.clone(),
+                    panes: panes
+                        .iter()
+                        .map(|pane| PaneSnapshot {
+                            command: self.launches[pane].clone(),
+                            cwd: self.panes[pane].cwd(),
+                        })
+                        .collect(),
+                    layout,
+                    active: position(window.active).unwrap_or(0),
+                })
+            })
+            .collect();
+        let selected = self
+            .selected_window
+            .filter(|idx| windows.iter().any(|window| window.idx == *idx));
+        SessionSnapshot {
+            name: self.name.clone(),
+            selected,
+            windows,
+        }
+    }
+
+    /// A record of the session as text: what each pane shows, what it runs
+    /// and where, and how the windows are laid out, with the windows closed
+    /// so far.
+    ///
+    /// Windows not arranged for the session's size are arranged first, so that
+    /// what their panes show fits. Private panes are recorded without it.
+    pub fn export(&mut self) -> SessionExport {
+        let idxs: Vec<_> = self.windows.keys().copied().collect();
+        let mut windows = Vec::new();
+        for idx in idxs {
+            if self.windows[&idx].arranged != Some(self.size) {
+                self.arrange_window(idx);
+            }
+            let window = &self.windows[&idx];
+            let area = Rect::from_size(self.size);
+            let areas = pane_areas(&window.layout, area, self.pane_titles);
+            let all = window.layout.panes();
+            let position = |pane| all.iter().position(|p| *p == pane).unwrap();
+            let mut layout = window.layout.clone();
+            layout.renumber(&mut |pane| position(pane));
+            let active = position(window.active);
+            let name = window.name.clone();
+            let input = window.input_log.as_ref().map(InputLog::text);
+            let panes = all
+                .iter()
+                .map(|pane_idx| {
+                    let rect = areas
+                        .iter()
+                        .find(|(idx, _, _)| idx == pane_idx)
+                        .map(|(_, rect, _)| *rect)
+                        .unwrap_or(area);
+                    let launch = self.launches.get(pane_idx);
+                    let pane = self.panes.get_mut(pane_idx).unwrap();
+                    PaneExport {
+                        applet: launch.is_none(),
+                        command: launch.cloned().flatten(),
+                        cwd: pane.cwd(),
+                        status: pane.exit_status().map(ExitStatus::code),
+                        cols: rect.cols,
+                        rows: rect.rows,
+                        contents: Some(pane.contents()).filter(|_| !pane.is_private()),
+                    }
+                })
+                .collect();
+            windows.push(WindowExport {
+                idx,
+                name,
+                panes,
+                layout,
+                active,
+                input,
+            });
+        }
+        SessionExport {
+            name: self.name.clone(),
+            selected: self.selected_window,
+            windows,
+            closed: self.closed_windows.clone(),
+            pane_base_index: self.pane_base_index,
+        }
+    }
+
+    /// Start the windows of `snapshot` again in this session, which takes its
+    /// name, selecting the window selected then; the streams of the new panes
+    /// are returned.
+    ///
+    /// Windows keep their indices where they are free. Each pane runs its
+    /// command again, in its directory if that is still there. Windows whose
+    /// layout does not match their panes are skipped.
+    pub fn restore(
+        &mut self,
+        snapshot: &SessionSnapshot,
+    ) -> Result<Vec<PaneUpdates>, SessionError> {
+        self.name = snapshot.name.clone();
+        let term = self.default_term.clone();
+        let mut updates = Vec::new();
+        for window in &snapshot.windows {
+            if !window.is_consistent() {
+                continue;
+            }
+            let mut panes = Vec::new();
+            for pane in &window.panes {
+                let cwd = pane.cwd.as_deref().filter(|cwd| cwd.is_dir());
+                let command = pane.command.as_deref();
+                let (pane_idx, pane_updates) =
+                    self.spawn_pane(self.size, term.as_deref(), &[], command, cwd)?;
+                panes.push(pane_idx);
+                updates.push(pane_updates);
+            }
+            let idx = self.insert_window(
+                window.name.clone(),
+                panes[window.active],
+                term.clone(),
+            );
+            let mut layout = window.layout.clone();
+            layout.renumber(&mut |position| panes[position]);
+            self.windows.get_mut(&idx).unwrap().layout = layout;
+            self.arrange_window(idx);
+            if self.move_window(idx, window.idx).is_err() {
+                debug!("window {} restored as {}", window.idx, idx);
+            }
+        }
+        if let Some(selected) = snapshot.selected {
+            self.select_window(selected);
+        }
+        Ok(updates)
+    }
+
+    /// The id of window `idx`, which it keeps when moved to a sibling session.
+    pub fn window_id(&self, idx: usize) -> Option<usize> {
+        Some(self.windows.get(&idx)?.id)
+    }
+
+    /// The index of the window with id `id`, if it is in this session.
+    pub fn find_window(&self, id: usize) -> Option<usize> {
+        self.windows
+            .iter()
+            .find(|(_, window)| window.id == id)
+            .map(|(idx, _)| *idx)
+    }
+
+    /// Whether pane `pane_idx` is in one of this session's windows.
+    pub fn has_pane(&self, pane_idx: usize) -> bool {
+        self.panes.contains_key(&pane_idx)
+    }
+
+    /// Set aside the next free window index, for a window to be put there
+    /// later.
+    pub fn reserve_window_idx(&mut self) -> usize {
+        self.next_window_idx += 1;
+        self.next_window_idx - 1
+    }
+
+    /// Take window `idx` out of this session, with its panes, to put it into a
+    /// sibling session with `put_window`; its panes' streams go on updating
+    /// them, as their indices are kept.
+    ///
+    /// Another window is selected in its place, as if it had closed.
+    pub fn take_window(&mut self, idx: usize) -> Result<TakenWindow<P>, SessionError> {
+        let window = self
+            .windows
+            .remove(&idx)
+            .ok_or(SessionError::NoSuchWindow(idx))?;
+        let mut panes = BTreeMap::new();
+        let mut launches = BTreeMap::new();
+        let mut hung_up = BTreeSet::new();
+        for pane in window.layout.panes() {
+            panes.insert(pane, self.panes.remove(&pane).unwrap());
+            if let Some(launch) = self.launches.remove(&pane) {
+                launches.insert(pane, launch);
+            }
+            if self.hung_up.remove(&pane) {
+                hung_up.insert(pane);
+            }
+        }
+        debug!("took window {}", idx);
+        self.deselect_window(idx);
+        Ok(TakenWindow {
+            window,
+            panes,
+            launches,
+            hung_up,
+        })
+    }
+
+    /// Put `taken`, a window taken out of a sibling session, into this one at
+    /// index `idx`, or the next free index if that is taken; the index it is
+    /// put at is returned.
+    ///
+    /// It is selected if no window is.
+    pub fn put_window(&mut self, taken: TakenWindow<P>, idx: usize) -> usize {
+        let TakenWindow {
+            mut window,
+            panes,
+            launches,
+            hung_up,
+        } = taken;
+        let idx = if self.windows.contains_key(&idx) {
+            self.reserve_window_idx()
+        } else {
+            self.next_window_idx = self.next_window_idx.max(idx + 1);
+            idx
+        };
+        for (pane_idx, mut pane) in panes {
+            pane.set_scale_line_attrs(self.scale_line_attrs);
+            pane.set_text_blink(self.text_blink);
+            pane.set_line_log(self.line_log);
+            pane.mark_dirty();
+            self.panes.insert(pane_idx, pane);
+        }
+        self.launches.extend(launches);
+        self.hung_up.extend(hung_up);
+        window.drawn_borders = None;
+        window.arranged = None;
+        self.windows.insert(idx, window);
+        debug!("put window {}", idx);
+        if self.selected_window.is_none() {
+            self.select_window(idx);
+        }
+        idx
+    }
+
+    /// Select the window `idx`, returning `None` if it does not exist.
+    pub fn select_window(&mut self, idx: usize) -> Option<usize> {
+        let window = self.windows.get_mut(&idx)?;
+        (window.activity, window.bell) = (false, false);
+        for pane in window.layout.panes() {
+            self.panes.get_mut(&pane).unwrap().mark_dirty();
+        }
+        match self.selected_window {
+            Some(selected) if selected != idx => self.last_selected = Some(selected),
+            _ if self.last_selected == Some(idx) => self.last_selected = None,
+            _ => (),
+        }
+        self.selected_window = Some(idx);
+        self.arrange_window(idx);
+        Some(idx)
+    }
+
+    /// The window selected, and the one before it.
+    pub fn selection(&self) -> Selection {
+        Selection {
+            window: self.selected_window,
+            last: self.last_selected,
+        }
+    }
+
+    /// Select the windows of `selection`, e.g. as another client attached left
+    /// them; a window closed since is passed over, leaving the selected window
+    /// selected.
+    ///
+    /// Unlike `select_window`, this marks nothing to be redrawn, so that the
+    /// windows of several clients can be drawn in turn.
+    pub fn set_selection(&mut self, selection: Selection) {
+        if let Some(idx) = selection.window {
+            if let Some(window) = self.windows.get(&idx) {
+                if window.arranged != Some(self.size) {
+                    self.arrange_window(idx);
+                }
+                self.selected_window = Some(idx);
+            }
+        }
+        self.last_selected = selection
+            .last
+            .filter(|idx| self.windows.contains_key(idx))
+            .filter(|&idx| Some(idx) != self.selected_window);
+    }
+
+    /// Exchange the indices of windows `a` and `b`; the selection follows the
+    /// window.
+    pub fn swap_window(&mut self, a: usize, b: usize) -> Result<(), SessionError> {
+        for idx in [a, b] {
+            if !self.windows.contains_key(&idx) {
+                return Err(SessionError::NoSuchWindow(idx));
+            }
+        }
+        let window_a = self.windows.remove(&a).unwrap();
+        let window_b = self.windows.insert(b, window_a).unwrap();
+        self.windows.insert(a, window_b);
+        for selection in [&mut self.selected_window, &mut self.last_selected] {
+            if *selection == Some(a) {
+                *selection = Some(b);
+            } else if *selection == Some(b) {
+                *selection = Some(a);
+            }
+        }
+        Ok(())
+    }
+
+    /// Give window `idx` the index `new_idx`, which must be free; the selection
+    /// follows the window.
+    ///
+    /// New windows are numbered after the highest index yet.
+    pub fn move_window(
+        &mut self,
+        idx: usize,
+        new_idx: usize,
+    ) -> Result<(), SessionError> {
+        if idx == new_idx && self.windows.contains_key(&idx) {
+            return Ok(());
+        }
+        if self.windows.contains_key(&new_idx) {
+            return Err(SessionError::WindowExists(new_idx));
+        }
+        let window = self
+            .windows
+            .remove(&idx)
+            .ok_or(SessionError::NoSuchWindow(idx))?;
+        self.windows.insert(new_idx, window);
+        self.next_window_idx = self.next_window_idx.max(new_idx + 1);
+        for selection in [&mut self.selected_window, &mut self.last_selected] {
+            if *selection == Some(idx) {
+                *selection = Some(new_idx);
+            }
+        }
+        Ok(())
+    }
+
+    /// Number the windows from the base index up without gaps, keeping their
+    /// order; the selection follows the windows, and new windows are numbered
+    /// after them.
+    ///
+    /// Returns the indices changed, old and new. Indices reserved by
+    /// `reserve_window_idx` may be given out again.
+    pub fn renumber_windows(&mut self) -> Vec<(usize, usize)> {
+        let windows = std::mem::take(&mut self.windows);
+        let mut renumbered = Vec::new();
+        for (new_idx, (idx, window)) in (self.base_index..).zip(windows) {
+            if idx != new_idx {
+                renumbered.push((idx, new_idx));
+            }
+            self.windows.insert(new_idx, window);
+        }
+        self.next_window_idx = self.base_index + self.windows.len();
+        for selection in [&mut self.selected_window, &mut self.last_selected] {
+            let moved = renumbered.iter().find(|(idx, _)| Some(*idx) == *selection);
+            if let Some((_, new_idx)) = moved {
+                *selection = Some(*new_idx);
+            }
+        }
+        renumbered
+    }
+
+    /// Rename the selected window.
+    pub fn rename_window(&mut self, name: &str) -> Result<(), SessionError> {
+        let idx = self.selected_window.ok_or(SessionError::NoSelectedWindow)?;
+        self.rename_window_idx(idx, name)
+    }
+
+    /// Rename window `idx`.
+    pub fn rename_window_idx(
+        &mut self,
+        idx: usize,
+        name: &str,
+    ) -> Result<(), SessionError> {
+        let window = self.windows.get_mut(&idx);
+        window.ok_or(SessionError::NoSuchWindow(idx))?.name = name.to_string();
+        Ok(())
+    }
+
+    /// The size the windows are displayed at.
+    pub fn size(&self) -> Winsize {
+        self.size
+    }
+
+    /// How many panes window `idx` has, if it exists.
+    pub fn pane_count(&self, idx: usize) -> Option<usize> {
+        Some(self.windows.get(&idx)?.layout.panes().len())
+    }
+
+    /// The name of window `idx`, if it exists.
+    pub fn window_name(&self, idx: usize) -> Option<&str> {
+        Some(&self.windows.get(&idx)?.name)
+    }
+
+    /// The panes of window `idx` in layout order, numbered from the pane base
+    /// index, with whether each is the window's active pane.
+    pub fn window_panes(&self, idx: usize) -> Vec<(usize, &P, bool)> {
+        let window = match self.windows.get(&idx) {
+            Some(window) => window,
+            None => return Vec::new(),
+        };
+        let panes = window.layout.panes().into_iter().enumerate();
+        panes
+            .map(|(position, pane_idx)| {
+                let number = self.pane_base_index + position;
+                (number, &self.panes[&pane_idx], pane_idx == window.active)
+            })
+            .collect()
+    }
+
+    /// Where each pane of window `idx` is drawn, in layout order, numbered from
+    /// the pane base index.
+    pub fn window_pane_rects(&self, idx: usize) -> Vec<(usize, Rect)> {
+        let window = match self.windows.get(&idx) {
+            Some(window) => window,
+            None => return Vec::new(),
+        };
+        let area = Rect::from_size(self.size);
+        let rects = pane_areas(&window.layout, area, self.pane_titles);
+        let panes = window.layout.panes().into_iter().enumerate();
+        panes
+            .filter_map(|(position, pane_idx)| {
+                let (_, rect, _) = rects.iter().find(|(idx, ..)| *idx == pane_idx)?;
+                Some((self.pane_base_index + position, *rect))
+            })
+            .collect()
+    }
+
+    /// Make the pane numbered `number` the active pane of window `idx`.
+    pub fn select_pane_number(
+        &mut self,
+        idx: usize,
+        number: usize,
+    ) -> Result<(), SessionError> {
+        let pane_idx = self.pane_by_number(idx, number)?;
+        let window = self.windows.get_mut(&idx).unwrap();
+        if window.active != pane_idx {
+            window.active = pane_idx;
+            if self.unzoom(idx) {
+                self.arrange_window(idx);
+            }
+        }
+        Ok(())
+    }
+
+    /// The index of the pane numbered `number` in window `idx`.
+    fn pane_by_number(&self, idx: usize, number: usize) -> Result<usize, SessionError> {
+        let window = self
+            .windows
+            .get(&idx)
+            .ok_or(SessionError::NoSuchWindow(idx))?;
+        let position = number.checked_sub(self.pane_base_index);
+        let pane =
+            position.and_then(|position| window.layout.panes().get(position).copied());
+        pane.ok_or(SessionError::NoSuchPane(number))
+    }
+
+    /// The windows, in order of creation, with their active panes.
+    pub fn windows(&self) -> impl Iterator<Item = (usize, &P)> {
+        self.windows
+            .iter()
+            .map(move |(idx, window)| (*idx, &self.panes[&window.active]))
+    }
+
+    /// The active pane of the selected window.
+    pub fn active_pane(&self) -> Option<&P> {
+        let window = self.windows.get(&self.selected_window?)?;
+        self.panes.get(&window.active)
+    }
+
+    /// The number of the active pane of the selected window: its position in
+    /// the window's layout, counting from the pane base index.
+    pub fn active_pane_number(&self) -> Option<usize> {
+        let window = self.windows.get(&self.selected_window?)?;
+        let panes = window.layout.panes();
+        let position = panes.iter().position(|pane| *pane == window.active)?;
+        Some(self.pane_base_index + position)
+    }
+
+    /// The active pane of the selected window.
+    pub fn active_pane_mut(&mut self) -> Option<&mut P> {
+        let window = self.windows.get(&self.selected_window?)?;
+        self.panes.get_mut(&window.active)
+    }
+
+    /// How many bytes of output the panes now in window `idx` have read.
+    pub fn window_bytes_read(&self, idx: usize) -> Option<u64> {
+        let window = self.windows.get(&idx)?;
+        let panes = window.layout.panes().into_iter();
+        Some(panes.map(|pane| self.panes[&pane].bytes_read()).sum())
+    }
+
+    /// Where the active pane of the selected window is drawn.
+    pub fn active_rect(&self) -> Option<Rect> {
+        let window = self.windows.get(&self.selected_window?)?;
+        let area = Rect::from_size(self.size);
+        pane_areas(&window.shown_layout(), area, self.pane_titles)
+            .into_iter()
+            .find(|(idx, _, _)| *idx == window.active)
+            .map(|(_, rect, _)| rect)
+    }
+
+    /// The windows whose panes have rung the bell since the last call. Those
+    /// not selected are flagged for it until they are.
+    pub fn take_bells(&mut self) -> Vec<usize> {
+        let mut rung: BTreeSet<_> = BTreeSet::new();
+        for (pane_idx, pane) in &mut self.panes {
+            if pane.take_bell() {
+                rung.insert(*pane_idx);
+            }
+        }
+        let selected = self.selected_window;
+        self.windows
+            .iter_mut()
+            .filter(|(_, window)| rung.iter().any(|idx| window.layout.contains(*idx)))
+            .map(|(idx, window)| {
+                window.bell |= Some(*idx) != selected;
+                *idx
+            })
+            .collect()
+    }
+
+    /// The flags of window `idx`, if there is one.
+    pub fn window_flags(&self, idx: usize) -> Option<WindowFlags> {
+        let window = self.windows.get(&idx)?;
+        Some(WindowFlags {
+            current: Some(idx) == self.selected_window,
+            last: Some(idx) == self.last_selected,
+            activity: window.activity,
+            bell: window.bell,
+            zoomed: window.zoomed,
+        })
+    }
+
+    /// The panes of the selected window, with where each is drawn, in layout
+    /// order.
+    pub fn pane_rects(&self) -> Vec<(usize, Rect)> {
+        let window = match self.selected_window.and_then(|idx| self.windows.get(&idx)) {
+            Some(window) => window,
+            None => return Vec::new(),
+        };
+        let area = Rect::from_size(self.size);
+        pane_areas(&window.shown_layout(), area, self.pane_titles)
+            .into_iter()
+            .map(|(idx, rect, _)| (idx, rect))
+            .collect()
+    }
+
+    /// Mark the whole of the selected window to be redrawn, e.g. after something
+    /// else was drawn over it.
+    ///
+    /// The cursor style is sent again too, in case the outer terminal is a new
+    /// one.
+    pub fn mark_dirty(&mut self) {
+        self.drawn_cursor_style = None;
+        let window = match self.selected_window {
+            Some(idx) => self.windows.get_mut(&idx).unwrap(),
+            None => return,
+        };
+        window.drawn_borders = None;
+        for pane in window.layout.panes() {
+            self.panes.get_mut(&pane).unwrap().mark_dirty();
+        }
+    }
+
+    /// Whether `col`, `row` is on a line between panes of the selected window.
+    pub fn is_separator(&self, col: u16, row: u16) -> bool {
+        let window = match self.selected_window {
+            Some(idx) => &self.windows[&idx],
+            None => return false,
+        };
+        let arrangement = window.shown_layout().arrange(Rect::from_size(self.size));
+        arrangement.separators.iter().any(|(_, rect)| {
+            (rect.col..rect.col + rect.cols).contains(&col)
+                && (rect.row..rect.row + rect.rows).contains(&row)
+        })
+    }
+
+    /// The index of the selected window.
+    pub fn selected_window_idx(&self) -> Option<usize> {
+        self.selected_window
+    }
+
+    /// The lowest window index.
+    pub fn first_window_idx(&self) -> Option<usize> {
+        self.windows.keys().next().copied()
+    }
+
+    /// The highest window index.
+    pub fn last_window_idx(&self) -> Option<usize> {
+        self.windows.keys().next_back().copied()
+    }
+
+    /// The index of the window selected before the selected window, if it is
+    /// still open.
+    pub fn last_selected_window_idx(&self) -> Option<usize> {
+        self.last_selected
+    }
+
+    /// The index of the window after the selected window.
+    pub fn next_window_idx(&self) -> Option<usize> {
+        let selected = self.selected_window?;
+        self.windows
+            .range(selected + 1..)
+            .next()
+            .map(|(idx, _)| *idx)
+    }
+
+    /// The index of the window before the selected window.
+    pub fn prev_window_idx(&self) -> Option<usize> {
+        let selected = self.selected_window?;
+        self.windows
+            .range(..selected)
+            .next_back()
+            .map(|(idx, _)| *idx)
+    }
+
+    /// Forward input to the active pane of the selected window, or to all its
+    /// panes if they are synchronized.
+    pub fn receive_stdin(&mut self, data: &[u8]) -> Result<(), SessionError> {
+        let idx = self.selected_window.ok_or(SessionError::NoSelectedWindow)?;
+        let window = &self.windows[&idx];
+        let panes = if window.synchronize {
+            window.layout.panes()
+        } else {
+            vec![window.active]
+        };
+        for pane in &panes {
+            self.panes.get_mut(pane).unwrap().receive_stdin(data)?;
+        }
+        // Nothing typed into a private pane is recorded.
+        let private = panes.iter().any(|pane| self.panes[pane].is_private());
+        let window = self.windows.get_mut(&idx).unwrap();
+        if let Some(log) = window.input_log.as_mut().filter(|_| !private) {
+            log.record(data);
+        }
+        Ok(())
+    }
+
+    /// Paste `text` into the selected window as `receive_stdin` does, its lines
+    /// entered as if typed, and bracketed if the active pane asked for it.
+    pub fn paste(&mut self, text: &str) -> Result<(), SessionError> {
+        // The end of the bracket is taken out of the text, as it would end
+        // the paste early.
+        let text = text.replace(PASTE_END, "").replace('\n', "\r");
+        let bracketed = self
+            .active_pane()
+            .is_some_and(|pane| pane.bracketed_paste());
+        let data = match bracketed {
+            true => format!("{}{}{}", PASTE_START, text, PASTE_END),
+            false => text,
+        };
+        self.receive_stdin(data.as_bytes())
+    }
+
+    /// Choose whether input to the selected window goes to all its panes.
+    pub fn set_synchronize_panes(
+        &mut self,
+        synchronize: bool,
+    ) -> Result<(), SessionError> {
+        let idx = self.selected_window.ok_or(SessionError::NoSelectedWindow)?;
+        self.windows.get_mut(&idx).unwrap().synchronize = synchronize;
+        Ok(())
+    }
+
+    /// Whether input to window `idx` goes to all its panes.
+    pub fn is_synchronized(&self, idx: usize) -> bool {
+        self.windows
+            .get(&idx)
+            .is_some_and(|window| window.synchronize)
+    }
+
+    /// Handle an update from the PTY of one of the panes.
+    ///
+    /// When the last pane of the selected window exits, the next window is
+    /// selected, or failing that, the previous one.
+    pub fn pty_update(&mut self, update: SessionPtyUpdate) -> Result<(), SessionError> {
+        match update.data {
+            PtyUpdate::Byte(byte) => {
+                if let Some(pane) = self.panes.get_mut(&update.pane_idx) {
+                    pane.pty_update(byte);
+                }
+                self.note_activity(update.pane_idx);
+            }
+            PtyUpdate::Exited { status } => {
+                let pane_idx = update.pane_idx;
+                if let Some(status) = status {
+                    debug!("pane {} {}", pane_idx, status);
+                    self.last_exit_status = Some(status);
+                    if let Some(pane) = self.panes.get_mut(&pane_idx) {
+                        pane.set_exit_status(status);
+                    }
+                }
+                let killed = self.hung_up.remove(&pane_idx);
+                let remain = !killed && self.remains_on_exit(pane_idx);
+                let kept = remain
+                    && self
+                        .panes
+                        .get_mut(&pane_idx)
+                        .is_some_and(|pane| pane.keep_exited());
+                if !kept {
+                    self.close_pane(pane_idx);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Flag the window of pane `pane_idx` for activity, unless it is selected.
+    fn note_activity(&mut self, pane_idx: usize) {
+        let selected = self.selected_window.and_then(|idx| self.windows.get(&idx));
+        if selected.is_some_and(|window| window.layout.contains(pane_idx)) {
+            return;
+        }
+        let mut windows = self.windows.iter_mut();
+        if let Some((idx, window)) =
+            windows.find(|(_, window)| window.layout.contains(pane_idx))
+        {
+            if !window.activity {
+                window.activity = true;
+                self.new_activity.insert(*idx);
+            }
+        }
+    }
+
+    /// The windows flagged for activity since the last call, which are still
+    /// open.
+    pub fn take_activity(&mut self) -> Vec<usize> {
+        let flagged = std::mem::take(&mut self.new_activity).into_iter();
+        flagged
+            .filter(|idx| self.windows.contains_key(idx))
+            .collect()
+    }
+
+    /// Whether pane `pane_idx` is to be kept when its process exits.
+    fn remains_on_exit(&self, pane_idx: usize) -> bool {
+        self.windows
+            .values()
+            .find(|window| window.layout.contains(pane_idx))
+            .and_then(|window| window.remain_on_exit)
+            .unwrap_or(self.remain_on_exit)
+    }
+
+    /// Resize the session, and with it the panes of the selected window.
+    pub fn resize(&mut self, size: Winsize) -> Result<(), SessionError> {
+        self.size = size;
+        if let Some(idx) = self.selected_window {
+            self.arrange_window(idx);
+        }
+        Ok(())
+    }
+
+    /// Draw the selected window to `output`.
+    ///
+    /// The outer terminal's cursor style follows the active pane, subject to the
+    /// `CursorBlink` setting.
+    pub fn redraw<T: Write>(&mut self, output: &mut T) -> Result<(), SessionError> {
+        let idx = self.selected_window.ok_or(SessionError::NoSelectedWindow)?;
+        self.redraw_window(idx, output)?;
+        let active = self.windows[&idx].active;
+        let pane = &self.panes[&active];
+        let mut style = pane.cursor_style();
+        match self.cursor_blink {
+            CursorBlink::Application => (),
+            CursorBlink::On => style.blinking = true,
+            CursorBlink::Off => style.blinking = false,
+        }
+        if self.drawn_cursor_style != Some(style) {
+            output.write_all(cursor_style_sequence(style).as_bytes())?;
+            self.drawn_cursor_style = Some(style);
+        }
+        Ok(())
+    }
+
+    /// Whether window `idx` has changed since it was last drawn: any of its
+    /// panes, the borders, or which pane is active.
+    pub fn is_window_dirty(&self, idx: usize) -> bool {
+        let window = match self.windows.get(&idx) {
+            Some(window) => window,
+            None => return false,
+        };
+        let drawn_active = window.drawn_borders.as_ref().map(|(active, _)| *active);
+        drawn_active != Some(window.active)
+            || window
+                .shown_layout()
+                .panes()
+                .iter()
+                .any(|pane| self.panes[pane].is_dirty())
+    }
+
+    /// Draw window `idx` to `output`, as `redraw` draws the selected window
+    /// but leaving the outer terminal's cursor style alone.
+    ///
+    /// The window is drawn as it was arranged, and only what changed since it
+    /// was last drawn is, so every client showing it is to be sent the output.
+    pub fn redraw_window<T: Write>(
+        &mut self,
+        idx: usize,
+        output: &mut T,
+    ) -> Result<(), SessionError> {
+        let window = self
+            .windows
+            .get_mut(&idx)
+            .ok_or(SessionError::NoSuchWindow(idx))?;
+        let area = Rect::from_size(self.size);
+        let layout = window.shown_layout();
+        let areas = pane_areas(&layout, area, self.pane_titles);
+        let panes = &self.panes;
+        let titles: Vec<_> = areas
+            .iter()
+            .filter(|(_, _, title)| title.is_some())
+            .map(|(idx, _, _)| panes[idx].title().map(String::from))
+            .collect();
+        let borders = (window.active, titles);
+        if window.drawn_borders.as_ref() != Some(&borders) {
+            let arrangement = layout.arrange(area);
+            let (_, active) = arrangement
+                .panes
+                .iter()
+                .find(|(idx, _)| *idx == window.active)
+                .unwrap();
+            let styles = &self.border_styles;
+            draw_borders(output, &arrangement.separators, *active, styles)?;
+            let title_rows = areas
+                .iter()
+                .filter_map(|(idx, _, title)| Some((*idx, (*title)?)));
+            for ((idx, row), title) in title_rows.zip(&borders.1) {
+                let title = title.as_deref().unwrap_or("");
+                let style = match idx == window.active {
+                    true => &styles.active,
+                    false => &styles.border,
+                };
+                draw_title(output, row, title, style)?;
+            }
+            window.drawn_borders = Some(borders);
+        }
+        // The active pane goes last, to leave the cursor in it.
+        let active = window.active;
+        for pane in layout.panes() {
+            if pane != active {
+                self.panes.get_mut(&pane).unwrap().redraw(output);
+            }
+        }
+        self.panes.get_mut(&active).unwrap().redraw(output);
+        Ok(())
+    }
+
+    /// The shell new panes run.
+    fn shell(&self) -> String {
+        self.default_shell.clone().unwrap_or_else(util::get_shell)
+    }
+
+    /// The name of a window running `command`, or the default command or the
+    /// shell if `None`.
+    fn command_name(&self, command: Option<&str>) -> String {
+        match command.or(self.default_command.as_deref()) {
+            Some(command) => command_name(command),
+            None => command_name(&self.shell()),
+        }
+    }
+
+    /// Start a pane running `command` with the shell, or the default command
+    /// or the shell itself if `None`, with the variables in `window_env` set
+    /// over the session's.
+    fn spawn_pane(
+        &mut self,
+        size: Winsize,
+        term: Option<&str>,
+        window_env: &[(String, String)],
+        command: Option<&str>,
+        cwd: Option<&Path>,
+    ) -> Result<(usize, PaneUpdates), SessionError> {
+        let mut env = self.environment.clone();
+        if let Some(term) = term {
+            env.set("TERM", term);
+        }
+        for (name, value) in window_env {
+            env.set(name, value);
+        }
+        let args = match command.or(self.default_command.as_deref()) {
+            Some(command) => vec!["-c", command],
+            None => Vec::new(),
+        };
+        let (mut pane, updates) = P::new(&self.shell(), &args, &env, cwd, size)?;
+        pane.set_scale_line_attrs(self.scale_line_attrs);
+        pane.set_text_blink(self.text_blink);
+        pane.set_line_log(self.line_log);
+        let pane_idx = self.insert_pane(pane);
+        self.launches.insert(pane_idx, command.map(String::from));
+        Ok((pane_idx, PaneUpdates { pane_idx, updates }))
+    }
+
+    fn insert_pane(&mut self, pane: P) -> usize {
+        let pane_idx = self.ids.pane.fetch_add(1, Ordering::Relaxed);
+        self.panes.insert(pane_idx, pane);
+        pane_idx
+    }
+
+    /// Add a window called `name` with pane `pane_idx` as its only pane.
+    fn insert_window(
+        &mut self,
+        name: String,
+        pane_idx: usize,
+        term: Option<String>,
+    ) -> usize {
+        let window_idx = self.next_window_idx;
+        self.next_window_idx += 1;
+        self.windows.insert(
+            window_idx,
+            Window {
+                id: self.ids.window.fetch_add(1, Ordering::Relaxed),
+                name,
+                layout: Layout::Pane(pane_idx),
+                active: pane_idx,
+                preset: None,
+                drawn_borders: None,
+                arranged: None,
+                synchronize: false,
+                zoomed: false,
+                term,
+                environment: Vec::new(),
+                remain_on_exit: None,
+                input_log: self
+                    .record_input
+                    .then(|| InputLog::new(InputLog::DEFAULT_CAPACITY)),
+                activity: false,
+                bell: false,
+            },
+        );
+        debug!("new window {}", window_idx);
+        window_idx
+    }
+
+    /// Fit the panes of window `idx` to the session's size.
+    fn arrange_window(&mut self, idx: usize) {
+        let window = self.windows.get_mut(&idx).unwrap();
+        let area = Rect::from_size(self.size);
+        let layout = window.shown_layout();
+        for (pane, rect, _) in pane_areas(&layout, area, self.pane_titles) {
+            let pane = self.panes.get_mut(&pane).unwrap();
+            let mut size = rect.size();
+            size.ws_col = size.ws_col.max(1);
+            size.ws_row = size.ws_row.max(1);
+            pane.resize(size);
+            pane.set_origin(rect.col, rect.row);
+            // Line attributes apply to whole lines of the terminal, so a pane
+            // sharing its lines has to space out its characters instead.
+            pane.set_scale_line_attrs(
+                self.scale_line_attrs || rect.cols < self.size.ws_col,
+            );
+        }
+        window.drawn_borders = None;
+        window.arranged = Some(self.size);
+    }
+
+    fn close_pane(&mut self, pane_idx: usize) {
+        let status = match self.panes.remove(&pane_idx) {
+            Some(pane) => pane.exit_status().map(ExitStatus::code),
+            None => return,
+        };
+        self.launches.remove(&pane_idx);
+        debug!("closed pane {}", pane_idx);
+        let window_idx = match self
+            .windows
+            .iter()
+            .find(|(_, window)| window.layout.contains(pane_idx))
+        {
+            Some((idx, _)) => *idx,
+            None => return,
+        };
+        if !self.take_pane(window_idx, pane_idx) {
+            self.close_window(window_idx, status);
+            return;
+        }
+        if self.selected_window == Some(window_idx) {
+            self.arrange_window(window_idx);
+        }
+    }
+
+    /// Remove pane `pane_idx` from the layout of window `window_idx`, making
+    /// another pane active if it was, without arranging the rest.
+    ///
+    /// Returns `false`, leaving the window alone, if it was the only pane.
+    fn take_pane(&mut self, window_idx: usize, pane_idx: usize) -> bool {
+        let window = self.windows.get_mut(&window_idx).unwrap();
+        let panes = window.layout.panes();
+        if !window.layout.remove(pane_idx) {
+            return false;
+        }
+        self.unzoom(window_idx);
+        let window = self.windows.get_mut(&window_idx).unwrap();
+        if window.active == pane_idx {
+            // Prefer the pane before the one taken.
+            let position = panes.iter().position(|idx| *idx == pane_idx).unwrap();
+            let remaining = window.layout.panes();
+            window.active =
+                remaining[position.saturating_sub(1).min(remaining.len() - 1)];
+        }
+        true
+    }
+
+    /// Show every pane of window `idx` again if its active pane is zoomed,
+    /// leaving them to be arranged; whether it was.
+    fn unzoom(&mut self, idx: usize) -> bool {
+        let window = self.windows.get_mut(&idx).unwrap();
+        if !std::mem::take(&mut window.zoomed) {
+            return false;
+        }
+        // The panes hidden were not drawn meanwhile.
+        for pane in window.layout.panes() {
+            if let Some(pane) = self.panes.get_mut(&pane) {
+                pane.mark_dirty();
+            }
+        }
+        debug!("unzoomed window {}", idx);
+        true
+    }
+
+    fn close_window(&mut self, idx: usize, status: Option<i32>) {
+        let window = match self.remove_window(idx, status) {
+            Some(window) => window,
+            None => return,
+        };
+        for pane in window.layout.panes() {
+            self.panes.remove(&pane);
+            self.launches.remove(&pane);
+        }
+    }
+
+    /// Remove window `idx`, recording that it closed and selecting another in
+    /// its place, but leaving its panes.
+    fn remove_window(&mut self, idx: usize, status: Option<i32>) -> Option<Window> {
+        let window = self.windows.remove(&idx)?;
+        self.closed_windows.push(ClosedWindow {
+            idx,
+            name: window.name.clone(),
+            status,
+        });
+        debug!("closed window {}", idx);
+        self.deselect_window(idx);
+        Some(window)
+    }
+
+    /// Select another window in place of window `idx`, which is gone: the next,
+    /// or failing that, the previous.
+    fn deselect_window(&mut self, idx: usize) {
+        if self.last_selected == Some(idx) {
+            self.last_selected = None;
+        }
+        if self.selected_window == Some(idx) {
+            self.selected_window = None;
+            // Whatever the window's programs left the outer terminal in, e.g. a
+            // bar cursor from an editor which crashed, is put right explicitly.
+            self.drawn_cursor_style = None;
+            let neighbour = self
+                .windows
+                .range(idx + 1..)
+                .next()
+                .or_else(|| self.windows.range(..idx).next_back())
+                .map(|(idx, _)| *idx);
+            if let Some(neighbour) = neighbour {
+                self.select_window(neighbour);
+            }
+        }
+    }
+}
+
+/// The name of the program `command` runs, without its directory.
+fn command_name(command: &str) -> String {
+    let name = command.split_whitespace().next().unwrap_or(command);
+    name.rsplit('/').next().unwrap_or(name).to_string()
+}
+
+/// `term` as owned, if it has a terminfo entry.
+fn check_term(term: Option<&str>) -> Result<Option<String>, SessionError> {
+    match term {
+        Some(term) if !terminfo::exists(term) => {
+            Err(SessionError::UnknownTerm(term.to_string()))
+        }
+        term => Ok(term.map(String::from)),
+    }
+}
+
+/// The SGR sequences the lines between panes are drawn with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BorderStyles {
+    pub border: String,
+    /// Those of the lines around the active pane, and of its title.
+    pub active: String,
+}
+
+impl Default for BorderStyles {
+    fn default() -> BorderStyles {
+        BorderStyles {
+            border: "\x1b[0m".to_string(),
+            active: "\x1b[32m".to_string(),
+        }
+    }
+}
+
+/// The sequences around pasted text, if the pane asked for them.
+const PASTE_START: &str = "\x1b[200~";
+const PASTE_END: &str = "\x1b[201~";
+
+/// Where the panes of `layout` go in `area`, each with the row above it for its
+/// title if `titles` and the window has more than one pane.
+///
+/// Panes only one row high have no title.
+fn pane_areas(
+    layout: &Layout,
+    area: Rect,
+    titles: bool,
+) -> Vec<(usize, Rect, Option<Rect>)> {
+    let titles = titles && matches!(layout, Layout::Split { .. });
+    layout
+        .arrange(area)
+        .panes
+        .into_iter()
+        .map(|(idx, rect)| {
+            if !titles || rect.rows < 2 {
+                return (idx, rect, None);
+            }
+            let title = Rect { rows: 1, ..rect };
+            let rect = Rect {
+                row: rect.row + 1,
+                rows: rect.rows - 1,
+                ..rect
+            };
+            (idx, rect, Some(title))
+        })
+        .collect()
+}
+
+/// The box-drawing character for each cell of `separators`, by row and column,
+/// joining lines which meet.
+fn border_cells(separators: &[(Direction, Rect)]) -> BTreeMap<(u16, u16), char> {
+    let mut lines = BTreeMap::new();
+    for (direction, rect) in separators {
+        for row in rect.row..rect.row + rect.rows {
+            for col in rect.col..rect.col + rect.cols {
+                lines.insert((row, col), *direction);
+            }
+        }
+    }
+    let is_line = |row: Option<u16>, col: Option<u16>| match (row, col) {
+        (Some(row), Some(col)) => lines.contains_key(&(row, col)),
+        _ => false,
+    };
+    lines
+        .iter()
+        .map(|(&(row, col), direction)| {
+            let (up, down, left, right) = match direction {
+                Direction::Horizontal => (
+                    true,
+                    true,
+                    is_line(Some(row), col.checked_sub(1)),
+                    is_line(Some(row), col.checked_add(1)),
+                ),
+                Direction::Vertical => (
+                    is_line(row.checked_sub(1), Some(col)),
+                    is_line(row.checked_add(1), Some(col)),
+                    true,
+                    true,
+                ),
+            };
+            let glyph = match (up, down, left, right) {
+                (true, true, true, true) => '\u{253c}',
+                (true, true, false, true) => '\u{251c}',
+                (true, true, true, false) => '\u{2524}',
+                (true, true, false, false) => '\u{2502}',
+                (false, true, true, true) => '\u{252c}',
+                (true, false, true, true) => '\u{2534}',
+                _ => '\u{2500}',
+            };
+            ((row, col), glyph)
+        })
+        .collect()
+}
+
+/// Draw the lines between panes in `styles`, those around `active` in its
+/// style.
+fn draw_borders<T: Write>(
+    output: &mut T,
+    separators: &[(Direction, Rect)],
+    active: Rect,
+    styles: &BorderStyles,
+) -> io::Result<()> {
+    if separators.is_empty() {
+        return Ok(());
+    }
+    output.write_all(styles.border.as_bytes())?;
+    let mut cursor = None;
+    let mut highlighted = false;
+    for ((row, col), glyph) in border_cells(separators) {
+        if cursor != Some((row, col)) {
+            write!(output, "\x1b[{};{}H", row + 1, col + 1)?;
+        }
+        let highlight = (active.row.saturating_sub(1)..=active.row + active.rows)
+            .contains(&row)
+            && (active.col.saturating_sub(1)..=active.col + active.cols).contains(&col);
+        if highlight != highlighted {
+            let style = if highlight {
+                &styles.active
+            } else {
+                &styles.border
+            };
+            output.write_all(style.as_bytes())?;
+            highlighted = highlight;
+        }
+        write!(output, "{}", width::box_char(glyph))?;
+        cursor = Some((row, col + 1));
+    }
+    output.write_all(b"\x1b[0m")
+}
+
+/// Draw `title` across the row `rect` in `style`.
+fn draw_title<T: Write>(
+    output: &mut T,
+    rect: Rect,
+    title: &str,
+    style: &str,
+) -> io::Result<()> {
+    let cols = rect.cols as usize;
+    let rule = width::box_char('\u{2500}');
+    let mut line = String::from(rule);
+    if !title.is_empty() {
+        line.push(' ');
+        line.extend(title.chars().filter(|c| !c.is_control()));
+        line.push(' ');
+    }
+    let mut line = width::truncate(&line, cols).to_string();
+    line.push_str(&rule.to_string().repeat(cols - width::str_width(&line)));
+    write!(
+        output,
+        "\x1b[0m\x1b[{};{}H{}{}\x1b[0m",
+        rect.row + 1,
+        rect.col + 1,
+        style,
+        line
+    )
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::tests::WINSZ;
+
+    use std::{cell::Cell, mem, rc::Rc};
+
+    use futures::channel::mpsc::{self, Sender};
+
+    pub struct MockPane {
+        stdin_channel: (Sender<u8>, Receiver<u8>),
+        pty_channel: (Sender<u8>, Receiver<u8>),
+        resize_channel: (Sender<Winsize>, Receiver<Winsize>),
+        dirty_channel: (Sender<bool>, Receiver<bool>),
+        hangup_channel: (Sender<bool>, Receiver<bool>),
+        killed: Rc<Cell<bool>>,
+        cursor_style: CursorStyle,
+        bracketed_paste: bool,
+        mouse_reporting: Option<MouseEncoding>,
+        view_offset: usize,
+        theme: Option<Theme>,
+        slow_print: Option<u32>,
+        private: bool,
+        title: Option<String>,
+        env: Environment,
+        /// The command the pane runs, and its arguments.
+        command: Vec<String>,
+        cwd: Option<PathBuf>,
+        /// The lines to be taken, if they are kept.
+        lines: Option<Vec<String>>,
+        bytes_read: u64,
+        dead: bool,
+        exit_status: Option<ExitStatus>,
+        bell: bool,
+    }
+
+    impl SessionPane for MockPane {
+        fn new(
+            command: &str,
+            args: &[&str],
+            env: &Environment,
+            cwd: Option<&Path>,
+            _: Winsize,
+        ) -> Result<(MockPane, Receiver<PtyUpdate>), PtyError> {
+            let (_, recv) = mpsc::channel(10);
+            let stdin_channel = mpsc::channel(100);
+            let pty_channel = mpsc::channel(10);
+            let resize_channel = mpsc::channel(10);
+            let dirty_channel = mpsc::channel(10);
+            let hangup_channel = mpsc::channel(1);
+            Ok((
+                MockPane {
+                    stdin_channel,
+                    pty_channel,
+                    resize_channel,
+                    dirty_channel,
+                    hangup_channel,
+                    killed: Rc::default(),
+                    cursor_style: CursorStyle::default(),
+                    bracketed_paste: false,
+                    mouse_reporting: None,
+                    view_offset: 0,
+                    theme: None,
+                    slow_print: None,
+                    private: false,
+                    title: None,
+                    env: env.clone(),
+                    command: std::iter::once(command)
+                        .chain(args.iter().copied())
+                        .map(String::from)
+                        .collect(),
+                    cwd: cwd.map(PathBuf::from),
+                    lines: None,
+                    bytes_read: 0,
+                    dead: false,
+                    exit_status: None,
+                    bell: false,
+                },
+                recv,
+            ))
+        }
+
+        fn with_applet(
+            applet: Box<dyn Applet>,
+            size: Winsize,
+        ) -> Result<(MockPane, Receiver<PtyUpdate>), PtyError> {
+            MockPane::new(applet.name(), &[], &Environment::new(), None, size)
+        }
+
+        fn hangup(&self) {
+            self.hangup_channel.0.clone().try_send(true).unwrap();
+        }
+
+        fn kill(&self) {
+            self.killed.set(true);
+        }
+
+        fn is_reading_secret(&self) -> bool {
+            false
+        }
+
+        fn keep_exited(&mut self) -> bool {
+            self.dead = true;
+            true
+        }
+
+        fn is_dead(&self) -> bool {
+            self.dead
+        }
+
+        fn set_exit_status(&mut self, status: ExitStatus) {
+            self.exit_status = Some(status);
+        }
+
+        fn exit_status(&self) -> Option<ExitStatus> {
+            self.exit_status
+        }
+
+        fn receive_stdin(&mut self, data: &[u8]) -> Result<(), io::Error> {
+            for byte in data {
+                self.stdin_channel.0.clone().try_send(*byte).unwrap();
+            }
+            Ok(())
+        }
+
+        fn pty_update(&mut self, byte: u8) {
+            self.bytes_read += 1;
+            self.pty_channel.0.try_send(byte).unwrap();
+        }
+
+        fn bytes_read(&self) -> u64 {
+            self.bytes_read
+        }
+
+        fn take_bell(&mut self) -> bool {
+            mem::take(&mut self.bell)
+        }
+
+        fn resize(&mut self, size: Winsize) {
+            self.resize_channel.0.try_send(size).unwrap();
+        }
+
+        fn set_origin(&mut self, _: u16, _: u16) {}
+
+        fn mark_dirty(&mut self) {
+            self.dirty_channel.0.try_send(true).unwrap();
+        }
+
+        fn redraw<T: Write>(&mut self, file: &mut T) {
+            file.write_all(b"hello").unwrap();
+            file.flush().unwrap();
+        }
+
+        fn is_dirty(&self) -> bool {
+            false
+        }
+
+        fn contents(&mut self) -> Vec<u8> {
+            b"hello".to_vec()
+        }
+
+        fn cursor_style(&self) -> CursorStyle {
+            self.cursor_style
+        }
+
+        fn bracketed_paste(&self) -> bool {
+            self.bracketed_paste
+        }
+
+        fn mouse_reporting(&self) -> Option<MouseEncoding> {
+            self.mouse_reporting
+        }
+
+        fn view_offset(&self) -> usize {
+            self.view_offset
+        }
+
+        fn scroll_view(&mut self, rows: isize) {
+            self.view_offset = self.view_offset.saturating_add_signed(rows);
+        }
+
+        fn view_top(&self) -> usize {
+            0
+        }
+
+        fn show_line(&mut self, _: usize) {}
+
+        fn find(&self, _: &Search, _: usize, _: bool) -> Option<usize> {
+            None
+        }
+
+        fn set_highlight(&mut self, _: Option<Search>) {}
+
+        fn set_scale_line_attrs(&mut self, _: bool) {}
+
+        fn set_theme(&mut self, theme: Option<Theme>) {
+            self.theme = theme;
+        }
+
+        fn theme(&self) -> Option<&Theme> {
+            self.theme.as_ref()
+        }
+
+        fn set_text_blink(&mut self, _: TextBlink) {}
+
+        fn set_blink_phase(&mut self, _: bool) {}
+
+        fn set_slow_print(&mut self, lines_per_sec: Option<u32>) {
+            self.slow_print = lines_per_sec;
+        }
+
+        fn slow_print(&self) -> Option<u32> {
+            self.slow_print
+        }
+
+        fn advance_slow_print(&mut self, _: Instant) {}
+
+        fn tick(&mut self) {}
+
+        fn set_private(&mut self, private: bool) {
+            self.private = private;
+        }
+
+        fn is_private(&self) -> bool {
+            self.private
+        }
+
+        fn title(&self) -> Option<&str> {
+            self.title.as_deref()
+        }
+
+        fn cwd(&self) -> Option<PathBuf> {
+            self.cwd.clone()
+        }
+
+        fn preview(&self) -> String {
+            String::new()
+        }
+
+        fn set_line_log(&mut self, log: bool) {
+            self.lines = if log { Some(Vec::new()) } else { None };
+        }
+
+        fn take_lines(&mut self) -> Vec<String> {
+            self.lines.as_mut().map(mem::take).unwrap_or_default()
+        }
+
+        fn cursor_line(&self) -> String {
+            String::new()
+        }
+    }
+
+    /// The active pane of window `idx`.
+    fn active_pane(session: &mut Session<MockPane>, idx: usize) -> &mut MockPane {
+        let pane = session.windows[&idx].active;
+        session.panes.get_mut(&pane).unwrap()
+    }
+
+    #[test]
+    fn session_report_unselected() {
+        let mut session: Session<MockPane> = Session::new(WINSZ);
+        match session.redraw(&mut io::sink()).unwrap_err() {
+            SessionError::NoSelectedWindow => (),
+            _ => panic!("wrong error when redrawing nonexistent window"),
+        };
+    }
+
+    #[test]
+    fn session_cursor_blink() {
+        let mut session: Session<MockPane> = Session::new(WINSZ);
+        let (first, _) = session.new_window().unwrap();
+        let (second, _) = session.new_window().unwrap();
+        active_pane(&mut session, second).cursor_style = CursorStyle {
+            shape: CursorShape::Beam,
+            blinking: true,
+        };
+        session.select_window(first);
+
+        let mut out = Vec::new();
+        session.redraw(&mut out).unwrap();
+        assert_eq!(out, b"hello\x1b[2 q\x1b[?12l");
+        out.clear();
+        session.redraw(&mut out).unwrap();
+        assert_eq!(out, b"hello", "resent unchanged cursor style");
+        session.mark_dirty();
+        out.clear();
+        session.redraw(&mut out).unwrap();
+        assert_eq!(out, b"hello\x1b[2 q\x1b[?12l");
+
+        session.select_window(second);
+        out.clear();
+        session.redraw(&mut out).unwrap();
+        assert_eq!(out, b"hello\x1b[5 q\x1b[?12h");
+
+        session.set_cursor_blink(CursorBlink::Off);
+        out.clear();
+        session.redraw(&mut out).unwrap();
+        assert_eq!(out, b"hello\x1b[6 q\x1b[?12l");
+
+        session.set_cursor_blink(CursorBlink::On);
+        session.select_window(first);
+        out.clear();
+        session.redraw(&mut out).unwrap();
+        assert_eq!(out, b"hello\x1b[1 q\x1b[?12h");
+        assert_eq!("off".parse(), Ok(CursorBlink::Off));
+        assert!("sometimes".parse::<CursorBlink>().is_err());
+    }
+
+    #[test]
+    fn session_window_relative() {
+        let mut session: Session<MockPane> = Session::new(WINSZ);
+        assert_eq!(session.windows.len(), 0);
+
+        let (first, _) = session.new_window().unwrap();
+        assert_eq!(session.windows.len(), 1);
+        session.select_window(first);
+        let first = session.first_window_idx().unwrap();
+        assert_eq!(
+            session.select_window(2475),
+            None,
+            "should not select invalid idx"
+        );
+        assert_eq!(Some(first), session.selected_window_idx());
+        assert_eq!(Some(first), session.first_window_idx());
+        assert_eq!(Some(first), session.last_window_idx());
+        assert_eq!(session.next_window_idx(), None);
+        assert_eq!(session.prev_window_idx(), None);
+
+        let (second, _) = session.new_window().unwrap();
+        assert_eq!(session.windows.len(), 2);
+        assert_ne!(first, second);
+        assert_eq!(
+            Some(first),
+            session.selected_window_idx(),
+            "selection changed when adding new window"
+        );
+        session.select_window(second);
+        assert_eq!(Some(first), session.first_window_idx(), "ordering broken");
+        assert_eq!(Some(second), session.last_window_idx(), "ordering broken");
+        assert_eq!(
+            Some(first),
+            session.prev_window_idx(),
+            "can't find first window"
+        );
+        assert_eq!(session.next_window_idx(), None);
+
+        session.select_window(first);
+        let (third, _) = session.new_window().unwrap();
+        assert_eq!(session.windows.len(), 3);
+        assert_eq!(Some(second), session.next_window_idx());
+        assert_eq!(Some(third), session.last_window_idx());
+        session.select_window(second);
+        assert_eq!(Some(first), session.prev_window_idx());
+        assert_eq!(Some(third), session.next_window_idx());
+        assert_eq!(Some(first), session.first_window_idx());
+        session.select_window(third);
+        assert_eq!(Some(second), session.prev_window_idx());
+        assert_eq!(None, session.next_window_idx());
+
+        session.select_window(second);
+        session
+            .pty_update(SessionPtyUpdate {
+                pane_idx: session.windows[&second].active,
+                data: PtyUpdate::Exited { status: None },
+            })
+            .unwrap();
+        assert_eq!(
+            Some(third),
+            session.selected_window_idx(),
+            "next younger window not selected"
+        );
+        assert_eq!(
+            Some(first),
+            session.prev_window_idx(),
+            "can't find first window"
+        );
+        session
+            .pty_update(SessionPtyUpdate {
+                pane_idx: session.windows[&first].active,
+                data: PtyUpdate::Exited { status: None },
+            })
+            .unwrap();
+        assert_eq!(Some(third), session.selected_window_idx());
+        assert_eq!(
+            Some(third),
+            session.first_window_idx(),
+            "only remaining window is not first"
+        );
+        assert_eq!(Some(third), session.last_window_idx());
+        assert_eq!(None, session.prev_window_idx());
+        session
+            .pty_update(SessionPtyUpdate {
+                pane_idx: session.windows[&third].active,
+                data: PtyUpdate::Exited { status: None },
+            })
+            .unwrap();
+        assert_eq!(session.windows.len(), 0);
+        assert_eq!(None, session.next_window_idx());
+        assert_eq!(None, session.last_window_idx());
+        assert_eq!(
+            None,
+            session.selected_window_idx(),
+            "closed window not deselected"
+        );
+    }
+
+    #[test]
+    fn session_rename_window() {
+        let mut session: Session<MockPane> = Session::new(WINSZ);
+        let (first, _) = session.new_window().unwrap();
+        let (second, _) = session.new_window().unwrap();
+        session.select_window(first);
+        assert_eq!(session.window_name(second), Some("sh"));
+        session.rename_window("logs").unwrap();
+        assert_eq!(session.window_name(first), Some("logs"));
+        assert_eq!(session.window_name(second), Some("sh"));
+        assert_eq!(session.window_name(2475), None);
+    }
+
+    #[test]
+    fn session_pane_count() {
+        let mut session: Session<MockPane> = Session::new(WINSZ);
+        let (first, _) = session.new_window().unwrap();
+        let (second, _) = session.new_window().unwrap();
+        session.select_window(first);
+        session.split_pane(Direction::Horizontal).unwrap();
+        assert_eq!(session.pane_count(first), Some(2));
+        assert_eq!(session.pane_count(second), Some(1));
+        assert_eq!(session.pane_count(2475), None);
+    }
+
+    #[test]
+    fn session_forward_stdin() {
+        let mut session: Session<MockPane> = Session::new(WINSZ);
+        let (first, _) = session.new_window().unwrap();
+        let (second, _) = session.new_window().unwrap();
+
+        session.select_window(second);
+        assert_eq!(session.selected_window_idx(), Some(second));
+        session.receive_stdin(b"Hello").unwrap();
+
+        let recv = &mut active_pane(&mut session, first).stdin_channel.1;
+        assert!(recv.try_next().is_err(), "other window received byte");
+        let recv = &mut active_pane(&mut session, second).stdin_channel.1;
+        for byte in b"Hello" {
+            assert_eq!(recv.try_next().unwrap(), Some(*byte), "failed to recv byte");
+        }
+        assert!(recv.try_next().is_err(), "recv too many bytes");
+
+        session.select_window(first);
+        session.receive_stdin(b"World").unwrap();
+
+        let recv = &mut active_pane(&mut session, first).stdin_channel.1;
+        for byte in b"World" {
+            assert_eq!(recv.try_next().unwrap(), Some(*byte), "failed to recv byte");
+        }
+        assert!(recv.try_next().is_err(), "recv too many bytes");
+        let recv = &mut active_pane(&mut session, second).stdin_channel.1;
+        assert!(recv.try_next().is_err(), "other window received byte");
+    }
+
+    #[test]
+    fn session_paste() {
+        let mut session: Session<MockPane> = Session::new(WINSZ);
+        assert!(session.paste("ls").is_err());
+        let (idx, _) = session.new_window().unwrap();
+        session.select_window(idx);
+        let received = |session: &mut Session<MockPane>| {
+            let recv = &mut active_pane(session, idx).stdin_channel.1;
+            let mut received = Vec::new();
+            while let Ok(Some(byte)) = recv.try_next() {
+                received.push(byte);
+            }
+            String::from_utf8(received).unwrap()
+        };
+        session.paste("ls\npwd\n").unwrap();
+        assert_eq!(received(&mut session), "ls\rpwd\r");
+        active_pane(&mut session, idx).bracketed_paste = true;
+        session.paste("ls\x1b[201~\n").unwrap();
+        assert_eq!(received(&mut session), "\x1b[200~ls\r\x1b[201~");
+    }
+
+    #[test]
+    fn session_report_mouse() {
+        let mut session: Session<MockPane> = Session::new(WINSZ);
+        let (idx, _) = session.new_window().unwrap();
+        session.select_window(idx);
+        let first = session.windows[&idx].active;
+        let second = session.split_pane(Direction::Horizontal).unwrap().pane_idx;
+        let arrangement = session.windows[&idx].layout.arrange(Rect::from_size(WINSZ));
+        let rect = arrangement
+            .panes
+            .iter()
+            .find(|(idx, _)| *idx == second)
+            .unwrap()
+            .1;
+        let report = MouseReport {
+            button: 16,
+            release: false,
+            col: rect.col + 2,
+            row: rect.row + 1,
+        };
+        // Nothing is reported to a process which did not ask.
+        assert!(!session.report_mouse(report).unwrap());
+        session.panes.get_mut(&second).unwrap().mouse_reporting =
+            Some(MouseEncoding::Sgr);
+        assert!(session.report_mouse(report).unwrap());
+        let mut received = Vec::new();
+        let recv = &mut session.panes.get_mut(&second).unwrap().stdin_channel.1;
+        while let Ok(Some(byte)) = recv.try_next() {
+            received.push(byte);
+        }
+        assert_eq!(received, b"\x1b[<16;3;2M");
+        let recv = &mut session.panes.get_mut(&first).unwrap().stdin_channel.1;
+        assert!(recv.try_next().is_err());
+    }
+
+    #[test]
+    fn session_synchronize_panes() {
+        let mut session: Session<MockPane> = Session::new(WINSZ);
+        assert!(session.set_synchronize_panes(true).is_err());
+        let (first, _) = session.new_window().unwrap();
+        let (second, _) = session.new_window().unwrap();
+        session.select_window(first);
+        let panes = [
+            session.windows[&first].active,
+            session.split_pane(Direction::Vertical).unwrap().pane_idx,
+            session.split_pane(Direction::Horizontal).unwrap().pane_idx,
+        ];
+        session.set_synchronize_panes(true).unwrap();
+        assert!(session.is_synchronized(first));
+        assert!(!session.is_synchronized(second));
+        session.receive_stdin(b"ls").unwrap();
+        session.set_synchronize_panes(false).unwrap();
+        session.receive_stdin(b"!").unwrap();
+
+        for (idx, pane) in panes.iter().enumerate() {
+            let recv = &mut session.panes.get_mut(pane).unwrap().stdin_channel.1;
+            let mut received = Vec::new();
+            while let Ok(Some(byte)) = recv.try_next() {
+                received.push(byte);
+            }
+            let expected: &[u8] = if idx == 2 { b"ls!" } else { b"ls" };
+            assert_eq!(received, expected);
+        }
+        let recv = &mut active_pane(&mut session, second).stdin_channel.1;
+        assert!(recv.try_next().is_err(), "other window received input");
+    }
+
+    #[test]
+    fn session_forward_pty_update() {
+        let mut session: Session<MockPane> = Session::new(WINSZ);
+        let (first, _) = session.new_window().unwrap();
+        let (second, _) = session.new_window().unwrap();
+        session.select_window(second);
+        assert_eq!(session.selected_window_idx(), Some(second));
+        session
+            .pty_update(SessionPtyUpdate {
+                pane_idx: session.windows[&first].active,
+                data: PtyUpdate::Byte(13),
+            })
+            .unwrap();
+
+        let recv = &mut active_pane(&mut session, second).pty_channel.1;
+        assert!(recv.try_next().is_err(), "other window received byte");
+        let recv = &mut active_pane(&mut session, first).pty_channel.1;
+        assert_eq!(recv.try_next().unwrap(), Some(13u8), "failed to recv byte");
+        assert!(recv.try_next().is_err(), "recv multiple bytes");
+    }
+
+    #[test]
+    fn session_window_bytes_read() {
+        let mut session: Session<MockPane> = Session::new(WINSZ);
+        let (first, _) = session.new_window().unwrap();
+        let (second, _) = session.new_window().unwrap();
+        session.select_window(first);
+        let split = session.split_pane(Direction::Horizontal).unwrap().pane_idx;
+        // Every pane of the window counts.
+        for pane_idx in [session.windows[&first].layout.panes()[0], split, split] {
+            let data = PtyUpdate::Byte(b'x');
+            session
+                .pty_update(SessionPtyUpdate { pane_idx, data })
+                .unwrap();
+        }
+        assert_eq!(session.window_bytes_read(first), Some(3));
+        assert_eq!(session.window_bytes_read(second), Some(0));
+        assert_eq!(session.window_bytes_read(second + 1), None);
+    }
+
+    #[test]
+    fn session_remain_on_exit() {
+        let mut session: Session<MockPane> = Session::new(WINSZ);
+        let exited = |session: &mut Session<MockPane>, idx: usize| {
+            let pane_idx = session.windows[&idx].active;
+            let data = PtyUpdate::Exited { status: None };
+            session
+                .pty_update(SessionPtyUpdate { pane_idx, data })
+                .unwrap();
+        };
+        let (first, _) = session.new_window().unwrap();
+        session.set_default_remain_on_exit(true);
+        let (second, _) = session.new_window().unwrap();
+        let (third, _) = session.new_window().unwrap();
+        session.select_window(third);
+        session.set_window_remain_on_exit(false).unwrap();
+
+        exited(&mut session, first);
+        assert!(active_pane(&mut session, first).is_dead());
+        exited(&mut session, third);
+        assert!(!session.windows.contains_key(&third));
+
+        // A dead pane closes when killed, and a live one even if it would
+        // otherwise be kept.
+        session.select_window(first);
+        session.kill_pane().unwrap();
+        assert!(!session.windows.contains_key(&first));
+        session.select_window(second);
+        session.kill_pane().unwrap();
+        assert!(active_pane(&mut session, second)
+            .hangup_channel
+            .1
+            .try_next()
+            .is_ok());
+        exited(&mut session, second);
+        assert_eq!(session.selected_window_idx(), None);
+    }
+
+    #[test]
+    fn session_take_bells() {
+        let mut session: Session<MockPane> = Session::new(WINSZ);
+        let (first, _) = session.new_window().unwrap();
+        let (second, _) = session.new_window().unwrap();
+        let (third, _) = session.new_window().unwrap();
+        session.select_window(second);
+        session.split_pane(Direction::Vertical).unwrap();
+        active_pane(&mut session, first).bell = true;
+        active_pane(&mut session, second).bell = true;
+        assert_eq!(session.take_bells(), [first, second]);
+        assert!(session.take_bells().is_empty());
+        active_pane(&mut session, third).bell = true;
+        assert_eq!(session.take_bells(), [third]);
+    }
+
+    #[test]
+    fn session_window_flags() {
+        let mut session: Session<MockPane> = Session::new(WINSZ);
+        let (first, _) = session.new_window().unwrap();
+        let (second, _) = session.new_window().unwrap();
+        let (third, _) = session.new_window().unwrap();
+        session.select_window(first);
+        session.select_window(second);
+        let symbols = |session: &Session<MockPane>, idx| {
+            session.window_flags(idx).unwrap().symbols()
+        };
+        assert_eq!(symbols(&session, first), "-");
+        assert_eq!(symbols(&session, second), "*");
+        assert_eq!(symbols(&session, third), "");
+        assert_eq!(session.window_flags(third + 1), None);
+
+        // Output and bells in windows not selected flag them until they are.
+        for idx in [second, third] {
+            let pane_idx = session.windows[&idx].active;
+            let data = PtyUpdate::Byte(b'x');
+            session
+                .pty_update(SessionPtyUpdate { pane_idx, data })
+                .unwrap();
+        }
+        active_pane(&mut session, first).bell = true;
+        active_pane(&mut session, second).bell = true;
+        session.take_bells();
+        assert_eq!(session.take_activity(), [third]);
+        assert!(session.take_activity().is_empty());
+        assert_eq!(symbols(&session, first), "-!");
+        assert_eq!(symbols(&session, second), "*");
+        assert_eq!(symbols(&session, third), "#");
+        session.select_window(third);
+        assert_eq!(symbols(&session, third), "*");
+        assert_eq!(symbols(&session, second), "-");
+        assert_eq!(symbols(&session, first), "!");
+    }
+
+    #[test]
+    fn session_window_flags_zoomed() {
+        let mut session: Session<MockPane> = Session::new(WINSZ);
+        let (first, _) = session.new_window().unwrap();
+        let (second, _) = session.new_window().unwrap();
+        session.select_window(first);
+        session.split_pane(Direction::Horizontal).unwrap();
+        session.toggle_zoom().unwrap();
+        let symbols = |session: &Session<MockPane>, idx| {
+            session.window_flags(idx).unwrap().symbols()
+        };
+        assert_eq!(symbols(&session, first), "*Z");
+        // Zoom stays with the window when another is selected.
+        session.select_window(second);
+        assert_eq!(symbols(&session, first), "-Z");
+        session.select_window(first);
+        session.toggle_zoom().unwrap();
+        assert_eq!(symbols(&session, first), "*");
+    }
+
+    #[test]
+    fn session_closed_windows() {
+        let mut session: Session<MockPane> = Session::new(WINSZ);
+        let (first, _) = session.new_window().unwrap();
+        let (second, _) = session.new_window().unwrap();
+        session.select_window(second);
+        session.split_pane(Direction::Vertical).unwrap();
+        let name = session.window_name(first).unwrap().to_string();
+        let pane_idx = session.windows[&first].active;
+        let status = Some(ExitStatus::Code(2));
+        let data = PtyUpdate::Exited { status };
+        session
+            .pty_update(SessionPtyUpdate { pane_idx, data })
+            .unwrap();
+        assert_eq!(session.last_exit_status(), status);
+        session.kill_window().unwrap();
+        assert_eq!(
+            session.closed_windows(),
+            [
+                ClosedWindow {
+                    idx: first,
+                    name: name.clone(),
+                    status: Some(2),
+                },
+                ClosedWindow {
+                    idx: second,
+                    name,
+                    status: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn session_resize() {
+        let mut session: Session<MockPane> = Session::new(WINSZ);
+        let (first, _) = session.new_window().unwrap();
+        let (second, _) = session.new_window().unwrap();
+        let (third, _) = session.new_window().unwrap();
+        session.select_window(second);
+        assert_eq!(session.selected_window_idx(), Some(second));
+        let recv = &mut active_pane(&mut session, second).resize_channel.1;
+        assert!(recv.try_next().is_ok(), "did not resize selected window");
+        assert!(
+            recv.try_next().is_err(),
+            "resized multiple times on selection"
+        );
+
+        session.resize(WINSZ).unwrap();
+        let recv = &mut active_pane(&mut session, first).resize_channel.1;
+        assert!(recv.try_next().is_err(), "resized background window");
+        let recv = &mut active_pane(&mut session, second).resize_channel.1;
+        assert!(recv.try_next().is_ok(), "did not resize selected window");
+        assert!(
+            recv.try_next().is_err(),
+            "resized multiple times on selection"
+        );
+        let recv = &mut active_pane(&mut session, third).resize_channel.1;
+        assert!(recv.try_next().is_err(), "resized background window");
+
+        // some noise
+        session
+            .pty_update(SessionPtyUpdate {
+                pane_idx: session.windows[&first].active,
+                data: PtyUpdate::Byte(13),
+            })
+            .unwrap();
+
+        session.select_window(third);
+        let recv = &mut active_pane(&mut session, first).resize_channel.1;
+        assert!(recv.try_next().is_err(), "resized background window");
+        let recv = &mut active_pane(&mut session, second).resize_channel.1;
+        assert!(recv.try_next().is_err(), "resized on exit");
+        let recv = &mut active_pane(&mut session, third).resize_channel.1;
+        assert!(recv.try_next().is_ok(), "did not resize selected window");
+
+        session
+            .pty_update(SessionPtyUpdate {
+                pane_idx: session.windows[&third].active,
+                data: PtyUpdate::Exited { status: None },
+            })
+            .unwrap();
+        let recv = &mut active_pane(&mut session, first).resize_channel.1;
+        assert!(recv.try_next().is_err(), "resized background window");
+        let recv = &mut active_pane(&mut session, second).resize_channel.1;
+        assert!(recv.try_next().is_ok(), "did not resize on selection");
+    }
+
+    #[test]
+    fn session_mark_dirty_on_select() {
+        let mut session: Session<MockPane> = Session::new(WINSZ);
+        let (first, _) = session.new_window().unwrap();
+        let (second, _) = session.new_window().unwrap();
+
+        let recv = &mut active_pane(&mut session, first).dirty_channel.1;
+        assert!(recv.try_next().is_err(), "marked before selection");
+        let recv = &mut active_pane(&mut session, second).dirty_channel.1;
+        assert!(recv.try_next().is_err(), "marked before selection");
+
+        session.select_window(second);
+        assert_eq!(session.selected_window_idx(), Some(second));
+        let recv = &mut active_pane(&mut session, first).dirty_channel.1;
+        assert!(recv.try_next().is_err(), "unselected window marked");
+        let recv = &mut active_pane(&mut session, second).dirty_channel.1;
+        assert!(recv.try_next().is_ok(), "selected window not marked");
+
+        session.select_window(first);
+        let recv = &mut active_pane(&mut session, first).dirty_channel.1;
+        assert!(recv.try_next().is_ok(), "selected window not marked");
+        let recv = &mut active_pane(&mut session, second).dirty_channel.1;
+        assert!(recv.try_next().is_err(), "unselected window marked");
+    }
+
+    #[test]
+    fn session_selection() {
+        let mut session: Session<MockPane> = Session::new(WINSZ);
+        let (first, _) = session.new_window().unwrap();
+        let (second, _) = session.new_window().unwrap();
+        let (third, _) = session.new_window().unwrap();
+        session.select_window(first);
+        session.select_window(second);
+        let selection = session.selection();
+        assert_eq!(
+            selection,
+            Selection {
+                window: Some(second),
+                last: Some(first),
+            }
+        );
+        session.select_window(third);
+        for idx in [first, second, third] {
+            active_pane(&mut session, idx)
+                .dirty_channel
+                .1
+                .try_next()
+                .ok();
+        }
+
+        session.set_selection(selection);
+        assert_eq!(session.selection(), selection);
+        let recv = &mut active_pane(&mut session, second).dirty_channel.1;
+        assert!(recv.try_next().is_err(), "marked on setting the selection");
+        let mut out = Vec::new();
+        session.redraw_window(third, &mut out).unwrap();
+        assert_eq!(out, b"hello");
+        assert!(session.redraw_window(7, &mut out).is_err());
+
+        // Closed windows are passed over.
+        session.select_window(first);
+        session.kill_window().unwrap();
+        session.set_selection(selection);
+        assert_eq!(
+            session.selection(),
+            Selection {
+                window: Some(second),
+                last: None,
+            }
+        );
+        session.set_selection(Selection {
+            window: Some(first),
+            last: Some(third),
+        });
+        assert_eq!(session.selected_window_idx(), Some(second));
+        assert_eq!(session.last_selected_window_idx(), Some(third));
+    }
+
+    #[test]
+    fn session_slow_print() {
+        let mut session: Session<MockPane> = Session::new(WINSZ);
+        assert!(session.set_slow_print(Some(10)).is_err());
+        let (first, _) = session.new_window().unwrap();
+        let (second, _) = session.new_window().unwrap();
+        session.select_window(second);
+        session.set_slow_print(Some(10)).unwrap();
+        assert_eq!(active_pane(&mut session, first).slow_print(), None);
+        assert_eq!(active_pane(&mut session, second).slow_print(), Some(10));
+        session.set_slow_print(None).unwrap();
+        assert_eq!(active_pane(&mut session, second).slow_print(), None);
+    }
+
+    #[test]
+    fn session_environment() {
+        let mut session: Session<MockPane> = Session::new(WINSZ);
+        let (first, _) = session.new_window().unwrap();
+        session.environment_mut().set("EDITOR", "vi");
+        session.set_update_environment(vec!["DISPLAY".to_string()]);
+        session.update_environment(vec![("DISPLAY", ":1"), ("EDITOR", "ed")]);
+        let (second, _) = session.new_window().unwrap();
+
+        assert_eq!(session.environment().get("DISPLAY"), Some(":1"));
+        let env = &active_pane(&mut session, second).env;
+        assert_eq!(env.get("EDITOR"), Some("vi"));
+        assert_eq!(env.get("DISPLAY"), Some(":1"));
+        let env = &active_pane(&mut session, first).env;
+        assert_eq!(env.get("PATH"), std::env::var("PATH").ok().as_deref());
+        assert_ne!(env.get("EDITOR"), Some("vi"));
+    }
+
+    #[test]
+    fn session_snapshot() {
+        use crate::applet::Viewer;
+
+        let mut session: Session<MockPane> = Session::new(WINSZ);
+        session.set_name("work");
+        session.new_window().unwrap();
+        let (edit, _) = session
+            .new_window_running(Some("vim notes"), None, &[])
+            .unwrap();
+        session.move_window(edit, 4).unwrap();
+        session.select_window(4);
+        session.split_pane(Direction::Horizontal).unwrap();
+        session.resize_pane(Direction::Horizontal, 10).unwrap();
+        let viewer = Viewer::new("notes", "text\n");
+        session.new_applet_window(Box::new(viewer)).unwrap();
+
+        let snapshot = session.snapshot();
+        assert_eq!(snapshot.name, "work");
+        assert_eq!(snapshot.selected, Some(4));
+        let windows: Vec<_> = snapshot
+            .windows
+            .iter()
+            .map(|window| (window.idx, window.name.as_str(), window.active))
+            .collect();
+        assert_eq!(windows, [(0, "sh", 0), (4, "vim", 1)]);
+        let commands: Vec<_> = snapshot.windows[1]
+            .panes
+            .iter()
+            .map(|pane| pane.command.as_deref())
+            .collect();
+        assert_eq!(commands, [Some("vim notes"), None]);
+
+        let mut restored: Session<MockPane> = Session::new(WINSZ);
+        restored.restore(&snapshot).unwrap();
+        assert_eq!(restored.snapshot(), snapshot);
+        assert_eq!(restored.name(), "work");
+        assert_eq!(restored.selected_window_idx(), Some(4));
+        assert_eq!(active_pane(&mut restored, 0).command, [util::get_shell()]);
+
+        // Directories which have gone are not started in.
+        let mut snapshot = snapshot;
+        snapshot.windows[0].panes[0].cwd = Some("/".into());
+        snapshot.windows[1].panes[1].cwd = Some("/no/such/directory".into());
+        let mut restored: Session<MockPane> = Session::new(WINSZ);
+        restored.restore(&snapshot).unwrap();
+        let cwds: Vec<_> = restored
+            .snapshot()
+            .windows
+            .iter()
+            .flat_map(|window| window.panes.iter().map(|pane| pane.cwd.clone()))
+            .collect();
+        assert_eq!(cwds, [Some("/".into()), None, None]);
+    }
+
+    #[test]
+    fn session_export() {
+        use crate::applet::Viewer;
+
+        let mut session: Session<MockPane> = Session::new(WINSZ);
+        session.set_name("work");
+        let (first, _) = session.new_window().unwrap();
+        let (edit, _) = session
+            .new_window_running(Some("vim notes"), None, &[])
+            .unwrap();
+        session.select_window(edit);
+        session.split_pane(Direction::Horizontal).unwrap();
+        session.set_window_record_input(true).unwrap();
+        session.receive_stdin(b"ls\r").unwrap();
+        session.set_private(true).unwrap();
+        let viewer = Viewer::new("notes", "text\n");
+        let (applet, _) = session.new_applet_window(Box::new(viewer)).unwrap();
+        session.select_window(first);
+        session.kill_window().unwrap();
+
+        let export = session.export();
+        assert_eq!(export.name, "work");
+        assert_eq!(export.selected, session.selected_window_idx());
+        let windows: Vec<_> = export
+            .windows
+            .iter()
+            .map(|window| (window.idx, window.active, window.panes.len()))
+            .collect();
+        assert_eq!(windows, [(edit, 1, 2), (applet, 0, 1)]);
+        let edit = &export.windows[0];
+        assert_eq!(edit.layout.to_string(), "h(1:0,1:1)");
+        assert_eq!(edit.input.as_deref(), Some("ls\n"));
+        assert_eq!(edit.panes[0].command.as_deref(), Some("vim notes"));
+        assert_eq!(edit.panes[0].contents.as_deref(), Some(&b"hello"[..]));
+        assert_eq!(edit.panes[1].command, None);
+        // What a private pane shows is left out.
+        assert_eq!(edit.panes[1].contents, None);
+        assert_eq!(edit.panes[0].cols + edit.panes[1].cols + 1, WINSZ.ws_col);
+        assert!(export.windows[1].panes[0].applet);
+        assert!(!edit.panes[0].applet);
+        assert_eq!(export.closed.len(), 1);
+        assert_eq!(export.closed[0].idx, first);
+    }
+
+    #[test]
+    fn session_take_lines() {
+        let mut session: Session<MockPane> = Session::new(WINSZ);
+        let (first, _) = session.new_window().unwrap();
+        assert_eq!(session.take_lines(), None);
+
+        session.set_line_log(true);
+        let (second, _) = session.new_window().unwrap();
+        for (idx, line) in [(first, "one"), (second, "two")] {
+            let pane = active_pane(&mut session, idx);
+            pane.lines.as_mut().unwrap().push(line.to_string());
+        }
+        session.select_window(second);
+        let new_lines = session.take_lines().unwrap();
+        assert_eq!(new_lines.lines, ["two"]);
+        assert_eq!(new_lines.pane_idx, session.windows[&second].active);
+        // The unselected window's lines were dropped.
+        session.select_window(first);
+        assert!(session.take_lines().unwrap().lines.is_empty());
+
+        session.set_line_log(false);
+        assert_eq!(active_pane(&mut session, first).lines, None);
+        assert_eq!(session.take_lines(), None);
+    }
+
+    #[test]
+    fn session_sibling() {
+        let mut session: Session<MockPane> = Session::new(WINSZ);
+        assert_eq!(session.name(), "0");
+        session.set_name("work");
+        session.environment_mut().set("EDITOR", "vi");
+        session.set_default_term(Some("xterm")).unwrap();
+        session.new_window().unwrap();
+
+        let mut sibling = session.sibling("play");
+        assert_eq!(sibling.name(), "play");
+        assert_eq!(session.name(), "work");
+        assert_eq!(sibling.windows().count(), 0);
+        assert_eq!(sibling.size(), WINSZ);
+        let (idx, _) = sibling.new_window().unwrap();
+        let env = &active_pane(&mut sibling, idx).env;
+        assert_eq!(env.get("EDITOR"), Some("vi"));
+        assert_eq!(env.get("TERM"), Some("xterm"));
+    }
+
+    #[test]
+    fn session_window_environment() {
+        let mut session: Session<MockPane> = Session::new(WINSZ);
+        session.environment_mut().set("EDITOR", "vi");
+        session.environment_mut().set("PAGER", "less");
+        let env = [("EDITOR".to_string(), "nano".to_string())];
+        let (first, _) = session.new_window_running(None, None, &env).unwrap();
+        let (second, _) = session.new_window().unwrap();
+        let editor = |session: &mut Session<MockPane>, idx| {
+            active_pane(session, idx)
+                .env
+                .get("EDITOR")
+                .map(String::from)
+        };
+        assert_eq!(editor(&mut session, first).as_deref(), Some("nano"));
+        assert_eq!(editor(&mut session, second).as_deref(), Some("vi"));
+        let pager = active_pane(&mut session, first).env.get("PAGER");
+        assert_eq!(pager, Some("less"));
+        // Panes split from the window, or broken out of it, get them too.
+        session.select_window(first);
+        session.split_pane(Direction::Vertical).unwrap();
+        assert_eq!(editor(&mut session, first).as_deref(), Some("nano"));
+        let broken = session.break_pane().unwrap();
+        assert_eq!(session.windows[&broken].environment, env);
+    }
+
+    #[test]
+    fn session_term() {
+        let mut session: Session<MockPane> = Session::new(WINSZ);
+        assert!(matches!(
+            session.set_default_term(Some("no-such-terminal")),
+            Err(SessionError::UnknownTerm(_))
+        ));
+        session.set_default_term(Some("xterm")).unwrap();
+        let (first, _) = session.new_window().unwrap();
+        session.set_default_term(None).unwrap();
+        let (second, _) = session.new_window().unwrap();
+        let term = |session: &mut Session<MockPane>, idx| {
+            active_pane(session, idx).env.get("TERM").map(String::from)
+        };
+        assert_eq!(term(&mut session, first).as_deref(), Some("xterm"));
+        let emulated = terminfo::emulated().map(String::from);
+        let emulated = emulated.or(std::env::var("TERM").ok());
+        assert_eq!(term(&mut session, second), emulated);
+
+        session.select_window(second);
+        assert!(session.set_window_term(Some("../x/xterm")).is_err());
+        session.set_window_term(Some("xterm")).unwrap();
+        session.split_pane(Direction::Vertical).unwrap();
+        assert_eq!(term(&mut session, second).as_deref(), Some("xterm"));
+    }
+
+    #[test]
+    fn session_private() {
+        let mut session: Session<MockPane> = Session::new(WINSZ);
+        assert!(!session.is_showing_private());
+        let (first, _) = session.new_window().unwrap();
+        let (second, _) = session.new_window().unwrap();
+        session.select_window(first);
+        session.split_pane(Direction::Vertical).unwrap();
+        session.set_private(true).unwrap();
+        assert!(session.is_showing_private());
+        session.select_next_pane();
+        assert!(session.is_showing_private());
+        session.select_window(second);
+        assert!(!session.is_showing_private());
+        session.select_window(first);
+        session.select_next_pane();
+        session.set_private(false).unwrap();
+        assert!(!session.is_showing_private());
+    }
+
+    #[test]
+    fn session_move_window() {
+        let mut first: Session<MockPane> = Session::new(WINSZ);
+        let mut second = first.sibling("second");
+        let (shared, _) = first.new_window().unwrap();
+        let (kept, _) = first.new_window().unwrap();
+        let (own, _) = second.new_window().unwrap();
+        first.select_window(shared);
+        let id = first.window_id(shared).unwrap();
+        assert_ne!(second.window_id(own), Some(id));
+        let pane = first.windows[&shared].active;
+        let reserved = second.reserve_window_idx();
+        assert_eq!(reserved, own + 1);
+
+        let taken = first.take_window(shared).unwrap();
+        assert!(!first.has_pane(pane));
+        assert_eq!(first.selected_window_idx(), Some(kept));
+        assert_eq!(second.put_window(taken, reserved), reserved);
+        assert!(second.has_pane(pane));
+        assert_eq!(second.find_window(id), Some(reserved));
+        // Updates for the pane reach it under the same index.
+        let data = PtyUpdate::Exited { status: None };
+        second
+            .pty_update(SessionPtyUpdate {
+                pane_idx: pane,
+                data,
+            })
+            .unwrap();
+        assert_eq!(second.find_window(id), None);
+
+        // A taken index is not reused.
+        let taken = first.take_window(kept).unwrap();
+        assert_eq!(first.selected_window_idx(), None);
+        assert_eq!(second.put_window(taken, own), reserved + 1);
+        assert!(first.take_window(kept).is_err());
+    }
+
+    #[test]
+    fn session_record_input() {
+        let mut session: Session<MockPane> = Session::new(WINSZ);
+        let (first, _) = session.new_window().unwrap();
+        session.set_default_record_input(true);
+        let (second, _) = session.new_window().unwrap();
+        session.select_window(first);
+        session.receive_stdin(b"unseen\r").unwrap();
+        assert!(session.input_log(first).is_none());
+        session.set_window_record_input(true).unwrap();
+        session.receive_stdin(b"ls\r").unwrap();
+        session.set_private(true).unwrap();
+        session.receive_stdin(b"secret\r").unwrap();
+        assert_eq!(session.input_log(first).unwrap().text(), "ls\n");
+
+        session.select_window(second);
+        session.receive_stdin(b"pwd\r").unwrap();
+        assert_eq!(session.input_log(second).unwrap().text(), "pwd\n");
+        session.set_window_record_input(false).unwrap();
+        assert!(session.input_log(second).is_none());
+    }
+
+    #[test]
+    fn slow_print_release() {
+        use std::time::Duration;
+
+        let start = Instant::now();
+        let mut slow_print = SlowPrint::new(4, start);
+        slow_print.pending.extend(b"one\ntwo\nthree\nfour");
+        assert_eq!(slow_print.release(start), b"one");
+        let later = |ms| start + Duration::from_millis(ms);
+        assert_eq!(slow_print.release(later(100)), b"");
+        assert_eq!(slow_print.release(later(250)), b"\ntwo");
+        assert_eq!(slow_print.release(later(750)), b"\nthree\nfour");
+        assert!(slow_print.pending.is_empty());
+
+        // Idle time is only saved up to a second.
+        slow_print.pending.extend(b"\n\n\n\n\n\n");
+        assert_eq!(slow_print.release(later(10_000)), b"\n\n\n\n");
+        assert_eq!(slow_print.release(later(10_250)), b"\n");
     }
 
-    impl SessionWindow for MockWindow {
-        fn new(_: &str, _: Winsize) -> Result<(MockWindow, Receiver<PtyUpdate>), ()> {
-            let (_, recv) = mpsc::channel(10);
-            let stdin_channel = mpsc::channel(100);
-            let pty_channel = mpsc::channel(10);
-            let resize_channel = mpsc::channel(10);
-            let dirty_channel = mpsc::channel(10);
-            Ok((
-                MockWindow {
-                    stdin_channel,
-                    pty_channel,
-                    resize_channel,
-                    dirty_channel,
-                },
-                recv,
-            ))
-        }
+    #[test]
+    fn session_split_pane() {
+        let mut session: Session<MockPane> = Session::new(WINSZ);
+        assert!(session.split_pane(Direction::Vertical).is_err());
+        let (window, _) = session.new_window().unwrap();
+        session.select_window(window);
+        let first = session.windows[&window].active;
+        let second = session.split_pane(Direction::Horizontal).unwrap().pane_idx;
+        assert_eq!(session.windows[&window].active, second);
 
-        fn receive_stdin(&self, data: &[u8]) -> Result<(), io::Error> {
-            for byte in data {
-                self.stdin_channel.0.clone().try_send(*byte).unwrap();
+        let size = |session: &mut Session<MockPane>, pane| {
+            let recv = &mut session.panes.get_mut(&pane).unwrap().resize_channel.1;
+            let mut size = None;
+            while let Ok(Some(s)) = recv.try_next() {
+                size = Some((s.ws_col, s.ws_row));
             }
-            Ok(())
-        }
+            size
+        };
+        assert_eq!(size(&mut session, first), Some((40, 24)));
+        assert_eq!(size(&mut session, second), Some((39, 24)));
 
-        fn pty_update(&mut self, byte: u8) {
-            self.pty_channel.0.try_send(byte).unwrap();
-        }
+        session.receive_stdin(b"x").unwrap();
+        let recv = &mut session.panes.get_mut(&second).unwrap().stdin_channel.1;
+        assert_eq!(recv.try_next().unwrap(), Some(b'x'));
+        assert_eq!(session.select_next_pane(), Some(first));
+        assert_eq!(session.select_next_pane(), Some(second));
 
-        fn resize(&mut self, size: Winsize) {
-            self.resize_channel.0.try_send(size).unwrap();
-        }
+        let mut out = Vec::new();
+        session.redraw(&mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.starts_with("\x1b[0m\x1b[1;41H\x1b[32m\u{2502}\x1b[2;41H\u{2502}"));
+        assert!(out.contains("hellohello"));
+        assert!(session.is_separator(40, 3));
+        assert!(!session.is_separator(39, 3));
 
-        fn mark_dirty(&mut self) {
-            self.dirty_channel.0.try_send(true).unwrap();
-        }
+        assert_eq!(session.select_pane_at(40, 3), None);
+        assert_eq!(session.select_pane_at(10, 3), Some(first));
+        let rect = Rect {
+            col: 0,
+            row: 0,
+            cols: 40,
+            rows: 24,
+        };
+        assert_eq!(session.active_rect(), Some(rect));
+        assert_eq!(session.select_pane_at(41, 3), Some(second));
+        session.kill_pane().unwrap();
+        let recv = &mut session.panes.get_mut(&second).unwrap().hangup_channel.1;
+        assert_eq!(recv.try_next().unwrap(), Some(true));
 
-        fn redraw<T: Write>(&mut self, file: &mut T) {
-            file.write(b"hello").unwrap();
-            file.flush().unwrap();
-        }
+        session
+            .pty_update(SessionPtyUpdate {
+                pane_idx: second,
+                data: PtyUpdate::Exited { status: None },
+            })
+            .unwrap();
+        assert_eq!(session.windows[&window].active, first);
+        assert_eq!(size(&mut session, first), Some((80, 24)));
+        assert_eq!(session.selected_window_idx(), Some(window));
+
+        session.split_pane(Direction::Horizontal).unwrap();
+        size(&mut session, first);
+        session.resize_pane(Direction::Horizontal, -10).unwrap();
+        assert_eq!(size(&mut session, first), Some((50, 24)));
+        let rects = session.pane_rects();
+        assert_eq!(rects.len(), 2);
+        assert_eq!(
+            rects[0],
+            (
+                first,
+                Rect::from_size(Winsize {
+                    ws_col: 50,
+                    ..WINSZ
+                })
+            )
+        );
+        session.resize_pane(Direction::Vertical, -10).unwrap();
+        assert_eq!(size(&mut session, first), None);
+
+        session.resize(Winsize { ws_col: 2, ..WINSZ }).unwrap();
+        assert!(matches!(
+            session.split_pane(Direction::Horizontal),
+            Err(SessionError::NoSpace)
+        ));
+
+        // Both halves must be at least the minimum size.
+        session.resize(WINSZ).unwrap();
+        session.set_min_pane_size(MinSize { cols: 10, rows: 12 });
+        assert!(matches!(
+            session.split_pane(Direction::Vertical),
+            Err(SessionError::NoSpace)
+        ));
+        session.set_min_pane_size(MinSize { cols: 10, rows: 11 });
+        assert!(session.split_pane(Direction::Vertical).is_ok());
     }
 
     #[test]
-    fn session_report_unselected() {
-        let mut session: Session<MockWindow> = Session::new(WINSZ);
-        match session.redraw(&mut io::sink()).unwrap_err() {
-            SessionError::NoSelectedWindow => (),
-            _ => assert!(false, "wrong error when redrawing nonexistent window"),
+    fn session_zoom_pane() {
+        let mut session: Session<MockPane> = Session::new(WINSZ);
+        let (window, _) = session.new_window().unwrap();
+        session.select_window(window);
+        // A window of one pane has nothing to zoom.
+        session.toggle_zoom().unwrap();
+        assert!(!session.windows[&window].zoomed);
+
+        let first = session.windows[&window].active;
+        let second = session.split_pane(Direction::Horizontal).unwrap().pane_idx;
+        let drawn = |session: &mut Session<MockPane>| {
+            let mut out = Vec::new();
+            session.redraw_window(window, &mut out).unwrap();
+            String::from_utf8(out).unwrap().matches("hello").count()
         };
+        assert_eq!(drawn(&mut session), 2);
+        session.toggle_zoom().unwrap();
+        assert!(session.windows[&window].zoomed);
+        assert_eq!(session.active_rect(), Some(Rect::from_size(WINSZ)));
+        assert_eq!(session.pane_rects(), [(second, Rect::from_size(WINSZ))]);
+        assert!(!session.is_separator(40, 0));
+        assert_eq!(drawn(&mut session), 1);
+        assert_eq!(session.select_pane_at(0, 0), Some(second));
+        // The panes hidden keep their places in the layout.
+        assert_eq!(session.window_pane_rects(window).len(), 2);
+        session.toggle_zoom().unwrap();
+        assert!(!session.windows[&window].zoomed);
+        assert_eq!(drawn(&mut session), 2);
+
+        // Selecting another pane, splitting or closing one shows them all.
+        session.toggle_zoom().unwrap();
+        assert_eq!(session.select_next_pane(), Some(first));
+        assert!(!session.windows[&window].zoomed);
+        session.toggle_zoom().unwrap();
+        let third = session.split_pane(Direction::Vertical).unwrap().pane_idx;
+        assert!(!session.windows[&window].zoomed);
+        assert_eq!(session.pane_rects().len(), 3);
+        session.toggle_zoom().unwrap();
+        session.close_pane(third);
+        assert!(!session.windows[&window].zoomed);
+        assert_eq!(session.pane_rects().len(), 2);
     }
 
     #[test]
-    fn session_window_relative() {
-        let mut session: Session<MockWindow> = Session::new(WINSZ);
-        assert_eq!(session.windows.len(), 0);
-
+    fn session_break_join_pane() {
+        let mut session: Session<MockPane> = Session::new(WINSZ);
         let (first, _) = session.new_window().unwrap();
-        assert_eq!(session.windows.len(), 1);
         session.select_window(first);
-        let first = session.first_window_idx().unwrap();
-        assert_eq!(
-            session.select_window(2475),
-            None,
-            "should not select invalid idx"
-        );
-        assert_eq!(Some(first), session.selected_window_idx());
-        assert_eq!(Some(first), session.first_window_idx());
-        assert_eq!(Some(first), session.last_window_idx());
-        assert_eq!(session.next_window_idx(), None);
-        assert_eq!(session.prev_window_idx(), None);
+        assert!(matches!(session.break_pane(), Err(SessionError::OnlyPane)));
+        let kept = session.windows[&first].active;
+        let moved = session.split_pane(Direction::Horizontal).unwrap().pane_idx;
 
-        let (second, _) = session.new_window().unwrap();
-        assert_eq!(session.windows.len(), 2);
-        assert_ne!(first, second);
-        assert_eq!(
-            Some(first),
-            session.selected_window_idx(),
-            "selection changed when adding new window"
-        );
+        let second = session.break_pane().unwrap();
+        assert_eq!(session.windows[&first].layout, Layout::Pane(kept));
+        assert_eq!(session.windows[&first].active, kept);
+        assert_eq!(session.windows[&second].layout, Layout::Pane(moved));
+        assert_eq!(session.selected_window_idx(), Some(first));
+        assert_eq!(session.panes.len(), 2);
+
+        assert!(matches!(
+            session.join_pane(first, Direction::Vertical),
+            Err(SessionError::NoSuchWindow(_))
+        ));
+        assert!(session.join_pane(7, Direction::Vertical).is_err());
+        // Joining a window's only pane elsewhere closes the window.
         session.select_window(second);
-        assert_eq!(Some(first), session.first_window_idx(), "ordering broken");
-        assert_eq!(Some(second), session.last_window_idx(), "ordering broken");
-        assert_eq!(
-            Some(first),
-            session.prev_window_idx(),
-            "can't find first window"
-        );
-        assert_eq!(session.next_window_idx(), None);
+        session.join_pane(first, Direction::Vertical).unwrap();
+        assert!(!session.windows.contains_key(&second));
+        assert_eq!(session.selected_window_idx(), Some(first));
+        assert_eq!(session.windows[&first].layout.panes(), [kept, moved]);
+        assert_eq!(session.windows[&first].active, moved);
+        let rect = Rect {
+            col: 0,
+            row: 13,
+            cols: 80,
+            rows: 11,
+        };
+        assert_eq!(session.active_rect(), Some(rect));
+        assert_eq!(session.panes.len(), 2);
+    }
 
-        session.select_window(first);
+    #[test]
+    fn session_join_last_pane() {
+        let mut session: Session<MockPane> = Session::new(WINSZ);
+        let (first, _) = session.new_window().unwrap();
+        let (second, _) = session.new_window().unwrap();
         let (third, _) = session.new_window().unwrap();
-        assert_eq!(session.windows.len(), 3);
-        assert_eq!(Some(second), session.next_window_idx());
-        assert_eq!(Some(third), session.last_window_idx());
+        session.select_window(first);
+        session.select_window(third);
         session.select_window(second);
-        assert_eq!(Some(first), session.prev_window_idx());
-        assert_eq!(Some(third), session.next_window_idx());
-        assert_eq!(Some(first), session.first_window_idx());
+        let moved = session.windows[&second].active;
+
+        // The window emptied closes as if its pane had exited.
+        session.join_pane(first, Direction::Vertical).unwrap();
+        assert!(!session.windows.contains_key(&second));
+        assert_eq!(session.windows[&first].layout.panes().len(), 2);
+        assert!(session.panes.contains_key(&moved));
+        let closed = session.closed_windows();
+        assert_eq!(closed.len(), 1);
+        assert_eq!((closed[0].idx, closed[0].status), (second, None));
+        assert_eq!(session.selected_window_idx(), Some(first));
+
+        // last-window goes back to the window selected before the one closed.
+        assert_eq!(session.last_selected_window_idx(), Some(third));
         session.select_window(third);
-        assert_eq!(Some(second), session.prev_window_idx());
-        assert_eq!(None, session.next_window_idx());
+        assert_eq!(session.last_selected_window_idx(), Some(first));
+        assert_eq!(session.renumber_windows(), [(third, 1)]);
+    }
 
-        session.select_window(second);
-        session
-            .pty_update(SessionPtyUpdate {
-                window_idx: second,
-                data: PtyUpdate::Exited,
-            })
+    #[test]
+    fn session_new_window_running() {
+        let mut session: Session<MockPane> = Session::new(WINSZ);
+        let (idx, _) = session
+            .new_window_running(Some("top -d 5"), None, &[])
             .unwrap();
-        assert_eq!(
-            Some(third),
-            session.selected_window_idx(),
-            "next younger window not selected"
-        );
-        assert_eq!(
-            Some(first),
-            session.prev_window_idx(),
-            "can't find first window"
-        );
-        session
-            .pty_update(SessionPtyUpdate {
-                window_idx: first,
-                data: PtyUpdate::Exited,
-            })
+        assert_eq!(session.window_name(idx), Some("top"));
+        let pane = &session.windows[&idx].active;
+        assert_eq!(session.panes[pane].command, ["/bin/sh", "-c", "top -d 5"]);
+
+        let (idx, _) = session.new_window().unwrap();
+        assert_eq!(session.window_name(idx), Some("sh"));
+        let pane = &session.windows[&idx].active;
+        assert_eq!(session.panes[pane].command, ["/bin/sh"]);
+
+        // The default shell runs the default command, unless given another.
+        session.set_default_shell(Some("/usr/bin/zsh"));
+        session.set_default_command(Some("htop"));
+        let (idx, _) = session.new_window().unwrap();
+        assert_eq!(session.window_name(idx), Some("htop"));
+        let pane = &session.windows[&idx].active;
+        assert_eq!(session.panes[pane].command, ["/usr/bin/zsh", "-c", "htop"]);
+        let (idx, _) = session.new_window_running(Some("top"), None, &[]).unwrap();
+        let pane = &session.windows[&idx].active;
+        assert_eq!(session.panes[pane].command, ["/usr/bin/zsh", "-c", "top"]);
+        session.set_default_command(None);
+        let sibling = session.sibling("other");
+        assert_eq!(sibling.command_name(None), "zsh");
+    }
+
+    #[test]
+    fn session_new_window_cwd() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("src")).unwrap();
+        let mut session: Session<MockPane> = Session::new(WINSZ);
+        let (first, _) = session
+            .new_window_running(None, Some(dir.path()), &[])
             .unwrap();
-        assert_eq!(Some(third), session.selected_window_idx());
         assert_eq!(
-            Some(third),
-            session.first_window_idx(),
-            "only remaining window is not first"
+            active_pane(&mut session, first).cwd(),
+            Some(dir.path().into())
         );
-        assert_eq!(Some(third), session.last_window_idx());
-        assert_eq!(None, session.prev_window_idx());
-        session
-            .pty_update(SessionPtyUpdate {
-                window_idx: third,
-                data: PtyUpdate::Exited,
-            })
-            .unwrap();
-        assert_eq!(session.windows.len(), 0);
-        assert_eq!(None, session.next_window_idx());
-        assert_eq!(None, session.last_window_idx());
+        // New windows start where the selected window's active pane is, and
+        // relative directories are taken from there.
+        session.select_window(first);
+        let (second, _) = session.new_window().unwrap();
         assert_eq!(
-            None,
-            session.selected_window_idx(),
-            "closed window not deselected"
+            active_pane(&mut session, second).cwd(),
+            Some(dir.path().into())
         );
+        let src = Path::new("src");
+        let (third, _) = session.new_window_running(None, Some(src), &[]).unwrap();
+        let cwd = active_pane(&mut session, third).cwd();
+        assert_eq!(cwd, Some(dir.path().join("src")));
+        assert!(matches!(
+            session.new_window_running(None, Some(Path::new("no-such-dir")), &[]),
+            Err(SessionError::NoSuchDirectory(_))
+        ));
     }
 
     #[test]
-    fn session_forward_stdin() {
-        let mut session: Session<MockWindow> = Session::new(WINSZ);
-        let (first, _) = session.new_window().unwrap();
-        let (second, _) = session.new_window().unwrap();
+    fn session_last_selected() {
+        let mut session: Session<MockPane> = Session::new(WINSZ);
+        for _ in 0..3 {
+            session.new_window().unwrap();
+        }
+        session.select_window(0);
+        assert_eq!(session.last_selected_window_idx(), None);
+        session.select_window(2);
+        session.select_window(2);
+        assert_eq!(session.last_selected_window_idx(), Some(0));
+        session.select_window(1);
+        assert_eq!(session.last_selected_window_idx(), Some(2));
 
-        session.select_window(second);
-        assert_eq!(session.selected_window_idx(), Some(second));
-        session.receive_stdin(b"Hello").unwrap();
+        session.swap_window(2, 0).unwrap();
+        assert_eq!(session.last_selected_window_idx(), Some(0));
+        session.move_window(0, 5).unwrap();
+        assert_eq!(session.last_selected_window_idx(), Some(5));
+        session.close_window(5, None);
+        assert_eq!(session.last_selected_window_idx(), None);
 
-        let recv = &mut session.windows.get_mut(&first).unwrap().stdin_channel.1;
-        assert!(recv.try_next().is_err(), "other window received byte");
-        let recv = &mut session.windows.get_mut(&second).unwrap().stdin_channel.1;
-        for byte in b"Hello" {
-            assert_eq!(recv.try_next().unwrap(), Some(*byte), "failed to recv byte");
+        // Closing the selected window selects a neighbour, which cannot also be
+        // the last selected.
+        session.select_window(2);
+        session.close_window(2, None);
+        assert_eq!(session.selected_window_idx(), Some(1));
+        assert_eq!(session.last_selected_window_idx(), None);
+    }
+
+    #[test]
+    fn session_reorder_windows() {
+        let mut session: Session<MockPane> = Session::new(WINSZ);
+        for _ in 0..3 {
+            session.new_window().unwrap();
         }
-        assert!(recv.try_next().is_err(), "recv too many bytes");
+        session.select_window(0);
+        session.rename_window("first").unwrap();
+
+        session.swap_window(0, 2).unwrap();
+        assert_eq!(session.selected_window_idx(), Some(2));
+        assert_eq!(session.window_name(2), Some("first"));
+        assert_eq!(session.window_name(0), Some("sh"));
+        assert!(matches!(
+            session.swap_window(2, 5),
+            Err(SessionError::NoSuchWindow(5))
+        ));
+
+        assert!(matches!(
+            session.move_window(2, 1),
+            Err(SessionError::WindowExists(1))
+        ));
+        session.move_window(2, 7).unwrap();
+        assert_eq!(session.selected_window_idx(), Some(7));
+        assert_eq!(session.window_name(7), Some("first"));
+        assert_eq!(session.prev_window_idx(), Some(1));
+        session.move_window(7, 7).unwrap();
+        assert!(session.move_window(2, 3).is_err());
+        assert_eq!(session.new_window().unwrap().0, 8);
+        session.move_window(0, 2).unwrap();
+        assert_eq!(
+            session.windows.keys().copied().collect::<Vec<_>>(),
+            [1, 2, 7, 8]
+        );
 
+        session.select_window(1);
+        assert_eq!(session.renumber_windows(), [(1, 0), (2, 1), (7, 2), (8, 3)]);
+        assert_eq!(session.selected_window_idx(), Some(0));
+        assert_eq!(session.last_selected_window_idx(), Some(2));
+        assert_eq!(session.window_name(2), Some("first"));
+        assert!(session.renumber_windows().is_empty());
+        assert_eq!(session.new_window().unwrap().0, 4);
+    }
+
+    #[test]
+    fn session_base_index() {
+        let mut session: Session<MockPane> = Session::new(WINSZ);
+        session.set_base_index(1);
+        session.set_pane_base_index(1);
+        let (first, _) = session.new_window().unwrap();
+        assert_eq!(first, 1);
+        assert_eq!(session.new_window().unwrap().0, 2);
         session.select_window(first);
-        session.receive_stdin(b"World").unwrap();
+        assert_eq!(session.active_pane_number(), Some(1));
+        session.split_pane(Direction::Vertical).unwrap();
+        assert_eq!(session.active_pane_number(), Some(2));
 
-        let recv = &mut session.windows.get_mut(&first).unwrap().stdin_channel.1;
-        for byte in b"World" {
-            assert_eq!(recv.try_next().unwrap(), Some(*byte), "failed to recv byte");
+        session.move_window(1, 5).unwrap();
+        assert_eq!(session.renumber_windows(), [(2, 1), (5, 2)]);
+        assert_eq!(session.new_window().unwrap().0, 3);
+        assert_eq!(session.export().pane_base_index, 1);
+        // Other sessions count from the same index.
+        let mut sibling = session.sibling("other");
+        assert_eq!(sibling.new_window().unwrap().0, 1);
+    }
+
+    #[test]
+    fn session_absorb() {
+        let mut session: Session<MockPane> = Session::new(WINSZ);
+        let (first, _) = session.new_window().unwrap();
+        session.select_window(first);
+        let mut other: Session<MockPane> = Session::new(WINSZ);
+        let mut updates = Vec::new();
+        for _ in 0..2 {
+            let (idx, window_updates) = other.new_window().unwrap();
+            updates.push(window_updates);
+            other.select_window(idx);
         }
-        assert!(recv.try_next().is_err(), "recv too many bytes");
-        let recv = &mut session.windows.get_mut(&second).unwrap().stdin_channel.1;
-        assert!(recv.try_next().is_err(), "other window received byte");
+        let split = other.split_pane(Direction::Vertical).unwrap();
+        updates.push(split);
+        other.rename_window("logs").unwrap();
+
+        let updates = session.absorb(other, updates);
+        assert_eq!(
+            session.windows.keys().copied().collect::<Vec<_>>(),
+            [0, 1, 2]
+        );
+        assert_eq!(session.window_name(2), Some("logs"));
+        assert_eq!(session.pane_count(2), Some(2));
+        assert_eq!(session.selected_window_idx(), Some(first));
+        assert_eq!(session.panes.len(), 4);
+        let mut panes: Vec<_> =
+            updates.iter().map(|updates| updates.pane_idx).collect();
+        panes.sort_unstable();
+        assert_eq!(panes, [1, 2, 3]);
+        assert_eq!(session.windows[&2].layout.panes(), [2, 3]);
+        assert_eq!(session.windows[&2].active, 3);
+        assert_eq!(session.select_window(2), Some(2));
     }
 
     #[test]
-    fn session_forward_pty_update() {
-        let mut session: Session<MockWindow> = Session::new(WINSZ);
+    fn session_kill_window() {
+        let mut session: Session<MockPane> = Session::new(WINSZ);
+        assert!(session.kill_window().is_err());
         let (first, _) = session.new_window().unwrap();
         let (second, _) = session.new_window().unwrap();
         session.select_window(second);
-        assert_eq!(session.selected_window_idx(), Some(second));
-        session
-            .pty_update(SessionPtyUpdate {
-                window_idx: first,
-                data: PtyUpdate::Byte(13),
-            })
-            .unwrap();
+        session.split_pane(Direction::Vertical).unwrap();
+        let killed: Vec<_> = session.windows[&second]
+            .layout
+            .panes()
+            .iter()
+            .map(|pane| session.panes[pane].killed.clone())
+            .collect();
 
-        let recv = &mut session.windows.get_mut(&second).unwrap().pty_channel.1;
-        assert!(recv.try_next().is_err(), "other window received byte");
-        let recv = &mut session.windows.get_mut(&first).unwrap().pty_channel.1;
-        assert_eq!(recv.try_next().unwrap(), Some(13u8), "failed to recv byte");
-        assert!(recv.try_next().is_err(), "recv multiple bytes");
+        let mut out = Vec::new();
+        session.redraw(&mut out).unwrap();
+        session.kill_window().unwrap();
+        assert!(killed.iter().all(|killed| killed.get()));
+        // The next window's cursor style is sent, though it is the same.
+        out.clear();
+        session.redraw(&mut out).unwrap();
+        assert_eq!(out, b"hello\x1b[2 q\x1b[?12l");
+        assert!(!session.windows.contains_key(&second));
+        assert_eq!(session.panes.len(), 1);
+        assert_eq!(session.selected_window_idx(), Some(first));
+        session.kill_window().unwrap();
+        assert_eq!(session.selected_window_idx(), None);
     }
 
     #[test]
-    fn session_resize() {
-        let mut session: Session<MockWindow> = Session::new(WINSZ);
+    fn session_panes_by_number() {
+        let mut session: Session<MockPane> = Session::new(WINSZ);
+        session.set_pane_base_index(1);
         let (first, _) = session.new_window().unwrap();
         let (second, _) = session.new_window().unwrap();
-        let (third, _) = session.new_window().unwrap();
         session.select_window(second);
-        assert_eq!(session.selected_window_idx(), Some(second));
-        let recv = &mut session.windows.get_mut(&second).unwrap().resize_channel.1;
-        assert!(recv.try_next().is_ok(), "did not resize selected window");
-        assert!(
-            recv.try_next().is_err(),
-            "resized multiple times on selection"
-        );
+        session.split_pane(Direction::Vertical).unwrap();
+        let numbers = |session: &Session<MockPane>| -> Vec<_> {
+            let panes = session.window_panes(second).into_iter();
+            panes.map(|(number, _, active)| (number, active)).collect()
+        };
+        assert_eq!(numbers(&session), [(1, false), (2, true)]);
+        assert_eq!(session.window_panes(first).len(), 1);
+        assert!(session.window_panes(7).is_empty());
+        // One above the other, each the window's width.
+        let rects = session.window_pane_rects(second);
+        let rect_numbers: Vec<_> = rects.iter().map(|(n, _)| *n).collect();
+        assert_eq!(rect_numbers, [1, 2]);
+        assert!(rects.iter().all(|(_, rect)| rect.cols == WINSZ.ws_col));
+        assert!(rects[0].1.row < rects[1].1.row);
+        assert!(session.window_pane_rects(7).is_empty());
 
-        session.resize(WINSZ).unwrap();
-        let recv = &mut session.windows.get_mut(&first).unwrap().resize_channel.1;
-        assert!(recv.try_next().is_err(), "resized background window");
-        let recv = &mut session.windows.get_mut(&second).unwrap().resize_channel.1;
-        assert!(recv.try_next().is_ok(), "did not resize selected window");
-        assert!(
-            recv.try_next().is_err(),
-            "resized multiple times on selection"
-        );
-        let recv = &mut session.windows.get_mut(&third).unwrap().resize_channel.1;
-        assert!(recv.try_next().is_err(), "resized background window");
+        session.select_pane_number(second, 1).unwrap();
+        assert_eq!(numbers(&session), [(1, true), (2, false)]);
+        assert_eq!(session.active_pane_number(), Some(1));
+        assert!(matches!(
+            session.select_pane_number(second, 0),
+            Err(SessionError::NoSuchPane(0))
+        ));
+        assert!(matches!(
+            session.select_pane_number(7, 1),
+            Err(SessionError::NoSuchWindow(7))
+        ));
 
-        // some noise
-        session
-            .pty_update(SessionPtyUpdate {
-                window_idx: first,
-                data: PtyUpdate::Byte(13),
-            })
-            .unwrap();
+        // Panes of windows not selected are killed and renamed by number.
+        session.select_window(first);
+        session.kill_pane_number(second, 2).unwrap();
+        let pane_idx = session.windows[&second].layout.panes()[1];
+        assert!(session.hung_up.contains(&pane_idx));
+        session.rename_window_idx(second, "logs").unwrap();
+        assert_eq!(session.window_name(second), Some("logs"));
+        assert!(session.rename_window_idx(7, "x").is_err());
+        session.kill_window_idx(second).unwrap();
+        assert_eq!(session.window_name(second), None);
+        assert_eq!(session.selected_window_idx(), Some(first));
+        assert!(session.kill_window_idx(second).is_err());
+    }
 
-        session.select_window(third);
-        let recv = &mut session.windows.get_mut(&first).unwrap().resize_channel.1;
-        assert!(recv.try_next().is_err(), "resized background window");
-        let recv = &mut session.windows.get_mut(&second).unwrap().resize_channel.1;
-        assert!(recv.try_next().is_err(), "resized on exit");
-        let recv = &mut session.windows.get_mut(&third).unwrap().resize_channel.1;
-        assert!(recv.try_next().is_ok(), "did not resize selected window");
+    #[test]
+    fn session_applet() {
+        use crate::applet::Viewer;
+        use futures::StreamExt;
 
-        session
-            .pty_update(SessionPtyUpdate {
-                window_idx: third,
-                data: PtyUpdate::Exited,
-            })
-            .unwrap();
-        let recv = &mut session.windows.get_mut(&first).unwrap().resize_channel.1;
-        assert!(recv.try_next().is_err(), "resized background window");
-        let recv = &mut session.windows.get_mut(&second).unwrap().resize_channel.1;
-        assert!(recv.try_next().is_ok(), "did not resize on selection");
+        let mut session: Session<Pane> = Session::new(WINSZ);
+        let viewer = Viewer::new("notes", "first\nlast\n");
+        let (idx, mut updates) = session.new_applet_window(Box::new(viewer)).unwrap();
+        session.select_window(idx);
+        assert_eq!(session.window_name(idx), Some("notes"));
+        assert_eq!(session.active_pane().unwrap().preview(), "last");
+        assert!(!session.active_pane().unwrap().is_reading_secret());
+
+        session.receive_stdin(b"q").unwrap();
+        let update = futures::executor::block_on(updates.next()).unwrap();
+        assert_eq!(update.data, PtyUpdate::Exited { status: None });
+        session.pty_update(update).unwrap();
+        assert_eq!(session.selected_window_idx(), None);
     }
 
     #[test]
-    fn session_mark_dirty_on_select() {
-        let mut session: Session<MockWindow> = Session::new(WINSZ);
-        let (first, _) = session.new_window().unwrap();
-        let (second, _) = session.new_window().unwrap();
+    fn session_pane_titles() {
+        let mut session: Session<MockPane> = Session::new(WINSZ);
+        let (window, _) = session.new_window().unwrap();
+        session.select_window(window);
+        session.set_pane_titles(true);
+        let first = session.windows[&window].active;
+        let mut out = Vec::new();
+        session.redraw(&mut out).unwrap();
+        assert!(!String::from_utf8(out).unwrap().contains('\u{2500}'));
 
-        let recv = &mut session.windows.get_mut(&first).unwrap().dirty_channel.1;
-        assert!(recv.try_next().is_err(), "marked before selection");
-        let recv = &mut session.windows.get_mut(&second).unwrap().dirty_channel.1;
-        assert!(recv.try_next().is_err(), "marked before selection");
+        let second = session.split_pane(Direction::Vertical).unwrap().pane_idx;
+        session.panes.get_mut(&first).unwrap().title = Some("vim".to_string());
+        let rect = Rect {
+            col: 0,
+            row: 14,
+            cols: 80,
+            rows: 10,
+        };
+        assert_eq!(session.active_rect(), Some(rect));
+        let mut out = Vec::new();
+        session.redraw(&mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let rule = |cols| "\u{2500}".repeat(cols);
+        let title = format!("\x1b[1;1H\x1b[0m\u{2500} vim {}\x1b[0m", rule(74));
+        assert!(out.contains(&title));
+        assert!(out.contains(&format!("\x1b[13;1H\x1b[32m{}\x1b[0m", rule(80))));
+        assert!(out.contains(&format!("\x1b[14;1H\x1b[32m{}\x1b[0m", rule(80))));
 
-        session.select_window(second);
-        assert_eq!(session.selected_window_idx(), Some(second));
-        let recv = &mut session.windows.get_mut(&first).unwrap().dirty_channel.1;
-        assert!(recv.try_next().is_err(), "unselected window marked");
-        let recv = &mut session.windows.get_mut(&second).unwrap().dirty_channel.1;
-        assert!(recv.try_next().is_ok(), "selected window not marked");
+        // The borders are only drawn again once something about them changes.
+        let mut out = Vec::new();
+        session.redraw(&mut out).unwrap();
+        assert!(!String::from_utf8(out).unwrap().contains('\u{2500}'));
+        session.panes.get_mut(&second).unwrap().title = Some("top".to_string());
+        let mut out = Vec::new();
+        session.redraw(&mut out).unwrap();
+        assert!(String::from_utf8(out).unwrap().contains(" top "));
+    }
 
-        session.select_window(first);
-        let recv = &mut session.windows.get_mut(&first).unwrap().dirty_channel.1;
-        assert!(recv.try_next().is_ok(), "selected window not marked");
-        let recv = &mut session.windows.get_mut(&second).unwrap().dirty_channel.1;
-        assert!(recv.try_next().is_err(), "unselected window marked");
+    #[test]
+    fn borders() {
+        // Two panes side by side, the right one split in three.
+        let separators = [
+            (
+                Direction::Horizontal,
+                Rect {
+                    col: 2,
+                    row: 0,
+                    cols: 1,
+                    rows: 5,
+                },
+            ),
+            (
+                Direction::Vertical,
+                Rect {
+                    col: 3,
+                    row: 1,
+                    cols: 2,
+                    rows: 1,
+                },
+            ),
+            (
+                Direction::Vertical,
+                Rect {
+                    col: 3,
+                    row: 3,
+                    cols: 2,
+                    rows: 1,
+                },
+            ),
+        ];
+        let cells = border_cells(&separators);
+        let glyphs: String = cells.values().collect();
+        assert_eq!(
+            glyphs,
+            "\u{2502}\u{251c}\u{2500}\u{2500}\u{2502}\u{251c}\u{2500}\u{2500}\u{2502}"
+        );
+
+        let active = Rect {
+            col: 3,
+            row: 2,
+            cols: 2,
+            rows: 1,
+        };
+        let mut out = Vec::new();
+        draw_borders(&mut out, &separators, active, &BorderStyles::default()).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "\x1b[0m\x1b[1;3H\u{2502}\x1b[2;3H\x1b[32m\u{251c}\u{2500}\u{2500}\
+             \x1b[3;3H\u{2502}\x1b[4;3H\u{251c}\u{2500}\u{2500}\x1b[5;3H\x1b[0m\
+             \u{2502}\x1b[0m"
+        );
+    }
+
+    #[test]
+    fn session_layout() {
+        let mut session: Session<MockPane> = Session::new(WINSZ);
+        let (window, _) = session.new_window().unwrap();
+        session.select_window(window);
+        session.split_pane(Direction::Vertical).unwrap();
+        session.split_pane(Direction::Vertical).unwrap();
+        let panes = session.windows[&window].layout.panes();
+
+        session.select_layout(Preset::MainVertical).unwrap();
+        assert_eq!(session.windows[&window].layout.panes(), panes);
+        let main = &mut session.panes.get_mut(&panes[0]).unwrap().resize_channel.1;
+        let mut last = None;
+        while let Ok(Some(size)) = main.try_next() {
+            last = Some((size.ws_col, size.ws_row));
+        }
+        assert_eq!(last, Some((40, 24)));
+
+        session.next_layout().unwrap();
+        assert_eq!(session.windows[&window].preset, Some(Preset::Tiled));
+        session.next_layout().unwrap();
+        assert_eq!(
+            session.windows[&window].preset,
+            Some(Preset::EvenHorizontal)
+        );
     }
 }
@@ -0,0 +1,245 @@
+//! What is kept of sessions to start them again, e.g. after a reboot.
+//!
+//! A snapshot records each window's index, name and layout, and the command
+//! and working directory of each of its panes, but not what they show. It is
+//! saved as lines of fields separated by tabs:
+//!
+//! ```text
+//! session NAME SELECTED
+//! window IDX NAME ACTIVE LAYOUT
+//! pane CWD COMMAND
+//! ```
+//!
+//! Each window follows its session, and each pane its window. `SELECTED` is
+//! empty if no window is selected, `CWD` if the directory is not known, and
+//! `COMMAND` for the shell. Backslashes, tabs and newlines in fields are
+//! escaped as `\\`, `\t` and `\n`.
+
+use std::path::PathBuf;
+
+use crate::layout::Layout;
+
+/// A session's windows.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionSnapshot {
+    pub name: String,
+    /// The index of the window selected.
+    pub selected: Option<usize>,
+    pub windows: Vec<WindowSnapshot>,
+}
+
+/// A window, with its panes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WindowSnapshot {
+    pub idx: usize,
+    pub name: String,
+    /// The panes, in layout order.
+    pub panes: Vec<PaneSnapshot>,
+    /// The arrangement of the panes, identified by their position in `panes`.
+    pub layout: Layout,
+    /// The position of the active pane in `panes`.
+    pub active: usize,
+}
+
+impl WindowSnapshot {
+    /// Whether the layout has exactly the panes, and the active one is there.
+    pub fn is_consistent(&self) -> bool {
+        let mut panes = self.layout.panes();
+        panes.sort_unstable();
+        panes.iter().copied().eq(0..self.panes.len()) && self.active < self.panes.len()
+    }
+}
+
+/// What a pane ran, and where.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PaneSnapshot {
+    /// The command run with the shell, or `None` for the shell itself.
+    pub command: Option<String>,
+    /// The working directory of the pane's process, if it is known.
+    pub cwd: Option<PathBuf>,
+}
+
+/// Write `sessions` as text, to be read back with `load`.
+pub fn save(sessions: &[SessionSnapshot]) -> String {
+    let mut text = String::new();
+    let mut line = |fields: &[&str]| {
+        let fields: Vec<_> = fields.iter().map(|field| escape(field)).collect();
+        text.push_str(&fields.join("\t"));
+        text.push('\n');
+    };
+    for session in sessions {
+        let selected = session.selected.map(|idx| idx.to_string());
+        line(&["session", &session.name, selected.as_deref().unwrap_or("")]);
+        for window in &session.windows {
+            line(&[
+                "window",
+                &window.idx.to_string(),
+                &window.name,
+                &window.active.to_string(),
+                &window.layout.to_string(),
+            ]);
+            for pane in &window.panes {
+                let cwd = pane.cwd.as_ref().map(|cwd| cwd.to_string_lossy());
+                line(&[
+                    "pane",
+                    cwd.as_deref().unwrap_or(""),
+                    pane.command.as_deref().unwrap_or(""),
+                ]);
+            }
+        }
+    }
+    text
+}
+
+/// Read sessions written by `save`.
+pub fn load(text: &str) -> Result<Vec<SessionSnapshot>, String> {
+    let mut sessions: Vec<SessionSnapshot> = Vec::new();
+    for (n, line) in text.lines().enumerate() {
+        let error = |message: &str| format!("line {}: {}", n + 1, message);
+        let fields: Vec<_> = line.split('\t').map(unescape).collect();
+        let number = |field: &str| field.parse().map_err(|_| error("bad number"));
+        match fields.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+            ["session", name, selected] => sessions.push(SessionSnapshot {
+                name: name.to_string(),
+                selected: match selected {
+                    "" => None,
+                    selected => Some(number(selected)?),
+                },
+                windows: Vec::new(),
+            }),
+            ["window", idx, name, active, layout] => {
+                let window = WindowSnapshot {
+                    idx: number(idx)?,
+                    name: name.to_string(),
+                    panes: Vec::new(),
+                    layout: layout.parse().map_err(|e: String| error(&e))?,
+                    active: number(active)?,
+                };
+                sessions
+                    .last_mut()
+                    .ok_or_else(|| error("window outside a session"))?
+                    .windows
+                    .push(window);
+            }
+            ["pane", cwd, command] => {
+                let pane = PaneSnapshot {
+                    command: Some(command.to_string()).filter(|c| !c.is_empty()),
+                    cwd: Some(PathBuf::from(cwd)).filter(|_| !cwd.is_empty()),
+                };
+                sessions
+                    .last_mut()
+                    .and_then(|session| session.windows.last_mut())
+                    .ok_or_else(|| error("pane outside a window"))?
+                    .panes
+                    .push(pane);
+            }
+            _ => return Err(error("not a session, window or pane")),
+        }
+    }
+    for session in &sessions {
+        for window in &session.windows {
+            if !window.is_consistent() {
+                return Err(format!(
+                    "window {} of session {}: the layout does not match the panes",
+                    window.idx, session.name
+                ));
+            }
+        }
+    }
+    Ok(sessions)
+}
+
+fn escape(field: &str) -> String {
+    field
+        .replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+}
+
+fn unescape(field: &str) -> String {
+    let mut text = String::with_capacity(field.len());
+    let mut chars = field.chars();
+    while let Some(c) = chars.next() {
+        match (c, chars.clone().next()) {
+            ('\\', Some('t')) => text.push('\t'),
+            ('\\', Some('n')) => text.push('\n'),
+            ('\\', Some('\\')) => text.push('\\'),
+            _ => {
+                text.push(c);
+                continue;
+            }
+        }
+        chars.next();
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::layout::Direction;
+
+    #[test]
+    fn save_and_load() {
+        let mut layout = Layout::Pane(0);
+        layout.split(0, 1, Direction::Vertical);
+        let sessions = vec![
+            SessionSnapshot {
+                name: "work\tstuff".to_string(),
+                selected: Some(3),
+                windows: vec![
+                    WindowSnapshot {
+                        idx: 0,
+                        name: "sh".to_string(),
+                        panes: vec![PaneSnapshot {
+                            command: None,
+                            cwd: None,
+                        }],
+                        layout: Layout::Pane(0),
+                        active: 0,
+                    },
+                    WindowSnapshot {
+                        idx: 3,
+                        name: "logs".to_string(),
+                        panes: vec![
+                            PaneSnapshot {
+                                command: Some("tail -f a\\b".to_string()),
+                                cwd: Some("/var/log".into()),
+                            },
+                            PaneSnapshot {
+                                command: Some("printf 'x\\n'".to_string()),
+                                cwd: Some("/tmp/a\nb".into()),
+                            },
+                        ],
+                        layout,
+                        active: 1,
+                    },
+                ],
+            },
+            SessionSnapshot {
+                name: "empty".to_string(),
+                selected: None,
+                windows: Vec::new(),
+            },
+        ];
+        let text = save(&sessions);
+        assert_eq!(text.lines().count(), 7);
+        assert_eq!(load(&text), Ok(sessions));
+    }
+
+    #[test]
+    fn load_errors() {
+        assert_eq!(load(""), Ok(Vec::new()));
+        for bad in [
+            "window\t0\tsh\t0\t0\n",
+            "session\t0\t\npane\t\t\n",
+            "session\t0\tx\n",
+            "session\t0\t\nwindow\t0\tsh\t0\th(1:0,1:1)\npane\t\t\n",
+            "session\t0\t\nwindow\t0\tsh\t1\t0\npane\t\t\n",
+            "session\t0\n",
+        ] {
+            assert!(load(bad).is_err(), "{:?}", bad);
+        }
+    }
+}
@@ -0,0 +1,101 @@
+//! Lookup of terminfo entries, to check a `TERM` before giving it to a pane.
+
+use std::{
+    env,
+    path::{Path, PathBuf},
+};
+
+/// The directories searched for terminfo entries, in the order ncurses uses.
+pub fn search_dirs() -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    if let Some(dir) = env::var_os("TERMINFO") {
+        dirs.push(PathBuf::from(dir));
+    }
+    if let Some(home) = env::var_os("HOME") {
+        dirs.push(Path::new(&home).join(".terminfo"));
+    }
+    let defaults = [
+        "/etc/terminfo",
+        "/lib/terminfo",
+        "/usr/share/terminfo",
+        "/usr/lib/terminfo",
+    ];
+    match env::var("TERMINFO_DIRS") {
+        // An empty entry stands for the default directories.
+        Ok(list) => {
+            for dir in list.split(':') {
+                if dir.is_empty() {
+                    dirs.extend(defaults.iter().map(PathBuf::from));
+                } else {
+                    dirs.push(PathBuf::from(dir));
+                }
+            }
+        }
+        Err(_) => dirs.extend(defaults.iter().map(PathBuf::from)),
+    }
+    dirs
+}
+
+/// The `TERM`s describing the terminal panes run in, best first: what `sm`
+/// emulates is much what `screen` does, with 256 colours.
+pub const EMULATED: [&str; 2] = ["screen-256color", "screen"];
+
+/// The first of `EMULATED` with a terminfo entry on this system, if any.
+pub fn emulated() -> Option<&'static str> {
+    EMULATED.iter().copied().find(|term| exists(term))
+}
+
+/// Whether there is a terminfo entry for `term` on this system.
+pub fn exists(term: &str) -> bool {
+    find_in(term, &search_dirs()).is_some()
+}
+
+/// The file describing `term` in one of `dirs`.
+///
+/// Entries are filed under their first character, or its hexadecimal code on
+/// systems with case-insensitive file names.
+pub fn find_in<P: AsRef<Path>>(term: &str, dirs: &[P]) -> Option<PathBuf> {
+    let first = term.chars().next()?;
+    if term.contains('/') || term.starts_with('.') {
+        return None;
+    }
+    let hex = format!("{:x}", first as u32);
+    dirs.iter().find_map(|dir| {
+        let dir = dir.as_ref();
+        [first.to_string(), hex.clone()]
+            .iter()
+            .map(|subdir| dir.join(subdir).join(term))
+            .find(|path| path.is_file())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs;
+
+    #[test]
+    fn find() {
+        let dir = tempfile::tempdir().unwrap();
+        let first = dir.path().join("first");
+        let second = dir.path().join("second");
+        fs::create_dir_all(first.join("s")).unwrap();
+        fs::create_dir_all(second.join("73")).unwrap();
+        fs::create_dir_all(second.join("x")).unwrap();
+        fs::write(first.join("s").join("screen"), b"").unwrap();
+        fs::write(second.join("73").join("screen-256color"), b"").unwrap();
+        fs::write(second.join("x").join("xterm"), b"").unwrap();
+        let dirs = [&first, &second];
+
+        assert_eq!(find_in("screen", &dirs), Some(first.join("s/screen")));
+        assert_eq!(
+            find_in("screen-256color", &dirs),
+            Some(second.join("73/screen-256color"))
+        );
+        assert_eq!(find_in("xterm", &dirs), Some(second.join("x/xterm")));
+        assert_eq!(find_in("vt100", &dirs), None);
+        assert_eq!(find_in("", &dirs), None);
+        assert_eq!(find_in("../x/xterm", &dirs), None);
+    }
+}
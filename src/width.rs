@@ -0,0 +1,110 @@
+//! The number of columns text takes up on a terminal.
+//!
+//! East Asian Ambiguous characters, such as `…`, `※` and the box-drawing
+//! characters, are one column wide on most terminals but two on those set up for
+//! CJK text. Which is counted is chosen for the terminal being drawn to with
+//! `set_ambiguous_wide`, for the thread drawing.
+
+use std::cell::Cell;
+
+use unicode_width::UnicodeWidthChar;
+
+thread_local! {
+    static AMBIGUOUS_WIDE: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Count East Asian Ambiguous characters as two columns wide on this thread if
+/// `wide`, or as one.
+pub fn set_ambiguous_wide(wide: bool) {
+    AMBIGUOUS_WIDE.with(|cell| cell.set(wide));
+}
+
+/// Whether East Asian Ambiguous characters are counted as two columns wide on
+/// this thread.
+pub fn ambiguous_wide() -> bool {
+    AMBIGUOUS_WIDE.with(Cell::get)
+}
+
+/// The columns `c` takes up: two for wide characters such as CJK ideographs and
+/// most emoji, none for combining and control characters, and one otherwise.
+pub fn char_width(c: char) -> usize {
+    match ambiguous_wide() {
+        true => c.width_cjk().unwrap_or(0),
+        false => c.width().unwrap_or(0),
+    }
+}
+
+/// `c`, a box-drawing character, or an ASCII character standing in for it if
+/// ambiguous characters are counted as wide, which would not line up.
+pub fn box_char(c: char) -> char {
+    if !ambiguous_wide() {
+        return c;
+    }
+    match c {
+        '\u{2500}' => '-',
+        '\u{2502}' => '|',
+        '\u{2500}'..='\u{257f}' => '+',
+        c => c,
+    }
+}
+
+/// The columns `s` takes up.
+pub fn str_width(s: &str) -> usize {
+    s.chars().map(char_width).sum()
+}
+
+/// The longest start of `s` at most `width` columns wide.
+pub fn truncate(s: &str, width: usize) -> &str {
+    let mut used = 0;
+    for (idx, c) in s.char_indices() {
+        used += char_width(c);
+        if used > width {
+            return &s[..idx];
+        }
+    }
+    s
+}
+
+/// `s` cut or padded with spaces to exactly `width` columns.
+///
+/// A wide character which would straddle the last column is left out, and the
+/// column filled with a space instead.
+pub fn fit(s: &str, width: usize) -> String {
+    let s = truncate(s, width);
+    format!("{}{}", s, " ".repeat(width - str_width(s)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn widths() {
+        assert_eq!(str_width("abc"), 3);
+        assert_eq!(str_width("日本"), 4);
+        assert_eq!(str_width("e\u{301}"), 1);
+        assert_eq!(str_width("🦀"), 2);
+
+        assert_eq!(truncate("日本語", 5), "日本");
+        assert_eq!(truncate("日本語", 6), "日本語");
+        assert_eq!(fit("日本語", 5), "日本 ");
+        assert_eq!(fit("ab", 4), "ab  ");
+        assert_eq!(fit("a🦀b", 2), "a ");
+    }
+
+    #[test]
+    fn ambiguous_widths() {
+        assert_eq!(str_width("…─"), 2);
+        assert_eq!(box_char('\u{251c}'), '\u{251c}');
+        set_ambiguous_wide(true);
+        assert_eq!(str_width("…─"), 4);
+        assert_eq!(str_width("abc日本"), 7);
+        assert_eq!(fit("a…", 2), "a ");
+        assert_eq!(box_char('\u{2500}'), '-');
+        assert_eq!(box_char('\u{2502}'), '|');
+        assert_eq!(box_char('\u{251c}'), '+');
+        assert_eq!(box_char('a'), 'a');
+        set_ambiguous_wide(false);
+        assert_eq!(str_width("…"), 1);
+    }
+}